@@ -0,0 +1,52 @@
+#!/usr/bin/env python3
+"""Generate src/markdown/emoji_table.rs, the `:shortcode:` table of posts and comments.
+
+Reads GitHub's gemoji database (db/emoji.json of https://github.com/github/gemoji), from a
+file given as the only argument or downloaded from GitHub:
+
+    python3 scripts/generate_emoji_table.py [emoji.json]
+"""
+
+import json
+import sys
+import urllib.request
+from pathlib import Path
+
+GEMOJI_URL = "https://raw.githubusercontent.com/github/gemoji/master/db/emoji.json"
+OUTPUT = Path(__file__).resolve().parent.parent / "src" / "markdown" / "emoji_table.rs"
+
+
+def load(argv):
+    if len(argv) > 1:
+        with open(argv[1], encoding="utf-8") as f:
+            return json.load(f)
+    with urllib.request.urlopen(GEMOJI_URL) as response:
+        return json.load(response)
+
+
+def rust_string(value):
+    return json.dumps(value, ensure_ascii=False)
+
+
+def main(argv):
+    shortcodes = {}
+    for entry in load(argv):
+        for alias in entry.get("aliases", []):
+            shortcodes.setdefault(alias, entry["emoji"])
+
+    lines = [
+        "// @generated by scripts/generate_emoji_table.py from gemoji; do not edit by hand.",
+        "",
+        "/// Emoji of every shortcode, sorted by shortcode",
+        "pub(super) static SHORTCODES: &[(&str, &str)] = &[",
+    ]
+    for alias in sorted(shortcodes):
+        lines.append("    ({}, {}),".format(rust_string(alias), rust_string(shortcodes[alias])))
+    lines.append("];")
+
+    OUTPUT.write_text("\n".join(lines) + "\n", encoding="utf-8")
+    print("Wrote {} shortcodes to {}".format(len(shortcodes), OUTPUT))
+
+
+if __name__ == "__main__":
+    main(sys.argv)
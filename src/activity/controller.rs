@@ -0,0 +1,124 @@
+use crate::activity::model::{ActivityError, ActivityPrivacySettings};
+use crate::activity::service::ActivityService;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn activity_error_response(e: ActivityError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Activity error: {:?}", e);
+    let status = match e {
+        ActivityError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+        ActivityError::UserNotFound => StatusCode::NOT_FOUND,
+        ActivityError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Get a user's activity feed
+///
+/// Returns a page of the user's public actions (published posts, comments and likes), newest
+/// first. Activity types the user has hidden are left out unless they are viewing their own feed.
+/// Further pages are linked from the `Link` response header.
+#[utoipa::path(
+    get,
+    path = "/api/users/{username}/activity",
+    tag = "activity",
+    params(
+        ("username" = String, Path, description = "Username of the user"),
+        PageParams
+    ),
+    responses(
+        (status = 200, description = "Activity retrieved successfully", body = Vec<ActivityItem>),
+        (status = 400, description = "Invalid pagination cursor"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_user_activity(
+    Path(username): Path<String>,
+    OriginalUri(uri): OriginalUri,
+    Extension(user): Extension<Option<AuthUser>>,
+    State(service): State<Arc<ActivityService>>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => return activity_error_response(ActivityError::InvalidParameter(e.to_string())),
+    };
+
+    match service
+        .get_user_activity(&username, user.map(|u| u.user_id), &pagination)
+        .await
+    {
+        Ok(items) => {
+            let headers = pagination.headers(&uri, items.len());
+            (StatusCode::OK, headers, Json(items)).into_response()
+        }
+        Err(e) => activity_error_response(e),
+    }
+}
+
+/// Get your activity privacy settings
+#[utoipa::path(
+    get,
+    path = "/api/activity/privacy",
+    tag = "activity",
+    responses(
+        (status = 200, description = "Privacy settings retrieved", body = ActivityPrivacySettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_privacy_settings(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ActivityService>>,
+) -> Response {
+    match service.get_privacy_settings(user.user_id).await {
+        Ok(settings) => (StatusCode::OK, Json(settings)).into_response(),
+        Err(e) => activity_error_response(e),
+    }
+}
+
+/// Update your activity privacy settings
+///
+/// Chooses which kinds of activity are hidden from other users viewing your activity feed.
+#[utoipa::path(
+    put,
+    path = "/api/activity/privacy",
+    tag = "activity",
+    request_body = ActivityPrivacySettings,
+    responses(
+        (status = 200, description = "Privacy settings updated", body = ActivityPrivacySettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_privacy_settings(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ActivityService>>,
+    Json(settings): Json<ActivityPrivacySettings>,
+) -> Response {
+    match service.set_privacy_settings(user.user_id, &settings).await {
+        Ok(()) => (StatusCode::OK, Json(settings)).into_response(),
+        Err(e) => activity_error_response(e),
+    }
+}
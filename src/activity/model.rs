@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Length of the comment excerpt shown in activity entries
+pub const ACTIVITY_EXCERPT_CHARS: i32 = 200;
+
+/// Kind of action shown in a user's activity feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityType {
+    PostPublished,
+    Comment,
+    Like,
+}
+
+impl ActivityType {
+    pub fn from_db(value: &str) -> Option<Self> {
+        match value {
+            "post_published" => Some(ActivityType::PostPublished),
+            "comment" => Some(ActivityType::Comment),
+            "like" => Some(ActivityType::Like),
+            _ => None,
+        }
+    }
+}
+
+/// A single entry in a user's activity feed
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ActivityItem {
+    /// What the user did
+    pub activity_type: ActivityType,
+
+    /// When it happened
+    pub occurred_at: DateTime<Utc>,
+
+    /// Post the activity relates to
+    #[schema(example = "42")]
+    pub post_id: i64,
+
+    /// Title of that post
+    #[schema(example = "Getting started with Rust")]
+    pub post_title: String,
+
+    /// Slug of that post
+    #[schema(example = "getting-started-with-rust")]
+    pub post_slug: String,
+
+    /// Comment ID, for comment activity
+    #[schema(example = "123")]
+    pub comment_id: Option<i64>,
+
+    /// Start of the comment text, for comment activity
+    #[schema(example = "This is a great post!")]
+    pub excerpt: Option<String>,
+}
+
+/// Which kinds of activity a user hides from their public feed
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ActivityPrivacySettings {
+    /// Hide published posts
+    #[schema(example = "false")]
+    pub hide_posts: bool,
+
+    /// Hide comments
+    #[schema(example = "false")]
+    pub hide_comments: bool,
+
+    /// Hide liked posts
+    #[schema(example = "true")]
+    pub hide_likes: bool,
+}
+
+/// Possible activity errors
+#[derive(Debug, thiserror::Error)]
+pub enum ActivityError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("User not found")]
+    UserNotFound,
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+}
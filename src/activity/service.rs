@@ -0,0 +1,197 @@
+use crate::activity::model::{
+    ActivityError, ActivityItem, ActivityPrivacySettings, ActivityType, ACTIVITY_EXCERPT_CHARS,
+};
+use crate::block::service::blocked_user_ids;
+use crate::cache::redis::RedisCache;
+use crate::db::instrument::timed;
+use crate::db::router::DbRouter;
+use crate::pagination::Pagination;
+use crate::tenant::middleware::current_blog_id;
+use sqlx::Row;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct ActivityService {
+    db: DbRouter,
+    redis_cache: Option<RedisCache>,
+}
+
+impl ActivityService {
+    pub fn new(db: DbRouter, redis_cache: Option<RedisCache>) -> Self {
+        Self { db, redis_cache }
+    }
+
+    // Resolve a username to the user's ID and shadow-ban state
+    async fn find_user(&self, username: &str) -> Result<(Uuid, bool), ActivityError> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, is_shadow_banned FROM global.users
+            WHERE username = $1
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+        )
+        .bind(username)
+        .fetch_optional(self.db.read())
+        .await?
+        .ok_or(ActivityError::UserNotFound)?;
+
+        Ok((row.get("id"), row.get("is_shadow_banned")))
+    }
+
+    // Get a user's privacy settings; users who never changed them share everything
+    pub async fn get_privacy_settings(
+        &self,
+        user_id: Uuid,
+    ) -> Result<ActivityPrivacySettings, ActivityError> {
+        let row = sqlx::query(
+            r#"
+            SELECT hide_posts, hide_comments, hide_likes
+            FROM global.user_activity_privacy
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(self.db.read())
+        .await?;
+
+        Ok(match row {
+            Some(row) => ActivityPrivacySettings {
+                hide_posts: row.get("hide_posts"),
+                hide_comments: row.get("hide_comments"),
+                hide_likes: row.get("hide_likes"),
+            },
+            None => ActivityPrivacySettings::default(),
+        })
+    }
+
+    // Replace a user's privacy settings
+    pub async fn set_privacy_settings(
+        &self,
+        user_id: Uuid,
+        settings: &ActivityPrivacySettings,
+    ) -> Result<(), ActivityError> {
+        sqlx::query(
+            r#"
+            INSERT INTO global.user_activity_privacy (
+                user_id, hide_posts, hide_comments, hide_likes, updated_at
+            )
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (user_id) DO UPDATE
+            SET hide_posts = EXCLUDED.hide_posts,
+                hide_comments = EXCLUDED.hide_comments,
+                hide_likes = EXCLUDED.hide_likes,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(user_id)
+        .bind(settings.hide_posts)
+        .bind(settings.hide_comments)
+        .bind(settings.hide_likes)
+        .execute(self.db.primary())
+        .await?;
+
+        info!("Updated activity privacy settings of user {}", user_id);
+        Ok(())
+    }
+
+    // Get a page of a user's activity, newest first. Users always see their own full feed;
+    // everyone else only sees the activity types the user has not hidden, and nothing at all
+    // of users they blocked.
+    pub async fn get_user_activity(
+        &self,
+        username: &str,
+        viewer_id: Option<Uuid>,
+        pagination: &Pagination,
+    ) -> Result<Vec<ActivityItem>, ActivityError> {
+        let (user_id, shadow_banned) = self.find_user(username).await?;
+        let is_self = viewer_id == Some(user_id);
+
+        if let Some(viewer_id) = viewer_id {
+            let blocked =
+                blocked_user_ids(self.db.primary(), self.redis_cache.as_ref(), viewer_id).await?;
+            if blocked.contains(&user_id) {
+                return Ok(Vec::new());
+            }
+        }
+
+        let privacy = if is_self {
+            ActivityPrivacySettings::default()
+        } else {
+            self.get_privacy_settings(user_id).await?
+        };
+        let show_posts = !privacy.hide_posts;
+        let show_comments = !privacy.hide_comments && (is_self || !shadow_banned);
+        let show_likes = !privacy.hide_likes;
+
+        let query = sqlx::query(
+            r#"
+            SELECT * FROM (
+                SELECT 'post_published' AS activity_type, p.created_at AS occurred_at,
+                    p.id AS post_id, p.title AS post_title, p.slug AS post_slug,
+                    NULL::BIGINT AS comment_id, NULL::TEXT AS excerpt
+                FROM global.posts p
+                WHERE p.user_id = $1 AND p.blog_id = $8
+                    AND p.is_draft = false AND p.is_deleted = false AND $2
+
+                UNION ALL
+
+                SELECT 'comment', c.created_at, p.id, p.title, p.slug,
+                    c.id, LEFT(c.content, $5)
+                FROM global.comments c
+                JOIN global.posts p ON p.id = c.post_id
+                WHERE c.user_id = $1 AND p.blog_id = $8 AND c.is_deleted = false
+                    AND c.moderation_status = 'approved'
+                    AND p.is_draft = false AND p.is_deleted = false AND $3
+
+                UNION ALL
+
+                SELECT 'like', ui.created_at, p.id, p.title, p.slug, NULL, NULL
+                FROM global.user_interactions ui
+                JOIN global.posts p ON p.id = ui.post_id
+                WHERE ui.user_id = $1 AND p.blog_id = $8 AND ui.interaction_type = 'like'
+                    AND p.is_draft = false AND p.is_deleted = false AND $4
+            ) activity
+            ORDER BY occurred_at DESC, post_id DESC
+            LIMIT $6 OFFSET $7
+            "#,
+        )
+        .bind(user_id)
+        .bind(show_posts)
+        .bind(show_comments)
+        .bind(show_likes)
+        .bind(ACTIVITY_EXCERPT_CHARS)
+        .bind(pagination.limit)
+        .bind(pagination.offset)
+        .bind(current_blog_id())
+        .fetch_all(self.db.read());
+        let rows = timed("activity.feed", query).await?;
+
+        let items = rows
+            .into_iter()
+            .filter_map(|row| {
+                let activity_type: String = row.get("activity_type");
+                let activity_type = match ActivityType::from_db(&activity_type) {
+                    Some(activity_type) => activity_type,
+                    None => {
+                        warn!("Skipping unknown activity type: {}", activity_type);
+                        return None;
+                    }
+                };
+
+                Some(ActivityItem {
+                    activity_type,
+                    occurred_at: row.get("occurred_at"),
+                    post_id: row.get("post_id"),
+                    post_title: row.get("post_title"),
+                    post_slug: row.get("post_slug"),
+                    comment_id: row.get("comment_id"),
+                    excerpt: row.get("excerpt"),
+                })
+            })
+            .collect();
+
+        Ok(items)
+    }
+}
@@ -0,0 +1,149 @@
+use crate::analytics::model::AnonymityMode;
+use crate::cache::redis::RedisCache;
+use chrono::{NaiveDate, Utc};
+use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use tracing::{error, warn};
+use uuid::{Builder, Uuid};
+
+// Salts are kept a little over a day, so instances whose clocks disagree around midnight
+// still agree on them, and are gone after that
+const SALT_TTL_SECONDS: u64 = 2 * 24 * 3600;
+
+fn salt_key(day: NaiveDate) -> String {
+    format!("analytics:pseudonym_salt:{}", day)
+}
+
+/// Pseudonym of a user under one day's salt
+fn pseudonym(salt: &str, user_id: Uuid) -> Uuid {
+    let digest = Sha256::new()
+        .chain_update(salt.as_bytes())
+        .chain_update(user_id.as_bytes())
+        .finalize();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+/// Removes user IDs from analytics data in anonymous mode.
+///
+/// Pseudonyms are derived from a random salt per day. The salt is shared between instances
+/// through Redis and expires soon after its day ends, so once it is gone nobody can link a
+/// pseudonym back to its user, or to the user's pseudonyms on other days. Without Redis each
+/// instance uses its own salts.
+#[derive(Clone)]
+pub struct Anonymizer {
+    mode: AnonymityMode,
+    redis_cache: Option<RedisCache>,
+    salt: Arc<Mutex<Option<(NaiveDate, String)>>>,
+}
+
+impl Anonymizer {
+    pub fn new(redis_cache: Option<RedisCache>) -> Self {
+        Self::with_mode(AnonymityMode::from_env(), redis_cache)
+    }
+
+    pub fn with_mode(mode: AnonymityMode, redis_cache: Option<RedisCache>) -> Self {
+        Self {
+            mode,
+            redis_cache,
+            salt: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Whether interactions are stored with the IDs of their users
+    pub fn keeps_user_ids(&self) -> bool {
+        matches!(self.mode, AnonymityMode::Off)
+    }
+
+    /// The user ID to store with an interaction
+    pub async fn user_id(&self, user_id: Option<Uuid>) -> Option<Uuid> {
+        match (self.mode, user_id) {
+            (AnonymityMode::Off, user_id) => user_id,
+            (AnonymityMode::Drop, _) | (_, None) => None,
+            (AnonymityMode::Pseudonymize, Some(user_id)) => {
+                let salt = self.salt_for(Utc::now().date_naive()).await;
+                Some(pseudonym(&salt, user_id))
+            }
+        }
+    }
+
+    async fn salt_for(&self, day: NaiveDate) -> String {
+        let current = self.salt.lock().unwrap().clone();
+        if let Some((salt_day, salt)) = current {
+            if salt_day == day {
+                return salt;
+            }
+        }
+
+        let candidate = format!("{:032x}", rand::random::<u128>());
+        let salt = match &self.redis_cache {
+            Some(cache) => {
+                match Self::shared_salt(cache, day, &candidate).await {
+                    Ok(salt) => salt,
+                    Err(e) => {
+                        error!("Failed to get the pseudonym salt from Redis: {}", e);
+                        warn!("Using a salt of this instance, pseudonyms will differ between instances");
+                        candidate
+                    }
+                }
+            }
+            None => candidate,
+        };
+
+        *self.salt.lock().unwrap() = Some((day, salt.clone()));
+        salt
+    }
+
+    // The salt of the day, set by the first instance that needs it
+    async fn shared_salt(
+        cache: &RedisCache,
+        day: NaiveDate,
+        candidate: &str,
+    ) -> Result<String, redis::RedisError> {
+        let key = salt_key(day);
+        let mut conn = cache.connection().await?;
+        redis::cmd("SET")
+            .arg(&key)
+            .arg(candidate)
+            .arg("NX")
+            .arg("EX")
+            .arg(SALT_TTL_SECONDS)
+            .query_async::<Option<String>>(&mut conn)
+            .await?;
+        conn.get(&key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pseudonym_depends_on_salt() {
+        let user_id = Uuid::new_v4();
+
+        assert_eq!(pseudonym("a", user_id), pseudonym("a", user_id));
+        assert_ne!(pseudonym("a", user_id), pseudonym("b", user_id));
+        assert_ne!(pseudonym("a", user_id), pseudonym("a", Uuid::new_v4()));
+        assert_ne!(pseudonym("a", user_id), user_id);
+    }
+
+    #[tokio::test]
+    async fn test_user_id_by_mode() {
+        let user_id = Some(Uuid::new_v4());
+
+        let off = Anonymizer::with_mode(AnonymityMode::Off, None);
+        assert_eq!(off.user_id(user_id).await, user_id);
+
+        let dropped = Anonymizer::with_mode(AnonymityMode::Drop, None);
+        assert_eq!(dropped.user_id(user_id).await, None);
+
+        let pseudonymized = Anonymizer::with_mode(AnonymityMode::Pseudonymize, None);
+        let pseudonym = pseudonymized.user_id(user_id).await;
+        assert!(pseudonym.is_some() && pseudonym != user_id);
+        assert_eq!(pseudonymized.user_id(user_id).await, pseudonym);
+        assert_eq!(pseudonymized.user_id(None).await, None);
+    }
+}
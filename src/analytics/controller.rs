@@ -1,11 +1,12 @@
 use crate::analytics::model::{
-    AnalyticsError, EngagementParams, PostStats, PostStatsParams, UserEngagement,
+    AnalyticsError, AudienceParams, CampaignParams, EngagementParams, HeatmapParams, PostStats,
+    PostStatsParams, UserEngagement, ViewEvent,
 };
 use crate::analytics::service::AnalyticsService;
 use crate::auth::jwt::Role;
 use crate::auth::middleware::AuthUser;
 use axum::{
-    extract::{Path, Query, State},
+    extract::{OriginalUri, Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Json},
     Extension,
@@ -16,6 +17,49 @@ use tracing::{error, info};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+/// Record a view of a post
+///
+/// Front-ends report reads here with the referrer and any `utm_*` parameters of the page, so
+/// views can be attributed to campaigns. Signed-in readers are recorded as themselves.
+#[utoipa::path(
+    post,
+    path = "/api/analytics/views",
+    tag = "analytics",
+    request_body = ViewEvent,
+    responses(
+        (status = 204, description = "View recorded"),
+        (status = 404, description = "Post not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn record_view(
+    Extension(user): Extension<Option<AuthUser>>,
+    State(service): State<Arc<AnalyticsService>>,
+    Json(event): Json<ViewEvent>,
+) -> impl IntoResponse {
+    match service
+        .record_view(user.map(|user| user.user_id), &event)
+        .await
+    {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            error!("Failed to record view of post {}: {:?}", event.post_id, e);
+            let status = match e {
+                AnalyticsError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+                AnalyticsError::NotFound => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(json!({
+                    "error": format!("Failed to record view: {}", e)
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Get user engagement metrics
 #[utoipa::path(
     get,
@@ -26,7 +70,8 @@ use uuid::Uuid;
         ("start_date" = Option<String>, Query, description = "Start date for custom range (YYYY-MM-DD)", example = "2025-03-19"),
         ("end_date" = Option<String>, Query, description = "End date for custom range (YYYY-MM-DD)", example = "2025-03-26"),
         ("limit" = Option<i64>, Query, description = "Maximum number of results", example = "100"),
-        ("offset" = Option<i64>, Query, description = "Offset for pagination", example = "0")
+        ("offset" = Option<i64>, Query, description = "Offset for pagination", example = "0"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from a previous response's Link header")
     ),
     responses(
         (status = 200, description = "User engagement metrics retrieved successfully", body = Vec<UserEngagement>),
@@ -40,13 +85,18 @@ use uuid::Uuid;
 )]
 pub async fn get_user_engagement(
     Extension(user): Extension<AuthUser>,
+    OriginalUri(uri): OriginalUri,
     State(service): State<Arc<AnalyticsService>>,
     Query(params): Query<EngagementParams>,
 ) -> impl IntoResponse {
     match service.get_user_engagement(&params).await {
         Ok(engagement) => {
             info!("Retrieved user engagement for user: {}", user.user_id);
-            (StatusCode::OK, Json(json!(engagement)))
+            let headers = params
+                .pagination()
+                .map(|p| p.headers(&uri, engagement.len()))
+                .unwrap_or_default();
+            (StatusCode::OK, headers, Json(json!(engagement))).into_response()
         }
         Err(e) => {
             error!("Failed to get user engagement: {:?}", e);
@@ -62,6 +112,7 @@ pub async fn get_user_engagement(
                     "error": format!("Failed to get user engagement: {}", e)
                 })),
             )
+                .into_response()
         }
     }
 }
@@ -148,7 +199,8 @@ pub async fn get_user_engagement_by_id(
         ("start_date" = Option<String>, Query, description = "Start date for custom range (YYYY-MM-DD)", example = "2025-03-19"),
         ("end_date" = Option<String>, Query, description = "End date for custom range (YYYY-MM-DD)", example = "2025-03-26"),
         ("limit" = Option<i64>, Query, description = "Maximum number of results", example = "100"),
-        ("offset" = Option<i64>, Query, description = "Offset for pagination", example = "0")
+        ("offset" = Option<i64>, Query, description = "Offset for pagination", example = "0"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from a previous response's Link header")
     ),
     responses(
         (status = 200, description = "Post statistics retrieved successfully", body = Vec<PostStats>),
@@ -158,13 +210,18 @@ pub async fn get_user_engagement_by_id(
 )]
 pub async fn get_post_stats(
     _auth_user: Option<Extension<AuthUser>>,
+    OriginalUri(uri): OriginalUri,
     State(service): State<Arc<AnalyticsService>>,
     Query(params): Query<PostStatsParams>,
 ) -> impl IntoResponse {
     match service.get_post_stats(&params).await {
         Ok(stats) => {
             info!("Retrieved post statistics");
-            (StatusCode::OK, Json(json!(stats)))
+            let headers = params
+                .pagination()
+                .map(|p| p.headers(&uri, stats.len()))
+                .unwrap_or_default();
+            (StatusCode::OK, headers, Json(json!(stats))).into_response()
         }
         Err(e) => {
             error!("Failed to get post statistics: {:?}", e);
@@ -180,6 +237,7 @@ pub async fn get_post_stats(
                     "error": format!("Failed to get post statistics: {}", e)
                 })),
             )
+                .into_response()
         }
     }
 }
@@ -283,13 +341,223 @@ pub async fn get_post_stats_by_time(
     }
 }
 
+/// Get the audience of a post (admins and analysts only)
+///
+/// Top commenters, most frequent readers, and how many readers were new or returning over
+/// the time range. Only signed-in users are counted.
+#[utoipa::path(
+    get,
+    path = "/api/analytics/posts/{post_id}/audience",
+    tag = "analytics",
+    params(
+        ("post_id" = i64, Path, description = "Post ID to get the audience of"),
+        AudienceParams
+    ),
+    responses(
+        (status = 200, description = "Post audience retrieved successfully", body = PostAudience),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - admin or analyst access required"),
+        (status = 404, description = "Post not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_post_audience(
+    Extension(auth_user): Extension<AuthUser>,
+    Path(post_id): Path<i64>,
+    State(service): State<Arc<AnalyticsService>>,
+    Query(params): Query<AudienceParams>,
+) -> impl IntoResponse {
+    if auth_user.role != Role::Admin && auth_user.role != Role::Analyst {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Only admins and analysts can view post audiences"
+            })),
+        );
+    }
+
+    match service.get_post_audience(post_id, &params).await {
+        Ok(audience) => {
+            info!("Retrieved audience for post: {}", post_id);
+            (StatusCode::OK, Json(json!(audience)))
+        }
+        Err(e) => {
+            error!("Failed to get audience for post {}: {:?}", post_id, e);
+            let status = match e {
+                AnalyticsError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+                AnalyticsError::NotFound => StatusCode::NOT_FOUND,
+                AnalyticsError::Unauthorized => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(json!({
+                    "error": format!("Failed to get post audience: {}", e)
+                })),
+            )
+        }
+    }
+}
+
+/// Get the views of a post by campaign (admins and analysts only)
+///
+/// Views grouped by `utm_campaign`, `utm_source` and `utm_medium`, most views first. Views
+/// without UTM parameters are attributed to their referring site, or counted as direct.
+#[utoipa::path(
+    get,
+    path = "/api/analytics/posts/{post_id}/campaigns",
+    tag = "analytics",
+    params(
+        ("post_id" = i64, Path, description = "Post ID to get the campaigns of"),
+        CampaignParams
+    ),
+    responses(
+        (status = 200, description = "Campaign statistics retrieved successfully", body = Vec<CampaignStats>),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - admin or analyst access required"),
+        (status = 404, description = "Post not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_post_campaigns(
+    Extension(auth_user): Extension<AuthUser>,
+    Path(post_id): Path<i64>,
+    State(service): State<Arc<AnalyticsService>>,
+    Query(params): Query<CampaignParams>,
+) -> impl IntoResponse {
+    if auth_user.role != Role::Admin && auth_user.role != Role::Analyst {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Only admins and analysts can view post campaigns"
+            })),
+        );
+    }
+
+    match service.get_post_campaigns(post_id, &params).await {
+        Ok(campaigns) => {
+            info!("Retrieved campaigns for post: {}", post_id);
+            (StatusCode::OK, Json(json!(campaigns)))
+        }
+        Err(e) => {
+            error!("Failed to get campaigns for post {}: {:?}", post_id, e);
+            let status = match e {
+                AnalyticsError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+                AnalyticsError::NotFound => StatusCode::NOT_FOUND,
+                AnalyticsError::Unauthorized => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(json!({
+                    "error": format!("Failed to get post campaigns: {}", e)
+                })),
+            )
+        }
+    }
+}
+
+/// Get the activity heatmap of the blog (authors, admins and analysts only)
+///
+/// Interactions with the blog's posts as a 7x24 matrix of weekdays (Monday first) and hours,
+/// for picking publish times. Cached for an hour.
+#[utoipa::path(
+    get,
+    path = "/api/analytics/activity-heatmap",
+    tag = "analytics",
+    params(HeatmapParams),
+    responses(
+        (status = 200, description = "Activity heatmap retrieved successfully", body = ActivityHeatmap),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_activity_heatmap(
+    Extension(auth_user): Extension<AuthUser>,
+    State(service): State<Arc<AnalyticsService>>,
+    Query(params): Query<HeatmapParams>,
+) -> impl IntoResponse {
+    if auth_user.role == Role::User {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Only authors, admins and analysts can view the activity heatmap"
+            })),
+        );
+    }
+
+    match service.get_activity_heatmap(&params).await {
+        Ok(heatmap) => {
+            info!("Retrieved activity heatmap");
+            (StatusCode::OK, Json(json!(heatmap)))
+        }
+        Err(e) => {
+            error!("Failed to get activity heatmap: {:?}", e);
+            let status = match e {
+                AnalyticsError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+                AnalyticsError::NotFound => StatusCode::NOT_FOUND,
+                AnalyticsError::Unauthorized => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(json!({
+                    "error": format!("Failed to get activity heatmap: {}", e)
+                })),
+            )
+        }
+    }
+}
+
+/// Get public sitewide statistics
+///
+/// Unauthenticated counters for homepage widgets. Values are cached for five minutes.
+#[utoipa::path(
+    get,
+    path = "/api/stats/public",
+    tag = "analytics",
+    responses(
+        (status = 200, description = "Sitewide statistics retrieved successfully", body = PublicStats),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_public_stats(State(service): State<Arc<AnalyticsService>>) -> impl IntoResponse {
+    match service.get_public_stats().await {
+        Ok(stats) => (StatusCode::OK, Json(json!(stats))).into_response(),
+        Err(e) => {
+            error!("Failed to get public statistics: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": "Failed to get public statistics"
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Refresh the analytics materialized views (admin only)
 #[utoipa::path(
     post,
     path = "/api/analytics/refresh",
     tag = "analytics",
     responses(
-        (status = 200, description = "Analytics views refreshed successfully"),
+        (status = 200, description = "Analytics views refreshed successfully", body = Object,
+            example = json!({"message": "Analytics materialized views refreshed successfully"})),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden - admin access required"),
         (status = 500, description = "Internal server error")
@@ -0,0 +1,300 @@
+use crate::cache::redis::{RedisCache, POST_VIEWS_STREAM_KEY};
+use crate::tenant::model::DEFAULT_BLOG_ID;
+use chrono::{DateTime, Utc};
+use redis::streams::{StreamId, StreamReadOptions, StreamReadReply};
+use redis::AsyncCommands;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+/// How far back active readers and the top posts right now look
+const LIVE_WINDOW_MS: i64 = 5 * 60 * 1000;
+/// Views per minute are counted over the last minute of the window
+const VIEWS_PER_MINUTE_WINDOW_MS: i64 = 60 * 1000;
+const LIVE_TOP_POSTS: usize = 5;
+// Stream entries read per round trip, and how long a read waits for new ones
+const STREAM_READ_COUNT: usize = 1000;
+const STREAM_BLOCK_MS: usize = 1000;
+// Dashboards are updated at most this often, however busy the stream is
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// A post read taken from the post views stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveView {
+    /// When the view was logged, in milliseconds since the epoch
+    pub at_ms: i64,
+    pub blog_id: i64,
+    pub post_id: i64,
+    /// The reader, for signed-in readers
+    pub user: Option<String>,
+}
+
+impl LiveView {
+    /// Parse a stream entry; the time comes from its ID. Entries logged before views carried
+    /// their blog belong to the default blog.
+    fn from_entry(entry: &StreamId) -> Option<Self> {
+        let at_ms = entry.id.split('-').next()?.parse().ok()?;
+        let post_id = entry.get::<String>("post_id")?.parse().ok()?;
+        let blog_id = entry
+            .get::<String>("blog_id")
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(DEFAULT_BLOG_ID);
+        let user = entry
+            .get::<String>("user")
+            .filter(|user| user != "anonymous");
+
+        Some(Self {
+            at_ms,
+            blog_id,
+            post_id,
+            user,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LivePost {
+    pub post_id: i64,
+    pub views: i64,
+}
+
+/// Rolling counters of one blog
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LiveStats {
+    pub views_per_minute: i64,
+    /// Distinct signed-in readers in the last five minutes
+    pub active_readers: i64,
+    /// Most viewed posts of the last five minutes
+    pub top_posts: Vec<LivePost>,
+}
+
+/// Counters of every blog with recent views, as of `generated_at`
+#[derive(Debug, Clone, Default)]
+pub struct LiveSnapshot {
+    pub generated_at: DateTime<Utc>,
+    pub blogs: HashMap<i64, LiveStats>,
+}
+
+/// The views of the last few minutes, which the live counters are computed from
+#[derive(Debug, Default)]
+pub struct SlidingWindow {
+    views: VecDeque<LiveView>,
+}
+
+impl SlidingWindow {
+    /// Add a view; views must come in the order they were logged
+    pub fn record(&mut self, view: LiveView) {
+        self.views.push_back(view);
+    }
+
+    /// Forget the views that fell out of the window
+    pub fn prune(&mut self, now_ms: i64) {
+        while self
+            .views
+            .front()
+            .is_some_and(|view| view.at_ms <= now_ms - LIVE_WINDOW_MS)
+        {
+            self.views.pop_front();
+        }
+    }
+
+    /// Counters of every blog with views in the window
+    pub fn snapshot(&self, now_ms: i64) -> HashMap<i64, LiveStats> {
+        #[derive(Default)]
+        struct BlogCounts<'a> {
+            last_minute: i64,
+            readers: HashSet<&'a str>,
+            posts: HashMap<i64, i64>,
+        }
+
+        let mut counts: HashMap<i64, BlogCounts> = HashMap::new();
+        for view in &self.views {
+            let blog = counts.entry(view.blog_id).or_default();
+            if view.at_ms > now_ms - VIEWS_PER_MINUTE_WINDOW_MS {
+                blog.last_minute += 1;
+            }
+            if let Some(user) = &view.user {
+                blog.readers.insert(user);
+            }
+            *blog.posts.entry(view.post_id).or_default() += 1;
+        }
+
+        counts
+            .into_iter()
+            .map(|(blog_id, blog)| {
+                let mut top_posts: Vec<LivePost> = blog
+                    .posts
+                    .into_iter()
+                    .map(|(post_id, views)| LivePost { post_id, views })
+                    .collect();
+                top_posts.sort_by(|a, b| b.views.cmp(&a.views).then(a.post_id.cmp(&b.post_id)));
+                top_posts.truncate(LIVE_TOP_POSTS);
+
+                let stats = LiveStats {
+                    views_per_minute: blog.last_minute,
+                    active_readers: blog.readers.len() as i64,
+                    top_posts,
+                };
+                (blog_id, stats)
+            })
+            .collect()
+    }
+}
+
+/// Rolling view counters for live dashboards.
+///
+/// A background task follows the post views stream and keeps the views of the last five
+/// minutes in memory. Every instance follows the whole stream, so each one can serve
+/// dashboards on its own. Without Redis there is no stream, so the dashboard is disabled.
+#[derive(Clone)]
+pub struct LiveDashboard {
+    enabled: bool,
+    snapshots: watch::Receiver<Arc<LiveSnapshot>>,
+}
+
+impl LiveDashboard {
+    /// Spawn the aggregator task and return a handle to its counters
+    pub fn start(redis_cache: Option<RedisCache>) -> Self {
+        let (sender, snapshots) = watch::channel(Arc::new(LiveSnapshot::default()));
+        let enabled = redis_cache.is_some();
+
+        match redis_cache {
+            Some(cache) => {
+                tokio::spawn(aggregate(cache, sender));
+            }
+            None => warn!("Redis is not configured, the live analytics dashboard is disabled"),
+        }
+        Self { enabled, snapshots }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Receiver of the counters, updated about once a second
+    pub fn subscribe(&self) -> watch::Receiver<Arc<LiveSnapshot>> {
+        self.snapshots.clone()
+    }
+}
+
+async fn aggregate(cache: RedisCache, sender: watch::Sender<Arc<LiveSnapshot>>) {
+    let mut window = SlidingWindow::default();
+    // Start a window back, so counters are complete right after a restart
+    let mut last_id = format!("{}-0", Utc::now().timestamp_millis() - LIVE_WINDOW_MS);
+    let options = StreamReadOptions::default()
+        .block(STREAM_BLOCK_MS)
+        .count(STREAM_READ_COUNT);
+    let mut published = Instant::now();
+
+    loop {
+        let mut conn = match cache.blocking_connection().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Live dashboard failed to connect to Redis: {}", e);
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        loop {
+            let reply: Option<StreamReadReply> = match conn
+                .xread_options(&[POST_VIEWS_STREAM_KEY], &[&last_id], &options)
+                .await
+            {
+                Ok(reply) => reply,
+                Err(e) => {
+                    error!("Live dashboard failed to read post views: {}", e);
+                    break;
+                }
+            };
+
+            for entry in reply
+                .into_iter()
+                .flat_map(|reply| reply.keys)
+                .flat_map(|key| key.ids)
+            {
+                if let Some(view) = LiveView::from_entry(&entry) {
+                    window.record(view);
+                }
+                last_id = entry.id;
+            }
+
+            if published.elapsed() >= PUBLISH_INTERVAL {
+                let now = Utc::now();
+                window.prune(now.timestamp_millis());
+                sender.send_replace(Arc::new(LiveSnapshot {
+                    generated_at: now,
+                    blogs: window.snapshot(now.timestamp_millis()),
+                }));
+                published = Instant::now();
+            }
+        }
+
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(at_ms: i64, blog_id: i64, post_id: i64, user: Option<&str>) -> LiveView {
+        LiveView {
+            at_ms,
+            blog_id,
+            post_id,
+            user: user.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let now = 10 * 60 * 1000;
+        let mut window = SlidingWindow::default();
+        window.record(view(now - 4 * 60 * 1000, 1, 7, Some("a")));
+        window.record(view(now - 30 * 1000, 1, 8, Some("a")));
+        window.record(view(now - 20 * 1000, 1, 8, None));
+        window.record(view(now - 10 * 1000, 1, 9, Some("b")));
+        window.record(view(now - 10 * 1000, 2, 3, None));
+
+        let blogs = window.snapshot(now);
+        let stats = &blogs[&1];
+        assert_eq!(stats.views_per_minute, 3);
+        assert_eq!(stats.active_readers, 2);
+        assert_eq!(
+            stats.top_posts,
+            vec![
+                LivePost {
+                    post_id: 8,
+                    views: 2
+                },
+                LivePost {
+                    post_id: 7,
+                    views: 1
+                },
+                LivePost {
+                    post_id: 9,
+                    views: 1
+                },
+            ]
+        );
+        assert_eq!(blogs[&2].active_readers, 0);
+    }
+
+    #[test]
+    fn test_prune() {
+        let now = 10 * 60 * 1000;
+        let mut window = SlidingWindow::default();
+        window.record(view(now - LIVE_WINDOW_MS, 1, 7, Some("a")));
+        window.record(view(now - 1000, 1, 8, Some("b")));
+
+        window.prune(now);
+        let stats = &window.snapshot(now)[&1];
+        assert_eq!(stats.active_readers, 1);
+        assert_eq!(stats.top_posts.len(), 1);
+    }
+}
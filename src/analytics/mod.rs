@@ -1,3 +1,6 @@
+pub mod anonymize;
 pub mod controller;
+pub mod live;
 pub mod model;
 pub mod service;
+pub mod sink;
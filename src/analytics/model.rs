@@ -1,3 +1,4 @@
+use crate::pagination::{Pagination, PaginationError};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -42,14 +43,14 @@ pub struct UserInteraction {
 /// User engagement metrics
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct UserEngagement {
-    #[schema(value_type = String, format = "uuid", example = "123e4567-e89b-12d3-a456-426614174000")]
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
     pub user_id: Uuid,
     pub views: i64,
     pub likes: i64,
     pub comments: i64,
     pub total_interactions: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[schema(nullable = true, value_type = String, format = "date-time", example = "2025-03-26T12:00:00Z")]
+    #[schema(example = "2025-03-26T12:00:00Z")]
     pub day: Option<DateTime<Utc>>,
 }
 
@@ -63,10 +64,160 @@ pub struct PostStats {
     pub total_interactions: i64,
     pub engagement_rate: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[schema(nullable = true, value_type = String, format = "date-time", example = "2025-03-26T12:00:00Z")]
+    #[schema(example = "2025-03-26T12:00:00Z")]
     pub day: Option<DateTime<Utc>>,
 }
 
+/// Sitewide counters that are safe to show publicly
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PublicStats {
+    /// Number of published posts
+    #[schema(example = "1250")]
+    pub post_count: i64,
+    /// Number of users with at least one published post
+    #[schema(example = "87")]
+    pub author_count: i64,
+    /// Total views across published posts
+    #[schema(example = "482113")]
+    pub total_views: i64,
+    /// Number of visible comments
+    #[schema(example = "9321")]
+    pub total_comments: i64,
+}
+
+/// A reader of a post and how often they interacted with it. In anonymous analytics mode
+/// readers are identified by their pseudonym of the day.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct AudienceMember {
+    #[schema(example = "123e4567-e89b-12d3-a456-426614174000")]
+    pub user_id: Uuid,
+    /// Missing for pseudonymous readers in anonymous analytics mode
+    #[schema(example = "jane")]
+    pub username: Option<String>,
+    /// Comments or views, depending on the list
+    #[schema(example = "12")]
+    pub count: i64,
+}
+
+/// Who a post reached over a time range
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PostAudience {
+    pub post_id: i64,
+    /// Users who commented the most
+    pub top_commenters: Vec<AudienceMember>,
+    /// Signed-in users who viewed the post the most
+    pub top_readers: Vec<AudienceMember>,
+    /// Signed-in readers whose first view of any post was in the range
+    #[schema(example = "140")]
+    pub new_readers: i64,
+    /// Signed-in readers who had viewed a post before the range
+    #[schema(example = "310")]
+    pub returning_readers: i64,
+}
+
+/// A view of a post reported by a front-end, with where the reader came from
+#[derive(Debug, Deserialize, Clone, Default, ToSchema)]
+pub struct ViewEvent {
+    #[schema(example = "123")]
+    pub post_id: i64,
+    /// `document.referrer` of the page
+    #[schema(example = "https://news.ycombinator.com/")]
+    pub referrer: Option<String>,
+    #[schema(example = "newsletter")]
+    pub utm_source: Option<String>,
+    #[schema(example = "email")]
+    pub utm_medium: Option<String>,
+    #[schema(example = "spring-launch")]
+    pub utm_campaign: Option<String>,
+    pub utm_term: Option<String>,
+    pub utm_content: Option<String>,
+    /// Time spent reading, if known
+    #[schema(example = "45000")]
+    pub duration_ms: Option<i32>,
+}
+
+/// Longest attribution value kept; longer ones are cut
+const MAX_ATTRIBUTION_LEN: usize = 200;
+
+impl ViewEvent {
+    /// Interaction metadata of the view. Empty fields are left out, and the referrer's host
+    /// is stored next to it so views can be grouped by referring site.
+    pub fn metadata(&self) -> Option<serde_json::Value> {
+        let mut metadata = serde_json::Map::new();
+        let fields = [
+            ("utm_source", &self.utm_source),
+            ("utm_medium", &self.utm_medium),
+            ("utm_campaign", &self.utm_campaign),
+            ("utm_term", &self.utm_term),
+            ("utm_content", &self.utm_content),
+            ("referrer", &self.referrer),
+        ];
+        for (name, value) in fields {
+            let value = value.as_deref().map(str::trim).unwrap_or_default();
+            if !value.is_empty() {
+                let value: String = value.chars().take(MAX_ATTRIBUTION_LEN).collect();
+                metadata.insert(name.to_string(), value.into());
+            }
+        }
+
+        let referrer_host = self
+            .referrer
+            .as_deref()
+            .and_then(|referrer| url::Url::parse(referrer.trim()).ok())
+            .and_then(|url| {
+                url.host_str()
+                    .map(|host| host.trim_start_matches("www.").to_string())
+            });
+        if let Some(host) = referrer_host {
+            metadata.insert("referrer_host".to_string(), host.into());
+        }
+        if let Some(duration) = self.duration_ms {
+            metadata.insert("duration_ms".to_string(), duration.into());
+        }
+
+        (!metadata.is_empty()).then_some(serde_json::Value::Object(metadata))
+    }
+}
+
+/// Views of a post from one campaign, source and medium
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct CampaignStats {
+    /// `utm_campaign`, or `(none)`
+    #[schema(example = "spring-launch")]
+    pub campaign: String,
+    /// `utm_source`, else the referring site, else `(direct)`
+    #[schema(example = "newsletter")]
+    pub source: String,
+    /// `utm_medium`, else `referral` for views with a referrer, else `(none)`
+    #[schema(example = "email")]
+    pub medium: String,
+    #[schema(example = "412")]
+    pub views: i64,
+}
+
+/// Interactions by weekday and hour of the day
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ActivityHeatmap {
+    /// Time zone the weekdays and hours are in
+    #[schema(example = "Europe/Berlin")]
+    pub timezone: String,
+    /// 7 rows of 24 counts: one row per weekday, Monday first, and one count per hour
+    pub counts: Vec<Vec<i64>>,
+}
+
+impl ActivityHeatmap {
+    /// Lay out `(ISO weekday, hour, count)` rows, as Postgres extracts them, as the matrix
+    pub fn from_counts(timezone: String, rows: impl IntoIterator<Item = (i32, i32, i64)>) -> Self {
+        let mut counts = vec![vec![0; 24]; 7];
+        for (weekday, hour, count) in rows {
+            if let (1..=7, 0..=23) = (weekday, hour) {
+                counts[weekday as usize - 1][hour as usize] += count;
+            }
+        }
+        Self { timezone, counts }
+    }
+}
+
 /// Time range for analytics queries
 #[derive(Debug, Serialize, Deserialize)]
 pub enum TimeRange {
@@ -97,12 +248,16 @@ pub struct EngagementParams {
     pub end_date: Option<String>,
 
     /// Maximum number of results
-    #[schema(example = "100", default = "100", minimum = 1, maximum = 1000)]
+    #[schema(example = "100", default = "100", minimum = 1, maximum = 100)]
     pub limit: Option<i64>,
 
     /// Offset for pagination
     #[schema(example = "0", default = "0", minimum = 0)]
     pub offset: Option<i64>,
+
+    /// Opaque pagination cursor from a previous response's `Link` header
+    #[schema(example = "djE6MTAw")]
+    pub cursor: Option<String>,
 }
 
 /// Query parameters for post statistics
@@ -126,12 +281,207 @@ pub struct PostStatsParams {
     pub end_date: Option<String>,
 
     /// Maximum number of results
-    #[schema(example = "100", default = "100", minimum = 1, maximum = 1000)]
+    #[schema(example = "100", default = "100", minimum = 1, maximum = 100)]
     pub limit: Option<i64>,
 
     /// Offset for pagination
     #[schema(example = "0", default = "0", minimum = 0)]
     pub offset: Option<i64>,
+
+    /// Opaque pagination cursor from a previous response's `Link` header
+    #[schema(example = "djE6MTAw")]
+    pub cursor: Option<String>,
+}
+
+/// Query parameters for post audience reports
+#[derive(Debug, Deserialize, Clone, ToSchema, IntoParams)]
+#[into_params(style = Form)]
+pub struct AudienceParams {
+    /// Time range: "day", "week", "month", "year"
+    #[schema(example = "week", default = "week")]
+    pub time_range: Option<String>,
+
+    /// Start date for custom range (format: YYYY-MM-DD)
+    #[schema(value_type = String, format = "date", example = "2025-03-19")]
+    pub start_date: Option<String>,
+
+    /// End date for custom range (format: YYYY-MM-DD)
+    #[schema(value_type = String, format = "date", example = "2025-03-26")]
+    pub end_date: Option<String>,
+
+    /// Length of the top commenter and reader lists
+    #[schema(example = "10", default = "10", minimum = 1, maximum = 50)]
+    pub limit: Option<i64>,
+}
+
+/// Query parameters for post campaign reports
+#[derive(Debug, Deserialize, Clone, ToSchema, IntoParams)]
+#[into_params(style = Form)]
+pub struct CampaignParams {
+    /// Time range: "day", "week", "month", "year"
+    #[schema(example = "month", default = "week")]
+    pub time_range: Option<String>,
+
+    /// Start date for custom range (format: YYYY-MM-DD)
+    #[schema(value_type = String, format = "date", example = "2025-03-19")]
+    pub start_date: Option<String>,
+
+    /// End date for custom range (format: YYYY-MM-DD)
+    #[schema(value_type = String, format = "date", example = "2025-03-26")]
+    pub end_date: Option<String>,
+}
+
+/// Query parameters for the activity heatmap
+#[derive(Debug, Deserialize, Clone, ToSchema, IntoParams)]
+#[into_params(style = Form)]
+pub struct HeatmapParams {
+    /// Time range: "day", "week", "month", "year"
+    #[schema(example = "month", default = "week")]
+    pub time_range: Option<String>,
+
+    /// Start date for custom range (format: YYYY-MM-DD)
+    #[schema(value_type = String, format = "date", example = "2025-03-19")]
+    pub start_date: Option<String>,
+
+    /// End date for custom range (format: YYYY-MM-DD)
+    #[schema(value_type = String, format = "date", example = "2025-03-26")]
+    pub end_date: Option<String>,
+
+    /// IANA time zone to bucket weekdays and hours in
+    #[schema(example = "Europe/Berlin", default = "UTC")]
+    pub timezone: Option<String>,
+}
+
+/// Default and largest length of the lists in audience reports
+pub const AUDIENCE_DEFAULT_LIMIT: i64 = 10;
+pub const AUDIENCE_MAX_LIMIT: i64 = 50;
+
+impl AudienceParams {
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(AUDIENCE_DEFAULT_LIMIT)
+            .clamp(1, AUDIENCE_MAX_LIMIT)
+    }
+}
+
+/// Default page size for analytics listings
+pub const ANALYTICS_PAGE_SIZE: i64 = 100;
+
+impl EngagementParams {
+    /// Resolve the limit/offset/cursor parameters into a page window
+    pub fn pagination(&self) -> Result<Pagination, PaginationError> {
+        Pagination::resolve(
+            self.limit,
+            self.cursor.as_deref(),
+            None,
+            self.offset,
+            ANALYTICS_PAGE_SIZE,
+        )
+    }
+}
+
+impl PostStatsParams {
+    /// Resolve the limit/offset/cursor parameters into a page window
+    pub fn pagination(&self) -> Result<Pagination, PaginationError> {
+        Pagination::resolve(
+            self.limit,
+            self.cursor.as_deref(),
+            None,
+            self.offset,
+            ANALYTICS_PAGE_SIZE,
+        )
+    }
+}
+
+/// Fraction of interactions of each type that are actually stored.
+///
+/// Recorded rows carry their sample rate in `metadata.sample_rate` so reporting queries can
+/// scale them back up. Likes and comments are always counted exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingConfig {
+    pub view: f64,
+    pub share: f64,
+    pub bookmark: f64,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            view: 1.0,
+            share: 1.0,
+            bookmark: 1.0,
+        }
+    }
+}
+
+impl SamplingConfig {
+    /// Read `ANALYTICS_SAMPLE_RATE_VIEW`, `_SHARE` and `_BOOKMARK`, each a rate in (0, 1]
+    pub fn from_env() -> Self {
+        let rate = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(Self::clamp_rate)
+                .unwrap_or(1.0)
+        };
+
+        Self {
+            view: rate("ANALYTICS_SAMPLE_RATE_VIEW"),
+            share: rate("ANALYTICS_SAMPLE_RATE_SHARE"),
+            bookmark: rate("ANALYTICS_SAMPLE_RATE_BOOKMARK"),
+        }
+    }
+
+    // A zero rate would make sampled counts impossible to scale back up
+    fn clamp_rate(rate: f64) -> f64 {
+        if rate.is_nan() {
+            1.0
+        } else {
+            rate.clamp(0.001, 1.0)
+        }
+    }
+
+    /// Sample rate for an interaction type; unknown types are recorded exactly
+    pub fn rate_for(&self, interaction_type: &str) -> f64 {
+        match interaction_type {
+            "view" => self.view,
+            "share" => self.share,
+            "bookmark" => self.bookmark,
+            _ => 1.0,
+        }
+    }
+}
+
+/// How user IDs are stored with recorded interactions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnonymityMode {
+    /// Interactions keep the ID of the user
+    #[default]
+    Off,
+    /// Interactions are stored without a user
+    Drop,
+    /// User IDs are replaced with a pseudonym that changes every day, so readers can be told
+    /// apart within a day but not followed across days
+    Pseudonymize,
+}
+
+impl AnonymityMode {
+    /// Read `ANALYTICS_ANONYMOUS_MODE`: `off`, `drop` or `pseudonymize`
+    pub fn from_env() -> Self {
+        std::env::var("ANALYTICS_ANONYMOUS_MODE")
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "off" | "false" => Some(AnonymityMode::Off),
+            "drop" | "true" => Some(AnonymityMode::Drop),
+            "pseudonymize" | "pseudonymise" => Some(AnonymityMode::Pseudonymize),
+            _ => None,
+        }
+    }
 }
 
 /// Error types for analytics operations
@@ -151,4 +501,80 @@ pub enum AnalyticsError {
 
     #[error("Unauthorized")]
     Unauthorized,
+
+    /// A query shared by concurrent requests failed; the cause is logged where it ran
+    #[error("Query failed: {0}")]
+    QueryFailed(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_anonymity_mode() {
+        assert_eq!(AnonymityMode::parse("drop"), Some(AnonymityMode::Drop));
+        assert_eq!(
+            AnonymityMode::parse(" Pseudonymize "),
+            Some(AnonymityMode::Pseudonymize)
+        );
+        assert_eq!(AnonymityMode::parse(""), Some(AnonymityMode::Off));
+        assert_eq!(AnonymityMode::parse("hash"), None);
+    }
+
+    #[test]
+    fn test_likes_and_comments_are_never_sampled() {
+        let config = SamplingConfig {
+            view: 0.1,
+            share: 0.5,
+            bookmark: 0.5,
+        };
+
+        assert_eq!(config.rate_for("view"), 0.1);
+        assert_eq!(config.rate_for("like"), 1.0);
+        assert_eq!(config.rate_for("comment"), 1.0);
+    }
+
+    #[test]
+    fn test_view_event_metadata() {
+        let event = ViewEvent {
+            post_id: 1,
+            referrer: Some("https://www.example.com/some/page".to_string()),
+            utm_source: Some(" newsletter ".to_string()),
+            utm_campaign: Some(String::new()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            event.metadata(),
+            Some(serde_json::json!({
+                "utm_source": "newsletter",
+                "referrer": "https://www.example.com/some/page",
+                "referrer_host": "example.com",
+            }))
+        );
+        assert_eq!(ViewEvent::default().metadata(), None);
+    }
+
+    #[test]
+    fn test_heatmap_from_counts() {
+        let heatmap = ActivityHeatmap::from_counts(
+            "UTC".to_string(),
+            vec![(1, 0, 3), (7, 23, 5), (3, 14, 2), (8, 0, 9)],
+        );
+
+        assert_eq!(heatmap.counts.len(), 7);
+        assert!(heatmap.counts.iter().all(|row| row.len() == 24));
+        assert_eq!(heatmap.counts[0][0], 3);
+        assert_eq!(heatmap.counts[6][23], 5);
+        assert_eq!(heatmap.counts[2][14], 2);
+        assert_eq!(heatmap.counts.iter().flatten().sum::<i64>(), 10);
+    }
+
+    #[test]
+    fn test_sample_rates_are_clamped() {
+        assert_eq!(SamplingConfig::clamp_rate(0.0), 0.001);
+        assert_eq!(SamplingConfig::clamp_rate(5.0), 1.0);
+        assert_eq!(SamplingConfig::clamp_rate(f64::NAN), 1.0);
+    }
 }
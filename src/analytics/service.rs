@@ -1,28 +1,226 @@
+use crate::analytics::anonymize::Anonymizer;
 use crate::analytics::model::{
-    AnalyticsError, EngagementParams, PostStats, PostStatsParams, UserEngagement,
+    ActivityHeatmap, AnalyticsError, AudienceMember, AudienceParams, CampaignParams, CampaignStats,
+    EngagementParams, HeatmapParams, PostAudience, PostStats, PostStatsParams, PublicStats,
+    SamplingConfig, UserEngagement, ViewEvent,
 };
+use crate::analytics::sink::{InteractionEvent, SinkDispatcher};
 use crate::cache::redis::RedisCache;
+use crate::consent::service::ConsentService;
+use crate::db::instrument::timed;
+use crate::db::router::DbRouter;
+use crate::tenant::middleware::{blog_key, current_blog_id, with_blog_id};
 use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use futures::future::{BoxFuture, FutureExt, Shared};
 use redis::AsyncCommands;
-use sqlx::PgPool;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{PgConnection, PgExecutor, Row};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use tracing::{error, info};
 use uuid::Uuid;
 
 const ENGAGEMENT_CACHE_TTL: u64 = 600; // 10 minutes
 const POST_STATS_CACHE_TTL: u64 = 300; // 5 minutes
+const PUBLIC_STATS_CACHE_TTL: u64 = 300; // 5 minutes
+const HEATMAP_CACHE_TTL: u64 = 3600; // 1 hour
+const NEGATIVE_CACHE_TTL: u64 = 60; // 1 minute, for empty results so new data shows up soon
+                                    // Campaign reports list at most this many campaign, source and medium combinations
+const MAX_CAMPAIGN_ROWS: i64 = 100;
+const PUBLIC_STATS_CACHE_KEY: &str = "analytics:public_stats";
+
+// TTLs are spread by up to this share either way, so entries cached together don't all
+// expire together
+const CACHE_TTL_JITTER: f64 = 0.1;
+
+/// A query being run for a cache key, shared by every request for that key that comes in
+/// meanwhile. Resolves to the result as JSON, or the error message.
+type InFlightQuery = Shared<BoxFuture<'static, Result<Arc<str>, String>>>;
+
+/// Results of cached analytics queries
+trait CachedResult: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Empty results are cached with the shorter negative TTL
+    fn is_empty_result(&self) -> bool;
+}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + 'static> CachedResult for Vec<T> {
+    fn is_empty_result(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl CachedResult for UserEngagement {
+    fn is_empty_result(&self) -> bool {
+        self.total_interactions == 0
+    }
+}
+
+impl CachedResult for PostAudience {
+    fn is_empty_result(&self) -> bool {
+        self.top_commenters.is_empty() && self.top_readers.is_empty()
+    }
+}
+
+impl CachedResult for ActivityHeatmap {
+    fn is_empty_result(&self) -> bool {
+        self.counts.iter().flatten().all(|count| *count == 0)
+    }
+}
+
+impl CachedResult for PublicStats {
+    fn is_empty_result(&self) -> bool {
+        self.post_count == 0 && self.total_comments == 0
+    }
+}
+
+/// `ttl` seconds moved randomly by up to [`CACHE_TTL_JITTER`] of it either way
+fn jittered_ttl(ttl: u64) -> u64 {
+    let jitter = (ttl as f64 * CACHE_TTL_JITTER) as u64;
+    (ttl - jitter + rand::random_range(0..=jitter * 2)).max(1)
+}
+
+/// The requested time range as part of a cache key. Relative ranges are keyed by name rather
+/// than by their start and end, which move with every request.
+fn range_cache_key<T: HasTimeRange>(params: &T) -> String {
+    match (params.start_date(), params.end_date()) {
+        (Some(start), Some(end)) => format!("{}:{}", start, end),
+        // Unknown ranges fall back to a week, as in `get_time_range`
+        _ => match params.time_range().as_deref() {
+            Some(range @ ("day" | "week" | "month" | "year")) => range.to_string(),
+            _ => "week".to_string(),
+        },
+    }
+}
+
+/// Cache a query result for a jittered `ttl`, or the negative TTL if it is empty, and return
+/// it as JSON
+async fn store_result<T: CachedResult>(
+    redis_cache: Option<&RedisCache>,
+    key: &str,
+    ttl: u64,
+    result: &T,
+) -> Result<Arc<str>, String> {
+    let json = serde_json::to_string(result).map_err(|e| e.to_string())?;
+
+    if let Some(cache) = redis_cache {
+        let ttl = if result.is_empty_result() {
+            NEGATIVE_CACHE_TTL
+        } else {
+            ttl
+        };
+        if let Ok(mut conn) = cache.connection().await {
+            if let Err(e) = conn.set_ex::<_, _, ()>(key, &json, jittered_ttl(ttl)).await {
+                error!("Failed to cache {}: {}", key, e);
+            }
+        }
+    }
+
+    Ok(Arc::from(json))
+}
 
 #[derive(Clone)]
 pub struct AnalyticsService {
-    pool: PgPool,
+    db: DbRouter,
     redis_cache: Option<RedisCache>,
+    sampling: SamplingConfig,
+    anonymizer: Anonymizer,
+    consent: ConsentService,
+    sink: Option<SinkDispatcher>,
+    in_flight: Arc<Mutex<HashMap<String, InFlightQuery>>>,
 }
 
 impl AnalyticsService {
-    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
-        Self { pool, redis_cache }
+    pub fn new(db: DbRouter, redis_cache: Option<RedisCache>) -> Self {
+        Self {
+            consent: ConsentService::new(db.primary().clone(), redis_cache.clone()),
+            anonymizer: Anonymizer::new(redis_cache.clone()),
+            db,
+            redis_cache,
+            sampling: SamplingConfig::from_env(),
+            sink: SinkDispatcher::global(),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
     }
 
-    /// Record a user interaction
+    /// Serve a query from the Redis cache, or run it and cache the result. Identical requests
+    /// that miss the cache at the same time share one run of the query. Cache failures only
+    /// cost a fresh query.
+    async fn cached<T, Q>(&self, key: String, ttl: u64, query: Q) -> Result<T, AnalyticsError>
+    where
+        T: CachedResult,
+        Q: Future<Output = Result<T, AnalyticsError>> + Send + 'static,
+    {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Ok(Some(cached_data)) = conn.get::<_, Option<String>>(&key).await {
+                    match serde_json::from_str::<T>(&cached_data) {
+                        Ok(result) => return Ok(result),
+                        Err(e) => error!("Failed to deserialize cached {}: {}", key, e),
+                    }
+                }
+            }
+        }
+
+        let shared = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some(shared) => shared.clone(),
+                None => {
+                    let task = tokio::spawn(self.run_query(key.clone(), ttl, query));
+                    let shared = async move { task.await.unwrap_or_else(|e| Err(e.to_string())) }
+                        .boxed()
+                        .shared();
+                    in_flight.insert(key.clone(), shared.clone());
+                    shared
+                }
+            }
+        };
+
+        match shared.await {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| {
+                AnalyticsError::QueryFailed(format!("Failed to deserialize {}: {}", key, e))
+            }),
+            Err(message) => Err(AnalyticsError::QueryFailed(message)),
+        }
+    }
+
+    // Run a query on behalf of everyone waiting for `key` and cache its result. It runs in its
+    // own task, so it finishes even if the request that started it goes away, and keeps the
+    // blog of that request.
+    fn run_query<T, Q>(
+        &self,
+        key: String,
+        ttl: u64,
+        query: Q,
+    ) -> impl Future<Output = Result<Arc<str>, String>> + Send + 'static
+    where
+        T: CachedResult,
+        Q: Future<Output = Result<T, AnalyticsError>> + Send + 'static,
+    {
+        let redis_cache = self.redis_cache.clone();
+        let in_flight = self.in_flight.clone();
+
+        with_blog_id(current_blog_id(), async move {
+            let outcome = match query.await {
+                Ok(result) => store_result(redis_cache.as_ref(), &key, ttl, &result).await,
+                Err(e) => {
+                    error!("Analytics query for {} failed: {:?}", key, e);
+                    Err(e.to_string())
+                }
+            };
+            // Requests from now on find the result in the cache
+            in_flight.lock().unwrap().remove(&key);
+            outcome
+        })
+    }
+
+    /// Record a user interaction.
+    ///
+    /// High-volume interaction types may be sampled (see [`SamplingConfig`]); returns `None`
+    /// when the interaction was dropped by sampling or not recorded because the request or user
+    /// opted out of tracking (see [`ConsentService`]). In anonymous mode the user is dropped or
+    /// replaced with a daily pseudonym (see [`Anonymizer`]).
     pub async fn record_interaction(
         &self,
         user_id: Option<Uuid>,
@@ -30,9 +228,94 @@ impl AnalyticsService {
         post_id: Option<i64>,
         comment_id: Option<i64>,
         metadata: Option<serde_json::Value>,
-    ) -> Result<i64, AnalyticsError> {
+    ) -> Result<Option<i64>, AnalyticsError> {
+        let event = self
+            .insert_interaction(
+                self.db.primary(),
+                user_id,
+                interaction_type,
+                post_id,
+                comment_id,
+                metadata,
+            )
+            .await?;
+
+        Ok(event.map(|event| {
+            let interaction_id = event.interaction_id;
+            self.export_interaction(event);
+            interaction_id
+        }))
+    }
+
+    /// Record a user interaction as part of the caller's transaction.
+    ///
+    /// The returned event should be passed to [`AnalyticsService::export_interaction`] once
+    /// the transaction has committed.
+    pub async fn record_interaction_in_tx(
+        &self,
+        conn: &mut PgConnection,
+        user_id: Option<Uuid>,
+        interaction_type: &str,
+        post_id: Option<i64>,
+        comment_id: Option<i64>,
+        metadata: Option<serde_json::Value>,
+    ) -> Result<Option<InteractionEvent>, AnalyticsError> {
+        self.insert_interaction(
+            conn,
+            user_id,
+            interaction_type,
+            post_id,
+            comment_id,
+            metadata,
+        )
+        .await
+    }
+
+    /// Mirror a recorded interaction to the external analytics store, if one is configured
+    pub fn export_interaction(&self, event: InteractionEvent) {
+        if let Some(sink) = &self.sink {
+            sink.dispatch(event);
+        }
+    }
+
+    async fn insert_interaction<'e, E>(
+        &self,
+        executor: E,
+        user_id: Option<Uuid>,
+        interaction_type: &str,
+        post_id: Option<i64>,
+        comment_id: Option<i64>,
+        metadata: Option<serde_json::Value>,
+    ) -> Result<Option<InteractionEvent>, AnalyticsError>
+    where
+        E: PgExecutor<'e>,
+    {
+        if !self.consent.allows_tracking(user_id).await {
+            return Ok(None);
+        }
+
+        let sample_rate = self.sampling.rate_for(interaction_type);
+        if sample_rate < 1.0 && rand::random::<f64>() >= sample_rate {
+            return Ok(None);
+        }
+
+        // Remember the rate so reporting can scale sampled rows back up
+        let metadata = if sample_rate < 1.0 {
+            let mut metadata = match metadata {
+                Some(serde_json::Value::Object(map)) => map,
+                _ => serde_json::Map::new(),
+            };
+            metadata.insert("sample_rate".to_string(), serde_json::json!(sample_rate));
+            Some(serde_json::Value::Object(metadata))
+        } else {
+            metadata
+        };
+
+        let user_id = self.anonymizer.user_id(user_id).await;
+
         // Insert interaction record
-        let interaction_id = sqlx::query_scalar!(
+        let created_at = Utc::now();
+        let query = sqlx::query_scalar!(
             r#"
             INSERT INTO global.user_interactions (
                 user_id, interaction_type, post_id, comment_id, metadata, created_at
@@ -45,17 +328,82 @@ impl AnalyticsService {
             post_id,
             comment_id,
             metadata,
-            Utc::now()
+            created_at
         )
-        .fetch_one(&self.pool)
-        .await?;
+        .fetch_one(executor);
+        let interaction_id = timed("analytics.record_interaction", query).await?;
 
         info!(
             "Recorded {} interaction for user {:?} on post {:?}, comment {:?}",
             interaction_type, user_id, post_id, comment_id
         );
 
-        Ok(interaction_id)
+        Ok(Some(InteractionEvent {
+            interaction_id,
+            user_id,
+            interaction_type: interaction_type.to_string(),
+            post_id,
+            comment_id,
+            sample_rate,
+            metadata,
+            created_at,
+        }))
+    }
+
+    /// Record a view reported by a front-end, along with its referrer and UTM parameters
+    pub async fn record_view(
+        &self,
+        user_id: Option<Uuid>,
+        event: &ViewEvent,
+    ) -> Result<Option<i64>, AnalyticsError> {
+        self.ensure_post_in_blog(event.post_id).await?;
+        self.record_interaction(user_id, "view", Some(event.post_id), None, event.metadata())
+            .await
+    }
+
+    /// Longest time the user spent reading the post, from the durations of their recorded
+    /// views. In anonymous mode only today's views can be attributed to the user, and none at
+    /// all when user IDs are dropped.
+    pub async fn longest_read(
+        &self,
+        user_id: Uuid,
+        post_id: i64,
+    ) -> Result<Option<std::time::Duration>, AnalyticsError> {
+        let user_id = match self.anonymizer.user_id(Some(user_id)).await {
+            Some(user_id) => user_id,
+            None => return Ok(None),
+        };
+
+        // Read from the primary, as the view may have been reported just now
+        let query = sqlx::query_scalar::<_, Option<i64>>(
+            r#"
+            SELECT MAX((metadata->>'duration_ms')::BIGINT)
+            FROM global.user_interactions
+            WHERE user_id = $1 AND post_id = $2 AND interaction_type = 'view'
+                AND metadata ? 'duration_ms'
+            "#,
+        )
+        .bind(user_id)
+        .bind(post_id)
+        .fetch_one(self.db.primary());
+        let longest = timed("analytics.longest_read", query).await?;
+
+        Ok(longest.map(|millis| std::time::Duration::from_millis(millis.max(0) as u64)))
+    }
+
+    // Reports and events about posts of other blogs are treated as about missing posts
+    async fn ensure_post_in_blog(&self, post_id: i64) -> Result<(), AnalyticsError> {
+        let query = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM global.posts WHERE id = $1 AND blog_id = $2 AND is_deleted = false)",
+        )
+        .bind(post_id)
+        .bind(current_blog_id())
+        .fetch_one(self.db.read());
+        if timed("analytics.post_in_blog", query).await? {
+            Ok(())
+        } else {
+            Err(AnalyticsError::NotFound)
+        }
     }
 
     /// Get user engagement metrics
@@ -63,102 +411,80 @@ impl AnalyticsService {
         &self,
         params: &EngagementParams,
     ) -> Result<Vec<UserEngagement>, AnalyticsError> {
-        let limit = params.limit.unwrap_or(100);
-        let offset = params.offset.unwrap_or(0);
+        let pagination = params
+            .pagination()
+            .map_err(|e| AnalyticsError::InvalidParameter(e.to_string()))?;
+        let (limit, offset) = (pagination.limit, pagination.offset);
 
         // Determine time range based on params
         let (start_date, end_date) = self.get_time_range(params)?;
 
-        // Try to get from cache if available
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = format!(
-                "analytics:user_engagement:range:{}:{}:{}:{}",
-                start_date.to_rfc3339(),
-                end_date.to_rfc3339(),
-                limit,
-                offset
-            );
-
-            let cache_result = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(AnalyticsError::CacheError)?
-                .get::<_, Option<String>>(&cache_key)
-                .await
-                .map_err(AnalyticsError::CacheError)?;
-
-            if let Some(cached_data) = cache_result {
-                return serde_json::from_str::<Vec<UserEngagement>>(&cached_data).map_err(|e| {
-                    error!("Failed to deserialize cached engagement data: {}", e);
-                    AnalyticsError::InvalidParameter(format!(
-                        "Failed to deserialize cached data: {}",
-                        e
-                    ))
-                });
-            }
-        }
+        let cache_key = blog_key(&format!(
+            "analytics:user_engagement:range:{}:{}:{}",
+            range_cache_key(params),
+            limit,
+            offset
+        ));
+        let query = Self::fetch_user_engagement(
+            self.db.clone(),
+            current_blog_id(),
+            start_date,
+            end_date,
+            limit,
+            offset,
+        );
+        self.cached(cache_key, ENGAGEMENT_CACHE_TTL, query).await
+    }
 
-        // Query database if not in cache
-        let rows = sqlx::query!(
+    async fn fetch_user_engagement(
+        db: DbRouter,
+        blog_id: i64,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UserEngagement>, AnalyticsError> {
+        let query = sqlx::query(
             r#"
             SELECT
-                user_id,
-                COUNT(*) FILTER (WHERE interaction_type = 'view') AS "views!",
-                COUNT(*) FILTER (WHERE interaction_type = 'like') AS "likes!",
-                COUNT(*) FILTER (WHERE interaction_type = 'comment') AS "comments!",
-                COUNT(*) AS "total_interactions!"
-            FROM global.user_interactions
+                ui.user_id,
+                ROUND(COALESCE(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)) FILTER (WHERE ui.interaction_type = 'view'), 0))::BIGINT AS views,
+                COUNT(*) FILTER (WHERE ui.interaction_type = 'like') AS likes,
+                COUNT(*) FILTER (WHERE ui.interaction_type = 'comment') AS comments,
+                ROUND(COALESCE(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)), 0))::BIGINT AS total_interactions
+            FROM global.user_interactions ui
+            JOIN global.posts p ON p.id = ui.post_id
             WHERE
-                user_id IS NOT NULL AND
-                created_at >= $1 AND
-                created_at <= $2
-            GROUP BY user_id
-            ORDER BY "total_interactions!" DESC
-            LIMIT $3
-            OFFSET $4
+                p.blog_id = $1 AND
+                ui.user_id IS NOT NULL AND
+                ui.created_at >= $2 AND
+                ui.created_at <= $3
+            GROUP BY ui.user_id
+            ORDER BY total_interactions DESC
+            LIMIT $4
+            OFFSET $5
             "#,
-            start_date,
-            end_date,
-            limit,
-            offset
         )
-        .fetch_all(&self.pool)
-        .await?;
+        .bind(blog_id)
+        .bind(start_date)
+        .bind(end_date)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db.read());
+        let rows = timed("analytics.user_engagement", query).await?;
 
         let engagement_data: Vec<UserEngagement> = rows
             .into_iter()
             .map(|row| UserEngagement {
-                user_id: row.user_id.unwrap(),
-                views: row.views,
-                likes: row.likes,
-                comments: row.comments,
-                total_interactions: row.total_interactions,
+                user_id: row.get("user_id"),
+                views: row.get("views"),
+                likes: row.get("likes"),
+                comments: row.get("comments"),
+                total_interactions: row.get("total_interactions"),
                 day: None,
             })
             .collect();
 
-        // Cache the result
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = format!(
-                "analytics:user_engagement:range:{}:{}:{}:{}",
-                start_date.to_rfc3339(),
-                end_date.to_rfc3339(),
-                limit,
-                offset
-            );
-
-            let json_data = serde_json::to_string(&engagement_data).unwrap_or_default();
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(AnalyticsError::CacheError)?
-                .set_ex(&cache_key, &json_data, ENGAGEMENT_CACHE_TTL)
-                .await
-                .map_err(AnalyticsError::CacheError)?;
-        }
-
         Ok(engagement_data)
     }
 
@@ -171,55 +497,49 @@ impl AnalyticsService {
         // Determine time range based on params
         let (start_date, end_date) = self.get_time_range(params)?;
 
-        // Try to get from cache if available
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = format!(
-                "analytics:user_engagement:{}:{}:{}",
-                user_id,
-                start_date.to_rfc3339(),
-                end_date.to_rfc3339()
-            );
-
-            let cache_result = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(AnalyticsError::CacheError)?
-                .get::<_, Option<String>>(&cache_key)
-                .await
-                .map_err(AnalyticsError::CacheError)?;
-
-            if let Some(cached_data) = cache_result {
-                return serde_json::from_str::<UserEngagement>(&cached_data).map_err(|e| {
-                    error!("Failed to deserialize cached user engagement data: {}", e);
-                    AnalyticsError::InvalidParameter(format!(
-                        "Failed to deserialize cached data: {}",
-                        e
-                    ))
-                });
-            }
-        }
+        let cache_key = blog_key(&format!(
+            "analytics:user_engagement:{}:{}",
+            user_id,
+            range_cache_key(params)
+        ));
+        let query = Self::fetch_user_engagement_by_id(
+            self.db.clone(),
+            current_blog_id(),
+            user_id,
+            start_date,
+            end_date,
+        );
+        self.cached(cache_key, ENGAGEMENT_CACHE_TTL, query).await
+    }
 
-        // Query database if not in cache
-        let row = sqlx::query!(
+    async fn fetch_user_engagement_by_id(
+        db: DbRouter,
+        blog_id: i64,
+        user_id: Uuid,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+    ) -> Result<UserEngagement, AnalyticsError> {
+        let query = sqlx::query!(
             r#"
             SELECT
-                COUNT(*) FILTER (WHERE interaction_type = 'view') AS "views!",
-                COUNT(*) FILTER (WHERE interaction_type = 'like') AS "likes!",
-                COUNT(*) FILTER (WHERE interaction_type = 'comment') AS "comments!",
-                COUNT(*) AS "total_interactions!"
-            FROM global.user_interactions
+                ROUND(COALESCE(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)) FILTER (WHERE ui.interaction_type = 'view'), 0))::BIGINT AS "views!",
+                COUNT(*) FILTER (WHERE ui.interaction_type = 'like') AS "likes!",
+                COUNT(*) FILTER (WHERE ui.interaction_type = 'comment') AS "comments!",
+                ROUND(COALESCE(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)), 0))::BIGINT AS "total_interactions!"
+            FROM global.user_interactions ui
+            JOIN global.posts p ON p.id = ui.post_id AND p.blog_id = $4
             WHERE
-                user_id = $1 AND
-                created_at >= $2 AND
-                created_at <= $3
+                ui.user_id = $1 AND
+                ui.created_at >= $2 AND
+                ui.created_at <= $3
             "#,
             user_id,
             start_date,
-            end_date
+            end_date,
+            blog_id
         )
-        .fetch_one(&self.pool)
-        .await?;
+        .fetch_one(db.read());
+        let row = timed("analytics.user_engagement_by_id", query).await?;
 
         let engagement = UserEngagement {
             user_id,
@@ -230,26 +550,6 @@ impl AnalyticsService {
             day: None,
         };
 
-        // Cache the result
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = format!(
-                "analytics:user_engagement:{}:{}:{}",
-                user_id,
-                start_date.to_rfc3339(),
-                end_date.to_rfc3339()
-            );
-
-            let json_data = serde_json::to_string(&engagement).unwrap_or_default();
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(AnalyticsError::CacheError)?
-                .set_ex(&cache_key, &json_data, ENGAGEMENT_CACHE_TTL)
-                .await
-                .map_err(AnalyticsError::CacheError)?;
-        }
-
         Ok(engagement)
     }
 
@@ -258,72 +558,78 @@ impl AnalyticsService {
         &self,
         params: &PostStatsParams,
     ) -> Result<Vec<PostStats>, AnalyticsError> {
-        let limit = params.limit.unwrap_or(100);
-        let offset = params.offset.unwrap_or(0);
+        let pagination = params
+            .pagination()
+            .map_err(|e| AnalyticsError::InvalidParameter(e.to_string()))?;
+        let (limit, offset) = (pagination.limit, pagination.offset);
 
         // Determine time range based on params
         let (start_date, end_date) = self.get_time_range(params)?;
 
-        // Try to get from cache if available
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = if let Some(post_id) = params.post_id {
-                format!("analytics:post_stats:{}", post_id)
-            } else {
-                format!(
-                    "analytics:post_stats:range:{}:{}:{}:{}",
-                    start_date.to_rfc3339(),
-                    end_date.to_rfc3339(),
-                    limit,
-                    offset
-                )
-            };
-
-            let cache_result = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(AnalyticsError::CacheError)?
-                .get::<_, Option<String>>(&cache_key)
-                .await
-                .map_err(AnalyticsError::CacheError)?;
-
-            if let Some(cached_data) = cache_result {
-                return serde_json::from_str::<Vec<PostStats>>(&cached_data).map_err(|e| {
-                    error!("Failed to deserialize cached post stats data: {}", e);
-                    AnalyticsError::InvalidParameter(format!(
-                        "Failed to deserialize cached data: {}",
-                        e
-                    ))
-                });
-            }
-        }
+        let cache_key = blog_key(&match params.post_id {
+            Some(post_id) => format!(
+                "analytics:post_stats:{}:{}",
+                post_id,
+                range_cache_key(params)
+            ),
+            None => format!(
+                "analytics:post_stats:range:{}:{}:{}",
+                range_cache_key(params),
+                limit,
+                offset
+            ),
+        });
+        let query = Self::fetch_post_stats(
+            self.db.clone(),
+            current_blog_id(),
+            params.post_id,
+            start_date,
+            end_date,
+            limit,
+            offset,
+        );
+        self.cached(cache_key, POST_STATS_CACHE_TTL, query).await
+    }
 
+    async fn fetch_post_stats(
+        db: DbRouter,
+        blog_id: i64,
+        post_id: Option<i64>,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<PostStats>, AnalyticsError> {
         // Build the query based on params
-        let rows = sqlx::query!(
+        let query = sqlx::query(
             r#"
             WITH post_data AS (
                 SELECT
-                    post_id,
-                    COUNT(*) FILTER (WHERE interaction_type = 'view') AS views,
-                    COUNT(*) FILTER (WHERE interaction_type = 'like') AS likes,
-                    COUNT(*) FILTER (WHERE interaction_type = 'comment') AS comments,
-                    COUNT(*) AS total_interactions
-                FROM global.user_interactions
+                    ui.post_id,
+                    ROUND(COALESCE(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)) FILTER (WHERE ui.interaction_type = 'view'), 0))::BIGINT AS views,
+                    COUNT(*) FILTER (WHERE ui.interaction_type = 'like') AS likes,
+                    COUNT(*) FILTER (WHERE ui.interaction_type = 'comment') AS comments,
+                    ROUND(COALESCE(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)), 0))::BIGINT AS total_interactions
+                FROM global.user_interactions ui
+                JOIN global.posts p ON p.id = ui.post_id
                 WHERE
-                    (CASE WHEN $1::BIGINT IS NOT NULL THEN post_id = $1 ELSE TRUE END) AND
-                    created_at >= $2 AND
-                    created_at <= $3
-                GROUP BY post_id
+                    p.blog_id = $6 AND
+                    (CASE WHEN $1::BIGINT IS NOT NULL THEN ui.post_id = $1 ELSE TRUE END) AND
+                    ui.created_at >= $2 AND
+                    ui.created_at <= $3
+                GROUP BY ui.post_id
             ),
             post_views AS (
                 SELECT
-                    post_id,
-                    COUNT(*) AS view_count
-                FROM global.user_interactions
+                    ui.post_id,
+                    ROUND(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)))::BIGINT AS view_count
+                FROM global.user_interactions ui
+                JOIN global.posts p ON p.id = ui.post_id
                 WHERE
-                    (CASE WHEN $1::BIGINT IS NOT NULL THEN post_id = $1 ELSE TRUE END) AND
-                    interaction_type = 'view'
-                GROUP BY post_id
+                    p.blog_id = $6 AND
+                    (CASE WHEN $1::BIGINT IS NOT NULL THEN ui.post_id = $1 ELSE TRUE END) AND
+                    ui.interaction_type = 'view'
+                GROUP BY ui.post_id
             )
             SELECT
                 pd.post_id,
@@ -342,25 +648,26 @@ impl AnalyticsService {
             LIMIT (CASE WHEN $1::BIGINT IS NULL THEN $4::BIGINT ELSE NULL::BIGINT END)
             OFFSET (CASE WHEN $1::BIGINT IS NULL THEN $5::BIGINT ELSE 0 END)
             "#,
-            params.post_id,
-            start_date,
-            end_date,
-            limit as i64,
-            offset as i64
         )
-        .fetch_all(&self.pool)
-        .await?;
+        .bind(post_id)
+        .bind(start_date)
+        .bind(end_date)
+        .bind(limit)
+        .bind(offset)
+        .bind(blog_id)
+        .fetch_all(db.read());
+        let rows = timed("analytics.post_stats", query).await?;
 
         let post_stats: Vec<PostStats> = rows
             .into_iter()
             .map(|row| PostStats {
-                post_id: row.post_id.unwrap(),
-                views: row.views.unwrap_or(0),
-                likes: row.likes.unwrap_or(0),
-                comments: row.comments.unwrap_or(0),
-                total_interactions: row.total_interactions.unwrap_or(0),
+                post_id: row.get("post_id"),
+                views: row.get::<Option<i64>, _>("views").unwrap_or(0),
+                likes: row.get::<Option<i64>, _>("likes").unwrap_or(0),
+                comments: row.get::<Option<i64>, _>("comments").unwrap_or(0),
+                total_interactions: row.get::<Option<i64>, _>("total_interactions").unwrap_or(0),
                 engagement_rate: row
-                    .engagement_rate
+                    .get::<Option<sqlx::types::BigDecimal>, _>("engagement_rate")
                     .unwrap_or_default()
                     .to_string()
                     .parse::<f64>()
@@ -369,31 +676,6 @@ impl AnalyticsService {
             })
             .collect();
 
-        // Cache the result
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = if let Some(post_id) = params.post_id {
-                format!("analytics:post_stats:{}", post_id)
-            } else {
-                format!(
-                    "analytics:post_stats:range:{}:{}:{}:{}",
-                    start_date.to_rfc3339(),
-                    end_date.to_rfc3339(),
-                    limit,
-                    offset
-                )
-            };
-
-            let json_data = serde_json::to_string(&post_stats).unwrap_or_default();
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(AnalyticsError::CacheError)?
-                .set_ex(&cache_key, &json_data, POST_STATS_CACHE_TTL)
-                .await
-                .map_err(AnalyticsError::CacheError)?;
-        }
-
         Ok(post_stats)
     }
 
@@ -434,30 +716,6 @@ impl AnalyticsService {
             }
         };
 
-        // Try to get from cache if available
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = format!("analytics:post_stats:{}:time:{}", post_id, time_range);
-
-            let cache_result = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(AnalyticsError::CacheError)?
-                .get::<_, Option<String>>(&cache_key)
-                .await
-                .map_err(AnalyticsError::CacheError)?;
-
-            if let Some(cached_data) = cache_result {
-                return serde_json::from_str::<Vec<PostStats>>(&cached_data).map_err(|e| {
-                    error!("Failed to deserialize cached time-based post stats: {}", e);
-                    AnalyticsError::InvalidParameter(format!(
-                        "Failed to deserialize cached data: {}",
-                        e
-                    ))
-                });
-            }
-        }
-
         // Get time interval for grouping
         let interval = match time_range {
             "day" => "hour",
@@ -466,18 +724,37 @@ impl AnalyticsService {
             "year" => "month",
             _ => "day",
         };
+        self.ensure_post_in_blog(post_id).await?;
 
+        let cache_key = format!("analytics:post_stats:{}:time:{}", post_id, time_range);
+        let query = Self::fetch_post_stats_by_time(
+            self.db.clone(),
+            post_id,
+            interval,
+            start_date,
+            end_date,
+        );
+        self.cached(cache_key, POST_STATS_CACHE_TTL, query).await
+    }
+
+    async fn fetch_post_stats_by_time(
+        db: DbRouter,
+        post_id: i64,
+        interval: &'static str,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+    ) -> Result<Vec<PostStats>, AnalyticsError> {
         // Query database
-        let rows = sqlx::query!(
+        let query = sqlx::query!(
             r#"
             WITH time_data AS (
                 SELECT
                     post_id,
                     DATE_TRUNC($1, created_at) AS time_bucket,
-                    COUNT(*) FILTER (WHERE interaction_type = 'view') AS views,
+                    ROUND(COALESCE(SUM(1.0 / COALESCE((metadata->>'sample_rate')::FLOAT8, 1.0)) FILTER (WHERE interaction_type = 'view'), 0))::BIGINT AS views,
                     COUNT(*) FILTER (WHERE interaction_type = 'like') AS likes,
                     COUNT(*) FILTER (WHERE interaction_type = 'comment') AS comments,
-                    COUNT(*) AS total_interactions
+                    ROUND(COALESCE(SUM(1.0 / COALESCE((metadata->>'sample_rate')::FLOAT8, 1.0)), 0))::BIGINT AS total_interactions
                 FROM global.user_interactions
                 WHERE
                     post_id = $2 AND
@@ -490,7 +767,7 @@ impl AnalyticsService {
                 SELECT
                     post_id,
                     DATE_TRUNC($1, created_at) AS time_bucket,
-                    COUNT(*) AS view_count
+                    ROUND(SUM(1.0 / COALESCE((metadata->>'sample_rate')::FLOAT8, 1.0)))::BIGINT AS view_count
                 FROM global.user_interactions
                 WHERE
                     post_id = $2 AND
@@ -519,8 +796,8 @@ impl AnalyticsService {
             start_date,
             end_date
         )
-        .fetch_all(&self.pool)
-        .await?;
+        .fetch_all(db.read());
+        let rows = timed("analytics.post_stats_by_time", query).await?;
 
         let stats: Vec<PostStats> = rows
             .into_iter()
@@ -540,22 +817,263 @@ impl AnalyticsService {
             })
             .collect();
 
-        // Cache the result
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = format!("analytics:post_stats:{}:time:{}", post_id, time_range);
+        Ok(stats)
+    }
 
-            let json_data = serde_json::to_string(&stats).unwrap_or_default();
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(AnalyticsError::CacheError)?
-                .set_ex(&cache_key, &json_data, POST_STATS_CACHE_TTL)
-                .await
-                .map_err(AnalyticsError::CacheError)?;
-        }
+    /// Get the top commenters, most frequent readers and new vs returning readers of a post.
+    ///
+    /// Only signed-in users are counted. With view sampling, reader counts are scaled back up
+    /// and the new vs returning split only covers the sampled readers.
+    pub async fn get_post_audience(
+        &self,
+        post_id: i64,
+        params: &AudienceParams,
+    ) -> Result<PostAudience, AnalyticsError> {
+        let (start_date, end_date) = self.get_time_range(params)?;
+        let limit = params.limit();
+        self.ensure_post_in_blog(post_id).await?;
 
-        Ok(stats)
+        let cache_key = format!(
+            "analytics:post_audience:{}:{}:{}",
+            post_id,
+            range_cache_key(params),
+            limit
+        );
+        let query =
+            Self::fetch_post_audience(self.db.clone(), post_id, start_date, end_date, limit);
+        self.cached(cache_key, POST_STATS_CACHE_TTL, query).await
+    }
+
+    async fn fetch_post_audience(
+        db: DbRouter,
+        post_id: i64,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<PostAudience, AnalyticsError> {
+        let top_commenters =
+            Self::fetch_top_audience(&db, post_id, "comment", start_date, end_date, limit).await?;
+        let top_readers =
+            Self::fetch_top_audience(&db, post_id, "view", start_date, end_date, limit).await?;
+
+        // A reader is returning if they viewed any post before the range started
+        let query = sqlx::query(
+            r#"
+            WITH readers AS (
+                SELECT DISTINCT user_id
+                FROM global.user_interactions
+                WHERE
+                    post_id = $1 AND
+                    interaction_type = 'view' AND
+                    user_id IS NOT NULL AND
+                    created_at >= $2 AND
+                    created_at <= $3
+            ),
+            classified AS (
+                SELECT EXISTS (
+                    SELECT 1 FROM global.user_interactions earlier
+                    WHERE
+                        earlier.user_id = r.user_id AND
+                        earlier.interaction_type = 'view' AND
+                        earlier.created_at < $2
+                ) AS returning
+                FROM readers r
+            )
+            SELECT
+                COUNT(*) FILTER (WHERE NOT returning) AS new_readers,
+                COUNT(*) FILTER (WHERE returning) AS returning_readers
+            FROM classified
+            "#,
+        )
+        .bind(post_id)
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_one(db.read());
+        let row = timed("analytics.post_audience_readers", query).await?;
+
+        Ok(PostAudience {
+            post_id,
+            top_commenters,
+            top_readers,
+            new_readers: row.get("new_readers"),
+            returning_readers: row.get("returning_readers"),
+        })
+    }
+
+    // Users with the most interactions of one type with a post, scaled for sampling
+    async fn fetch_top_audience(
+        db: &DbRouter,
+        post_id: i64,
+        interaction_type: &str,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<AudienceMember>, AnalyticsError> {
+        let query = sqlx::query(
+            r#"
+            SELECT
+                ui.user_id,
+                u.username,
+                ROUND(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)))::BIGINT AS count
+            FROM global.user_interactions ui
+            LEFT JOIN global.users u ON u.id = ui.user_id
+            WHERE
+                ui.post_id = $1 AND
+                ui.interaction_type = $2 AND
+                ui.user_id IS NOT NULL AND
+                ui.created_at >= $3 AND
+                ui.created_at <= $4
+            GROUP BY ui.user_id, u.username
+            ORDER BY count DESC, u.username ASC NULLS LAST, ui.user_id
+            LIMIT $5
+            "#,
+        )
+        .bind(post_id)
+        .bind(interaction_type)
+        .bind(start_date)
+        .bind(end_date)
+        .bind(limit)
+        .fetch_all(db.read());
+        let rows = timed("analytics.post_audience_top", query).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AudienceMember {
+                user_id: row.get("user_id"),
+                username: row.get("username"),
+                count: row.get("count"),
+            })
+            .collect())
+    }
+
+    /// Get the views of a post by campaign, source and medium. Views without UTM parameters
+    /// are attributed to their referring site, or counted as direct.
+    pub async fn get_post_campaigns(
+        &self,
+        post_id: i64,
+        params: &CampaignParams,
+    ) -> Result<Vec<CampaignStats>, AnalyticsError> {
+        let (start_date, end_date) = self.get_time_range(params)?;
+        self.ensure_post_in_blog(post_id).await?;
+
+        let cache_key = format!(
+            "analytics:post_campaigns:{}:{}",
+            post_id,
+            range_cache_key(params)
+        );
+        let query = Self::fetch_post_campaigns(self.db.clone(), post_id, start_date, end_date);
+        self.cached(cache_key, POST_STATS_CACHE_TTL, query).await
+    }
+
+    async fn fetch_post_campaigns(
+        db: DbRouter,
+        post_id: i64,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+    ) -> Result<Vec<CampaignStats>, AnalyticsError> {
+        let query = sqlx::query(
+            r#"
+            SELECT
+                COALESCE(metadata->>'utm_campaign', '(none)') AS campaign,
+                COALESCE(metadata->>'utm_source', metadata->>'referrer_host', '(direct)') AS source,
+                COALESCE(
+                    metadata->>'utm_medium',
+                    CASE WHEN metadata->>'referrer_host' IS NOT NULL THEN 'referral' END,
+                    '(none)'
+                ) AS medium,
+                ROUND(SUM(1.0 / COALESCE((metadata->>'sample_rate')::FLOAT8, 1.0)))::BIGINT AS views
+            FROM global.user_interactions
+            WHERE
+                post_id = $1 AND
+                interaction_type = 'view' AND
+                created_at >= $2 AND
+                created_at <= $3
+            GROUP BY campaign, source, medium
+            ORDER BY views DESC, campaign, source, medium
+            LIMIT $4
+            "#,
+        )
+        .bind(post_id)
+        .bind(start_date)
+        .bind(end_date)
+        .bind(MAX_CAMPAIGN_ROWS)
+        .fetch_all(db.read());
+        let rows = timed("analytics.post_campaigns", query).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| CampaignStats {
+                campaign: row.get("campaign"),
+                source: row.get("source"),
+                medium: row.get("medium"),
+                views: row.get("views"),
+            })
+            .collect())
+    }
+
+    /// Get the interactions with the blog's posts by weekday and hour, for picking publish
+    /// times
+    pub async fn get_activity_heatmap(
+        &self,
+        params: &HeatmapParams,
+    ) -> Result<ActivityHeatmap, AnalyticsError> {
+        let (start_date, end_date) = self.get_time_range(params)?;
+        let timezone = match params.timezone.as_deref().map(str::trim) {
+            None | Some("") => chrono_tz::UTC,
+            Some(timezone) => timezone.parse::<chrono_tz::Tz>().map_err(|_| {
+                AnalyticsError::InvalidParameter(format!("Unknown time zone: {}", timezone))
+            })?,
+        };
+
+        let cache_key = blog_key(&format!(
+            "analytics:activity_heatmap:{}:{}",
+            range_cache_key(params),
+            timezone.name()
+        ));
+        let query = Self::fetch_activity_heatmap(
+            self.db.clone(),
+            current_blog_id(),
+            timezone.name(),
+            start_date,
+            end_date,
+        );
+        self.cached(cache_key, HEATMAP_CACHE_TTL, query).await
+    }
+
+    async fn fetch_activity_heatmap(
+        db: DbRouter,
+        blog_id: i64,
+        timezone: &'static str,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+    ) -> Result<ActivityHeatmap, AnalyticsError> {
+        let query = sqlx::query(
+            r#"
+            SELECT
+                EXTRACT(ISODOW FROM ui.created_at AT TIME ZONE $2)::INT AS weekday,
+                EXTRACT(HOUR FROM ui.created_at AT TIME ZONE $2)::INT AS hour,
+                ROUND(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)))::BIGINT AS count
+            FROM global.user_interactions ui
+            JOIN global.posts p ON p.id = ui.post_id
+            WHERE
+                p.blog_id = $1 AND
+                ui.created_at >= $3 AND
+                ui.created_at <= $4
+            GROUP BY weekday, hour
+            "#,
+        )
+        .bind(blog_id)
+        .bind(timezone)
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_all(db.read());
+        let rows = timed("analytics.activity_heatmap", query).await?;
+
+        Ok(ActivityHeatmap::from_counts(
+            timezone.to_string(),
+            rows.into_iter()
+                .map(|row| (row.get("weekday"), row.get("hour"), row.get("count"))),
+        ))
     }
 
     /// Helper to get the time range based on parameters
@@ -633,13 +1151,58 @@ impl AnalyticsService {
         }
     }
 
+    /// Get sitewide counters for public widgets.
+    ///
+    /// This is hit by every homepage load, so it is always served from cache when possible and
+    /// a cache outage only costs a fresh query instead of failing the request.
+    pub async fn get_public_stats(&self) -> Result<PublicStats, AnalyticsError> {
+        let query = Self::fetch_public_stats(self.db.clone(), current_blog_id());
+        self.cached(
+            blog_key(PUBLIC_STATS_CACHE_KEY),
+            PUBLIC_STATS_CACHE_TTL,
+            query,
+        )
+        .await
+    }
+
+    async fn fetch_public_stats(db: DbRouter, blog_id: i64) -> Result<PublicStats, AnalyticsError> {
+        let query = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM global.posts
+                    WHERE blog_id = $1 AND is_draft = false AND is_deleted = false) AS post_count,
+                (SELECT COUNT(DISTINCT user_id) FROM global.posts
+                    WHERE blog_id = $1 AND is_draft = false AND is_deleted = false) AS author_count,
+                (SELECT COALESCE(SUM(views), 0)::BIGINT FROM global.posts
+                    WHERE blog_id = $1 AND is_draft = false AND is_deleted = false) AS total_views,
+                (SELECT COUNT(*) FROM global.comments c
+                    LEFT JOIN global.users u ON c.user_id = u.id
+                    WHERE c.blog_id = $1 AND c.is_deleted = false
+                        AND c.moderation_status = 'approved'
+                        AND (u.id IS NULL OR NOT u.is_shadow_banned)) AS total_comments
+            "#,
+        )
+        .bind(blog_id)
+        .fetch_one(db.read());
+        let row = timed("analytics.public_stats", query).await?;
+
+        let stats = PublicStats {
+            post_count: row.get("post_count"),
+            author_count: row.get("author_count"),
+            total_views: row.get("total_views"),
+            total_comments: row.get("total_comments"),
+        };
+
+        Ok(stats)
+    }
+
     /// Refresh materialized views for analytics
     pub async fn refresh_materialized_views(&self) -> Result<(), AnalyticsError> {
         info!("Refreshing analytics materialized views");
 
-        sqlx::query("SELECT global.refresh_analytics_views()")
-            .execute(&self.pool)
-            .await?;
+        let query =
+            sqlx::query("SELECT global.refresh_analytics_views()").execute(self.db.primary());
+        timed("analytics.refresh_views", query).await?;
 
         info!("Analytics materialized views refreshed successfully");
         Ok(())
@@ -650,8 +1213,7 @@ impl AnalyticsService {
         if let Some(cache) = &self.redis_cache {
             // Get all keys with the prefix
             let keys = cache
-                .get_client()
-                .get_multiplexed_async_connection()
+                .connection()
                 .await
                 .map_err(AnalyticsError::CacheError)?
                 .keys::<_, Vec<String>>(format!("{}*", prefix))
@@ -661,8 +1223,7 @@ impl AnalyticsService {
             // Delete all keys
             for key in keys {
                 cache
-                    .get_client()
-                    .get_multiplexed_async_connection()
+                    .connection()
                     .await
                     .map_err(AnalyticsError::CacheError)?
                     .del::<_, ()>(&key)
@@ -681,7 +1242,7 @@ impl AnalyticsService {
         post_id: Option<i64>,
         comment_id: Option<i64>,
         duration_ms: Option<i32>,
-    ) -> Result<i64, AnalyticsError> {
+    ) -> Result<Option<i64>, AnalyticsError> {
         // Create metadata if we have duration
         let metadata = if let Some(duration) = duration_ms {
             Some(serde_json::json!({ "duration_ms": duration }))
@@ -731,3 +1292,79 @@ impl HasTimeRange for PostStatsParams {
         self.time_range.clone()
     }
 }
+
+impl HasTimeRange for AudienceParams {
+    fn start_date(&self) -> Option<String> {
+        self.start_date.clone()
+    }
+
+    fn end_date(&self) -> Option<String> {
+        self.end_date.clone()
+    }
+
+    fn time_range(&self) -> Option<String> {
+        self.time_range.clone()
+    }
+}
+
+impl HasTimeRange for CampaignParams {
+    fn start_date(&self) -> Option<String> {
+        self.start_date.clone()
+    }
+
+    fn end_date(&self) -> Option<String> {
+        self.end_date.clone()
+    }
+
+    fn time_range(&self) -> Option<String> {
+        self.time_range.clone()
+    }
+}
+
+impl HasTimeRange for HeatmapParams {
+    fn start_date(&self) -> Option<String> {
+        self.start_date.clone()
+    }
+
+    fn end_date(&self) -> Option<String> {
+        self.end_date.clone()
+    }
+
+    fn time_range(&self) -> Option<String> {
+        self.time_range.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jittered_ttl() {
+        for _ in 0..100 {
+            let ttl = jittered_ttl(300);
+            assert!((270..=330).contains(&ttl));
+        }
+        assert_eq!(jittered_ttl(1), 1);
+    }
+
+    #[test]
+    fn test_range_cache_key() {
+        let params =
+            |time_range: Option<&str>, start: Option<&str>, end: Option<&str>| EngagementParams {
+                time_range: time_range.map(str::to_string),
+                start_date: start.map(str::to_string),
+                end_date: end.map(str::to_string),
+                limit: None,
+                offset: None,
+                cursor: None,
+            };
+
+        assert_eq!(range_cache_key(&params(Some("day"), None, None)), "day");
+        assert_eq!(range_cache_key(&params(Some("decade"), None, None)), "week");
+        assert_eq!(
+            range_cache_key(&params(Some("day"), Some("2025-03-19"), Some("2025-03-26"))),
+            "2025-03-19:2025-03-26"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+// Events waiting for the sink; once full, new events are dropped instead of blocking requests
+const SINK_QUEUE_CAPACITY: usize = 10_000;
+// Events are shipped in batches of up to this size...
+const SINK_BATCH_SIZE: usize = 500;
+// ...or whatever has accumulated after this long
+const SINK_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+// A batch that takes longer than this to deliver is given up on
+const SINK_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An interaction as exported to an external analytics store
+#[derive(Debug, Clone, Serialize)]
+pub struct InteractionEvent {
+    pub interaction_id: i64,
+    pub user_id: Option<Uuid>,
+    pub interaction_type: String,
+    pub post_id: Option<i64>,
+    pub comment_id: Option<i64>,
+    /// Fraction of interactions of this type that are recorded; scale counts by its inverse
+    pub sample_rate: f64,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error("Request error: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Sink rejected batch with status {0}")]
+    Rejected(reqwest::StatusCode),
+}
+
+/// Destination for interaction events besides Postgres
+pub trait InteractionSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn send<'a>(&'a self, events: &'a [InteractionEvent]) -> BoxFuture<'a, Result<(), SinkError>>;
+}
+
+/// Inserts events into a ClickHouse table over its HTTP interface
+pub struct ClickHouseSink {
+    client: reqwest::Client,
+    url: String,
+    table: String,
+    user: Option<String>,
+    password: Option<String>,
+}
+
+impl InteractionSink for ClickHouseSink {
+    fn name(&self) -> &'static str {
+        "clickhouse"
+    }
+
+    fn send<'a>(&'a self, events: &'a [InteractionEvent]) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            let mut body = String::new();
+            for event in events {
+                body.push_str(&serde_json::to_string(event)?);
+                body.push('\n');
+            }
+
+            let mut request = self
+                .client
+                .post(&self.url)
+                .query(&[(
+                    "query",
+                    format!("INSERT INTO {} FORMAT JSONEachRow", self.table),
+                )])
+                .body(body);
+            if let Some(user) = &self.user {
+                request = request.basic_auth(user, self.password.as_ref());
+            }
+
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(SinkError::Rejected(response.status()));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Produces events to a Kafka topic through a Kafka REST proxy
+pub struct KafkaRestSink {
+    client: reqwest::Client,
+    url: String,
+    topic: String,
+}
+
+impl InteractionSink for KafkaRestSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn send<'a>(&'a self, events: &'a [InteractionEvent]) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            let records: Vec<serde_json::Value> = events
+                .iter()
+                .map(|event| {
+                    serde_json::json!({
+                        "key": event.post_id.map(|id| id.to_string()),
+                        "value": event,
+                    })
+                })
+                .collect();
+
+            let response = self
+                .client
+                .post(format!(
+                    "{}/topics/{}",
+                    self.url.trim_end_matches('/'),
+                    self.topic
+                ))
+                .header("Content-Type", "application/vnd.kafka.json.v2+json")
+                .json(&serde_json::json!({ "records": records }))
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(SinkError::Rejected(response.status()));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Which external sink to use, read from the environment:
+///
+/// - `ANALYTICS_SINK`: `clickhouse` or `kafka`; unset disables exporting
+/// - `CLICKHOUSE_URL`, `CLICKHOUSE_TABLE` (default `interactions`), `CLICKHOUSE_USER`,
+///   `CLICKHOUSE_PASSWORD`
+/// - `KAFKA_REST_URL`, `KAFKA_TOPIC` (default `interactions`)
+pub fn sink_from_env() -> Option<Arc<dyn InteractionSink>> {
+    let kind = std::env::var("ANALYTICS_SINK").ok()?;
+    let client = reqwest::Client::new();
+
+    match kind.to_lowercase().as_str() {
+        "clickhouse" => match std::env::var("CLICKHOUSE_URL") {
+            Ok(url) => Some(Arc::new(ClickHouseSink {
+                client,
+                url,
+                table: std::env::var("CLICKHOUSE_TABLE")
+                    .unwrap_or_else(|_| "interactions".to_string()),
+                user: std::env::var("CLICKHOUSE_USER").ok(),
+                password: std::env::var("CLICKHOUSE_PASSWORD").ok(),
+            })),
+            Err(_) => {
+                error!("ANALYTICS_SINK=clickhouse but CLICKHOUSE_URL is not set");
+                None
+            }
+        },
+        "kafka" => match std::env::var("KAFKA_REST_URL") {
+            Ok(url) => Some(Arc::new(KafkaRestSink {
+                client,
+                url,
+                topic: std::env::var("KAFKA_TOPIC").unwrap_or_else(|_| "interactions".to_string()),
+            })),
+            Err(_) => {
+                error!("ANALYTICS_SINK=kafka but KAFKA_REST_URL is not set");
+                None
+            }
+        },
+        other => {
+            error!("Unknown ANALYTICS_SINK: {}", other);
+            None
+        }
+    }
+}
+
+/// Hands events to a background task that batches them into the sink.
+///
+/// Exporting is best effort: a slow or unavailable sink never delays or fails the request
+/// that produced the event. Events are dropped (and logged) when the queue is full or a
+/// batch cannot be delivered.
+#[derive(Clone)]
+pub struct SinkDispatcher {
+    sender: mpsc::Sender<InteractionEvent>,
+}
+
+impl SinkDispatcher {
+    /// Shared dispatcher for the configured sink, started on first use
+    pub fn global() -> Option<SinkDispatcher> {
+        static DISPATCHER: OnceLock<Option<SinkDispatcher>> = OnceLock::new();
+
+        DISPATCHER
+            .get_or_init(|| sink_from_env().map(SinkDispatcher::start))
+            .clone()
+    }
+
+    /// Spawn the delivery task for `sink`
+    pub fn start(sink: Arc<dyn InteractionSink>) -> SinkDispatcher {
+        let (sender, receiver) = mpsc::channel(SINK_QUEUE_CAPACITY);
+        info!("Exporting interactions to {} sink", sink.name());
+        tokio::spawn(run_sink(sink, receiver));
+        SinkDispatcher { sender }
+    }
+
+    /// Queue an event without waiting
+    pub fn dispatch(&self, event: InteractionEvent) {
+        if let Err(e) = self.sender.try_send(event) {
+            warn!("Dropping interaction event for analytics sink: {}", e);
+        }
+    }
+}
+
+async fn run_sink(sink: Arc<dyn InteractionSink>, mut receiver: mpsc::Receiver<InteractionEvent>) {
+    let mut batch = Vec::with_capacity(SINK_BATCH_SIZE);
+
+    loop {
+        // Wait for the first event of a batch, then collect more until it is full or stale
+        match receiver.recv().await {
+            Some(event) => batch.push(event),
+            None => return,
+        }
+
+        let deadline = tokio::time::Instant::now() + SINK_FLUSH_INTERVAL;
+        while batch.len() < SINK_BATCH_SIZE {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(event)) => batch.push(event),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        match tokio::time::timeout(SINK_SEND_TIMEOUT, sink.send(&batch)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!(
+                "Failed to export {} interactions to {} sink: {}",
+                batch.len(),
+                sink.name(),
+                e
+            ),
+            Err(_) => error!(
+                "Timed out exporting {} interactions to {} sink",
+                batch.len(),
+                sink.name()
+            ),
+        }
+        batch.clear();
+    }
+}
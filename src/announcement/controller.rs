@@ -0,0 +1,213 @@
+use crate::announcement::model::{AnnouncementError, CreateAnnouncementRequest, ANNOUNCEMENT_JOB};
+use crate::announcement::service::AnnouncementService;
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::jobs::controller::job_error_response;
+use crate::jobs::service::JobService;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn announcement_error_response(e: AnnouncementError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        AnnouncementError::ValidationError(_) => StatusCode::BAD_REQUEST,
+        AnnouncementError::TierNotFound | AnnouncementError::NotFound => StatusCode::NOT_FOUND,
+        AnnouncementError::NotScheduled => StatusCode::CONFLICT,
+        AnnouncementError::DatabaseError(_) => {
+            error!("Announcement error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn admin_required() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can manage announcements" })),
+    )
+        .into_response()
+}
+
+/// Announce something to the users (admin only)
+///
+/// Sends a `SystemMessage` notification to every user, or only to those of `role` or current
+/// members of `tier_id`, at `scheduled_for` or right away. A background job stores the
+/// notifications in batches and pushes them to open WebSocket connections; progress is on
+/// the announcement.
+#[utoipa::path(
+    post,
+    path = "/api/admin/announcements",
+    tag = "announcements",
+    request_body = CreateAnnouncementRequest,
+    responses(
+        (status = 202, description = "Announcement scheduled", body = Announcement),
+        (status = 400, description = "Invalid content or role"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Membership tier not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_announcement(
+    Extension(user): Extension<AuthUser>,
+    Extension(jobs): Extension<Arc<JobService>>,
+    State(service): State<Arc<AnnouncementService>>,
+    Json(request): Json<CreateAnnouncementRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    let announcement = match service.create_announcement(&request, user.user_id).await {
+        Ok(announcement) => announcement,
+        Err(e) => return announcement_error_response(e),
+    };
+
+    let payload = json!({ "announcement_id": announcement.id });
+    match jobs
+        .schedule(
+            ANNOUNCEMENT_JOB,
+            payload,
+            Some(user.user_id),
+            Some(announcement.scheduled_for),
+        )
+        .await
+    {
+        Ok(_) => (StatusCode::ACCEPTED, Json(announcement)).into_response(),
+        Err(e) => job_error_response(e),
+    }
+}
+
+/// List announcements (admin only)
+///
+/// Latest scheduled first.
+#[utoipa::path(
+    get,
+    path = "/api/admin/announcements",
+    tag = "announcements",
+    params(PageParams),
+    responses(
+        (status = 200, description = "Announcements retrieved successfully", body = [Announcement]),
+        (status = 400, description = "Invalid page"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_announcements(
+    Extension(user): Extension<AuthUser>,
+    OriginalUri(uri): OriginalUri,
+    State(service): State<Arc<AnnouncementService>>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return announcement_error_response(AnnouncementError::ValidationError(e.to_string()))
+        }
+    };
+
+    match service.list_announcements(&pagination).await {
+        Ok(announcements) => (
+            StatusCode::OK,
+            pagination.headers(&uri, announcements.len()),
+            Json(announcements),
+        )
+            .into_response(),
+        Err(e) => announcement_error_response(e),
+    }
+}
+
+/// Get an announcement (admin only)
+#[utoipa::path(
+    get,
+    path = "/api/admin/announcements/{id}",
+    tag = "announcements",
+    params(
+        ("id" = i64, Path, description = "Announcement ID")
+    ),
+    responses(
+        (status = 200, description = "Announcement retrieved successfully", body = Announcement),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Announcement not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_announcement(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<AnnouncementService>>,
+    Path(id): Path<i64>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    match service.get_announcement(id).await {
+        Ok(announcement) => (StatusCode::OK, Json(announcement)).into_response(),
+        Err(e) => announcement_error_response(e),
+    }
+}
+
+/// Cancel a scheduled announcement (admin only)
+///
+/// Only announcements still waiting for their time can be cancelled.
+#[utoipa::path(
+    delete,
+    path = "/api/admin/announcements/{id}",
+    tag = "announcements",
+    params(
+        ("id" = i64, Path, description = "Announcement ID")
+    ),
+    responses(
+        (status = 204, description = "Announcement cancelled"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Announcement not found"),
+        (status = 409, description = "Announcement already sending or sent"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn cancel_announcement(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<AnnouncementService>>,
+    Path(id): Path<i64>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    match service.cancel_announcement(id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => announcement_error_response(e),
+    }
+}
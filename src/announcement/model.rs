@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Kind of the job that notifies the recipients of an announcement
+pub const ANNOUNCEMENT_JOB: &str = "announcement";
+/// Longest accepted announcement, in characters
+pub const MAX_CONTENT_LENGTH: usize = 1000;
+
+/// Request to announce something to the users
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateAnnouncementRequest {
+    #[schema(example = "The site will be read-only on Sunday from 02:00 to 03:00 UTC.")]
+    pub content: String,
+    /// Only notify users of this role
+    #[schema(example = "author")]
+    pub role: Option<String>,
+    /// Only notify current members of this membership tier of the blog
+    #[schema(example = 2)]
+    pub tier_id: Option<i64>,
+    /// When to send it; right away by default
+    pub scheduled_for: Option<DateTime<Utc>>,
+}
+
+/// Progress of an announcement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnouncementStatus {
+    /// Waiting for its time; it can still be cancelled
+    Scheduled,
+    Sending,
+    /// Every recipient was notified
+    Sent,
+}
+
+impl AnnouncementStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "scheduled" => Some(AnnouncementStatus::Scheduled),
+            "sending" => Some(AnnouncementStatus::Sending),
+            "sent" => Some(AnnouncementStatus::Sent),
+            _ => None,
+        }
+    }
+}
+
+/// A system message sent to the users as a notification
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Announcement {
+    #[schema(example = 4)]
+    pub id: i64,
+    pub content: String,
+    /// Role its recipients have, if limited to one
+    #[schema(example = "author")]
+    pub role: Option<String>,
+    /// Membership tier its recipients have, if limited to one
+    pub tier_id: Option<i64>,
+    pub status: AnnouncementStatus,
+    pub scheduled_for: DateTime<Utc>,
+    /// Users notified so far
+    #[schema(example = 1250)]
+    pub recipients: i64,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Possible announcement errors
+#[derive(Debug, thiserror::Error)]
+pub enum AnnouncementError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Membership tier not found")]
+    TierNotFound,
+
+    #[error("Announcement not found")]
+    NotFound,
+
+    #[error("Only scheduled announcements can be cancelled")]
+    NotScheduled,
+}
@@ -0,0 +1,344 @@
+use crate::announcement::model::{
+    Announcement, AnnouncementError, AnnouncementStatus, CreateAnnouncementRequest,
+    ANNOUNCEMENT_JOB, MAX_CONTENT_LENGTH,
+};
+use crate::auth::jwt::Role;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::JobHandler;
+use crate::notification::model::{NotificationPayload, NotificationType};
+use crate::notification::service::NotificationService;
+use crate::pagination::Pagination;
+use crate::tenant::middleware::current_blog_id;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// Recipients notified per transaction
+const ANNOUNCEMENT_BATCH_SIZE: i64 = 500;
+// Columns of an announcement, with the number of recipients notified so far
+const ANNOUNCEMENT_COLUMNS: &str = "id, content, role, tier_id, status, scheduled_for, \
+    recipients, created_by, created_at, finished_at";
+
+fn announcement_from_row(row: &PgRow) -> Announcement {
+    Announcement {
+        id: row.get("id"),
+        content: row.get("content"),
+        role: row.get("role"),
+        tier_id: row.get("tier_id"),
+        status: AnnouncementStatus::parse(row.get("status"))
+            .unwrap_or(AnnouncementStatus::Scheduled),
+        scheduled_for: row.get("scheduled_for"),
+        recipients: row.get("recipients"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        finished_at: row.get("finished_at"),
+    }
+}
+
+// Trimmed content and the role in its stored form
+fn validate(
+    request: &CreateAnnouncementRequest,
+) -> Result<(String, Option<String>), AnnouncementError> {
+    let content = request.content.trim();
+    if content.is_empty() || content.chars().count() > MAX_CONTENT_LENGTH {
+        return Err(AnnouncementError::ValidationError(format!(
+            "Content must be between 1 and {} characters",
+            MAX_CONTENT_LENGTH
+        )));
+    }
+
+    let role = request
+        .role
+        .as_deref()
+        .map(|role| Role::from_str(role).map(|role| role.as_str().to_string()))
+        .transpose()
+        .map_err(AnnouncementError::ValidationError)?;
+
+    Ok((content.to_string(), role))
+}
+
+/// System announcements of each blog, sent to every user or to those of one role or
+/// membership tier as `SystemMessage` notifications.
+///
+/// A background job, scheduled for the announcement's time, stores the notifications in
+/// batches, each with the progress made in one transaction, and pushes them to the
+/// recipients' open WebSocket connections. A job picked up again after its instance stopped
+/// carries on after the last recipient notified. Announcements can be cancelled until their
+/// job starts.
+pub struct AnnouncementService {
+    pool: PgPool,
+    notifications: Arc<NotificationService>,
+}
+
+impl AnnouncementService {
+    pub fn new(pool: PgPool, notifications: Arc<NotificationService>) -> Self {
+        Self {
+            pool,
+            notifications,
+        }
+    }
+
+    /// Store an announcement to send at its time, which is now if not given or past. The
+    /// notifications go out from the job scheduled for it.
+    pub async fn create_announcement(
+        &self,
+        request: &CreateAnnouncementRequest,
+        created_by: Uuid,
+    ) -> Result<Announcement, AnnouncementError> {
+        let (content, role) = validate(request)?;
+
+        if let Some(tier_id) = request.tier_id {
+            let exists = sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS(SELECT 1 FROM global.membership_tiers WHERE id = $1)",
+            )
+            .bind(tier_id)
+            .fetch_one(&self.pool)
+            .await?;
+            if !exists {
+                return Err(AnnouncementError::TierNotFound);
+            }
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO global.announcements (
+                blog_id, content, role, tier_id, scheduled_for, created_by
+            )
+            VALUES ($1, $2, $3, $4, GREATEST(COALESCE($5, NOW()), NOW()), $6)
+            RETURNING {}
+            "#,
+            ANNOUNCEMENT_COLUMNS
+        ))
+        .bind(current_blog_id())
+        .bind(&content)
+        .bind(&role)
+        .bind(request.tier_id)
+        .bind(request.scheduled_for)
+        .bind(created_by)
+        .fetch_one(&self.pool)
+        .await?;
+        let announcement = announcement_from_row(&row);
+
+        info!(
+            "Created announcement {} for {}",
+            announcement.id, announcement.scheduled_for
+        );
+        Ok(announcement)
+    }
+
+    /// An announcement of the blog with its progress
+    pub async fn get_announcement(&self, id: i64) -> Result<Announcement, AnnouncementError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM global.announcements WHERE id = $1 AND blog_id = $2",
+            ANNOUNCEMENT_COLUMNS
+        ))
+        .bind(id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AnnouncementError::NotFound)?;
+
+        Ok(announcement_from_row(&row))
+    }
+
+    /// Announcements of the blog, latest scheduled first
+    pub async fn list_announcements(
+        &self,
+        pagination: &Pagination,
+    ) -> Result<Vec<Announcement>, AnnouncementError> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {} FROM global.announcements
+            WHERE blog_id = $1
+            ORDER BY scheduled_for DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            ANNOUNCEMENT_COLUMNS
+        ))
+        .bind(current_blog_id())
+        .bind(pagination.limit)
+        .bind(pagination.offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(announcement_from_row).collect())
+    }
+
+    /// Cancel an announcement whose job has not started. Its job finds it gone and does
+    /// nothing.
+    pub async fn cancel_announcement(&self, id: i64) -> Result<(), AnnouncementError> {
+        let deleted = sqlx::query(
+            r#"
+            DELETE FROM global.announcements
+            WHERE id = $1 AND blog_id = $2 AND status = 'scheduled'
+            "#,
+        )
+        .bind(id)
+        .bind(current_blog_id())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if deleted == 0 {
+            // Tell a missing announcement from one already going out
+            self.get_announcement(id).await?;
+            return Err(AnnouncementError::NotScheduled);
+        }
+
+        info!("Cancelled announcement {}", id);
+        Ok(())
+    }
+
+    /// Notify the recipients of an announcement of the current blog not notified yet.
+    /// Returns how many were notified, or `None` if it was cancelled or already sent.
+    pub async fn send_announcement(&self, id: i64) -> Result<Option<i64>, JobError> {
+        let row = sqlx::query(
+            r#"
+            UPDATE global.announcements SET status = 'sending'
+            WHERE id = $1 AND blog_id = $2 AND status <> 'sent'
+            RETURNING content, role, tier_id, last_recipient_id, created_by
+            "#,
+        )
+        .bind(id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let role: Option<String> = row.get("role");
+        let tier_id: Option<i64> = row.get("tier_id");
+        let mut last_recipient_id: Option<Uuid> = row.get("last_recipient_id");
+        let payload = NotificationPayload {
+            recipient_id: Uuid::nil(),
+            notification_type: NotificationType::SystemMessage,
+            object_id: id,
+            related_object_id: None,
+            actor_id: row
+                .get::<Option<Uuid>, _>("created_by")
+                .unwrap_or_else(Uuid::nil),
+            content: row.get("content"),
+            context: Default::default(),
+        };
+
+        let mut notified = 0;
+        loop {
+            let mut tx = self.pool.begin().await?;
+
+            let recipient_ids = sqlx::query_scalar::<_, Uuid>(
+                r#"
+                SELECT u.id FROM global.users u
+                WHERE ($1::VARCHAR IS NULL OR u.role = $1)
+                    AND ($2::BIGINT IS NULL OR EXISTS (
+                        SELECT 1 FROM global.user_memberships m
+                        WHERE m.user_id = u.id AND m.blog_id = $3 AND m.tier_id = $2
+                            AND (m.expires_at IS NULL OR m.expires_at > NOW())
+                    ))
+                    AND ($4::UUID IS NULL OR u.id > $4)
+                ORDER BY u.id
+                LIMIT $5
+                "#,
+            )
+            .bind(&role)
+            .bind(tier_id)
+            .bind(current_blog_id())
+            .bind(last_recipient_id)
+            .bind(ANNOUNCEMENT_BATCH_SIZE)
+            .fetch_all(&mut *tx)
+            .await?;
+            let Some(&last) = recipient_ids.last() else {
+                break;
+            };
+
+            let stored = self
+                .notifications
+                .store_for_recipients(&mut tx, &recipient_ids, &payload)
+                .await
+                .map_err(|e| JobError::InternalError(e.to_string()))?;
+            sqlx::query(
+                r#"
+                UPDATE global.announcements
+                SET last_recipient_id = $2, recipients = recipients + $3
+                WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .bind(last)
+            .bind(stored.len() as i64)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            // The notifications are stored, so a failed push only delays them
+            if let Err(e) = self.notifications.push_stored(&stored).await {
+                warn!("Failed to push announcement {}: {}", id, e);
+            }
+            notified += stored.len() as i64;
+            last_recipient_id = Some(last);
+
+            if (recipient_ids.len() as i64) < ANNOUNCEMENT_BATCH_SIZE {
+                break;
+            }
+        }
+
+        sqlx::query(
+            "UPDATE global.announcements SET status = 'sent', finished_at = NOW() WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        info!("Sent announcement {} to {} users", id, notified);
+        Ok(Some(notified))
+    }
+
+    async fn run_job(&self, job: &Job) -> Result<Value, JobError> {
+        let announcement_id = job
+            .payload
+            .get("announcement_id")
+            .and_then(Value::as_i64)
+            .ok_or_else(|| JobError::Failed("Missing announcement_id".to_string()))?;
+        let notified = self.send_announcement(announcement_id).await?;
+        Ok(json!({ "announcement_id": announcement_id, "notified": notified }))
+    }
+}
+
+impl JobHandler for AnnouncementService {
+    fn kind(&self) -> &'static str {
+        ANNOUNCEMENT_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(content: &str, role: Option<&str>) -> CreateAnnouncementRequest {
+        CreateAnnouncementRequest {
+            content: content.to_string(),
+            role: role.map(str::to_string),
+            tier_id: None,
+            scheduled_for: None,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let (content, role) = validate(&request("  Maintenance tonight ", Some("Author"))).unwrap();
+        assert_eq!(content, "Maintenance tonight");
+        assert_eq!(role.as_deref(), Some("author"));
+
+        assert!(validate(&request("Hello", None)).unwrap().1.is_none());
+        assert!(validate(&request("   ", None)).is_err());
+        assert!(validate(&request(&"a".repeat(MAX_CONTENT_LENGTH + 1), None)).is_err());
+        assert!(validate(&request("Hello", Some("superuser"))).is_err());
+    }
+}
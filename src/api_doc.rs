@@ -1,4 +1,7 @@
+use crate::routes::versioning::{API_V1_PREFIX, LEGACY_API_PREFIX};
+use std::path::PathBuf;
 use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::openapi::{Deprecated, Paths};
 use utoipa::{Modify, OpenApi};
 
 /// Security scheme configuration for OpenAPI
@@ -29,67 +32,361 @@ impl Modify for SecurityAddon {
         // Add health check endpoints
         crate::routes::health::health_check,
         crate::routes::health::protected_health_check,
+        crate::routes::health::readiness_check,
         // Add authentication endpoints 
         crate::auth::controller::login,
         crate::auth::controller::register,
+        crate::auth::controller::change_password,
         // Add post endpoints
         crate::post::controller::create_post,
         crate::post::controller::get_post,
+        crate::post::controller::get_post_meta,
+        crate::post::oembed::get_oembed,
+        crate::media::controller::list_attachments,
+        crate::media::controller::upload_attachment,
+        crate::media::controller::download_attachment,
+        crate::media::controller::delete_attachment,
+        crate::media::controller::upload_cover_image,
+        crate::media::controller::get_cover_image_file,
+        crate::media::controller::upload_avatar,
+        crate::media::controller::delete_avatar,
+        crate::media::controller::get_avatar_file,
+        crate::block::controller::block_user,
+        crate::block::controller::unblock_user,
+        crate::like::controller::like_post,
+        crate::like::controller::unlike_post,
+        crate::like::controller::get_like_flags,
+        crate::consent::controller::get_consent,
+        crate::consent::controller::set_consent,
+        crate::quota::controller::get_quota,
+        crate::storage::controller::get_my_storage,
+        crate::storage::controller::get_storage_overview,
         crate::post::controller::update_post,
+        crate::post::controller::duplicate_post,
+        crate::tag::controller::retag,
         crate::post::controller::delete_post,
+        crate::post::controller::list_posts,
         crate::post::controller::get_popular_posts,
+        crate::post::controller::get_archive,
+        crate::post::controller::get_archive_posts,
+        crate::post::controller::get_my_posts,
+        crate::post::controller::acquire_edit_lock,
+        crate::post::controller::release_edit_lock,
+        crate::post::controller::get_duplicate_flags,
+        crate::post::export::export_post,
+        crate::post::export::start_site_export,
+        crate::post::export::download_site_export,
+        crate::jobs::controller::get_job,
+        crate::retention::controller::get_policy,
+        crate::retention::controller::run_cleanup,
+        crate::cache::controller::warm_cache,
+        crate::seed::controller::generate_seed_data,
+        crate::import::controller::import_posts,
+        crate::ghost::controller::create_key,
+        crate::ghost::controller::list_keys,
+        crate::ghost::controller::delete_key,
         // Add comment endpoints
         crate::comment::controller::create_comment,
+        crate::comment::controller::create_guest_comment,
         crate::comment::controller::get_post_comments,
+        crate::comment::controller::get_comment,
         crate::comment::controller::delete_comment,
+        crate::comment::controller::subscribe_to_thread,
+        crate::comment::controller::unsubscribe_from_thread,
+        crate::comment::controller::pin_comment,
+        crate::comment::controller::unpin_comment,
+        crate::comment::controller::react_to_comment,
+        crate::comment::controller::remove_comment_reaction,
+        crate::comment::controller::report_comment,
+        crate::comment::controller::lock_comments,
+        crate::comment::controller::unlock_comments,
+        crate::comment::controller::get_pending_comments,
+        crate::comment::controller::approve_comment,
+        crate::comment::controller::reject_comment,
+        crate::comment::controller::set_shadow_ban,
+        // Add notification endpoints
+        crate::notification::controller::list_notifications,
+        crate::notification::controller::get_unread_count,
+        crate::notification::controller::mark_as_read,
+        crate::notification::controller::mark_all_read,
+        crate::notification::controller::get_quiet_hours,
+        crate::notification::controller::set_quiet_hours,
         // Add analytics endpoints
+        crate::analytics::controller::record_view,
         crate::analytics::controller::get_user_engagement,
         crate::analytics::controller::get_user_engagement_by_id,
         crate::analytics::controller::get_post_stats,
         crate::analytics::controller::get_post_stats_by_id,
         crate::analytics::controller::get_post_stats_by_time,
+        crate::analytics::controller::get_post_audience,
+        crate::analytics::controller::get_activity_heatmap,
+        crate::analytics::controller::get_post_campaigns,
+        crate::analytics::controller::get_public_stats,
         crate::analytics::controller::refresh_analytics_views,
+        // Add membership endpoints
+        crate::membership::controller::list_tiers,
+        crate::membership::controller::create_tier,
+        crate::membership::controller::get_user_membership,
+        crate::membership::controller::set_user_membership,
+        // Add activity feed endpoints
+        crate::activity::controller::get_user_activity,
+        crate::activity::controller::get_privacy_settings,
+        crate::activity::controller::set_privacy_settings,
+        crate::reputation::controller::get_user_profile,
+        // Add leaderboard endpoints
+        crate::leaderboard::controller::get_author_leaderboard,
+        crate::leaderboard::controller::get_commenter_leaderboard,
+        // Add newsletter endpoints
+        crate::newsletter::controller::subscribe,
+        crate::newsletter::controller::confirm,
+        crate::newsletter::controller::unsubscribe,
+        crate::newsletter::controller::create_campaign,
+        crate::newsletter::controller::get_campaign,
+        crate::newsletter::controller::get_deliveries,
+        // Add title test endpoints
+        crate::title_test::controller::start_title_test,
+        crate::title_test::controller::stop_title_test,
+        crate::title_test::controller::record_title_impressions,
+        crate::title_test::controller::record_title_click,
+        crate::title_test::controller::get_title_test,
+        // Add reading list endpoints
+        crate::reading_list::controller::list_reading_lists,
+        crate::reading_list::controller::create_reading_list,
+        crate::reading_list::controller::get_my_reading_list,
+        crate::reading_list::controller::update_reading_list,
+        crate::reading_list::controller::delete_reading_list,
+        crate::reading_list::controller::add_reading_list_post,
+        crate::reading_list::controller::reorder_reading_list,
+        crate::reading_list::controller::remove_reading_list_post,
+        crate::reading_list::controller::get_reading_list,
+        // Add tip endpoints
+        crate::tip::controller::create_tip,
+        crate::tip::controller::tip_webhook,
+        crate::tip::controller::get_earnings,
+        // Add announcement endpoints
+        crate::announcement::controller::create_announcement,
+        crate::announcement::controller::list_announcements,
+        crate::announcement::controller::get_announcement,
+        crate::announcement::controller::cancel_announcement,
+        // Add invite endpoints
+        crate::invite::controller::create_invites,
+        crate::invite::controller::list_invites,
+        crate::invite::controller::get_invite_usage,
+        crate::invite::controller::delete_invite,
+        // Add feedback endpoints
+        crate::feedback::controller::create_feedback,
+        crate::feedback::controller::list_feedback,
+        crate::feedback::controller::update_feedback,
+        // Add blog provisioning endpoints
+        crate::tenant::controller::list_blogs,
+        crate::tenant::controller::create_blog,
+        crate::tenant::controller::update_blog,
+        // Add blog settings endpoints
+        crate::settings::controller::get_public_settings,
+        crate::settings::controller::get_settings,
+        crate::settings::controller::update_settings,
+        // Add feature flag endpoints
+        crate::feature_flags::controller::get_evaluated_flags,
+        crate::feature_flags::controller::list_flags,
+        crate::feature_flags::controller::upsert_flag,
+        crate::feature_flags::controller::delete_flag,
+        crate::link_preview::controller::get_link_preview,
         // Add recommendation endpoints
         crate::recommendations::controller::get_recommended_posts,
         crate::recommendations::controller::get_similar_posts,
-        crate::recommendations::controller::refresh_recommendation_model
+        crate::recommendations::controller::get_digest_settings,
+        crate::recommendations::controller::set_digest_settings,
+        crate::recommendations::controller::refresh_recommendation_model,
+        crate::recommendations::controller::preview_recommendations
     ),
     components(
         schemas(
             // Auth schemas
             crate::auth::controller::RegisterRequest,
             crate::auth::controller::LoginRequest,
+            crate::auth::controller::ChangePasswordRequest,
             crate::auth::controller::AuthResponse,
-            crate::auth::controller::ErrorResponse,
+            crate::auth::controller::AuthErrorResponse,
             // Health schemas
             crate::routes::health::HealthResponse,
+            crate::routes::health::ReadinessResponse,
             // Post schemas
             crate::post::model::CreatePostRequest,
             crate::post::model::UpdatePostRequest,
             crate::post::model::PostResponse,
+            crate::post::model::PostMeta,
+            crate::post::oembed::OEmbedResponse,
+            crate::media::model::Attachment,
+            crate::media::model::CoverImage,
+            crate::media::model::ImageVariant,
+            crate::media::model::AvatarResponse,
+            crate::block::model::BlockResponse,
+            crate::like::model::LikeResponse,
+            crate::like::model::LikeFlag,
+            crate::like::model::LikeFlagReason,
+            crate::consent::model::ConsentSettings,
+            crate::quota::model::QuotaKind,
+            crate::quota::model::QuotaUsage,
+            crate::quota::model::QuotaResponse,
+            crate::quota::model::QuotaExceededResponse,
+            crate::storage::model::StorageScope,
+            crate::storage::model::StorageUsage,
+            crate::storage::model::UserStorage,
+            crate::storage::model::StorageOverview,
+            crate::storage::model::StorageQuotaExceeded,
+            crate::storage::model::StorageQuotaExceededResponse,
+            crate::rate_limit::RateLimitStatus,
+            crate::rate_limit::RateLimitExceededResponse,
             crate::post::model::PopularPostsResponse,
+            crate::post::model::ArchiveMonth,
+            crate::post::model::AuthorPostSummary,
+            crate::post::model::PostStatusFilter,
+            crate::post::model::AuthorPostSort,
             crate::post::model::UserBrief,
             crate::post::model::Tag,
+            crate::post::model::EditLock,
+            crate::post::model::DuplicateWarning,
+            crate::post::model::DuplicatePostFlag,
             crate::post::controller::ErrorResponse,
+            // Tag maintenance schemas
+            crate::tag::model::RetagRequest,
+            crate::tag::model::RetagResponse,
             // Comment schemas
             crate::comment::model::CreateCommentRequest,
             crate::comment::model::CommentResponse,
             crate::comment::model::CommentsListResponse,
+            crate::comment::model::CommentPermalinkResponse,
             crate::comment::model::CommentAuthor,
+            crate::comment::model::CreateGuestCommentRequest,
+            crate::comment::model::GuestAuthor,
+            crate::comment::model::PendingCommentResponse,
             crate::comment::model::CommentErrorResponse,
+            crate::comment::model::ThreadSubscriptionResponse,
+            crate::comment::model::CommentPinResponse,
+            crate::comment::model::CommentReactionRequest,
+            crate::comment::model::CommentReportRequest,
+            crate::comment::model::CommentScoreResponse,
+            crate::comment::model::CommentLockResponse,
+            crate::comment::model::ShadowBanRequest,
+            crate::comment::model::ShadowBanResponse,
+            // Notification schemas
+            crate::notification::model::Notification,
+            crate::notification::model::NotificationType,
+            crate::notification::model::NotificationContext,
+            crate::notification::model::UnreadCountResponse,
+            crate::notification::model::QuietHoursSettings,
             // Analytics schemas
             crate::analytics::model::UserEngagement,
             crate::analytics::model::PostStats,
             crate::analytics::model::EngagementParams,
             crate::analytics::model::PostStatsParams,
             crate::analytics::model::InteractionType,
+            crate::analytics::model::PublicStats,
+            crate::analytics::model::PostAudience,
+            crate::analytics::model::AudienceMember,
+            crate::analytics::model::AudienceParams,
+            crate::analytics::model::ActivityHeatmap,
+            crate::analytics::model::HeatmapParams,
+            crate::analytics::model::ViewEvent,
+            crate::analytics::model::CampaignStats,
+            crate::analytics::model::CampaignParams,
+            // Membership schemas
+            crate::membership::model::MembershipTier,
+            crate::membership::model::CreateTierRequest,
+            crate::membership::model::SetUserTierRequest,
+            crate::membership::model::UserMembership,
+            // Activity feed schemas
+            crate::activity::model::ActivityType,
+            crate::activity::model::ActivityItem,
+            crate::activity::model::ActivityPrivacySettings,
+            crate::reputation::model::Reputation,
+            crate::reputation::model::UserProfile,
+            // Leaderboard schemas
+            crate::leaderboard::model::LeaderboardPeriod,
+            crate::leaderboard::model::LeaderboardEntry,
+            crate::leaderboard::model::Leaderboard,
+            // Newsletter schemas
+            crate::newsletter::model::SubscriberStatus,
+            crate::newsletter::model::SubscribeRequest,
+            crate::newsletter::model::SubscriptionResponse,
+            crate::newsletter::model::CreateCampaignRequest,
+            crate::newsletter::model::CampaignStatus,
+            crate::newsletter::model::Campaign,
+            crate::newsletter::model::DeliveryStatus,
+            crate::newsletter::model::Delivery,
+            // Title test schemas
+            crate::title_test::model::StartTitleTestRequest,
+            crate::title_test::model::TitleTestStatus,
+            crate::title_test::model::TitleTest,
+            crate::title_test::model::TitleImpressionsRequest,
+            crate::title_test::model::AssignedTitle,
+            crate::title_test::model::TitleClickRequest,
+            crate::title_test::model::VariantStats,
+            crate::title_test::model::TitleTestReport,
+            // Reading list schemas
+            crate::reading_list::model::CreateReadingListRequest,
+            crate::reading_list::model::UpdateReadingListRequest,
+            crate::reading_list::model::AddListPostRequest,
+            crate::reading_list::model::ReorderListRequest,
+            crate::reading_list::model::ReadingList,
+            crate::reading_list::model::ReadingListPost,
+            crate::reading_list::model::ReadingListDetail,
+            // Tip schemas
+            crate::tip::model::TipStatus,
+            crate::tip::model::CreateTipRequest,
+            crate::tip::model::TipCheckout,
+            crate::tip::model::Tip,
+            crate::tip::model::CurrencyEarnings,
+            crate::tip::model::PostEarnings,
+            crate::tip::model::EarningsSummary,
+            // Feedback schemas
+            crate::announcement::model::CreateAnnouncementRequest,
+            crate::announcement::model::AnnouncementStatus,
+            crate::announcement::model::Announcement,
+            crate::invite::model::CreateInvitesRequest,
+            crate::invite::model::InviteStatus,
+            crate::invite::model::Invite,
+            crate::invite::model::InviteUsage,
+            crate::feedback::model::FeedbackStatus,
+            crate::feedback::model::CreateFeedbackRequest,
+            crate::feedback::model::Feedback,
+            crate::feedback::model::UpdateFeedbackRequest,
+            // Blog schemas
+            crate::tenant::model::Blog,
+            crate::tenant::model::CreateBlogRequest,
+            crate::tenant::model::UpdateBlogRequest,
+            // Settings schemas
+            crate::settings::model::SettingsResponse,
+            crate::settings::model::UpdateSettingsRequest,
+            // Feature flag schemas
+            crate::feature_flags::model::FeatureFlag,
+            crate::feature_flags::model::UpsertFeatureFlagRequest,
+            crate::feature_flags::model::EvaluatedFlagsResponse,
+            crate::link_preview::model::LinkPreview,
+            crate::import::model::ImportReport,
+            crate::import::model::ImportItemReport,
+            crate::import::model::ImportItemStatus,
+            crate::ghost::model::ContentApiKey,
+            crate::ghost::model::CreateContentApiKeyRequest,
             // Recommendation schemas
             crate::recommendations::model::PostRecommendation,
+            crate::recommendations::model::DigestSettings,
             crate::recommendations::model::RecommendationParams,
             crate::recommendations::model::RecommendationResponse,
-            // External type schemas
-            crate::schema_ext::DateTimeWrapper,
-            crate::schema_ext::UuidWrapper
+            crate::recommendations::model::RecommendationPreview,
+            crate::recommendations::model::ScoredCandidate,
+            // Data retention schemas
+            crate::retention::model::DataClass,
+            crate::retention::model::RetentionPolicy,
+            crate::retention::model::PurgeResult,
+            crate::retention::model::RetentionReport,
+            crate::retention::model::RunRetentionRequest,
+            // Seed data schemas
+            crate::seed::model::SeedRequest,
+            crate::seed::model::SeedReport,
+            // Background job schemas
+            crate::jobs::model::Job,
+            crate::jobs::model::JobStatus
         )
     ),
     tags(
@@ -97,7 +394,29 @@ impl Modify for SecurityAddon {
         (name = "health", description = "Health check endpoints"),
         (name = "posts", description = "Blog post management endpoints"),
         (name = "comments", description = "Comment management endpoints"),
+        (name = "notifications", description = "Notification endpoints"),
         (name = "analytics", description = "Analytics and statistics endpoints"),
+        (name = "membership", description = "Membership tier endpoints"),
+        (name = "activity", description = "User activity feed endpoints"),
+        (name = "users", description = "User profile endpoints"),
+        (name = "leaderboards", description = "Top author and commenter endpoints"),
+        (name = "newsletter", description = "Newsletter subscription and campaign endpoints"),
+        (name = "reading-lists", description = "Reading list endpoints"),
+        (name = "tips", description = "Author tip and earnings endpoints"),
+        (name = "feedback", description = "Reader feedback endpoints"),
+        (name = "announcements", description = "System announcement endpoints"),
+        (name = "invites", description = "Invite code endpoints"),
+        (name = "storage", description = "Storage quota endpoints"),
+        (name = "blogs", description = "Blog provisioning endpoints"),
+        (name = "settings", description = "Blog settings endpoints"),
+        (name = "feature-flags", description = "Feature flag endpoints"),
+        (name = "link-previews", description = "Link preview endpoints"),
+        (name = "jobs", description = "Background job endpoints"),
+        (name = "retention", description = "Data retention endpoints"),
+        (name = "cache", description = "Cache management endpoints"),
+        (name = "seed", description = "Seed data endpoints"),
+        (name = "import", description = "Post import endpoints"),
+        (name = "ghost", description = "Ghost-compatible content API keys"),
         (name = "recommendations", description = "Content recommendation endpoints")
     ),
     security(
@@ -106,3 +425,411 @@ impl Modify for SecurityAddon {
     modifiers(&SecurityAddon)
 )]
 pub struct ApiDoc;
+
+/// OpenAPI document for the `/api/v1` surface
+///
+/// Handlers are annotated with their unversioned `/api/...` paths, so the
+/// paths are re-rooted under the version prefix here.
+pub fn v1_openapi() -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    openapi.info.version = "1.0.0".to_string();
+
+    let mut paths = Paths::new();
+    for (path, item) in openapi.paths.paths {
+        let versioned = match path.strip_prefix(LEGACY_API_PREFIX) {
+            Some(rest) => format!("{}{}", API_V1_PREFIX, rest),
+            None => path,
+        };
+        paths.paths.insert(versioned, item);
+    }
+    openapi.paths = paths;
+
+    openapi
+}
+
+/// OpenAPI document for the deprecated unversioned `/api` surface
+pub fn legacy_openapi() -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    openapi.info.title = format!("{} (deprecated, use {})", openapi.info.title, API_V1_PREFIX);
+
+    for item in openapi.paths.paths.values_mut() {
+        for operation in item.operations.values_mut() {
+            operation.deprecated = Some(Deprecated::True);
+        }
+    }
+
+    openapi
+}
+
+/// Format an OpenAPI document is exported in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecFormat {
+    Json,
+    Yaml,
+}
+
+/// What the `openapi` command exports, and where
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub format: SpecFormat,
+    /// Export the deprecated `/api` document instead of `/api/v1`
+    pub legacy: bool,
+    /// Written to stdout when absent
+    pub output: Option<PathBuf>,
+}
+
+/// Errors raised while exporting an OpenAPI document
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(String),
+
+    #[error("Failed to serialize the document: {0}")]
+    Serialize(String),
+
+    #[error("Failed to write the document: {0}")]
+    Write(#[from] std::io::Error),
+}
+
+impl ExportOptions {
+    /// Parse the arguments of the `openapi` command:
+    /// `[--format json|yaml] [--legacy] [--output PATH]`.
+    ///
+    /// The format defaults to YAML for `.yaml` and `.yml` outputs and to JSON otherwise.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, ExportError> {
+        let mut format = None;
+        let mut legacy = false;
+        let mut output: Option<PathBuf> = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    format = match args.next().as_deref() {
+                        Some("json") => Some(SpecFormat::Json),
+                        Some("yaml") => Some(SpecFormat::Yaml),
+                        _ => {
+                            return Err(ExportError::InvalidArguments(
+                                "--format must be json or yaml".to_string(),
+                            ))
+                        }
+                    }
+                }
+                "--legacy" => legacy = true,
+                "--output" | "-o" => match args.next() {
+                    Some(path) => output = Some(path.into()),
+                    None => {
+                        return Err(ExportError::InvalidArguments(
+                            "--output needs a path".to_string(),
+                        ))
+                    }
+                },
+                other => {
+                    return Err(ExportError::InvalidArguments(format!(
+                        "Unknown argument {}; usage: openapi [--format json|yaml] [--legacy] \
+                         [--output PATH]",
+                        other
+                    )))
+                }
+            }
+        }
+
+        let format = format.unwrap_or_else(|| {
+            let yaml = output
+                .as_ref()
+                .and_then(|path| path.extension())
+                .is_some_and(|ext| ext == "yaml" || ext == "yml");
+            if yaml {
+                SpecFormat::Yaml
+            } else {
+                SpecFormat::Json
+            }
+        });
+
+        Ok(Self {
+            format,
+            legacy,
+            output,
+        })
+    }
+}
+
+/// Render the OpenAPI document selected by `options`
+pub fn render(options: &ExportOptions) -> Result<String, ExportError> {
+    let openapi = if options.legacy {
+        legacy_openapi()
+    } else {
+        v1_openapi()
+    };
+
+    match options.format {
+        SpecFormat::Json => openapi
+            .to_pretty_json()
+            .map_err(|e| ExportError::Serialize(e.to_string())),
+        SpecFormat::Yaml => openapi
+            .to_yaml()
+            .map_err(|e| ExportError::Serialize(e.to_string())),
+    }
+}
+
+/// Write the OpenAPI document selected by `options` to its output, or stdout, without
+/// starting the server
+pub fn export(options: &ExportOptions) -> Result<(), ExportError> {
+    let mut document = render(options)?;
+    if !document.ends_with('\n') {
+        document.push('\n');
+    }
+
+    match &options.output {
+        Some(path) => std::fs::write(path, document)?,
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(document.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_export_options_from_args() {
+        let options = ExportOptions::from_args(args(&[])).unwrap();
+        assert_eq!(options.format, SpecFormat::Json);
+        assert!(!options.legacy && options.output.is_none());
+
+        let options = ExportOptions::from_args(args(&["-o", "openapi.yml"])).unwrap();
+        assert_eq!(options.format, SpecFormat::Yaml);
+
+        let options =
+            ExportOptions::from_args(args(&["--legacy", "--format", "json", "-o", "spec.yaml"]))
+                .unwrap();
+        assert_eq!(options.format, SpecFormat::Json);
+        assert!(options.legacy);
+
+        assert!(ExportOptions::from_args(args(&["--format", "xml"])).is_err());
+        assert!(ExportOptions::from_args(args(&["--output"])).is_err());
+        assert!(ExportOptions::from_args(args(&["--verbose"])).is_err());
+    }
+
+    // Every handler annotated with `#[utoipa::path]` must be listed in `ApiDoc`, or it is
+    // missing from the exported document
+    #[test]
+    fn test_every_annotated_handler_is_documented() {
+        fn count_annotations(dir: &std::path::Path) -> usize {
+            std::fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .map(|path| {
+                    if path.is_dir() {
+                        count_annotations(&path)
+                    } else if path.extension().is_some_and(|ext| ext == "rs") {
+                        std::fs::read_to_string(&path)
+                            .unwrap()
+                            .lines()
+                            .filter(|line| line.trim_start().starts_with("#[utoipa::path("))
+                            .count()
+                    } else {
+                        0
+                    }
+                })
+                .sum()
+        }
+
+        let annotated =
+            count_annotations(&std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src"));
+        let documented: usize = ApiDoc::openapi()
+            .paths
+            .paths
+            .values()
+            .map(|item| item.operations.len())
+            .sum();
+
+        assert_eq!(annotated, documented);
+    }
+
+    // A schema used in a document but missing from `ApiDoc` components leaves a dangling
+    // `$ref`, which client generators reject
+    #[test]
+    fn test_schema_references_resolve() {
+        fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+            match value {
+                serde_json::Value::Object(object) => {
+                    for (key, value) in object {
+                        match value.as_str() {
+                            Some(reference) if key == "$ref" => refs.push(reference.to_string()),
+                            _ => collect_refs(value, refs),
+                        }
+                    }
+                }
+                serde_json::Value::Array(values) => {
+                    values.iter().for_each(|value| collect_refs(value, refs))
+                }
+                _ => {}
+            }
+        }
+
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut refs = Vec::new();
+        collect_refs(&document, &mut refs);
+
+        let dangling: Vec<_> = refs
+            .iter()
+            .filter(|reference| {
+                reference
+                    .strip_prefix('#')
+                    .and_then(|pointer| document.pointer(pointer))
+                    .is_none()
+            })
+            .collect();
+        assert!(dangling.is_empty(), "Unresolved references: {:?}", dangling);
+    }
+
+    const HTTP_METHODS: [&str; 5] = ["get", "post", "put", "delete", "patch"];
+
+    // Routes that are deliberately left out of the document, by path prefix relative to
+    // `/api`, with the reason
+    const UNDOCUMENTED_ROUTES: &[(&str, &str)] = &[
+        ("/ws", "WebSocket"),
+        ("/notifications/ws", "WebSocket"),
+        ("/analytics/live/ws", "WebSocket"),
+        ("/ghost/", "Ghost content API, mounted outside /api"),
+        (
+            "/admin/example",
+            "example in src/routes/users.rs, not mounted",
+        ),
+        (
+            "/author/example",
+            "example in src/routes/users.rs, not mounted",
+        ),
+    ];
+
+    // Path with its parameters reduced to `{}`, so `:id` in a route matches `{id}` in the
+    // document and differently named parameters in the same place match each other
+    fn route_shape(path: &str) -> String {
+        path.split('/')
+            .map(|segment| {
+                if segment.starts_with(':') || segment.starts_with('{') {
+                    "{}"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    // Operations declared in src/routes as `.route("/path", get(..).post(..))`, relative to
+    // the `/api` prefix the routes are mounted under
+    fn declared_routes() -> BTreeSet<(String, String)> {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/routes");
+        let mut routes = BTreeSet::new();
+
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            for (start, call) in source.match_indices(".route(") {
+                let rest = source[start + call.len()..].trim_start();
+                let path = match rest
+                    .strip_prefix('"')
+                    .and_then(|rest| rest.split('"').next())
+                {
+                    Some(path) => path,
+                    None => continue,
+                };
+
+                // The method router runs up to the parenthesis closing `.route(`
+                let mut depth = 1;
+                let end = rest
+                    .char_indices()
+                    .find_map(|(i, c)| {
+                        match c {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            _ => {}
+                        }
+                        (depth == 0).then_some(i)
+                    })
+                    .unwrap();
+                let method_router = &rest[..end];
+
+                for method in HTTP_METHODS {
+                    let routed =
+                        method_router
+                            .match_indices(&format!("{}(", method))
+                            .any(|(i, _)| {
+                                !method_router[..i]
+                                    .ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                            });
+                    if routed {
+                        routes.insert((method.to_string(), route_shape(path)));
+                    }
+                }
+            }
+        }
+
+        routes
+    }
+
+    // The document and the router are written separately, so check them against each other
+    // without starting the application: every documented operation must be routed, and every
+    // routed one documented
+    #[test]
+    fn test_documented_operations_match_routes() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let documented: BTreeSet<(String, String)> = document["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                let path = route_shape(path.strip_prefix(LEGACY_API_PREFIX).unwrap_or(path));
+                item.as_object()
+                    .unwrap()
+                    .keys()
+                    .filter(|key| HTTP_METHODS.contains(&key.as_str()))
+                    .map(move |method| (method.clone(), path.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let routed = declared_routes();
+
+        let unrouted: Vec<_> = documented.difference(&routed).collect();
+        assert!(
+            unrouted.is_empty(),
+            "Documented but not routed: {:?}",
+            unrouted
+        );
+
+        let undocumented: Vec<_> = routed
+            .difference(&documented)
+            .filter(|(_, path)| {
+                !UNDOCUMENTED_ROUTES
+                    .iter()
+                    .any(|(prefix, _)| path.starts_with(prefix))
+            })
+            .collect();
+        assert!(
+            undocumented.is_empty(),
+            "Routed but not documented: {:?}",
+            undocumented
+        );
+    }
+
+    #[test]
+    fn test_render_v1_document() {
+        let options = ExportOptions::from_args(args(&[])).unwrap();
+        let document: serde_json::Value = serde_json::from_str(&render(&options).unwrap()).unwrap();
+
+        assert!(document["paths"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .all(|path| path.starts_with(API_V1_PREFIX)));
+    }
+}
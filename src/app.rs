@@ -0,0 +1,622 @@
+use crate::analytics::live::LiveDashboard;
+use crate::analytics::service::AnalyticsService;
+use crate::announcement::service::AnnouncementService;
+use crate::auth::token_version::TokenVersions;
+use crate::cache::redis::RedisCache;
+use crate::cache::warmer::CacheWarmer;
+use crate::comment::score::CommentScoreService;
+use crate::comment::service::CommentService;
+use crate::comment::threads::ThreadNotificationJob;
+use crate::db::pool::{monitor_pool, PoolConfig};
+use crate::db::router::DbRouter;
+use crate::feature_flags::service::FeatureFlagService;
+use crate::feedback::service::FeedbackService;
+use crate::health_monitor::{HealthMonitor, HealthMonitorConfig};
+use crate::jobs::service::JobService;
+use crate::leaderboard::service::LeaderboardService;
+use crate::link_preview::service::LinkPreviewService;
+use crate::mailer::mailer_from_env;
+use crate::newsletter::service::NewsletterService;
+use crate::notification::service::NotificationService;
+use crate::post::popularity::PopularityService;
+use crate::post::service::PostService;
+use crate::quota::service::QuotaService;
+use crate::recommendations::digest::DigestService;
+use crate::recommendations::embedding::EmbeddingService;
+use crate::reputation::service::ReputationService;
+use crate::retention::service::RetentionService;
+use crate::seed::service::SeedService;
+use crate::tag::service::TagService;
+use crate::tenant::service::TenantService;
+use crate::title_test::service::TitleTestService;
+use crate::websocket::notifications::NotificationState;
+use crate::{
+    api_doc, consent, db, error_reporting, logging, metrics, post, routes, sitemap, tenant,
+};
+use axum::{
+    body::Body, http::Request, middleware, response::Response, routing::get, Extension, Router,
+};
+use redis::Client;
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tower::{Layer, Service};
+use tower_http::catch_panic::CatchPanicLayer;
+use tracing::{error, info};
+use utoipa_swagger_ui::SwaggerUi;
+
+/// Where an instance finds its database and cache
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub database_url: String,
+    /// Redis is optional; without it nothing is cached and realtime features stay local
+    pub redis_url: Option<String>,
+    pub pool: PoolConfig,
+}
+
+/// Errors raised while starting an instance
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Missing configuration: {0}")]
+    MissingConfig(&'static str),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl AppConfig {
+    /// Read the configuration from `DATABASE_URL`, `REDIS_URL` and the pool settings (see
+    /// `PoolConfig::from_env`)
+    pub fn from_env() -> Result<Self, AppError> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        Ok(Self {
+            database_url: var("DATABASE_URL").ok_or(AppError::MissingConfig("DATABASE_URL"))?,
+            redis_url: var("REDIS_URL"),
+            pool: PoolConfig::from_env(),
+        })
+    }
+}
+
+/// The services of an instance, shared by its routes and background workers
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    /// Heavy reads go to the read replica when one is configured
+    pub db_router: DbRouter,
+    pub redis_cache: Option<Arc<RedisCache>>,
+    pub analytics_service: Arc<AnalyticsService>,
+    pub post_service: Arc<PostService>,
+    pub notification_service: Arc<NotificationService>,
+    pub notification_state: Arc<NotificationState>,
+    pub comment_service: Arc<CommentService>,
+    pub feature_flag_service: Arc<FeatureFlagService>,
+    pub link_preview_service: Arc<LinkPreviewService>,
+    pub live_dashboard: Arc<LiveDashboard>,
+    pub retention_service: Arc<RetentionService>,
+    pub popularity_service: Arc<PopularityService>,
+    pub comment_score_service: Arc<CommentScoreService>,
+    pub reputation_service: Arc<ReputationService>,
+    pub leaderboard_service: Arc<LeaderboardService>,
+    pub newsletter_service: Arc<NewsletterService>,
+    pub feedback_service: Arc<FeedbackService>,
+    pub announcement_service: Arc<AnnouncementService>,
+    pub title_test_service: Arc<TitleTestService>,
+    pub tag_service: Arc<TagService>,
+    pub tenant_service: Arc<TenantService>,
+    pub token_versions: Arc<TokenVersions>,
+    pub quota_service: Arc<QuotaService>,
+    pub job_service: Arc<JobService>,
+    /// Present when a cache is configured
+    pub cache_warmer: Option<Arc<CacheWarmer>>,
+    /// Present when an embedder is configured
+    pub embedding_service: Option<Arc<EmbeddingService>>,
+    /// Present when a mailer is configured
+    pub digest_service: Option<Arc<DigestService>>,
+    /// Present when seed data is enabled
+    pub seed_service: Option<Arc<SeedService>>,
+}
+
+impl AppState {
+    /// Connect to the configured database and cache, creating the schema on first start, and
+    /// set up the services on them
+    pub async fn from_config(config: &AppConfig) -> Result<Self, AppError> {
+        let pool = config.pool.options().connect(&config.database_url).await?;
+        monitor_pool("primary", pool.clone(), &config.pool);
+
+        // Check if the database is initialized
+        if !db::check_db_initialized(&pool).await {
+            db::init_db(&pool).await?;
+        }
+
+        // Initialize Redis cache if configured
+        let redis_cache = if let Some(url) = &config.redis_url {
+            info!("Initializing Redis cache with URL: {}", url);
+            match Client::open(url.clone()) {
+                Ok(client) => {
+                    let cache = RedisCache::new(client, None);
+                    Some(Arc::new(cache))
+                }
+                Err(e) => {
+                    error!("Failed to connect to Redis: {}", e);
+                    None
+                }
+            }
+        } else {
+            info!("No Redis URL configured, proceeding without cache");
+            None
+        };
+
+        Ok(Self::new(pool, redis_cache).await)
+    }
+
+    /// Set up the services on an existing pool, whose schema is already created, and cache
+    pub async fn new(pool: PgPool, redis_cache: Option<Arc<RedisCache>>) -> Self {
+        // Create service instances with unwrapped redis_cache
+        let redis_cache_for_services = redis_cache.as_ref().map(|arc| (**arc).clone());
+
+        let db_router = DbRouter::from_env(pool.clone());
+
+        let analytics_service = Arc::new(AnalyticsService::new(
+            db_router.clone(),
+            redis_cache_for_services.clone(),
+        ));
+        let post_service = Arc::new(PostService::new(
+            db_router.clone(),
+            redis_cache_for_services.clone(),
+        ));
+        let notification_service = Arc::new(NotificationService::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+        ));
+        // Deliver notifications queued in the outbox that were not sent right after their commit
+        notification_service.clone().start_outbox_relay();
+
+        // Comment scores, kept up to date on reactions and reports and by a background job
+        let comment_score_service = Arc::new(CommentScoreService::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+        ));
+
+        // Notifies thread subscribers about batches of replies, as a background job
+        let thread_notifier = Arc::new(ThreadNotificationJob::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+            notification_service.clone(),
+        ));
+
+        // Configure notification routes with NotificationState
+        let notification_state = Arc::new(NotificationState {
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            redis_cache: redis_cache.clone(),
+        });
+
+        // Feature flags, also made available to every API handler for gating risky features
+        let feature_flag_service = Arc::new(FeatureFlagService::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+        ));
+
+        // Background fetcher for link preview cards of URLs in posts and comments
+        let link_preview_service =
+            Arc::new(LinkPreviewService::start(redis_cache_for_services.clone()));
+
+        // Rolling view counters for the live analytics dashboard, fed by the post views stream
+        let live_dashboard = Arc::new(LiveDashboard::start(redis_cache_for_services.clone()));
+
+        // Purges data past its retention period, as a background job
+        let retention_service = Arc::new(RetentionService::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+        ));
+
+        // Popularity scores of posts, recomputed by a background job
+        let popularity_service = Arc::new(PopularityService::new(pool.clone()));
+
+        // Reputation of users, updated by a background job after their activity
+        let reputation_service = Arc::new(ReputationService::new(pool.clone()));
+
+        // Top authors and commenters, kept in Redis and rolled up by a background job
+        let leaderboard_service = Arc::new(LeaderboardService::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+        ));
+
+        // Newsletter subscriptions, and campaigns sent by a background job when a mailer is set up
+        let newsletter_service = Arc::new(NewsletterService::new(pool.clone(), mailer_from_env()));
+
+        // A/B tests of post titles, whose winners a background job promotes
+        let title_test_service = Arc::new(TitleTestService::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+            analytics_service.clone(),
+        ));
+
+        // Reader messages to the admins, who are notified of each one
+        let feedback_service = Arc::new(FeedbackService::new(
+            pool.clone(),
+            notification_service.clone(),
+        ));
+
+        // System announcements, sent to many users at once by a background job
+        let announcement_service = Arc::new(AnnouncementService::new(
+            pool.clone(),
+            notification_service.clone(),
+        ));
+
+        // Tag maintenance, and a background job that cleans up duplicate and unused tags
+        let tag_service = Arc::new(TagService::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+        ));
+
+        // Fills the cache of every blog after a deploy, when a cache is configured
+        let cache_warmer = redis_cache_for_services
+            .clone()
+            .map(|redis_cache| Arc::new(CacheWarmer::new(db_router.clone(), redis_cache)));
+
+        // Post embeddings for the `embedding` recommendation algorithm, when an embedder is set up
+        let embedding_service = EmbeddingService::from_env(pool.clone()).await;
+        // Weekly "posts you missed" emails, when a mailer is set up
+        let digest_service = DigestService::from_env(pool.clone());
+        // Seed data for staging and benchmarks, when enabled
+        let seed_service = SeedService::from_env(pool.clone());
+
+        // Background jobs, run by a worker in every instance
+        let mut job_service = JobService::new(pool.clone())
+            .with_handler(Arc::new(post::export::SiteExportJob::new(
+                db_router.clone(),
+            )))
+            .with_handler(retention_service.clone())
+            .with_handler(popularity_service.clone())
+            .with_handler(comment_score_service.clone())
+            .with_handler(reputation_service.clone())
+            .with_handler(leaderboard_service.clone())
+            .with_handler(newsletter_service.clone())
+            .with_handler(announcement_service.clone())
+            .with_handler(title_test_service.clone())
+            .with_handler(tag_service.clone())
+            .with_handler(thread_notifier.clone());
+        if let Some(embedding_service) = &embedding_service {
+            job_service = job_service.with_handler(embedding_service.clone());
+        }
+        if let Some(digest_service) = &digest_service {
+            job_service = job_service.with_handler(digest_service.clone());
+        }
+        if let Some(cache_warmer) = &cache_warmer {
+            job_service = job_service.with_handler(cache_warmer.clone());
+        }
+        if let Some(seed_service) = &seed_service {
+            job_service = job_service.with_handler(seed_service.clone());
+        }
+        let job_service = Arc::new(job_service);
+
+        // Initialize comment service with required dependencies
+        let comment_service = Arc::new(CommentService::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+            analytics_service.clone(),
+            notification_service.clone(),
+            comment_score_service.clone(),
+            job_service.clone(),
+            thread_notifier,
+        ));
+
+        // Blogs hosted on this instance; every request is scoped to one of them
+        let tenant_service = Arc::new(TenantService::new(pool.clone()));
+
+        // Checked by the auth middleware to reject tokens revoked by a password change
+        let token_versions = Arc::new(TokenVersions::new(
+            pool.clone(),
+            redis_cache_for_services.clone(),
+        ));
+
+        // Daily post and comment quotas per role
+        let quota_service = Arc::new(QuotaService::new(redis_cache_for_services.clone()));
+
+        Self {
+            pool,
+            db_router,
+            redis_cache,
+            analytics_service,
+            post_service,
+            notification_service,
+            notification_state,
+            comment_service,
+            feature_flag_service,
+            link_preview_service,
+            live_dashboard,
+            retention_service,
+            popularity_service,
+            comment_score_service,
+            reputation_service,
+            leaderboard_service,
+            newsletter_service,
+            feedback_service,
+            announcement_service,
+            title_test_service,
+            tag_service,
+            tenant_service,
+            token_versions,
+            quota_service,
+            job_service,
+            cache_warmer,
+            embedding_service,
+            digest_service,
+            seed_service,
+        }
+    }
+
+    /// Start the job worker and the schedulers that queue periodic jobs, which every instance
+    /// serving requests runs; programs embedding the API may leave them to another process
+    pub fn start_background_tasks(&self) {
+        self.job_service.clone().start_worker();
+        self.retention_service
+            .clone()
+            .start_scheduler(self.job_service.clone());
+        self.popularity_service
+            .clone()
+            .start_scheduler(self.job_service.clone());
+        self.comment_score_service
+            .clone()
+            .start_scheduler(self.job_service.clone());
+        self.reputation_service
+            .clone()
+            .start_scheduler(self.job_service.clone());
+        self.leaderboard_service
+            .clone()
+            .start_scheduler(self.job_service.clone());
+        self.tag_service
+            .clone()
+            .start_scheduler(self.job_service.clone());
+        self.title_test_service
+            .clone()
+            .start_scheduler(self.job_service.clone());
+        if let Some(embedding_service) = &self.embedding_service {
+            embedding_service
+                .clone()
+                .start_scheduler(self.job_service.clone());
+        }
+        if let Some(digest_service) = &self.digest_service {
+            digest_service
+                .clone()
+                .start_scheduler(self.job_service.clone());
+        }
+        if let Some(cache_warmer) = &self.cache_warmer {
+            cache_warmer.warm_on_startup(self.job_service.clone());
+        }
+        Arc::new(HealthMonitor::new(
+            self.pool.clone(),
+            self.redis_cache.clone(),
+            self.notification_service.clone(),
+            HealthMonitorConfig::from_env(),
+        ))
+        .start();
+    }
+}
+
+/// The service that handles the requests of an instance, with its routes and middleware.
+///
+/// Serve it with `axum::Server` after `into_make_service`, or call it directly as a tower
+/// service, as the integration tests do.
+pub fn build_router(
+    state: AppState,
+) -> impl Service<
+    Request<Body>,
+    Response = Response,
+    Error = Infallible,
+    Future = impl Future<Output = Result<Response, Infallible>> + Send + 'static,
+> + Clone
+       + Send
+       + 'static {
+    let AppState {
+        pool,
+        db_router,
+        redis_cache,
+        analytics_service,
+        notification_service,
+        notification_state,
+        comment_service,
+        feature_flag_service,
+        link_preview_service,
+        live_dashboard,
+        retention_service,
+        reputation_service,
+        leaderboard_service,
+        newsletter_service,
+        feedback_service,
+        announcement_service,
+        title_test_service,
+        tag_service,
+        tenant_service,
+        token_versions,
+        quota_service,
+        job_service,
+        cache_warmer,
+        embedding_service,
+        seed_service,
+        ..
+    } = state;
+    let redis_cache_for_services = redis_cache.as_ref().map(|arc| (**arc).clone());
+
+    // Build the router
+    let app = Router::new()
+        // API documentation, one document per API version
+        .merge(
+            SwaggerUi::new("/docs")
+                .url("/api-docs/v1/openapi.json", api_doc::v1_openapi())
+                .url("/api-docs/openapi.json", api_doc::legacy_openapi()),
+        )
+        // API routes under /api/v1, with /api kept as a deprecated alias
+        .merge(routes::versioning::versioned_routes(|| {
+            Router::new()
+                // Health routes
+                .merge(routes::health::routes(pool.clone(), redis_cache.clone()))
+                // Auth routes
+                .merge(routes::auth::routes(
+                    pool.clone(),
+                    notification_service.clone(),
+                ))
+                // Add post routes
+                .merge(routes::posts::routes(
+                    db_router.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Analytics routes, including the live dashboard WebSocket
+                .merge(routes::analytics::routes(
+                    db_router.clone(),
+                    redis_cache_for_services.clone(),
+                    live_dashboard.clone(),
+                ))
+                // Add recommendations routes
+                .merge(routes::recommendations::routes(
+                    pool.clone(),
+                    redis_cache_for_services.clone(),
+                    embedding_service.is_some(),
+                ))
+                // Membership tiers and admin membership management
+                .merge(routes::membership::routes(pool.clone()))
+                // Per-blog site settings
+                .merge(routes::settings::routes(
+                    pool.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Blog provisioning
+                .merge(routes::tenants::routes(tenant_service.clone()))
+                // Public profiles with reputation
+                .merge(routes::reputation::routes(reputation_service.clone()))
+                // Top authors and commenters
+                .merge(routes::leaderboards::routes(leaderboard_service.clone()))
+                // Newsletter subscriptions and campaigns
+                .merge(routes::newsletter::routes(newsletter_service.clone()))
+                // A/B tests of post titles
+                .merge(routes::title_tests::routes(title_test_service.clone()))
+                // Reading lists
+                .merge(routes::reading_lists::routes(pool.clone()))
+                // Tips to authors and their earnings
+                .merge(routes::tips::routes(
+                    pool.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Reader feedback to the admins
+                .merge(routes::feedback::routes(
+                    feedback_service.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // System announcements to the users
+                .merge(routes::announcements::routes(announcement_service.clone()))
+                // Invite codes for invite-only registration
+                .merge(routes::invites::routes(pool.clone()))
+                // User activity feeds
+                .merge(routes::activity::routes(
+                    db_router.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Blocking other users
+                .merge(routes::blocks::routes(
+                    pool.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Daily post and comment quotas
+                .merge(routes::quota::routes(quota_service.clone()))
+                // Storage use against the upload quotas
+                .merge(routes::storage::routes(pool.clone()))
+                // Tracking consent
+                .merge(routes::consent::routes(
+                    pool.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Bulk tag maintenance
+                .merge(routes::tags::routes(tag_service.clone()))
+                // Post likes and flagged like activity
+                .merge(routes::likes::routes(
+                    pool.clone(),
+                    redis_cache_for_services.clone(),
+                    analytics_service.clone(),
+                ))
+                // Add comment routes
+                .merge(routes::comments::routes(
+                    comment_service.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Notifications, their unread count, and the notification WebSocket (which also
+                // carries post editing events)
+                .merge(routes::notifications::routes(
+                    notification_state.clone(),
+                    notification_service.clone(),
+                ))
+                // Feature flag evaluation and admin management
+                .merge(routes::feature_flags::routes(feature_flag_service.clone()))
+                // User avatars
+                .merge(routes::media::routes(
+                    db_router.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Link preview cards
+                .merge(routes::link_previews::routes(
+                    link_preview_service.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Background job status
+                .merge(routes::jobs::routes())
+                // Data retention policy and cleanups
+                .merge(routes::retention::routes(retention_service.clone()))
+                // Cache warming
+                .merge(routes::cache::routes(cache_warmer.clone()))
+                // Seed data generation
+                .merge(routes::seed::routes(seed_service.clone()))
+                // Post imports from markdown and WordPress
+                .merge(routes::import::routes(
+                    pool.clone(),
+                    redis_cache_for_services.clone(),
+                ))
+                // Content API keys of the Ghost-compatible API
+                .merge(routes::ghost::key_routes(pool.clone()))
+                // Handlers can check flags with `Extension<Arc<FeatureFlagService>>`
+                .layer(Extension(feature_flag_service.clone()))
+                // Post and comment handlers queue previews of the links they contain
+                .layer(Extension(link_preview_service.clone()))
+                // Handlers queue background work with `Extension<Arc<JobService>>`
+                .layer(Extension(job_service.clone()))
+                // Post and comment handlers count what they create against daily quotas
+                .layer(Extension(quota_service.clone()))
+                // The auth middleware rejects revoked tokens with `Extension<Arc<TokenVersions>>`
+                .layer(Extension(token_versions.clone()))
+        }))
+        // Ghost-compatible content API for Ghost themes and integrations
+        .merge(routes::ghost::content_api_routes(
+            db_router.clone(),
+            redis_cache_for_services.clone(),
+        ))
+        // Sitemap of the requested blog for search engines
+        .route(
+            "/sitemap.xml",
+            get(sitemap::sitemap_handler).with_state(db_router.clone()),
+        )
+        // Prometheus metrics, including database query latencies
+        .route("/metrics", get(metrics::metrics_handler))
+        // Add welcome route
+        .route(
+            "/",
+            get(|| async { "Welcome to Realtime Blog Backend API" }),
+        )
+        // Tag database queries with the route that issued them
+        .layer(middleware::from_fn(metrics::route_context))
+        // Let services skip tracking for requests with `DNT` or `Sec-GPC` set
+        .layer(middleware::from_fn(
+            consent::middleware::tracking_preference,
+        ));
+
+    // Resolve the blog before routing, so a `/blogs/{slug}` prefix can be stripped from the path
+    let app = middleware::from_fn_with_state(tenant_service, tenant::middleware::resolve_tenant)
+        .layer(app);
+    // Answer requests whose handler panicked with a 500 rather than dropping the connection
+    let app = CatchPanicLayer::custom(error_reporting::panic_response).layer(app);
+    let app = middleware::from_fn(error_reporting::report_server_errors).layer(app);
+    // Tag every log line of a request with its id, outside of everything else
+    middleware::from_fn(logging::request_span).layer(app)
+}
@@ -0,0 +1,26 @@
+//! Audit trail of administrative changes, kept in `global.audit_log`.
+
+use crate::tenant::middleware::current_blog_id;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Record that `actor_id` performed `action` (e.g. `tags.retag`) on the current blog, with
+/// what it affected. Pass the transaction of the change itself, so the entry is written
+/// exactly when the change is committed.
+pub async fn record(
+    conn: &mut PgConnection,
+    actor_id: Uuid,
+    action: &str,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO global.audit_log (blog_id, actor_id, action, details) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(current_blog_id())
+    .bind(actor_id)
+    .bind(action)
+    .bind(details)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
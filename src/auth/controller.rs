@@ -2,13 +2,19 @@ use axum::{
     extract::{Json, State},
     http::StatusCode,
     response::{IntoResponse, Response},
+    Extension,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use tracing::{error, info};
+use std::sync::Arc;
+use tracing::{error, info, warn};
 use utoipa::ToSchema;
 
-use super::service::{self, AuthError, AuthResult, LoginData, RegisterData};
+use super::middleware::AuthUser;
+use super::service::{self, AuthError, AuthResult, ChangePasswordData, LoginData, RegisterData};
+use super::token_version::TokenVersions;
+use crate::notification::model::{NotificationPayload, NotificationType};
+use crate::notification::service::NotificationService;
 
 // Request DTOs
 #[derive(Debug, Deserialize, ToSchema)]
@@ -17,6 +23,8 @@ pub struct RegisterRequest {
     pub email: String,
     pub password: String,
     pub role: Option<String>,
+    /// Invite code, required while the blog's registration is invite-only
+    pub invite_code: Option<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -25,6 +33,12 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
 // Response DTOs
 #[derive(Debug, Serialize, ToSchema)]
 pub struct AuthResponse {
@@ -35,8 +49,9 @@ pub struct AuthResponse {
     pub token: String,
 }
 
+/// Error body of failed authentication, also returned by the auth middleware
 #[derive(Debug, Serialize, ToSchema)]
-pub struct ErrorResponse {
+pub struct AuthErrorResponse {
     pub error: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
@@ -75,7 +90,7 @@ fn handle_error(error: AuthError) -> Response {
 
     (
         status,
-        Json(ErrorResponse {
+        Json(AuthErrorResponse {
             error: message,
             details,
         }),
@@ -90,7 +105,8 @@ fn handle_error(error: AuthError) -> Response {
     request_body = RegisterRequest,
     responses(
         (status = 201, description = "User registered successfully", body = AuthResponse),
-        (status = 400, description = "Bad request", body = ErrorResponse)
+        (status = 400, description = "Bad request", body = AuthErrorResponse),
+        (status = 403, description = "Registration closed or invite code invalid", body = AuthErrorResponse)
     ),
     tag = "authentication"
 )]
@@ -102,6 +118,7 @@ pub async fn register(State(pool): State<PgPool>, Json(req): Json<RegisterReques
         email: req.email,
         password: req.password,
         role: req.role,
+        invite_code: req.invite_code,
     };
 
     match service::register(&pool, data).await {
@@ -121,7 +138,7 @@ pub async fn register(State(pool): State<PgPool>, Json(req): Json<RegisterReques
     request_body = LoginRequest,
     responses(
         (status = 200, description = "Login successful", body = AuthResponse),
-        (status = 401, description = "Invalid credentials", body = ErrorResponse)
+        (status = 401, description = "Invalid credentials", body = AuthErrorResponse)
     ),
     tag = "authentication"
 )]
@@ -142,3 +159,71 @@ pub async fn login(State(pool): State<PgPool>, Json(req): Json<LoginRequest>) ->
         Err(error) => handle_error(error),
     }
 }
+
+/// Change your password
+///
+/// Signs out every other session: tokens issued before the change are rejected from then on,
+/// and the response carries a new token for this session. Your open notification connections
+/// get a `SecurityAlert` notification.
+#[utoipa::path(
+    put,
+    path = "/api/users/me/password",
+    tag = "users",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed", body = AuthResponse),
+        (status = 400, description = "Invalid new password", body = AuthErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 403, description = "Current password is incorrect", body = AuthErrorResponse),
+        (status = 500, description = "Internal server error", body = AuthErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn change_password(
+    Extension(user): Extension<AuthUser>,
+    Extension(token_versions): Extension<Arc<TokenVersions>>,
+    Extension(notification_service): Extension<Arc<NotificationService>>,
+    State(pool): State<PgPool>,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Response {
+    info!("Password change requested by user {}", user.user_id);
+
+    let data = ChangePasswordData {
+        current_password: req.current_password,
+        new_password: req.new_password,
+    };
+
+    match service::change_password(&pool, user.user_id, data).await {
+        Ok((result, version)) => {
+            // Other sessions stay signed in on instances that still have the old version cached
+            if let Err(e) = token_versions.record_bump(user.user_id, version).await {
+                return handle_error(AuthError::InternalError(format!(
+                    "Password changed, but other sessions could not be signed out: {}",
+                    e
+                )));
+            }
+
+            let alert = NotificationPayload {
+                recipient_id: user.user_id,
+                notification_type: NotificationType::SecurityAlert,
+                object_id: 0,
+                related_object_id: None,
+                actor_id: user.user_id,
+                content: "Your password was changed. Your other sessions have been signed out."
+                    .to_string(),
+                context: Default::default(),
+            };
+            if let Err(e) = notification_service.deliver(alert).await {
+                warn!(
+                    "Failed to notify user {} of their password change: {}",
+                    user.user_id, e
+                );
+            }
+
+            (StatusCode::OK, Json(to_response(result))).into_response()
+        }
+        Err(error) => handle_error(error),
+    }
+}
@@ -42,10 +42,23 @@ pub struct Claims {
     pub role: Role,  // User role
     pub exp: usize,  // Expiration time
     pub iat: usize,  // Issued at
+    // Token version of the user when it was issued; tokens issued before it had none
+    #[serde(default)]
+    pub ver: i32,
 }
 
 /// Generate a JWT token for a user
 pub fn generate_token(user_id: &Uuid, role: Role) -> Result<String, JwtError> {
+    generate_versioned_token(user_id, role, 0)
+}
+
+/// Generate a JWT token for a user at their current token version, so it is revoked when the
+/// version is bumped
+pub fn generate_versioned_token(
+    user_id: &Uuid,
+    role: Role,
+    version: i32,
+) -> Result<String, JwtError> {
     let jwt_secret = std::env::var("JWT_SECRET").map_err(|_| JwtError::MissingSecret)?;
 
     let now = Utc::now();
@@ -56,6 +69,7 @@ pub fn generate_token(user_id: &Uuid, role: Role) -> Result<String, JwtError> {
         role,
         exp: expiry.timestamp() as usize,
         iat: now.timestamp() as usize,
+        ver: version,
     };
 
     encode(
@@ -203,6 +217,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_token_version_in_claims() {
+        env::set_var("JWT_SECRET", "test_secret");
+        let user_id = Uuid::new_v4();
+
+        let token = generate_versioned_token(&user_id, Role::User, 3).unwrap();
+        assert_eq!(validate_token(&token).unwrap().ver, 3);
+
+        let token = generate_token(&user_id, Role::User).unwrap();
+        assert_eq!(validate_token(&token).unwrap().ver, 0);
+    }
+
     #[test]
     fn test_jwt_secret_environment_variable() {
         // Test missing JWT secret
@@ -401,6 +427,7 @@ mod tests {
             role: Role::User,
             iat: now.timestamp() as usize,
             exp: (now.timestamp() + 1) as usize, // Expire in 1 second
+            ver: 0,
         };
 
         // Encode the token
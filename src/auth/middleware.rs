@@ -7,11 +7,14 @@ use axum::{
     response::{IntoResponse, Json, Response},
     RequestPartsExt, TypedHeader,
 };
-use serde::Serialize;
+use std::sync::Arc;
 use tracing::{error, info};
 use uuid::Uuid;
 
+use super::controller::AuthErrorResponse;
 use super::jwt::{validate_token, Role};
+use super::token_version::TokenVersions;
+use crate::logging::record_user_id;
 
 /// Authenticated user information
 #[derive(Debug, Clone)]
@@ -20,11 +23,6 @@ pub struct AuthUser {
     pub role: Role,
 }
 
-#[derive(Debug, Serialize)]
-struct AuthErrorResponse {
-    error: String,
-}
-
 /// Authentication middleware to protect routes
 pub async fn auth_middleware<B>(req: Request<B>, next: Next<B>) -> Result<Response, Response> {
     let (mut parts, body) = req.into_parts();
@@ -39,6 +37,7 @@ pub async fn auth_middleware<B>(req: Request<B>, next: Next<B>) -> Result<Respon
             Json(AuthErrorResponse {
                 error: "Missing or invalid Authorization header. Please provide a Bearer token"
                     .to_string(),
+                details: None,
             }),
         )
             .into_response());
@@ -54,6 +53,7 @@ pub async fn auth_middleware<B>(req: Request<B>, next: Next<B>) -> Result<Respon
             StatusCode::UNAUTHORIZED,
             Json(AuthErrorResponse {
                 error: "Invalid token. Please login again".to_string(),
+                details: None,
             }),
         )
             .into_response());
@@ -69,12 +69,38 @@ pub async fn auth_middleware<B>(req: Request<B>, next: Next<B>) -> Result<Respon
             StatusCode::UNAUTHORIZED,
             Json(AuthErrorResponse {
                 error: "Invalid user identifier in token".to_string(),
+                details: None,
             }),
         )
             .into_response());
     }
 
     let user_id = user_id_result.unwrap();
+
+    // Reject tokens revoked by a password change
+    let Some(token_versions) = token_versions(&parts) else {
+        return Err(token_check_failed());
+    };
+    match token_versions.is_current(user_id, claims.ver).await {
+        Ok(true) => {}
+        Ok(false) => {
+            info!("Rejected revoked token of user {}", user_id);
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(AuthErrorResponse {
+                    error: "Token has been revoked. Please login again".to_string(),
+                    details: None,
+                }),
+            )
+                .into_response());
+        }
+        Err(e) => {
+            error!("Token version lookup failed: {}", e);
+            return Err(token_check_failed());
+        }
+    }
+
+    record_user_id(user_id);
     info!(
         "User authenticated: {} with role {:?}",
         user_id, claims.role
@@ -93,6 +119,27 @@ pub async fn auth_middleware<B>(req: Request<B>, next: Next<B>) -> Result<Respon
     Ok(next.run(req).await)
 }
 
+// Token versions the auth middlewares check tokens against. Tokens cannot be checked for
+// revocation without them, so a router missing them fails every authenticated request.
+fn token_versions(parts: &Parts) -> Option<Arc<TokenVersions>> {
+    let token_versions = parts.extensions.get::<Arc<TokenVersions>>().cloned();
+    if token_versions.is_none() {
+        error!("Token versions missing from request extensions");
+    }
+    token_versions
+}
+
+fn token_check_failed() -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(AuthErrorResponse {
+            error: "Failed to verify token".to_string(),
+            details: None,
+        }),
+    )
+        .into_response()
+}
+
 /// Role-based authorization middleware
 pub async fn require_role<B>(
     role: Role,
@@ -107,6 +154,7 @@ pub async fn require_role<B>(
                 StatusCode::UNAUTHORIZED,
                 Json(AuthErrorResponse {
                     error: "Authentication required".to_string(),
+                    details: None,
                 }),
             )
                 .into_response());
@@ -133,6 +181,7 @@ pub async fn require_role<B>(
                 StatusCode::FORBIDDEN,
                 Json(AuthErrorResponse {
                     error: format!("Insufficient permissions. Required role: {:?}", role),
+                    details: None,
                 }),
             )
                 .into_response());
@@ -156,6 +205,7 @@ where
                 StatusCode::UNAUTHORIZED,
                 Json(AuthErrorResponse {
                     error: "Authentication required".to_string(),
+                    details: None,
                 }),
             )
                 .into_response()
@@ -170,26 +220,33 @@ pub async fn optional_auth_middleware<B>(req: Request<B>, next: Next<B>) -> Resp
     // Extract the token from the Authorization header if present
     let bearer_result = parts.extract::<TypedHeader<Authorization<Bearer>>>().await;
 
+    let mut auth_user = None;
     if let Ok(TypedHeader(Authorization(bearer))) = bearer_result {
         // If token is present, try to validate it
         if let Ok(claims) = validate_token(bearer.token()) {
-            // Parse the user ID
+            let Some(token_versions) = token_versions(&parts) else {
+                return token_check_failed();
+            };
+            // Parse the user ID; revoked tokens are treated as no token
             if let Ok(user_id) = Uuid::parse_str(&claims.sub) {
-                // Create AuthUser and insert into request extensions
-                let auth_user = AuthUser {
-                    user_id,
-                    role: claims.role,
-                };
-
-                // Insert as Option<AuthUser>
-                parts.extensions.insert(Some(auth_user));
+                if token_versions
+                    .is_current(user_id, claims.ver)
+                    .await
+                    .unwrap_or(false)
+                {
+                    record_user_id(user_id);
+                    auth_user = Some(AuthUser {
+                        user_id,
+                        role: claims.role,
+                    });
+                }
             }
         }
-    } else {
-        // No valid token, insert None as Option<AuthUser>
-        parts.extensions.insert(Option::<AuthUser>::None);
     }
 
+    // Insert as Option<AuthUser>, None when there is no valid token
+    parts.extensions.insert(auth_user);
+
     // Continue with the request
     let req = Request::from_parts(parts, body);
     next.run(req).await
@@ -2,3 +2,4 @@ pub mod controller;
 pub mod jwt;
 pub mod middleware;
 pub mod service;
+pub mod token_version;
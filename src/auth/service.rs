@@ -8,7 +8,9 @@ use sqlx::PgPool;
 use tracing::{error, info};
 use uuid::Uuid;
 
-use super::jwt::{generate_token, Role};
+use super::jwt::{generate_token, generate_versioned_token, Role};
+use crate::invite::model::RegistrationMode;
+use crate::invite::service::{redeem_invite, registration_mode};
 
 // Input data structures
 pub struct RegisterData {
@@ -16,6 +18,8 @@ pub struct RegisterData {
     pub email: String,
     pub password: String,
     pub role: Option<String>,
+    /// Required while the blog's registration is invite-only
+    pub invite_code: Option<String>,
 }
 
 pub struct LoginData {
@@ -23,6 +27,11 @@ pub struct LoginData {
     pub password: String,
 }
 
+pub struct ChangePasswordData {
+    pub current_password: String,
+    pub new_password: String,
+}
+
 // Result data structure
 pub struct AuthResult {
     pub user_id: Uuid,
@@ -37,6 +46,10 @@ pub enum AuthError {
     InvalidInput(String),
     AlreadyExists(String),
     InvalidCredentials,
+    IncorrectPassword,
+    RegistrationClosed,
+    InvalidInvite,
+    UserNotFound,
     DatabaseError(String),
     TokenError,
     InternalError(String),
@@ -48,6 +61,10 @@ impl AuthError {
             Self::InvalidInput(_) => StatusCode::BAD_REQUEST,
             Self::AlreadyExists(_) => StatusCode::CONFLICT,
             Self::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            Self::IncorrectPassword | Self::RegistrationClosed | Self::InvalidInvite => {
+                StatusCode::FORBIDDEN
+            }
+            Self::UserNotFound => StatusCode::NOT_FOUND,
             Self::DatabaseError(_) | Self::TokenError | Self::InternalError(_) => {
                 StatusCode::INTERNAL_SERVER_ERROR
             }
@@ -59,6 +76,10 @@ impl AuthError {
             Self::InvalidInput(msg) => msg.clone(),
             Self::AlreadyExists(msg) => msg.clone(),
             Self::InvalidCredentials => "Invalid email or password".to_string(),
+            Self::IncorrectPassword => "Current password is incorrect".to_string(),
+            Self::RegistrationClosed => "Registration is closed".to_string(),
+            Self::InvalidInvite => "A valid invite code is required".to_string(),
+            Self::UserNotFound => "User not found".to_string(),
             Self::DatabaseError(msg) => format!("Database error: {}", msg),
             Self::TokenError => "Failed to generate auth token".to_string(),
             Self::InternalError(msg) => msg.clone(),
@@ -66,6 +87,18 @@ impl AuthError {
     }
 }
 
+// Hash a password with Argon2 and a fresh salt
+fn hash_password(password: &str) -> Result<String, AuthError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            error!("Password hashing failed: {}", e);
+            AuthError::InternalError(format!("Password hashing failed: {}", e))
+        })
+}
+
 // User registration service
 pub async fn register(pool: &PgPool, data: RegisterData) -> Result<AuthResult, AuthError> {
     // Validate input
@@ -75,6 +108,20 @@ pub async fn register(pool: &PgPool, data: RegisterData) -> Result<AuthResult, A
         ));
     }
 
+    // Check who may register on this blog
+    let mode = registration_mode(pool).await.map_err(|e| {
+        error!("Database error while checking the registration mode: {}", e);
+        AuthError::DatabaseError(e.to_string())
+    })?;
+    let invite_code = match mode {
+        RegistrationMode::Open => None,
+        RegistrationMode::InviteOnly => match data.invite_code.as_deref().map(str::trim) {
+            Some(code) if !code.is_empty() => Some(code),
+            _ => return Err(AuthError::InvalidInvite),
+        },
+        RegistrationMode::Closed => return Err(AuthError::RegistrationClosed),
+    };
+
     info!("Checking if user with email {} already exists", data.email);
 
     // Check if user with email already exists
@@ -96,22 +143,18 @@ pub async fn register(pool: &PgPool, data: RegisterData) -> Result<AuthResult, A
     info!("Creating new user with email {}", data.email);
 
     // Hash password
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(data.password.as_bytes(), &salt)
-        .map_err(|e| {
-            error!("Password hashing failed: {}", e);
-            AuthError::InternalError(format!("Password hashing failed: {}", e))
-        })?
-        .to_string();
+    let password_hash = hash_password(&data.password)?;
 
     // Determine role
     let role_str = data.role.unwrap_or_else(|| "user".to_string());
     let role = Role::from_str(&role_str).map_err(|e| AuthError::InvalidInput(e))?;
 
-    // Create new user
+    // Create new user, using up the invite with it
     let user_id = Uuid::new_v4();
+    let mut tx = pool.begin().await.map_err(|e| {
+        error!("Failed to begin transaction: {}", e);
+        AuthError::DatabaseError(e.to_string())
+    })?;
     sqlx::query(
         "INSERT INTO global.users (id, username, email, password_hash, role) VALUES ($1, $2, $3, $4, $5)",
     )
@@ -120,13 +163,32 @@ pub async fn register(pool: &PgPool, data: RegisterData) -> Result<AuthResult, A
     .bind(&data.email)
     .bind(&password_hash)
     .bind(&role_str)
-    .execute(pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         error!("Failed to insert new user: {}", e);
         AuthError::DatabaseError(e.to_string())
     })?;
 
+    if let Some(code) = invite_code {
+        let redeemed = redeem_invite(&mut tx, code, user_id).await.map_err(|e| {
+            error!("Failed to redeem invite: {}", e);
+            AuthError::DatabaseError(e.to_string())
+        })?;
+        if !redeemed {
+            info!(
+                "Rejected unknown, used or expired invite for {}",
+                data.email
+            );
+            return Err(AuthError::InvalidInvite);
+        }
+    }
+
+    tx.commit().await.map_err(|e| {
+        error!("Failed to commit new user: {}", e);
+        AuthError::DatabaseError(e.to_string())
+    })?;
+
     info!("User created successfully with ID: {}", user_id);
 
     // Generate token
@@ -150,8 +212,8 @@ pub async fn login(pool: &PgPool, data: LoginData) -> Result<AuthResult, AuthErr
     info!("Attempting login for user with email: {}", data.email);
 
     // Find user by email (without role column)
-    let user = sqlx::query_as::<_, (Uuid, String, String, String)>(
-        "SELECT id, username, email, password_hash FROM global.users WHERE email = $1",
+    let user = sqlx::query_as::<_, (Uuid, String, String, String, i32)>(
+        "SELECT id, username, email, password_hash, token_version FROM global.users WHERE email = $1",
     )
     .bind(&data.email)
     .fetch_optional(pool)
@@ -192,7 +254,7 @@ pub async fn login(pool: &PgPool, data: LoginData) -> Result<AuthResult, AuthErr
     let role_str = "user".to_string();
 
     // Generate token
-    let token = generate_token(&user.0, role).map_err(|e| {
+    let token = generate_versioned_token(&user.0, role, user.4).map_err(|e| {
         error!("Token generation failed: {:?}", e);
         AuthError::TokenError
     })?;
@@ -208,3 +270,93 @@ pub async fn login(pool: &PgPool, data: LoginData) -> Result<AuthResult, AuthErr
         token,
     })
 }
+
+// Change a user's password. Bumping the token version revokes every token issued before, so
+// other sessions have to log in again; the returned token keeps the current session signed in.
+pub async fn change_password(
+    pool: &PgPool,
+    user_id: Uuid,
+    data: ChangePasswordData,
+) -> Result<(AuthResult, i32), AuthError> {
+    if data.new_password.is_empty() {
+        return Err(AuthError::InvalidInput(
+            "New password is required".to_string(),
+        ));
+    }
+    if data.new_password == data.current_password {
+        return Err(AuthError::InvalidInput(
+            "New password must differ from the current one".to_string(),
+        ));
+    }
+
+    let mut tx = pool.begin().await.map_err(|e| {
+        error!("Failed to start password change: {}", e);
+        AuthError::DatabaseError(e.to_string())
+    })?;
+
+    // Lock the row so concurrent changes cannot both verify the old password
+    let password_hash = sqlx::query_scalar::<_, String>(
+        "SELECT password_hash FROM global.users WHERE id = $1 FOR UPDATE",
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        error!("Database error while fetching user: {}", e);
+        AuthError::DatabaseError(e.to_string())
+    })?
+    .ok_or(AuthError::UserNotFound)?;
+
+    let parsed_hash = argon2::password_hash::PasswordHash::new(&password_hash).map_err(|e| {
+        error!("Failed to parse password hash: {}", e);
+        AuthError::InternalError("Stored password hash is invalid".to_string())
+    })?;
+    Argon2::default()
+        .verify_password(data.current_password.as_bytes(), &parsed_hash)
+        .map_err(|_| {
+            info!("Current password verification failed for user {}", user_id);
+            AuthError::IncorrectPassword
+        })?;
+
+    let new_hash = hash_password(&data.new_password)?;
+    let (username, email, role_str, version) = sqlx::query_as::<_, (String, String, String, i32)>(
+        r#"
+        UPDATE global.users
+        SET password_hash = $2, token_version = token_version + 1, updated_at = NOW()
+        WHERE id = $1
+        RETURNING username, email, role, token_version
+        "#,
+    )
+    .bind(user_id)
+    .bind(&new_hash)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        error!("Failed to update password: {}", e);
+        AuthError::DatabaseError(e.to_string())
+    })?;
+
+    tx.commit().await.map_err(|e| {
+        error!("Failed to commit password change: {}", e);
+        AuthError::DatabaseError(e.to_string())
+    })?;
+
+    info!("Password changed for user {}", user_id);
+
+    let role = Role::from_str(&role_str).unwrap_or(Role::User);
+    let token = generate_versioned_token(&user_id, role, version).map_err(|e| {
+        error!("Token generation failed: {:?}", e);
+        AuthError::TokenError
+    })?;
+
+    Ok((
+        AuthResult {
+            user_id,
+            username,
+            email,
+            role: role_str,
+            token,
+        },
+        version,
+    ))
+}
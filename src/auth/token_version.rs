@@ -0,0 +1,93 @@
+use crate::cache::redis::RedisCache;
+use redis::{AsyncCommands, RedisError};
+use sqlx::PgPool;
+use tracing::error;
+use uuid::Uuid;
+
+const TOKEN_VERSION_CACHE_TTL: u64 = 3600; // 1 hour
+
+// SET that never lowers the cached version, so a version read before a bump cannot be written
+// back over the bumped one
+const RAISE_VERSION_SCRIPT: &str = r#"
+local cached = tonumber(redis.call('GET', KEYS[1]))
+if cached and cached >= tonumber(ARGV[1]) then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[2])
+return 1
+"#;
+
+fn token_version_cache_key(user_id: Uuid) -> String {
+    format!("user_token_version:{}", user_id)
+}
+
+/// Token versions of users.
+///
+/// Every token carries the version of its user at the time it was issued; bumping the version
+/// revokes all tokens issued before. Checked on every authenticated request, so versions are
+/// served from cache when possible. Cached versions only ever go up: a bump raises the cached
+/// version, so revocation takes effect on every instance at once, and a lookup that read the
+/// version before the bump cannot put the old one back.
+#[derive(Debug, Clone)]
+pub struct TokenVersions {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+}
+
+impl TokenVersions {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self { pool, redis_cache }
+    }
+
+    /// Current token version of a user; `None` when the user no longer exists
+    pub async fn current(&self, user_id: Uuid) -> Result<Option<i32>, sqlx::Error> {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Ok(Some(version)) = conn
+                    .get::<_, Option<i32>>(token_version_cache_key(user_id))
+                    .await
+                {
+                    return Ok(Some(version));
+                }
+            }
+        }
+
+        let version =
+            sqlx::query_scalar::<_, i32>("SELECT token_version FROM global.users WHERE id = $1")
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        if let Some(version) = version {
+            if let Err(e) = self.raise_cached(user_id, version).await {
+                error!("Failed to cache token version: {}", e);
+            }
+        }
+        Ok(version)
+    }
+
+    /// Publish a user's new version after it was bumped. Until this succeeds, instances may
+    /// still accept tokens of the old version from their cache, so failures must be reported.
+    pub async fn record_bump(&self, user_id: Uuid, version: i32) -> Result<(), RedisError> {
+        self.raise_cached(user_id, version).await
+    }
+
+    async fn raise_cached(&self, user_id: Uuid, version: i32) -> Result<(), RedisError> {
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            None => return Ok(()),
+        };
+        redis::Script::new(RAISE_VERSION_SCRIPT)
+            .key(token_version_cache_key(user_id))
+            .arg(version)
+            .arg(TOKEN_VERSION_CACHE_TTL)
+            .invoke_async::<i64>(&mut cache.connection().await?)
+            .await
+            .map(|_| ())
+    }
+
+    /// Whether a token issued at `version` is still valid for the user
+    pub async fn is_current(&self, user_id: Uuid, version: i32) -> Result<bool, sqlx::Error> {
+        Ok(self.current(user_id).await? == Some(version))
+    }
+}
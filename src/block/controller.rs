@@ -0,0 +1,103 @@
+use crate::auth::middleware::AuthUser;
+use crate::block::model::{BlockError, BlockResponse};
+use crate::block::service::BlockService;
+use crate::db::retry::unavailable_response;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+
+fn block_error_response(e: BlockError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Block error: {:?}", e);
+    let status = match e {
+        BlockError::SelfBlock => StatusCode::BAD_REQUEST,
+        BlockError::UserNotFound => StatusCode::NOT_FOUND,
+        BlockError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Block a user
+///
+/// Hides the user's comments from your comment listings and their activity feed from you,
+/// and stops notifications caused by them.
+#[utoipa::path(
+    post,
+    path = "/api/users/{id}/block",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ID of the user to block")
+    ),
+    responses(
+        (status = 200, description = "User blocked", body = BlockResponse),
+        (status = 400, description = "Cannot block yourself"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn block_user(
+    Path(user_id): Path<Uuid>,
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<BlockService>>,
+) -> Response {
+    match service.block_user(user.user_id, user_id).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(BlockResponse {
+                user_id,
+                blocked: true,
+            }),
+        )
+            .into_response(),
+        Err(e) => block_error_response(e),
+    }
+}
+
+/// Unblock a user
+#[utoipa::path(
+    delete,
+    path = "/api/users/{id}/block",
+    tag = "users",
+    params(
+        ("id" = String, Path, description = "ID of the user to unblock")
+    ),
+    responses(
+        (status = 200, description = "User unblocked", body = BlockResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn unblock_user(
+    Path(user_id): Path<Uuid>,
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<BlockService>>,
+) -> Response {
+    match service.unblock_user(user.user_id, user_id).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(BlockResponse {
+                user_id,
+                blocked: false,
+            }),
+        )
+            .into_response(),
+        Err(e) => block_error_response(e),
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Block state returned by the block/unblock endpoints
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BlockResponse {
+    /// ID of the other user
+    pub user_id: Uuid,
+
+    /// Whether their comments and notifications are now hidden from you
+    #[schema(example = "true")]
+    pub blocked: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlockError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("User not found")]
+    UserNotFound,
+
+    #[error("You cannot block yourself")]
+    SelfBlock,
+}
@@ -0,0 +1,127 @@
+use crate::block::model::BlockError;
+use crate::cache::redis::RedisCache;
+use redis::AsyncCommands;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use tracing::{error, info};
+use uuid::Uuid;
+
+const BLOCKS_CACHE_TTL: u64 = 3600; // 1 hour
+
+fn blocks_cache_key(user_id: Uuid) -> String {
+    format!("user_blocks:{}", user_id)
+}
+
+/// Users the given user has blocked.
+///
+/// Asked for on every comment listing and notification, so the list is served from cache
+/// when possible and a cache outage only costs a fresh query.
+pub async fn blocked_user_ids(
+    pool: &PgPool,
+    redis_cache: Option<&RedisCache>,
+    user_id: Uuid,
+) -> Result<HashSet<Uuid>, sqlx::Error> {
+    if let Some(cache) = redis_cache {
+        if let Ok(mut conn) = cache.connection().await {
+            if let Ok(Some(cached_data)) = conn
+                .get::<_, Option<String>>(blocks_cache_key(user_id))
+                .await
+            {
+                match serde_json::from_str::<HashSet<Uuid>>(&cached_data) {
+                    Ok(blocked) => return Ok(blocked),
+                    Err(e) => error!("Failed to deserialize cached block list: {}", e),
+                }
+            }
+        }
+    }
+
+    let blocked: HashSet<Uuid> =
+        sqlx::query_scalar("SELECT blocked_id FROM global.user_blocks WHERE blocker_id = $1")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+
+    if let Some(cache) = redis_cache {
+        if let Ok(mut conn) = cache.connection().await {
+            let json_data = serde_json::to_string(&blocked).unwrap_or_default();
+            if let Err(e) = conn
+                .set_ex::<_, _, ()>(blocks_cache_key(user_id), json_data, BLOCKS_CACHE_TTL)
+                .await
+            {
+                error!("Failed to cache block list: {}", e);
+            }
+        }
+    }
+
+    Ok(blocked)
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+}
+
+impl BlockService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self { pool, redis_cache }
+    }
+
+    /// Block a user. Blocking someone already blocked is a no-op.
+    pub async fn block_user(&self, blocker_id: Uuid, blocked_id: Uuid) -> Result<(), BlockError> {
+        if blocker_id == blocked_id {
+            return Err(BlockError::SelfBlock);
+        }
+
+        let exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM global.users WHERE id = $1)",
+        )
+        .bind(blocked_id)
+        .fetch_one(&self.pool)
+        .await?;
+        if !exists {
+            return Err(BlockError::UserNotFound);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO global.user_blocks (blocker_id, blocked_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(blocker_id)
+        .bind(blocked_id)
+        .execute(&self.pool)
+        .await?;
+
+        self.invalidate_cache(blocker_id).await;
+        info!("User {} blocked user {}", blocker_id, blocked_id);
+        Ok(())
+    }
+
+    /// Unblock a user. Unblocking someone not blocked is a no-op.
+    pub async fn unblock_user(&self, blocker_id: Uuid, blocked_id: Uuid) -> Result<(), BlockError> {
+        sqlx::query("DELETE FROM global.user_blocks WHERE blocker_id = $1 AND blocked_id = $2")
+            .bind(blocker_id)
+            .bind(blocked_id)
+            .execute(&self.pool)
+            .await?;
+
+        self.invalidate_cache(blocker_id).await;
+        info!("User {} unblocked user {}", blocker_id, blocked_id);
+        Ok(())
+    }
+
+    async fn invalidate_cache(&self, user_id: Uuid) {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Err(e) = conn.del::<_, ()>(blocks_cache_key(user_id)).await {
+                    error!("Failed to invalidate cached block list: {}", e);
+                }
+            }
+        }
+    }
+}
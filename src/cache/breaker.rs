@@ -0,0 +1,231 @@
+//! Circuit breaker for Redis.
+//!
+//! Every Redis call opens a connection first. When Redis is down, each of those waits for
+//! the connection to fail, and a request may make several. After
+//! `REDIS_BREAKER_FAILURE_THRESHOLD` connection failures in a row the breaker opens, and
+//! calls fail at once, which callers treat as a cache miss. Once
+//! `REDIS_BREAKER_COOLDOWN_SECONDS` have passed, the next call is let through as a probe:
+//! the breaker closes if it connects and opens again if not.
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Calls go through
+    Closed,
+    /// Calls fail at once
+    Open,
+    /// One call is probing whether the service is back; others fail at once
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub const ALL: [BreakerState; 3] = [
+        BreakerState::Closed,
+        BreakerState::Open,
+        BreakerState::HalfOpen,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trips: u64,
+}
+
+/// Stops calls to a service after repeated failures, letting one through now and then to
+/// find out whether it has recovered
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Breaker>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            name,
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Breaker {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                trips: 0,
+            }),
+        }
+    }
+
+    /// Breaker configured from `REDIS_BREAKER_FAILURE_THRESHOLD` (5) and
+    /// `REDIS_BREAKER_COOLDOWN_SECONDS` (30)
+    pub fn from_env(name: &'static str) -> Self {
+        let number = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+        Self::new(
+            name,
+            number("REDIS_BREAKER_FAILURE_THRESHOLD")
+                .filter(|threshold| *threshold > 0)
+                .map_or(DEFAULT_FAILURE_THRESHOLD, |threshold| threshold as u32),
+            number("REDIS_BREAKER_COOLDOWN_SECONDS").map_or(DEFAULT_COOLDOWN, Duration::from_secs),
+        )
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Breaker> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.lock().state
+    }
+
+    /// Times the breaker has opened
+    pub fn trips(&self) -> u64 {
+        self.lock().trips
+    }
+
+    /// Whether a call may go ahead. After the cooldown, the first call asking is the probe.
+    pub fn allow(&self) -> bool {
+        let mut breaker = self.lock();
+        match breaker.state {
+            BreakerState::Closed => true,
+            BreakerState::HalfOpen => false,
+            BreakerState::Open => {
+                let cooled_down = breaker
+                    .opened_at
+                    .is_none_or(|opened_at| opened_at.elapsed() >= self.cooldown);
+                if cooled_down {
+                    breaker.state = BreakerState::HalfOpen;
+                }
+                cooled_down
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut breaker = self.lock();
+        if breaker.state != BreakerState::Closed {
+            info!(
+                "{} is reachable again, closing its circuit breaker",
+                self.name
+            );
+        }
+        breaker.state = BreakerState::Closed;
+        breaker.consecutive_failures = 0;
+        breaker.opened_at = None;
+    }
+
+    pub fn record_failure(&self) {
+        let mut breaker = self.lock();
+        breaker.consecutive_failures = breaker.consecutive_failures.saturating_add(1);
+        let trip = match breaker.state {
+            BreakerState::Closed => breaker.consecutive_failures >= self.failure_threshold,
+            // A failed probe, or a call let through before the breaker opened
+            BreakerState::HalfOpen | BreakerState::Open => true,
+        };
+        if trip {
+            if breaker.state != BreakerState::Open {
+                breaker.trips += 1;
+                warn!(
+                    "Opening the circuit breaker of {} after {} failures; retrying in {}s",
+                    self.name,
+                    breaker.consecutive_failures,
+                    self.cooldown.as_secs()
+                );
+            }
+            breaker.state = BreakerState::Open;
+            breaker.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+fn registry() -> &'static Mutex<Vec<Arc<CircuitBreaker>>> {
+    static BREAKERS: OnceLock<Mutex<Vec<Arc<CircuitBreaker>>>> = OnceLock::new();
+    BREAKERS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Report a breaker at `/metrics` and in the readiness probe, replacing one of the same name
+pub fn register(breaker: Arc<CircuitBreaker>) {
+    let mut breakers = registry()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    breakers.retain(|registered| registered.name() != breaker.name());
+    breakers.push(breaker);
+}
+
+/// The registered breakers
+pub fn breakers() -> Vec<Arc<CircuitBreaker>> {
+    registry()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new("test", 3, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow());
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(breaker.trips(), 1);
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn test_breaker_probes_after_cooldown() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::ZERO);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        // One probe at a time
+        assert!(breaker.allow());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(!breaker.allow());
+
+        // A failed probe opens the breaker again
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(breaker.trips(), 2);
+
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow());
+    }
+}
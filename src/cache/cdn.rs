@@ -0,0 +1,162 @@
+use futures::future::BoxFuture;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::error;
+
+const DEFAULT_FASTLY_API_URL: &str = "https://api.fastly.com";
+const DEFAULT_CLOUDFLARE_API_URL: &str = "https://api.cloudflare.com/client/v4";
+const CDN_TIMEOUT: Duration = Duration::from_secs(10);
+// Most keys the providers accept in one purge call
+const FASTLY_KEYS_PER_PURGE: usize = 256;
+const CLOUDFLARE_TAGS_PER_PURGE: usize = 30;
+
+/// Drops the responses a CDN keeps under surrogate keys, as set in the `Surrogate-Key` and
+/// `Cache-Tag` response headers
+pub trait CdnPurger: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn purge<'a>(&'a self, keys: &'a [String]) -> BoxFuture<'a, Result<(), String>>;
+}
+
+fn check_purge(response: &reqwest::Response, provider: &str) -> Result<(), String> {
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} refused the purge with status {}",
+            provider,
+            response.status()
+        ))
+    }
+}
+
+/// Purges by surrogate key through the Fastly API
+pub struct FastlyPurger {
+    client: reqwest::Client,
+    api_url: String,
+    api_token: String,
+    service_id: String,
+}
+
+impl CdnPurger for FastlyPurger {
+    fn name(&self) -> &'static str {
+        "fastly"
+    }
+
+    fn purge<'a>(&'a self, keys: &'a [String]) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            for keys in keys.chunks(FASTLY_KEYS_PER_PURGE) {
+                let response = self
+                    .client
+                    .post(format!(
+                        "{}/service/{}/purge",
+                        self.api_url.trim_end_matches('/'),
+                        self.service_id
+                    ))
+                    .header("Fastly-Key", &self.api_token)
+                    .header("Surrogate-Key", keys.join(" "))
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                check_purge(&response, "Fastly")?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Purges by cache tag through the Cloudflare API
+pub struct CloudflarePurger {
+    client: reqwest::Client,
+    api_url: String,
+    api_token: String,
+    zone_id: String,
+}
+
+impl CdnPurger for CloudflarePurger {
+    fn name(&self) -> &'static str {
+        "cloudflare"
+    }
+
+    fn purge<'a>(&'a self, keys: &'a [String]) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            for tags in keys.chunks(CLOUDFLARE_TAGS_PER_PURGE) {
+                let response = self
+                    .client
+                    .post(format!(
+                        "{}/zones/{}/purge_cache",
+                        self.api_url.trim_end_matches('/'),
+                        self.zone_id
+                    ))
+                    .bearer_auth(&self.api_token)
+                    .json(&json!({ "tags": tags }))
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                check_purge(&response, "Cloudflare")?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Which CDN purges are sent to, read from the environment:
+///
+/// - `CDN_PROVIDER`: `fastly` or `cloudflare`; unset leaves CDNs to expire responses
+/// - Fastly: `FASTLY_API_TOKEN` and `FASTLY_SERVICE_ID`, both required, and `FASTLY_API_URL`
+///   (default `https://api.fastly.com`)
+/// - Cloudflare: `CLOUDFLARE_API_TOKEN` and `CLOUDFLARE_ZONE_ID`, both required, and
+///   `CLOUDFLARE_API_URL` (default `https://api.cloudflare.com/client/v4`)
+pub fn cdn_purger_from_env() -> Option<Arc<dyn CdnPurger>> {
+    let kind = std::env::var("CDN_PROVIDER").ok()?;
+    let client = match reqwest::Client::builder().timeout(CDN_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to create CDN client: {}", e);
+            return None;
+        }
+    };
+
+    match kind.to_lowercase().as_str() {
+        "fastly" => {
+            let (Ok(api_token), Ok(service_id)) = (
+                std::env::var("FASTLY_API_TOKEN"),
+                std::env::var("FASTLY_SERVICE_ID"),
+            ) else {
+                error!("CDN_PROVIDER=fastly but FASTLY_API_TOKEN or FASTLY_SERVICE_ID is not set");
+                return None;
+            };
+            Some(Arc::new(FastlyPurger {
+                client,
+                api_url: std::env::var("FASTLY_API_URL")
+                    .unwrap_or_else(|_| DEFAULT_FASTLY_API_URL.to_string()),
+                api_token,
+                service_id,
+            }))
+        }
+        "cloudflare" => {
+            let (Ok(api_token), Ok(zone_id)) = (
+                std::env::var("CLOUDFLARE_API_TOKEN"),
+                std::env::var("CLOUDFLARE_ZONE_ID"),
+            ) else {
+                error!(
+                    "CDN_PROVIDER=cloudflare but CLOUDFLARE_API_TOKEN or CLOUDFLARE_ZONE_ID \
+                     is not set"
+                );
+                return None;
+            };
+            Some(Arc::new(CloudflarePurger {
+                client,
+                api_url: std::env::var("CLOUDFLARE_API_URL")
+                    .unwrap_or_else(|_| DEFAULT_CLOUDFLARE_API_URL.to_string()),
+                api_token,
+                zone_id,
+            }))
+        }
+        other => {
+            error!("Unknown CDN_PROVIDER: {}", other);
+            None
+        }
+    }
+}
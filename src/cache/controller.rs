@@ -0,0 +1,63 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::cache::warmer::{CacheWarmer, CACHE_WARM_JOB};
+use crate::jobs::controller::job_error_response;
+use crate::jobs::service::JobService;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Warm the cache now (admin only)
+///
+/// Queues a warming of the cache of every blog as a background job and returns it. Its
+/// result at `/api/admin/jobs/{id}` counts the blogs warmed and the cache entries filled and
+/// failed.
+#[utoipa::path(
+    post,
+    path = "/api/admin/cache/warm",
+    tag = "cache",
+    responses(
+        (status = 202, description = "Warming queued", body = Job),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 503, description = "No cache configured"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn warm_cache(
+    Extension(user): Extension<AuthUser>,
+    Extension(jobs): Extension<Arc<JobService>>,
+    State(warmer): State<Option<Arc<CacheWarmer>>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Admin access required" })),
+        )
+            .into_response();
+    }
+
+    if warmer.is_none() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "No cache is configured" })),
+        )
+            .into_response();
+    }
+
+    match jobs
+        .enqueue(CACHE_WARM_JOB, json!({}), Some(user.user_id))
+        .await
+    {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(e) => job_error_response(e),
+    }
+}
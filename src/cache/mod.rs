@@ -1 +1,7 @@
+pub mod breaker;
+pub mod cdn;
+pub mod controller;
 pub mod redis;
+pub mod response;
+pub mod surrogate;
+pub mod warmer;
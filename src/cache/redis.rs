@@ -1,19 +1,42 @@
+use crate::cache::breaker::{self, CircuitBreaker};
+use crate::cache::surrogate::{tag_entry, SurrogateKey};
+use crate::slug::match_key;
+use crate::tenant::middleware::{blog_key, current_blog_id};
 use chrono;
-use redis::{AsyncCommands, Client, RedisError};
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, AsyncConnectionConfig, Client, ErrorKind, RedisError};
 use serde_json;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{error, info};
 use uuid::Uuid;
 
-// Redis cache key prefixes
-pub const POST_KEY_PREFIX: &str = "post";
+// Redis cache key prefixes. Post and popular post keys are further prefixed with the blog.
 pub const POPULAR_POSTS_KEY: &str = "popular_posts";
+pub const POST_ARCHIVE_KEY: &str = "post_archive";
 pub const POST_VIEWS_STREAM: &str = "post_views";
+/// Redis stream every post read is logged to, followed by the live analytics dashboard
+pub const POST_VIEWS_STREAM_KEY: &str = "stream:post_views";
 const POST_CACHE_TTL_SECONDS: u64 = 3600; // 1 hour
 const POPULAR_POSTS_TTL_SECONDS: u64 = 3600; // 1 hour
+const POST_ARCHIVE_TTL_SECONDS: u64 = 3600; // 1 hour
 const POST_STATS_TTL_SECONDS: u64 = 86400; // 24 hours
 const USER_ENGAGEMENT_TTL_SECONDS: u64 = 86400; // 24 hours
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_millis(500);
+// DEL that only removes a key still holding the expected value
+const COMPARE_AND_DELETE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+// Posts are found by slug ignoring case and accents, so they are cached by its match key
+fn slug_key(slug: &str) -> String {
+    blog_key(&format!("post:slug:{}", match_key(slug)))
+}
 
 // Error type for cache operations
 #[derive(Debug, thiserror::Error)]
@@ -58,21 +81,53 @@ pub struct RedisConfig {
     pub user_engagement_ttl: Option<Duration>,
 }
 
+/// How long Redis calls may take before they fail, so a slow Redis costs a cache miss
+/// rather than a stalled request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedisTimeouts {
+    pub connect: Duration,
+    /// Per command, on connections from `RedisCache::connection`
+    pub command: Duration,
+}
+
+impl RedisTimeouts {
+    /// Timeouts from `REDIS_CONNECT_TIMEOUT_MS` (1000) and `REDIS_COMMAND_TIMEOUT_MS` (500)
+    pub fn from_env() -> Self {
+        let budget = |name: &str, default: Duration| {
+            std::env::var(name)
+                .ok()
+                .and_then(|ms| ms.trim().parse::<u64>().ok())
+                .filter(|ms| *ms > 0)
+                .map_or(default, Duration::from_millis)
+        };
+        Self {
+            connect: budget("REDIS_CONNECT_TIMEOUT_MS", DEFAULT_CONNECT_TIMEOUT),
+            command: budget("REDIS_COMMAND_TIMEOUT_MS", DEFAULT_COMMAND_TIMEOUT),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RedisCache {
     client: Client,
     config: Option<RedisConfig>,
     prefix: Option<String>,
+    breaker: Arc<CircuitBreaker>,
+    timeouts: RedisTimeouts,
 }
 
 impl RedisCache {
     pub fn new(client: Client, config: Option<RedisConfig>) -> Self {
         // Just create the instance without validation
         // Connection validation will happen on first use
+        let breaker = Arc::new(CircuitBreaker::from_env("redis"));
+        breaker::register(breaker.clone());
         Self {
             client,
             config,
             prefix: None,
+            breaker,
+            timeouts: RedisTimeouts::from_env(),
         }
     }
 
@@ -81,34 +136,113 @@ impl RedisCache {
         &self.client
     }
 
-    // Cache a post by ID
-    pub async fn cache_post_by_id(&self, id: i64, json_data: &str) -> Result<(), RedisError> {
-        let key = format!("post:id:{}", id);
-        self.get_client()
-            .get_multiplexed_async_connection()
-            .await?
-            .set_ex(key, json_data, POST_CACHE_TTL_SECONDS)
+    /// Open a connection, failing at once while the circuit breaker is open. Connecting and
+    /// each command on the connection are limited by the configured `RedisTimeouts`.
+    pub async fn connection(&self) -> Result<MultiplexedConnection, RedisError> {
+        self.connect(Some(self.timeouts.command)).await
+    }
+
+    /// Open a connection for commands that wait on purpose, like blocking stream reads,
+    /// without a limit on how long commands take
+    pub async fn blocking_connection(&self) -> Result<MultiplexedConnection, RedisError> {
+        self.connect(None).await
+    }
+
+    async fn connect(
+        &self,
+        command_timeout: Option<Duration>,
+    ) -> Result<MultiplexedConnection, RedisError> {
+        if !self.breaker.allow() {
+            return Err(RedisError::from((
+                ErrorKind::IoError,
+                "Redis circuit breaker is open",
+            )));
+        }
+
+        let mut config = AsyncConnectionConfig::new().set_connection_timeout(self.timeouts.connect);
+        if let Some(timeout) = command_timeout {
+            config = config.set_response_timeout(timeout);
+        }
+        match self
+            .client
+            .get_multiplexed_async_connection_with_config(&config)
             .await
-            .map(|_: ()| ())
+        {
+            Ok(connection) => {
+                self.breaker.record_success();
+                Ok(connection)
+            }
+            Err(e) => {
+                self.breaker.record_failure();
+                Err(e)
+            }
+        }
     }
 
-    // Cache a post by slug
-    pub async fn cache_post_by_slug(&self, slug: &str, json_data: &str) -> Result<(), RedisError> {
-        let key = format!("post:slug:{}", slug);
-        self.get_client()
-            .get_multiplexed_async_connection()
-            .await?
-            .set_ex(key, json_data, POST_CACHE_TTL_SECONDS)
+    pub fn breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Check that Redis answers. While the breaker is open this fails at once, except when
+    /// it is time to probe.
+    pub async fn ping(&self) -> Result<(), RedisError> {
+        let mut connection = self.connection().await?;
+        redis::cmd("PING").query_async(&mut connection).await
+    }
+
+    // Store one rendering of a post; all variants of a post share a hash so they are invalidated together
+    async fn cache_post_variant(
+        &self,
+        key: String,
+        id: i64,
+        variant: &str,
+        json_data: &str,
+    ) -> Result<(), RedisError> {
+        let mut connection = self.connection().await?;
+
+        connection
+            .hset::<_, _, _, ()>(&key, variant, json_data)
+            .await?;
+        connection
+            .expire::<_, ()>(&key, POST_CACHE_TTL_SECONDS as i64)
+            .await?;
+
+        tag_entry(self, &key, &[SurrogateKey::Post(id)]).await
+    }
+
+    // Cache a post by ID, for readers of the given variant (see `PostViewer::cache_variant`)
+    pub async fn cache_post_by_id(
+        &self,
+        id: i64,
+        variant: &str,
+        json_data: &str,
+    ) -> Result<(), RedisError> {
+        self.cache_post_variant(blog_key(&format!("post:id:{}", id)), id, variant, json_data)
+            .await
+    }
+
+    // Cache post `id` by its slug, for readers of the given variant
+    pub async fn cache_post_by_slug(
+        &self,
+        id: i64,
+        slug: &str,
+        variant: &str,
+        json_data: &str,
+    ) -> Result<(), RedisError> {
+        self.cache_post_variant(slug_key(slug), id, variant, json_data)
             .await
-            .map(|_: ()| ())
     }
 
     // Get post by ID from cache
-    pub async fn get_post_by_id(&self, id: i64) -> Result<Option<String>, RedisError> {
-        let mut connection = self.client.get_multiplexed_async_connection().await?;
-        let key = format!("{}{}", POST_KEY_PREFIX, id);
+    pub async fn get_post_by_id(
+        &self,
+        id: i64,
+        variant: &str,
+    ) -> Result<Option<String>, RedisError> {
+        let mut connection = self.connection().await?;
+        let key = blog_key(&format!("post:id:{}", id));
 
-        let result: Option<String> = connection.get(key).await?;
+        let result: Option<String> = connection.hget(key, variant).await?;
 
         if result.is_some() {
             info!("Cache hit for post ID: {}", id);
@@ -120,11 +254,15 @@ impl RedisCache {
     }
 
     // Get post by slug from cache
-    pub async fn get_post_by_slug(&self, slug: &str) -> Result<Option<String>, RedisError> {
-        let mut connection = self.client.get_multiplexed_async_connection().await?;
-        let key = format!("{}{}", POST_KEY_PREFIX, slug);
+    pub async fn get_post_by_slug(
+        &self,
+        slug: &str,
+        variant: &str,
+    ) -> Result<Option<String>, RedisError> {
+        let mut connection = self.connection().await?;
+        let key = slug_key(slug);
 
-        let result: Option<String> = connection.get(key).await?;
+        let result: Option<String> = connection.hget(key, variant).await?;
 
         if result.is_some() {
             info!("Cache hit for post slug: {}", slug);
@@ -135,39 +273,94 @@ impl RedisCache {
         Ok(result)
     }
 
-    // Cache popular posts
-    pub async fn cache_popular_posts(&self, json_data: &str) -> Result<(), RedisError> {
-        self.get_client()
-            .get_multiplexed_async_connection()
-            .await?
-            .set_ex(POPULAR_POSTS_KEY, json_data, POPULAR_POSTS_TTL_SECONDS)
-            .await
-            .map(|_: ()| ())
+    // Cache one page of popular posts under the surrogate keys of its posts; pages share a hash
+    // so they are invalidated together
+    pub async fn cache_popular_posts(
+        &self,
+        page: &str,
+        json_data: &str,
+        keys: &[SurrogateKey],
+    ) -> Result<(), RedisError> {
+        let mut connection = self.connection().await?;
+
+        connection
+            .hset::<_, _, _, ()>(blog_key(POPULAR_POSTS_KEY), page, json_data)
+            .await?;
+        connection
+            .expire::<_, ()>(
+                blog_key(POPULAR_POSTS_KEY),
+                POPULAR_POSTS_TTL_SECONDS as i64,
+            )
+            .await?;
+
+        tag_entry(self, &blog_key(POPULAR_POSTS_KEY), keys).await
+    }
+
+    // Get one page of popular posts from cache
+    pub async fn get_popular_posts(&self, page: &str) -> Result<Option<String>, RedisError> {
+        let mut connection = self.connection().await?;
+
+        let result: Option<String> = connection.hget(blog_key(POPULAR_POSTS_KEY), page).await?;
+
+        if result.is_some() {
+            info!("Cache hit for popular posts page {}", page);
+        } else {
+            info!("Cache miss for popular posts page {}", page);
+        }
+
+        Ok(result)
     }
 
-    // Get popular posts from cache
-    pub async fn get_popular_posts(&self) -> Result<Option<String>, RedisError> {
-        let mut connection = self.client.get_multiplexed_async_connection().await?;
+    // Cache a part of the post archive, the month counts or a page of a month's posts, under
+    // the surrogate keys of what it shows; parts share a hash so they are invalidated together
+    pub async fn cache_post_archive(
+        &self,
+        field: &str,
+        json_data: &str,
+        keys: &[SurrogateKey],
+    ) -> Result<(), RedisError> {
+        let mut connection = self.connection().await?;
 
-        let result: Option<String> = connection.get(POPULAR_POSTS_KEY).await?;
+        connection
+            .hset::<_, _, _, ()>(blog_key(POST_ARCHIVE_KEY), field, json_data)
+            .await?;
+        connection
+            .expire::<_, ()>(blog_key(POST_ARCHIVE_KEY), POST_ARCHIVE_TTL_SECONDS as i64)
+            .await?;
+
+        tag_entry(self, &blog_key(POST_ARCHIVE_KEY), keys).await
+    }
+
+    // Get a part of the post archive from cache
+    pub async fn get_post_archive(&self, field: &str) -> Result<Option<String>, RedisError> {
+        let mut connection = self.connection().await?;
+
+        let result: Option<String> = connection.hget(blog_key(POST_ARCHIVE_KEY), field).await?;
 
         if result.is_some() {
-            info!("Cache hit for popular posts");
+            info!("Cache hit for post archive {}", field);
         } else {
-            info!("Cache miss for popular posts");
+            info!("Cache miss for post archive {}", field);
         }
 
         Ok(result)
     }
 
+    // Invalidate post archive cache
+    pub async fn invalidate_post_archive(&self) -> Result<(), RedisError> {
+        self.connection()
+            .await?
+            .del(blog_key(POST_ARCHIVE_KEY))
+            .await
+            .map(|_: ()| ())
+    }
+
     // Invalidate post cache
     pub async fn invalidate_post(&self, id: i64, slug: &str) -> Result<(), RedisError> {
-        let mut connection = self.get_client().get_multiplexed_async_connection().await?;
+        let mut connection = self.connection().await?;
 
-        let id_key = format!("post:id:{}", id);
-        let slug_key = format!("post:slug:{}", slug);
-
-        connection.del(&[id_key, slug_key]).await?;
+        let id_key = blog_key(&format!("post:id:{}", id));
+        connection.del(&[id_key, slug_key(slug)]).await?;
         info!(
             "Invalidated cache for post with ID: {} and slug: {}",
             id, slug
@@ -177,14 +370,56 @@ impl RedisCache {
 
     // Invalidate popular posts cache
     pub async fn invalidate_popular_posts(&self) -> Result<(), RedisError> {
-        self.get_client()
-            .get_multiplexed_async_connection()
+        self.connection()
             .await?
-            .del(POPULAR_POSTS_KEY)
+            .del(blog_key(POPULAR_POSTS_KEY))
             .await
             .map(|_: ()| ())
     }
 
+    // Store a post edit lock. With `only_if_absent` the lock is only written when nobody holds it.
+    pub async fn set_edit_lock(
+        &self,
+        post_id: i64,
+        json_data: &str,
+        ttl_seconds: u64,
+        only_if_absent: bool,
+    ) -> Result<bool, RedisError> {
+        let mut connection = self.connection().await?;
+        let key = format!("post:edit_lock:{}", post_id);
+
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(&key).arg(json_data).arg("EX").arg(ttl_seconds);
+        if only_if_absent {
+            cmd.arg("NX");
+        }
+
+        let result: Option<String> = cmd.query_async(&mut connection).await?;
+        Ok(result.is_some())
+    }
+
+    // Get the current edit lock of a post
+    pub async fn get_edit_lock(&self, post_id: i64) -> Result<Option<String>, RedisError> {
+        let key = format!("post:edit_lock:{}", post_id);
+        self.connection().await?.get(key).await
+    }
+
+    // Release the edit lock of a post if it is still the lock read as `json_data`, so a lock
+    // refreshed or taken over in the meantime is never released. Returns whether it was.
+    pub async fn release_edit_lock(
+        &self,
+        post_id: i64,
+        json_data: &str,
+    ) -> Result<bool, RedisError> {
+        let key = format!("post:edit_lock:{}", post_id);
+        let released: i64 = redis::Script::new(COMPARE_AND_DELETE_SCRIPT)
+            .key(key)
+            .arg(json_data)
+            .invoke_async(&mut self.connection().await?)
+            .await?;
+        Ok(released == 1)
+    }
+
     // Log a post view
     pub async fn log_post_view(
         &self,
@@ -192,14 +427,15 @@ impl RedisCache {
         user_id: Option<Uuid>,
         ip_hash: Option<String>,
     ) -> Result<(), RedisError> {
-        let mut connection = self.client.get_multiplexed_async_connection().await?;
-        let stream_key = "stream:post_views";
+        let mut connection = self.connection().await?;
+        let stream_key = POST_VIEWS_STREAM_KEY;
 
         // Create timestamp
         let timestamp = chrono::Utc::now().timestamp();
 
         // Prepare fields for the stream entry
         let mut fields = Vec::new();
+        fields.push(("blog_id", current_blog_id().to_string()));
         fields.push(("post_id", post_id.to_string()));
         fields.push(("timestamp", timestamp.to_string()));
 
@@ -223,7 +459,7 @@ impl RedisCache {
     // Increment post view count
     pub async fn increment_post_views(&self, post_id: i64) -> Result<(), RedisError> {
         let stats_key = format!("stats:post:{}", post_id);
-        let mut connection = self.get_client().get_multiplexed_async_connection().await?;
+        let mut connection = self.connection().await?;
 
         // Increment the view count in the hash
         connection.hincr(&stats_key, "views", 1).await?;
@@ -239,14 +475,10 @@ impl RedisCache {
 
     // Get post statistics
     pub async fn get_post_stats(&self, post_id: i64) -> Result<Option<PostStats>, CacheError> {
-        let mut connection = self
-            .client
-            .get_multiplexed_async_connection()
-            .await
-            .map_err(|e| {
-                error!("Redis connection error while getting post stats: {}", e);
-                CacheError::RedisError(e.to_string())
-            })?;
+        let mut connection = self.connection().await.map_err(|e| {
+            error!("Redis connection error while getting post stats: {}", e);
+            CacheError::RedisError(e.to_string())
+        })?;
 
         let cache_key = format!("post_stats:{}", post_id);
         let result: Option<String> = connection.get(&cache_key).await.map_err(|e| {
@@ -269,7 +501,7 @@ impl RedisCache {
     // Set post stats
     pub async fn set_post_stats(&self, post_id: i64, stats: &PostStats) -> Result<(), RedisError> {
         let stats_key = format!("stats:post:{}", post_id);
-        let mut connection = self.get_client().get_multiplexed_async_connection().await?;
+        let mut connection = self.connection().await?;
 
         // Convert PostStats to HashMap with safe conversions for Option types
         let mut fields = HashMap::new();
@@ -298,8 +530,7 @@ impl RedisCache {
     // Invalidate post stats
     pub async fn invalidate_post_stats(&self, post_id: i64) -> Result<(), RedisError> {
         let stats_key = format!("stats:post:{}", post_id);
-        self.get_client()
-            .get_multiplexed_async_connection()
+        self.connection()
             .await?
             .del(stats_key)
             .await
@@ -311,17 +542,13 @@ impl RedisCache {
         &self,
         user_id: Uuid,
     ) -> Result<Option<UserEngagement>, CacheError> {
-        let mut connection = self
-            .client
-            .get_multiplexed_async_connection()
-            .await
-            .map_err(|e| {
-                error!(
-                    "Redis connection error while getting user engagement: {}",
-                    e
-                );
-                CacheError::RedisError(e.to_string())
-            })?;
+        let mut connection = self.connection().await.map_err(|e| {
+            error!(
+                "Redis connection error while getting user engagement: {}",
+                e
+            );
+            CacheError::RedisError(e.to_string())
+        })?;
 
         let cache_key = format!("user_engagement:{}", user_id);
         let result: Option<String> = connection.get(&cache_key).await.map_err(|e| {
@@ -349,7 +576,7 @@ impl RedisCache {
         engagement: &UserEngagement,
     ) -> Result<(), RedisError> {
         let engagement_key = format!("engagement:user:{}:post:{}", user_id, post_id);
-        let mut connection = self.get_client().get_multiplexed_async_connection().await?;
+        let mut connection = self.connection().await?;
 
         // Convert UserEngagement to HashMap with safe conversions for Option types
         let mut fields = HashMap::new();
@@ -385,8 +612,7 @@ impl RedisCache {
         post_id: i64,
     ) -> Result<(), RedisError> {
         let engagement_key = format!("engagement:user:{}:post:{}", user_id, post_id);
-        self.get_client()
-            .get_multiplexed_async_connection()
+        self.connection()
             .await?
             .del(engagement_key)
             .await
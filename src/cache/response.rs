@@ -0,0 +1,352 @@
+//! HTTP caching of public GET routes.
+//!
+//! Wrapped routes tell browsers and shared caches (CDNs, reverse proxies) how long their
+//! responses stay fresh with `Cache-Control: public, max-age=…, s-maxage=…`. Responses may
+//! differ by viewer, so they are only public for anonymous requests: requests with an
+//! `Authorization` header get `Cache-Control: private, no-cache`, and every response carries
+//! `Vary: Authorization`. With `RESPONSE_CACHE_ENABLED=true` the server also keeps whole
+//! anonymous responses in Redis for `s-maxage`, keyed by blog, path and query, and marks
+//! them with `X-Cache: HIT` or `MISS`; they are recorded under the response's surrogate keys,
+//! so writes purge them (see `crate::cache::surrogate`). Without Redis, or when it fails,
+//! responses are built as usual.
+
+use crate::cache::redis::RedisCache;
+use crate::cache::surrogate::{tag_entry, SurrogateKeys};
+use crate::tenant::middleware::blog_key;
+use axum::{
+    body::{boxed, Full, HttpBody},
+    extract::{OriginalUri, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+
+pub const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+// Larger responses are passed through without being kept in Redis
+const MAX_CACHED_BODY_BYTES: usize = 1024 * 1024;
+
+/// How long responses of a route stay fresh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// Names the route's Redis keys and its environment variables
+    pub name: &'static str,
+    /// Freshness in browsers
+    pub max_age: Duration,
+    /// Freshness in shared caches and in the server's cache
+    pub s_maxage: Duration,
+}
+
+/// The post listing: a minute in browsers, five in shared caches
+pub const POST_LIST_CACHE: CachePolicy = CachePolicy {
+    name: "post_list",
+    max_age: Duration::from_secs(60),
+    s_maxage: Duration::from_secs(300),
+};
+
+/// Popular posts: a minute in browsers, five in shared caches
+pub const POPULAR_POSTS_CACHE: CachePolicy = CachePolicy {
+    name: "popular_posts",
+    max_age: Duration::from_secs(60),
+    s_maxage: Duration::from_secs(300),
+};
+
+/// The post archive and its months: five minutes in browsers, an hour in shared caches
+pub const POST_ARCHIVE_CACHE: CachePolicy = CachePolicy {
+    name: "post_archive",
+    max_age: Duration::from_secs(300),
+    s_maxage: Duration::from_secs(3600),
+};
+
+/// Public sitewide statistics: five minutes everywhere
+pub const PUBLIC_STATS_CACHE: CachePolicy = CachePolicy {
+    name: "public_stats",
+    max_age: Duration::from_secs(300),
+    s_maxage: Duration::from_secs(300),
+};
+
+impl CachePolicy {
+    /// The policy with `<NAME>_CACHE_MAX_AGE_SECONDS` and `<NAME>_CACHE_S_MAXAGE_SECONDS`
+    /// (e.g. `POPULAR_POSTS_CACHE_S_MAXAGE_SECONDS=600`) in place of its defaults; anything
+    /// but a number keeps the default, and 0 stops that kind of caching
+    pub fn configured(self) -> Self {
+        self.configured_with(|name| std::env::var(name).ok())
+    }
+
+    /// The policy with the settings `lookup` returns by variable name, as for `configured`
+    pub fn configured_with(self, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let setting = |suffix: &str| {
+            let name = format!("{}_CACHE_{}", self.name.to_uppercase(), suffix);
+            lookup(&name)
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(Duration::from_secs)
+        };
+
+        Self {
+            max_age: setting("MAX_AGE_SECONDS").unwrap_or(self.max_age),
+            s_maxage: setting("S_MAXAGE_SECONDS").unwrap_or(self.s_maxage),
+            ..self
+        }
+    }
+
+    /// `Cache-Control` of anonymous responses
+    pub fn cache_control(&self) -> String {
+        format!(
+            "public, max-age={}, s-maxage={}",
+            self.max_age.as_secs(),
+            self.s_maxage.as_secs()
+        )
+    }
+}
+
+/// A response kept in Redis
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl CachedResponse {
+    fn into_response(self) -> Response {
+        let mut response = Full::from(self.body).into_response();
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) =
+                (HeaderName::try_from(name), HeaderValue::try_from(value))
+            {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+    }
+}
+
+// Headers a cached response is stored with; those set per request are left out
+fn cacheable_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            ![
+                header::CACHE_CONTROL,
+                header::VARY,
+                header::SET_COOKIE,
+                header::CONTENT_LENGTH,
+                X_CACHE,
+            ]
+            .contains(name)
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Caches the responses of one route by a `CachePolicy`
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    redis_cache: Option<RedisCache>,
+    policy: CachePolicy,
+}
+
+impl ResponseCache {
+    /// Responses are kept in Redis only with `RESPONSE_CACHE_ENABLED=true`
+    pub fn new(redis_cache: Option<RedisCache>, policy: CachePolicy) -> Self {
+        let enabled = std::env::var("RESPONSE_CACHE_ENABLED")
+            .map(|value| value == "true")
+            .unwrap_or(false);
+        Self {
+            redis_cache: redis_cache.filter(|_| enabled && !policy.s_maxage.is_zero()),
+            policy,
+        }
+    }
+
+    fn key(&self, path_and_query: &str) -> String {
+        blog_key(&format!("response:{}:{}", self.policy.name, path_and_query))
+    }
+
+    async fn get(&self, cache: &RedisCache, key: &str) -> Result<Option<Response>, String> {
+        let mut conn = cache.connection().await.map_err(|e| e.to_string())?;
+        let Some(data) = conn
+            .get::<_, Option<String>>(key)
+            .await
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(None);
+        };
+        let cached: CachedResponse = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+        Ok(Some(cached.into_response()))
+    }
+
+    async fn set(
+        &self,
+        cache: &RedisCache,
+        key: &str,
+        cached: &CachedResponse,
+    ) -> Result<(), String> {
+        let data = serde_json::to_string(cached).map_err(|e| e.to_string())?;
+        let mut conn = cache.connection().await.map_err(|e| e.to_string())?;
+        conn.set_ex::<_, _, ()>(key, data, self.policy.s_maxage.as_secs())
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    // Let shared caches keep successful anonymous responses
+    fn finish(&self, mut response: Response, x_cache: Option<&'static str>) -> Response {
+        let public = response_is_public(response.status());
+        let headers = response.headers_mut();
+        if public {
+            if let Ok(value) = HeaderValue::try_from(self.policy.cache_control()) {
+                headers.insert(header::CACHE_CONTROL, value);
+            }
+        }
+        headers.insert(header::VARY, HeaderValue::from_static("Authorization"));
+        if let Some(x_cache) = x_cache {
+            headers.insert(X_CACHE, HeaderValue::from_static(x_cache));
+        }
+        response
+    }
+}
+
+fn response_is_public(status: StatusCode) -> bool {
+    status == StatusCode::OK
+}
+
+/// Middleware caching the responses of the GET route it wraps by a `ResponseCache`
+pub async fn cache_response<B>(
+    State(cache): State<Arc<ResponseCache>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if req.headers().contains_key(header::AUTHORIZATION) {
+        let mut response = next.run(req).await;
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, no-cache"),
+        );
+        headers.insert(header::VARY, HeaderValue::from_static("Authorization"));
+        return response;
+    }
+
+    let Some(redis_cache) = cache.redis_cache.as_ref() else {
+        let response = next.run(req).await;
+        return cache.finish(response, None);
+    };
+
+    // Keyed by the path as requested, as `Link` headers point back at it
+    let uri = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|OriginalUri(uri)| uri.clone())
+        .unwrap_or_else(|| req.uri().clone());
+    let key = cache.key(
+        uri.path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or_else(|| uri.path()),
+    );
+
+    match cache.get(redis_cache, &key).await {
+        Ok(Some(response)) => return cache.finish(response, Some("HIT")),
+        Ok(None) => {}
+        Err(e) => error!(
+            "Failed to read cached {} response: {}",
+            cache.policy.name, e
+        ),
+    }
+
+    let response = next.run(req).await;
+    if !response_is_public(response.status()) {
+        return cache.finish(response, None);
+    }
+
+    let (parts, mut body) = response.into_parts();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        match chunk {
+            Ok(chunk) => bytes.extend_from_slice(&chunk),
+            Err(e) => {
+                error!("Failed to read {} response: {}", cache.policy.name, e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+
+    if bytes.len() <= MAX_CACHED_BODY_BYTES {
+        match String::from_utf8(bytes.clone()) {
+            Ok(body) => {
+                let cached = CachedResponse {
+                    status: parts.status.as_u16(),
+                    headers: cacheable_headers(&parts.headers),
+                    body,
+                };
+                if let Err(e) = cache.set(redis_cache, &key, &cached).await {
+                    error!("Failed to cache {} response: {}", cache.policy.name, e);
+                } else if let Some(SurrogateKeys(keys)) = parts.extensions.get() {
+                    if let Err(e) = tag_entry(redis_cache, &key, keys).await {
+                        error!("Failed to tag cached {} response: {}", cache.policy.name, e);
+                    }
+                }
+            }
+            Err(_) => warn!("Not caching binary {} response", cache.policy.name),
+        }
+    }
+
+    let response = Response::from_parts(parts, boxed(Full::from(bytes)));
+    cache.finish(response, Some("MISS"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_cache_policy_configured() {
+        let policy = CachePolicy {
+            name: "response_cache_test",
+            max_age: Duration::from_secs(60),
+            s_maxage: Duration::from_secs(300),
+        };
+        assert_eq!(policy.cache_control(), "public, max-age=60, s-maxage=300");
+
+        let settings = HashMap::from([
+            ("RESPONSE_CACHE_TEST_CACHE_MAX_AGE_SECONDS", "0"),
+            ("RESPONSE_CACHE_TEST_CACHE_S_MAXAGE_SECONDS", "soon"),
+        ]);
+        let configured =
+            policy.configured_with(|name| settings.get(name).map(|value| value.to_string()));
+        assert_eq!(configured.max_age, Duration::ZERO);
+        assert_eq!(configured.s_maxage, Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_cacheable_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            header::LINK,
+            HeaderValue::from_static("</api/v1/posts>; rel=\"next\""),
+        );
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        headers.insert(header::SET_COOKIE, HeaderValue::from_static("session=1"));
+
+        let mut cached = cacheable_headers(&headers);
+        cached.sort();
+        assert_eq!(
+            cached,
+            vec![
+                ("content-type".to_string(), "application/json".to_string()),
+                (
+                    "link".to_string(),
+                    "</api/v1/posts>; rel=\"next\"".to_string()
+                ),
+            ]
+        );
+    }
+}
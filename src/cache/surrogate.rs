@@ -0,0 +1,217 @@
+//! Surrogate keys: tags naming what cached content shows, so it can be purged when that
+//! changes instead of waiting for it to expire.
+//!
+//! Handlers return the keys of a response as `SurrogateKeys`, which sets them, scoped to the
+//! blog, in the `Surrogate-Key` (Fastly) and `Cache-Tag` (Cloudflare) headers. Redis entries
+//! are recorded under their keys when cached, in a set per key. Writes call `purge` with the
+//! keys they affect, which deletes the Redis entries and asks the CDN configured with
+//! `CDN_PROVIDER` (see `cdn_purger_from_env`) to drop its copies.
+
+use crate::cache::cdn::{cdn_purger_from_env, CdnPurger};
+use crate::cache::redis::RedisCache;
+use crate::tenant::middleware::blog_key;
+use axum::http::{HeaderName, HeaderValue};
+use axum::response::{IntoResponseParts, ResponseParts};
+use redis::{AsyncCommands, RedisError};
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use tracing::{error, info};
+use uuid::Uuid;
+
+pub const SURROGATE_KEY: HeaderName = HeaderName::from_static("surrogate-key");
+pub const CACHE_TAG: HeaderName = HeaderName::from_static("cache-tag");
+// The sets of entries outlive the entries they list
+const SURROGATE_SET_TTL_SECONDS: i64 = 86400; // 24 hours
+
+/// What cached content shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SurrogateKey {
+    /// Every listing of posts, which a new or removed post may enter or leave
+    Posts,
+    Post(i64),
+    /// Content showing a tag, by name
+    Tag(String),
+    /// Content showing a user's name or avatar
+    User(Uuid),
+}
+
+// Tag names are matched ignoring case and accents, and keys hold no spaces, commas or
+// non-ASCII characters, which CDNs do not accept
+fn tag_key(name: &str) -> String {
+    let mut key = String::new();
+    for c in crate::slug::match_key(name.trim()).chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+            key.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                key.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    key
+}
+
+impl fmt::Display for SurrogateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SurrogateKey::Posts => write!(f, "posts"),
+            SurrogateKey::Post(id) => write!(f, "post:{}", id),
+            SurrogateKey::Tag(name) => write!(f, "tag:{}", tag_key(name)),
+            SurrogateKey::User(id) => write!(f, "user:{}", id),
+        }
+    }
+}
+
+// Keys as sent to CDNs, which may serve several blogs: prefixed with the blog, without
+// duplicates
+fn scoped_keys(keys: &[SurrogateKey]) -> Vec<String> {
+    let mut scoped: Vec<String> = Vec::new();
+    for key in keys {
+        let key = blog_key(&key.to_string());
+        if !scoped.contains(&key) {
+            scoped.push(key);
+        }
+    }
+    scoped
+}
+
+/// Surrogate keys of a response, set in its headers and kept in its extensions for the
+/// response cache
+#[derive(Debug, Clone, Default)]
+pub struct SurrogateKeys(pub Vec<SurrogateKey>);
+
+impl IntoResponseParts for SurrogateKeys {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let scoped = scoped_keys(&self.0);
+        if !scoped.is_empty() {
+            if let Ok(value) = HeaderValue::try_from(scoped.join(" ")) {
+                res.headers_mut().insert(SURROGATE_KEY, value);
+            }
+            if let Ok(value) = HeaderValue::try_from(scoped.join(",")) {
+                res.headers_mut().insert(CACHE_TAG, value);
+            }
+        }
+        res.extensions_mut().insert(self);
+        Ok(res)
+    }
+}
+
+// Redis set of the entries cached under a key
+fn set_key(key: &SurrogateKey) -> String {
+    blog_key(&format!("surrogate:{}", key))
+}
+
+/// Record the Redis entry at `entry_key` under surrogate keys, so purging any of them
+/// deletes it
+pub async fn tag_entry(
+    cache: &RedisCache,
+    entry_key: &str,
+    keys: &[SurrogateKey],
+) -> Result<(), RedisError> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = cache.connection().await?;
+    let mut pipe = redis::pipe();
+    for key in keys {
+        let set = set_key(key);
+        pipe.sadd(&set, entry_key)
+            .ignore()
+            .expire(&set, SURROGATE_SET_TTL_SECONDS)
+            .ignore();
+    }
+    pipe.query_async(&mut conn).await
+}
+
+async fn purge_redis(cache: &RedisCache, keys: &[SurrogateKey]) -> Result<usize, RedisError> {
+    let mut conn = cache.connection().await?;
+    let mut purged = 0;
+    for key in keys {
+        let set = set_key(key);
+        let mut entries: Vec<String> = conn.smembers(&set).await?;
+        purged += entries.len();
+        entries.push(set);
+        conn.del::<_, ()>(entries).await?;
+    }
+    Ok(purged)
+}
+
+// The CDN purges are sent to, set up on first use
+fn cdn_purger() -> Option<&'static Arc<dyn CdnPurger>> {
+    static PURGER: OnceLock<Option<Arc<dyn CdnPurger>>> = OnceLock::new();
+    PURGER.get_or_init(cdn_purger_from_env).as_ref()
+}
+
+/// Drop what is cached under the keys of the current blog: the Redis entries recorded under
+/// them, and, in the background, the CDN's copies. Failures are logged; the content then
+/// expires as usual.
+pub async fn purge(redis_cache: Option<&RedisCache>, keys: &[SurrogateKey]) {
+    if keys.is_empty() {
+        return;
+    }
+
+    if let Some(cache) = redis_cache {
+        match purge_redis(cache, keys).await {
+            Ok(purged) => info!("Purged {} cache entries of {:?}", purged, keys),
+            Err(e) => error!("Failed to purge cache entries of {:?}: {}", keys, e),
+        }
+    }
+
+    if let Some(purger) = cdn_purger() {
+        let purger = purger.clone();
+        let scoped = scoped_keys(keys);
+        tokio::spawn(async move {
+            if let Err(e) = purger.purge(&scoped).await {
+                error!(
+                    "Failed to purge {} from {}: {}",
+                    scoped.join(" "),
+                    purger.name(),
+                    e
+                );
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_surrogate_key_format() {
+        assert_eq!(SurrogateKey::Posts.to_string(), "posts");
+        assert_eq!(SurrogateKey::Post(42).to_string(), "post:42");
+        assert_eq!(
+            SurrogateKey::Tag(" Crème Brûlée ".to_string()).to_string(),
+            "tag:creme%20brulee"
+        );
+        assert_eq!(
+            SurrogateKey::Tag("C++, Rust".to_string()).to_string(),
+            "tag:c%2B%2B%2C%20rust"
+        );
+        assert_eq!(
+            SurrogateKey::Tag("日本".to_string()).to_string(),
+            "tag:%E6%97%A5%E6%9C%AC"
+        );
+    }
+
+    #[test]
+    fn test_scoped_keys() {
+        let keys = [
+            SurrogateKey::Post(1),
+            SurrogateKey::Tag("Rust".to_string()),
+            SurrogateKey::Post(1),
+            SurrogateKey::Tag("rust".to_string()),
+        ];
+        // Outside a request, keys are those of the default blog
+        assert_eq!(
+            scoped_keys(&keys),
+            vec![blog_key("post:1"), blog_key("tag:rust")]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::analytics::service::AnalyticsService;
+use crate::cache::redis::RedisCache;
+use crate::db::router::DbRouter;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::pagination::Pagination;
+use crate::post::service::PostService;
+use crate::tenant::middleware::with_blog_id;
+use crate::tenant::service::TenantService;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Kind of the job that fills the Redis cache of every blog
+pub const CACHE_WARM_JOB: &str = "cache_warm";
+
+// Instances started together, as in a deploy, queue one warming between them
+const STARTUP_WARM_INTERVAL: Duration = Duration::from_secs(5 * 60);
+// Page size the popular and archive post lists are served with by default
+const LIST_PAGE_SIZE: i64 = 10;
+// Popular post pages warmed per blog
+const POPULAR_PAGES: i64 = 3;
+
+/// Fills the Redis cache with what most requests read first, so the first readers after a
+/// deploy or a cache flush don't pay for cold queries.
+///
+/// For every blog it warms the first pages of popular posts and the post archive, as
+/// anonymous readers get them, and the public stats widget. Entries already cached are left
+/// as they are. Warming runs as a background job, queued on startup when
+/// `CACHE_WARM_ON_STARTUP` is set and by admins at any time.
+pub struct CacheWarmer {
+    db: DbRouter,
+    redis_cache: RedisCache,
+    tenants: TenantService,
+}
+
+impl CacheWarmer {
+    pub fn new(db: DbRouter, redis_cache: RedisCache) -> Self {
+        Self {
+            tenants: TenantService::new(db.primary().clone()),
+            db,
+            redis_cache,
+        }
+    }
+
+    /// Queue a warming if `CACHE_WARM_ON_STARTUP` is `true`
+    pub fn warm_on_startup(&self, jobs: Arc<JobService>) {
+        let enabled = std::env::var("CACHE_WARM_ON_STARTUP")
+            .map(|value| value == "true")
+            .unwrap_or(false);
+        if !enabled {
+            return;
+        }
+
+        tokio::spawn(async move {
+            if let Err(e) = jobs
+                .enqueue_unless_recent(CACHE_WARM_JOB, json!({}), STARTUP_WARM_INTERVAL)
+                .await
+            {
+                warn!("Failed to queue cache warming: {}", e);
+            }
+        });
+    }
+
+    /// Warm the cache of every blog. Returns how many blogs were warmed, and how many cache
+    /// entries were filled and failed.
+    pub async fn warm_all(&self) -> Result<(u64, u64, u64), JobError> {
+        let blogs = self
+            .tenants
+            .list_blogs()
+            .await
+            .map_err(|e| JobError::InternalError(e.to_string()))?;
+
+        let (mut warmed, mut failed) = (0, 0);
+        for blog in &blogs {
+            let (blog_warmed, blog_failed) = with_blog_id(blog.id, self.warm_blog()).await;
+            warmed += blog_warmed;
+            failed += blog_failed;
+        }
+
+        info!(
+            "Warmed {} cache entries of {} blogs, {} failed",
+            warmed,
+            blogs.len(),
+            failed
+        );
+        Ok((blogs.len() as u64, warmed, failed))
+    }
+
+    // Warm the cache of the current blog. A failed entry is logged and the rest still warmed.
+    async fn warm_blog(&self) -> (u64, u64) {
+        let (mut warmed, mut failed) = (0, 0);
+        let mut tally = |what: &str, result: Result<(), String>| match result {
+            Ok(()) => warmed += 1,
+            Err(e) => {
+                warn!("Failed to warm {}: {}", what, e);
+                failed += 1;
+            }
+        };
+
+        let posts = PostService::new(self.db.clone(), Some(self.redis_cache.clone()));
+        let viewer = match posts.viewer(None).await {
+            Ok(viewer) => viewer,
+            Err(e) => {
+                tally("post lists", Err(e.to_string()));
+                return (warmed, failed);
+            }
+        };
+
+        for page in 0..POPULAR_PAGES {
+            let pagination = Pagination {
+                limit: LIST_PAGE_SIZE,
+                offset: page * LIST_PAGE_SIZE,
+            };
+            match posts.get_popular_posts(&pagination, &viewer).await {
+                Ok(page_posts) => {
+                    tally("popular posts", Ok(()));
+                    if (page_posts.len() as i64) < LIST_PAGE_SIZE {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    tally("popular posts", Err(e.to_string()));
+                    break;
+                }
+            }
+        }
+
+        match posts.get_archive().await {
+            Ok(months) => {
+                tally("post archive", Ok(()));
+                if let Some(latest) = months.first() {
+                    let pagination = Pagination {
+                        limit: LIST_PAGE_SIZE,
+                        offset: 0,
+                    };
+                    let result = posts
+                        .get_archive_posts(latest.year, latest.month as u32, &pagination, &viewer)
+                        .await;
+                    tally(
+                        "archive posts",
+                        result.map(|_| ()).map_err(|e| e.to_string()),
+                    );
+                }
+            }
+            Err(e) => tally("post archive", Err(e.to_string())),
+        }
+
+        let analytics = AnalyticsService::new(self.db.clone(), Some(self.redis_cache.clone()));
+        let result = analytics.get_public_stats().await;
+        tally(
+            "public stats",
+            result.map(|_| ()).map_err(|e| e.to_string()),
+        );
+
+        (warmed, failed)
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let (blogs, warmed, failed) = self.warm_all().await?;
+        Ok(json!({ "blogs": blogs, "warmed": warmed, "failed": failed }))
+    }
+}
+
+impl JobHandler for CacheWarmer {
+    fn kind(&self) -> &'static str {
+        CACHE_WARM_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
@@ -0,0 +1,70 @@
+use crate::comment::model::CommentError;
+use serde::Deserialize;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// hCaptcha-compatible verification endpoint used when `CAPTCHA_VERIFY_URL` is not set
+const DEFAULT_CAPTCHA_VERIFY_URL: &str = "https://hcaptcha.com/siteverify";
+
+/// How long a verification request may take, including reading the response
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct CaptchaVerifyResponse {
+    success: bool,
+}
+
+/// Client shared by all verifications, so connections to the provider are reused
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(VERIFY_TIMEOUT)
+            .build()
+            .expect("Failed to build captcha HTTP client")
+    })
+}
+
+/// Verify a captcha token against the configured provider.
+///
+/// Works with any provider speaking the hCaptcha/reCAPTCHA `siteverify` protocol.
+/// Fails closed: a missing `CAPTCHA_SECRET` or an unreachable or slow provider rejects the
+/// token.
+pub async fn verify_captcha(token: &str) -> Result<(), CommentError> {
+    if token.trim().is_empty() {
+        return Err(CommentError::CaptchaFailed);
+    }
+
+    let secret = match std::env::var("CAPTCHA_SECRET") {
+        Ok(secret) => secret,
+        Err(_) => {
+            error!("CAPTCHA_SECRET is not set, rejecting guest comment");
+            return Err(CommentError::CaptchaFailed);
+        }
+    };
+    let verify_url = std::env::var("CAPTCHA_VERIFY_URL")
+        .unwrap_or_else(|_| DEFAULT_CAPTCHA_VERIFY_URL.to_string());
+
+    let response = client()
+        .post(&verify_url)
+        .form(&[("secret", secret.as_str()), ("response", token)])
+        .send()
+        .await
+        .map_err(|e| {
+            warn!("Captcha verification request failed: {}", e);
+            CommentError::CaptchaFailed
+        })?
+        .json::<CaptchaVerifyResponse>()
+        .await
+        .map_err(|e| {
+            warn!("Invalid captcha verification response: {}", e);
+            CommentError::CaptchaFailed
+        })?;
+
+    if response.success {
+        Ok(())
+    } else {
+        Err(CommentError::CaptchaFailed)
+    }
+}
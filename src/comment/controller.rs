@@ -1,26 +1,29 @@
+use crate::auth::jwt::Role;
 use crate::auth::middleware::AuthUser;
 use crate::comment::model::{
-    CommentError, CommentErrorResponse, CommentsListResponse, CreateCommentRequest,
+    CommentError, CommentErrorResponse, CommentLockResponse, CommentPermalinkParams,
+    CommentPermalinkResponse, CommentPinResponse, CommentReactionRequest, CommentReportRequest,
+    CommentsListResponse, CreateCommentRequest, CreateGuestCommentRequest, ShadowBanRequest,
+    ShadowBanResponse, ThreadSubscriptionResponse,
 };
 use crate::comment::service::CommentService;
+use crate::feature_flags::model::ANONYMOUS_COMMENTS_FLAG;
+use crate::feature_flags::service::FeatureFlagService;
+use crate::link_preview::service::LinkPreviewService;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::quota::controller::quota_error_response;
+use crate::quota::model::QuotaKind;
+use crate::quota::service::QuotaService;
 use axum::http::header::HeaderMap;
 use axum::{
-    extract::{Extension, Path, Query},
+    extract::{Extension, OriginalUri, Path, Query},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use serde::Deserialize;
 use std::sync::Arc;
 use tracing::{error, info};
-use utoipa::{IntoParams, ToSchema};
-
-// Query parameters for pagination
-#[derive(Debug, Deserialize, ToSchema, IntoParams)]
-pub struct CommentsQueryParams {
-    #[schema(example = "1")]
-    page: Option<i64>,
-}
+use uuid::Uuid;
 
 // Helper function to convert CommentError to HTTP response
 fn comment_error_to_response(err: CommentError) -> (StatusCode, Json<CommentErrorResponse>) {
@@ -43,6 +46,7 @@ fn comment_error_to_response(err: CommentError) -> (StatusCode, Json<CommentErro
         }
         CommentError::NotFound => (StatusCode::NOT_FOUND, "Comment not found", "NOT_FOUND"),
         CommentError::PostNotFound => (StatusCode::NOT_FOUND, "Post not found", "POST_NOT_FOUND"),
+        CommentError::UserNotFound => (StatusCode::NOT_FOUND, "User not found", "USER_NOT_FOUND"),
         CommentError::ParentCommentNotFound => (
             StatusCode::NOT_FOUND,
             "Parent comment not found",
@@ -53,11 +57,6 @@ fn comment_error_to_response(err: CommentError) -> (StatusCode, Json<CommentErro
             "Not authorized to perform this action",
             "UNAUTHORIZED",
         ),
-        CommentError::RateLimitExceeded => (
-            StatusCode::TOO_MANY_REQUESTS,
-            "Rate limit exceeded, please try again later",
-            "RATE_LIMITED",
-        ),
         CommentError::MaxNestingDepthReached => (
             StatusCode::BAD_REQUEST,
             "Maximum nesting depth reached for comments",
@@ -76,6 +75,26 @@ fn comment_error_to_response(err: CommentError) -> (StatusCode, Json<CommentErro
             "Failed to process comment data",
             "DESERIALIZATION_ERROR",
         ),
+        CommentError::GuestCommentsDisabled => (
+            StatusCode::FORBIDDEN,
+            "Guest comments are disabled",
+            "GUEST_COMMENTS_DISABLED",
+        ),
+        CommentError::CaptchaFailed => (
+            StatusCode::BAD_REQUEST,
+            "Captcha verification failed",
+            "CAPTCHA_FAILED",
+        ),
+        CommentError::CommentsLocked => (
+            StatusCode::FORBIDDEN,
+            "Comments are locked on this post",
+            "COMMENTS_LOCKED",
+        ),
+        CommentError::CommentLimitReached => (
+            StatusCode::FORBIDDEN,
+            "This post has reached its comment limit",
+            "COMMENT_LIMIT_REACHED",
+        ),
         CommentError::InternalError(_) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             "Internal server error",
@@ -93,7 +112,11 @@ fn comment_error_to_response(err: CommentError) -> (StatusCode, Json<CommentErro
 
 /// Create a new comment for a post
 ///
-/// This endpoint allows authenticated users to add a comment to a specific post.
+/// This endpoint allows authenticated users to add a comment to a specific post. Counts against
+/// the daily comments quota of the user's role; a used-up quota is reported with a `quota`
+/// object next to the error. Comments are also rate limited, by default to one every 100
+/// seconds: responses carry `X-RateLimit-*` headers, a refusal reports a `rate_limit` object,
+/// and comments that fail do not count.
 #[utoipa::path(
     post,
     path = "/api/posts/{id}/comments",
@@ -105,9 +128,10 @@ fn comment_error_to_response(err: CommentError) -> (StatusCode, Json<CommentErro
     responses(
         (status = 201, description = "Comment created successfully", body = CommentResponse),
         (status = 400, description = "Invalid input", body = CommentErrorResponse),
-        (status = 401, description = "Unauthorized", body = CommentErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 403, description = "Comments are locked or at the post's limit", body = CommentErrorResponse),
         (status = 404, description = "Post not found", body = CommentErrorResponse),
-        (status = 429, description = "Rate limit exceeded", body = CommentErrorResponse),
+        (status = 429, description = "Rate limit or daily comments quota exceeded", body = CommentErrorResponse),
         (status = 500, description = "Internal server error", body = CommentErrorResponse)
     ),
     security(
@@ -118,6 +142,8 @@ pub async fn create_comment(
     Path(post_id): Path<i64>,
     Extension(user): Extension<AuthUser>,
     Extension(comment_service): Extension<Arc<CommentService>>,
+    Extension(link_previews): Extension<Arc<LinkPreviewService>>,
+    Extension(quotas): Extension<Arc<QuotaService>>,
     Json(comment_data): Json<CreateCommentRequest>,
 ) -> impl IntoResponse {
     info!(
@@ -140,31 +166,113 @@ pub async fn create_comment(
         .into_response();
     }
 
+    if let Err(e) = quotas.consume(&user, QuotaKind::Comments).await {
+        return quota_error_response(e);
+    }
+
+    let content = comment_data.content.clone();
     match comment_service
         .create_comment(post_id, user.user_id, comment_data)
         .await
     {
         Ok(comment) => {
             info!("Successfully created comment with ID: {}", comment.id);
+            link_previews.enqueue_from_content(&content);
             (StatusCode::CREATED, Json(comment)).into_response()
         }
+        Err(e) => {
+            quotas.refund(user.user_id, QuotaKind::Comments).await;
+            comment_error_to_response(e).into_response()
+        }
+    }
+}
+
+/// Create a guest comment for a post
+///
+/// This endpoint lets visitors without an account comment on a post when guest commenting
+/// is enabled, by config or the `anonymous_comments` feature flag. A valid captcha token is required and the comment is held for moderation.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/comments/guest",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the post to comment on")
+    ),
+    request_body = CreateGuestCommentRequest,
+    responses(
+        (status = 202, description = "Comment accepted for moderation", body = CommentResponse),
+        (status = 400, description = "Invalid input or captcha", body = CommentErrorResponse),
+        (status = 403, description = "Guest comments are disabled, or comments are locked or at the post's limit", body = CommentErrorResponse),
+        (status = 404, description = "Post not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    )
+)]
+pub async fn create_guest_comment(
+    Path(post_id): Path<i64>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+    Extension(feature_flags): Extension<Arc<FeatureFlagService>>,
+    Json(comment_data): Json<CreateGuestCommentRequest>,
+) -> impl IntoResponse {
+    info!("Creating guest comment for post: {}", post_id);
+
+    // Validate input
+    let name = comment_data.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return comment_error_to_response(CommentError::ValidationError(
+            "Name must be between 1 and 100 characters".to_string(),
+        ))
+        .into_response();
+    }
+
+    let email = comment_data.email.trim();
+    if email.len() > 255 || !email.contains('@') {
+        return comment_error_to_response(CommentError::ValidationError(
+            "A valid email address is required".to_string(),
+        ))
+        .into_response();
+    }
+
+    if comment_data.content.trim().is_empty() {
+        return comment_error_to_response(CommentError::ValidationError(
+            "Comment content cannot be empty".to_string(),
+        ))
+        .into_response();
+    }
+
+    if comment_data.content.len() > 5000 {
+        return comment_error_to_response(CommentError::ValidationError(
+            "Comment content exceeds maximum length".to_string(),
+        ))
+        .into_response();
+    }
+
+    let flag_enabled = feature_flags
+        .is_enabled(ANONYMOUS_COMMENTS_FLAG, None)
+        .await;
+    match comment_service
+        .create_guest_comment(post_id, comment_data, flag_enabled)
+        .await
+    {
+        Ok(comment) => (StatusCode::ACCEPTED, Json(comment)).into_response(),
         Err(e) => comment_error_to_response(e).into_response(),
     }
 }
 
 /// Get comments for a post
 ///
-/// This endpoint retrieves all comments for a specific post, with optional pagination.
+/// This endpoint retrieves a page of root comments (with their replies) for a specific post.
+/// Further pages are linked from the `Link` response header.
 #[utoipa::path(
     get,
     path = "/api/posts/{id}/comments",
     tag = "comments",
     params(
         ("id" = i64, Path, description = "The ID of the post to get comments for"),
-        ("page" = Option<i64>, Query, description = "Page number for pagination", example = "1")
+        PageParams
     ),
     responses(
         (status = 200, description = "Comments retrieved successfully", body = CommentsListResponse),
+        (status = 400, description = "Invalid pagination cursor", body = CommentErrorResponse),
         (status = 404, description = "Post not found", body = CommentErrorResponse),
         (status = 500, description = "Internal server error", body = CommentErrorResponse)
     ),
@@ -174,13 +282,22 @@ pub async fn create_comment(
 )]
 pub async fn get_post_comments(
     Path(post_id): Path<i64>,
+    Extension(user): Extension<Option<AuthUser>>,
+    OriginalUri(uri): OriginalUri,
     Extension(comment_service): Extension<Arc<CommentService>>,
-    Query(params): Query<CommentsQueryParams>,
-) -> Result<(StatusCode, Json<CommentsListResponse>), (StatusCode, Json<CommentErrorResponse>)> {
+    Query(params): Query<PageParams>,
+) -> Result<
+    (StatusCode, HeaderMap, Json<CommentsListResponse>),
+    (StatusCode, Json<CommentErrorResponse>),
+> {
     info!("Getting comments for post: {}", post_id);
 
+    let pagination = params
+        .pagination(DEFAULT_PAGE_SIZE)
+        .map_err(|e| comment_error_to_response(CommentError::ValidationError(e.to_string())))?;
+
     match comment_service
-        .get_post_comments(post_id, params.page, true)
+        .get_post_comments(post_id, &pagination, true, user.map(|u| u.user_id))
         .await
     {
         Ok(comments) => {
@@ -192,12 +309,14 @@ pub async fn get_post_comments(
                 }
             };
 
+            let headers = pagination.headers(&uri, comments.len());
             let response = CommentsListResponse {
+                next_cursor: pagination.next_cursor(comments.len()),
                 comments,
                 total_count,
             };
 
-            Ok((StatusCode::OK, Json(response)))
+            Ok((StatusCode::OK, headers, Json(response)))
         }
         Err(err) => {
             error!("Error getting comments: {:?}", err);
@@ -206,6 +325,46 @@ pub async fn get_post_comments(
     }
 }
 
+/// Get a comment by ID
+///
+/// Returns the comment with its post, the root comment of its thread and the page of the post's
+/// comments the thread is on for the given page size, so clients can jump to it from a
+/// notification. Comments hidden from the viewer's listing are not found.
+#[utoipa::path(
+    get,
+    path = "/api/comments/{id}",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the comment"),
+        CommentPermalinkParams
+    ),
+    responses(
+        (status = 200, description = "Comment found", body = CommentPermalinkResponse),
+        (status = 404, description = "Comment not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_comment(
+    Path(comment_id): Path<i64>,
+    Query(params): Query<CommentPermalinkParams>,
+    Extension(user): Extension<Option<AuthUser>>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> Result<Json<CommentPermalinkResponse>, (StatusCode, Json<CommentErrorResponse>)> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    comment_service
+        .get_comment_permalink(comment_id, limit, user.map(|u| u.user_id))
+        .await
+        .map(Json)
+        .map_err(comment_error_to_response)
+}
+
 /// Delete a comment
 ///
 /// This endpoint allows users to delete their own comments or admins to delete any comment.
@@ -218,7 +377,7 @@ pub async fn get_post_comments(
     ),
     responses(
         (status = 204, description = "Comment deleted successfully"),
-        (status = 401, description = "Unauthorized", body = CommentErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
         (status = 404, description = "Comment not found", body = CommentErrorResponse),
         (status = 500, description = "Internal server error", body = CommentErrorResponse)
     ),
@@ -248,3 +407,522 @@ pub async fn delete_comment(
         Err(e) => comment_error_to_response(e).into_response(),
     }
 }
+
+/// Pin a comment
+///
+/// Pins a top-level comment above the rest of the post's comments, replacing any comment
+/// pinned before. Only the post author or an admin can pin comments.
+#[utoipa::path(
+    post,
+    path = "/api/comments/{id}/pin",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the comment to pin")
+    ),
+    responses(
+        (status = 200, description = "Comment pinned", body = CommentPinResponse),
+        (status = 400, description = "Only top-level comments can be pinned", body = CommentErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Comment not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn pin_comment(
+    Path(comment_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    set_comment_pinned(comment_id, user, comment_service, true).await
+}
+
+/// Unpin a comment
+///
+/// Only the post author or an admin can unpin comments.
+#[utoipa::path(
+    delete,
+    path = "/api/comments/{id}/pin",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the comment to unpin")
+    ),
+    responses(
+        (status = 200, description = "Comment unpinned", body = CommentPinResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Comment not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn unpin_comment(
+    Path(comment_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    set_comment_pinned(comment_id, user, comment_service, false).await
+}
+
+// Shared body of the pin/unpin handlers
+async fn set_comment_pinned(
+    comment_id: i64,
+    user: AuthUser,
+    comment_service: Arc<CommentService>,
+    pinned: bool,
+) -> axum::response::Response {
+    let is_admin = user.role == Role::Admin;
+
+    match comment_service
+        .set_comment_pinned(comment_id, user.user_id, is_admin, pinned)
+        .await
+    {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(CommentPinResponse { comment_id, pinned }),
+        )
+            .into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// React to a comment
+///
+/// Reactions are the emoji allowed by `REACTION_EMOJI`, by shortcode or as the emoji itself.
+/// Each counts toward the comment's score, except `-1`, which counts against it.
+#[utoipa::path(
+    post,
+    path = "/api/comments/{id}/reactions",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the comment to react to")
+    ),
+    request_body = CommentReactionRequest,
+    responses(
+        (status = 200, description = "Reaction added", body = CommentScoreResponse),
+        (status = 400, description = "Unknown reaction", body = CommentErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Comment not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn react_to_comment(
+    Path(comment_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+    Json(reaction): Json<CommentReactionRequest>,
+) -> impl IntoResponse {
+    match comment_service
+        .set_comment_reaction(comment_id, user.user_id, &reaction.reaction, true)
+        .await
+    {
+        Ok(score) => (StatusCode::OK, Json(score)).into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// Take back a reaction to a comment
+#[utoipa::path(
+    delete,
+    path = "/api/comments/{id}/reactions/{reaction}",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the comment"),
+        ("reaction" = String, Path, description = "Shortcode of the reaction")
+    ),
+    responses(
+        (status = 200, description = "Reaction removed", body = CommentScoreResponse),
+        (status = 400, description = "Unknown reaction", body = CommentErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Comment not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn remove_comment_reaction(
+    Path((comment_id, reaction)): Path<(i64, String)>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    match comment_service
+        .set_comment_reaction(comment_id, user.user_id, &reaction, false)
+        .await
+    {
+        Ok(score) => (StatusCode::OK, Json(score)).into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// Report a comment
+///
+/// Reports count against the comment's score, which collapses the comment in listings once
+/// it falls to `COMMENT_COLLAPSE_SCORE`. Users cannot report their own comments; reporting a
+/// comment again only updates the reason.
+#[utoipa::path(
+    post,
+    path = "/api/comments/{id}/report",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the comment to report")
+    ),
+    request_body = CommentReportRequest,
+    responses(
+        (status = 200, description = "Comment reported", body = CommentScoreResponse),
+        (status = 400, description = "Own comment or reason too long", body = CommentErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Comment not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn report_comment(
+    Path(comment_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+    Json(report): Json<CommentReportRequest>,
+) -> impl IntoResponse {
+    match comment_service
+        .report_comment(comment_id, user.user_id, report.reason)
+        .await
+    {
+        Ok(score) => (StatusCode::OK, Json(score)).into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// Lock comments on a post
+///
+/// Refuses new comments, including guest comments, while keeping existing ones visible.
+/// Only the post author or an admin can lock comments.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/comments/lock",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the post")
+    ),
+    responses(
+        (status = 200, description = "Comments locked", body = CommentLockResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Post not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn lock_comments(
+    Path(post_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    set_comments_locked(post_id, user, comment_service, true).await
+}
+
+/// Unlock comments on a post
+///
+/// Only the post author or an admin can unlock comments.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/comments/unlock",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the post")
+    ),
+    responses(
+        (status = 200, description = "Comments unlocked", body = CommentLockResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Post not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn unlock_comments(
+    Path(post_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    set_comments_locked(post_id, user, comment_service, false).await
+}
+
+// Shared body of the lock/unlock handlers
+async fn set_comments_locked(
+    post_id: i64,
+    user: AuthUser,
+    comment_service: Arc<CommentService>,
+    locked: bool,
+) -> axum::response::Response {
+    let is_admin = user.role == Role::Admin;
+
+    match comment_service
+        .set_comments_locked(post_id, user.user_id, is_admin, locked)
+        .await
+    {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(CommentLockResponse {
+                post_id,
+                comments_locked: locked,
+            }),
+        )
+            .into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// Subscribe to a comment thread
+///
+/// Subscribers are notified about new replies anywhere in the thread the comment belongs to.
+/// Commenting in a thread subscribes you automatically.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/comments/{comment_id}/subscribe",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the post"),
+        ("comment_id" = i64, Path, description = "The ID of any comment in the thread")
+    ),
+    responses(
+        (status = 200, description = "Subscribed to thread", body = ThreadSubscriptionResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Comment not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn subscribe_to_thread(
+    Path((post_id, comment_id)): Path<(i64, i64)>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    match comment_service
+        .subscribe_to_thread(post_id, comment_id, user.user_id)
+        .await
+    {
+        Ok(root_comment_id) => (
+            StatusCode::OK,
+            Json(ThreadSubscriptionResponse {
+                root_comment_id,
+                subscribed: true,
+            }),
+        )
+            .into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// Unsubscribe from a comment thread
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/comments/{comment_id}/unsubscribe",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the post"),
+        ("comment_id" = i64, Path, description = "The ID of any comment in the thread")
+    ),
+    responses(
+        (status = 200, description = "Unsubscribed from thread", body = ThreadSubscriptionResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Comment not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn unsubscribe_from_thread(
+    Path((post_id, comment_id)): Path<(i64, i64)>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    match comment_service
+        .unsubscribe_from_thread(post_id, comment_id, user.user_id)
+        .await
+    {
+        Ok(root_comment_id) => (
+            StatusCode::OK,
+            Json(ThreadSubscriptionResponse {
+                root_comment_id,
+                subscribed: false,
+            }),
+        )
+            .into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// List comments awaiting moderation
+///
+/// Admin only. Guest comments land here until they are approved or rejected.
+#[utoipa::path(
+    get,
+    path = "/api/moderation/comments",
+    tag = "comments",
+    params(PageParams),
+    responses(
+        (status = 200, description = "Pending comments retrieved successfully", body = Vec<PendingCommentResponse>),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_pending_comments(
+    Extension(user): Extension<AuthUser>,
+    OriginalUri(uri): OriginalUri,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+    Query(params): Query<PageParams>,
+) -> impl IntoResponse {
+    if user.role != Role::Admin {
+        return comment_error_to_response(CommentError::Unauthorized).into_response();
+    }
+
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return comment_error_to_response(CommentError::ValidationError(e.to_string()))
+                .into_response()
+        }
+    };
+
+    match comment_service.get_pending_comments(&pagination).await {
+        Ok(comments) => (
+            StatusCode::OK,
+            pagination.headers(&uri, comments.len()),
+            Json(comments),
+        )
+            .into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// Approve a pending comment
+#[utoipa::path(
+    post,
+    path = "/api/moderation/comments/{id}/approve",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the pending comment")
+    ),
+    responses(
+        (status = 204, description = "Comment approved"),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "No pending comment with this ID", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn approve_comment(
+    Path(comment_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    moderate_comment(comment_id, user, comment_service, true).await
+}
+
+/// Reject a pending comment
+#[utoipa::path(
+    post,
+    path = "/api/moderation/comments/{id}/reject",
+    tag = "comments",
+    params(
+        ("id" = i64, Path, description = "The ID of the pending comment")
+    ),
+    responses(
+        (status = 204, description = "Comment rejected"),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "No pending comment with this ID", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn reject_comment(
+    Path(comment_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+) -> impl IntoResponse {
+    moderate_comment(comment_id, user, comment_service, false).await
+}
+
+// Shared body of the approve/reject handlers
+async fn moderate_comment(
+    comment_id: i64,
+    user: AuthUser,
+    comment_service: Arc<CommentService>,
+    approve: bool,
+) -> axum::response::Response {
+    if user.role != Role::Admin {
+        return comment_error_to_response(CommentError::Unauthorized).into_response();
+    }
+
+    match comment_service.moderate_comment(comment_id, approve).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
+
+/// Shadow-ban a user
+///
+/// Admin only. A shadow-banned user can keep commenting and sees their own comments as usual,
+/// but nobody else sees them and they trigger no notifications.
+#[utoipa::path(
+    put,
+    path = "/api/admin/users/{user_id}/shadow-ban",
+    tag = "comments",
+    params(
+        ("user_id" = String, Path, description = "The UUID of the user")
+    ),
+    request_body = ShadowBanRequest,
+    responses(
+        (status = 200, description = "Shadow-ban updated", body = ShadowBanResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "User not found", body = CommentErrorResponse),
+        (status = 500, description = "Internal server error", body = CommentErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_shadow_ban(
+    Path(user_id): Path<Uuid>,
+    Extension(user): Extension<AuthUser>,
+    Extension(comment_service): Extension<Arc<CommentService>>,
+    Json(request): Json<ShadowBanRequest>,
+) -> impl IntoResponse {
+    if user.role != Role::Admin {
+        return comment_error_to_response(CommentError::Unauthorized).into_response();
+    }
+
+    match comment_service
+        .set_shadow_ban(user_id, request.shadow_banned)
+        .await
+    {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ShadowBanResponse {
+                user_id,
+                shadow_banned: request.shadow_banned,
+            }),
+        )
+            .into_response(),
+        Err(e) => comment_error_to_response(e).into_response(),
+    }
+}
@@ -1,5 +1,8 @@
+pub mod captcha;
 pub mod controller;
 pub mod model;
+pub mod score;
 pub mod service;
+pub mod threads;
 
 // We don't need to re-export these types for now
@@ -2,7 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use thiserror;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 /// Database model for a comment
@@ -10,7 +10,9 @@ use uuid::Uuid;
 pub struct Comment {
     pub id: i64,
     pub post_id: i64,
-    pub user_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub guest_name: Option<String>,
+    pub guest_email: Option<String>,
     pub parent_comment_id: Option<i64>,
     pub content: String,
     pub content_html: String,
@@ -18,6 +20,9 @@ pub struct Comment {
     pub deleted_by: Option<Uuid>,
     pub deleted_at: Option<DateTime<Utc>>,
     pub markdown_enabled: bool,
+    pub moderation_status: String,
+    pub is_pinned: bool,
+    pub score: i32,
     pub nesting_level: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -39,17 +44,52 @@ pub struct CreateCommentRequest {
     pub markdown_enabled: bool,
 }
 
+/// Request to create a comment without an account
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateGuestCommentRequest {
+    /// Display name shown with the comment
+    #[schema(example = "Jane")]
+    pub name: String,
+
+    /// Contact email, never shown publicly
+    #[schema(example = "jane@example.com")]
+    pub email: String,
+
+    /// The comment content as plain text
+    #[schema(example = "This is a great post!")]
+    pub content: String,
+
+    /// ID of the parent comment if this is a reply
+    #[schema(example = "null")]
+    pub parent_comment_id: Option<i64>,
+
+    /// Token returned by the captcha widget
+    #[schema(example = "10000000-aaaa-bbbb-cccc-000000000001")]
+    pub captcha_token: String,
+}
+
 /// User information in comment responses
-#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct CommentAuthor {
     /// User's UUID
-    #[schema(value_type = UuidWrapper)]
     #[schema(example = "a1b2c3d4-e5f6-7890-abcd-1234567890ab")]
     pub id: Uuid,
 
     /// User's display name
     #[schema(example = "John Doe")]
     pub name: String,
+
+    /// Uploaded avatar, or the user's Gravatar
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+}
+
+/// Guest author information in comment responses
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GuestAuthor {
+    /// Display name given by the guest
+    #[schema(example = "Jane")]
+    pub name: String,
 }
 
 /// Response format for a single comment
@@ -63,11 +103,13 @@ pub struct CommentResponse {
     #[schema(example = "<p>This is a great post!</p>")]
     pub content_html: String,
 
-    /// Author information
-    pub author: CommentAuthor,
+    /// Author information, absent for guest comments
+    pub author: Option<CommentAuthor>,
+
+    /// Guest author information, present only for comments posted without an account
+    pub guest_author: Option<GuestAuthor>,
 
     /// When the comment was created
-    #[schema(value_type = DateTimeWrapper)]
     #[schema(example = "2023-01-01T12:00:00Z")]
     pub created_at: DateTime<Utc>,
 
@@ -75,6 +117,27 @@ pub struct CommentResponse {
     #[schema(example = "null")]
     pub parent_comment_id: Option<i64>,
 
+    /// Whether the comment is pinned to the top of the post's comments
+    #[serde(default)]
+    #[schema(example = "false")]
+    pub pinned: bool,
+
+    /// Whether the comment was written by the author of the post
+    #[serde(default)]
+    #[schema(example = "false")]
+    pub is_post_author: bool,
+
+    /// Quality score: reactions minus reports, plus the reputation of the author
+    #[serde(default)]
+    #[schema(example = "3")]
+    pub score: i32,
+
+    /// Whether the score is so low that clients should hide the comment behind a
+    /// "show anyway" control
+    #[serde(default)]
+    #[schema(example = "false")]
+    pub collapsed: bool,
+
     /// Nested replies
     pub replies: Option<Vec<CommentResponse>>,
 }
@@ -88,6 +151,152 @@ pub struct CommentsListResponse {
     /// Total number of comments
     #[schema(example = "42")]
     pub total_count: i64,
+
+    /// Cursor for the next page of root comments, if there is one
+    #[schema(example = "djE6MjA")]
+    pub next_cursor: Option<String>,
+}
+
+/// Query parameters of a comment permalink lookup
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CommentPermalinkParams {
+    /// Page size the client lists the post's comments with
+    pub limit: Option<i64>,
+}
+
+/// Where a comment appears in its post's comment listing
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CommentPermalinkResponse {
+    /// The comment, without its replies
+    pub comment: CommentResponse,
+
+    /// ID of the post the comment was left on
+    #[schema(example = "42")]
+    pub post_id: i64,
+
+    /// Slug of the post the comment was left on
+    #[schema(example = "my-first-post")]
+    pub post_slug: String,
+
+    /// ID of the root comment of the thread; the comment itself if it is not a reply
+    #[schema(example = "123")]
+    pub root_comment_id: i64,
+
+    /// Page (1-based) of the post's root comments the thread is on, pinned comments first
+    #[schema(example = "3")]
+    pub page: i64,
+
+    /// Page size the page number is based on
+    #[schema(example = "20")]
+    pub limit: i64,
+
+    /// Cursor of that page, for clients paging with cursors
+    #[schema(example = "djE6NDA")]
+    pub cursor: String,
+}
+
+/// A guest comment awaiting moderation
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PendingCommentResponse {
+    /// The comment as it will be shown once approved
+    pub comment: CommentResponse,
+
+    /// ID of the post the comment was left on
+    #[schema(example = "42")]
+    pub post_id: i64,
+
+    /// Guest contact email, visible to moderators only
+    #[schema(example = "jane@example.com")]
+    pub guest_email: Option<String>,
+}
+
+/// Thread subscription state returned by the subscribe/unsubscribe endpoints
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ThreadSubscriptionResponse {
+    /// ID of the root comment identifying the thread
+    #[schema(example = "123")]
+    pub root_comment_id: i64,
+
+    /// Whether the user is now subscribed to the thread
+    #[schema(example = "true")]
+    pub subscribed: bool,
+}
+
+/// Pin state returned by the pin/unpin endpoints
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CommentPinResponse {
+    /// ID of the comment
+    #[schema(example = "123")]
+    pub comment_id: i64,
+
+    /// Whether the comment is now pinned
+    #[schema(example = "true")]
+    pub pinned: bool,
+}
+
+/// Request to react to a comment
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CommentReactionRequest {
+    /// Shortcode of an allowed reaction, with or without colons, or the emoji itself
+    #[schema(example = "rocket")]
+    pub reaction: String,
+}
+
+/// Request to report a comment
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CommentReportRequest {
+    /// What is wrong with the comment, for moderators
+    #[schema(example = "Spam")]
+    pub reason: Option<String>,
+}
+
+/// Score of a comment after it was reacted to or reported
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CommentScoreResponse {
+    /// ID of the comment
+    #[schema(example = "123")]
+    pub comment_id: i64,
+
+    /// Quality score: reactions minus reports, plus the reputation of the author
+    #[schema(example = "3")]
+    pub score: i32,
+
+    /// Whether the comment is now collapsed in listings
+    #[schema(example = "false")]
+    pub collapsed: bool,
+}
+
+/// Lock state returned by the lock/unlock endpoints
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CommentLockResponse {
+    /// ID of the post
+    #[schema(example = "42")]
+    pub post_id: i64,
+
+    /// Whether new comments are now refused
+    #[schema(example = "true")]
+    pub comments_locked: bool,
+}
+
+/// Request to shadow-ban or un-ban a user
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ShadowBanRequest {
+    /// Whether the user's comments should be hidden from everyone but themselves
+    #[schema(example = "true")]
+    pub shadow_banned: bool,
+}
+
+/// Shadow-ban state of a user
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ShadowBanResponse {
+    /// User's UUID
+    #[schema(example = "a1b2c3d4-e5f6-7890-abcd-1234567890ab")]
+    pub user_id: Uuid,
+
+    /// Whether the user is now shadow-banned
+    #[schema(example = "true")]
+    pub shadow_banned: bool,
 }
 
 /// Possible comment errors
@@ -102,12 +311,12 @@ pub enum CommentError {
     #[error("Post not found")]
     PostNotFound,
 
+    #[error("User not found")]
+    UserNotFound,
+
     #[error("Not authorized to perform this action")]
     Unauthorized,
 
-    #[error("Rate limit exceeded")]
-    RateLimitExceeded,
-
     #[error("Invalid comment")]
     InvalidComment,
 
@@ -126,6 +335,18 @@ pub enum CommentError {
     #[error("Deserialization error")]
     DeserializationError,
 
+    #[error("Guest comments are disabled")]
+    GuestCommentsDisabled,
+
+    #[error("Captcha verification failed")]
+    CaptchaFailed,
+
+    #[error("Comments are locked on this post")]
+    CommentsLocked,
+
+    #[error("This post has reached its comment limit")]
+    CommentLimitReached,
+
     #[error("Internal server error: {0}")]
     InternalError(String),
 }
@@ -153,14 +374,14 @@ impl From<CommentError> for CommentErrorResponse {
                 error: "Post not found".to_string(),
                 code: "POST_NOT_FOUND".to_string(),
             },
+            CommentError::UserNotFound => Self {
+                error: "User not found".to_string(),
+                code: "USER_NOT_FOUND".to_string(),
+            },
             CommentError::Unauthorized => Self {
                 error: "Not authorized to perform this action".to_string(),
                 code: "UNAUTHORIZED".to_string(),
             },
-            CommentError::RateLimitExceeded => Self {
-                error: "Rate limit exceeded".to_string(),
-                code: "RATE_LIMIT_EXCEEDED".to_string(),
-            },
             CommentError::InvalidComment => Self {
                 error: "Invalid comment".to_string(),
                 code: "INVALID_COMMENT".to_string(),
@@ -189,6 +410,22 @@ impl From<CommentError> for CommentErrorResponse {
                 error: "Failed to process comment data".to_string(),
                 code: "DESERIALIZATION_ERROR".to_string(),
             },
+            CommentError::GuestCommentsDisabled => Self {
+                error: "Guest comments are disabled".to_string(),
+                code: "GUEST_COMMENTS_DISABLED".to_string(),
+            },
+            CommentError::CaptchaFailed => Self {
+                error: "Captcha verification failed".to_string(),
+                code: "CAPTCHA_FAILED".to_string(),
+            },
+            CommentError::CommentsLocked => Self {
+                error: "Comments are locked on this post".to_string(),
+                code: "COMMENTS_LOCKED".to_string(),
+            },
+            CommentError::CommentLimitReached => Self {
+                error: "This post has reached its comment limit".to_string(),
+                code: "COMMENT_LIMIT_REACHED".to_string(),
+            },
             CommentError::InternalError(msg) => Self {
                 error: msg,
                 code: "INTERNAL_ERROR".to_string(),
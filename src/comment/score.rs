@@ -0,0 +1,238 @@
+use crate::cache::redis::RedisCache;
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Kind of the job that recomputes the score of every comment
+pub const COMMENT_SCORE_JOB: &str = "comment_scores";
+
+// How often scores are recomputed, and how often instances check whether a run is due
+const COMMENT_SCORE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const COMMENT_SCORE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Comments scored per statement
+const COMMENT_SCORE_BATCH_SIZE: i64 = 5000;
+// Points a report costs, against one per reaction
+const REPORT_WEIGHT: i64 = 3;
+// The author's reputation adds a point per this many reputation points, up to the cap either
+// way
+const REPUTATION_POINTS: i32 = 25;
+const MAX_REPUTATION: i32 = 5;
+// Score at or below which comments are collapsed unless COMMENT_COLLAPSE_SCORE says otherwise
+const DEFAULT_COLLAPSE_SCORE: i32 = -5;
+
+/// Score of a comment from its reactions and reports, and the reputation of its author.
+/// Reactions are net of `-1` reactions, which count against a comment.
+pub fn comment_score(reactions: i64, reports: i64, author_reputation: i32) -> i32 {
+    let net = reactions - reports * REPORT_WEIGHT;
+    let reputation = (author_reputation / REPUTATION_POINTS).clamp(-MAX_REPUTATION, MAX_REPUTATION);
+    (net + reputation as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// Whether a comment is collapsed in listings, for clients to hide it behind a "show anyway"
+/// control. Pinned comments never are.
+pub fn is_collapsed(score: i32, pinned: bool) -> bool {
+    static COLLAPSE_SCORE: OnceLock<i32> = OnceLock::new();
+    let collapse_score = *COLLAPSE_SCORE.get_or_init(|| {
+        std::env::var("COMMENT_COLLAPSE_SCORE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_COLLAPSE_SCORE)
+    });
+    !pinned && score <= collapse_score
+}
+
+/// Keeps `score` of every comment up to date, the quality signal that collapses low-score
+/// comments in listings.
+///
+/// A comment scores a point per reaction (`-1` reactions take one away) and loses three per
+/// report, plus the reputation of its author: a point per 25 points of their user reputation,
+/// at most five either way. Guest comments have no reputation. Comments are rescored as soon
+/// as they are reacted to or reported; reputations change in the background, so a job queued
+/// every hour rescores them all.
+pub struct CommentScoreService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+}
+
+impl CommentScoreService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self { pool, redis_cache }
+    }
+
+    /// Queue a rescoring every hour
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(COMMENT_SCORE_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(COMMENT_SCORE_JOB, json!({}), COMMENT_SCORE_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule comment scoring: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Recompute the score of every comment. Returns how many were scored.
+    pub async fn rescore_all(&self) -> Result<i64, sqlx::Error> {
+        let mut scored = 0;
+        let mut after_id = 0_i64;
+
+        loop {
+            let batch = self
+                .rescore_batch(after_id, COMMENT_SCORE_BATCH_SIZE)
+                .await?;
+            match batch.last() {
+                Some((last_id, _)) => after_id = *last_id,
+                None => break,
+            }
+            scored += batch.len() as i64;
+        }
+
+        info!("Scored {} comments", scored);
+        Ok(scored)
+    }
+
+    /// Recompute the score of one comment, e.g. after it was reacted to. Returns its new
+    /// score, or `None` if there is no such comment.
+    pub async fn rescore_comment(&self, comment_id: i64) -> Result<Option<i32>, sqlx::Error> {
+        let batch = self.rescore_batch(comment_id - 1, 1).await?;
+        Ok(batch
+            .first()
+            .filter(|(id, _)| *id == comment_id)
+            .map(|(_, score)| *score))
+    }
+
+    // Rescore the comments following `after_id` in ID order, returning their IDs and scores,
+    // and drop the cached listings of the posts whose comments' scores changed
+    async fn rescore_batch(
+        &self,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<(i64, i32)>, sqlx::Error> {
+        let query = sqlx::query(
+            r#"
+            SELECT c.id, c.post_id, c.score,
+                (SELECT COALESCE(SUM(CASE WHEN r.reaction = '-1' THEN -1 ELSE 1 END), 0)
+                    FROM global.comment_reactions r WHERE r.comment_id = c.id)::BIGINT
+                    AS reactions,
+                (SELECT COUNT(*) FROM global.comment_reports r WHERE r.comment_id = c.id)
+                    AS reports,
+                COALESCE(u.points, 0) AS author_reputation
+            FROM global.comments c
+            LEFT JOIN global.user_reputation u ON u.user_id = c.user_id
+            WHERE c.id > $1
+            ORDER BY c.id
+            LIMIT $2
+            "#,
+        )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool);
+        let rows = timed("comments.scores", query).await?;
+
+        let mut scores = Vec::with_capacity(rows.len());
+        let mut changed_ids = Vec::new();
+        let mut changed_scores = Vec::new();
+        let mut changed_posts = HashSet::new();
+        for row in rows {
+            let id: i64 = row.get("id");
+            let score = comment_score(
+                row.get("reactions"),
+                row.get("reports"),
+                row.get("author_reputation"),
+            );
+            if score != row.get::<i32, _>("score") {
+                changed_ids.push(id);
+                changed_scores.push(score);
+                changed_posts.insert(row.get::<i64, _>("post_id"));
+            }
+            scores.push((id, score));
+        }
+
+        if !changed_ids.is_empty() {
+            sqlx::query(
+                r#"
+                UPDATE global.comments c
+                SET score = s.score
+                FROM UNNEST($1::BIGINT[], $2::INTEGER[]) AS s(id, score)
+                WHERE c.id = s.id
+                "#,
+            )
+            .bind(&changed_ids)
+            .bind(&changed_scores)
+            .execute(&self.pool)
+            .await?;
+            self.invalidate_listings(changed_posts).await;
+        }
+
+        Ok(scores)
+    }
+
+    // Listings carry scores, so the cached pages of the posts go
+    async fn invalidate_listings(&self, post_ids: HashSet<i64>) {
+        let Some(cache) = &self.redis_cache else {
+            return;
+        };
+        let keys: Vec<String> = post_ids
+            .into_iter()
+            .map(|post_id| format!("comments:post:{}", post_id))
+            .collect();
+        let result: Result<(), redis::RedisError> = async {
+            let mut conn = cache.connection().await?;
+            redis::cmd("DEL").arg(&keys).query_async(&mut conn).await
+        }
+        .await;
+        if let Err(e) = result {
+            error!("Failed to invalidate comment listings: {}", e);
+        }
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let scored = self.rescore_all().await?;
+        Ok(json!({ "scored": scored }))
+    }
+}
+
+impl JobHandler for CommentScoreService {
+    fn kind(&self) -> &'static str {
+        COMMENT_SCORE_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_score() {
+        assert_eq!(comment_score(0, 0, 0), 0);
+        assert_eq!(comment_score(4, 1, 0), 1);
+        // Reputation adds a point per 25 reputation points, capped either way
+        assert_eq!(comment_score(2, 0, 60), 4);
+        assert_eq!(comment_score(2, 0, 5000), 7);
+        assert_eq!(comment_score(0, 2, -400), -11);
+        // Reputation short of 25 points adds nothing
+        assert_eq!(comment_score(0, 0, -24), 0);
+    }
+
+    #[test]
+    fn test_is_collapsed() {
+        assert!(is_collapsed(DEFAULT_COLLAPSE_SCORE, false));
+        assert!(!is_collapsed(DEFAULT_COLLAPSE_SCORE + 1, false));
+        assert!(!is_collapsed(DEFAULT_COLLAPSE_SCORE - 10, true));
+    }
+}
@@ -1,23 +1,53 @@
 use crate::analytics::model::InteractionType;
 use crate::analytics::service::AnalyticsService;
+use crate::block::service::blocked_user_ids;
 use crate::cache::redis::RedisCache;
+use crate::comment::captcha::verify_captcha;
 use crate::comment::model::{
-    Comment, CommentAuthor, CommentError, CommentResponse, CreateCommentRequest,
+    Comment, CommentAuthor, CommentError, CommentPermalinkResponse, CommentResponse,
+    CommentScoreResponse, CreateCommentRequest, CreateGuestCommentRequest, GuestAuthor,
+    PendingCommentResponse,
 };
-use crate::notification::model::{NotificationPayload, NotificationType};
+use crate::comment::score::{is_collapsed, CommentScoreService};
+use crate::comment::threads::{
+    pending_replies_key, PendingThreadReply, ThreadNotificationJob, THREAD_NOTIFICATION_JOB,
+};
+use crate::db::instrument::timed;
+use crate::jobs::service::JobService;
+use crate::markdown;
+use crate::notification::model::{NotificationContext, NotificationPayload, NotificationType};
 use crate::notification::service::NotificationService;
-use crate::websocket::notifications::publish_notification;
+use crate::pagination::{encode_cursor, Pagination};
+use crate::reputation::service::queue_reputation_update;
+use crate::tenant::middleware::current_blog_id;
 use chrono::Utc;
 use redis::AsyncCommands;
-use sqlx::{PgPool, Row};
+use serde_json::json;
+use sqlx::{postgres::PgRow, PgPool, Row};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
 // Constants
 const MAX_NESTING_DEPTH: i32 = 3;
-const COMMENTS_PER_PAGE: i64 = 20;
-const COMMENT_RATE_LIMIT_SECONDS: u64 = 100;
+// Levels of replies below root comments shown in listings
+const REPLY_LEVELS: usize = 3;
+// Hard cap on comments per post unless MAX_COMMENTS_PER_POST says otherwise
+const DEFAULT_MAX_COMMENTS_PER_POST: i64 = 10_000;
+// Longest reason a comment can be reported for
+const MAX_REPORT_REASON_LENGTH: usize = 1000;
+// Replies to the same thread within this window are folded into one notification per subscriber
+const THREAD_NOTIFICATION_WINDOW_SECONDS: i64 = 30;
+// Replies stay queued this long if the job flushing their batch never runs
+const THREAD_PENDING_TTL_SECONDS: i64 = 24 * 60 * 60;
+// INCRBY that leaves missing keys alone
+const ADJUST_CACHED_COUNT_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return redis.call('INCRBY', KEYS[1], ARGV[1])
+end
+return false
+"#;
 
 #[derive(Clone)]
 pub struct CommentService {
@@ -25,6 +55,203 @@ pub struct CommentService {
     redis_cache: Option<RedisCache>,
     analytics_service: Arc<AnalyticsService>,
     notification_service: Arc<NotificationService>,
+    score_service: Arc<CommentScoreService>,
+    job_service: Arc<JobService>,
+    thread_notifier: Arc<ThreadNotificationJob>,
+    guest_comments_enabled: bool,
+    max_comments_per_post: i64,
+}
+
+// Build the author fields of a comment row selected with `u.id as author_id,
+// u.username as author_name` and the user's avatar as `author_avatar_url`
+fn row_authors(row: &PgRow) -> (Option<CommentAuthor>, Option<GuestAuthor>) {
+    match (
+        row.get::<Option<Uuid>, _>("author_id"),
+        row.get::<Option<String>, _>("author_name"),
+    ) {
+        (Some(id), Some(name)) => (
+            Some(CommentAuthor {
+                id,
+                name,
+                avatar_url: row.get("author_avatar_url"),
+            }),
+            None,
+        ),
+        _ => (
+            None,
+            row.get::<Option<String>, _>("guest_name")
+                .map(|name| GuestAuthor { name }),
+        ),
+    }
+}
+
+// Build the response for a reply row selected like `row_authors` expects, with its own
+// replies
+fn reply_response(row: &PgRow, replies: Option<Vec<CommentResponse>>) -> CommentResponse {
+    let (author, guest_author) = row_authors(row);
+    CommentResponse {
+        id: row.get("id"),
+        content_html: row.get("content_html"),
+        author,
+        guest_author,
+        created_at: row.get("created_at"),
+        parent_comment_id: row.get("parent_comment_id"),
+        pinned: row.get("is_pinned"),
+        is_post_author: row.get("is_post_author"),
+        score: row.get("score"),
+        collapsed: is_collapsed(row.get("score"), row.get("is_pinned")),
+        replies,
+    }
+}
+
+// Notifications for a new comment: its parent's author hears about the reply and the
+// post's author about the comment, never both and never the commenter themselves
+fn comment_notifications(
+    comment: &Comment,
+    actor_id: Uuid,
+    post_author_id: Uuid,
+    parent_author_id: Option<Uuid>,
+) -> Vec<NotificationPayload> {
+    let mut notifications = Vec::new();
+
+    if let Some(parent_author) = parent_author_id.filter(|author| *author != actor_id) {
+        notifications.push(NotificationPayload {
+            recipient_id: parent_author,
+            notification_type: NotificationType::CommentReply,
+            object_id: comment.id,
+            related_object_id: Some(comment.post_id),
+            actor_id,
+            content: "You have a new reply to your comment.".to_string(),
+            context: NotificationContext::default(),
+        });
+    }
+
+    if post_author_id != actor_id && Some(post_author_id) != parent_author_id {
+        notifications.push(NotificationPayload {
+            recipient_id: post_author_id,
+            notification_type: NotificationType::NewComment,
+            object_id: comment.id,
+            related_object_id: Some(comment.post_id),
+            actor_id,
+            content: "New comment on your post".to_string(),
+            context: NotificationContext::default(),
+        });
+    }
+
+    notifications
+}
+
+// Announce a comment that became visible on the realtime comments stream
+async fn publish_comment_created(
+    cache: &RedisCache,
+    post_id: i64,
+    comment_id: i64,
+    parent_id: Option<i64>,
+) {
+    if let Ok(mut conn) = cache.connection().await {
+        let _: Result<String, redis::RedisError> = conn
+            .xadd(
+                "stream:comments",
+                "*",
+                &[
+                    ("event", "comment_created"),
+                    ("post_id", &post_id.to_string()),
+                    ("comment_id", &comment_id.to_string()),
+                    (
+                        "parent_id",
+                        &parent_id
+                            .map(|id| id.to_string())
+                            .unwrap_or_else(|| "null".to_string()),
+                    ),
+                ],
+            )
+            .await;
+    }
+}
+
+// Drop comments by blocked users from a listing, together with the replies under them
+fn remove_blocked_authors(comments: &mut Vec<CommentResponse>, blocked: &HashSet<Uuid>) {
+    comments.retain(|comment| {
+        !comment
+            .author
+            .as_ref()
+            .is_some_and(|author| blocked.contains(&author.id))
+    });
+    for comment in comments.iter_mut() {
+        if let Some(replies) = comment.replies.as_mut() {
+            remove_blocked_authors(replies, blocked);
+        }
+    }
+}
+
+/// Number of visible comments on a post, served from the counter cached in Redis when possible
+pub async fn comment_count(
+    pool: &PgPool,
+    redis_cache: Option<&RedisCache>,
+    post_id: i64,
+) -> Result<i64, CommentError> {
+    // Try to get from cache first
+    if let Some(cache) = redis_cache {
+        let count_key = format!("post:comment_count:{}", post_id);
+
+        if let Ok(Some(count)) = cache
+            .connection()
+            .await
+            .map_err(CommentError::CacheError)?
+            .get::<_, Option<i64>>(&count_key)
+            .await
+        {
+            return Ok(count);
+        }
+    }
+
+    // Cache miss, get from DB
+    let query = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM global.comments c
+        LEFT JOIN global.users u ON c.user_id = u.id
+        WHERE c.post_id = $1 AND c.is_deleted = false AND c.moderation_status = 'approved'
+            AND (u.id IS NULL OR NOT u.is_shadow_banned)
+        "#,
+        post_id
+    )
+    .fetch_one(pool);
+    let count = timed("comments.count", query)
+        .await
+        .map_err(CommentError::DatabaseError)?;
+
+    // Update cache
+    if let Some(cache) = redis_cache {
+        let count_key = format!("post:comment_count:{}", post_id);
+        cache
+            .connection()
+            .await
+            .map_err(CommentError::CacheError)?
+            .set_ex::<_, _, ()>(&count_key, count.to_string(), 3600)
+            .await
+            .map_err(CommentError::CacheError)?;
+    }
+
+    Ok(count)
+}
+
+// Adjust a post's cached comment count, but only while it is cached; a missing counter is
+// recomputed on the next read instead of restarting from zero
+async fn adjust_cached_comment_count(
+    cache: &RedisCache,
+    post_id: i64,
+    delta: i64,
+) -> Result<(), CommentError> {
+    let mut conn = cache.connection().await.map_err(CommentError::CacheError)?;
+
+    let _: Option<i64> = redis::Script::new(ADJUST_CACHED_COUNT_SCRIPT)
+        .key(format!("post:comment_count:{}", post_id))
+        .arg(delta)
+        .invoke_async(&mut conn)
+        .await
+        .map_err(CommentError::CacheError)?;
+
+    Ok(())
 }
 
 impl CommentService {
@@ -33,17 +260,35 @@ impl CommentService {
         redis_cache: Option<RedisCache>,
         analytics_service: Arc<AnalyticsService>,
         notification_service: Arc<NotificationService>,
+        score_service: Arc<CommentScoreService>,
+        job_service: Arc<JobService>,
+        thread_notifier: Arc<ThreadNotificationJob>,
     ) -> Self {
+        // Guest commenting is opt-in per deployment
+        let guest_comments_enabled = std::env::var("GUEST_COMMENTS_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
+        let max_comments_per_post = std::env::var("MAX_COMMENTS_PER_POST")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_COMMENTS_PER_POST);
+
         Self {
             pool,
             redis_cache,
             analytics_service,
             notification_service,
+            score_service,
+            job_service,
+            thread_notifier,
+            guest_comments_enabled,
+            max_comments_per_post,
         }
     }
 
     // Helper function to sanitize and render markdown
-    fn process_markdown(
+    async fn process_markdown(
         &self,
         content: &str,
         markdown_enabled: bool,
@@ -53,56 +298,24 @@ impl CommentService {
             return Ok(html_escape::encode_safe(content).to_string());
         }
 
-        // In a real implementation, we would sanitize and convert markdown to HTML
-        // For this example, we're just returning the content with a simple formatting
-        Ok(format!("<div class=\"markdown\">{}</div>", content))
-    }
-
-    // Check if user can add a comment (rate limiting)
-    async fn check_rate_limit(&self, user_id: &Uuid) -> Result<bool, CommentError> {
-        if let Some(cache) = &self.redis_cache {
-            let rate_limit_key = format!("rate_limit:comment:{}", user_id);
-
-            // Check if rate limit key exists
-            let exists: bool = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(CommentError::CacheError)?
-                .exists(&rate_limit_key)
-                .await
-                .map_err(CommentError::CacheError)?;
-
-            if exists {
-                return Ok(true);
-            }
-
-            // Set rate limit key with expiration
-            cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(CommentError::CacheError)?
-                .set_ex(&rate_limit_key, "1", COMMENT_RATE_LIMIT_SECONDS)
-                .await
-                .map_err(CommentError::CacheError)?;
-        }
-
-        Ok(false)
+        Ok(markdown::render_with_embeds(content).await)
     }
 
-    // Get the nesting level of a comment
-    async fn get_parent_nesting_level(&self, parent_id: i64) -> Result<i32, CommentError> {
-        let result = sqlx::query("SELECT nesting_level FROM global.comments WHERE id = $1")
-            .bind(parent_id)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(CommentError::DatabaseError)?;
-
-        match result {
-            Some(row) => Ok(row.get::<i32, _>("nesting_level")),
-            None => Err(CommentError::ParentCommentNotFound),
-        }
+    // Get the nesting level of a comment, which must belong to the post being replied on
+    async fn get_parent_nesting_level(
+        &self,
+        post_id: i64,
+        parent_id: i64,
+    ) -> Result<i32, CommentError> {
+        sqlx::query_scalar!(
+            "SELECT nesting_level FROM global.comments WHERE id = $1 AND post_id = $2",
+            parent_id,
+            post_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?
+        .ok_or(CommentError::ParentCommentNotFound)
     }
 
     // Create a new comment
@@ -112,44 +325,32 @@ impl CommentService {
         user_id: Uuid,
         comment_data: CreateCommentRequest,
     ) -> Result<CommentResponse, CommentError> {
-        // Check rate limit
-        if !self.check_rate_limit(&user_id).await? {
-            return Err(CommentError::RateLimitExceeded);
-        }
-
         // Check if post exists
-        let post_exists = sqlx::query(
-            "SELECT EXISTS(SELECT 1 FROM global.posts WHERE id = $1 AND is_deleted = false)",
-        )
-        .bind(post_id)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(CommentError::DatabaseError)?
-        .get::<bool, _>(0);
-
-        if !post_exists {
-            return Err(CommentError::PostNotFound);
-        }
+        let post_author_id = self
+            .get_post_author(post_id)
+            .await?
+            .ok_or(CommentError::PostNotFound)?;
+        self.check_accepts_comments(post_id).await?;
 
         // Get parent comment author if this is a reply
         let parent_author_id = if let Some(parent_id) = comment_data.parent_comment_id {
-            let result = sqlx::query("SELECT user_id FROM global.comments WHERE id = $1")
-                .bind(parent_id)
-                .fetch_optional(&self.pool)
-                .await
-                .map_err(CommentError::DatabaseError)?;
-
-            match result {
-                Some(row) => Some(row.get::<Uuid, _>("user_id")),
-                None => return Err(CommentError::ParentCommentNotFound),
-            }
+            // Guest comments have no account to notify
+            sqlx::query_scalar!(
+                "SELECT user_id FROM global.comments WHERE id = $1 AND post_id = $2",
+                parent_id,
+                post_id
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(CommentError::DatabaseError)?
+            .ok_or(CommentError::ParentCommentNotFound)?
         } else {
             None
         };
 
         // Calculate nesting level and validate max depth
         let nesting_level = if let Some(parent_id) = comment_data.parent_comment_id {
-            let parent_level = self.get_parent_nesting_level(parent_id).await?;
+            let parent_level = self.get_parent_nesting_level(post_id, parent_id).await?;
             let new_level = parent_level + 1;
 
             if new_level > MAX_NESTING_DEPTH {
@@ -161,35 +362,50 @@ impl CommentService {
             0 // Root level comment
         };
 
+        // Replies belong to the thread of their root comment
+        let thread_root_id = match comment_data.parent_comment_id {
+            Some(parent_id) => Some(self.get_thread_root(parent_id).await?.0),
+            None => None,
+        };
+
         // Process markdown content
-        let content_html =
-            self.process_markdown(&comment_data.content, comment_data.markdown_enabled)?;
+        let content_html = self
+            .process_markdown(&comment_data.content, comment_data.markdown_enabled)
+            .await?;
+
+        // Comments from shadow-banned users are stored but never announced to anyone
+        let shadow_banned = self.is_shadow_banned(user_id).await?;
 
-        // Start transaction
+        // The comment, its analytics interaction and its notifications are written in one
+        // transaction, so none of them can be lost without the others
         let mut tx = self.pool.begin().await.map_err(|e| {
             error!("Failed to begin transaction: {}", e);
             CommentError::DatabaseError(e)
         })?;
 
         // Insert comment
-        let comment_result = sqlx::query_as::<_, Comment>(
+        let comment_result = sqlx::query_as!(
+            Comment,
             r#"
             INSERT INTO global.comments (
-                post_id, user_id, parent_comment_id, content, content_html, 
-                is_deleted, markdown_enabled, nesting_level, created_at, updated_at
-            ) 
-            VALUES ($1, $2, $3, $4, $5, false, $6, $7, $8, $8)
-            RETURNING *
+                post_id, user_id, parent_comment_id, content, content_html,
+                is_deleted, markdown_enabled, nesting_level, created_at, updated_at, blog_id
+            )
+            VALUES ($1, $2, $3, $4, $5, false, $6, $7, $8, $8,
+                (SELECT blog_id FROM global.posts WHERE id = $1))
+            RETURNING id, post_id, user_id, guest_name, guest_email, parent_comment_id, content, content_html,
+                is_deleted, deleted_by, deleted_at, markdown_enabled, moderation_status, is_pinned,
+                score, nesting_level, created_at, updated_at
             "#,
+            post_id,
+            user_id,
+            comment_data.parent_comment_id,
+            comment_data.content,
+            content_html,
+            comment_data.markdown_enabled,
+            nesting_level,
+            Utc::now()
         )
-        .bind(post_id)
-        .bind(user_id)
-        .bind(comment_data.parent_comment_id)
-        .bind(&comment_data.content)
-        .bind(&content_html)
-        .bind(comment_data.markdown_enabled)
-        .bind(nesting_level)
-        .bind(Utc::now())
         .fetch_one(&mut *tx)
         .await
         .map_err(|e| {
@@ -197,6 +413,54 @@ impl CommentService {
             CommentError::DatabaseError(e)
         })?;
 
+        // Commenting subscribes the author to the thread (a root comment starts its own)
+        sqlx::query!(
+            r#"
+            INSERT INTO global.comment_thread_subscriptions (root_comment_id, user_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            thread_root_id.unwrap_or(comment_result.id),
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Failed to subscribe comment author to thread: {}", e);
+            CommentError::DatabaseError(e)
+        })?;
+
+        let mut interaction = None;
+        if !shadow_banned {
+            interaction = self
+                .analytics_service
+                .record_interaction_in_tx(
+                    &mut tx,
+                    Some(user_id),
+                    &InteractionType::Comment.to_string(),
+                    Some(post_id),
+                    Some(comment_result.id),
+                    None,
+                )
+                .await
+                .map_err(|e| {
+                    error!("Failed to record comment interaction: {}", e);
+                    CommentError::InternalError(e.to_string())
+                })?;
+
+            for notification in
+                comment_notifications(&comment_result, user_id, post_author_id, parent_author_id)
+            {
+                self.notification_service
+                    .enqueue_notification(&mut tx, &notification)
+                    .await
+                    .map_err(|e| {
+                        error!("Failed to queue comment notification: {}", e);
+                        CommentError::InternalError(e.to_string())
+                    })?;
+            }
+        }
+
         // Commit transaction
         tx.commit().await.map_err(|e| {
             error!("Failed to commit transaction: {}", e);
@@ -204,31 +468,51 @@ impl CommentService {
         })?;
 
         // Get author info for response
-        let author = sqlx::query_as::<_, CommentAuthor>(
+        let author = sqlx::query_as!(
+            CommentAuthor,
             r#"
-            SELECT id, username as name FROM global.users
+            SELECT id, username as name,
+                global.user_avatar_url(avatar_url, email) AS avatar_url
+            FROM global.users
             WHERE id = $1
             "#,
+            user_id
         )
-        .bind(user_id)
         .fetch_one(&self.pool)
         .await
         .map_err(CommentError::DatabaseError)?;
 
-        // Send notification if this is a reply and parent author is not the same as current user
-        if let Some(parent_author) = parent_author_id {
-            if parent_author != user_id {
-                // Send notification asynchronously - don't block the response
-                let comment_clone = comment_result.clone();
-                let self_clone = self.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = self_clone
-                        .send_reply_notification(&comment_clone, &parent_author)
-                        .await
-                    {
-                        error!("Failed to send notification: {:?}", e);
-                    }
-                });
+        if let Some(event) = interaction {
+            self.analytics_service.export_interaction(event);
+        }
+
+        // Deliver the queued notifications now rather than waiting for the next relay pass
+        if !shadow_banned {
+            let notification_service = self.notification_service.clone();
+            tokio::spawn(async move {
+                if let Err(e) = notification_service.deliver_outbox().await {
+                    error!("Failed to deliver comment notifications: {}", e);
+                }
+            });
+        }
+
+        // Comments from others earn the post author reputation
+        if !shadow_banned && post_author_id != user_id {
+            queue_reputation_update(&self.pool, &[post_author_id]).await;
+        }
+
+        // Let everyone following the thread know about the new reply
+        if let Some(root_id) = thread_root_id.filter(|_| !shadow_banned) {
+            let reply = PendingThreadReply {
+                comment_id: comment_result.id,
+                actor_id: user_id,
+                notified_directly: parent_author_id.filter(|author| *author != user_id),
+            };
+            if let Err(e) = self
+                .queue_thread_notification(root_id, post_id, reply)
+                .await
+            {
+                error!("Failed to queue thread notification: {:?}", e);
             }
         }
 
@@ -238,45 +522,26 @@ impl CommentService {
 
             // Delete the comments cache
             let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
+                .connection()
                 .await
                 .map_err(CommentError::CacheError)?
                 .del(&cache_key)
                 .await
                 .map_err(CommentError::CacheError)?;
 
-            // Increment comment count in cache if exists
-            let count_key = format!("post:comment_count:{}", post_id);
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(CommentError::CacheError)?
-                .incr(&count_key, 1)
-                .await
-                .map_err(CommentError::CacheError)?;
-
-            // Publish realtime event via Redis
-            if let Ok(mut conn) = cache.get_client().get_multiplexed_async_connection().await {
-                let _: Result<String, redis::RedisError> = conn
-                    .xadd(
-                        "stream:comments",
-                        "*",
-                        &[
-                            ("event", "comment_created"),
-                            ("post_id", &post_id.to_string()),
-                            ("comment_id", &comment_result.id.to_string()),
-                            (
-                                "parent_id",
-                                &comment_data
-                                    .parent_comment_id
-                                    .map(|id| id.to_string())
-                                    .unwrap_or_else(|| "null".to_string()),
-                            ),
-                        ],
-                    )
-                    .await;
+            // The public count and realtime stream never include shadow-banned comments
+            if !shadow_banned {
+                // Increment comment count in cache if exists
+                adjust_cached_comment_count(cache, post_id, 1).await?;
+
+                // Publish realtime event via Redis
+                publish_comment_created(
+                    cache,
+                    post_id,
+                    comment_result.id,
+                    comment_data.parent_comment_id,
+                )
+                .await;
             }
         }
 
@@ -284,9 +549,14 @@ impl CommentService {
         let comment_response = CommentResponse {
             id: comment_result.id,
             content_html: content_html,
-            author,
+            author: Some(author),
+            guest_author: None,
             created_at: comment_result.created_at,
             parent_comment_id: comment_result.parent_comment_id,
+            pinned: false,
+            is_post_author: user_id == post_author_id,
+            score: 0,
+            collapsed: false,
             replies: None, // New comment has no replies
         };
 
@@ -297,291 +567,1061 @@ impl CommentService {
         Ok(comment_response)
     }
 
-    // Get comments for a post (with threading)
-    pub async fn get_post_comments(
+    // Create a comment without an account. Guest comments are held for moderation
+    // and only become visible once approved. `flag_enabled` is the state of the
+    // anonymous comments feature flag, which enables them on top of the config.
+    pub async fn create_guest_comment(
         &self,
         post_id: i64,
-        page: Option<i64>,
-        with_cache: bool,
-    ) -> Result<Vec<CommentResponse>, CommentError> {
-        let page = page.unwrap_or(1);
-        let offset = (page - 1) * COMMENTS_PER_PAGE;
-
-        if with_cache && self.redis_cache.is_some() {
-            let cache_key = format!("comments:post:{}", post_id);
-
-            // Try to get from cache first
-            let cache_result = self
-                .redis_cache
-                .as_ref()
-                .unwrap()
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(|e| {
-                    error!("Error accessing cache: {}", e);
-                    CommentError::CacheError(e)
-                })?
-                .get::<_, Option<String>>(&cache_key)
-                .await;
-
-            // If we have a cached result, use it
-            if let Ok(Some(cached_data)) = cache_result {
-                return serde_json::from_str::<Vec<CommentResponse>>(&cached_data).map_err(|e| {
-                    error!("Error deserializing cached data: {}", e);
-                    CommentError::DeserializationError
-                });
-            }
+        comment_data: CreateGuestCommentRequest,
+        flag_enabled: bool,
+    ) -> Result<CommentResponse, CommentError> {
+        if !self.guest_comments_enabled && !flag_enabled {
+            return Err(CommentError::GuestCommentsDisabled);
         }
 
-        // Get all comments for the post (limited to root comments + pagination)
-        let root_comments = sqlx::query_as::<_, Comment>(
+        verify_captcha(&comment_data.captcha_token).await?;
+
+        // Check if post exists
+        let post_exists = sqlx::query_scalar!(
             r#"
-            SELECT * FROM global.comments
-            WHERE post_id = $1 AND parent_comment_id IS NULL AND is_deleted = false
-            ORDER BY created_at DESC
-            LIMIT $2 OFFSET $3
+            SELECT EXISTS(
+                SELECT 1 FROM global.posts WHERE id = $1 AND blog_id = $2 AND is_deleted = false
+            ) AS "exists!"
             "#,
+            post_id,
+            current_blog_id()
         )
-        .bind(post_id)
-        .bind(COMMENTS_PER_PAGE)
-        .bind(offset)
-        .fetch_all(&self.pool)
+        .fetch_one(&self.pool)
         .await
         .map_err(CommentError::DatabaseError)?;
 
-        let mut comment_responses = Vec::new();
+        if !post_exists {
+            return Err(CommentError::PostNotFound);
+        }
+        self.check_accepts_comments(post_id).await?;
 
-        // Process each root comment
-        for comment in root_comments {
-            let replies = self.get_comment_replies(comment.id).await?;
+        // Calculate nesting level and validate max depth
+        let nesting_level = if let Some(parent_id) = comment_data.parent_comment_id {
+            let new_level = self.get_parent_nesting_level(post_id, parent_id).await? + 1;
 
-            // Get author info
-            let author = sqlx::query_as::<_, CommentAuthor>(
-                r#"
-                SELECT id, username as name FROM global.users
-                WHERE id = $1
-                "#,
-            )
-            .bind(comment.user_id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(CommentError::DatabaseError)?;
+            if new_level > MAX_NESTING_DEPTH {
+                return Err(CommentError::MaxNestingDepthReached);
+            }
 
-            // Build response
-            let comment_response = CommentResponse {
-                id: comment.id,
-                content_html: comment.content_html,
-                author,
-                created_at: comment.created_at,
-                parent_comment_id: None,
-                replies: Some(replies),
-            };
+            new_level
+        } else {
+            0 // Root level comment
+        };
 
-            comment_responses.push(comment_response);
-        }
+        // Guests never get markdown rendering
+        let content_html = self.process_markdown(&comment_data.content, false).await?;
 
-        // Cache the results if a cache client is available
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = format!("comments:post:{}", post_id);
-            let json_data = serde_json::to_string(&comment_responses).unwrap_or_default();
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(CommentError::CacheError)?
-                .set_ex(&cache_key, &json_data, 3600) // 1 hour cache
-                .await
-                .map_err(CommentError::CacheError)?;
-        }
+        let comment = sqlx::query_as!(
+            Comment,
+            r#"
+            INSERT INTO global.comments (
+                post_id, user_id, guest_name, guest_email, parent_comment_id, content,
+                content_html, is_deleted, markdown_enabled, moderation_status, nesting_level,
+                created_at, updated_at, blog_id
+            )
+            VALUES ($1, NULL, $2, $3, $4, $5, $6, false, false, 'pending', $7, $8, $8,
+                (SELECT blog_id FROM global.posts WHERE id = $1))
+            RETURNING id, post_id, user_id, guest_name, guest_email, parent_comment_id, content, content_html,
+                is_deleted, deleted_by, deleted_at, markdown_enabled, moderation_status, is_pinned,
+                score, nesting_level, created_at, updated_at
+            "#,
+            post_id,
+            comment_data.name.trim(),
+            comment_data.email.trim(),
+            comment_data.parent_comment_id,
+            comment_data.content,
+            content_html,
+            nesting_level,
+            Utc::now()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Failed to insert guest comment: {}", e);
+            CommentError::DatabaseError(e)
+        })?;
 
         info!(
-            "Retrieved {} comments for post {}",
-            comment_responses.len(),
-            post_id
+            "Created guest comment with ID: {} for post: {}, awaiting moderation",
+            comment.id, post_id
         );
-        Ok(comment_responses)
+
+        Ok(CommentResponse {
+            id: comment.id,
+            content_html: comment.content_html,
+            author: None,
+            guest_author: comment.guest_name.map(|name| GuestAuthor { name }),
+            created_at: comment.created_at,
+            parent_comment_id: comment.parent_comment_id,
+            pinned: false,
+            is_post_author: false,
+            score: 0,
+            collapsed: false,
+            replies: None,
+        })
     }
 
-    // Get replies for a specific comment (non-recursive implementation to avoid infinite futures)
-    async fn get_comment_replies(
+    // List comments waiting in the moderation queue, oldest first
+    pub async fn get_pending_comments(
         &self,
-        comment_id: i64,
-    ) -> Result<Vec<CommentResponse>, CommentError> {
-        // Get all direct replies to this comment
-        let comment_replies = sqlx::query(
+        pagination: &Pagination,
+    ) -> Result<Vec<PendingCommentResponse>, CommentError> {
+        let comments = sqlx::query_as!(
+            Comment,
             r#"
-            SELECT c.*, u.username as author_name, u.id as author_id
-            FROM global.comments c
-            JOIN global.users u ON c.user_id = u.id
-            WHERE c.parent_comment_id = $1 AND c.is_deleted = false
-            ORDER BY c.created_at ASC
+            SELECT id, post_id, user_id, guest_name, guest_email, parent_comment_id, content, content_html,
+                is_deleted, deleted_by, deleted_at, markdown_enabled, moderation_status, is_pinned,
+                score, nesting_level, created_at, updated_at
+            FROM global.comments
+            WHERE moderation_status = 'pending' AND is_deleted = false AND blog_id = $3
+            ORDER BY created_at ASC
+            LIMIT $1 OFFSET $2
             "#,
+            pagination.limit,
+            pagination.offset,
+            current_blog_id()
         )
-        .bind(comment_id)
         .fetch_all(&self.pool)
         .await
         .map_err(CommentError::DatabaseError)?;
 
-        let mut replies = Vec::with_capacity(comment_replies.len());
-
-        // Process each reply
-        for row in comment_replies {
-            let reply_id: i64 = row.get("id");
-            let created_at: chrono::DateTime<chrono::Utc> = row.get("created_at");
-            let parent_comment_id: Option<i64> = row.get("parent_comment_id");
-            let content_html: String = row.get("content_html");
-            let author_id: uuid::Uuid = row.get("author_id");
-            let author_name: String = row.get("author_name");
-
-            // We'll use a non-recursive approach for nested replies
-            // by fetching them explicitly for each level
-            let nested_replies = if row.get::<i32, _>("nesting_level") < MAX_NESTING_DEPTH {
-                // Get 2nd level replies using a separate query
-                let second_level_replies = sqlx::query(
-                    r#"
-                    SELECT c.*, u.username as author_name, u.id as author_id
-                    FROM global.comments c
-                    JOIN global.users u ON c.user_id = u.id
-                    WHERE c.parent_comment_id = $1 AND c.is_deleted = false
-                    ORDER BY c.created_at ASC
-                    "#,
-                )
-                .bind(reply_id)
-                .fetch_all(&self.pool)
-                .await
-                .map_err(CommentError::DatabaseError)?;
-
-                // Only process if we have replies
-                if !second_level_replies.is_empty() {
-                    let mut level2_replies = Vec::with_capacity(second_level_replies.len());
-
-                    for l2_row in second_level_replies {
-                        let l2_reply_id: i64 = l2_row.get("id");
-                        let l2_created_at: chrono::DateTime<chrono::Utc> = l2_row.get("created_at");
-                        let l2_parent_comment_id: Option<i64> = l2_row.get("parent_comment_id");
-                        let l2_content_html: String = l2_row.get("content_html");
-                        let l2_author_id: uuid::Uuid = l2_row.get("author_id");
-                        let l2_author_name: String = l2_row.get("author_name");
-
-                        // Check for 3rd level of nesting (final level)
-                        let l3_replies =
-                            if l2_row.get::<i32, _>("nesting_level") < MAX_NESTING_DEPTH {
-                                let third_level_replies = sqlx::query(
-                                    r#"
-                                SELECT c.*, u.username as author_name, u.id as author_id
-                                FROM global.comments c
-                                JOIN global.users u ON c.user_id = u.id
-                                WHERE c.parent_comment_id = $1 AND c.is_deleted = false
-                                ORDER BY c.created_at ASC
-                                "#,
-                                )
-                                .bind(l2_reply_id)
-                                .fetch_all(&self.pool)
-                                .await
-                                .map_err(CommentError::DatabaseError)?;
-
-                                if !third_level_replies.is_empty() {
-                                    let mut l3_replies_vec =
-                                        Vec::with_capacity(third_level_replies.len());
-
-                                    for l3_row in third_level_replies {
-                                        let l3_reply = CommentResponse {
-                                            id: l3_row.get("id"),
-                                            content_html: l3_row.get("content_html"),
-                                            author: CommentAuthor {
-                                                id: l3_row.get("author_id"),
-                                                name: l3_row.get("author_name"),
-                                            },
-                                            created_at: l3_row.get("created_at"),
-                                            parent_comment_id: l3_row.get("parent_comment_id"),
-                                            replies: None, // No more nesting
-                                        };
-                                        l3_replies_vec.push(l3_reply);
-                                    }
-
-                                    Some(l3_replies_vec)
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            };
-
-                        // Add level 2 reply
-                        let l2_reply = CommentResponse {
-                            id: l2_reply_id,
-                            content_html: l2_content_html,
-                            author: CommentAuthor {
-                                id: l2_author_id,
-                                name: l2_author_name,
-                            },
-                            created_at: l2_created_at,
-                            parent_comment_id: l2_parent_comment_id,
-                            replies: l3_replies,
-                        };
-
-                        level2_replies.push(l2_reply);
-                    }
-
-                    Some(level2_replies)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
-            // Add main reply
-            let reply = CommentResponse {
-                id: reply_id,
-                content_html,
-                author: CommentAuthor {
-                    id: author_id,
-                    name: author_name,
+        Ok(comments
+            .into_iter()
+            .map(|comment| PendingCommentResponse {
+                post_id: comment.post_id,
+                guest_email: comment.guest_email,
+                comment: CommentResponse {
+                    id: comment.id,
+                    content_html: comment.content_html,
+                    author: None,
+                    guest_author: comment.guest_name.map(|name| GuestAuthor { name }),
+                    created_at: comment.created_at,
+                    parent_comment_id: comment.parent_comment_id,
+                    pinned: false,
+                    is_post_author: false,
+                    score: comment.score,
+                    collapsed: is_collapsed(comment.score, false),
+                    replies: None,
                 },
-                created_at,
-                parent_comment_id,
-                replies: nested_replies,
-            };
-
-            replies.push(reply);
-        }
-
-        Ok(replies)
+            })
+            .collect())
     }
 
-    // Delete a comment (soft delete)
-    pub async fn delete_comment(
+    // Approve or reject a comment from the moderation queue. An approved comment is announced
+    // like a new one: to the authors of the post and of its parent, to the subscribers of its
+    // thread and on the realtime stream. Guests have no account to act as, so their comments
+    // are announced with the nil actor id, which matches no user.
+    pub async fn moderate_comment(
         &self,
         comment_id: i64,
-        user_id: Uuid,
-        is_admin: bool,
-    ) -> Result<i64, CommentError> {
-        // Get the comment
-        let comment = sqlx::query_as::<_, Comment>(
+        approve: bool,
+    ) -> Result<(), CommentError> {
+        let status = if approve { "approved" } else { "rejected" };
+
+        // The new status and the approved comment's notifications are written together
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(CommentError::DatabaseError)?;
+
+        let moderated = sqlx::query!(
             r#"
-            SELECT * FROM global.comments
-            WHERE id = $1 AND is_deleted = false
+            UPDATE global.comments
+            SET moderation_status = $1, updated_at = $2
+            WHERE id = $3 AND moderation_status = 'pending' AND blog_id = $4
+            RETURNING post_id, user_id
             "#,
+            status,
+            Utc::now(),
+            comment_id,
+            current_blog_id()
         )
-        .bind(comment_id)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&mut *tx)
         .await
         .map_err(CommentError::DatabaseError)?
         .ok_or(CommentError::NotFound)?;
+        let (post_id, author_id) = (moderated.post_id, moderated.user_id);
 
-        // Check ownership
-        if comment.user_id != user_id && !is_admin {
-            return Err(CommentError::Unauthorized);
-        }
+        let shadow_banned = match author_id {
+            Some(author_id) => self.is_shadow_banned(author_id).await?,
+            None => false,
+        };
 
-        // Soft delete the comment
-        sqlx::query(
+        let mut announced = None;
+        if approve && !shadow_banned {
+            let actor_id = author_id.unwrap_or_else(Uuid::nil);
+            let comment = sqlx::query_as::<_, Comment>(
+                r#"
+                SELECT id, post_id, user_id, guest_name, guest_email, parent_comment_id, content,
+                    content_html, is_deleted, deleted_by, deleted_at, markdown_enabled,
+                    moderation_status, is_pinned, score, nesting_level, created_at, updated_at
+                FROM global.comments
+                WHERE id = $1
+                "#,
+            )
+            .bind(comment_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(CommentError::DatabaseError)?;
+
+            let post_author_id =
+                sqlx::query_scalar::<_, Uuid>("SELECT user_id FROM global.posts WHERE id = $1")
+                    .bind(post_id)
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(CommentError::DatabaseError)?;
+
+            let parent_author_id = match comment.parent_comment_id {
+                Some(parent_id) => sqlx::query_scalar::<_, Option<Uuid>>(
+                    "SELECT user_id FROM global.comments WHERE id = $1",
+                )
+                .bind(parent_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(CommentError::DatabaseError)?
+                .flatten(),
+                None => None,
+            };
+
+            for notification in
+                comment_notifications(&comment, actor_id, post_author_id, parent_author_id)
+            {
+                self.notification_service
+                    .enqueue_notification(&mut tx, &notification)
+                    .await
+                    .map_err(|e| {
+                        error!("Failed to queue comment notification: {}", e);
+                        CommentError::InternalError(e.to_string())
+                    })?;
+            }
+
+            announced = Some((comment, actor_id, parent_author_id));
+        }
+
+        tx.commit().await.map_err(CommentError::DatabaseError)?;
+
+        // A newly visible comment changes the post's listing and count
+        if approve {
+            if let Some(cache) = &self.redis_cache {
+                let mut conn = cache.connection().await.map_err(CommentError::CacheError)?;
+                conn.del::<_, ()>(&[
+                    format!("comments:post:{}", post_id),
+                    format!("post:comment_count:{}", post_id),
+                ])
+                .await
+                .map_err(CommentError::CacheError)?;
+            }
+        }
+
+        if let Some((comment, actor_id, parent_author_id)) = announced {
+            let notification_service = self.notification_service.clone();
+            tokio::spawn(async move {
+                if let Err(e) = notification_service.deliver_outbox().await {
+                    error!("Failed to deliver comment notifications: {}", e);
+                }
+            });
+
+            if let Some(parent_id) = comment.parent_comment_id {
+                let reply = PendingThreadReply {
+                    comment_id,
+                    actor_id,
+                    notified_directly: parent_author_id.filter(|author| *author != actor_id),
+                };
+                let queued = match self.get_thread_root(parent_id).await {
+                    Ok((root_id, _)) => {
+                        self.queue_thread_notification(root_id, post_id, reply)
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = queued {
+                    error!("Failed to queue thread notification: {:?}", e);
+                }
+            }
+
+            if let Some(cache) = &self.redis_cache {
+                publish_comment_created(cache, post_id, comment_id, comment.parent_comment_id)
+                    .await;
+            }
+        }
+
+        // Approved comments earn the post author reputation, rejected ones cost their author
+        let affected = if approve {
+            self.get_post_author(post_id).await?
+        } else {
+            author_id
+        };
+        queue_reputation_update(&self.pool, affected.as_slice()).await;
+
+        info!("Comment {} {} by moderator", comment_id, status);
+        Ok(())
+    }
+
+    // Get comments for a post (with threading). Comments by users the viewer blocked are
+    // left out of the viewer's listing; the cached pages are shared and unfiltered.
+    pub async fn get_post_comments(
+        &self,
+        post_id: i64,
+        pagination: &Pagination,
+        with_cache: bool,
+        viewer_id: Option<Uuid>,
+    ) -> Result<Vec<CommentResponse>, CommentError> {
+        // Every page of a post's comments lives in one hash so a single DEL invalidates them all
+        let cache_key = format!("comments:post:{}", post_id);
+
+        // Shadow-banned users still see their own comments, so their view of a page is cached
+        // separately from the public one that everybody else shares
+        let page_field = match viewer_id {
+            Some(viewer_id) if self.is_shadow_banned(viewer_id).await? => {
+                format!("{}:viewer:{}", pagination.cache_field(), viewer_id)
+            }
+            _ => pagination.cache_field(),
+        };
+
+        let blocked = match viewer_id {
+            Some(viewer_id) => {
+                blocked_user_ids(&self.pool, self.redis_cache.as_ref(), viewer_id).await?
+            }
+            None => HashSet::new(),
+        };
+
+        if with_cache && self.redis_cache.is_some() {
+            // Try to get from cache first
+            let cache_result = self
+                .redis_cache
+                .as_ref()
+                .unwrap()
+                .connection()
+                .await
+                .map_err(|e| {
+                    error!("Error accessing cache: {}", e);
+                    CommentError::CacheError(e)
+                })?
+                .hget::<_, _, Option<String>>(&cache_key, &page_field)
+                .await;
+
+            // If we have a cached result, use it
+            if let Ok(Some(cached_data)) = cache_result {
+                let mut comments = serde_json::from_str::<Vec<CommentResponse>>(&cached_data)
+                    .map_err(|e| {
+                        error!("Error deserializing cached data: {}", e);
+                        CommentError::DeserializationError
+                    })?;
+                remove_blocked_authors(&mut comments, &blocked);
+                return Ok(comments);
+            }
+        }
+
+        // Comments by the post author are highlighted in the listing
+        let post_author_id = self.get_post_author(post_id).await?;
+
+        // Get all comments for the post (limited to root comments + pagination), pinned first
+        let query = sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT id, post_id, user_id, guest_name, guest_email, parent_comment_id, content, content_html,
+                is_deleted, deleted_by, deleted_at, markdown_enabled, moderation_status, is_pinned,
+                score, nesting_level, created_at, updated_at
+            FROM global.comments
+            WHERE post_id = $1 AND parent_comment_id IS NULL AND is_deleted = false
+                AND moderation_status = 'approved'
+                AND NOT EXISTS (
+                    SELECT 1 FROM global.users u
+                    WHERE u.id = user_id AND u.is_shadow_banned AND u.id IS DISTINCT FROM $4
+                )
+            ORDER BY is_pinned DESC, created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            post_id,
+            pagination.limit,
+            pagination.offset,
+            viewer_id
+        )
+        .fetch_all(&self.pool);
+        let root_comments = timed("comments.post_listing", query)
+            .await
+            .map_err(CommentError::DatabaseError)?;
+
+        // Authors and replies of all root comments at once
+        let root_ids: Vec<i64> = root_comments.iter().map(|comment| comment.id).collect();
+        let mut user_ids: Vec<Uuid> = root_comments
+            .iter()
+            .filter_map(|comment| comment.user_id)
+            .collect();
+        user_ids.sort();
+        user_ids.dedup();
+        let authors = async {
+            sqlx::query_as!(
+                CommentAuthor,
+                r#"
+                SELECT id, username as name,
+                    global.user_avatar_url(avatar_url, email) AS avatar_url
+                FROM global.users
+                WHERE id = ANY($1)
+                "#,
+                &user_ids
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(CommentError::DatabaseError)
+        };
+        let (authors, mut replies) =
+            tokio::try_join!(authors, self.get_comment_replies(&root_ids, viewer_id))?;
+        let authors: HashMap<Uuid, CommentAuthor> = authors
+            .into_iter()
+            .map(|author| (author.id, author))
+            .collect();
+
+        let mut comment_responses = Vec::with_capacity(root_comments.len());
+        for comment in root_comments {
+            // Guest comments have no account
+            let author = comment
+                .user_id
+                .and_then(|user_id| authors.get(&user_id).cloned());
+            let guest_author = comment.guest_name.map(|name| GuestAuthor { name });
+            let is_post_author = comment.user_id.is_some() && comment.user_id == post_author_id;
+
+            comment_responses.push(CommentResponse {
+                id: comment.id,
+                content_html: comment.content_html,
+                author,
+                guest_author,
+                created_at: comment.created_at,
+                parent_comment_id: None,
+                pinned: comment.is_pinned,
+                is_post_author,
+                score: comment.score,
+                collapsed: is_collapsed(comment.score, comment.is_pinned),
+                replies: Some(replies.remove(&comment.id).unwrap_or_default()),
+            });
+        }
+
+        // Cache the results if a cache client is available
+        if let Some(cache) = &self.redis_cache {
+            let json_data = serde_json::to_string(&comment_responses).unwrap_or_default();
+            let mut conn = cache.connection().await.map_err(CommentError::CacheError)?;
+            conn.hset::<_, _, _, ()>(&cache_key, &page_field, &json_data)
+                .await
+                .map_err(CommentError::CacheError)?;
+            conn.expire::<_, ()>(&cache_key, 3600) // 1 hour cache
+                .await
+                .map_err(CommentError::CacheError)?;
+        }
+
+        remove_blocked_authors(&mut comment_responses, &blocked);
+        info!(
+            "Retrieved {} comments for post {}",
+            comment_responses.len(),
+            post_id
+        );
+        Ok(comment_responses)
+    }
+
+    // Find a comment and the page of its post's listing its thread is on, for links from
+    // notifications. Comments hidden from the viewer's listing, or inside a thread hidden from
+    // it, are not found.
+    pub async fn get_comment_permalink(
+        &self,
+        comment_id: i64,
+        limit: i64,
+        viewer_id: Option<Uuid>,
+    ) -> Result<CommentPermalinkResponse, CommentError> {
+        let row = sqlx::query(
+            r#"
+            SELECT c.*, u.username as author_name, u.id as author_id,
+                global.user_avatar_url(u.avatar_url, u.email) as author_avatar_url,
+                COALESCE(c.user_id = p.user_id, false) as is_post_author,
+                p.slug as post_slug
+            FROM global.comments c
+            JOIN global.posts p ON c.post_id = p.id
+            LEFT JOIN global.users u ON c.user_id = u.id
+            WHERE c.id = $1 AND p.blog_id = $2 AND p.is_deleted = false
+            "#,
+        )
+        .bind(comment_id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?
+        .ok_or(CommentError::NotFound)?;
+
+        // The comment and every comment above it must be in the viewer's listing
+        let thread = sqlx::query!(
+            r#"
+            WITH RECURSIVE thread AS (
+                SELECT id, parent_comment_id, user_id, is_deleted, moderation_status
+                FROM global.comments WHERE id = $1
+                UNION ALL
+                SELECT c.id, c.parent_comment_id, c.user_id, c.is_deleted, c.moderation_status
+                FROM global.comments c
+                JOIN thread t ON c.id = t.parent_comment_id
+            )
+            SELECT t.id AS "id!", t.parent_comment_id, t.user_id,
+                t.is_deleted OR t.moderation_status <> 'approved'
+                    OR COALESCE(u.is_shadow_banned AND u.id IS DISTINCT FROM $2, false)
+                    AS "hidden!"
+            FROM thread t
+            LEFT JOIN global.users u ON u.id = t.user_id
+            "#,
+            comment_id,
+            viewer_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?;
+
+        let blocked = match viewer_id {
+            Some(viewer_id) => {
+                blocked_user_ids(&self.pool, self.redis_cache.as_ref(), viewer_id).await?
+            }
+            None => HashSet::new(),
+        };
+        let visible = thread.iter().all(|row| {
+            !row.hidden
+                && row
+                    .user_id
+                    .is_none_or(|user_id| !blocked.contains(&user_id))
+        });
+        let root_comment_id = thread
+            .iter()
+            .find(|row| row.parent_comment_id.is_none())
+            .map(|row| row.id);
+        let root_comment_id = match root_comment_id {
+            Some(root_comment_id) if visible => root_comment_id,
+            _ => return Err(CommentError::NotFound),
+        };
+
+        // Rank of the thread among the post's root comments, in the order they are listed
+        let post_id: i64 = row.get("post_id");
+        let position = sqlx::query_scalar!(
+            r#"
+            SELECT position AS "position!" FROM (
+                SELECT id, ROW_NUMBER() OVER (ORDER BY is_pinned DESC, created_at DESC) AS position
+                FROM global.comments
+                WHERE post_id = $1 AND parent_comment_id IS NULL AND is_deleted = false
+                    AND moderation_status = 'approved'
+                    AND NOT EXISTS (
+                        SELECT 1 FROM global.users u
+                        WHERE u.id = user_id AND u.is_shadow_banned AND u.id IS DISTINCT FROM $3
+                    )
+            ) ranked
+            WHERE id = $2
+            "#,
+            post_id,
+            root_comment_id,
+            viewer_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?
+        .ok_or(CommentError::NotFound)?;
+        let page_offset = (position - 1) / limit * limit;
+
+        let (author, guest_author) = row_authors(&row);
+        Ok(CommentPermalinkResponse {
+            comment: CommentResponse {
+                id: comment_id,
+                content_html: row.get("content_html"),
+                author,
+                guest_author,
+                created_at: row.get("created_at"),
+                parent_comment_id: row.get("parent_comment_id"),
+                pinned: row.get("is_pinned"),
+                is_post_author: row.get("is_post_author"),
+                score: row.get("score"),
+                collapsed: is_collapsed(row.get("score"), row.get("is_pinned")),
+                replies: None,
+            },
+            post_id,
+            post_slug: row.get("post_slug"),
+            root_comment_id,
+            page: page_offset / limit + 1,
+            limit,
+            cursor: encode_cursor(page_offset),
+        })
+    }
+
+    // Replies to the given comments, keyed by the comment they reply to, down to the deepest
+    // nesting level. Each level is loaded for all comments at once.
+    async fn get_comment_replies(
+        &self,
+        comment_ids: &[i64],
+        viewer_id: Option<Uuid>,
+    ) -> Result<HashMap<i64, Vec<CommentResponse>>, CommentError> {
+        let mut levels: Vec<Vec<PgRow>> = Vec::new();
+        let mut parent_ids = comment_ids.to_vec();
+        while !parent_ids.is_empty() && levels.len() < REPLY_LEVELS {
+            let rows = sqlx::query(
+                r#"
+                SELECT c.*, u.username as author_name, u.id as author_id,
+                    global.user_avatar_url(u.avatar_url, u.email) as author_avatar_url,
+                    COALESCE(c.user_id = p.user_id, false) as is_post_author
+                FROM global.comments c
+                JOIN global.posts p ON c.post_id = p.id
+                LEFT JOIN global.users u ON c.user_id = u.id
+                WHERE c.parent_comment_id = ANY($1) AND c.is_deleted = false
+                    AND c.moderation_status = 'approved'
+                    AND (u.id IS NULL OR NOT u.is_shadow_banned OR u.id = $2)
+                ORDER BY c.created_at ASC
+                "#,
+            )
+            .bind(&parent_ids)
+            .bind(viewer_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(CommentError::DatabaseError)?;
+
+            parent_ids = rows
+                .iter()
+                .filter(|row| row.get::<i32, _>("nesting_level") < MAX_NESTING_DEPTH)
+                .map(|row| row.get("id"))
+                .collect();
+            levels.push(rows);
+        }
+
+        // Assemble the threads from the deepest level up; replies of the last level loaded
+        // are not looked at
+        let mut replies_by_parent: HashMap<i64, Vec<CommentResponse>> = HashMap::new();
+        for (depth, rows) in levels.into_iter().enumerate().rev() {
+            let mut level: HashMap<i64, Vec<CommentResponse>> = HashMap::new();
+            for row in rows {
+                let Some(parent_id) = row.get::<Option<i64>, _>("parent_comment_id") else {
+                    continue;
+                };
+                let nested = depth + 1 < REPLY_LEVELS
+                    && row.get::<i32, _>("nesting_level") < MAX_NESTING_DEPTH;
+                let replies = if nested {
+                    replies_by_parent.remove(&row.get::<i64, _>("id"))
+                } else {
+                    None
+                };
+                level
+                    .entry(parent_id)
+                    .or_default()
+                    .push(reply_response(&row, replies));
+            }
+            replies_by_parent = level;
+        }
+
+        Ok(replies_by_parent)
+    }
+
+    // Check whether a user's comments are hidden from everyone but themselves
+    async fn is_shadow_banned(&self, user_id: Uuid) -> Result<bool, CommentError> {
+        let banned = sqlx::query_scalar!(
+            "SELECT is_shadow_banned FROM global.users WHERE id = $1",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?;
+
+        Ok(banned.unwrap_or(false))
+    }
+
+    // Shadow-ban or un-ban a user. Their existing comments change visibility at once, so the
+    // cached listings and counts of every post they commented on are dropped.
+    pub async fn set_shadow_ban(&self, user_id: Uuid, banned: bool) -> Result<(), CommentError> {
+        sqlx::query_scalar!(
+            "UPDATE global.users SET is_shadow_banned = $1, updated_at = $2 WHERE id = $3 RETURNING id",
+            banned,
+            Utc::now(),
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?
+        .ok_or(CommentError::UserNotFound)?;
+
+        if let Some(cache) = &self.redis_cache {
+            let post_ids = sqlx::query_scalar!(
+                "SELECT DISTINCT post_id FROM global.comments WHERE user_id = $1",
+                user_id
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(CommentError::DatabaseError)?;
+
+            if !post_ids.is_empty() {
+                let keys: Vec<String> = post_ids
+                    .iter()
+                    .flat_map(|post_id| {
+                        [
+                            format!("comments:post:{}", post_id),
+                            format!("post:comment_count:{}", post_id),
+                        ]
+                    })
+                    .collect();
+
+                cache
+                    .connection()
+                    .await
+                    .map_err(CommentError::CacheError)?
+                    .del::<_, ()>(keys)
+                    .await
+                    .map_err(CommentError::CacheError)?;
+            }
+        }
+
+        info!(
+            "User {} {}",
+            user_id,
+            if banned {
+                "shadow-banned"
+            } else {
+                "no longer shadow-banned"
+            }
+        );
+        Ok(())
+    }
+
+    // Get the author of a post, or None if the post does not exist
+    async fn get_post_author(&self, post_id: i64) -> Result<Option<Uuid>, CommentError> {
+        sqlx::query_scalar!(
+            "SELECT user_id FROM global.posts WHERE id = $1 AND blog_id = $2 AND is_deleted = false",
+            post_id,
+            current_blog_id()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)
+    }
+
+    // Refuse new comments on locked posts and on posts that reached the comment limit. Pending
+    // comments count towards the limit, so a flood held for moderation cannot exceed it.
+    async fn check_accepts_comments(&self, post_id: i64) -> Result<(), CommentError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT p.comments_locked,
+                (SELECT COUNT(*) FROM global.comments c
+                 WHERE c.post_id = p.id AND c.is_deleted = false) AS "total_comments!"
+            FROM global.posts p
+            WHERE p.id = $1
+            "#,
+            post_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?;
+
+        if row.comments_locked {
+            return Err(CommentError::CommentsLocked);
+        }
+        if row.total_comments >= self.max_comments_per_post {
+            return Err(CommentError::CommentLimitReached);
+        }
+        Ok(())
+    }
+
+    // Lock or unlock comments on a post. Only the post author or an admin may do this;
+    // existing comments stay visible either way.
+    pub async fn set_comments_locked(
+        &self,
+        post_id: i64,
+        user_id: Uuid,
+        is_admin: bool,
+        locked: bool,
+    ) -> Result<(), CommentError> {
+        let post_author_id = self
+            .get_post_author(post_id)
+            .await?
+            .ok_or(CommentError::PostNotFound)?;
+
+        if post_author_id != user_id && !is_admin {
+            return Err(CommentError::Unauthorized);
+        }
+
+        let slug = sqlx::query_scalar!(
+            "UPDATE global.posts SET comments_locked = $1 WHERE id = $2 RETURNING slug",
+            locked,
+            post_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?;
+
+        // The lock state is part of the cached post
+        if let Some(cache) = &self.redis_cache {
+            cache
+                .invalidate_post(post_id, &slug)
+                .await
+                .map_err(CommentError::CacheError)?;
+        }
+
+        info!(
+            "Comments on post {} {} by user {}",
+            post_id,
+            if locked { "locked" } else { "unlocked" },
+            user_id
+        );
+        Ok(())
+    }
+
+    // Pin or unpin a top-level comment. Only the post author or an admin may do this, and
+    // pinning a comment replaces any comment already pinned on the same post.
+    pub async fn set_comment_pinned(
+        &self,
+        comment_id: i64,
+        user_id: Uuid,
+        is_admin: bool,
+        pinned: bool,
+    ) -> Result<(), CommentError> {
+        let comment = sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT id, post_id, user_id, guest_name, guest_email, parent_comment_id, content, content_html,
+                is_deleted, deleted_by, deleted_at, markdown_enabled, moderation_status, is_pinned,
+                score, nesting_level, created_at, updated_at
+            FROM global.comments
+            WHERE id = $1 AND is_deleted = false AND moderation_status = 'approved'
+            "#,
+            comment_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?
+        .ok_or(CommentError::NotFound)?;
+
+        let post_author_id = self
+            .get_post_author(comment.post_id)
+            .await?
+            .ok_or(CommentError::PostNotFound)?;
+
+        if post_author_id != user_id && !is_admin {
+            return Err(CommentError::Unauthorized);
+        }
+
+        if comment.parent_comment_id.is_some() {
+            return Err(CommentError::ValidationError(
+                "Only top-level comments can be pinned".to_string(),
+            ));
+        }
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(CommentError::DatabaseError)?;
+
+        if pinned {
+            sqlx::query!(
+                "UPDATE global.comments SET is_pinned = false WHERE post_id = $1 AND is_pinned",
+                comment.post_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(CommentError::DatabaseError)?;
+        }
+
+        sqlx::query!(
+            "UPDATE global.comments SET is_pinned = $1 WHERE id = $2",
+            pinned,
+            comment_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(CommentError::DatabaseError)?;
+
+        tx.commit().await.map_err(CommentError::DatabaseError)?;
+
+        // Pinning reorders the post's listing
+        if let Some(cache) = &self.redis_cache {
+            cache
+                .connection()
+                .await
+                .map_err(CommentError::CacheError)?
+                .del::<_, ()>(format!("comments:post:{}", comment.post_id))
+                .await
+                .map_err(CommentError::CacheError)?;
+        }
+
+        info!(
+            "Comment {} {} by user {}",
+            comment_id,
+            if pinned { "pinned" } else { "unpinned" },
+            user_id
+        );
+        Ok(())
+    }
+
+    // React to a comment, or take a reaction back, and rescore it. Reacting again with the
+    // same reaction is a no-op.
+    pub async fn set_comment_reaction(
+        &self,
+        comment_id: i64,
+        user_id: Uuid,
+        reaction: &str,
+        reacted: bool,
+    ) -> Result<CommentScoreResponse, CommentError> {
+        let shortcode = markdown::emoji::reaction_shortcode(reaction).ok_or_else(|| {
+            CommentError::ValidationError(format!("Unknown reaction: {}", reaction.trim()))
+        })?;
+        let comment = self.get_listed_comment(comment_id, user_id).await?;
+
+        if reacted {
+            sqlx::query!(
+                r#"
+                INSERT INTO global.comment_reactions (comment_id, user_id, reaction)
+                VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING
+                "#,
+                comment_id,
+                user_id,
+                shortcode
+            )
+            .execute(&self.pool)
+            .await
+        } else {
+            sqlx::query!(
+                "DELETE FROM global.comment_reactions WHERE comment_id = $1 AND user_id = $2 AND reaction = $3",
+                comment_id,
+                user_id,
+                shortcode
+            )
+            .execute(&self.pool)
+            .await
+        }
+        .map_err(CommentError::DatabaseError)?;
+
+        self.rescore_comment(&comment).await
+    }
+
+    // Report a comment to the moderators and rescore it. Users report a comment once; their
+    // later reports only update the reason.
+    pub async fn report_comment(
+        &self,
+        comment_id: i64,
+        user_id: Uuid,
+        reason: Option<String>,
+    ) -> Result<CommentScoreResponse, CommentError> {
+        let reason = reason
+            .map(|reason| reason.trim().to_string())
+            .filter(|reason| !reason.is_empty());
+        if reason
+            .as_ref()
+            .is_some_and(|reason| reason.chars().count() > MAX_REPORT_REASON_LENGTH)
+        {
+            return Err(CommentError::ValidationError(format!(
+                "Report reason cannot exceed {} characters",
+                MAX_REPORT_REASON_LENGTH
+            )));
+        }
+
+        let comment = self.get_listed_comment(comment_id, user_id).await?;
+        if comment.user_id == Some(user_id) {
+            return Err(CommentError::ValidationError(
+                "You cannot report your own comment".to_string(),
+            ));
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO global.comment_reports (comment_id, user_id, reason)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (comment_id, user_id) DO UPDATE SET reason = EXCLUDED.reason
+            "#,
+            comment_id,
+            user_id,
+            reason
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?;
+
+        warn!("Comment {} reported by user {}", comment_id, user_id);
+        self.rescore_comment(&comment).await
+    }
+
+    // A comment of the blog as listed to the user, who can react to it or report it
+    async fn get_listed_comment(
+        &self,
+        comment_id: i64,
+        user_id: Uuid,
+    ) -> Result<Comment, CommentError> {
+        sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT c.id, c.post_id, c.user_id, c.guest_name, c.guest_email, c.parent_comment_id,
+                c.content, c.content_html, c.is_deleted, c.deleted_by, c.deleted_at,
+                c.markdown_enabled, c.moderation_status, c.is_pinned, c.score, c.nesting_level,
+                c.created_at, c.updated_at
+            FROM global.comments c
+            JOIN global.posts p ON c.post_id = p.id
+            LEFT JOIN global.users u ON c.user_id = u.id
+            WHERE c.id = $1 AND p.blog_id = $2 AND p.is_deleted = false
+                AND c.is_deleted = false AND c.moderation_status = 'approved'
+                AND (u.id IS NULL OR NOT u.is_shadow_banned OR u.id = $3)
+            "#,
+            comment_id,
+            current_blog_id(),
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?
+        .ok_or(CommentError::NotFound)
+    }
+
+    async fn rescore_comment(
+        &self,
+        comment: &Comment,
+    ) -> Result<CommentScoreResponse, CommentError> {
+        let score = self
+            .score_service
+            .rescore_comment(comment.id)
+            .await
+            .map_err(CommentError::DatabaseError)?
+            .ok_or(CommentError::NotFound)?;
+
+        Ok(CommentScoreResponse {
+            comment_id: comment.id,
+            score,
+            collapsed: is_collapsed(score, comment.is_pinned),
+        })
+    }
+
+    // Delete a comment (soft delete)
+    pub async fn delete_comment(
+        &self,
+        comment_id: i64,
+        user_id: Uuid,
+        is_admin: bool,
+    ) -> Result<i64, CommentError> {
+        // Get the comment
+        let comment = sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT id, post_id, user_id, guest_name, guest_email, parent_comment_id, content, content_html,
+                is_deleted, deleted_by, deleted_at, markdown_enabled, moderation_status, is_pinned,
+                score, nesting_level, created_at, updated_at
+            FROM global.comments
+            WHERE id = $1 AND is_deleted = false
+            "#,
+            comment_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?
+        .ok_or(CommentError::NotFound)?;
+
+        // Check ownership
+        if comment.user_id != Some(user_id) && !is_admin {
+            return Err(CommentError::Unauthorized);
+        }
+
+        // Soft delete the comment
+        sqlx::query!(
             r#"
             UPDATE global.comments
-            SET 
-                is_deleted = true, 
+            SET
+                is_deleted = true,
                 content = '[deleted]',
                 content_html = '<p>[deleted]</p>',
                 deleted_by = $1,
@@ -589,10 +1629,10 @@ impl CommentService {
                 updated_at = $2
             WHERE id = $3
             "#,
+            user_id,
+            Utc::now(),
+            comment_id
         )
-        .bind(user_id)
-        .bind(Utc::now())
-        .bind(comment_id)
         .execute(&self.pool)
         .await
         .map_err(CommentError::DatabaseError)?;
@@ -602,27 +1642,24 @@ impl CommentService {
             // Invalidate post comments cache
             let cache_key = format!("comments:post:{}", comment.post_id);
             let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
+                .connection()
                 .await
                 .map_err(CommentError::CacheError)?
                 .del(&cache_key)
                 .await
                 .map_err(CommentError::CacheError)?;
 
-            // Update comment count in cache
-            let count_key = format!("post:comment_count:{}", comment.post_id);
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(CommentError::CacheError)?
-                .decr(&count_key, 1)
-                .await
-                .map_err(CommentError::CacheError)?;
+            // Update comment count in cache (shadow-banned comments were never counted)
+            let hidden = match comment.user_id {
+                Some(author_id) => self.is_shadow_banned(author_id).await?,
+                None => false,
+            };
+            if !hidden {
+                adjust_cached_comment_count(cache, comment.post_id, -1).await?;
+            }
 
             // Push to comment events stream
-            if let Ok(mut conn) = cache.get_client().get_multiplexed_async_connection().await {
+            if let Ok(mut conn) = cache.connection().await {
                 let _: Result<String, redis::RedisError> = conn
                     .xadd(
                         "stream:comments",
@@ -637,130 +1674,169 @@ impl CommentService {
             }
         }
 
+        // The post author loses the comment, and its author is penalized if a moderator removed it
+        let mut affected: Vec<Uuid> = self
+            .get_post_author(comment.post_id)
+            .await?
+            .into_iter()
+            .collect();
+        affected.extend(comment.user_id.filter(|author_id| *author_id != user_id));
+        queue_reputation_update(&self.pool, &affected).await;
+
         info!("Comment {} deleted by user {}", comment_id, user_id);
         Ok(comment_id)
     }
 
     // Get comment count for a post (cached)
     pub async fn get_comment_count(&self, post_id: i64) -> Result<i64, CommentError> {
-        // Try to get from cache first
-        if let Some(cache) = &self.redis_cache {
-            let count_key = format!("post:comment_count:{}", post_id);
+        comment_count(&self.pool, self.redis_cache.as_ref(), post_id).await
+    }
 
-            if let Ok(cached_count) = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(CommentError::CacheError)?
-                .get::<_, Option<i64>>(&count_key)
-                .await
-            {
-                if let Some(count) = cached_count {
-                    return Ok(count);
-                }
-            }
+    // Find the root comment of the thread containing `comment_id`, returning (root_id, post_id)
+    async fn get_thread_root(&self, comment_id: i64) -> Result<(i64, i64), CommentError> {
+        let row = sqlx::query!(
+            r#"
+            WITH RECURSIVE thread AS (
+                SELECT id, parent_comment_id, post_id FROM global.comments WHERE id = $1
+                UNION ALL
+                SELECT c.id, c.parent_comment_id, c.post_id
+                FROM global.comments c
+                JOIN thread t ON c.id = t.parent_comment_id
+            )
+            SELECT id AS "id!", post_id AS "post_id!" FROM thread WHERE parent_comment_id IS NULL
+            "#,
+            comment_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?
+        .ok_or(CommentError::ParentCommentNotFound)?;
+
+        Ok((row.id, row.post_id))
+    }
+
+    // Resolve the thread a comment on `post_id` belongs to
+    async fn get_post_thread_root(
+        &self,
+        post_id: i64,
+        comment_id: i64,
+    ) -> Result<i64, CommentError> {
+        match self.get_thread_root(comment_id).await {
+            Ok((root_id, thread_post_id)) if thread_post_id == post_id => Ok(root_id),
+            Ok(_) | Err(CommentError::ParentCommentNotFound) => Err(CommentError::NotFound),
+            Err(e) => Err(e),
         }
+    }
 
-        // Cache miss, get from DB
-        let count = sqlx::query_scalar::<_, i64>(
-            "SELECT COUNT(*) FROM global.comments WHERE post_id = $1 AND is_deleted = false",
+    // Subscribe a user to the thread containing a comment
+    pub async fn subscribe_to_thread(
+        &self,
+        post_id: i64,
+        comment_id: i64,
+        user_id: Uuid,
+    ) -> Result<i64, CommentError> {
+        let root_id = self.get_post_thread_root(post_id, comment_id).await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO global.comment_thread_subscriptions (root_comment_id, user_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            root_id,
+            user_id
         )
-        .bind(post_id)
-        .fetch_one(&self.pool)
+        .execute(&self.pool)
         .await
         .map_err(CommentError::DatabaseError)?;
 
-        // Update cache
-        if let Some(cache) = &self.redis_cache {
-            let count_key = format!("post:comment_count:{}", post_id);
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(CommentError::CacheError)?
-                .set_ex(&count_key, count.to_string(), 3600)
-                .await
-                .map_err(CommentError::CacheError)?;
-        }
-
-        Ok(count)
+        info!("User {} subscribed to comment thread {}", user_id, root_id);
+        Ok(root_id)
     }
 
-    // Helper function to send a notification for a new comment reply
-    async fn send_reply_notification(
+    // Unsubscribe a user from the thread containing a comment
+    pub async fn unsubscribe_from_thread(
         &self,
-        comment: &Comment,
-        reply_to_user_id: &Uuid,
-    ) -> Result<(), CommentError> {
-        if let Some(redis_cache) = &self.redis_cache {
-            let notification = NotificationPayload {
-                recipient_id: *reply_to_user_id,
-                notification_type: NotificationType::CommentReply,
-                object_id: comment.id,
-                related_object_id: Some(comment.post_id),
-                actor_id: comment.user_id,
-                content: format!("You have a new reply to your comment."),
-            };
+        post_id: i64,
+        comment_id: i64,
+        user_id: Uuid,
+    ) -> Result<i64, CommentError> {
+        let root_id = self.get_post_thread_root(post_id, comment_id).await?;
 
-            // Publish notification
-            if let Err(e) = publish_notification(redis_cache, reply_to_user_id, notification).await
-            {
-                error!("Failed to publish notification: {}", e);
-                // Don't fail the whole operation if notification fails
-            }
-        }
+        sqlx::query!(
+            "DELETE FROM global.comment_thread_subscriptions WHERE root_comment_id = $1 AND user_id = $2",
+            root_id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?;
 
-        Ok(())
+        info!(
+            "User {} unsubscribed from comment thread {}",
+            user_id, root_id
+        );
+        Ok(root_id)
     }
 
-    async fn send_comment_notifications(
+    // Queue a reply for the thread's subscribers. The first reply in a window opens a batch
+    // and schedules the job that flushes it once the window closes, so busy threads produce
+    // one notification per subscriber instead of one per reply.
+    async fn queue_thread_notification(
         &self,
-        comment: &Comment,
-        _tx: &sqlx::Transaction<'_, sqlx::Postgres>,
+        root_id: i64,
+        post_id: i64,
+        reply: PendingThreadReply,
     ) -> Result<(), CommentError> {
-        // Get post author (simplified)
-        let post_author = match sqlx::query_scalar!(
-            "SELECT user_id FROM global.posts WHERE id = $1",
-            comment.post_id
-        )
-        .fetch_optional(&self.pool)
-        .await
-        {
-            Ok(Some(author_id)) => Some(author_id),
-            Ok(None) => {
-                warn!(
-                    "Post not found when sending notifications: {}",
-                    comment.post_id
-                );
-                None
-            }
-            Err(e) => {
-                error!("Error fetching post author: {}", e);
-                return Err(CommentError::DatabaseError(e));
-            }
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            // Without Redis there is nowhere to batch, so notify straight away
+            None => return self.thread_notifier.send(root_id, post_id, &[reply]).await,
         };
 
-        // Only send notification if post author exists and is not the commenter
-        if let Some(author_id) = post_author {
-            if author_id != comment.user_id {
-                let notification = NotificationPayload {
-                    recipient_id: author_id,
-                    notification_type: NotificationType::NewComment,
-                    object_id: comment.id,
-                    related_object_id: Some(comment.post_id),
-                    actor_id: comment.user_id,
-                    content: format!("New comment on your post"),
-                };
+        let pending_key = pending_replies_key(root_id);
+        let batch_key = format!("comments:thread:{}:batch", root_id);
+        let mut conn = cache.connection().await.map_err(CommentError::CacheError)?;
 
-                match self
-                    .notification_service
-                    .create_notification(notification)
-                    .await
-                {
-                    Ok(_) => info!("Notification sent successfully"),
-                    Err(e) => warn!("Failed to send notification to post author: {}", e),
-                }
-            }
+        conn.rpush::<_, _, ()>(&pending_key, reply.to_entry())
+            .await
+            .map_err(CommentError::CacheError)?;
+        conn.expire::<_, ()>(&pending_key, THREAD_PENDING_TTL_SECONDS)
+            .await
+            .map_err(CommentError::CacheError)?;
+
+        let opened: Option<String> = redis::cmd("SET")
+            .arg(&batch_key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(THREAD_NOTIFICATION_WINDOW_SECONDS)
+            .query_async(&mut conn)
+            .await
+            .map_err(CommentError::CacheError)?;
+
+        // Another reply already opened the batch; its job will pick this one up
+        if opened.is_none() {
+            return Ok(());
+        }
+
+        let flush_at = Utc::now() + chrono::Duration::seconds(THREAD_NOTIFICATION_WINDOW_SECONDS);
+        if let Err(e) = self
+            .job_service
+            .schedule(
+                THREAD_NOTIFICATION_JOB,
+                json!({ "root_id": root_id, "post_id": post_id }),
+                None,
+                Some(flush_at),
+            )
+            .await
+        {
+            // Let the next reply open a batch again; this one stays pending for it
+            let _ = conn.del::<_, ()>(&batch_key).await;
+            return Err(CommentError::InternalError(format!(
+                "Could not schedule thread notifications: {}",
+                e
+            )));
         }
 
         Ok(())
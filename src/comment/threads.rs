@@ -0,0 +1,290 @@
+use crate::block::service::blocked_user_ids;
+use crate::cache::redis::RedisCache;
+use crate::comment::model::CommentError;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::JobHandler;
+use crate::notification::model::{NotificationContext, NotificationPayload, NotificationType};
+use crate::notification::service::{notification_context, NotificationService};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Kind of the job that notifies the subscribers of a thread about a batch of replies
+pub const THREAD_NOTIFICATION_JOB: &str = "thread_notifications";
+
+/// Redis list of the replies to a thread waiting for its batch to be flushed
+pub fn pending_replies_key(root_id: i64) -> String {
+    format!("comments:thread:{}:pending", root_id)
+}
+
+/// A reply waiting to be announced to the subscribers of its thread
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingThreadReply {
+    pub comment_id: i64,
+    pub actor_id: Uuid,
+    // User who already got a direct reply notification for this comment
+    pub notified_directly: Option<Uuid>,
+}
+
+impl PendingThreadReply {
+    pub fn to_entry(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.comment_id,
+            self.actor_id,
+            self.notified_directly
+                .map(|id| id.to_string())
+                .unwrap_or_default()
+        )
+    }
+
+    pub fn from_entry(entry: &str) -> Option<Self> {
+        let mut parts = entry.splitn(3, ':');
+        let comment_id = parts.next()?.parse().ok()?;
+        let actor_id = parts.next()?.parse().ok()?;
+        let notified_directly = match parts.next()? {
+            "" => None,
+            id => Some(id.parse().ok()?),
+        };
+
+        Some(Self {
+            comment_id,
+            actor_id,
+            notified_directly,
+        })
+    }
+}
+
+// Replies of a batch a subscriber should hear about: not their own, not ones they were
+// already notified about directly and not ones by users they blocked
+fn replies_for_subscriber<'a>(
+    replies: &'a [PendingThreadReply],
+    subscriber: Uuid,
+    blocked: &HashSet<Uuid>,
+) -> Vec<&'a PendingThreadReply> {
+    replies
+        .iter()
+        .filter(|reply| {
+            reply.actor_id != subscriber
+                && reply.notified_directly != Some(subscriber)
+                && !blocked.contains(&reply.actor_id)
+        })
+        .collect()
+}
+
+fn thread_notification_content(reply_count: usize) -> String {
+    if reply_count == 1 {
+        "There is a new reply in a thread you follow.".to_string()
+    } else {
+        format!(
+            "There are {} new replies in a thread you follow.",
+            reply_count
+        )
+    }
+}
+
+/// Notifies the subscribers of comment threads about new replies.
+///
+/// Replies are batched per thread in Redis by the comment service, which schedules a job for
+/// when the batch's window closes. The job takes every reply queued by then, so a busy thread
+/// produces one notification per subscriber instead of one per reply, and batches survive a
+/// restart of the instance that opened them.
+pub struct ThreadNotificationJob {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+    notification_service: Arc<NotificationService>,
+}
+
+impl ThreadNotificationJob {
+    pub fn new(
+        pool: PgPool,
+        redis_cache: Option<RedisCache>,
+        notification_service: Arc<NotificationService>,
+    ) -> Self {
+        Self {
+            pool,
+            redis_cache,
+            notification_service,
+        }
+    }
+
+    /// Notify every subscriber of a thread about a batch of replies
+    pub async fn send(
+        &self,
+        root_id: i64,
+        post_id: i64,
+        replies: &[PendingThreadReply],
+    ) -> Result<(), CommentError> {
+        let subscribers = sqlx::query_scalar!(
+            "SELECT user_id FROM global.comment_thread_subscriptions WHERE root_comment_id = $1",
+            root_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(CommentError::DatabaseError)?;
+
+        for subscriber in subscribers {
+            let blocked =
+                blocked_user_ids(&self.pool, self.redis_cache.as_ref(), subscriber).await?;
+            let relevant = replies_for_subscriber(replies, subscriber, &blocked);
+
+            let latest = match relevant.last() {
+                Some(reply) => *reply,
+                None => continue,
+            };
+
+            let mut notification = NotificationPayload {
+                recipient_id: subscriber,
+                notification_type: NotificationType::ThreadReply,
+                object_id: latest.comment_id,
+                related_object_id: Some(post_id),
+                actor_id: latest.actor_id,
+                content: thread_notification_content(relevant.len()),
+                context: NotificationContext::default(),
+            };
+            match self.pool.acquire().await {
+                Ok(mut conn) => match notification_context(&mut conn, &notification).await {
+                    Ok(context) => notification.context = context,
+                    Err(e) => warn!("Failed to resolve thread notification context: {}", e),
+                },
+                Err(e) => warn!("Failed to resolve thread notification context: {}", e),
+            }
+
+            if let Err(e) = self.notification_service.deliver(notification).await {
+                warn!(
+                    "Failed to deliver thread notification to {}: {}",
+                    subscriber, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn flush(&self, job: &Job) -> Result<Value, JobError> {
+        let root_id = job.payload["root_id"]
+            .as_i64()
+            .ok_or_else(|| JobError::Failed("Missing root_id".to_string()))?;
+        let post_id = job.payload["post_id"]
+            .as_i64()
+            .ok_or_else(|| JobError::Failed("Missing post_id".to_string()))?;
+
+        // Batches are only opened when there is a cache to keep them in
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            None => return Ok(json!({ "replies": 0 })),
+        };
+        let mut conn = cache
+            .connection()
+            .await
+            .map_err(|e| JobError::Failed(format!("Cache unavailable: {}", e)))?;
+
+        let pending_key = pending_replies_key(root_id);
+        let (entries,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .lrange(&pending_key, 0, -1)
+            .del(&pending_key)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| JobError::Failed(format!("Could not take pending replies: {}", e)))?;
+
+        let replies: Vec<PendingThreadReply> = entries
+            .iter()
+            .filter_map(|entry| PendingThreadReply::from_entry(entry))
+            .collect();
+        if replies.is_empty() {
+            return Ok(json!({ "replies": 0 }));
+        }
+
+        self.send(root_id, post_id, &replies)
+            .await
+            .map_err(|e| JobError::Failed(format!("Could not notify subscribers: {:?}", e)))?;
+
+        info!(
+            "Notified subscribers of thread {} about {} replies",
+            root_id,
+            replies.len()
+        );
+        Ok(json!({ "replies": replies.len() }))
+    }
+}
+
+impl JobHandler for ThreadNotificationJob {
+    fn kind(&self) -> &'static str {
+        THREAD_NOTIFICATION_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.flush(job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(
+        comment_id: i64,
+        actor_id: Uuid,
+        notified_directly: Option<Uuid>,
+    ) -> PendingThreadReply {
+        PendingThreadReply {
+            comment_id,
+            actor_id,
+            notified_directly,
+        }
+    }
+
+    #[test]
+    fn test_pending_reply_entry_round_trip() {
+        let direct = reply(7, Uuid::new_v4(), Some(Uuid::new_v4()));
+        assert_eq!(
+            PendingThreadReply::from_entry(&direct.to_entry()),
+            Some(direct)
+        );
+
+        let indirect = reply(8, Uuid::new_v4(), None);
+        assert_eq!(
+            PendingThreadReply::from_entry(&indirect.to_entry()),
+            Some(indirect)
+        );
+
+        assert_eq!(PendingThreadReply::from_entry("not-an-entry"), None);
+    }
+
+    #[test]
+    fn test_batch_skips_own_direct_and_blocked_replies() {
+        let subscriber = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let blocked_user = Uuid::new_v4();
+        let replies = vec![
+            reply(1, other, None),
+            reply(2, subscriber, None),
+            reply(3, other, Some(subscriber)),
+            reply(4, blocked_user, None),
+            reply(5, other, None),
+        ];
+        let blocked = HashSet::from([blocked_user]);
+
+        let relevant = replies_for_subscriber(&replies, subscriber, &blocked);
+        let ids: Vec<i64> = relevant.iter().map(|reply| reply.comment_id).collect();
+        assert_eq!(ids, vec![1, 5]);
+    }
+
+    #[test]
+    fn test_batch_folds_into_one_notification() {
+        assert_eq!(
+            thread_notification_content(1),
+            "There is a new reply in a thread you follow."
+        );
+        assert_eq!(
+            thread_notification_content(3),
+            "There are 3 new replies in a thread you follow."
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use crate::auth::middleware::AuthUser;
+use crate::consent::model::{ConsentError, ConsentSettings};
+use crate::consent::service::ConsentService;
+use crate::db::retry::unavailable_response;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn consent_error_response(e: ConsentError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Consent error: {:?}", e);
+    let status = match e {
+        ConsentError::UserNotFound => StatusCode::NOT_FOUND,
+        ConsentError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Get your tracking consent
+#[utoipa::path(
+    get,
+    path = "/api/users/me/consent",
+    tag = "users",
+    responses(
+        (status = 200, description = "Consent retrieved", body = ConsentSettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_consent(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ConsentService>>,
+) -> Response {
+    match service.get_consent(user.user_id).await {
+        Ok(settings) => (StatusCode::OK, Json(settings)).into_response(),
+        Err(e) => consent_error_response(e),
+    }
+}
+
+/// Set your tracking consent
+///
+/// Without analytics consent your post reads and interactions are not recorded, as for
+/// requests sending `DNT: 1` or `Sec-GPC: 1`. Posts and comments are served as usual.
+#[utoipa::path(
+    put,
+    path = "/api/users/me/consent",
+    tag = "users",
+    request_body = ConsentSettings,
+    responses(
+        (status = 200, description = "Consent updated", body = ConsentSettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_consent(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ConsentService>>,
+    Json(settings): Json<ConsentSettings>,
+) -> Response {
+    match service.set_consent(user.user_id, &settings).await {
+        Ok(settings) => (StatusCode::OK, Json(settings)).into_response(),
+        Err(e) => consent_error_response(e),
+    }
+}
@@ -0,0 +1,41 @@
+use axum::{http::Request, middleware::Next, response::Response};
+
+tokio::task_local! {
+    static DO_NOT_TRACK: bool;
+}
+
+/// Whether the request being handled asked not to be tracked; false outside of requests
+pub fn do_not_track() -> bool {
+    DO_NOT_TRACK.try_with(|dnt| *dnt).unwrap_or(false)
+}
+
+// `DNT: 1` and `Sec-GPC: 1` both opt out
+fn header_opts_out<B>(req: &Request<B>, name: &str) -> bool {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim() == "1")
+}
+
+/// Middleware that makes the Do Not Track and Global Privacy Control headers of the request
+/// available to the services that record interactions and views
+pub async fn tracking_preference<B>(req: Request<B>, next: Next<B>) -> Response {
+    let dnt = header_opts_out(&req, "dnt") || header_opts_out(&req, "sec-gpc");
+
+    DO_NOT_TRACK.scope(dnt, next.run(req)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_opts_out() {
+        let req = Request::builder().header("DNT", "1").body(()).unwrap();
+        assert!(header_opts_out(&req, "dnt"));
+
+        let req = Request::builder().header("Sec-GPC", "0").body(()).unwrap();
+        assert!(!header_opts_out(&req, "sec-gpc"));
+        assert!(!header_opts_out(&req, "dnt"));
+    }
+}
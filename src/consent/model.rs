@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// What a user agrees to be tracked for
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ConsentSettings {
+    /// Whether your post reads and interactions are recorded for analytics
+    #[schema(example = "false")]
+    pub analytics: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConsentError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("User not found")]
+    UserNotFound,
+}
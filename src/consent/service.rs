@@ -0,0 +1,125 @@
+use crate::cache::redis::RedisCache;
+use crate::consent::middleware::do_not_track;
+use crate::consent::model::{ConsentError, ConsentSettings};
+use redis::AsyncCommands;
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+
+const CONSENT_CACHE_TTL: u64 = 3600; // 1 hour
+
+fn consent_cache_key(user_id: Uuid) -> String {
+    format!("user_consent:analytics:{}", user_id)
+}
+
+/// Tracking consent of users.
+///
+/// Checked before every recorded interaction and logged post view, so answers are served
+/// from cache when possible and a cache outage only costs a fresh query.
+#[derive(Debug, Clone)]
+pub struct ConsentService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+}
+
+impl ConsentService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self { pool, redis_cache }
+    }
+
+    /// Whether interactions and views of the current request may be recorded: never when it
+    /// sent `DNT` or `Sec-GPC`, and otherwise unless the user withdrew consent. Readers who
+    /// are not signed in are only covered by the headers. If consent cannot be looked up,
+    /// nothing is recorded.
+    pub async fn allows_tracking(&self, user_id: Option<Uuid>) -> bool {
+        if do_not_track() {
+            return false;
+        }
+
+        match user_id {
+            Some(user_id) => match self.analytics_consent(user_id).await {
+                Ok(consent) => consent,
+                Err(e) => {
+                    error!("Failed to look up analytics consent of {}: {}", user_id, e);
+                    false
+                }
+            },
+            None => true,
+        }
+    }
+
+    async fn analytics_consent(&self, user_id: Uuid) -> Result<bool, ConsentError> {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Ok(Some(consent)) = conn
+                    .get::<_, Option<bool>>(consent_cache_key(user_id))
+                    .await
+                {
+                    return Ok(consent);
+                }
+            }
+        }
+
+        let consent = self.get_consent(user_id).await?.analytics;
+        self.cache_consent(user_id, consent).await;
+        Ok(consent)
+    }
+
+    async fn cache_consent(&self, user_id: Uuid, consent: bool) {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Err(e) = conn
+                    .set_ex::<_, _, ()>(consent_cache_key(user_id), consent, CONSENT_CACHE_TTL)
+                    .await
+                {
+                    error!("Failed to cache analytics consent: {}", e);
+                }
+            }
+        }
+    }
+
+    pub async fn get_consent(&self, user_id: Uuid) -> Result<ConsentSettings, ConsentError> {
+        let analytics = sqlx::query_scalar::<_, bool>(
+            "SELECT analytics_consent FROM global.users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(ConsentError::UserNotFound)?;
+
+        Ok(ConsentSettings { analytics })
+    }
+
+    pub async fn set_consent(
+        &self,
+        user_id: Uuid,
+        settings: &ConsentSettings,
+    ) -> Result<ConsentSettings, ConsentError> {
+        let analytics = sqlx::query_scalar::<_, bool>(
+            r#"
+            UPDATE global.users SET analytics_consent = $2, updated_at = NOW()
+            WHERE id = $1
+            RETURNING analytics_consent
+            "#,
+        )
+        .bind(user_id)
+        .bind(settings.analytics)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(ConsentError::UserNotFound)?;
+
+        // Read history is only kept with consent
+        if !analytics {
+            sqlx::query("DELETE FROM global.post_reads WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        // Takes effect right away rather than when the cached answer expires
+        self.cache_consent(user_id, analytics).await;
+
+        info!("User {} set analytics consent to {}", user_id, analytics);
+        Ok(ConsentSettings { analytics })
+    }
+}
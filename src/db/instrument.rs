@@ -0,0 +1,155 @@
+use crate::metrics;
+use std::future::Future;
+use std::io;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Queries slower than this are logged when `SLOW_QUERY_THRESHOLD_MS` is not set
+const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(500);
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_REPORT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Threshold above which queries are logged as slow, from `SLOW_QUERY_THRESHOLD_MS`
+pub fn slow_query_threshold() -> Duration {
+    static THRESHOLD: OnceLock<Duration> = OnceLock::new();
+
+    *THRESHOLD.get_or_init(|| {
+        std::env::var("SLOW_QUERY_THRESHOLD_MS")
+            .ok()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_SLOW_QUERY_THRESHOLD)
+    })
+}
+
+/// How long a timed query may run, by what waits on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryClass {
+    /// Queries of ordinary requests
+    Request,
+    /// Aggregations behind analytics and earnings reports
+    Report,
+    /// Queries of background jobs, which no request waits on
+    Background,
+}
+
+impl QueryClass {
+    /// Class of a query by its name
+    pub fn of(name: &str) -> Self {
+        const REPORTS: [&str; 3] = ["analytics.", "tips.earnings", "posts.author_dashboard"];
+        const BACKGROUND: [&str; 8] = [
+            "analytics.refresh_views",
+            "embeddings.",
+            "newsletter.",
+            "posts.popularity",
+            "recommendations.generate",
+            "recommendations.digest",
+            "retention.",
+            "seed.",
+        ];
+
+        if BACKGROUND.iter().any(|prefix| name.starts_with(prefix)) {
+            QueryClass::Background
+        } else if REPORTS.iter().any(|prefix| name.starts_with(prefix)) {
+            QueryClass::Report
+        } else {
+            QueryClass::Request
+        }
+    }
+
+    /// Budget of the class, `None` for no limit: `DATABASE_QUERY_TIMEOUT_MS` (5000) for
+    /// requests, `DATABASE_REPORT_TIMEOUT_MS` (30000) for reports and
+    /// `DATABASE_BACKGROUND_TIMEOUT_MS` (no limit) for background jobs. 0 also means no limit.
+    pub fn timeout(self) -> Option<Duration> {
+        static TIMEOUTS: OnceLock<[Option<Duration>; 3]> = OnceLock::new();
+
+        let timeouts = TIMEOUTS.get_or_init(|| {
+            let budget = |name: &str, default: Option<Duration>| {
+                std::env::var(name)
+                    .ok()
+                    .and_then(|ms| ms.trim().parse::<u64>().ok())
+                    .map_or(default, |ms| {
+                        Some(Duration::from_millis(ms)).filter(|budget| !budget.is_zero())
+                    })
+            };
+            [
+                budget("DATABASE_QUERY_TIMEOUT_MS", Some(DEFAULT_QUERY_TIMEOUT)),
+                budget("DATABASE_REPORT_TIMEOUT_MS", Some(DEFAULT_REPORT_TIMEOUT)),
+                budget("DATABASE_BACKGROUND_TIMEOUT_MS", None),
+            ]
+        });
+        timeouts[self as usize]
+    }
+}
+
+/// Error of a query that ran out of its budget. It reads as an unavailable database, so
+/// requests get a 503 with `Retry-After`, and is not retried.
+pub fn query_timeout_error(name: &str, budget: Duration) -> sqlx::Error {
+    sqlx::Error::Io(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("Query {} timed out after {}ms", name, budget.as_millis()),
+    ))
+}
+
+/// Run a query, recording its latency under `name` and logging it if it is slow. The query
+/// is given up once it runs over the budget of its class (see `QueryClass`).
+///
+/// `name` should be a stable, low-cardinality identifier such as `posts.popular`.
+pub async fn timed<F, T, E>(name: &'static str, query: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: From<sqlx::Error>,
+{
+    let start = Instant::now();
+    let result = match QueryClass::of(name).timeout() {
+        Some(budget) => match tokio::time::timeout(budget, query).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("Query {} timed out after {}ms", name, budget.as_millis());
+                Err(query_timeout_error(name, budget).into())
+            }
+        },
+        None => query.await,
+    };
+    let elapsed = start.elapsed();
+
+    let route = metrics::current_route();
+    if elapsed >= slow_query_threshold() {
+        warn!(
+            "Slow query {} took {}ms (route: {})",
+            name,
+            elapsed.as_millis(),
+            route
+        );
+    }
+    metrics::record_query(name, route, elapsed);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_classes() {
+        assert_eq!(QueryClass::of("posts.popular"), QueryClass::Request);
+        assert_eq!(QueryClass::of("analytics.post_stats"), QueryClass::Report);
+        assert_eq!(QueryClass::of("tips.earnings_totals"), QueryClass::Report);
+        assert_eq!(
+            QueryClass::of("analytics.refresh_views"),
+            QueryClass::Background
+        );
+        assert_eq!(QueryClass::of("seed.users"), QueryClass::Background);
+    }
+
+    #[test]
+    fn test_query_timeout_error() {
+        let error = query_timeout_error("posts.popular", Duration::from_secs(5));
+        match error {
+            sqlx::Error::Io(e) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+            other => panic!("expected an I/O error, got {:?}", other),
+        }
+    }
+}
@@ -1,4 +1,8 @@
+pub mod instrument;
+pub mod pool;
 pub mod queries;
+pub mod retry;
+pub mod router;
 
 use sqlx::{PgPool, Row};
 use std::fs;
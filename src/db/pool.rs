@@ -0,0 +1,174 @@
+use crate::metrics;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Executor, PgPool};
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+const DEFAULT_SLOW_ACQUIRE_THRESHOLD: Duration = Duration::from_millis(100);
+// How often the wait for a connection is sampled
+const POOL_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Sizing and timeouts of a database pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    /// Connections kept open even when idle
+    pub min_connections: u32,
+    /// How long a query waits for a free connection before failing
+    pub acquire_timeout: Duration,
+    /// Idle connections beyond `min_connections` are closed after this; `None` keeps them
+    pub idle_timeout: Option<Duration>,
+    /// Statements running longer than this are cancelled by the database; `None` for no limit
+    pub statement_timeout: Option<Duration>,
+    /// Waits for a connection longer than this are logged as warnings
+    pub slow_acquire_threshold: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            min_connections: 0,
+            acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+            statement_timeout: None,
+            slow_acquire_threshold: DEFAULT_SLOW_ACQUIRE_THRESHOLD,
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Read the pool configuration, keeping the default of anything unset or invalid:
+    ///
+    /// - `DATABASE_MAX_CONNECTIONS` (5) and `DATABASE_MIN_CONNECTIONS` (0)
+    /// - `DATABASE_ACQUIRE_TIMEOUT_SECONDS` (30)
+    /// - `DATABASE_IDLE_TIMEOUT_SECONDS` (600; 0 keeps idle connections open)
+    /// - `DATABASE_STATEMENT_TIMEOUT_MS` (no limit; 0 also means none)
+    /// - `DATABASE_SLOW_ACQUIRE_MS` (100)
+    pub fn from_env() -> Self {
+        let number = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+        let defaults = Self::default();
+
+        let max_connections = number("DATABASE_MAX_CONNECTIONS")
+            .filter(|max| *max > 0)
+            .map_or(defaults.max_connections, |max| max as u32);
+        Self {
+            max_connections,
+            min_connections: number("DATABASE_MIN_CONNECTIONS")
+                .map_or(defaults.min_connections, |min| {
+                    (min as u32).min(max_connections)
+                }),
+            acquire_timeout: number("DATABASE_ACQUIRE_TIMEOUT_SECONDS")
+                .filter(|seconds| *seconds > 0)
+                .map_or(defaults.acquire_timeout, Duration::from_secs),
+            idle_timeout: number("DATABASE_IDLE_TIMEOUT_SECONDS")
+                .map_or(defaults.idle_timeout, |seconds| {
+                    Some(Duration::from_secs(seconds)).filter(|idle| !idle.is_zero())
+                }),
+            statement_timeout: number("DATABASE_STATEMENT_TIMEOUT_MS")
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+            slow_acquire_threshold: number("DATABASE_SLOW_ACQUIRE_MS")
+                .map_or(defaults.slow_acquire_threshold, Duration::from_millis),
+        }
+    }
+
+    /// Options of a pool with this configuration. The statement timeout is set on each new
+    /// connection.
+    pub fn options(&self) -> PgPoolOptions {
+        let options = PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout);
+
+        match self.statement_timeout {
+            Some(timeout) => {
+                let set_timeout = format!("SET statement_timeout = {}", timeout.as_millis());
+                options.after_connect(move |conn, _meta| {
+                    let set_timeout = set_timeout.clone();
+                    Box::pin(async move {
+                        conn.execute(set_timeout.as_str()).await?;
+                        Ok(())
+                    })
+                })
+            }
+            None => options,
+        }
+    }
+}
+
+/// Report the utilization of a pool at `/metrics` under `name`, and sample how long getting
+/// a connection from it takes, warning when that exceeds `slow_acquire_threshold`
+pub fn monitor_pool(name: &'static str, pool: PgPool, config: &PoolConfig) {
+    metrics::register_pool(name, pool.clone());
+    let threshold = config.slow_acquire_threshold;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POOL_SAMPLE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if pool.is_closed() {
+                break;
+            }
+
+            let start = Instant::now();
+            let acquired = pool.acquire().await;
+            let waited = start.elapsed();
+            let slow = waited >= threshold;
+            metrics::record_pool_acquire(name, waited, slow);
+
+            match acquired {
+                Ok(_) if slow => warn!(
+                    "Waited {}ms for a connection of the {} database pool ({} of {} in use)",
+                    waited.as_millis(),
+                    name,
+                    // Besides the sample's own connection
+                    (pool.size() as usize - pool.num_idle()).saturating_sub(1),
+                    pool.options().get_max_connections()
+                ),
+                Ok(_) => {}
+                Err(e) => error!(
+                    "Failed to get a connection of the {} database pool after {}ms: {}",
+                    name,
+                    waited.as_millis(),
+                    e
+                ),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_config_from_env() {
+        std::env::set_var("DATABASE_MAX_CONNECTIONS", "20");
+        std::env::set_var("DATABASE_MIN_CONNECTIONS", "50");
+        std::env::set_var("DATABASE_ACQUIRE_TIMEOUT_SECONDS", "0");
+        std::env::set_var("DATABASE_IDLE_TIMEOUT_SECONDS", "0");
+        std::env::set_var("DATABASE_STATEMENT_TIMEOUT_MS", "15000");
+        std::env::set_var("DATABASE_SLOW_ACQUIRE_MS", "soon");
+
+        let config = PoolConfig::from_env();
+        assert_eq!(config.max_connections, 20);
+        // No more kept open than may be open at all
+        assert_eq!(config.min_connections, 20);
+        assert_eq!(config.acquire_timeout, DEFAULT_ACQUIRE_TIMEOUT);
+        assert_eq!(config.idle_timeout, None);
+        assert_eq!(config.statement_timeout, Some(Duration::from_secs(15)));
+        assert_eq!(
+            config.slow_acquire_threshold,
+            DEFAULT_SLOW_ACQUIRE_THRESHOLD
+        );
+    }
+}
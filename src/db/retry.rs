@@ -0,0 +1,250 @@
+//! Handling of transient database failures.
+//!
+//! Serialization failures, deadlocks and dropped connections are retried by `with_retry`,
+//! which services wrap around operations that are safe to run again. When the database
+//! cannot be reached at all, or the pool has no connection to give, controllers answer with
+//! `unavailable_response` (503 with `Retry-After`) instead of a 500.
+
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use serde_json::json;
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Seconds clients are asked to wait when the database is unavailable
+const UNAVAILABLE_RETRY_AFTER_SECONDS: u64 = 5;
+
+/// The database error behind an error, if any: the error itself or one of its sources, as
+/// the `DatabaseError(#[from] sqlx::Error)` variants of the services' errors record it
+pub fn database_error<'a>(e: &'a (dyn Error + 'static)) -> Option<&'a sqlx::Error> {
+    let mut current = Some(e);
+    while let Some(e) = current {
+        if let Some(db_error) = e.downcast_ref::<sqlx::Error>() {
+            return Some(db_error);
+        }
+        current = e.source();
+    }
+    None
+}
+
+fn sqlstate(e: &sqlx::Error) -> Option<String> {
+    match e {
+        sqlx::Error::Database(db_error) => db_error.code().map(|code| code.into_owned()),
+        _ => None,
+    }
+}
+
+/// Whether running the failed operation again may succeed: serialization failures,
+/// deadlocks, lost connections and a database that is shutting down or restarting
+pub fn is_retryable(e: &sqlx::Error) -> bool {
+    match e {
+        // Queries that ran out of their budget would likely do so again
+        sqlx::Error::Io(e) => e.kind() != io::ErrorKind::TimedOut,
+        sqlx::Error::WorkerCrashed => true,
+        sqlx::Error::Database(_) => sqlstate(e).is_some_and(|code| {
+            matches!(
+                code.as_str(),
+                "40001" | "40P01" | "57P01" | "57P02" | "57P03"
+            ) || code.starts_with("08")
+        }),
+        _ => false,
+    }
+}
+
+/// Whether the database cannot serve requests for now: no free connection in the pool, no
+/// connection to the server, or a server refusing connections
+pub fn is_unavailable(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => true,
+        sqlx::Error::Database(_) => sqlstate(e).is_some_and(|code| {
+            matches!(code.as_str(), "53300" | "57P03") || code.starts_with("08")
+        }),
+        _ => false,
+    }
+}
+
+/// 503 response with `Retry-After` for errors of an unavailable database, `None` for others
+pub fn unavailable_response(e: &(dyn Error + 'static)) -> Option<Response> {
+    let db_error = database_error(e).filter(|db_error| is_unavailable(db_error))?;
+    warn!("Database unavailable: {}", db_error);
+
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({
+            "error": "The database is temporarily unavailable, please retry shortly",
+            "code": "DATABASE_UNAVAILABLE",
+        })),
+    )
+        .into_response();
+    response.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(UNAVAILABLE_RETRY_AFTER_SECONDS),
+    );
+    Some(response)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RetryPolicy {
+    /// Attempts in all, the first included
+    attempts: u32,
+    base_delay: Duration,
+}
+
+// Retry policy from `DATABASE_RETRY_ATTEMPTS` (3) and `DATABASE_RETRY_BASE_DELAY_MS` (50)
+fn retry_policy() -> RetryPolicy {
+    static POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+
+    *POLICY.get_or_init(|| {
+        let number = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+        RetryPolicy {
+            attempts: number("DATABASE_RETRY_ATTEMPTS")
+                .filter(|attempts| *attempts > 0)
+                .map_or(DEFAULT_RETRY_ATTEMPTS, |attempts| attempts as u32),
+            base_delay: number("DATABASE_RETRY_BASE_DELAY_MS")
+                .map_or(DEFAULT_RETRY_BASE_DELAY, Duration::from_millis),
+        }
+    })
+}
+
+// Upper bound of the wait before retry number `retry` (from 1), doubling from the base
+// delay up to `MAX_RETRY_DELAY`
+fn backoff_ceiling(base_delay: Duration, retry: u32) -> Duration {
+    base_delay
+        .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+        .min(MAX_RETRY_DELAY)
+}
+
+// Full jitter: a random wait up to the ceiling, so clients failing together do not retry
+// together
+fn backoff(base_delay: Duration, retry: u32) -> Duration {
+    backoff_ceiling(base_delay, retry).mul_f64(rand::random::<f64>())
+}
+
+/// Run a database operation, running it again after a short jittered backoff when it fails
+/// with a retryable error, up to `DATABASE_RETRY_ATTEMPTS` times in all.
+///
+/// The operation must be safe to repeat: a read, or a transaction that is rolled back when
+/// it fails. `name` identifies it in logs.
+pub async fn with_retry<T, E, F, Fut>(name: &'static str, mut operation: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+{
+    let policy = retry_policy();
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if attempt < policy.attempts => {
+                let Some(db_error) = database_error(&e).filter(|db_error| is_retryable(db_error))
+                else {
+                    return Err(e);
+                };
+                let delay = backoff(policy.base_delay, attempt);
+                warn!(
+                    "Retrying {} in {}ms after attempt {} of {} failed: {}",
+                    name,
+                    delay.as_millis(),
+                    attempt,
+                    policy.attempts,
+                    db_error
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::like::model::LikeError;
+
+    #[test]
+    fn test_error_classes() {
+        let io = || sqlx::Error::Io(io::Error::new(io::ErrorKind::ConnectionReset, "reset"));
+        assert!(is_retryable(&io()));
+        assert!(is_unavailable(&io()));
+
+        let timeout = crate::db::instrument::query_timeout_error("test", Duration::from_secs(1));
+        assert!(!is_retryable(&timeout));
+        assert!(is_unavailable(&timeout));
+
+        assert!(!is_retryable(&sqlx::Error::PoolTimedOut));
+        assert!(is_unavailable(&sqlx::Error::PoolTimedOut));
+        assert!(is_unavailable(&sqlx::Error::PoolClosed));
+
+        assert!(!is_retryable(&sqlx::Error::RowNotFound));
+        assert!(!is_unavailable(&sqlx::Error::RowNotFound));
+        assert!(unavailable_response(&sqlx::Error::RowNotFound).is_none());
+
+        // Found behind the errors of services
+        let wrapped = LikeError::DatabaseError(sqlx::Error::PoolClosed);
+        assert!(unavailable_response(&wrapped).is_some());
+        assert!(database_error(&LikeError::PostNotFound).is_none());
+    }
+
+    #[test]
+    fn test_unavailable_response() {
+        let response = unavailable_response(&sqlx::Error::PoolTimedOut).unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "5");
+    }
+
+    #[test]
+    fn test_backoff_bounds() {
+        let base = Duration::from_millis(50);
+        assert_eq!(backoff_ceiling(base, 1), Duration::from_millis(50));
+        assert_eq!(backoff_ceiling(base, 3), Duration::from_millis(200));
+        assert_eq!(backoff_ceiling(base, 40), MAX_RETRY_DELAY);
+        for retry in 1..10 {
+            assert!(backoff(base, retry) <= backoff_ceiling(base, retry));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_with_retry_stops_at_non_retryable_errors() {
+        let mut calls = 0;
+        let result: Result<(), sqlx::Error> = with_retry("test", || {
+            calls += 1;
+            async { Err(sqlx::Error::RowNotFound) }
+        })
+        .await;
+        assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_with_retry_retries_transient_errors() {
+        let mut calls = 0;
+        let result = with_retry("test", || {
+            calls += 1;
+            let call = calls;
+            async move {
+                if call < 2 {
+                    Err(sqlx::Error::Io(io::Error::new(
+                        io::ErrorKind::ConnectionReset,
+                        "reset",
+                    )))
+                } else {
+                    Ok(call)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+    }
+}
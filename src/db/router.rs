@@ -0,0 +1,109 @@
+use crate::db::pool::{monitor_pool, PoolConfig};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+// How often the replica is probed
+const REPLICA_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// A probe slower than this counts as a failure
+const REPLICA_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+struct Replica {
+    pool: PgPool,
+    healthy: AtomicBool,
+}
+
+/// Routes queries between the primary database and an optional read-only replica.
+///
+/// Writes, and reads that must see them immediately, use [`DbRouter::primary`]. Heavy reads
+/// that tolerate replication lag (analytics, listings, feeds) use [`DbRouter::read`], which
+/// falls back to the primary while the replica is failing its health checks.
+#[derive(Debug, Clone)]
+pub struct DbRouter {
+    primary: PgPool,
+    replica: Option<Arc<Replica>>,
+}
+
+impl DbRouter {
+    /// A router that sends everything to `primary`
+    pub fn new(primary: PgPool) -> Self {
+        Self {
+            primary,
+            replica: None,
+        }
+    }
+
+    /// Set up the replica from `DATABASE_REPLICA_URL`, if set, and start its health checks.
+    /// The replica is connected lazily so an unreachable replica never blocks startup. Its
+    /// pool is configured like the primary's (see `PoolConfig::from_env`), but gives up
+    /// waiting for a connection as soon as a health check would.
+    pub fn from_env(primary: PgPool) -> Self {
+        let url = match std::env::var("DATABASE_REPLICA_URL") {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Self::new(primary),
+        };
+
+        let config = PoolConfig::from_env();
+        let pool = match config
+            .options()
+            .acquire_timeout(REPLICA_HEALTH_CHECK_TIMEOUT)
+            .connect_lazy(&url)
+        {
+            Ok(pool) => pool,
+            Err(e) => {
+                error!("Invalid DATABASE_REPLICA_URL, using primary only: {}", e);
+                return Self::new(primary);
+            }
+        };
+
+        info!("Routing heavy reads to the read replica");
+        monitor_pool("replica", pool.clone(), &config);
+        let replica = Arc::new(Replica {
+            pool,
+            healthy: AtomicBool::new(true),
+        });
+        tokio::spawn(monitor_replica(replica.clone()));
+
+        Self {
+            primary,
+            replica: Some(replica),
+        }
+    }
+
+    /// Pool for writes and consistency-sensitive reads
+    pub fn primary(&self) -> &PgPool {
+        &self.primary
+    }
+
+    /// Pool for heavy reads: the replica when it is healthy, the primary otherwise
+    pub fn read(&self) -> &PgPool {
+        match &self.replica {
+            Some(replica) if replica.healthy.load(Ordering::Relaxed) => &replica.pool,
+            _ => &self.primary,
+        }
+    }
+}
+
+async fn monitor_replica(replica: Arc<Replica>) {
+    let mut interval = tokio::time::interval(REPLICA_HEALTH_CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let probe = sqlx::query("SELECT 1").execute(&replica.pool);
+        let healthy = matches!(
+            tokio::time::timeout(REPLICA_HEALTH_CHECK_TIMEOUT, probe).await,
+            Ok(Ok(_))
+        );
+
+        let was_healthy = replica.healthy.swap(healthy, Ordering::Relaxed);
+        if was_healthy && !healthy {
+            warn!("Read replica is unavailable, falling back to the primary");
+        } else if !was_healthy && healthy {
+            info!("Read replica recovered, routing heavy reads to it again");
+        }
+    }
+}
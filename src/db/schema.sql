@@ -3,6 +3,50 @@
 -- Create global schema if it doesn't exist
 CREATE SCHEMA IF NOT EXISTS global;
 
+-- Data migrations applied to this database, by name. This script runs on every start, so
+-- a step that must run only once records itself here and only does its work when the
+-- record is new.
+CREATE TABLE IF NOT EXISTS global.schema_migrations (
+    name VARCHAR(100) PRIMARY KEY,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Blogs hosted on the platform. Posts, comments and memberships belong to one blog;
+-- requests are routed to a blog by custom domain or a /blogs/{slug} path prefix.
+CREATE TABLE IF NOT EXISTS global.blogs (
+    id BIGSERIAL PRIMARY KEY,
+    slug VARCHAR(63) NOT NULL UNIQUE,
+    name VARCHAR(255) NOT NULL,
+    domain VARCHAR(255) UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The default blog serves requests that name no other blog
+INSERT INTO global.blogs (id, slug, name) VALUES (1, 'default', 'Default Blog') ON CONFLICT DO NOTHING;
+SELECT setval(pg_get_serial_sequence('global.blogs', 'id'), GREATEST((SELECT MAX(id) FROM global.blogs), 1));
+
+-- Site settings of each blog (title, theme, social links, feature flags), one row per key.
+-- Keys and value types are defined in src/settings/model.rs.
+CREATE TABLE IF NOT EXISTS global.blog_settings (
+    blog_id BIGINT NOT NULL REFERENCES global.blogs(id) ON DELETE CASCADE,
+    key VARCHAR(100) NOT NULL,
+    value JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (blog_id, key)
+);
+
+-- Feature flags for gradual rollouts, shared by all blogs. A flag is on for a user when it is
+-- enabled and the user is targeted by ID or role, or hashes into the rollout percentage.
+CREATE TABLE IF NOT EXISTS global.feature_flags (
+    key VARCHAR(100) PRIMARY KEY,
+    description TEXT,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    rollout_percentage INTEGER NOT NULL DEFAULT 0 CHECK (rollout_percentage BETWEEN 0 AND 100),
+    target_roles TEXT[] NOT NULL DEFAULT '{}',
+    target_users UUID[] NOT NULL DEFAULT '{}',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
 -- Create users table
 CREATE TABLE IF NOT EXISTS global.users (
     id UUID PRIMARY KEY,
@@ -10,15 +54,61 @@ CREATE TABLE IF NOT EXISTS global.users (
     email VARCHAR(255) NOT NULL UNIQUE,
     password_hash VARCHAR(255) NOT NULL,
     role VARCHAR(20) NOT NULL DEFAULT 'user',
+    -- Tokens carry the version they were issued at; bumping it revokes all earlier tokens
+    token_version INTEGER NOT NULL DEFAULT 0,
+    -- Shadow-banned users' comments are only visible to themselves
+    is_shadow_banned BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Uploaded avatar; read through global.user_avatar_url, which falls back to Gravatar
+    avatar_url TEXT,
+    -- Whether the user's post reads and interactions may be recorded for analytics
+    analytics_consent BOOLEAN NOT NULL DEFAULT TRUE,
+    -- Whether the user gets the weekly email of recommended posts they missed
+    recommendation_digest BOOLEAN NOT NULL DEFAULT TRUE,
     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
     updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
 );
 
+-- Columns added after the table was first created, for databases created before them
+ALTER TABLE global.users ADD COLUMN IF NOT EXISTS is_shadow_banned BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE global.users ADD COLUMN IF NOT EXISTS token_version INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE global.users ADD COLUMN IF NOT EXISTS avatar_url TEXT;
+ALTER TABLE global.users ADD COLUMN IF NOT EXISTS analytics_consent BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE global.users ADD COLUMN IF NOT EXISTS recommendation_digest BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Avatar to show for a user: the uploaded one, or the Gravatar of their email
+CREATE OR REPLACE FUNCTION global.user_avatar_url(avatar_url TEXT, email TEXT) RETURNS TEXT AS $$
+    SELECT COALESCE(
+        avatar_url,
+        'https://www.gravatar.com/avatar/'
+            || encode(sha256(convert_to(lower(trim(email)), 'UTF8')), 'hex')
+            || '?d=identicon'
+    )
+$$ LANGUAGE SQL IMMUTABLE;
+
+-- Slugs and tag names are matched on this key: lowercased, without diacritics, so that
+-- `My-Post` finds `my-post` and `Cafe` finds `Café`. unaccent is only stable, as its
+-- dictionary could change, so it is wrapped to be indexable.
+CREATE EXTENSION IF NOT EXISTS unaccent;
+CREATE OR REPLACE FUNCTION global.match_key(value TEXT) RETURNS TEXT AS $$
+    SELECT lower(public.unaccent('public.unaccent'::regdictionary, value))
+$$ LANGUAGE SQL IMMUTABLE STRICT PARALLEL SAFE;
+
+-- Membership tiers; a reader can open posts whose tier level is at most their own
+CREATE TABLE IF NOT EXISTS global.membership_tiers (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR(50) NOT NULL UNIQUE,
+    level INTEGER NOT NULL UNIQUE CHECK (level > 0),
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
 -- Create posts table
 CREATE TABLE IF NOT EXISTS global.posts (
     id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id),
     title VARCHAR(255) NOT NULL,
-    slug VARCHAR(255) NOT NULL UNIQUE,
+    -- Unique within the blog by its match key, see idx_posts_blog_slug_key
+    slug VARCHAR(255) NOT NULL,
     content TEXT NOT NULL,
     content_html TEXT NOT NULL,
     user_id UUID NOT NULL REFERENCES global.users(id),
@@ -27,10 +117,59 @@ CREATE TABLE IF NOT EXISTS global.posts (
     is_draft BOOLEAN NOT NULL DEFAULT FALSE,
     is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
     cover_image_url VARCHAR(1024),
+    -- Original location of cross-posted content
+    canonical_url TEXT,
+    -- Minimum membership tier needed to read the full post; NULL means free
+    required_tier_id BIGINT REFERENCES global.membership_tiers(id),
+    -- Set by the author or an admin to stop new comments
+    comments_locked BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Arbitrary JSON object set by integrations, checked against POST_METADATA_SCHEMA
+    metadata JSONB NOT NULL DEFAULT '{}',
+    -- Engagement decayed by age, recomputed by the post_popularity job
+    popularity_score DOUBLE PRECISION NOT NULL DEFAULT 0,
+    -- SimHash of the content for finding near-duplicates, see src/post/duplicates.rs; NULL for
+    -- short posts
+    content_fingerprint BIGINT,
     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
     updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
 );
 
+-- Columns added after the table was first created, for databases created before them
+ALTER TABLE global.posts ADD COLUMN IF NOT EXISTS required_tier_id BIGINT REFERENCES global.membership_tiers(id);
+ALTER TABLE global.posts ADD COLUMN IF NOT EXISTS blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id);
+ALTER TABLE global.posts ADD COLUMN IF NOT EXISTS canonical_url TEXT;
+ALTER TABLE global.posts ADD COLUMN IF NOT EXISTS comments_locked BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE global.posts ADD COLUMN IF NOT EXISTS popularity_score DOUBLE PRECISION NOT NULL DEFAULT 0;
+ALTER TABLE global.posts ADD COLUMN IF NOT EXISTS metadata JSONB NOT NULL DEFAULT '{}';
+ALTER TABLE global.posts ADD COLUMN IF NOT EXISTS content_fingerprint BIGINT;
+-- Slugs used to be unique across blogs rather than within each
+ALTER TABLE global.posts DROP CONSTRAINT IF EXISTS posts_slug_key;
+DROP INDEX IF EXISTS global.idx_posts_slug;
+
+-- Current membership per user
+CREATE TABLE IF NOT EXISTS global.user_memberships (
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    tier_id BIGINT NOT NULL REFERENCES global.membership_tiers(id),
+    expires_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, blog_id)
+);
+
+-- Columns added after the table was first created, for databases created before them
+ALTER TABLE global.user_memberships ADD COLUMN IF NOT EXISTS blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE;
+-- Memberships used to be one per user rather than one per user and blog
+DO $$
+BEGIN
+    IF (
+        SELECT array_length(conkey, 1) FROM pg_constraint
+        WHERE conrelid = 'global.user_memberships'::regclass AND contype = 'p'
+    ) = 1 THEN
+        ALTER TABLE global.user_memberships DROP CONSTRAINT user_memberships_pkey;
+        ALTER TABLE global.user_memberships ADD PRIMARY KEY (user_id, blog_id);
+    END IF;
+END $$;
+
 -- Create tags table
 CREATE TABLE IF NOT EXISTS global.tags (
     id BIGSERIAL PRIMARY KEY,
@@ -44,11 +183,76 @@ CREATE TABLE IF NOT EXISTS global.post_tags (
     PRIMARY KEY (post_id, tag_id)
 );
 
+-- Files attached to posts; the files themselves are stored under ATTACHMENTS_DIR
+CREATE TABLE IF NOT EXISTS global.post_attachments (
+    id BIGSERIAL PRIMARY KEY,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    uploader_id UUID NOT NULL REFERENCES global.users(id),
+    filename VARCHAR(255) NOT NULL,
+    content_type VARCHAR(255) NOT NULL,
+    size_bytes BIGINT NOT NULL CHECK (size_bytes > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Uploaded cover images; the original and its WebP variants are stored under ATTACHMENTS_DIR
+CREATE TABLE IF NOT EXISTS global.cover_images (
+    id BIGSERIAL PRIMARY KEY,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    uploader_id UUID NOT NULL REFERENCES global.users(id),
+    content_type VARCHAR(50) NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    blurhash VARCHAR(100) NOT NULL,
+    variant_widths INTEGER[] NOT NULL,
+    -- The original and its variants; 0 for images from before sizes were recorded
+    size_bytes BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Columns added after the table was first created, for databases created before them
+ALTER TABLE global.cover_images ADD COLUMN IF NOT EXISTS size_bytes BIGINT NOT NULL DEFAULT 0;
+
+-- Bytes of attachments and cover images each user stores in each blog, counted on upload and
+-- delete and checked against the storage quotas (see src/storage/model.rs)
+CREATE TABLE IF NOT EXISTS global.storage_usage (
+    blog_id BIGINT NOT NULL REFERENCES global.blogs(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    bytes BIGINT NOT NULL DEFAULT 0,
+    uploads INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (blog_id, user_id)
+);
+
+-- Uploads from before usage was counted, counted once when the table is added; every later
+-- upload and delete updates its uploader's row
+WITH migration AS (
+    INSERT INTO global.schema_migrations (name) VALUES ('storage_usage_backfill')
+    ON CONFLICT DO NOTHING
+    RETURNING name
+)
+INSERT INTO global.storage_usage (blog_id, user_id, bytes, uploads)
+SELECT blog_id, uploader_id, SUM(size_bytes), COUNT(*)
+FROM (
+    SELECT blog_id, uploader_id, size_bytes FROM global.post_attachments
+    UNION ALL
+    SELECT blog_id, uploader_id, size_bytes FROM global.cover_images
+) uploads
+WHERE EXISTS (SELECT 1 FROM migration)
+GROUP BY blog_id, uploader_id
+ON CONFLICT DO NOTHING;
+
 -- Create comments table
 CREATE TABLE IF NOT EXISTS global.comments (
     id BIGSERIAL PRIMARY KEY,
     post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
-    user_id UUID NOT NULL REFERENCES global.users(id),
+    -- Always the blog of the post
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id),
+    -- NULL for guest comments, which carry guest_name/guest_email instead
+    user_id UUID REFERENCES global.users(id),
+    guest_name VARCHAR(100),
+    guest_email VARCHAR(255),
     parent_comment_id BIGINT REFERENCES global.comments(id),
     content TEXT NOT NULL,
     content_html TEXT NOT NULL,
@@ -56,22 +260,514 @@ CREATE TABLE IF NOT EXISTS global.comments (
     deleted_by UUID REFERENCES global.users(id),
     deleted_at TIMESTAMPTZ,
     markdown_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    -- 'approved', 'pending' or 'rejected'; only approved comments are listed
+    moderation_status VARCHAR(20) NOT NULL DEFAULT 'approved',
+    -- Pinned by the post author or an admin; listed ahead of other top-level comments
+    is_pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Reactions minus reports plus the author's reputation, kept by the comment scoring job;
+    -- comments at or below COMMENT_COLLAPSE_SCORE are collapsed in listings
+    score INTEGER NOT NULL DEFAULT 0,
     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
     updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
     -- Comments can only be nested to a certain depth (tracked for performance)
-    nesting_level INTEGER NOT NULL DEFAULT 0 
+    nesting_level INTEGER NOT NULL DEFAULT 0,
+    CHECK (user_id IS NOT NULL OR guest_name IS NOT NULL)
+);
+
+-- Columns added after the table was first created, for databases created before them
+ALTER TABLE global.comments ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE global.comments ADD COLUMN IF NOT EXISTS guest_name VARCHAR(100);
+ALTER TABLE global.comments ADD COLUMN IF NOT EXISTS guest_email VARCHAR(255);
+ALTER TABLE global.comments ADD COLUMN IF NOT EXISTS moderation_status VARCHAR(20) NOT NULL DEFAULT 'approved';
+ALTER TABLE global.comments ADD COLUMN IF NOT EXISTS is_pinned BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE global.comments ADD COLUMN IF NOT EXISTS blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id);
+ALTER TABLE global.comments ADD COLUMN IF NOT EXISTS score INTEGER NOT NULL DEFAULT 0;
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_constraint
+        WHERE conrelid = 'global.comments'::regclass AND conname = 'comments_check'
+    ) THEN
+        ALTER TABLE global.comments
+            ADD CONSTRAINT comments_check CHECK (user_id IS NOT NULL OR guest_name IS NOT NULL);
+    END IF;
+END $$;
+
+-- Which kinds of activity a user hides from their public activity feed
+CREATE TABLE IF NOT EXISTS global.user_activity_privacy (
+    user_id UUID PRIMARY KEY REFERENCES global.users(id) ON DELETE CASCADE,
+    hide_posts BOOLEAN NOT NULL DEFAULT FALSE,
+    hide_comments BOOLEAN NOT NULL DEFAULT FALSE,
+    hide_likes BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Users a user has blocked; their comments and notifications are hidden from the blocker
+CREATE TABLE IF NOT EXISTS global.user_blocks (
+    blocker_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    blocked_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (blocker_id, blocked_id)
 );
 
+-- Reputation of users, recounted from their posts and comments by the reputation job
+CREATE TABLE IF NOT EXISTS global.user_reputation (
+    user_id UUID PRIMARY KEY REFERENCES global.users(id) ON DELETE CASCADE,
+    points INTEGER NOT NULL DEFAULT 0,
+    posts_published INTEGER NOT NULL DEFAULT 0,
+    likes_received INTEGER NOT NULL DEFAULT 0,
+    comments_received INTEGER NOT NULL DEFAULT 0,
+    content_removed INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Users whose reputation the reputation job is to recount
+CREATE TABLE IF NOT EXISTS global.reputation_updates (
+    user_id UUID PRIMARY KEY REFERENCES global.users(id) ON DELETE CASCADE,
+    queued_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Users without a reputation yet, such as those from before reputation existed, are counted
+INSERT INTO global.reputation_updates (user_id)
+SELECT id FROM global.users u
+WHERE NOT EXISTS (SELECT 1 FROM global.user_reputation r WHERE r.user_id = u.id)
+ON CONFLICT DO NOTHING;
+
+-- Final standings of finished weeks and months, rolled up by the leaderboard job
+CREATE TABLE IF NOT EXISTS global.leaderboard_rollups (
+    blog_id BIGINT NOT NULL REFERENCES global.blogs(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL,
+    period VARCHAR(10) NOT NULL,
+    starts_on DATE NOT NULL,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    score BIGINT NOT NULL,
+    PRIMARY KEY (blog_id, kind, period, starts_on, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_rollups_score ON global.leaderboard_rollups(blog_id, kind, period, starts_on, score DESC);
+
+-- Periods of each blog whose standings are rolled up
+CREATE TABLE IF NOT EXISTS global.leaderboard_periods (
+    blog_id BIGINT NOT NULL REFERENCES global.blogs(id) ON DELETE CASCADE,
+    period VARCHAR(10) NOT NULL,
+    starts_on DATE NOT NULL,
+    rolled_up_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (blog_id, period, starts_on)
+);
+
+-- Newsletter subscribers of each blog; only confirmed ones receive campaigns
+CREATE TABLE IF NOT EXISTS global.newsletter_subscribers (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL REFERENCES global.blogs(id) ON DELETE CASCADE,
+    -- Lowercased
+    email VARCHAR(255) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    -- Token of the confirmation link, cleared once confirmed
+    confirm_token VARCHAR(64) UNIQUE,
+    unsubscribe_token VARCHAR(64) NOT NULL UNIQUE,
+    confirmation_sent_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    confirmed_at TIMESTAMPTZ,
+    unsubscribed_at TIMESTAMPTZ,
+    UNIQUE (blog_id, email)
+);
+
+-- Posts sent to the newsletter subscribers
+CREATE TABLE IF NOT EXISTS global.newsletter_campaigns (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL REFERENCES global.blogs(id) ON DELETE CASCADE,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    subject VARCHAR(255) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'sending',
+    created_by UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+-- Delivery of each campaign to each recipient
+CREATE TABLE IF NOT EXISTS global.newsletter_deliveries (
+    campaign_id BIGINT NOT NULL REFERENCES global.newsletter_campaigns(id) ON DELETE CASCADE,
+    subscriber_id BIGINT NOT NULL REFERENCES global.newsletter_subscribers(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    error TEXT,
+    attempted_at TIMESTAMPTZ,
+    PRIMARY KEY (campaign_id, subscriber_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_newsletter_deliveries_pending ON global.newsletter_deliveries(campaign_id, subscriber_id) WHERE status = 'pending';
+
+-- System announcements, sent to every user or to those of one role or membership tier as
+-- SystemMessage notifications by a job scheduled for scheduled_for
+CREATE TABLE IF NOT EXISTS global.announcements (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL REFERENCES global.blogs(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    role VARCHAR(20),
+    tier_id BIGINT REFERENCES global.membership_tiers(id) ON DELETE CASCADE,
+    -- scheduled, sending or sent
+    status VARCHAR(20) NOT NULL DEFAULT 'scheduled',
+    scheduled_for TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Recipients are notified in order of ID; a resumed job carries on after the last one
+    last_recipient_id UUID,
+    recipients BIGINT NOT NULL DEFAULT 0,
+    created_by UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_announcements_blog ON global.announcements(blog_id, scheduled_for DESC);
+
+-- Invite codes for registering while a blog's registration.mode setting is invite-only;
+-- each can be used once, until it expires
+CREATE TABLE IF NOT EXISTS global.invites (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL REFERENCES global.blogs(id) ON DELETE CASCADE,
+    -- Uppercase, see src/invite/service.rs
+    code VARCHAR(32) NOT NULL,
+    note TEXT,
+    created_by UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_by UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    used_at TIMESTAMPTZ,
+    UNIQUE (blog_id, code)
+);
+
+CREATE INDEX IF NOT EXISTS idx_invites_blog ON global.invites(blog_id, created_at DESC);
+
 -- Create indexes
 CREATE INDEX IF NOT EXISTS idx_posts_user_id ON global.posts(user_id);
-CREATE INDEX IF NOT EXISTS idx_posts_slug ON global.posts(slug);
+-- Slugs and tag names used to be matched as stored, and tags by lower(name)
+DROP INDEX IF EXISTS global.idx_posts_blog_slug;
+DROP INDEX IF EXISTS global.idx_tags_lower_name;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_posts_blog_slug_key ON global.posts(blog_id, global.match_key(slug));
+CREATE INDEX IF NOT EXISTS idx_posts_blog_id ON global.posts(blog_id, created_at DESC);
 CREATE INDEX IF NOT EXISTS idx_posts_created_at ON global.posts(created_at DESC);
+-- Listings filtered by `meta.` parameters match with @>
+CREATE INDEX IF NOT EXISTS idx_posts_metadata ON global.posts USING GIN (metadata jsonb_path_ops);
+CREATE INDEX IF NOT EXISTS idx_posts_blog_popularity ON global.posts(blog_id, popularity_score DESC, id DESC) WHERE is_draft = false AND is_deleted = false;
 CREATE INDEX IF NOT EXISTS idx_post_tags_post_id ON global.post_tags(post_id);
 CREATE INDEX IF NOT EXISTS idx_post_tags_tag_id ON global.post_tags(tag_id);
+CREATE INDEX IF NOT EXISTS idx_post_attachments_post_id ON global.post_attachments(post_id);
+CREATE INDEX IF NOT EXISTS idx_cover_images_post_id ON global.cover_images(post_id);
 CREATE INDEX IF NOT EXISTS idx_tags_name ON global.tags(name);
+CREATE INDEX IF NOT EXISTS idx_tags_match_key ON global.tags(global.match_key(name));
 
 -- Comment indexes
 CREATE INDEX IF NOT EXISTS idx_comments_post_id ON global.comments(post_id);
 CREATE INDEX IF NOT EXISTS idx_comments_user_id ON global.comments(user_id);
 CREATE INDEX IF NOT EXISTS idx_comments_parent_id ON global.comments(parent_comment_id);
 CREATE INDEX IF NOT EXISTS idx_comments_created_at ON global.comments(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_comments_pending ON global.comments(created_at) WHERE moderation_status = 'pending';
+-- At most one pinned comment per post
+CREATE UNIQUE INDEX IF NOT EXISTS idx_comments_pinned ON global.comments(post_id) WHERE is_pinned;
+
+-- Thread subscriptions: users following replies anywhere under a root comment
+CREATE TABLE IF NOT EXISTS global.comment_thread_subscriptions (
+    root_comment_id BIGINT NOT NULL REFERENCES global.comments(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (root_comment_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_comment_thread_subscriptions_user_id ON global.comment_thread_subscriptions(user_id);
+
+-- Emoji reactions to comments, by shortcode; one of each per user
+CREATE TABLE IF NOT EXISTS global.comment_reactions (
+    comment_id BIGINT NOT NULL REFERENCES global.comments(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    reaction VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (comment_id, user_id, reaction)
+);
+
+-- Reports of abusive or low-quality comments; one per user
+CREATE TABLE IF NOT EXISTS global.comment_reports (
+    comment_id BIGINT NOT NULL REFERENCES global.comments(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (comment_id, user_id)
+);
+
+-- Notifications written in the same transaction as the change that causes them and
+-- delivered after commit by the notification outbox relay
+CREATE TABLE IF NOT EXISTS global.notification_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_outbox_pending ON global.notification_outbox(id) WHERE delivered_at IS NULL;
+
+-- Delivered notifications; the unread count of each user is also cached in Redis
+CREATE TABLE IF NOT EXISTS global.notifications (
+    id BIGSERIAL PRIMARY KEY,
+    recipient_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    notification_type VARCHAR(32) NOT NULL,
+    object_id BIGINT NOT NULL,
+    related_object_id BIGINT,
+    actor_id UUID NOT NULL,
+    content TEXT NOT NULL,
+    context JSONB NOT NULL DEFAULT '{}',
+    -- What notifications of the same type are batched by, e.g. the post commented on
+    target_id BIGINT,
+    -- Notifications folded into this one within the batch window
+    aggregate_count INTEGER NOT NULL DEFAULT 1,
+    is_read BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Columns added after the table was first created, for databases created before them
+ALTER TABLE global.notifications ADD COLUMN IF NOT EXISTS target_id BIGINT;
+ALTER TABLE global.notifications ADD COLUMN IF NOT EXISTS aggregate_count INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE global.notifications ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+-- Notifications used to be listed by created_at and counted as unread per recipient only
+DROP INDEX IF EXISTS global.idx_notifications_recipient;
+DROP INDEX IF EXISTS global.idx_notifications_unread;
+
+CREATE INDEX IF NOT EXISTS idx_notifications_recipient_updated ON global.notifications(recipient_id, updated_at DESC);
+CREATE INDEX IF NOT EXISTS idx_notifications_unread_target ON global.notifications(recipient_id, notification_type, target_id) WHERE is_read = false;
+-- Purging notifications past their retention period
+CREATE INDEX IF NOT EXISTS idx_notifications_updated_at ON global.notifications(updated_at);
+
+-- Do-not-disturb schedules; notifications held back during quiet hours are counted so a
+-- summary can be pushed when they end
+CREATE TABLE IF NOT EXISTS global.notification_quiet_hours (
+    user_id UUID PRIMARY KEY REFERENCES global.users(id) ON DELETE CASCADE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    timezone VARCHAR(64) NOT NULL,
+    held_count INTEGER NOT NULL DEFAULT 0,
+    summary_due_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_quiet_hours_due ON global.notification_quiet_hours(summary_due_at) WHERE summary_due_at IS NOT NULL;
+
+-- Background jobs, run by the job worker of any instance; see src/jobs/service.rs
+CREATE TABLE IF NOT EXISTS global.jobs (
+    id UUID PRIMARY KEY,
+    kind VARCHAR(64) NOT NULL,
+    -- queued, running, succeeded or failed
+    status VARCHAR(16) NOT NULL DEFAULT 'queued',
+    payload JSONB NOT NULL DEFAULT '{}',
+    result JSONB,
+    error TEXT,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    created_by UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Queued jobs are not started before this time
+    run_after TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ
+);
+
+-- Columns added after the table was first created, for databases created before them
+ALTER TABLE global.jobs ADD COLUMN IF NOT EXISTS run_after TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS idx_jobs_runnable ON global.jobs(created_at) WHERE status IN ('queued', 'running');
+
+-- Keys of the Ghost-compatible content API; public like Ghost's, so stored as issued
+CREATE TABLE IF NOT EXISTS global.content_api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    key VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Interactions analytics reports are computed from. Usually created by analytics_schema.sql
+-- along with its materialized views; declared here too so the indexes below always apply.
+CREATE TABLE IF NOT EXISTS global.user_interactions (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID,
+    interaction_type VARCHAR(20) NOT NULL,
+    post_id BIGINT,
+    comment_id BIGINT,
+    metadata JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Per-post audience reports
+CREATE INDEX IF NOT EXISTS idx_user_interactions_post_audience ON global.user_interactions(post_id, interaction_type, created_at) INCLUDE (user_id);
+-- Finding whether a reader viewed anything before, for new vs returning readers
+CREATE INDEX IF NOT EXISTS idx_user_interactions_user_views ON global.user_interactions(user_id, created_at) WHERE interaction_type = 'view';
+-- Purging interactions past their retention period
+CREATE INDEX IF NOT EXISTS idx_user_interactions_created_at ON global.user_interactions(created_at);
+
+-- Daily interaction counts per post, kept after the raw interactions are purged
+CREATE TABLE IF NOT EXISTS global.interaction_rollups (
+    day DATE NOT NULL,
+    post_id BIGINT NOT NULL,
+    interaction_type VARCHAR(20) NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, post_id, interaction_type)
+);
+
+-- Posts recommended to each user, generated ahead of time and served until they expire
+CREATE TABLE IF NOT EXISTS global.recommendations (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    score DOUBLE PRECISION NOT NULL,
+    recommendation_type VARCHAR(20) NOT NULL,
+    -- Why the post is recommended, e.g. "Because you read X", shown to the user
+    reason TEXT NOT NULL,
+    -- Signals the score was computed from, shown in the admin preview
+    features JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (user_id, post_id)
+);
+
+-- Columns added after the table was first created, for databases created before them
+ALTER TABLE global.recommendations ADD COLUMN IF NOT EXISTS features JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_recommendations_user_score ON global.recommendations(user_id, score DESC) INCLUDE (expires_at);
+-- Posts a user has interacted with, which recommendations leave out
+CREATE INDEX IF NOT EXISTS idx_user_interactions_user_posts ON global.user_interactions(user_id, post_id);
+
+-- Posts each user has read, so they can be left out of recommendations. Unlike interactions
+-- these are not purged by retention cleanups.
+CREATE TABLE IF NOT EXISTS global.post_reads (
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    read_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, post_id)
+);
+
+-- Current likes of posts. Only counted likes are part of posts.likes; the others were made
+-- while the user was flagged for gaming likes.
+CREATE TABLE IF NOT EXISTS global.post_likes (
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    counted BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (post_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_post_likes_user ON global.post_likes(user_id);
+
+-- Suspicious like activity, for moderators
+CREATE TABLE IF NOT EXISTS global.like_flags (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    reason VARCHAR(20) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_like_flags_blog_created ON global.like_flags(blog_id, created_at DESC);
+
+-- New posts that look like a copy of another user's recent post, for moderators
+CREATE TABLE IF NOT EXISTS global.post_flags (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    duplicate_of BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    -- Bits the content fingerprints differ in
+    distance INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_post_flags_blog_created ON global.post_flags(blog_id, created_at DESC);
+
+-- Administrative changes, who made them and what they affected
+CREATE TABLE IF NOT EXISTS global.audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    actor_id UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    action VARCHAR(50) NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_blog_created ON global.audit_log(blog_id, created_at DESC);
+
+-- Messages from readers to the site admins
+CREATE TABLE IF NOT EXISTS global.feedback (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    -- Signed-in reader who sent it
+    user_id UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    name VARCHAR(100),
+    email VARCHAR(255) NOT NULL,
+    message TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'new',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    answered_at TIMESTAMPTZ,
+    answered_by UUID REFERENCES global.users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_feedback_blog_status_created ON global.feedback(blog_id, status, created_at DESC);
+
+-- A/B tests of post titles. Impressions and clicks are interactions with the test and
+-- variant in their metadata.
+CREATE TABLE IF NOT EXISTS global.post_title_tests (
+    id BIGSERIAL PRIMARY KEY,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    titles TEXT[] NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'running',
+    -- Index into titles of the variant promoted to the post's title
+    winner SMALLINT,
+    created_by UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ended_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_post_title_tests_running ON global.post_title_tests(post_id) WHERE status = 'running';
+CREATE INDEX IF NOT EXISTS idx_post_title_tests_post_started ON global.post_title_tests(post_id, started_at DESC);
+
+-- Named reading lists of posts, kept by readers in addition to their bookmarks
+CREATE TABLE IF NOT EXISTS global.reading_lists (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    is_public BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_reading_lists_blog_user ON global.reading_lists(blog_id, user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS global.reading_list_posts (
+    list_id BIGINT NOT NULL REFERENCES global.reading_lists(id) ON DELETE CASCADE,
+    post_id BIGINT NOT NULL REFERENCES global.posts(id) ON DELETE CASCADE,
+    -- Zero-based place of the post in the list
+    position INTEGER NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (list_id, post_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_reading_list_posts_post ON global.reading_list_posts(post_id);
+
+-- Tips from readers to the authors of posts, paid through the configured payment provider
+CREATE TABLE IF NOT EXISTS global.tips (
+    id BIGSERIAL PRIMARY KEY,
+    blog_id BIGINT NOT NULL DEFAULT 1 REFERENCES global.blogs(id) ON DELETE CASCADE,
+    post_id BIGINT REFERENCES global.posts(id) ON DELETE SET NULL,
+    author_id UUID NOT NULL REFERENCES global.users(id) ON DELETE CASCADE,
+    tipper_id UUID REFERENCES global.users(id) ON DELETE SET NULL,
+    -- In the minor unit of the currency
+    amount BIGINT NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    message TEXT,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    provider VARCHAR(20) NOT NULL,
+    -- The provider's ID of the checkout, set once it is opened
+    provider_ref VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    paid_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tips_provider_ref ON global.tips(provider, provider_ref);
+CREATE INDEX IF NOT EXISTS idx_tips_author_paid ON global.tips(blog_id, author_id, paid_at DESC) WHERE status = 'paid';
@@ -0,0 +1,217 @@
+use crate::logging::{current_request, RequestContext};
+use axum::{
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+use std::any::Any;
+use std::panic::PanicHookInfo;
+use std::sync::OnceLock;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// What went wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A thread panicked, in a request handler or a background task
+    Panic,
+    /// A request was answered with `500 Internal Server Error`
+    ServerError,
+}
+
+/// An error worth a developer's attention, with the request it happened in
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub request: Option<RequestContext>,
+}
+
+impl ErrorReport {
+    /// A report of an error in the current request, if any
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            request: current_request(),
+        }
+    }
+
+    fn request_id(&self) -> Option<&str> {
+        self.request.as_ref().map(|r| r.request_id.as_str())
+    }
+
+    fn user_id(&self) -> Option<Uuid> {
+        self.request.as_ref().and_then(|r| r.user_id.get().copied())
+    }
+}
+
+/// Destination of error reports, such as an error tracking service
+pub trait ErrorReporter: Send + Sync {
+    fn report(&self, report: &ErrorReport);
+
+    /// Send reports still queued, before the process exits
+    fn flush(&self) {}
+}
+
+/// Reports errors to the log only, the default when no error tracking service is configured
+pub struct LogReporter;
+
+impl ErrorReporter for LogReporter {
+    fn report(&self, report: &ErrorReport) {
+        error!(
+            kind = ?report.kind,
+            request_id = report.request_id(),
+            user_id = report.user_id().map(|id| id.to_string()),
+            "{}",
+            report.message
+        );
+    }
+}
+
+/// Reports errors to Sentry, with the request and user as context
+#[cfg(feature = "sentry")]
+pub struct SentryReporter {
+    _guard: sentry::ClientInitGuard,
+}
+
+#[cfg(feature = "sentry")]
+impl SentryReporter {
+    pub fn new(dsn: &str) -> Self {
+        let guard = sentry::init((
+            dsn,
+            sentry::ClientOptions {
+                release: sentry::release_name!(),
+                environment: std::env::var("SENTRY_ENVIRONMENT").ok().map(Into::into),
+                ..Default::default()
+            },
+        ));
+        Self { _guard: guard }
+    }
+}
+
+#[cfg(feature = "sentry")]
+impl ErrorReporter for SentryReporter {
+    fn report(&self, report: &ErrorReport) {
+        LogReporter.report(report);
+
+        sentry::with_scope(
+            |scope| {
+                scope.set_tag(
+                    "kind",
+                    match report.kind {
+                        ErrorKind::Panic => "panic",
+                        ErrorKind::ServerError => "server_error",
+                    },
+                );
+                if let Some(request) = &report.request {
+                    scope.set_tag("request_id", &request.request_id);
+                    scope.set_tag("route", format!("{} {}", request.method, request.path));
+                }
+                scope.set_user(report.user_id().map(|id| sentry::User {
+                    id: Some(id.to_string()),
+                    ..Default::default()
+                }));
+            },
+            || sentry::capture_message(&report.message, sentry::Level::Error),
+        );
+    }
+
+    fn flush(&self) {
+        if let Some(client) = sentry::Hub::current().client() {
+            client.flush(Some(std::time::Duration::from_secs(2)));
+        }
+    }
+}
+
+static REPORTER: OnceLock<Box<dyn ErrorReporter>> = OnceLock::new();
+
+/// Set up error reporting: to Sentry when built with the `sentry` feature and `SENTRY_DSN`
+/// is set, to the log otherwise. Panics are reported from then on.
+pub fn init() {
+    let reporter: Box<dyn ErrorReporter> = match std::env::var("SENTRY_DSN") {
+        #[cfg(feature = "sentry")]
+        Ok(dsn) if !dsn.is_empty() => {
+            info!("Reporting errors to Sentry");
+            Box::new(SentryReporter::new(&dsn))
+        }
+        #[cfg(not(feature = "sentry"))]
+        Ok(dsn) if !dsn.is_empty() => {
+            info!("SENTRY_DSN is set but the `sentry` feature is not built in, logging errors");
+            Box::new(LogReporter)
+        }
+        _ => Box::new(LogReporter),
+    };
+    let _ = REPORTER.set(reporter);
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        report(&ErrorReport::new(ErrorKind::Panic, panic_message(info)));
+        default_hook(info);
+    }));
+}
+
+/// Report an error
+pub fn report(report: &ErrorReport) {
+    match REPORTER.get() {
+        Some(reporter) => reporter.report(report),
+        None => LogReporter.report(report),
+    }
+}
+
+/// Send reports still queued, before the process exits
+pub fn flush() {
+    if let Some(reporter) = REPORTER.get() {
+        reporter.flush();
+    }
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+
+    match info.location() {
+        Some(location) => format!("Panicked at {}: {}", location, message),
+        None => format!("Panicked: {}", message),
+    }
+}
+
+// Marks responses to panicked requests, which the panic hook has already reported
+#[derive(Debug, Clone, Copy)]
+struct PanicReported;
+
+/// Answer a request whose handler panicked with a 500; used with tower-http's
+/// `CatchPanicLayer`
+pub fn panic_response(_panic: Box<dyn Any + Send + 'static>) -> Response {
+    let mut response = (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "error": "Internal server error" })),
+    )
+        .into_response();
+    response.extensions_mut().insert(PanicReported);
+    response
+}
+
+/// Middleware that reports requests answered with `500 Internal Server Error`
+pub async fn report_server_errors<B>(req: Request<B>, next: Next<B>) -> Response {
+    let response = next.run(req).await;
+
+    if response.status() == StatusCode::INTERNAL_SERVER_ERROR
+        && response.extensions().get::<PanicReported>().is_none()
+    {
+        let message = match current_request() {
+            Some(request) => format!(
+                "500 Internal Server Error on {} {}",
+                request.method, request.path
+            ),
+            None => "500 Internal Server Error".to_string(),
+        };
+        report(&ErrorReport::new(ErrorKind::ServerError, message));
+    }
+    response
+}
@@ -0,0 +1,163 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::feature_flags::model::{FeatureFlagError, UpsertFeatureFlagRequest};
+use crate::feature_flags::service::FeatureFlagService;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+
+fn feature_flag_error_response(e: FeatureFlagError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Feature flag error: {:?}", e);
+    let status = match e {
+        FeatureFlagError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+        FeatureFlagError::FlagNotFound => StatusCode::NOT_FOUND,
+        FeatureFlagError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can manage feature flags" })),
+    )
+        .into_response()
+}
+
+/// Get the feature flags as evaluated for the requesting user
+///
+/// Anonymous readers only see flags that are rolled out to everyone.
+#[utoipa::path(
+    get,
+    path = "/api/feature-flags",
+    tag = "feature-flags",
+    responses(
+        (status = 200, description = "Flags evaluated successfully", body = EvaluatedFlagsResponse),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_evaluated_flags(
+    Extension(user): Extension<Option<AuthUser>>,
+    State(service): State<Arc<FeatureFlagService>>,
+) -> Response {
+    match service.evaluate_all(user.as_ref()).await {
+        Ok(flags) => (StatusCode::OK, Json(flags)).into_response(),
+        Err(e) => feature_flag_error_response(e),
+    }
+}
+
+/// List all feature flags with their targeting (admin only)
+#[utoipa::path(
+    get,
+    path = "/api/admin/feature-flags",
+    tag = "feature-flags",
+    responses(
+        (status = 200, description = "Flags retrieved successfully", body = Vec<FeatureFlag>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_flags(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<FeatureFlagService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.list_flags().await {
+        Ok(flags) => (StatusCode::OK, Json(json!(flags))).into_response(),
+        Err(e) => feature_flag_error_response(e),
+    }
+}
+
+/// Create or update a feature flag (admin only)
+#[utoipa::path(
+    put,
+    path = "/api/admin/feature-flags/{key}",
+    tag = "feature-flags",
+    params(
+        ("key" = String, Path, description = "Flag key")
+    ),
+    request_body = UpsertFeatureFlagRequest,
+    responses(
+        (status = 200, description = "Flag saved", body = FeatureFlag),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn upsert_flag(
+    Extension(user): Extension<AuthUser>,
+    Path(key): Path<String>,
+    State(service): State<Arc<FeatureFlagService>>,
+    Json(request): Json<UpsertFeatureFlagRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.upsert_flag(&key, request).await {
+        Ok(flag) => {
+            info!("Admin {} updated feature flag {}", user.user_id, flag.key);
+            (StatusCode::OK, Json(flag)).into_response()
+        }
+        Err(e) => feature_flag_error_response(e),
+    }
+}
+
+/// Delete a feature flag (admin only)
+#[utoipa::path(
+    delete,
+    path = "/api/admin/feature-flags/{key}",
+    tag = "feature-flags",
+    params(
+        ("key" = String, Path, description = "Flag key")
+    ),
+    responses(
+        (status = 204, description = "Flag deleted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Flag not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_flag(
+    Extension(user): Extension<AuthUser>,
+    Path(key): Path<String>,
+    State(service): State<Arc<FeatureFlagService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.delete_flag(&key).await {
+        Ok(()) => {
+            info!("Admin {} deleted feature flag {}", user.user_id, key);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => feature_flag_error_response(e),
+    }
+}
@@ -0,0 +1,164 @@
+use crate::auth::middleware::AuthUser;
+use crate::fnv;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Flag that turns on guest comments without `GUEST_COMMENTS_ENABLED`, e.g. to trial them
+/// on a running deployment. Guests are anonymous, so it only takes effect at a 100% rollout.
+pub const ANONYMOUS_COMMENTS_FLAG: &str = "anonymous_comments";
+
+/// A feature flag. A flag is on for a user when it is enabled and the user is targeted, either
+/// explicitly, through their role, or by falling into the rollout percentage.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct FeatureFlag {
+    /// Unique flag key
+    #[schema(example = "new_recommender")]
+    pub key: String,
+
+    /// What the flag controls
+    #[schema(example = "Serve recommendations from the embedding based recommender")]
+    pub description: Option<String>,
+
+    /// Master switch; a disabled flag is off for everyone
+    pub enabled: bool,
+
+    /// Share of users, 0-100, the flag is on for. Anonymous readers only get it at 100.
+    #[schema(example = "10")]
+    pub rollout_percentage: i32,
+
+    /// Roles the flag is always on for
+    #[schema(example = json!(["admin"]))]
+    pub target_roles: Vec<String>,
+
+    /// Users the flag is always on for
+    pub target_users: Vec<Uuid>,
+
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FeatureFlag {
+    /// Whether the flag is on for the given user, or for an anonymous reader
+    pub fn is_enabled_for(&self, user: Option<&AuthUser>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        match user {
+            Some(user) => {
+                self.target_users.contains(&user.user_id)
+                    || self
+                        .target_roles
+                        .iter()
+                        .any(|role| role == user.role.as_str())
+                    || rollout_bucket(&self.key, user.user_id) < self.rollout_percentage
+            }
+            None => self.rollout_percentage >= 100,
+        }
+    }
+}
+
+/// Stable bucket, 0-99, of a user for a flag.
+///
+/// Hashing the flag key with the user ID keeps a user in the same bucket as a rollout grows,
+/// while different flags roll out to different users.
+pub fn rollout_bucket(flag_key: &str, user_id: Uuid) -> i32 {
+    // FNV-1a, which unlike the std hasher is stable across Rust versions and processes
+    let hash = fnv::hash(flag_key.bytes().chain(user_id.as_bytes().iter().copied()));
+    (hash % 100) as i32
+}
+
+/// Request to create or update a flag
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpsertFeatureFlagRequest {
+    /// What the flag controls
+    pub description: Option<String>,
+
+    /// Master switch
+    pub enabled: bool,
+
+    /// Share of users, 0-100, the flag is on for
+    #[serde(default)]
+    #[schema(example = "10")]
+    pub rollout_percentage: i32,
+
+    /// Roles the flag is always on for
+    #[serde(default)]
+    #[schema(example = json!(["admin"]))]
+    pub target_roles: Vec<String>,
+
+    /// Users the flag is always on for
+    #[serde(default)]
+    pub target_users: Vec<Uuid>,
+}
+
+/// Flags as evaluated for the requesting user
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EvaluatedFlagsResponse {
+    #[schema(value_type = Object, example = json!({"new_recommender": true, "anonymous_comments": false}))]
+    pub flags: BTreeMap<String, bool>,
+}
+
+/// Possible feature flag errors
+#[derive(Debug, thiserror::Error)]
+pub enum FeatureFlagError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Feature flag not found")]
+    FlagNotFound,
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt::Role;
+
+    fn flag(rollout_percentage: i32) -> FeatureFlag {
+        FeatureFlag {
+            key: "new_recommender".to_string(),
+            description: None,
+            enabled: true,
+            rollout_percentage,
+            target_roles: vec!["admin".to_string()],
+            target_users: Vec::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_rollout_bucket_is_stable() {
+        let user_id = Uuid::new_v4();
+        let bucket = rollout_bucket("new_recommender", user_id);
+        assert!((0..100).contains(&bucket));
+        assert_eq!(bucket, rollout_bucket("new_recommender", user_id));
+    }
+
+    #[test]
+    fn test_flag_targeting() {
+        let admin = AuthUser {
+            user_id: Uuid::new_v4(),
+            role: Role::Admin,
+        };
+        let user = AuthUser {
+            user_id: Uuid::new_v4(),
+            role: Role::User,
+        };
+
+        assert!(flag(0).is_enabled_for(Some(&admin)));
+        assert!(!flag(0).is_enabled_for(Some(&user)));
+        assert!(flag(100).is_enabled_for(Some(&user)));
+        assert!(!flag(99).is_enabled_for(None));
+        assert!(flag(100).is_enabled_for(None));
+
+        let mut disabled = flag(100);
+        disabled.enabled = false;
+        assert!(!disabled.is_enabled_for(Some(&admin)));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::auth::middleware::AuthUser;
+use crate::cache::redis::RedisCache;
+use crate::feature_flags::model::{
+    EvaluatedFlagsResponse, FeatureFlag, FeatureFlagError, UpsertFeatureFlagRequest,
+};
+use redis::AsyncCommands;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+// Flags are platform wide, so unlike most cached data they are not scoped to a blog
+const FEATURE_FLAGS_CACHE_KEY: &str = "feature_flags";
+// Short, because every instance must pick up a flag change quickly, even one made elsewhere
+const FEATURE_FLAGS_CACHE_TTL: u64 = 60;
+
+const FLAG_COLUMNS: &str =
+    "key, description, enabled, rollout_percentage, target_roles, target_users, updated_at";
+
+#[derive(Debug, Clone)]
+pub struct FeatureFlagService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+}
+
+impl FeatureFlagService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self { pool, redis_cache }
+    }
+
+    /// Get all flags, from cache when possible
+    pub async fn list_flags(&self) -> Result<Vec<FeatureFlag>, FeatureFlagError> {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Ok(Some(cached_data)) =
+                    conn.get::<_, Option<String>>(FEATURE_FLAGS_CACHE_KEY).await
+                {
+                    match serde_json::from_str::<Vec<FeatureFlag>>(&cached_data) {
+                        Ok(flags) => return Ok(flags),
+                        Err(e) => error!("Failed to deserialize cached feature flags: {}", e),
+                    }
+                }
+            }
+        }
+
+        let flags = sqlx::query_as::<_, FeatureFlag>(&format!(
+            "SELECT {} FROM global.feature_flags ORDER BY key",
+            FLAG_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                let json_data = serde_json::to_string(&flags).unwrap_or_default();
+                if let Err(e) = conn
+                    .set_ex::<_, _, ()>(FEATURE_FLAGS_CACHE_KEY, json_data, FEATURE_FLAGS_CACHE_TTL)
+                    .await
+                {
+                    error!("Failed to cache feature flags: {}", e);
+                }
+            }
+        }
+
+        Ok(flags)
+    }
+
+    /// Whether a flag is on for the user. Unknown flags, and flags that cannot be loaded, are
+    /// off, so a risky feature stays hidden when in doubt.
+    pub async fn is_enabled(&self, key: &str, user: Option<&AuthUser>) -> bool {
+        match self.list_flags().await {
+            Ok(flags) => flags
+                .iter()
+                .find(|flag| flag.key == key)
+                .is_some_and(|flag| flag.is_enabled_for(user)),
+            Err(e) => {
+                error!(
+                    "Failed to load feature flags, treating {} as off: {}",
+                    key, e
+                );
+                false
+            }
+        }
+    }
+
+    /// Evaluate every flag for the user
+    pub async fn evaluate_all(
+        &self,
+        user: Option<&AuthUser>,
+    ) -> Result<EvaluatedFlagsResponse, FeatureFlagError> {
+        let flags = self
+            .list_flags()
+            .await?
+            .iter()
+            .map(|flag| (flag.key.clone(), flag.is_enabled_for(user)))
+            .collect();
+
+        Ok(EvaluatedFlagsResponse { flags })
+    }
+
+    /// Create a flag or replace its configuration
+    pub async fn upsert_flag(
+        &self,
+        key: &str,
+        request: UpsertFeatureFlagRequest,
+    ) -> Result<FeatureFlag, FeatureFlagError> {
+        if key.is_empty()
+            || key.len() > 100
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.')
+        {
+            return Err(FeatureFlagError::InvalidParameter(
+                "Flag key must be 1-100 lowercase letters, digits, underscores or dots".to_string(),
+            ));
+        }
+        if !(0..=100).contains(&request.rollout_percentage) {
+            return Err(FeatureFlagError::InvalidParameter(
+                "rollout_percentage must be between 0 and 100".to_string(),
+            ));
+        }
+
+        let flag = sqlx::query_as::<_, FeatureFlag>(&format!(
+            r#"
+            INSERT INTO global.feature_flags (
+                key, description, enabled, rollout_percentage, target_roles, target_users,
+                updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, NOW())
+            ON CONFLICT (key) DO UPDATE
+            SET description = EXCLUDED.description,
+                enabled = EXCLUDED.enabled,
+                rollout_percentage = EXCLUDED.rollout_percentage,
+                target_roles = EXCLUDED.target_roles,
+                target_users = EXCLUDED.target_users,
+                updated_at = NOW()
+            RETURNING {}
+            "#,
+            FLAG_COLUMNS
+        ))
+        .bind(key)
+        .bind(&request.description)
+        .bind(request.enabled)
+        .bind(request.rollout_percentage)
+        .bind(&request.target_roles)
+        .bind(&request.target_users)
+        .fetch_one(&self.pool)
+        .await?;
+
+        self.invalidate_cache().await;
+        info!(
+            "Feature flag {} set to enabled={} rollout={}%",
+            flag.key, flag.enabled, flag.rollout_percentage
+        );
+        Ok(flag)
+    }
+
+    /// Delete a flag; code checking it will see it as off
+    pub async fn delete_flag(&self, key: &str) -> Result<(), FeatureFlagError> {
+        let result = sqlx::query("DELETE FROM global.feature_flags WHERE key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(FeatureFlagError::FlagNotFound);
+        }
+
+        self.invalidate_cache().await;
+        info!("Deleted feature flag {}", key);
+        Ok(())
+    }
+
+    async fn invalidate_cache(&self) {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Err(e) = conn.del::<_, ()>(FEATURE_FLAGS_CACHE_KEY).await {
+                    error!("Failed to invalidate cached feature flags: {}", e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::feedback::model::{
+    CreateFeedbackRequest, FeedbackError, FeedbackListParams, UpdateFeedbackRequest,
+};
+use crate::feedback::service::FeedbackService;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn feedback_error_response(e: FeedbackError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        FeedbackError::ValidationError(_) => StatusCode::BAD_REQUEST,
+        FeedbackError::CaptchaFailed => StatusCode::FORBIDDEN,
+        FeedbackError::NotFound => StatusCode::NOT_FOUND,
+        FeedbackError::DatabaseError(_) | FeedbackError::InternalError(_) => {
+            error!("Feedback error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn admin_required() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can read feedback" })),
+    )
+        .into_response()
+}
+
+/// Send a message to the site admins
+///
+/// Open to anyone. Anonymous readers must give an email address to answer to, and solve a
+/// captcha when one is configured; signed-in readers answer to their account by default.
+/// Rate limited per user or client address.
+#[utoipa::path(
+    post,
+    path = "/api/feedback",
+    tag = "feedback",
+    request_body = CreateFeedbackRequest,
+    responses(
+        (status = 201, description = "Message sent", body = Feedback),
+        (status = 400, description = "Invalid message, name or email address"),
+        (status = 403, description = "Captcha verification failed"),
+        (status = 429, description = "Too many messages"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn create_feedback(
+    Extension(user): Extension<Option<AuthUser>>,
+    State(service): State<Arc<FeedbackService>>,
+    Json(request): Json<CreateFeedbackRequest>,
+) -> Response {
+    match service.create_feedback(user.as_ref(), request).await {
+        Ok(feedback) => (StatusCode::CREATED, Json(feedback)).into_response(),
+        Err(e) => feedback_error_response(e),
+    }
+}
+
+/// List messages to the site admins (admin only)
+///
+/// Newest first, optionally only `new` or `answered` ones.
+#[utoipa::path(
+    get,
+    path = "/api/admin/feedback",
+    tag = "feedback",
+    params(FeedbackListParams, PageParams),
+    responses(
+        (status = 200, description = "Feedback retrieved successfully", body = [Feedback]),
+        (status = 400, description = "Invalid page"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_feedback(
+    Extension(user): Extension<AuthUser>,
+    OriginalUri(uri): OriginalUri,
+    State(service): State<Arc<FeedbackService>>,
+    Query(filter): Query<FeedbackListParams>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    };
+
+    match service.list_feedback(filter.status, &pagination).await {
+        Ok(feedback) => (
+            StatusCode::OK,
+            pagination.headers(&uri, feedback.len()),
+            Json(feedback),
+        )
+            .into_response(),
+        Err(e) => feedback_error_response(e),
+    }
+}
+
+/// Mark a message to the site admins answered, or new again (admin only)
+#[utoipa::path(
+    put,
+    path = "/api/admin/feedback/{id}",
+    tag = "feedback",
+    params(
+        ("id" = i64, Path, description = "Feedback ID")
+    ),
+    request_body = UpdateFeedbackRequest,
+    responses(
+        (status = 200, description = "Status updated", body = Feedback),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Feedback not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_feedback(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<FeedbackService>>,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateFeedbackRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    match service
+        .update_status(id, request.status, user.user_id)
+        .await
+    {
+        Ok(feedback) => (StatusCode::OK, Json(feedback)).into_response(),
+        Err(e) => feedback_error_response(e),
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Longest accepted message, in characters
+pub const MAX_MESSAGE_LENGTH: usize = 5000;
+/// Longest accepted name, in characters
+pub const MAX_NAME_LENGTH: usize = 100;
+
+/// Whether an admin has dealt with a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackStatus {
+    New,
+    Answered,
+}
+
+impl FeedbackStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FeedbackStatus::New => "new",
+            FeedbackStatus::Answered => "answered",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "new" => Some(FeedbackStatus::New),
+            "answered" => Some(FeedbackStatus::Answered),
+            _ => None,
+        }
+    }
+}
+
+/// A message to the site admins
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateFeedbackRequest {
+    /// Name to answer to; the username of signed-in readers by default
+    #[schema(example = "Ada")]
+    pub name: Option<String>,
+    /// Address to answer to; required unless signed in, when it defaults to the account's
+    #[schema(example = "ada@example.com")]
+    pub email: Option<String>,
+    #[schema(example = "The RSS feed of the engineering blog is empty.")]
+    pub message: String,
+    /// Captcha token, required from anonymous readers when a captcha is configured
+    pub captcha_token: Option<String>,
+}
+
+/// A message sent to the site admins
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Feedback {
+    #[schema(example = 12)]
+    pub id: i64,
+    /// Signed-in reader who sent it
+    pub user_id: Option<Uuid>,
+    #[schema(example = "Ada")]
+    pub name: Option<String>,
+    #[schema(example = "ada@example.com")]
+    pub email: String,
+    pub message: String,
+    pub status: FeedbackStatus,
+    pub created_at: DateTime<Utc>,
+    /// When an admin marked it answered
+    pub answered_at: Option<DateTime<Utc>>,
+    pub answered_by: Option<Uuid>,
+}
+
+/// Filter of the admin feedback list
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FeedbackListParams {
+    /// Only messages with this status
+    #[param(inline)]
+    pub status: Option<FeedbackStatus>,
+}
+
+/// Request to change the status of a message
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateFeedbackRequest {
+    pub status: FeedbackStatus,
+}
+
+/// Possible feedback errors
+#[derive(Debug, thiserror::Error)]
+pub enum FeedbackError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Captcha verification failed")]
+    CaptchaFailed,
+
+    #[error("Feedback not found")]
+    NotFound,
+
+    #[error("Internal error: {0}")]
+    InternalError(String),
+}
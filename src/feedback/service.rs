@@ -0,0 +1,262 @@
+use crate::auth::middleware::AuthUser;
+use crate::comment::captcha::verify_captcha;
+use crate::db::instrument::timed;
+use crate::feedback::model::{
+    CreateFeedbackRequest, Feedback, FeedbackError, FeedbackStatus, MAX_MESSAGE_LENGTH,
+    MAX_NAME_LENGTH,
+};
+use crate::notification::model::{comment_excerpt, NotificationPayload, NotificationType};
+use crate::notification::service::NotificationService;
+use crate::pagination::Pagination;
+use crate::tenant::middleware::current_blog_id;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+const MAX_EMAIL_LENGTH: usize = 255;
+
+const FEEDBACK_COLUMNS: &str =
+    "id, user_id, name, email, message, status, created_at, answered_at, answered_by";
+
+fn feedback_from_row(row: &PgRow) -> Result<Feedback, FeedbackError> {
+    let status: String = row.get("status");
+    Ok(Feedback {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        name: row.get("name"),
+        email: row.get("email"),
+        message: row.get("message"),
+        status: FeedbackStatus::parse(&status).ok_or_else(|| {
+            FeedbackError::InternalError(format!("Unknown feedback status: {}", status))
+        })?,
+        created_at: row.get("created_at"),
+        answered_at: row.get("answered_at"),
+        answered_by: row.get("answered_by"),
+    })
+}
+
+// Trimmed message, name and address of a submission, checked against their limits
+fn validate(
+    message: &str,
+    name: Option<&str>,
+    email: &str,
+) -> Result<(String, Option<String>, String), FeedbackError> {
+    let message = message.trim();
+    if message.is_empty() {
+        return Err(FeedbackError::ValidationError(
+            "Message cannot be empty".to_string(),
+        ));
+    }
+    if message.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err(FeedbackError::ValidationError(format!(
+            "Message cannot be longer than {} characters",
+            MAX_MESSAGE_LENGTH
+        )));
+    }
+
+    let name = name.map(str::trim).filter(|name| !name.is_empty());
+    if name.is_some_and(|name| name.chars().count() > MAX_NAME_LENGTH) {
+        return Err(FeedbackError::ValidationError(format!(
+            "Name cannot be longer than {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+
+    let email = email.trim();
+    if email.len() > MAX_EMAIL_LENGTH || email.parse::<lettre::Address>().is_err() {
+        return Err(FeedbackError::ValidationError(
+            "A valid email address is required".to_string(),
+        ));
+    }
+
+    Ok((
+        message.to_string(),
+        name.map(str::to_string),
+        email.to_string(),
+    ))
+}
+
+/// Messages from readers to the admins of each blog.
+///
+/// Anyone may send one; the route is rate limited per user or address, and anonymous senders
+/// must solve a captcha when `CAPTCHA_SECRET` is set. Every admin gets a `SystemMessage`
+/// notification of each message, queued in the transaction that stores it. Admins mark
+/// messages answered once they have replied by email.
+pub struct FeedbackService {
+    pool: PgPool,
+    notifications: Arc<NotificationService>,
+}
+
+impl FeedbackService {
+    pub fn new(pool: PgPool, notifications: Arc<NotificationService>) -> Self {
+        Self {
+            pool,
+            notifications,
+        }
+    }
+
+    /// Store a message to the blog's admins and notify them. Signed-in readers answer to
+    /// their account's username and address unless they give others.
+    pub async fn create_feedback(
+        &self,
+        user: Option<&AuthUser>,
+        request: CreateFeedbackRequest,
+    ) -> Result<Feedback, FeedbackError> {
+        let (default_name, default_email) = match user {
+            Some(user) => {
+                let row = sqlx::query("SELECT username, email FROM global.users WHERE id = $1")
+                    .bind(user.user_id)
+                    .fetch_one(&self.pool)
+                    .await?;
+                (
+                    Some(row.get::<String, _>("username")),
+                    Some(row.get::<String, _>("email")),
+                )
+            }
+            None => {
+                if std::env::var("CAPTCHA_SECRET").is_ok() {
+                    verify_captcha(request.captcha_token.as_deref().unwrap_or(""))
+                        .await
+                        .map_err(|_| FeedbackError::CaptchaFailed)?;
+                }
+                (None, None)
+            }
+        };
+
+        let email = request.email.or(default_email).unwrap_or_default();
+        let name = request.name.or(default_name);
+        let (message, name, email) = validate(&request.message, name.as_deref(), &email)?;
+        let user_id = user.map(|user| user.user_id);
+
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO global.feedback (blog_id, user_id, name, email, message)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING {}
+            "#,
+            FEEDBACK_COLUMNS
+        ))
+        .bind(current_blog_id())
+        .bind(user_id)
+        .bind(&name)
+        .bind(&email)
+        .bind(&message)
+        .fetch_one(&mut *tx)
+        .await?;
+        let feedback = feedback_from_row(&row)?;
+
+        let admin_ids: Vec<Uuid> =
+            sqlx::query_scalar("SELECT id FROM global.users WHERE role = 'admin'")
+                .fetch_all(&mut *tx)
+                .await?;
+        let sender = name.as_deref().unwrap_or(&email);
+        for admin_id in admin_ids {
+            let payload = NotificationPayload {
+                recipient_id: admin_id,
+                notification_type: NotificationType::SystemMessage,
+                object_id: feedback.id,
+                related_object_id: None,
+                actor_id: user_id.unwrap_or_else(Uuid::nil),
+                content: format!(
+                    "New feedback from {}: {}",
+                    sender,
+                    comment_excerpt(&message)
+                ),
+                context: Default::default(),
+            };
+            self.notifications
+                .enqueue_notification(&mut tx, &payload)
+                .await
+                .map_err(|e| FeedbackError::InternalError(e.to_string()))?;
+        }
+
+        tx.commit().await?;
+
+        info!("Stored feedback {} from {}", feedback.id, email);
+        Ok(feedback)
+    }
+
+    /// Messages to the blog's admins, newest first, optionally only those with `status`
+    pub async fn list_feedback(
+        &self,
+        status: Option<FeedbackStatus>,
+        pagination: &Pagination,
+    ) -> Result<Vec<Feedback>, FeedbackError> {
+        let sql = format!(
+            r#"
+            SELECT {}
+            FROM global.feedback
+            WHERE blog_id = $1 AND ($2::VARCHAR IS NULL OR status = $2)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            FEEDBACK_COLUMNS
+        );
+        let query = sqlx::query(&sql)
+            .bind(current_blog_id())
+            .bind(status.map(FeedbackStatus::as_str))
+            .bind(pagination.limit)
+            .bind(pagination.offset)
+            .fetch_all(&self.pool);
+        let rows = timed("feedback.list", query).await?;
+
+        rows.iter().map(feedback_from_row).collect()
+    }
+
+    /// Mark a message answered by the admin, or new again
+    pub async fn update_status(
+        &self,
+        id: i64,
+        status: FeedbackStatus,
+        admin_id: Uuid,
+    ) -> Result<Feedback, FeedbackError> {
+        let answered_by = (status == FeedbackStatus::Answered).then_some(admin_id);
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE global.feedback
+            SET status = $3,
+                answered_at = CASE WHEN $4::UUID IS NULL THEN NULL ELSE NOW() END,
+                answered_by = $4
+            WHERE id = $1 AND blog_id = $2
+            RETURNING {}
+            "#,
+            FEEDBACK_COLUMNS
+        ))
+        .bind(id)
+        .bind(current_blog_id())
+        .bind(status.as_str())
+        .bind(answered_by)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(FeedbackError::NotFound)?;
+
+        feedback_from_row(&row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_trims_and_checks_fields() {
+        let (message, name, email) =
+            validate("  Hello  ", Some("  "), " ada@example.com ").unwrap();
+        assert_eq!(message, "Hello");
+        assert_eq!(name, None);
+        assert_eq!(email, "ada@example.com");
+
+        assert!(validate("   ", None, "ada@example.com").is_err());
+        assert!(validate("Hello", None, "").is_err());
+        assert!(validate("Hello", None, "not an address").is_err());
+        let long_name = "a".repeat(MAX_NAME_LENGTH + 1);
+        assert!(validate("Hello", Some(&long_name), "ada@example.com").is_err());
+        let long_message = "a".repeat(MAX_MESSAGE_LENGTH + 1);
+        assert!(validate(&long_message, None, "ada@example.com").is_err());
+    }
+}
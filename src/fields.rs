@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::IntoParams;
+
+/// Query parameter of endpoints that can return sparse fieldsets
+#[derive(Debug, Default, Clone, Deserialize, IntoParams)]
+#[into_params(style = Form, parameter_in = Query)]
+pub struct FieldsParams {
+    /// Comma-separated fields to return of each item, e.g. `id,title,excerpt,author`; all
+    /// fields when absent
+    pub fields: Option<String>,
+}
+
+/// Errors raised while interpreting a `fields` parameter
+#[derive(Debug, thiserror::Error)]
+pub enum FieldsError {
+    #[error("Unknown field {field}, expected one of: {available}")]
+    UnknownField { field: String, available: String },
+}
+
+/// Top-level fields requested of the items a route returns, JSON:API-style.
+///
+/// Routes pass the fields they offer, so a misspelt field is reported rather than silently
+/// left out. Fields are selected from the response as it would be served in full, after any
+/// role-based gating such as paywalls, so a fieldset can only ever narrow what a reader sees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fieldset {
+    fields: Vec<String>,
+}
+
+impl Fieldset {
+    /// The requested fields, or `None` when all fields are wanted
+    pub fn parse(fields: Option<&str>, available: &[&str]) -> Result<Option<Self>, FieldsError> {
+        let mut requested = Vec::new();
+        for field in split_fields(fields) {
+            if !available.contains(&field) {
+                return Err(FieldsError::UnknownField {
+                    field: field.to_string(),
+                    available: available.join(", "),
+                });
+            }
+            if !requested.iter().any(|f| f == field) {
+                requested.push(field.to_string());
+            }
+        }
+
+        Ok((!requested.is_empty()).then_some(Self { fields: requested }))
+    }
+
+    /// Whether a field is requested
+    pub fn contains(&self, field: &str) -> bool {
+        self.fields.iter().any(|f| f == field)
+    }
+
+    /// Keep only the requested fields of a resource
+    pub fn select(&self, resource: Value) -> Value {
+        match resource {
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .filter(|(key, _)| self.contains(key))
+                    .collect::<Map<_, _>>(),
+            ),
+            resource => resource,
+        }
+    }
+
+    /// Serialize items with only the requested fields, or in full without a fieldset
+    pub fn apply<T: Serialize>(fieldset: Option<&Self>, items: &[T]) -> Vec<Value> {
+        items
+            .iter()
+            .map(|item| {
+                let value = serde_json::to_value(item).unwrap_or_default();
+                match fieldset {
+                    Some(fieldset) => fieldset.select(value),
+                    None => value,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Keep only the listed top-level fields of a resource, ignoring fields it does not have;
+/// all fields when none are listed
+pub fn select_fields(resource: Value, fields: Option<&str>) -> Value {
+    let fields: Vec<&str> = split_fields(fields).collect();
+    match resource {
+        Value::Object(object) if !fields.is_empty() => Value::Object(
+            object
+                .into_iter()
+                .filter(|(key, _)| fields.contains(&key.as_str()))
+                .collect::<Map<_, _>>(),
+        ),
+        resource => resource,
+    }
+}
+
+fn split_fields(fields: Option<&str>) -> impl Iterator<Item = &str> {
+    fields
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select_fields() {
+        let post = json!({ "id": "1", "title": "Hello", "html": "<p>Hi</p>" });
+        assert_eq!(
+            select_fields(post.clone(), Some("id,title")),
+            json!({ "id": "1", "title": "Hello" })
+        );
+        assert_eq!(select_fields(post.clone(), None), post);
+    }
+
+    #[test]
+    fn test_fieldset() {
+        let available = ["id", "title", "content"];
+        assert_eq!(Fieldset::parse(None, &available).unwrap(), None);
+        assert_eq!(Fieldset::parse(Some(" , "), &available).unwrap(), None);
+        assert!(matches!(
+            Fieldset::parse(Some("id,body"), &available),
+            Err(FieldsError::UnknownField { field, .. }) if field == "body"
+        ));
+
+        let fieldset = Fieldset::parse(Some("title, id,id"), &available)
+            .unwrap()
+            .unwrap();
+        let posts = [json!({ "id": 1, "title": "Hello", "content": "Hi" })];
+        assert_eq!(
+            Fieldset::apply(Some(&fieldset), &posts),
+            vec![json!({ "id": 1, "title": "Hello" })]
+        );
+        assert_eq!(Fieldset::apply(None, &posts), posts.to_vec());
+    }
+}
@@ -0,0 +1,24 @@
+//! FNV-1a hashing. Unlike the standard library's hasher it gives the same hash across
+//! processes, builds and Rust versions, so its hashes can be stored and compared later.
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a hash of bytes
+pub fn hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_hashes() {
+        assert_eq!(hash(*b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(*b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash("foo".bytes()), hash(b"foo".iter().copied()));
+    }
+}
@@ -0,0 +1,315 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::cache::redis::RedisCache;
+use crate::db::retry::unavailable_response;
+use crate::db::router::DbRouter;
+use crate::fields::select_fields;
+use crate::ghost::model::{
+    ghost_post, parse_limit, parse_tag_filter, BrowseParams, ContentKeyParams,
+    CreateContentApiKeyRequest, GhostError, GhostPagination, GhostPost, Include, ReadParams,
+};
+use crate::ghost::service::ContentApiKeyService;
+use crate::pagination::Pagination;
+use crate::post::model::PostResponse;
+use crate::post::service::{PostError, PostService};
+use crate::sitemap::site_base_url;
+use crate::tenant::model::Blog;
+use axum::{
+    extract::{Path, Query, State},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::error;
+
+/// Errors of the content API, in Ghost's format so Ghost clients can handle them
+fn ghost_error_response(e: GhostError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let (status, error_type) = match &e {
+        GhostError::MissingKey | GhostError::InvalidKey => {
+            (StatusCode::UNAUTHORIZED, "UnauthorizedError")
+        }
+        GhostError::NotFound | GhostError::KeyNotFound => (StatusCode::NOT_FOUND, "NotFoundError"),
+        GhostError::BadRequest(_) => (StatusCode::BAD_REQUEST, "BadRequestError"),
+        GhostError::DatabaseError(_) | GhostError::InternalError(_) => {
+            error!("Content API error: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "InternalServerError")
+        }
+    };
+
+    (
+        status,
+        Json(json!({ "errors": [{ "message": e.to_string(), "type": error_type }] })),
+    )
+        .into_response()
+}
+
+fn key_error_response(e: GhostError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Content API key error: {:?}", e);
+    let status = match e {
+        GhostError::BadRequest(_) => StatusCode::BAD_REQUEST,
+        GhostError::KeyNotFound | GhostError::NotFound => StatusCode::NOT_FOUND,
+        GhostError::MissingKey | GhostError::InvalidKey => StatusCode::UNAUTHORIZED,
+        GhostError::DatabaseError(_) | GhostError::InternalError(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can manage Content API keys" })),
+    )
+        .into_response()
+}
+
+fn post_error(e: PostError) -> GhostError {
+    match e {
+        PostError::NotFound => GhostError::NotFound,
+        e => GhostError::InternalError(e.to_string()),
+    }
+}
+
+/// Middleware that only lets requests with a Content API key of the blog through
+pub async fn require_content_api_key<B>(
+    State((db, _)): State<(DbRouter, Option<RedisCache>)>,
+    Query(params): Query<ContentKeyParams>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let key = match params.key.as_deref().map(str::trim) {
+        Some(key) if !key.is_empty() => key.to_string(),
+        _ => return ghost_error_response(GhostError::MissingKey),
+    };
+
+    match ContentApiKeyService::new(db.primary().clone())
+        .verify_key(&key)
+        .await
+    {
+        Ok(()) => next.run(req).await,
+        Err(e) => ghost_error_response(e),
+    }
+}
+
+fn posts_body(posts: Vec<GhostPost>, fields: Option<&str>) -> Vec<Value> {
+    posts
+        .into_iter()
+        .map(|post| select_fields(serde_json::to_value(post).unwrap_or_default(), fields))
+        .collect()
+}
+
+/// Browse published posts, newest first (`GET /ghost/api/content/posts/`)
+pub async fn browse_posts(
+    Query(params): Query<BrowseParams>,
+    Extension(blog): Extension<Blog>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let limit = match parse_limit(params.limit.as_deref()) {
+        Ok(limit) => limit,
+        Err(e) => return ghost_error_response(e),
+    };
+    let tag = match parse_tag_filter(params.filter.as_deref()) {
+        Ok(tag) => tag,
+        Err(e) => return ghost_error_response(e),
+    };
+    let page = params.page.unwrap_or(1).max(1);
+    let pagination = Pagination {
+        limit,
+        offset: (page - 1) * limit,
+    };
+
+    let service = PostService::new(db, redis_cache);
+    let result = async {
+        let viewer = service.viewer(None).await?;
+        service
+            .get_published_posts(&pagination, tag.as_deref(), &[], &viewer)
+            .await
+    }
+    .await;
+
+    match result {
+        Ok((posts, total)) => {
+            let base_url = site_base_url(&blog);
+            let include = Include::parse(params.include.as_deref());
+            let posts = posts
+                .into_iter()
+                .map(|post| ghost_post(post, &base_url, include))
+                .collect();
+            Json(json!({
+                "posts": posts_body(posts, params.fields.as_deref()),
+                "meta": { "pagination": GhostPagination::new(page, limit, total) },
+            }))
+            .into_response()
+        }
+        Err(e) => ghost_error_response(post_error(e)),
+    }
+}
+
+fn read_response(
+    result: Result<PostResponse, PostError>,
+    blog: &Blog,
+    params: &ReadParams,
+) -> Response {
+    match result {
+        // Drafts are not part of the public content
+        Ok(post) if post.is_draft => ghost_error_response(GhostError::NotFound),
+        Ok(post) => {
+            let post = ghost_post(
+                post,
+                &site_base_url(blog),
+                Include::parse(params.include.as_deref()),
+            );
+            Json(json!({ "posts": posts_body(vec![post], params.fields.as_deref()) }))
+                .into_response()
+        }
+        Err(e) => ghost_error_response(post_error(e)),
+    }
+}
+
+/// Read a published post by ID (`GET /ghost/api/content/posts/{id}/`)
+pub async fn read_post(
+    Path(id): Path<String>,
+    Query(params): Query<ReadParams>,
+    Extension(blog): Extension<Blog>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let id = match id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return ghost_error_response(GhostError::NotFound),
+    };
+
+    let service = PostService::new(db, redis_cache);
+    let result = async {
+        let viewer = service.viewer(None).await?;
+        service.get_post_by_id(id, &viewer).await
+    }
+    .await;
+
+    read_response(result, &blog, &params)
+}
+
+/// Read a published post by slug (`GET /ghost/api/content/posts/slug/{slug}/`)
+pub async fn read_post_by_slug(
+    Path(slug): Path<String>,
+    Query(params): Query<ReadParams>,
+    Extension(blog): Extension<Blog>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = PostService::new(db, redis_cache);
+    let result = async {
+        let viewer = service.viewer(None).await?;
+        service.get_post_by_slug(&slug, &viewer).await
+    }
+    .await;
+
+    read_response(result, &blog, &params)
+}
+
+/// Create a Content API key (admin only)
+///
+/// The key gives read access to the published posts of the blog through the Ghost-compatible
+/// content API under `/ghost/api/content`.
+#[utoipa::path(
+    post,
+    path = "/api/admin/content-api-keys",
+    tag = "ghost",
+    request_body = CreateContentApiKeyRequest,
+    responses(
+        (status = 201, description = "Key created", body = ContentApiKey),
+        (status = 400, description = "Missing name"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_key(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ContentApiKeyService>>,
+    Json(request): Json<CreateContentApiKeyRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.create_key(&request.name).await {
+        Ok(key) => (StatusCode::CREATED, Json(key)).into_response(),
+        Err(e) => key_error_response(e),
+    }
+}
+
+/// List the Content API keys of the blog (admin only)
+#[utoipa::path(
+    get,
+    path = "/api/admin/content-api-keys",
+    tag = "ghost",
+    responses(
+        (status = 200, description = "Keys of the blog", body = Vec<ContentApiKey>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_keys(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ContentApiKeyService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.list_keys().await {
+        Ok(keys) => (StatusCode::OK, Json(keys)).into_response(),
+        Err(e) => key_error_response(e),
+    }
+}
+
+/// Revoke a Content API key (admin only)
+#[utoipa::path(
+    delete,
+    path = "/api/admin/content-api-keys/{id}",
+    tag = "ghost",
+    params(
+        ("id" = i64, Path, description = "Key ID")
+    ),
+    responses(
+        (status = 204, description = "Key revoked"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Key not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_key(
+    Path(id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ContentApiKeyService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.delete_key(id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => key_error_response(e),
+    }
+}
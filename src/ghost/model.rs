@@ -0,0 +1,319 @@
+use crate::import::parser::slugify;
+use crate::pagination::MAX_PAGE_SIZE;
+use crate::post::model::PostResponse;
+use crate::post::service::meta_description;
+use crate::sitemap::encode_path_segment;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+/// Page size of list endpoints when `limit` is not given, as in Ghost
+pub const GHOST_DEFAULT_LIMIT: i64 = 15;
+/// Reading speed Ghost bases `reading_time` on
+const WORDS_PER_MINUTE: usize = 275;
+
+/// A key giving read access to the Ghost-compatible content API of a blog. Like Ghost's own
+/// Content API keys, these only unlock public content and are safe to embed in a front-end.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ContentApiKey {
+    pub id: i64,
+    /// What the key is used for
+    #[schema(example = "Casper theme")]
+    pub name: String,
+    /// Sent as the `key` query parameter
+    #[schema(example = "22444f78447824223cefc48062")]
+    pub key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateContentApiKeyRequest {
+    #[schema(example = "Casper theme")]
+    pub name: String,
+}
+
+/// The Content API key every request to the content API carries
+#[derive(Debug, Deserialize)]
+pub struct ContentKeyParams {
+    pub key: Option<String>,
+}
+
+/// Query parameters of `GET /ghost/api/content/posts/`
+#[derive(Debug, Default, Deserialize)]
+pub struct BrowseParams {
+    /// Posts per page, or `all` for the largest page allowed
+    pub limit: Option<String>,
+    pub page: Option<i64>,
+    /// Only `tag:{slug}` is supported
+    pub filter: Option<String>,
+    pub include: Option<String>,
+    pub fields: Option<String>,
+}
+
+/// Query parameters of the endpoints reading a single post
+#[derive(Debug, Default, Deserialize)]
+pub struct ReadParams {
+    pub include: Option<String>,
+    pub fields: Option<String>,
+}
+
+/// Relations added to posts with the `include` parameter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Include {
+    pub tags: bool,
+    pub authors: bool,
+}
+
+impl Include {
+    pub fn parse(include: Option<&str>) -> Self {
+        let mut parsed = Self::default();
+        for relation in include.unwrap_or_default().split(',') {
+            match relation.trim() {
+                "tags" => parsed.tags = true,
+                "authors" => parsed.authors = true,
+                _ => {}
+            }
+        }
+        parsed
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GhostTag {
+    pub id: String,
+    pub name: String,
+    pub slug: String,
+    pub visibility: &'static str,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GhostAuthor {
+    pub id: String,
+    pub name: String,
+    pub slug: String,
+    pub profile_image: Option<String>,
+    pub url: String,
+}
+
+/// A post in the shape of Ghost's Content API
+#[derive(Debug, Clone, Serialize)]
+pub struct GhostPost {
+    pub id: String,
+    pub title: String,
+    pub slug: String,
+    pub html: String,
+    pub comment_id: String,
+    pub feature_image: Option<String>,
+    pub featured: bool,
+    /// `public`, or `paid` for posts gated behind a membership tier
+    pub visibility: &'static str,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub published_at: DateTime<Utc>,
+    pub custom_excerpt: Option<String>,
+    pub excerpt: String,
+    pub reading_time: usize,
+    pub url: String,
+    pub canonical_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<GhostTag>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_tag: Option<Option<GhostTag>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authors: Option<Vec<GhostAuthor>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_author: Option<GhostAuthor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GhostPagination {
+    pub page: i64,
+    pub limit: i64,
+    pub pages: i64,
+    pub total: i64,
+    pub next: Option<i64>,
+    pub prev: Option<i64>,
+}
+
+impl GhostPagination {
+    pub fn new(page: i64, limit: i64, total: i64) -> Self {
+        let pages = ((total + limit - 1) / limit).max(1);
+        Self {
+            page,
+            limit,
+            pages,
+            total,
+            next: (page < pages).then_some(page + 1),
+            prev: (page > 1).then_some(page - 1),
+        }
+    }
+}
+
+/// Page size of a `limit` parameter: a number, or `all` for the most allowed
+pub fn parse_limit(limit: Option<&str>) -> Result<i64, GhostError> {
+    match limit.map(str::trim) {
+        None | Some("") => Ok(GHOST_DEFAULT_LIMIT),
+        Some("all") => Ok(MAX_PAGE_SIZE),
+        Some(limit) => limit
+            .parse::<i64>()
+            .map(|limit| limit.clamp(1, MAX_PAGE_SIZE))
+            .map_err(|_| GhostError::BadRequest(format!("Invalid limit: {}", limit))),
+    }
+}
+
+/// Tag slug of a `filter` parameter. Ghost's filter language is much larger; only filtering
+/// by a single tag, which themes use for tag archives, is supported.
+pub fn parse_tag_filter(filter: Option<&str>) -> Result<Option<String>, GhostError> {
+    let filter = match filter.map(str::trim) {
+        None | Some("") => return Ok(None),
+        Some(filter) => filter,
+    };
+
+    let slug = filter
+        .strip_prefix("tag:")
+        .or_else(|| filter.strip_prefix("tags:"))
+        .map(|slug| slug.trim_matches(|c| c == '\'' || c == '"'))
+        .filter(|slug| !slug.is_empty() && !slug.contains(['+', ',', '[']));
+    match slug {
+        Some(slug) => Ok(Some(slug.to_string())),
+        None => Err(GhostError::BadRequest(format!(
+            "Unsupported filter: {}",
+            filter
+        ))),
+    }
+}
+
+fn ghost_tag(name: &str, base_url: &str) -> GhostTag {
+    let slug = slugify(name);
+    GhostTag {
+        id: slug.clone(),
+        name: name.to_string(),
+        slug,
+        visibility: "public",
+        url: format!("{}/tags/{}", base_url, encode_path_segment(name)),
+    }
+}
+
+/// Map a post onto Ghost's post resource. `base_url` is the blog's front-end URL.
+pub fn ghost_post(post: PostResponse, base_url: &str, include: Include) -> GhostPost {
+    let url = format!("{}/posts/{}", base_url, post.slug);
+    let tags: Vec<GhostTag> = post
+        .tags
+        .iter()
+        .map(|name| ghost_tag(name, base_url))
+        .collect();
+    let author = GhostAuthor {
+        id: post.author.id.to_string(),
+        slug: post.author.name.clone(),
+        url: format!(
+            "{}/authors/{}",
+            base_url,
+            encode_path_segment(&post.author.name)
+        ),
+        name: post.author.name,
+        profile_image: post.author.avatar_url,
+    };
+    let reading_time = post.content.split_whitespace().count() / WORDS_PER_MINUTE;
+
+    GhostPost {
+        id: post.id.to_string(),
+        comment_id: post.id.to_string(),
+        title: post.title,
+        slug: post.slug,
+        html: post.content_html,
+        feature_image: post
+            .cover_image
+            .map(|image| image.url)
+            .or(post.cover_image_url),
+        featured: false,
+        visibility: if post.requires_tier.is_some() {
+            "paid"
+        } else {
+            "public"
+        },
+        created_at: post.created_at,
+        updated_at: post.updated_at,
+        published_at: post.created_at,
+        custom_excerpt: None,
+        excerpt: meta_description(&post.content),
+        reading_time,
+        url,
+        canonical_url: post.canonical_url,
+        primary_tag: include.tags.then(|| tags.first().cloned()),
+        tags: include.tags.then_some(tags),
+        primary_author: include.authors.then(|| author.clone()),
+        authors: include.authors.then(|| vec![author]),
+    }
+}
+
+/// Possible errors of the content API and its keys
+#[derive(Debug, thiserror::Error)]
+pub enum GhostError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Authorization failed: missing Content API key")]
+    MissingKey,
+
+    #[error("Unknown Content API Key")]
+    InvalidKey,
+
+    #[error("Resource not found")]
+    NotFound,
+
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("Content API key not found")]
+    KeyNotFound,
+
+    #[error("Internal error: {0}")]
+    InternalError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pagination() {
+        assert_eq!(
+            GhostPagination::new(2, 15, 40),
+            GhostPagination {
+                page: 2,
+                limit: 15,
+                pages: 3,
+                total: 40,
+                next: Some(3),
+                prev: Some(1),
+            }
+        );
+        let empty = GhostPagination::new(1, 15, 0);
+        assert_eq!((empty.pages, empty.next, empty.prev), (1, None, None));
+    }
+
+    #[test]
+    fn test_parse_params() {
+        assert_eq!(parse_limit(None).unwrap(), GHOST_DEFAULT_LIMIT);
+        assert_eq!(parse_limit(Some("all")).unwrap(), MAX_PAGE_SIZE);
+        assert!(parse_limit(Some("ten")).is_err());
+
+        assert_eq!(
+            parse_tag_filter(Some("tag:getting-started")).unwrap(),
+            Some("getting-started".to_string())
+        );
+        assert_eq!(parse_tag_filter(None).unwrap(), None);
+        assert!(parse_tag_filter(Some("featured:true")).is_err());
+        assert!(parse_tag_filter(Some("tag:[a,b]")).is_err());
+
+        assert_eq!(
+            Include::parse(Some("tags, authors")),
+            Include {
+                tags: true,
+                authors: true
+            }
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use crate::ghost::model::{ContentApiKey, GhostError};
+use crate::hex;
+use crate::tenant::middleware::current_blog_id;
+use sqlx::PgPool;
+use tracing::info;
+
+/// Random bytes in a Content API key; 26 hex characters, the length Ghost uses
+const CONTENT_API_KEY_BYTES: usize = 13;
+
+/// Manages the Content API keys of the current blog
+#[derive(Debug, Clone)]
+pub struct ContentApiKeyService {
+    pool: PgPool,
+}
+
+impl ContentApiKeyService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_key(&self, name: &str) -> Result<ContentApiKey, GhostError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(GhostError::BadRequest("Name must not be empty".to_string()));
+        }
+
+        let key = hex::encode(rand::random::<[u8; CONTENT_API_KEY_BYTES]>());
+
+        let key = sqlx::query_as::<_, ContentApiKey>(
+            r#"
+            INSERT INTO global.content_api_keys (blog_id, name, key)
+            VALUES ($1, $2, $3)
+            RETURNING id, name, key, created_at
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(name)
+        .bind(&key)
+        .fetch_one(&self.pool)
+        .await?;
+
+        info!("Created Content API key {} ({})", key.id, key.name);
+        Ok(key)
+    }
+
+    pub async fn list_keys(&self) -> Result<Vec<ContentApiKey>, GhostError> {
+        let keys = sqlx::query_as::<_, ContentApiKey>(
+            r#"
+            SELECT id, name, key, created_at FROM global.content_api_keys
+            WHERE blog_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(current_blog_id())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    pub async fn delete_key(&self, id: i64) -> Result<(), GhostError> {
+        let result =
+            sqlx::query("DELETE FROM global.content_api_keys WHERE id = $1 AND blog_id = $2")
+                .bind(id)
+                .bind(current_blog_id())
+                .execute(&self.pool)
+                .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(GhostError::KeyNotFound);
+        }
+
+        info!("Deleted Content API key {}", id);
+        Ok(())
+    }
+
+    /// Check that a key belongs to the current blog
+    pub async fn verify_key(&self, key: &str) -> Result<(), GhostError> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM global.content_api_keys WHERE key = $1 AND blog_id = $2)",
+        )
+        .bind(key)
+        .bind(current_blog_id())
+        .fetch_one(&self.pool)
+        .await?;
+
+        if exists {
+            Ok(())
+        } else {
+            Err(GhostError::InvalidKey)
+        }
+    }
+}
@@ -0,0 +1,405 @@
+//! Tells admins when the services the server depends on degrade and when they recover.
+//!
+//! Every `HEALTH_MONITOR_INTERVAL_SECONDS` the monitor runs the readiness checks, reads the
+//! circuit breakers and looks for notifications stuck in the outbox. A component that fails
+//! `HEALTH_MONITOR_CONFIRMATIONS` checks in a row is reported as degraded, and reported again
+//! once it passes as many checks in a row, so one slow check does not alert anyone. Reports
+//! go to every admin as a `SystemMessage` notification and, when `HEALTH_WEBHOOK_URL` is set,
+//! are posted there as JSON, which still arrives while the database is down. Each instance
+//! runs its own monitor and reports what it sees.
+
+use crate::cache::redis::RedisCache;
+use crate::notification::model::{NotificationPayload, NotificationType};
+use crate::notification::service::{NotificationService, OUTBOX_MAX_ATTEMPTS};
+use crate::routes::health::check_readiness;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_CONFIRMATIONS: u32 = 2;
+const DEFAULT_OUTBOX_LAG: Duration = Duration::from_secs(120);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the health monitor checks, and where it reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthMonitorConfig {
+    pub interval: Duration,
+    /// Checks in a row a component must fail, or pass again, before the change is reported
+    pub confirmations: u32,
+    /// Notifications waiting in the outbox longer than this mean delivery is falling behind
+    pub outbox_lag: Duration,
+    pub webhook_url: Option<String>,
+}
+
+impl Default for HealthMonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            confirmations: DEFAULT_CONFIRMATIONS,
+            outbox_lag: DEFAULT_OUTBOX_LAG,
+            webhook_url: None,
+        }
+    }
+}
+
+impl HealthMonitorConfig {
+    /// Read the configuration, keeping the default of anything unset or invalid:
+    ///
+    /// - `HEALTH_MONITOR_INTERVAL_SECONDS` (30)
+    /// - `HEALTH_MONITOR_CONFIRMATIONS` (2)
+    /// - `HEALTH_OUTBOX_LAG_SECONDS` (120)
+    /// - `HEALTH_WEBHOOK_URL` (none)
+    pub fn from_env() -> Self {
+        let number = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .filter(|value| *value > 0)
+        };
+        let defaults = Self::default();
+
+        Self {
+            interval: number("HEALTH_MONITOR_INTERVAL_SECONDS")
+                .map_or(defaults.interval, Duration::from_secs),
+            confirmations: number("HEALTH_MONITOR_CONFIRMATIONS")
+                .map_or(defaults.confirmations, |confirmations| confirmations as u32),
+            outbox_lag: number("HEALTH_OUTBOX_LAG_SECONDS")
+                .map_or(defaults.outbox_lag, Duration::from_secs),
+            webhook_url: std::env::var("HEALTH_WEBHOOK_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty()),
+        }
+    }
+}
+
+/// A confirmed change in the health of a component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthTransition {
+    pub component: String,
+    /// Whether the component recovered, rather than degraded
+    pub healthy: bool,
+    /// What the last failed check reported
+    pub detail: String,
+    /// When the component started failing
+    pub since: DateTime<Utc>,
+    pub at: DateTime<Utc>,
+}
+
+impl HealthTransition {
+    /// Text of the notification sent to admins
+    pub fn message(&self) -> String {
+        if !self.healthy {
+            return format!("{} is degraded: {}", self.component, self.detail);
+        }
+        let seconds = (self.at - self.since).num_seconds().max(0);
+        let outage = if seconds < 120 {
+            format!("{} seconds", seconds)
+        } else {
+            format!("{} minutes", seconds / 60)
+        };
+        format!(
+            "{} has recovered after {} ({})",
+            self.component, outage, self.detail
+        )
+    }
+}
+
+#[derive(Debug)]
+struct ComponentHealth {
+    /// Health as last reported; components start out healthy
+    healthy: bool,
+    /// Checks in a row that disagree with `healthy`, and when the first of them ran
+    streak: u32,
+    streak_started: DateTime<Utc>,
+    down_since: DateTime<Utc>,
+    detail: String,
+}
+
+/// Turns the results of repeated checks into confirmed transitions
+#[derive(Debug)]
+pub struct HealthTracker {
+    confirmations: u32,
+    components: HashMap<String, ComponentHealth>,
+}
+
+impl HealthTracker {
+    pub fn new(confirmations: u32) -> Self {
+        Self {
+            confirmations: confirmations.max(1),
+            components: HashMap::new(),
+        }
+    }
+
+    /// Record a check of a component, `Err` with what went wrong when it failed. Returns the
+    /// transition once enough checks in a row confirm it.
+    pub fn observe(
+        &mut self,
+        component: &str,
+        result: Result<(), String>,
+        now: DateTime<Utc>,
+    ) -> Option<HealthTransition> {
+        let state = self
+            .components
+            .entry(component.to_string())
+            .or_insert_with(|| ComponentHealth {
+                healthy: true,
+                streak: 0,
+                streak_started: now,
+                down_since: now,
+                detail: String::new(),
+            });
+        let healthy = result.is_ok();
+        if let Err(detail) = result {
+            state.detail = detail;
+        }
+
+        if healthy == state.healthy {
+            state.streak = 0;
+            return None;
+        }
+        if state.streak == 0 {
+            state.streak_started = now;
+        }
+        state.streak += 1;
+        if state.streak < self.confirmations {
+            return None;
+        }
+
+        state.streak = 0;
+        state.healthy = healthy;
+        if !healthy {
+            state.down_since = state.streak_started;
+        }
+        Some(HealthTransition {
+            component: component.to_string(),
+            healthy,
+            detail: state.detail.clone(),
+            since: state.down_since,
+            at: now,
+        })
+    }
+}
+
+pub struct HealthMonitor {
+    pool: PgPool,
+    redis_cache: Option<Arc<RedisCache>>,
+    notifications: Arc<NotificationService>,
+    config: HealthMonitorConfig,
+    client: reqwest::Client,
+}
+
+impl HealthMonitor {
+    pub fn new(
+        pool: PgPool,
+        redis_cache: Option<Arc<RedisCache>>,
+        notifications: Arc<NotificationService>,
+        config: HealthMonitorConfig,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            pool,
+            redis_cache,
+            notifications,
+            config,
+            client,
+        }
+    }
+
+    // Check every component once
+    async fn check(&self) -> Vec<(String, Result<(), String>)> {
+        let readiness = check_readiness(&self.pool, self.redis_cache.as_deref()).await;
+        let database_ok = readiness.database == "ok";
+        let mut results = vec![(
+            "Database".to_string(),
+            if database_ok {
+                Ok(())
+            } else {
+                Err("Postgres cannot be reached".to_string())
+            },
+        )];
+
+        if readiness.redis != "disabled" {
+            let result = match readiness.breakers.get("redis").map(String::as_str) {
+                _ if readiness.redis == "ok" => Ok(()),
+                Some("open") => {
+                    Err("Redis cannot be reached and its circuit breaker is open".to_string())
+                }
+                _ => Err("Redis cannot be reached".to_string()),
+            };
+            results.push(("Redis".to_string(), result));
+        }
+        // Other breakers guard services without a check of their own
+        for (name, state) in &readiness.breakers {
+            if name != "redis" {
+                let result = if state == "open" {
+                    Err(format!("The {} circuit breaker is open", name))
+                } else {
+                    Ok(())
+                };
+                results.push((format!("The {} circuit breaker", name), result));
+            }
+        }
+
+        // The outbox is in the database, so its backlog is only known while that is up
+        if database_ok {
+            match self.outbox_backlog().await {
+                Ok(0) => results.push(("Notification delivery".to_string(), Ok(()))),
+                Ok(waiting) => results.push((
+                    "Notification delivery".to_string(),
+                    Err(format!(
+                        "{} notifications have waited over {} seconds",
+                        waiting,
+                        self.config.outbox_lag.as_secs()
+                    )),
+                )),
+                Err(e) => warn!("Failed to check the notification outbox: {}", e),
+            }
+        }
+
+        results
+    }
+
+    // Notifications still to be delivered that have waited longer than the allowed lag
+    async fn outbox_backlog(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM global.notification_outbox
+            WHERE delivered_at IS NULL AND attempts < $1
+                AND created_at < NOW() - make_interval(secs => $2)
+            "#,
+        )
+        .bind(OUTBOX_MAX_ATTEMPTS)
+        .bind(self.config.outbox_lag.as_secs_f64())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    // Send a transition to the webhook and to every admin. Either may fail while the
+    // components they rely on are down, which is only logged.
+    async fn report(&self, transition: &HealthTransition) {
+        let message = transition.message();
+        if transition.healthy {
+            info!("{}", message);
+        } else {
+            warn!("{}", message);
+        }
+
+        if let Some(url) = &self.config.webhook_url {
+            let body = json!({
+                "component": transition.component,
+                "status": if transition.healthy { "recovered" } else { "degraded" },
+                "message": message,
+                "detail": transition.detail,
+                "since": transition.since,
+                "at": transition.at,
+            });
+            match self.client.post(url).json(&body).send().await {
+                Ok(response) if !response.status().is_success() => error!(
+                    "Health webhook refused the report with status {}",
+                    response.status()
+                ),
+                Ok(_) => {}
+                Err(e) => error!("Failed to send the health report to the webhook: {}", e),
+            }
+        }
+
+        let admin_ids: Vec<Uuid> =
+            match sqlx::query_scalar("SELECT id FROM global.users WHERE role = 'admin'")
+                .fetch_all(&self.pool)
+                .await
+            {
+                Ok(admin_ids) => admin_ids,
+                Err(e) => {
+                    error!("Failed to find the admins to notify of {}: {}", message, e);
+                    return;
+                }
+            };
+        for admin_id in admin_ids {
+            let payload = NotificationPayload {
+                recipient_id: admin_id,
+                notification_type: NotificationType::SystemMessage,
+                object_id: 0,
+                related_object_id: None,
+                actor_id: Uuid::nil(),
+                content: message.clone(),
+                context: Default::default(),
+            };
+            if let Err(e) = self.notifications.deliver(payload).await {
+                error!("Failed to notify admin {} of {}: {}", admin_id, message, e);
+            }
+        }
+    }
+
+    /// Check the components periodically, reporting each confirmed change
+    pub fn start(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut tracker = HealthTracker::new(self.config.confirmations);
+            let mut interval = tokio::time::interval(self.config.interval);
+            loop {
+                interval.tick().await;
+                let now = Utc::now();
+                for (component, result) in self.check().await {
+                    if let Some(transition) = tracker.observe(&component, result, now) {
+                        self.report(&transition).await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn test_tracker_confirms_transitions() {
+        let mut tracker = HealthTracker::new(2);
+        let down = || Err("Postgres cannot be reached".to_string());
+
+        assert_eq!(tracker.observe("Database", Ok(()), at(0)), None);
+        // One failed check is not enough, and a pass in between starts over
+        assert_eq!(tracker.observe("Database", down(), at(30)), None);
+        assert_eq!(tracker.observe("Database", Ok(()), at(60)), None);
+        assert_eq!(tracker.observe("Database", down(), at(90)), None);
+
+        let degraded = tracker.observe("Database", down(), at(120)).unwrap();
+        assert!(!degraded.healthy);
+        assert_eq!(degraded.since, at(90));
+        assert_eq!(
+            degraded.message(),
+            "Database is degraded: Postgres cannot be reached"
+        );
+        assert_eq!(tracker.observe("Database", down(), at(150)), None);
+
+        assert_eq!(tracker.observe("Database", Ok(()), at(600)), None);
+        let recovered = tracker.observe("Database", Ok(()), at(630)).unwrap();
+        assert!(recovered.healthy);
+        assert_eq!(recovered.since, at(90));
+        assert_eq!(
+            recovered.message(),
+            "Database has recovered after 9 minutes (Postgres cannot be reached)"
+        );
+    }
+
+    #[test]
+    fn test_tracker_keeps_components_apart() {
+        let mut tracker = HealthTracker::new(1);
+        let redis = tracker.observe("Redis", Err("Redis cannot be reached".to_string()), at(0));
+        assert!(redis.is_some_and(|transition| !transition.healthy));
+        assert_eq!(tracker.observe("Database", Ok(()), at(0)), None);
+    }
+}
@@ -0,0 +1,42 @@
+//! Lowercase hex encoding of bytes, as used for random tokens and keys and for webhook
+//! signatures.
+
+use std::fmt::Write;
+
+/// Bytes as lowercase hex, two characters per byte
+pub fn encode(bytes: impl AsRef<[u8]>) -> String {
+    let bytes = bytes.as_ref();
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+/// Bytes of a hex string in either case; `None` unless it is whole bytes of hex digits
+pub fn decode(hex: &str) -> Option<Vec<u8>> {
+    // from_str_radix would also take a sign
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(encode([0x00, 0x0f, 0xa0, 0xff]), "000fa0ff");
+        assert_eq!(encode([]), "");
+        assert_eq!(decode("000fA0ff"), Some(vec![0x00, 0x0f, 0xa0, 0xff]));
+
+        assert_eq!(decode("abc"), None);
+        assert_eq!(decode("zz"), None);
+        assert_eq!(decode("+f"), None);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::import::model::{ImportError, ImportParams};
+use crate::import::service::ImportService;
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn import_error_response(e: ImportError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Import error: {:?}", e);
+    let status = match e {
+        ImportError::UnsupportedFormat | ImportError::InvalidFile(_) => StatusCode::BAD_REQUEST,
+        ImportError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Import posts (admin only)
+///
+/// The file is the raw request body: a zip of markdown files with front matter (`title`,
+/// `slug`, `tags`, `date`, `author`, `draft`) or a WordPress export (WXR). Posts whose slug
+/// already exists are skipped, so an import can safely be run again. With `dry_run=true`
+/// nothing is created and the report says what would be.
+#[utoipa::path(
+    post,
+    path = "/api/admin/import",
+    tag = "import",
+    params(ImportParams),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Per-item import report", body = ImportReport),
+        (status = 400, description = "Unsupported or invalid import file"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 413, description = "File is too large"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn import_posts(
+    Extension(user): Extension<AuthUser>,
+    Query(params): Query<ImportParams>,
+    State(service): State<Arc<ImportService>>,
+    body: Bytes,
+) -> Response {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Only admins can import posts" })),
+        )
+            .into_response();
+    }
+
+    match service.import(&body, params.dry_run, user.user_id).await {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(e) => import_error_response(e),
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Largest import file accepted
+pub const MAX_IMPORT_BYTES: usize = 50 * 1024 * 1024;
+/// Largest markdown file read from an import zip, against zip bombs
+pub const MAX_IMPORT_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// Most posts taken from one import
+pub const MAX_IMPORT_ITEMS: usize = 5000;
+
+/// Query parameters of an import; the file itself is the raw request body
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportParams {
+    /// Only report what would be imported, without creating anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A post read from an import file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedPost {
+    pub title: String,
+    pub slug: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    /// Username of the author in the source, if it names one
+    pub author: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub is_draft: bool,
+}
+
+/// An item of an import file: a post, or why it could not be read
+#[derive(Debug)]
+pub struct ParsedItem {
+    /// File name or URL of the item in the source
+    pub source: String,
+    pub post: Result<ImportedPost, String>,
+}
+
+/// What happened to an imported item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportItemStatus {
+    /// The post was created
+    Created,
+    /// The post would be created; dry runs only
+    WouldCreate,
+    /// A post with the same slug already exists, e.g. from an earlier run of the same import
+    Skipped,
+    /// The item could not be imported
+    Failed,
+}
+
+/// Outcome of one imported item
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportItemReport {
+    /// File name or URL of the item in the source
+    #[schema(example = "posts/hello-world.md")]
+    pub source: String,
+    pub status: ImportItemStatus,
+    pub title: Option<String>,
+    pub slug: Option<String>,
+    /// The created post, or the existing one a skipped item matched
+    pub post_id: Option<i64>,
+    /// Why the item failed or was skipped, or how its author was mapped
+    pub message: Option<String>,
+}
+
+/// Per-item report of an import
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub created: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub items: Vec<ImportItemReport>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Unsupported import file; expected a zip of markdown files or a WordPress export")]
+    UnsupportedFormat,
+
+    #[error("Invalid import file: {0}")]
+    InvalidFile(String),
+}
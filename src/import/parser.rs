@@ -0,0 +1,489 @@
+use crate::import::model::{
+    ImportError, ImportedPost, ParsedItem, MAX_IMPORT_FILE_BYTES, MAX_IMPORT_ITEMS,
+};
+use crate::slug::match_key;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+/// Slug of a title: lowercase ASCII letters and digits separated by single hyphens, with
+/// accents dropped (`Café` becomes `cafe`)
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in match_key(title).chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Value of a front matter scalar without its quotes
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        serde_json::from_str(value).unwrap_or_else(|_| value[1..value.len() - 1].to_string())
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        value[1..value.len() - 1].replace("''", "'")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Items of a flow list such as `[rust, "web dev"]`
+fn flow_list(value: &str) -> Vec<String> {
+    let inner = value.trim().trim_start_matches('[').trim_end_matches(']');
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+
+    for c in inner.chars() {
+        match quote {
+            Some(q) => {
+                current.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == ',' => items.push(std::mem::take(&mut current)),
+            None => {
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                }
+                current.push(c);
+            }
+        }
+    }
+    items.push(current);
+
+    items
+        .iter()
+        .map(|item| unquote(item))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// The front matter of a markdown file as key/value lists, and the body after it. Covers the
+/// YAML subset static site generators write: scalars, flow lists and block lists.
+pub fn split_front_matter(text: &str) -> (HashMap<String, Vec<String>>, &str) {
+    let mut fields = HashMap::new();
+    let text = text.trim_start_matches('\u{feff}');
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (fields, text);
+    };
+    let (header, body) = match rest.find("\n---") {
+        Some(end) => {
+            let body = &rest[end + 4..];
+            let body = body.split_once('\n').map_or("", |(_, body)| body);
+            (&rest[..end], body)
+        }
+        None => return (fields, text),
+    };
+
+    let mut list_key: Option<String> = None;
+    for line in header.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let (Some(key), Some(item)) = (&list_key, trimmed.strip_prefix("- ")) {
+            fields
+                .entry(key.clone())
+                .or_insert_with(Vec::new)
+                .push(unquote(item));
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        if value.is_empty() {
+            list_key = Some(key.clone());
+            fields.insert(key, Vec::new());
+        } else if value.starts_with('[') {
+            list_key = None;
+            fields.insert(key, flow_list(value));
+        } else {
+            list_key = None;
+            fields.insert(key, vec![unquote(value)]);
+        }
+    }
+
+    (fields, body)
+}
+
+/// Parse a date as written by common exporters: RFC 3339, `2024-05-01 12:30:00` or `2024-05-01`
+pub fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Some(date.and_utc());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc())
+}
+
+/// Read a markdown file with front matter. The title falls back to the first heading and then
+/// the file name, and the slug to the slug of the title.
+pub fn parse_markdown(file_name: &str, text: &str) -> Result<ImportedPost, String> {
+    let (fields, body) = split_front_matter(text);
+    let first = |key: &str| {
+        fields
+            .get(key)
+            .and_then(|values| values.first())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let stem = file_name
+        .rsplit('/')
+        .next()
+        .unwrap_or(file_name)
+        .trim_end_matches(".markdown")
+        .trim_end_matches(".md");
+    let title = first("title")
+        .or_else(|| {
+            body.lines()
+                .find_map(|line| line.strip_prefix("# "))
+                .map(|heading| heading.trim().to_string())
+        })
+        .unwrap_or_else(|| stem.to_string());
+    let slug = first("slug")
+        .map(|slug| slugify(&slug))
+        .unwrap_or_else(|| slugify(&title));
+    if slug.is_empty() {
+        return Err("No title or slug to name the post by".to_string());
+    }
+
+    let mut tags: Vec<String> = Vec::new();
+    for key in ["tags", "categories"] {
+        for tag in fields.get(key).into_iter().flatten() {
+            // A scalar value lists its tags separated by commas
+            for tag in tag.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+                if !tags.iter().any(|existing| existing == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+        }
+    }
+
+    let created_at = match first("date") {
+        Some(date) => Some(parse_date(&date).ok_or_else(|| format!("Invalid date {}", date))?),
+        None => None,
+    };
+
+    Ok(ImportedPost {
+        title,
+        slug,
+        content: body.trim().to_string(),
+        tags,
+        author: first("author"),
+        created_at,
+        is_draft: first("draft").is_some_and(|draft| draft == "true"),
+    })
+}
+
+/// Read every markdown file in a zip
+pub fn parse_markdown_zip(data: &[u8]) -> Result<Vec<ParsedItem>, ImportError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .map_err(|e| ImportError::InvalidFile(e.to_string()))?;
+    let mut items = Vec::new();
+
+    for index in 0..archive.len() {
+        let file = archive
+            .by_index(index)
+            .map_err(|e| ImportError::InvalidFile(e.to_string()))?;
+        let name = file.name().to_string();
+        let lower = name.to_ascii_lowercase();
+        if file.is_dir()
+            || name.starts_with("__MACOSX/")
+            || !(lower.ends_with(".md") || lower.ends_with(".markdown"))
+        {
+            continue;
+        }
+        if items.len() == MAX_IMPORT_ITEMS {
+            return Err(ImportError::InvalidFile(format!(
+                "More than {} posts in one import",
+                MAX_IMPORT_ITEMS
+            )));
+        }
+
+        let mut text = String::new();
+        let post = match file
+            .take(MAX_IMPORT_FILE_BYTES + 1)
+            .read_to_string(&mut text)
+        {
+            Ok(read) if read as u64 > MAX_IMPORT_FILE_BYTES => {
+                Err(format!("Larger than {} bytes", MAX_IMPORT_FILE_BYTES))
+            }
+            Ok(_) => parse_markdown(&name, &text),
+            Err(e) => Err(format!("Unreadable file: {}", e)),
+        };
+        items.push(ParsedItem { source: name, post });
+    }
+
+    Ok(items)
+}
+
+/// Fields of one `<item>` of a WordPress export
+#[derive(Debug, Default)]
+struct WxrItem {
+    fields: HashMap<String, String>,
+    /// `(domain, name)` of each `<category>`, which holds both categories and tags
+    categories: Vec<(String, String)>,
+}
+
+impl WxrItem {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// The post in the item; `None` for pages, attachments and trashed posts
+    fn into_parsed(self) -> Option<ParsedItem> {
+        if self.field("wp:post_type").unwrap_or("post") != "post" {
+            return None;
+        }
+        let status = self.field("wp:status").unwrap_or("publish");
+        if status == "trash" {
+            return None;
+        }
+
+        let title = self.field("title").unwrap_or_default().to_string();
+        let source = self
+            .field("link")
+            .or_else(|| self.field("guid"))
+            .unwrap_or(&title)
+            .to_string();
+        let slug = self
+            .field("wp:post_name")
+            .map(slugify)
+            .unwrap_or_else(|| slugify(&title));
+        if slug.is_empty() {
+            return Some(ParsedItem {
+                source,
+                post: Err("No title or slug to name the post by".to_string()),
+            });
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        for (domain, name) in &self.categories {
+            let name = name.trim();
+            if (domain == "post_tag" || domain == "category")
+                && !name.is_empty()
+                && name != "Uncategorized"
+                && !tags.iter().any(|tag| tag == name)
+            {
+                tags.push(name.to_string());
+            }
+        }
+
+        // Unpublished posts have a zero GMT date
+        let created_at = self
+            .field("wp:post_date_gmt")
+            .and_then(parse_date)
+            .or_else(|| self.field("wp:post_date").and_then(parse_date));
+
+        Some(ParsedItem {
+            source,
+            post: Ok(ImportedPost {
+                title: if title.is_empty() {
+                    slug.clone()
+                } else {
+                    title
+                },
+                slug,
+                content: self
+                    .field("content:encoded")
+                    .unwrap_or_default()
+                    .to_string(),
+                tags,
+                author: self.field("dc:creator").map(str::to_string),
+                created_at,
+                is_draft: status != "publish",
+            }),
+        })
+    }
+}
+
+/// Read the posts of a WordPress export (WXR) file
+pub fn parse_wxr(data: &[u8]) -> Result<Vec<ParsedItem>, ImportError> {
+    let mut reader = Reader::from_reader(data);
+    let mut buf = Vec::new();
+    let mut items = Vec::new();
+    let mut item: Option<WxrItem> = None;
+    // Element whose text is being read, with the domain of a category
+    let mut current: Option<(String, String)> = None;
+    let mut text = String::new();
+
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(|e| {
+            ImportError::InvalidFile(format!(
+                "Invalid XML at byte {}: {}",
+                reader.buffer_position(),
+                e
+            ))
+        })?;
+        match event {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+                if name == "item" {
+                    item = Some(WxrItem::default());
+                } else if item.is_some() {
+                    let domain = match element.try_get_attribute("domain") {
+                        Ok(Some(domain)) => domain
+                            .unescape_value()
+                            .map(|value| value.into_owned())
+                            .unwrap_or_default(),
+                        _ => String::new(),
+                    };
+                    current = Some((name, domain));
+                    text.clear();
+                }
+            }
+            Event::Text(content) if current.is_some() => {
+                let content = content
+                    .unescape()
+                    .map_err(|e| ImportError::InvalidFile(e.to_string()))?;
+                text.push_str(&content);
+            }
+            Event::CData(content) if current.is_some() => {
+                text.push_str(&String::from_utf8_lossy(&content.into_inner()));
+            }
+            Event::End(element) => {
+                let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+                if name == "item" {
+                    if let Some(parsed) = item.take().and_then(WxrItem::into_parsed) {
+                        if items.len() == MAX_IMPORT_ITEMS {
+                            return Err(ImportError::InvalidFile(format!(
+                                "More than {} posts in one import",
+                                MAX_IMPORT_ITEMS
+                            )));
+                        }
+                        items.push(parsed);
+                    }
+                } else if let (Some(item), Some((current_name, domain))) =
+                    (item.as_mut(), current.take())
+                {
+                    if current_name == name {
+                        let value = std::mem::take(&mut text);
+                        if name == "category" {
+                            item.categories.push((domain, value));
+                        } else {
+                            // Comments come after the post's own fields and may reuse names
+                            item.fields.entry(name).or_insert(value);
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(items)
+}
+
+/// Read an import file, telling a zip of markdown files from a WordPress export by its content
+pub fn parse_import(data: &[u8]) -> Result<Vec<ParsedItem>, ImportError> {
+    if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        return parse_markdown_zip(data);
+    }
+
+    let start = String::from_utf8_lossy(&data[..data.len().min(1024)]).to_string();
+    if start.contains("<rss") || start.trim_start_matches('\u{feff}').starts_with("<?xml") {
+        parse_wxr(data)
+    } else {
+        Err(ImportError::UnsupportedFormat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_markdown_front_matter() {
+        let text = "---\ntitle: \"Say \\\"hello\\\": a guide\"\nslug: say-hello\n\
+                    tags: [rust, \"web dev\"]\ncategories:\n  - notes\ndate: 2024-05-01T12:30:00Z\n\
+                    author: alice\n---\n\n# Hello\n\nWorld\n";
+        let post = parse_markdown("posts/whatever.md", text).unwrap();
+
+        assert_eq!(post.title, "Say \"hello\": a guide");
+        assert_eq!(post.slug, "say-hello");
+        assert_eq!(post.tags, vec!["rust", "web dev", "notes"]);
+        assert_eq!(post.author.as_deref(), Some("alice"));
+        assert_eq!(post.created_at, parse_date("2024-05-01 12:30:00"));
+        assert_eq!(post.content, "# Hello\n\nWorld");
+        assert!(!post.is_draft);
+    }
+
+    #[test]
+    fn test_parse_markdown_fallbacks() {
+        let post = parse_markdown("drafts/my-notes.md", "# Notes on Rust!\n\nText").unwrap();
+        assert_eq!(post.title, "Notes on Rust!");
+        assert_eq!(post.slug, "notes-on-rust");
+        assert!(post.created_at.is_none());
+
+        assert!(parse_markdown("x.md", "---\ndate: yesterday\n---\nText").is_err());
+    }
+
+    #[test]
+    fn test_parse_wxr() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:wp="http://wordpress.org/export/1.2/">
+<channel>
+  <item>
+    <title>Hello &amp; welcome</title>
+    <link>https://old.example.com/hello</link>
+    <dc:creator><![CDATA[alice]]></dc:creator>
+    <content:encoded><![CDATA[<p>First post</p>]]></content:encoded>
+    <wp:post_date_gmt>2020-01-02 03:04:05</wp:post_date_gmt>
+    <wp:post_name>hello-welcome</wp:post_name>
+    <wp:status>publish</wp:status>
+    <wp:post_type>post</wp:post_type>
+    <category domain="category" nicename="news"><![CDATA[News]]></category>
+    <category domain="post_tag" nicename="intro"><![CDATA[intro]]></category>
+    <wp:comment><wp:comment_content>Nice</wp:comment_content></wp:comment>
+  </item>
+  <item>
+    <title>About</title>
+    <wp:post_type>page</wp:post_type>
+  </item>
+</channel>
+</rss>"#;
+        let items = parse_import(xml.as_bytes()).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].source, "https://old.example.com/hello");
+
+        let post = items[0].post.as_ref().unwrap();
+        assert_eq!(post.title, "Hello & welcome");
+        assert_eq!(post.slug, "hello-welcome");
+        assert_eq!(post.content, "<p>First post</p>");
+        assert_eq!(post.tags, vec!["News", "intro"]);
+        assert_eq!(post.author.as_deref(), Some("alice"));
+        assert_eq!(post.created_at, parse_date("2020-01-02 03:04:05"));
+    }
+}
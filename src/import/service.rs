@@ -0,0 +1,223 @@
+use crate::cache::redis::RedisCache;
+use crate::import::model::{
+    ImportError, ImportItemReport, ImportItemStatus, ImportReport, ImportedPost,
+};
+use crate::import::parser::parse_import;
+use crate::post::duplicates::check_content;
+use crate::post::service::render_markdown;
+use crate::reputation::service::queue_reputation_update;
+use crate::tag::service::set_post_tags;
+use crate::tenant::middleware::current_blog_id;
+use chrono::Utc;
+use sqlx::{PgPool, Row};
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+use uuid::Uuid;
+
+pub struct ImportService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+}
+
+impl ImportService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self { pool, redis_cache }
+    }
+
+    /// Import the posts of a zip of markdown files or a WordPress export into the current blog.
+    ///
+    /// Items whose slug already exists are skipped, so running the same import twice creates
+    /// nothing new. Authors are matched by username; posts of unknown authors go to the
+    /// importing user. Each item is created in its own transaction, so one failure doesn't
+    /// undo the rest.
+    pub async fn import(
+        &self,
+        data: &[u8],
+        dry_run: bool,
+        importer: Uuid,
+    ) -> Result<ImportReport, ImportError> {
+        let items = parse_import(data)?;
+        let mut authors: HashMap<String, Option<Uuid>> = HashMap::new();
+        let mut seen_slugs = HashSet::new();
+        let mut report = ImportReport {
+            dry_run,
+            created: 0,
+            skipped: 0,
+            failed: 0,
+            items: Vec::with_capacity(items.len()),
+        };
+
+        for item in items {
+            let mut item_report = ImportItemReport {
+                source: item.source,
+                status: ImportItemStatus::Failed,
+                title: None,
+                slug: None,
+                post_id: None,
+                message: None,
+            };
+
+            let post = match item.post {
+                Ok(post) => post,
+                Err(message) => {
+                    item_report.message = Some(message);
+                    report.failed += 1;
+                    report.items.push(item_report);
+                    continue;
+                }
+            };
+            item_report.title = Some(post.title.clone());
+            item_report.slug = Some(post.slug.clone());
+
+            if !seen_slugs.insert(post.slug.clone()) {
+                item_report.status = ImportItemStatus::Skipped;
+                item_report.message = Some("Same slug as an earlier item of this import".into());
+                report.skipped += 1;
+                report.items.push(item_report);
+                continue;
+            }
+
+            // Slugs are unique within a blog, deleted posts included
+            let existing: Option<i64> = sqlx::query(
+                r#"
+                SELECT id FROM global.posts
+                WHERE global.match_key(slug) = global.match_key($1) AND blog_id = $2
+                "#,
+            )
+            .bind(&post.slug)
+            .bind(current_blog_id())
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| row.get(0));
+            if let Some(post_id) = existing {
+                item_report.status = ImportItemStatus::Skipped;
+                item_report.post_id = Some(post_id);
+                item_report.message = Some("A post with this slug already exists".into());
+                report.skipped += 1;
+                report.items.push(item_report);
+                continue;
+            }
+
+            let title_taken: bool = sqlx::query(
+                "SELECT EXISTS(SELECT 1 FROM global.posts WHERE title = $1 AND blog_id = $2 AND is_deleted = false)",
+            )
+            .bind(&post.title)
+            .bind(current_blog_id())
+            .fetch_one(&self.pool)
+            .await?
+            .get(0);
+            if title_taken {
+                item_report.message =
+                    Some("A post with this title already exists under another slug".into());
+                report.failed += 1;
+                report.items.push(item_report);
+                continue;
+            }
+
+            let user_id = match &post.author {
+                Some(username) => {
+                    let author = match authors.get(username) {
+                        Some(author) => *author,
+                        None => {
+                            let author = self.find_author(username).await?;
+                            authors.insert(username.clone(), author);
+                            author
+                        }
+                    };
+                    author.unwrap_or_else(|| {
+                        item_report.message = Some(format!(
+                            "No user named {}; assigned to the importing user",
+                            username
+                        ));
+                        importer
+                    })
+                }
+                None => importer,
+            };
+
+            if dry_run {
+                item_report.status = ImportItemStatus::WouldCreate;
+                report.created += 1;
+                report.items.push(item_report);
+                continue;
+            }
+
+            match self.create_post(&post, user_id).await {
+                Ok(post_id) => {
+                    item_report.status = ImportItemStatus::Created;
+                    item_report.post_id = Some(post_id);
+                    report.created += 1;
+                }
+                Err(e) => {
+                    item_report.message = Some(e.to_string());
+                    report.failed += 1;
+                }
+            }
+            report.items.push(item_report);
+        }
+
+        if !dry_run && report.created > 0 {
+            if let Some(cache) = &self.redis_cache {
+                let _ = cache.invalidate_popular_posts().await;
+                let _ = cache.invalidate_post_archive().await;
+            }
+        }
+
+        info!(
+            "Import{}: {} created, {} skipped, {} failed",
+            if dry_run { " (dry run)" } else { "" },
+            report.created,
+            report.skipped,
+            report.failed
+        );
+
+        Ok(report)
+    }
+
+    async fn find_author(&self, username: &str) -> Result<Option<Uuid>, sqlx::Error> {
+        let row = sqlx::query("SELECT id FROM global.users WHERE username = $1 LIMIT 1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn create_post(&self, post: &ImportedPost, user_id: Uuid) -> Result<i64, sqlx::Error> {
+        let created_at = post.created_at.unwrap_or_else(Utc::now);
+        let mut tx = self.pool.begin().await?;
+
+        let post_id: i64 = sqlx::query(
+            r#"
+            INSERT INTO global.posts (
+                title, slug, content, content_html, user_id, is_draft, created_at, updated_at,
+                blog_id
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $8)
+            RETURNING id
+            "#,
+        )
+        .bind(&post.title)
+        .bind(&post.slug)
+        .bind(&post.content)
+        .bind(render_markdown(&post.content))
+        .bind(user_id)
+        .bind(post.is_draft)
+        .bind(created_at)
+        .bind(current_blog_id())
+        .fetch_one(&mut *tx)
+        .await?
+        .get(0);
+
+        set_post_tags(&mut tx, post_id, &post.tags).await?;
+        check_content(&mut tx, post_id, user_id, &post.content).await?;
+
+        tx.commit().await?;
+
+        if !post.is_draft {
+            queue_reputation_update(&self.pool, &[user_id]).await;
+        }
+
+        Ok(post_id)
+    }
+}
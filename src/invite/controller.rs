@@ -0,0 +1,189 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::invite::model::{CreateInvitesRequest, InviteError, InviteListParams};
+use crate::invite::service::InviteService;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn invite_error_response(e: InviteError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        InviteError::ValidationError(_) => StatusCode::BAD_REQUEST,
+        InviteError::NotFound => StatusCode::NOT_FOUND,
+        InviteError::AlreadyUsed => StatusCode::CONFLICT,
+        InviteError::DatabaseError(_) => {
+            error!("Invite error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn admin_required() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can manage invites" })),
+    )
+        .into_response()
+}
+
+/// Create invite codes (admin only)
+///
+/// Each code lets one user register while the blog's `registration.mode` setting is
+/// `invite-only`, until it expires.
+#[utoipa::path(
+    post,
+    path = "/api/admin/invites",
+    tag = "invites",
+    request_body = CreateInvitesRequest,
+    responses(
+        (status = 201, description = "Invites created", body = [Invite]),
+        (status = 400, description = "Invalid count, expiry or note"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_invites(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<InviteService>>,
+    Json(request): Json<CreateInvitesRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    match service.create_invites(&request, user.user_id).await {
+        Ok(invites) => (StatusCode::CREATED, Json(invites)).into_response(),
+        Err(e) => invite_error_response(e),
+    }
+}
+
+/// List invites (admin only)
+///
+/// Newest first, with who used each.
+#[utoipa::path(
+    get,
+    path = "/api/admin/invites",
+    tag = "invites",
+    params(InviteListParams, PageParams),
+    responses(
+        (status = 200, description = "Invites retrieved successfully", body = [Invite]),
+        (status = 400, description = "Invalid status or page"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_invites(
+    Extension(user): Extension<AuthUser>,
+    OriginalUri(uri): OriginalUri,
+    State(service): State<Arc<InviteService>>,
+    Query(filter): Query<InviteListParams>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => return invite_error_response(InviteError::ValidationError(e.to_string())),
+    };
+
+    match service.list_invites(filter.status, &pagination).await {
+        Ok(invites) => (
+            StatusCode::OK,
+            pagination.headers(&uri, invites.len()),
+            Json(invites),
+        )
+            .into_response(),
+        Err(e) => invite_error_response(e),
+    }
+}
+
+/// Get invite usage (admin only)
+///
+/// How many invites were created, used, are still unused or expired unused.
+#[utoipa::path(
+    get,
+    path = "/api/admin/invites/usage",
+    tag = "invites",
+    responses(
+        (status = 200, description = "Invite usage retrieved successfully", body = InviteUsage),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_invite_usage(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<InviteService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    match service.usage().await {
+        Ok(usage) => (StatusCode::OK, Json(usage)).into_response(),
+        Err(e) => invite_error_response(e),
+    }
+}
+
+/// Delete an invite (admin only)
+///
+/// Only unused invites can be deleted, revoking their code.
+#[utoipa::path(
+    delete,
+    path = "/api/admin/invites/{id}",
+    tag = "invites",
+    params(
+        ("id" = i64, Path, description = "Invite ID")
+    ),
+    responses(
+        (status = 204, description = "Invite deleted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Invite not found"),
+        (status = 409, description = "Invite already used"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_invite(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<InviteService>>,
+    Path(id): Path<i64>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    match service.delete_invite(id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => invite_error_response(e),
+    }
+}
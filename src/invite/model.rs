@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Blog setting holding the registration mode
+pub const REGISTRATION_MODE_SETTING: &str = "registration.mode";
+/// Most invites created by one request
+pub const MAX_INVITES_PER_REQUEST: u32 = 100;
+/// Longest time an invite may stay valid, in days
+pub const MAX_INVITE_DAYS: u32 = 90;
+pub const DEFAULT_INVITE_DAYS: u32 = 14;
+/// Longest accepted note, in characters
+pub const MAX_NOTE_LENGTH: usize = 200;
+
+/// Who may sign up through a blog, from its `registration.mode` setting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// Anyone; the default
+    #[default]
+    Open,
+    /// Only holders of an unused, unexpired invite code
+    InviteOnly,
+    /// Nobody
+    Closed,
+}
+
+impl RegistrationMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "open" => Some(RegistrationMode::Open),
+            "invite-only" => Some(RegistrationMode::InviteOnly),
+            "closed" => Some(RegistrationMode::Closed),
+            _ => None,
+        }
+    }
+}
+
+/// Request to create invite codes
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CreateInvitesRequest {
+    /// How many codes to create, 1 by default
+    #[schema(example = 5)]
+    pub count: Option<u32>,
+    /// Days the codes stay valid, 14 by default
+    #[schema(example = 30)]
+    pub expires_in_days: Option<u32>,
+    /// Who the codes are for, for the admins' reference
+    #[schema(example = "Beta readers from the meetup")]
+    pub note: Option<String>,
+}
+
+/// Whether an invite can still be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InviteStatus {
+    Unused,
+    Used,
+    /// Expired before it was used
+    Expired,
+}
+
+impl InviteStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InviteStatus::Unused => "unused",
+            InviteStatus::Used => "used",
+            InviteStatus::Expired => "expired",
+        }
+    }
+}
+
+/// An invite code and who used it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Invite {
+    #[schema(example = 3)]
+    pub id: i64,
+    #[schema(example = "K7QX2MPR9HTA")]
+    pub code: String,
+    pub note: Option<String>,
+    pub status: InviteStatus,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// User who registered with it
+    pub used_by: Option<Uuid>,
+    #[schema(example = "ada")]
+    pub used_by_username: Option<String>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Filter of the invite list
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InviteListParams {
+    /// Only invites with this status
+    #[param(inline)]
+    pub status: Option<InviteStatus>,
+}
+
+/// How the blog's invites have been used
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InviteUsage {
+    #[schema(example = 40)]
+    pub created: i64,
+    #[schema(example = 25)]
+    pub used: i64,
+    #[schema(example = 10)]
+    pub unused: i64,
+    #[schema(example = 5)]
+    pub expired: i64,
+    /// Invites used in the last 30 days
+    #[schema(example = 8)]
+    pub used_last_30_days: i64,
+}
+
+/// Possible invite errors
+#[derive(Debug, thiserror::Error)]
+pub enum InviteError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Invite not found")]
+    NotFound,
+
+    #[error("Used invites cannot be deleted")]
+    AlreadyUsed,
+}
@@ -0,0 +1,276 @@
+use crate::invite::model::{
+    CreateInvitesRequest, Invite, InviteError, InviteStatus, InviteUsage, RegistrationMode,
+    DEFAULT_INVITE_DAYS, MAX_INVITES_PER_REQUEST, MAX_INVITE_DAYS, MAX_NOTE_LENGTH,
+    REGISTRATION_MODE_SETTING,
+};
+use crate::pagination::Pagination;
+use crate::tenant::middleware::current_blog_id;
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Row};
+use tracing::info;
+use uuid::Uuid;
+
+// Letters and digits that cannot be mistaken for one another; 32 of them, so every random
+// byte maps to one without bias
+const CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 12;
+// Status of an invite as stored in the `status` column of the queries below
+const INVITE_STATUS: &str = "CASE WHEN i.used_at IS NOT NULL THEN 'used' \
+    WHEN i.expires_at <= NOW() THEN 'expired' ELSE 'unused' END";
+
+fn generate_code() -> String {
+    rand::random::<[u8; CODE_LENGTH]>()
+        .iter()
+        .map(|byte| CODE_ALPHABET[(*byte % 32) as usize] as char)
+        .collect()
+}
+
+/// Invite codes as stored: typed codes match ignoring case, spaces and dashes
+pub fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+fn invite_from_row(row: &PgRow) -> Invite {
+    let status = match row.get::<&str, _>("status") {
+        "used" => InviteStatus::Used,
+        "expired" => InviteStatus::Expired,
+        _ => InviteStatus::Unused,
+    };
+
+    Invite {
+        id: row.get("id"),
+        code: row.get("code"),
+        note: row.get("note"),
+        status,
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+        used_by: row.get("used_by"),
+        used_by_username: row.get("used_by_username"),
+        used_at: row.get("used_at"),
+    }
+}
+
+/// Who may sign up through the current blog; open unless its `registration.mode` setting
+/// says otherwise
+pub async fn registration_mode(pool: &PgPool) -> Result<RegistrationMode, sqlx::Error> {
+    let value = sqlx::query_scalar::<_, Value>(
+        "SELECT value FROM global.blog_settings WHERE blog_id = $1 AND key = $2",
+    )
+    .bind(current_blog_id())
+    .bind(REGISTRATION_MODE_SETTING)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(value
+        .as_ref()
+        .and_then(Value::as_str)
+        .and_then(RegistrationMode::parse)
+        .unwrap_or_default())
+}
+
+/// Use an invite of the current blog for a new user, in the transaction creating the user.
+/// Returns whether the code was valid: known, unused and unexpired.
+pub async fn redeem_invite(
+    conn: &mut PgConnection,
+    code: &str,
+    user_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let redeemed = sqlx::query(
+        r#"
+        UPDATE global.invites SET used_by = $3, used_at = NOW()
+        WHERE blog_id = $1 AND code = $2 AND used_at IS NULL AND expires_at > NOW()
+        "#,
+    )
+    .bind(current_blog_id())
+    .bind(normalize_code(code))
+    .bind(user_id)
+    .execute(conn)
+    .await?
+    .rows_affected();
+
+    Ok(redeemed > 0)
+}
+
+/// Invite codes of each blog, required to register while its registration mode is
+/// `invite-only`.
+///
+/// Admins create codes in bulk; each can be used once, until it expires. The admins can see
+/// who used each code and how many have been used.
+pub struct InviteService {
+    pool: PgPool,
+}
+
+impl InviteService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Create invite codes for the current blog
+    pub async fn create_invites(
+        &self,
+        request: &CreateInvitesRequest,
+        created_by: Uuid,
+    ) -> Result<Vec<Invite>, InviteError> {
+        let count = request.count.unwrap_or(1);
+        if count == 0 || count > MAX_INVITES_PER_REQUEST {
+            return Err(InviteError::ValidationError(format!(
+                "Count must be between 1 and {}",
+                MAX_INVITES_PER_REQUEST
+            )));
+        }
+        let days = request.expires_in_days.unwrap_or(DEFAULT_INVITE_DAYS);
+        if days == 0 || days > MAX_INVITE_DAYS {
+            return Err(InviteError::ValidationError(format!(
+                "Invites must expire within 1 to {} days",
+                MAX_INVITE_DAYS
+            )));
+        }
+        let note = request
+            .note
+            .as_deref()
+            .map(str::trim)
+            .filter(|note| !note.is_empty());
+        if note.is_some_and(|note| note.chars().count() > MAX_NOTE_LENGTH) {
+            return Err(InviteError::ValidationError(format!(
+                "Note cannot be longer than {} characters",
+                MAX_NOTE_LENGTH
+            )));
+        }
+
+        let codes: Vec<String> = (0..count).map(|_| generate_code()).collect();
+        let rows = sqlx::query(&format!(
+            r#"
+            INSERT INTO global.invites AS i (blog_id, code, note, created_by, expires_at)
+            SELECT $1, code, $3, $4, NOW() + make_interval(days => $5)
+            FROM UNNEST($2::VARCHAR[]) AS codes(code)
+            RETURNING i.*, NULL::VARCHAR AS used_by_username, {} AS status
+            "#,
+            INVITE_STATUS
+        ))
+        .bind(current_blog_id())
+        .bind(&codes)
+        .bind(note)
+        .bind(created_by)
+        .bind(days as i32)
+        .fetch_all(&self.pool)
+        .await?;
+
+        info!(
+            "Created {} invites for blog {} valid for {} days",
+            rows.len(),
+            current_blog_id(),
+            days
+        );
+        Ok(rows.iter().map(invite_from_row).collect())
+    }
+
+    /// Invites of the current blog, newest first, optionally only those with `status`
+    pub async fn list_invites(
+        &self,
+        status: Option<InviteStatus>,
+        pagination: &Pagination,
+    ) -> Result<Vec<Invite>, InviteError> {
+        let sql = format!(
+            r#"
+            SELECT * FROM (
+                SELECT i.*, u.username AS used_by_username, {} AS status
+                FROM global.invites i
+                LEFT JOIN global.users u ON u.id = i.used_by
+                WHERE i.blog_id = $1
+            ) invites
+            WHERE $2::VARCHAR IS NULL OR status = $2
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            INVITE_STATUS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(current_blog_id())
+            .bind(status.map(InviteStatus::as_str))
+            .bind(pagination.limit)
+            .bind(pagination.offset)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(invite_from_row).collect())
+    }
+
+    /// How many of the current blog's invites have been used, are still open or expired
+    pub async fn usage(&self) -> Result<InviteUsage, InviteError> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) AS created,
+                COUNT(*) FILTER (WHERE used_at IS NOT NULL) AS used,
+                COUNT(*) FILTER (WHERE used_at IS NULL AND expires_at > NOW()) AS unused,
+                COUNT(*) FILTER (WHERE used_at IS NULL AND expires_at <= NOW()) AS expired,
+                COUNT(*) FILTER (WHERE used_at > NOW() - INTERVAL '30 days')
+                    AS used_last_30_days
+            FROM global.invites
+            WHERE blog_id = $1
+            "#,
+        )
+        .bind(current_blog_id())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(InviteUsage {
+            created: row.get("created"),
+            used: row.get("used"),
+            unused: row.get("unused"),
+            expired: row.get("expired"),
+            used_last_30_days: row.get("used_last_30_days"),
+        })
+    }
+
+    /// Delete an unused invite of the current blog, so that it can no longer be used
+    pub async fn delete_invite(&self, id: i64) -> Result<(), InviteError> {
+        let deleted = sqlx::query(
+            "DELETE FROM global.invites WHERE id = $1 AND blog_id = $2 AND used_at IS NULL",
+        )
+        .bind(id)
+        .bind(current_blog_id())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if deleted > 0 {
+            return Ok(());
+        }
+
+        let exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM global.invites WHERE id = $1 AND blog_id = $2)",
+        )
+        .bind(id)
+        .bind(current_blog_id())
+        .fetch_one(&self.pool)
+        .await?;
+        Err(if exists {
+            InviteError::AlreadyUsed
+        } else {
+            InviteError::NotFound
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_codes_use_the_alphabet() {
+        let code = generate_code();
+        assert_eq!(code.len(), CODE_LENGTH);
+        assert!(code.bytes().all(|byte| CODE_ALPHABET.contains(&byte)));
+        assert_ne!(code, generate_code());
+    }
+
+    #[test]
+    fn test_normalize_code() {
+        assert_eq!(normalize_code(" k7qx-2mpr-9hta "), "K7QX2MPR9HTA");
+        assert_eq!(normalize_code(&normalize_code("ab cd")), "ABCD");
+    }
+}
@@ -0,0 +1,71 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::jobs::model::JobError;
+use crate::jobs::service::JobService;
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+
+pub fn job_error_response(e: JobError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Job error: {:?}", e);
+    let status = match e {
+        JobError::NotFound => StatusCode::NOT_FOUND,
+        JobError::UnknownKind(_) => StatusCode::BAD_REQUEST,
+        JobError::DatabaseError(_) | JobError::Failed(_) | JobError::InternalError(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Get a background job (admin only)
+///
+/// Returns the status of a job, and its result once it has finished.
+#[utoipa::path(
+    get,
+    path = "/api/admin/jobs/{id}",
+    tag = "jobs",
+    params(
+        ("id" = String, Path, description = "ID of the job")
+    ),
+    responses(
+        (status = 200, description = "Job retrieved", body = Job),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Job not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_job(
+    Path(id): Path<Uuid>,
+    Extension(user): Extension<AuthUser>,
+    Extension(jobs): Extension<Arc<JobService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Only admins can view jobs" })),
+        )
+            .into_response();
+    }
+
+    match jobs.get_job(id).await {
+        Ok(job) => (StatusCode::OK, Json(job)).into_response(),
+        Err(e) => job_error_response(e),
+    }
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Where a background job is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "succeeded" => Some(JobStatus::Succeeded),
+            "failed" => Some(JobStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A background job and its outcome
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Job {
+    pub id: Uuid,
+
+    /// What the job does, e.g. `site_export`
+    #[schema(example = "site_export")]
+    pub kind: String,
+
+    pub status: JobStatus,
+
+    /// Input of the job
+    #[schema(value_type = Object)]
+    pub payload: Value,
+
+    /// Output of a succeeded job
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<Value>,
+
+    /// Why a failed job failed
+    pub error: Option<String>,
+
+    /// Blog the job runs for
+    #[serde(skip)]
+    pub blog_id: i64,
+
+    pub created_by: Option<Uuid>,
+
+    pub created_at: DateTime<Utc>,
+
+    /// When the job may start; later than its creation for scheduled jobs
+    pub run_after: DateTime<Utc>,
+
+    pub started_at: Option<DateTime<Utc>>,
+
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl Job {
+    pub fn from_row(row: &PgRow) -> Result<Self, JobError> {
+        let status: String = row.get("status");
+
+        Ok(Self {
+            id: row.get("id"),
+            kind: row.get("kind"),
+            status: JobStatus::from_db(&status)
+                .ok_or_else(|| JobError::InternalError(format!("Unknown job status {}", status)))?,
+            payload: row.get("payload"),
+            result: row.get("result"),
+            error: row.get("error"),
+            blog_id: row.get("blog_id"),
+            created_by: row.get("created_by"),
+            created_at: row.get("created_at"),
+            run_after: row.get("run_after"),
+            started_at: row.get("started_at"),
+            finished_at: row.get("finished_at"),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JobError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Job not found")]
+    NotFound,
+
+    #[error("No handler for job kind {0}")]
+    UnknownKind(String),
+
+    #[error("Job failed: {0}")]
+    Failed(String),
+
+    #[error("Internal error: {0}")]
+    InternalError(String),
+}
@@ -0,0 +1,245 @@
+use crate::jobs::model::{Job, JobError, JobStatus};
+use crate::tenant::middleware::{current_blog_id, with_blog_id};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+// How often the worker looks for jobs queued by other instances
+const JOB_POLL_INTERVAL: Duration = Duration::from_secs(10);
+// A job running longer than this is given up on. Jobs still marked running after it, because
+// their instance stopped, are picked up again.
+const JOB_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Work that can run as a background job
+pub trait JobHandler: Send + Sync {
+    /// Kind of the jobs this handler runs
+    fn kind(&self) -> &'static str;
+
+    /// Run a job, returning its result. Runs scoped to the blog the job was queued for.
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>>;
+}
+
+/// Queue of background jobs kept in Postgres.
+///
+/// Jobs are queued by request handlers and run by a worker in every instance. A job is claimed
+/// with `FOR UPDATE SKIP LOCKED`, so each runs once however many instances there are, and its
+/// status and result stay queryable after it finishes.
+#[derive(Clone)]
+pub struct JobService {
+    pool: PgPool,
+    handlers: HashMap<&'static str, Arc<dyn JobHandler>>,
+    /// Wakes the local worker when a job is queued
+    queued: Arc<Notify>,
+}
+
+impl JobService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            handlers: HashMap::new(),
+            queued: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn with_handler(mut self, handler: Arc<dyn JobHandler>) -> Self {
+        self.handlers.insert(handler.kind(), handler);
+        self
+    }
+
+    /// Queue a job for the current blog
+    pub async fn enqueue(
+        &self,
+        kind: &str,
+        payload: Value,
+        created_by: Option<Uuid>,
+    ) -> Result<Job, JobError> {
+        self.schedule(kind, payload, created_by, None).await
+    }
+
+    /// Queue a job for the current blog that does not start before `run_after`, or at once
+    /// when it is `None`. Workers pick it up within their poll interval of it becoming due.
+    pub async fn schedule(
+        &self,
+        kind: &str,
+        payload: Value,
+        created_by: Option<Uuid>,
+        run_after: Option<DateTime<Utc>>,
+    ) -> Result<Job, JobError> {
+        if !self.handlers.contains_key(kind) {
+            return Err(JobError::UnknownKind(kind.to_string()));
+        }
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO global.jobs (id, kind, status, payload, blog_id, created_by, run_after)
+            VALUES ($1, $2, 'queued', $3, $4, $5, COALESCE($6, NOW()))
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(kind)
+        .bind(payload)
+        .bind(current_blog_id())
+        .bind(created_by)
+        .bind(run_after)
+        .fetch_one(&self.pool)
+        .await?;
+        let job = Job::from_row(&row)?;
+
+        if run_after.is_some() {
+            info!(
+                "Scheduled {} job {} for {}",
+                job.kind, job.id, job.run_after
+            );
+        } else {
+            info!("Queued {} job {}", job.kind, job.id);
+        }
+        self.queued.notify_one();
+        Ok(job)
+    }
+
+    /// Queue a job for the current blog unless a job of the same kind was queued within
+    /// `interval`, for work that runs on a schedule. Returns `None` if it was not queued.
+    pub async fn enqueue_unless_recent(
+        &self,
+        kind: &str,
+        payload: Value,
+        interval: Duration,
+    ) -> Result<Option<Job>, JobError> {
+        if !self.handlers.contains_key(kind) {
+            return Err(JobError::UnknownKind(kind.to_string()));
+        }
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO global.jobs (id, kind, status, payload, blog_id)
+            SELECT $1, $2, 'queued', $3, $4
+            WHERE NOT EXISTS (
+                SELECT 1 FROM global.jobs
+                WHERE kind = $2 AND created_at > NOW() - make_interval(secs => $5)
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(kind)
+        .bind(payload)
+        .bind(current_blog_id())
+        .bind(interval.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await?;
+        let job = row.as_ref().map(Job::from_row).transpose()?;
+
+        if let Some(job) = &job {
+            info!("Queued scheduled {} job {}", job.kind, job.id);
+            self.queued.notify_one();
+        }
+        Ok(job)
+    }
+
+    /// Get a job of the current blog
+    pub async fn get_job(&self, id: Uuid) -> Result<Job, JobError> {
+        let row = sqlx::query("SELECT * FROM global.jobs WHERE id = $1 AND blog_id = $2")
+            .bind(id)
+            .bind(current_blog_id())
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(JobError::NotFound)?;
+
+        Job::from_row(&row)
+    }
+
+    // Claim the oldest runnable job, if any: a due queued job, or one left running too long
+    async fn claim_next(&self) -> Result<Option<Job>, JobError> {
+        let row = sqlx::query(
+            r#"
+            UPDATE global.jobs SET status = 'running', started_at = NOW()
+            WHERE id = (
+                SELECT id FROM global.jobs
+                WHERE (status = 'queued' AND run_after <= NOW())
+                    OR (status = 'running' AND started_at < NOW() - make_interval(secs => $1))
+                ORDER BY created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(JOB_TIMEOUT.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(Job::from_row).transpose()
+    }
+
+    // Run one claimed job and record its outcome
+    async fn run_job(&self, job: Job) -> Result<(), JobError> {
+        let outcome = match self.handlers.get(job.kind.as_str()) {
+            Some(handler) => {
+                match tokio::time::timeout(
+                    JOB_TIMEOUT,
+                    with_blog_id(job.blog_id, handler.run(&job)),
+                )
+                .await
+                {
+                    Ok(result) => result,
+                    Err(_) => Err(JobError::Failed("Timed out".to_string())),
+                }
+            }
+            None => Err(JobError::UnknownKind(job.kind.clone())),
+        };
+
+        let (status, result, error) = match outcome {
+            Ok(result) => (JobStatus::Succeeded, Some(result), None),
+            Err(e) => {
+                warn!("{} job {} failed: {}", job.kind, job.id, e);
+                (JobStatus::Failed, None, Some(e.to_string()))
+            }
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE global.jobs SET status = $1, result = $2, error = $3, finished_at = NOW()
+            WHERE id = $4
+            "#,
+        )
+        .bind(status.as_str())
+        .bind(result)
+        .bind(error)
+        .bind(job.id)
+        .execute(&self.pool)
+        .await?;
+
+        info!("{} job {} {}", job.kind, job.id, status.as_str());
+        Ok(())
+    }
+
+    /// Run queued jobs until none are left, returning how many ran
+    pub async fn run_pending(&self) -> Result<usize, JobError> {
+        let mut ran = 0;
+        while let Some(job) = self.claim_next().await? {
+            self.run_job(job).await?;
+            ran += 1;
+        }
+        Ok(ran)
+    }
+
+    // Run jobs as they are queued here, and poll for jobs queued by other instances
+    pub fn start_worker(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.run_pending().await {
+                    error!("Job worker failed: {}", e);
+                }
+                let _ = tokio::time::timeout(JOB_POLL_INTERVAL, self.queued.notified()).await;
+            }
+        });
+    }
+}
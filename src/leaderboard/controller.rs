@@ -0,0 +1,96 @@
+use crate::db::retry::unavailable_response;
+use crate::leaderboard::model::{
+    LeaderboardError, LeaderboardKind, LeaderboardParams, DEFAULT_LEADERBOARD_SIZE,
+    MAX_LEADERBOARD_SIZE,
+};
+use crate::leaderboard::service::LeaderboardService;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn leaderboard_error_response(e: LeaderboardError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        LeaderboardError::ValidationError(_) => StatusCode::BAD_REQUEST,
+        LeaderboardError::DatabaseError(_) => {
+            error!("Leaderboard error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+async fn leaderboard(
+    service: &LeaderboardService,
+    kind: LeaderboardKind,
+    params: LeaderboardParams,
+) -> Response {
+    let limit = params.limit.unwrap_or(DEFAULT_LEADERBOARD_SIZE);
+    if !(1..=MAX_LEADERBOARD_SIZE).contains(&limit) {
+        return leaderboard_error_response(LeaderboardError::ValidationError(format!(
+            "limit must be between 1 and {}",
+            MAX_LEADERBOARD_SIZE
+        )));
+    }
+
+    match service
+        .get_leaderboard(kind, params.period.unwrap_or_default(), params.date, limit)
+        .await
+    {
+        Ok(leaderboard) => (StatusCode::OK, Json(leaderboard)).into_response(),
+        Err(e) => leaderboard_error_response(e),
+    }
+}
+
+/// Get the top authors
+///
+/// Authors are ranked by a point per view, 3 per like and 5 per comment their posts received
+/// in the period. The current period is refreshed every five minutes; pass `date` for an
+/// earlier week or month.
+#[utoipa::path(
+    get,
+    path = "/api/leaderboards/authors",
+    tag = "leaderboards",
+    params(LeaderboardParams),
+    responses(
+        (status = 200, description = "Leaderboard retrieved successfully", body = Leaderboard),
+        (status = 400, description = "Invalid period, date or limit"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_author_leaderboard(
+    State(service): State<Arc<LeaderboardService>>,
+    Query(params): Query<LeaderboardParams>,
+) -> Response {
+    leaderboard(&service, LeaderboardKind::Authors, params).await
+}
+
+/// Get the top commenters
+///
+/// Commenters are ranked by their approved comments in the period. The current period is
+/// refreshed every five minutes; pass `date` for an earlier week or month.
+#[utoipa::path(
+    get,
+    path = "/api/leaderboards/commenters",
+    tag = "leaderboards",
+    params(LeaderboardParams),
+    responses(
+        (status = 200, description = "Leaderboard retrieved successfully", body = Leaderboard),
+        (status = 400, description = "Invalid period, date or limit"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_commenter_leaderboard(
+    State(service): State<Arc<LeaderboardService>>,
+    Query(params): Query<LeaderboardParams>,
+) -> Response {
+    leaderboard(&service, LeaderboardKind::Commenters, params).await
+}
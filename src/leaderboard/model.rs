@@ -0,0 +1,195 @@
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Users listed when no limit is given
+pub const DEFAULT_LEADERBOARD_SIZE: i64 = 10;
+/// Most users a leaderboard lists, and how many are kept in Redis
+pub const MAX_LEADERBOARD_SIZE: i64 = 100;
+
+/// Points an author scores per view, like and comment of their posts
+pub const VIEW_POINTS: i64 = 1;
+pub const LIKE_POINTS: i64 = 3;
+pub const COMMENT_POINTS: i64 = 5;
+
+/// What users are ranked by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardKind {
+    /// Authors, by the views, likes and comments their posts receive
+    Authors,
+    /// Commenters, by the comments they write
+    Commenters,
+}
+
+impl LeaderboardKind {
+    pub const ALL: [LeaderboardKind; 2] = [LeaderboardKind::Authors, LeaderboardKind::Commenters];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LeaderboardKind::Authors => "authors",
+            LeaderboardKind::Commenters => "commenters",
+        }
+    }
+}
+
+/// Period a leaderboard covers. Weeks start on Monday; periods follow UTC dates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardPeriod {
+    #[default]
+    Week,
+    Month,
+    All,
+}
+
+impl LeaderboardPeriod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LeaderboardPeriod::Week => "week",
+            LeaderboardPeriod::Month => "month",
+            LeaderboardPeriod::All => "all",
+        }
+    }
+
+    /// First day of the period that contains `day`; `None` for all time
+    pub fn start(self, day: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            LeaderboardPeriod::Week => {
+                Some(day - Duration::days(day.weekday().num_days_from_monday() as i64))
+            }
+            LeaderboardPeriod::Month => day.with_day(1),
+            LeaderboardPeriod::All => None,
+        }
+    }
+
+    /// First day after the period starting on `start`
+    pub fn end(self, start: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            LeaderboardPeriod::Week => Some(start + Duration::days(7)),
+            LeaderboardPeriod::Month => start.checked_add_months(Months::new(1)),
+            LeaderboardPeriod::All => None,
+        }
+    }
+
+    /// Start of the period before the one that contains `day`
+    pub fn previous_start(self, day: NaiveDate) -> Option<NaiveDate> {
+        let start = self.start(day)?;
+        self.start(start - Duration::days(1))
+    }
+}
+
+/// Ranks of scores listed from highest to lowest; equal scores share a rank, and the next
+/// score is ranked as if they had not
+pub fn ranks(scores: &[i64]) -> Vec<i64> {
+    let mut ranks = Vec::with_capacity(scores.len());
+    for (i, score) in scores.iter().enumerate() {
+        let rank = match i {
+            0 => 1,
+            _ if scores[i - 1] == *score => ranks[i - 1],
+            _ => i as i64 + 1,
+        };
+        ranks.push(rank);
+    }
+    ranks
+}
+
+/// Query parameters of the leaderboards
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardParams {
+    /// `week` (default), `month` or `all`
+    #[param(inline)]
+    pub period: Option<LeaderboardPeriod>,
+    /// A day of an earlier week or month to rank, instead of the current one
+    #[param(example = "2026-09-14")]
+    pub date: Option<NaiveDate>,
+    /// Number of users to list, 10 by default and at most 100
+    #[param(minimum = 1, maximum = 100)]
+    pub limit: Option<i64>,
+}
+
+/// A user's place on a leaderboard
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LeaderboardEntry {
+    /// 1 for the top score; users with equal scores share a rank
+    #[schema(example = 1)]
+    pub rank: i64,
+    pub user_id: Uuid,
+    #[schema(example = "johndoe")]
+    pub username: String,
+    pub avatar_url: Option<String>,
+    /// Authors score a point per view, 3 per like and 5 per comment of their posts;
+    /// commenters a point per comment
+    #[schema(example = 42)]
+    pub score: i64,
+}
+
+/// Top users of a period
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Leaderboard {
+    pub period: LeaderboardPeriod,
+    /// First day of the period; absent for all time
+    #[schema(example = "2026-10-12")]
+    pub starts_on: Option<NaiveDate>,
+    /// First day after the period; absent for all time
+    #[schema(example = "2026-10-19")]
+    pub ends_before: Option<NaiveDate>,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// Possible leaderboard errors
+#[derive(Debug, thiserror::Error)]
+pub enum LeaderboardError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    ValidationError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        // 2026-10-16 is a Friday
+        let friday = day("2026-10-16");
+        assert_eq!(
+            LeaderboardPeriod::Week.start(friday),
+            Some(day("2026-10-12"))
+        );
+        assert_eq!(
+            LeaderboardPeriod::Week.end(day("2026-10-12")),
+            Some(day("2026-10-19"))
+        );
+        assert_eq!(
+            LeaderboardPeriod::Week.previous_start(friday),
+            Some(day("2026-10-05"))
+        );
+        assert_eq!(
+            LeaderboardPeriod::Month.start(friday),
+            Some(day("2026-10-01"))
+        );
+        assert_eq!(
+            LeaderboardPeriod::Month.end(day("2026-12-01")),
+            Some(day("2027-01-01"))
+        );
+        assert_eq!(
+            LeaderboardPeriod::Month.previous_start(day("2026-01-31")),
+            Some(day("2025-12-01"))
+        );
+        assert_eq!(LeaderboardPeriod::All.start(friday), None);
+    }
+
+    #[test]
+    fn test_ranks() {
+        assert_eq!(ranks(&[]), Vec::<i64>::new());
+        assert_eq!(ranks(&[9, 7, 7, 3, 3, 3, 1]), vec![1, 2, 2, 4, 4, 4, 7]);
+    }
+}
@@ -0,0 +1,548 @@
+use crate::cache::redis::RedisCache;
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::leaderboard::model::{
+    ranks, Leaderboard, LeaderboardEntry, LeaderboardError, LeaderboardKind, LeaderboardPeriod,
+    COMMENT_POINTS, LIKE_POINTS, MAX_LEADERBOARD_SIZE, VIEW_POINTS,
+};
+use crate::tenant::middleware::{blog_key, current_blog_id, with_blog_id};
+use crate::tenant::service::TenantService;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Kind of the job that refreshes the current leaderboards and rolls up finished periods
+pub const LEADERBOARD_JOB: &str = "leaderboards";
+
+// How often leaderboards are refreshed, and how often instances check whether a run is due
+const LEADERBOARD_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const LEADERBOARD_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Leaderboards left in Redis when refreshes stop are read from Postgres after this long
+const LEADERBOARD_CACHE_TTL_SECONDS: i64 = 60 * 60;
+// Periods that are rolled up when they end
+const ROLLED_UP_PERIODS: [LeaderboardPeriod; 2] =
+    [LeaderboardPeriod::Week, LeaderboardPeriod::Month];
+const CACHED_PERIODS: [LeaderboardPeriod; 3] = [
+    LeaderboardPeriod::Week,
+    LeaderboardPeriod::Month,
+    LeaderboardPeriod::All,
+];
+
+// Authors score their posts' views (sampled views weigh by their sample rate), counted likes
+// and approved comments by others. Without bounds views are the posts' all-time counters, as
+// raw interactions are purged by the retention policy.
+const AUTHOR_SCORES_SQL: &str = r#"
+    WITH points AS (
+        SELECT p.user_id, p.views::BIGINT * $5 AS points
+        FROM global.posts p
+        WHERE $2::TIMESTAMPTZ IS NULL
+            AND p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false
+        UNION ALL
+        SELECT p.user_id,
+            ROUND(SUM(1.0 / COALESCE((i.metadata->>'sample_rate')::FLOAT8, 1.0)))::BIGINT * $5
+        FROM global.user_interactions i
+        JOIN global.posts p ON p.id = i.post_id
+        WHERE $2::TIMESTAMPTZ IS NOT NULL
+            AND i.interaction_type = 'view' AND i.created_at >= $2 AND i.created_at < $3
+            AND p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false
+        GROUP BY p.user_id
+        UNION ALL
+        SELECT p.user_id, COUNT(*) * $6
+        FROM global.post_likes l
+        JOIN global.posts p ON p.id = l.post_id
+        WHERE l.counted
+            AND ($2::TIMESTAMPTZ IS NULL OR l.created_at >= $2)
+            AND ($3::TIMESTAMPTZ IS NULL OR l.created_at < $3)
+            AND p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false
+        GROUP BY p.user_id
+        UNION ALL
+        SELECT p.user_id, COUNT(*) * $7
+        FROM global.comments c
+        JOIN global.posts p ON p.id = c.post_id
+        WHERE c.is_deleted = false AND c.moderation_status = 'approved'
+            AND c.user_id IS DISTINCT FROM p.user_id
+            AND ($2::TIMESTAMPTZ IS NULL OR c.created_at >= $2)
+            AND ($3::TIMESTAMPTZ IS NULL OR c.created_at < $3)
+            AND p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false
+        GROUP BY p.user_id
+    )
+    SELECT s.user_id, SUM(s.points)::BIGINT AS score
+    FROM points s
+    JOIN global.users u ON u.id = s.user_id
+    WHERE NOT u.is_shadow_banned
+    GROUP BY s.user_id
+    HAVING SUM(s.points) > 0
+    ORDER BY score DESC, s.user_id
+    LIMIT $4
+"#;
+
+// Commenters score their approved comments on published posts; guests are not ranked
+const COMMENTER_SCORES_SQL: &str = r#"
+    SELECT c.user_id, COUNT(*) AS score
+    FROM global.comments c
+    JOIN global.posts p ON p.id = c.post_id
+    JOIN global.users u ON u.id = c.user_id
+    WHERE c.blog_id = $1 AND c.is_deleted = false AND c.moderation_status = 'approved'
+        AND ($2::TIMESTAMPTZ IS NULL OR c.created_at >= $2)
+        AND ($3::TIMESTAMPTZ IS NULL OR c.created_at < $3)
+        AND p.is_draft = false AND p.is_deleted = false AND NOT u.is_shadow_banned
+    GROUP BY c.user_id
+    ORDER BY score DESC, c.user_id
+    LIMIT $4
+"#;
+
+fn leaderboard_key(
+    kind: LeaderboardKind,
+    period: LeaderboardPeriod,
+    starts_on: Option<NaiveDate>,
+) -> String {
+    let starts_on = starts_on.map_or_else(|| "all".to_string(), |day| day.to_string());
+    blog_key(&format!(
+        "leaderboard:{}:{}:{}",
+        kind.as_str(),
+        period.as_str(),
+        starts_on
+    ))
+}
+
+fn day_start(day: NaiveDate) -> DateTime<Utc> {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Leaderboards of the top authors and commenters of a blog, by week, month and all time.
+///
+/// The current week, month and all-time leaderboards are kept in Redis sorted sets, which a
+/// background job queued every five minutes rebuilds from Postgres; without Redis, or before
+/// the first run, they are computed on request. When a week or month ends, the job rolls up
+/// its final standings into Postgres, where earlier periods are read from. Periods that ended
+/// before leaderboards existed are computed from what analytics still holds.
+pub struct LeaderboardService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+    tenants: TenantService,
+}
+
+impl LeaderboardService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self {
+            tenants: TenantService::new(pool.clone()),
+            pool,
+            redis_cache,
+        }
+    }
+
+    /// Queue a refresh every five minutes
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(LEADERBOARD_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(LEADERBOARD_JOB, json!({}), LEADERBOARD_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule leaderboard refresh: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Top `limit` users of the period containing `date`, or of the current period
+    pub async fn get_leaderboard(
+        &self,
+        kind: LeaderboardKind,
+        period: LeaderboardPeriod,
+        date: Option<NaiveDate>,
+        limit: i64,
+    ) -> Result<Leaderboard, LeaderboardError> {
+        let today = Utc::now().date_naive();
+        let day = match date {
+            Some(_) if period == LeaderboardPeriod::All => {
+                return Err(LeaderboardError::ValidationError(
+                    "date only applies to week and month leaderboards".to_string(),
+                ))
+            }
+            Some(day) if day > today => {
+                return Err(LeaderboardError::ValidationError(
+                    "date must not be in the future".to_string(),
+                ))
+            }
+            Some(day) => day,
+            None => today,
+        };
+        let starts_on = period.start(day);
+        let ends_before = starts_on.and_then(|start| period.end(start));
+
+        let scores = if starts_on == period.start(today) {
+            match self.cached_scores(kind, period, starts_on, limit).await {
+                Some(scores) => scores,
+                None => {
+                    self.scores(kind, starts_on, ends_before, Some(limit))
+                        .await?
+                }
+            }
+        } else {
+            match starts_on {
+                Some(start) if self.is_rolled_up(period, start).await? => {
+                    self.rolled_up_scores(kind, period, start, limit).await?
+                }
+                _ => {
+                    self.scores(kind, starts_on, ends_before, Some(limit))
+                        .await?
+                }
+            }
+        };
+
+        Ok(Leaderboard {
+            period,
+            starts_on,
+            ends_before,
+            entries: self.entries(scores).await?,
+        })
+    }
+
+    /// Roll up the periods that ended and refresh the cached leaderboards of every blog.
+    /// Returns how many periods were rolled up.
+    pub async fn update_all(&self) -> Result<u64, JobError> {
+        let blogs = self
+            .tenants
+            .list_blogs()
+            .await
+            .map_err(|e| JobError::InternalError(e.to_string()))?;
+
+        let mut rolled_up = 0;
+        for blog in &blogs {
+            rolled_up += with_blog_id(blog.id, self.update_blog()).await?;
+        }
+
+        info!(
+            "Refreshed the leaderboards of {} blogs, rolled up {} periods",
+            blogs.len(),
+            rolled_up
+        );
+        Ok(rolled_up)
+    }
+
+    // Roll up the previous week and month of the current blog if they are not yet, and
+    // rebuild its cached leaderboards
+    async fn update_blog(&self) -> Result<u64, sqlx::Error> {
+        let today = Utc::now().date_naive();
+        let mut rolled_up = 0;
+        for period in ROLLED_UP_PERIODS {
+            if let Some(start) = period.previous_start(today) {
+                if self.roll_up(period, start).await? {
+                    rolled_up += 1;
+                }
+            }
+        }
+
+        if self.redis_cache.is_some() {
+            for period in CACHED_PERIODS {
+                let starts_on = period.start(today);
+                let ends_before = starts_on.and_then(|start| period.end(start));
+                for kind in LeaderboardKind::ALL {
+                    let scores = self
+                        .scores(kind, starts_on, ends_before, Some(MAX_LEADERBOARD_SIZE))
+                        .await?;
+                    self.cache_scores(kind, period, starts_on, &scores).await;
+                }
+            }
+        }
+
+        Ok(rolled_up)
+    }
+
+    // Store the final standings of a finished period. Returns whether it was rolled up now,
+    // rather than before.
+    async fn roll_up(
+        &self,
+        period: LeaderboardPeriod,
+        start: NaiveDate,
+    ) -> Result<bool, sqlx::Error> {
+        if self.is_rolled_up(period, start).await? {
+            return Ok(false);
+        }
+
+        let end = period.end(start);
+        let mut standings = Vec::new();
+        for kind in LeaderboardKind::ALL {
+            let scores = self.scores(kind, Some(start), end, None).await?;
+            standings.push((kind, scores));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let marked = sqlx::query(
+            r#"
+            INSERT INTO global.leaderboard_periods (blog_id, period, starts_on)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(period.as_str())
+        .bind(start)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        if !marked {
+            // Rolled up by another run in the meantime
+            tx.rollback().await?;
+            return Ok(false);
+        }
+
+        for (kind, scores) in standings {
+            let (user_ids, points): (Vec<Uuid>, Vec<i64>) = scores.into_iter().unzip();
+            sqlx::query(
+                r#"
+                INSERT INTO global.leaderboard_rollups (
+                    blog_id, kind, period, starts_on, user_id, score
+                )
+                SELECT $1, $2, $3, $4, s.user_id, s.score
+                FROM UNNEST($5::UUID[], $6::BIGINT[]) AS s(user_id, score)
+                "#,
+            )
+            .bind(current_blog_id())
+            .bind(kind.as_str())
+            .bind(period.as_str())
+            .bind(start)
+            .bind(&user_ids)
+            .bind(&points)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        info!(
+            "Rolled up the leaderboards of the {} starting {} of blog {}",
+            period.as_str(),
+            start,
+            current_blog_id()
+        );
+        Ok(true)
+    }
+
+    async fn is_rolled_up(
+        &self,
+        period: LeaderboardPeriod,
+        start: NaiveDate,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM global.leaderboard_periods
+                WHERE blog_id = $1 AND period = $2 AND starts_on = $3
+            )
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(period.as_str())
+        .bind(start)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    // Scores of the current blog's users between two days, or of all time without them,
+    // highest first
+    async fn scores(
+        &self,
+        kind: LeaderboardKind,
+        starts_on: Option<NaiveDate>,
+        ends_before: Option<NaiveDate>,
+        limit: Option<i64>,
+    ) -> Result<Vec<(Uuid, i64)>, sqlx::Error> {
+        let query = match kind {
+            LeaderboardKind::Authors => sqlx::query(AUTHOR_SCORES_SQL),
+            LeaderboardKind::Commenters => sqlx::query(COMMENTER_SCORES_SQL),
+        }
+        .bind(current_blog_id())
+        .bind(starts_on.map(day_start))
+        .bind(ends_before.map(day_start))
+        .bind(limit);
+        let query = match kind {
+            LeaderboardKind::Authors => query
+                .bind(VIEW_POINTS)
+                .bind(LIKE_POINTS)
+                .bind(COMMENT_POINTS),
+            LeaderboardKind::Commenters => query,
+        };
+        let rows = timed("leaderboards.scores", query.fetch_all(&self.pool)).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("user_id"), row.get("score")))
+            .collect())
+    }
+
+    async fn rolled_up_scores(
+        &self,
+        kind: LeaderboardKind,
+        period: LeaderboardPeriod,
+        start: NaiveDate,
+        limit: i64,
+    ) -> Result<Vec<(Uuid, i64)>, sqlx::Error> {
+        sqlx::query_as::<_, (Uuid, i64)>(
+            r#"
+            SELECT user_id, score FROM global.leaderboard_rollups
+            WHERE blog_id = $1 AND kind = $2 AND period = $3 AND starts_on = $4
+            ORDER BY score DESC, user_id
+            LIMIT $5
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(kind.as_str())
+        .bind(period.as_str())
+        .bind(start)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // Top scores from Redis; `None` without Redis, when the leaderboard is not cached, or on
+    // errors, for them to be computed instead
+    async fn cached_scores(
+        &self,
+        kind: LeaderboardKind,
+        period: LeaderboardPeriod,
+        starts_on: Option<NaiveDate>,
+        limit: i64,
+    ) -> Option<Vec<(Uuid, i64)>> {
+        let cache = self.redis_cache.as_ref()?;
+        let key = leaderboard_key(kind, period, starts_on);
+        let result: Result<(bool, Vec<(String, f64)>), redis::RedisError> = async {
+            let mut conn = cache.connection().await?;
+            redis::pipe()
+                .exists(&key)
+                .zrevrange_withscores(&key, 0, limit as isize - 1)
+                .query_async(&mut conn)
+                .await
+        }
+        .await;
+
+        match result {
+            Ok((true, members)) => Some(
+                members
+                    .into_iter()
+                    .filter_map(|(member, score)| Some((member.parse().ok()?, score as i64)))
+                    .collect(),
+            ),
+            Ok((false, _)) => None,
+            Err(e) => {
+                warn!("Failed to read leaderboard from Redis: {}", e);
+                None
+            }
+        }
+    }
+
+    // Replace a cached leaderboard. An empty leaderboard is cached as a sorted set holding
+    // only a placeholder, so that it exists; it is never read as it has no user ID.
+    async fn cache_scores(
+        &self,
+        kind: LeaderboardKind,
+        period: LeaderboardPeriod,
+        starts_on: Option<NaiveDate>,
+        scores: &[(Uuid, i64)],
+    ) {
+        let Some(cache) = &self.redis_cache else {
+            return;
+        };
+        let key = leaderboard_key(kind, period, starts_on);
+        let mut members: Vec<(i64, String)> = scores
+            .iter()
+            .map(|(user_id, score)| (*score, user_id.to_string()))
+            .collect();
+        if members.is_empty() {
+            members.push((0, "empty".to_string()));
+        }
+
+        let result: Result<(), redis::RedisError> = async {
+            let mut conn = cache.connection().await?;
+            redis::pipe()
+                .atomic()
+                .del(&key)
+                .ignore()
+                .zadd_multiple(&key, &members)
+                .ignore()
+                .expire(&key, LEADERBOARD_CACHE_TTL_SECONDS)
+                .ignore()
+                .query_async(&mut conn)
+                .await
+        }
+        .await;
+        if let Err(e) = result {
+            error!("Failed to cache leaderboard: {}", e);
+        }
+    }
+
+    // Entries of the users with these scores, skipping users that no longer exist
+    async fn entries(
+        &self,
+        scores: Vec<(Uuid, i64)>,
+    ) -> Result<Vec<LeaderboardEntry>, sqlx::Error> {
+        let user_ids: Vec<Uuid> = scores.iter().map(|(user_id, _)| *user_id).collect();
+        let rows = sqlx::query(
+            r#"
+            SELECT id, username, global.user_avatar_url(avatar_url, email) AS avatar_url
+            FROM global.users
+            WHERE id = ANY($1)
+            "#,
+        )
+        .bind(&user_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut users: HashMap<Uuid, (String, Option<String>)> = rows
+            .into_iter()
+            .map(|row| (row.get("id"), (row.get("username"), row.get("avatar_url"))))
+            .collect();
+
+        let listed: Vec<(Uuid, i64, String, Option<String>)> = scores
+            .into_iter()
+            .filter_map(|(user_id, score)| {
+                let (username, avatar_url) = users.remove(&user_id)?;
+                Some((user_id, score, username, avatar_url))
+            })
+            .collect();
+        let ranks = ranks(
+            &listed
+                .iter()
+                .map(|(_, score, _, _)| *score)
+                .collect::<Vec<_>>(),
+        );
+
+        Ok(listed
+            .into_iter()
+            .zip(ranks)
+            .map(
+                |((user_id, score, username, avatar_url), rank)| LeaderboardEntry {
+                    rank,
+                    user_id,
+                    username,
+                    avatar_url,
+                    score,
+                },
+            )
+            .collect())
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let rolled_up = self.update_all().await?;
+        Ok(json!({ "rolled_up": rolled_up }))
+    }
+}
+
+impl JobHandler for LeaderboardService {
+    fn kind(&self) -> &'static str {
+        LEADERBOARD_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
@@ -0,0 +1,63 @@
+//! Backend of a realtime multi-tenant blog platform: posts, comments, notifications over
+//! WebSockets, analytics and recommendations, served over a REST API.
+//!
+//! The `realtime-blog-backend` binary serves the API; other programs can mount it too. Set up
+//! the services with [`AppState::from_config`], or [`AppState::new`] on an existing pool, start
+//! the background workers with [`AppState::start_background_tasks`], and serve the service
+//! returned by [`build_router`].
+
+pub mod activity;
+pub mod analytics;
+pub mod announcement;
+pub mod api_doc;
+pub mod app;
+pub mod audit;
+pub mod auth;
+pub mod block;
+pub mod cache;
+pub mod comment;
+pub mod consent;
+pub mod db;
+pub mod error_reporting;
+pub mod feature_flags;
+pub mod feedback;
+pub mod fields;
+pub mod fnv;
+pub mod ghost;
+pub mod health_monitor;
+pub mod hex;
+pub mod import;
+pub mod invite;
+pub mod jobs;
+pub mod leaderboard;
+pub mod like;
+pub mod link_preview;
+pub mod logging;
+pub mod mailer;
+pub mod markdown;
+pub mod media;
+pub mod membership;
+pub mod metrics;
+pub mod newsletter;
+pub mod notification;
+pub mod pagination;
+pub mod post;
+pub mod quota;
+pub mod rate_limit;
+pub mod reading_list;
+pub mod recommendations;
+pub mod reputation;
+pub mod retention;
+pub mod routes;
+pub mod seed;
+pub mod settings;
+pub mod sitemap;
+pub mod slug;
+pub mod storage;
+pub mod tag;
+pub mod tenant;
+pub mod tip;
+pub mod title_test;
+pub mod websocket;
+
+pub use app::{build_router, AppConfig, AppError, AppState};
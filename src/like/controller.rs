@@ -0,0 +1,147 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::like::model::LikeError;
+use crate::like::service::LikeService;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn like_error_response(e: LikeError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Like error: {:?}", e);
+    let status = match e {
+        LikeError::PostNotFound => StatusCode::NOT_FOUND,
+        LikeError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Like a post
+///
+/// Rate limited per user, with `X-RateLimit-*` headers on every response. Likes that follow
+/// rapid liking and unliking, or that come without a long enough read when the blog requires
+/// one, are kept but do not count toward the post's likes.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/like",
+    tag = "posts",
+    params(
+        ("id" = i64, Path, description = "ID of the post to like")
+    ),
+    responses(
+        (status = 200, description = "Post liked", body = LikeResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Post not found"),
+        (status = 429, description = "Rate limit exceeded", body = RateLimitExceededResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn like_post(
+    Path(post_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<LikeService>>,
+) -> Response {
+    match service.like_post(user.user_id, post_id).await {
+        Ok(like) => (StatusCode::OK, Json(like)).into_response(),
+        Err(e) => like_error_response(e),
+    }
+}
+
+/// Unlike a post
+#[utoipa::path(
+    delete,
+    path = "/api/posts/{id}/like",
+    tag = "posts",
+    params(
+        ("id" = i64, Path, description = "ID of the post to unlike")
+    ),
+    responses(
+        (status = 200, description = "Post unliked", body = LikeResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Post not found"),
+        (status = 429, description = "Rate limit exceeded", body = RateLimitExceededResponse),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn unlike_post(
+    Path(post_id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<LikeService>>,
+) -> Response {
+    match service.unlike_post(user.user_id, post_id).await {
+        Ok(like) => (StatusCode::OK, Json(like)).into_response(),
+        Err(e) => like_error_response(e),
+    }
+}
+
+/// List flagged like activity
+///
+/// Admin only. Newest first.
+#[utoipa::path(
+    get,
+    path = "/api/moderation/likes",
+    tag = "posts",
+    params(PageParams),
+    responses(
+        (status = 200, description = "Flagged like activity", body = Vec<LikeFlag>),
+        (status = 400, description = "Invalid pagination"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_like_flags(
+    Extension(user): Extension<AuthUser>,
+    OriginalUri(uri): OriginalUri,
+    State(service): State<Arc<LikeService>>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Only admins can review flagged likes" })),
+        )
+            .into_response();
+    }
+
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    };
+
+    match service.get_flags(&pagination).await {
+        Ok(flags) => (
+            StatusCode::OK,
+            pagination.headers(&uri, flags.len()),
+            Json(flags),
+        )
+            .into_response(),
+        Err(e) => like_error_response(e),
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::Duration;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// How likes are checked before they count toward the popularity of a post.
+///
+/// `LIKE_MIN_READ_SECONDS` makes only likes of readers who spent at least that long on the
+/// post, according to the views front-ends report with a `duration_ms`, count. Unset, every
+/// like counts.
+#[derive(Debug, Clone, Default)]
+pub struct LikeConfig {
+    pub min_read: Option<Duration>,
+}
+
+impl LikeConfig {
+    pub fn from_env() -> Self {
+        let min_read = std::env::var("LIKE_MIN_READ_SECONDS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs);
+        Self { min_read }
+    }
+
+    /// Shared config, read on first use
+    pub fn global() -> &'static LikeConfig {
+        static CONFIG: OnceLock<LikeConfig> = OnceLock::new();
+        CONFIG.get_or_init(LikeConfig::from_env)
+    }
+}
+
+/// Like state returned by the like/unlike endpoints
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LikeResponse {
+    #[schema(example = "123")]
+    pub post_id: i64,
+
+    /// Whether you now like the post
+    #[schema(example = "true")]
+    pub liked: bool,
+
+    /// Likes of the post
+    #[schema(example = "42")]
+    pub likes: i32,
+}
+
+/// Why like activity was flagged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LikeFlagReason {
+    /// The user liked and unliked the post over and over
+    Toggling,
+    /// The user liked the post without having read it for long enough
+    Unread,
+    /// The user's reputation is at or below `LOW_REPUTATION_THRESHOLD`
+    LowReputation,
+}
+
+impl LikeFlagReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LikeFlagReason::Toggling => "toggling",
+            LikeFlagReason::Unread => "unread",
+            LikeFlagReason::LowReputation => "low_reputation",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "toggling" => Some(LikeFlagReason::Toggling),
+            "unread" => Some(LikeFlagReason::Unread),
+            "low_reputation" => Some(LikeFlagReason::LowReputation),
+            _ => None,
+        }
+    }
+}
+
+/// Like activity flagged for moderators. Flagged likes do not count toward popularity.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LikeFlag {
+    #[schema(example = "7")]
+    pub id: i64,
+    pub user_id: Uuid,
+    #[schema(example = "jane")]
+    pub username: String,
+    #[schema(example = "123")]
+    pub post_id: i64,
+    pub reason: LikeFlagReason,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LikeError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Post not found")]
+    PostNotFound,
+}
@@ -0,0 +1,332 @@
+use crate::analytics::service::AnalyticsService;
+use crate::cache::redis::RedisCache;
+use crate::db::instrument::timed;
+use crate::db::retry::with_retry;
+use crate::like::model::{LikeConfig, LikeError, LikeFlag, LikeFlagReason, LikeResponse};
+use crate::pagination::Pagination;
+use crate::reputation::model::ReputationConfig;
+use crate::reputation::service::{queue_reputation_update, user_reputation};
+use crate::tenant::middleware::current_blog_id;
+use sqlx::{PgConnection, PgPool, Row};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+// Likes and unlikes of one post a user may make within the window before further likes
+// stop counting and the user is flagged
+const MAX_LIKE_TOGGLES: i64 = 4;
+const LIKE_TOGGLE_WINDOW_SECONDS: i64 = 600;
+
+fn toggles_key(user_id: Uuid, post_id: i64) -> String {
+    format!("like_toggles:{}:{}", user_id, post_id)
+}
+
+/// Likes of posts, and the heuristics that keep bots from gaming popularity with them.
+///
+/// Every like is kept, but only likes that pass the heuristics count toward the `likes` of
+/// a post and so its popularity: likes that follow rapid liking and unliking of the same post
+/// do not, and neither do likes without a long enough read when `LIKE_MIN_READ_SECONDS` is
+/// set or likes by users of low reputation. All are flagged for moderators. Toggles are
+/// counted in Redis; without it they are not detected.
+#[derive(Clone)]
+pub struct LikeService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+    analytics: Arc<AnalyticsService>,
+}
+
+impl LikeService {
+    pub fn new(
+        pool: PgPool,
+        redis_cache: Option<RedisCache>,
+        analytics: Arc<AnalyticsService>,
+    ) -> Self {
+        Self {
+            pool,
+            redis_cache,
+            analytics,
+        }
+    }
+
+    /// Like a post. Liking a post again is a no-op.
+    pub async fn like_post(&self, user_id: Uuid, post_id: i64) -> Result<LikeResponse, LikeError> {
+        with_retry("likes.like", || self.try_like_post(user_id, post_id)).await
+    }
+
+    // Once the like is in, running this again finds it and changes nothing, so it can be
+    // retried as a whole
+    async fn try_like_post(&self, user_id: Uuid, post_id: i64) -> Result<LikeResponse, LikeError> {
+        let (slug, author_id) = self.find_post(post_id).await?;
+
+        let liked = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM global.post_likes WHERE post_id = $1 AND user_id = $2)",
+        )
+        .bind(post_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        if liked {
+            return self.like_response(post_id, true).await;
+        }
+
+        let toggling = self.count_toggle(user_id, post_id).await;
+        let read = self.has_read(user_id, post_id).await;
+        let reputable = self.is_reputable(user_id).await;
+        let counted = toggling.is_none() && read && reputable;
+
+        let mut tx = self.pool.begin().await?;
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO global.post_likes (post_id, user_id, counted)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (post_id, user_id) DO NOTHING
+            "#,
+        )
+        .bind(post_id)
+        .bind(user_id)
+        .bind(counted)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if inserted {
+            if counted {
+                sqlx::query("UPDATE global.posts SET likes = likes + 1 WHERE id = $1")
+                    .bind(post_id)
+                    .execute(&mut *tx)
+                    .await?;
+            } else if !read {
+                flag(&mut tx, user_id, post_id, LikeFlagReason::Unread).await?;
+            } else if !reputable {
+                flag(&mut tx, user_id, post_id, LikeFlagReason::LowReputation).await?;
+            }
+            if toggling == Some(true) {
+                flag(&mut tx, user_id, post_id, LikeFlagReason::Toggling).await?;
+            }
+        }
+        tx.commit().await?;
+
+        if inserted {
+            info!(
+                "User {} liked post {} (counted: {})",
+                user_id, post_id, counted
+            );
+            if counted {
+                self.invalidate_post(post_id, &slug).await;
+                queue_reputation_update(&self.pool, &[author_id]).await;
+            }
+            if let Err(e) = self
+                .analytics
+                .record_interaction(Some(user_id), "like", Some(post_id), None, None)
+                .await
+            {
+                error!("Failed to record like of post {}: {}", post_id, e);
+            }
+        }
+
+        self.like_response(post_id, true).await
+    }
+
+    /// Take back a like. Unliking a post that is not liked is a no-op.
+    pub async fn unlike_post(
+        &self,
+        user_id: Uuid,
+        post_id: i64,
+    ) -> Result<LikeResponse, LikeError> {
+        with_retry("likes.unlike", || self.try_unlike_post(user_id, post_id)).await
+    }
+
+    async fn try_unlike_post(
+        &self,
+        user_id: Uuid,
+        post_id: i64,
+    ) -> Result<LikeResponse, LikeError> {
+        let (slug, author_id) = self.find_post(post_id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        let counted = sqlx::query_scalar::<_, bool>(
+            "DELETE FROM global.post_likes WHERE post_id = $1 AND user_id = $2 RETURNING counted",
+        )
+        .bind(post_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(counted) = counted else {
+            tx.rollback().await?;
+            return self.like_response(post_id, false).await;
+        };
+
+        if counted {
+            sqlx::query("UPDATE global.posts SET likes = GREATEST(likes - 1, 0) WHERE id = $1")
+                .bind(post_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        if self.count_toggle(user_id, post_id).await == Some(true) {
+            flag(&mut tx, user_id, post_id, LikeFlagReason::Toggling).await?;
+        }
+        tx.commit().await?;
+
+        info!("User {} unliked post {}", user_id, post_id);
+        if counted {
+            self.invalidate_post(post_id, &slug).await;
+            queue_reputation_update(&self.pool, &[author_id]).await;
+        }
+
+        self.like_response(post_id, false).await
+    }
+
+    /// Flagged like activity of the blog, newest first
+    pub async fn get_flags(&self, pagination: &Pagination) -> Result<Vec<LikeFlag>, LikeError> {
+        let query = sqlx::query(
+            r#"
+            SELECT f.id, f.user_id, u.username, f.post_id, f.reason, f.created_at
+            FROM global.like_flags f
+            JOIN global.users u ON u.id = f.user_id
+            WHERE f.blog_id = $1
+            ORDER BY f.created_at DESC, f.id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(pagination.limit)
+        .bind(pagination.offset)
+        .fetch_all(&self.pool);
+        let rows = timed("likes.flags", query).await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let reason = LikeFlagReason::parse(row.get("reason"))?;
+                Some(LikeFlag {
+                    id: row.get("id"),
+                    user_id: row.get("user_id"),
+                    username: row.get("username"),
+                    post_id: row.get("post_id"),
+                    reason,
+                    created_at: row.get("created_at"),
+                })
+            })
+            .collect())
+    }
+
+    // Slug and author of a published post of the blog, the only posts that can be liked
+    async fn find_post(&self, post_id: i64) -> Result<(String, Uuid), LikeError> {
+        sqlx::query_as::<_, (String, Uuid)>(
+            r#"
+            SELECT slug, user_id FROM global.posts
+            WHERE id = $1 AND blog_id = $2 AND is_deleted = false AND is_draft = false
+            "#,
+        )
+        .bind(post_id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(LikeError::PostNotFound)
+    }
+
+    async fn like_response(&self, post_id: i64, liked: bool) -> Result<LikeResponse, LikeError> {
+        let likes = sqlx::query_scalar::<_, i32>("SELECT likes FROM global.posts WHERE id = $1")
+            .bind(post_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(LikeResponse {
+            post_id,
+            liked,
+            likes,
+        })
+    }
+
+    // Count a like or unlike of the post by the user. `None` while the user is within the
+    // allowed toggles (or without Redis), `Some(true)` the first time they go beyond them,
+    // when they are flagged, and `Some(false)` after that.
+    async fn count_toggle(&self, user_id: Uuid, post_id: i64) -> Option<bool> {
+        let cache = self.redis_cache.as_ref()?;
+        let key = toggles_key(user_id, post_id);
+        let result: Result<(i64,), redis::RedisError> = async {
+            let mut conn = cache.connection().await?;
+            redis::pipe()
+                .atomic()
+                .incr(&key, 1)
+                .cmd("EXPIRE")
+                .arg(&key)
+                .arg(LIKE_TOGGLE_WINDOW_SECONDS)
+                .arg("NX")
+                .ignore()
+                .query_async(&mut conn)
+                .await
+        }
+        .await;
+
+        match result {
+            Ok((toggles,)) if toggles > MAX_LIKE_TOGGLES => Some(toggles == MAX_LIKE_TOGGLES + 1),
+            Ok(_) => None,
+            Err(e) => {
+                error!("Failed to count like toggles: {}", e);
+                None
+            }
+        }
+    }
+
+    // Whether the user read the post long enough for their like to count. Failures to look
+    // it up let the like count, rather than penalizing readers for an outage.
+    async fn has_read(&self, user_id: Uuid, post_id: i64) -> bool {
+        let min_read = match LikeConfig::global().min_read {
+            Some(min_read) => min_read,
+            None => return true,
+        };
+
+        match self.analytics.longest_read(user_id, post_id).await {
+            Ok(longest) => longest.is_some_and(|longest| longest >= min_read),
+            Err(e) => {
+                warn!("Failed to look up reads of post {}: {}", post_id, e);
+                true
+            }
+        }
+    }
+
+    // Whether the user's reputation is high enough for their likes to count. Failures to look
+    // it up let the like count.
+    async fn is_reputable(&self, user_id: Uuid) -> bool {
+        match user_reputation(&self.pool, user_id).await {
+            Ok(points) => points > ReputationConfig::global().low_reputation,
+            Err(e) => {
+                warn!("Failed to look up reputation of user {}: {}", user_id, e);
+                true
+            }
+        }
+    }
+
+    async fn invalidate_post(&self, post_id: i64, slug: &str) {
+        if let Some(cache) = &self.redis_cache {
+            if let Err(e) = cache.invalidate_post(post_id, slug).await {
+                error!("Failed to invalidate cache of post {}: {}", post_id, e);
+            }
+        }
+    }
+}
+
+async fn flag(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    post_id: i64,
+    reason: LikeFlagReason,
+) -> Result<(), sqlx::Error> {
+    warn!(
+        "Flagged likes of post {} by user {}: {}",
+        post_id,
+        user_id,
+        reason.as_str()
+    );
+    sqlx::query(
+        "INSERT INTO global.like_flags (blog_id, user_id, post_id, reason) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(current_blog_id())
+    .bind(user_id)
+    .bind(post_id)
+    .bind(reason.as_str())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
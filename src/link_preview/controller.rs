@@ -0,0 +1,65 @@
+use crate::link_preview::model::LinkPreviewParams;
+use crate::link_preview::service::LinkPreviewService;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use reqwest::Url;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Longest URL accepted for previewing
+const MAX_PREVIEW_URL_LEN: usize = 2048;
+
+/// Get the preview card of a URL
+///
+/// Previews are fetched in the background. When one is not ready yet its URL is queued and
+/// `202 Accepted` is returned; ask again shortly. A preview without title, description or image
+/// means the page could not be previewed and should be rendered as a plain link. Signed-in
+/// users only, and rate limited, since a miss makes the server fetch the page.
+#[utoipa::path(
+    get,
+    path = "/api/link-previews",
+    tag = "link-previews",
+    params(LinkPreviewParams),
+    responses(
+        (status = 200, description = "Preview of the page", body = LinkPreview),
+        (status = 202, description = "Preview is being fetched", body = Object,
+            example = json!({"status": "pending"})),
+        (status = 400, description = "Not an http(s) URL"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Rate limit exceeded", body = RateLimitExceededResponse),
+        (status = 503, description = "Link previews are disabled")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_link_preview(
+    Query(params): Query<LinkPreviewParams>,
+    State(service): State<Arc<LinkPreviewService>>,
+) -> Response {
+    let is_http_url = params.url.len() <= MAX_PREVIEW_URL_LEN
+        && Url::parse(&params.url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+    if !is_http_url {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Expected an http or https URL" })),
+        )
+            .into_response();
+    }
+
+    if !service.is_enabled() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "Link previews are disabled" })),
+        )
+            .into_response();
+    }
+
+    match service.get_preview(&params.url).await {
+        Some(preview) => (StatusCode::OK, Json(preview)).into_response(),
+        None => (StatusCode::ACCEPTED, Json(json!({ "status": "pending" }))).into_response(),
+    }
+}
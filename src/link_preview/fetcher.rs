@@ -0,0 +1,366 @@
+use crate::link_preview::model::{LinkPreview, LinkPreviewError};
+use reqwest::{header, redirect::Policy, Url};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// Most URLs taken from one post or comment
+pub const MAX_URLS_PER_CONTENT: usize = 10;
+/// Longest URL that is previewed
+const MAX_URL_LEN: usize = 2048;
+/// Total time allowed for fetching one page, redirects included
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bytes of a page read at most; metadata lives in `<head>`, so this is plenty
+const MAX_BODY_BYTES: usize = 512 * 1024;
+/// Redirects followed at most, each one validated like the original URL
+const MAX_REDIRECTS: usize = 3;
+const MAX_TITLE_CHARS: usize = 300;
+const MAX_DESCRIPTION_CHARS: usize = 500;
+
+/// Bare http(s) URLs in post or comment content, without duplicates
+pub fn extract_urls(content: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = ["http://", "https://"]
+        .iter()
+        .filter_map(|scheme| rest.find(scheme))
+        .min()
+    {
+        let candidate = &rest[start..];
+        let end = candidate
+            .find(|c: char| c.is_whitespace() || "<>\"'()[]{}`".contains(c))
+            .unwrap_or(candidate.len());
+        let url = candidate[..end].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+
+        if url.len() <= MAX_URL_LEN && !urls.iter().any(|seen| seen == url) {
+            urls.push(url.to_string());
+            if urls.len() == MAX_URLS_PER_CONTENT {
+                break;
+            }
+        }
+        rest = &candidate[end..];
+    }
+
+    urls
+}
+
+/// Whether an address may be fetched: anything private, local or reserved is refused so
+/// previews cannot be used to reach internal services
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || a == 0
+        // Carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    let first = segments[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // IPv4-compatible (deprecated), which some stacks still route to the IPv4 address
+        || segments[..6].iter().all(|segment| *segment == 0)
+        // NAT64, which a DNS64 resolver may hand out for private IPv4 addresses
+        || (first == 0x64 && segments[1] == 0xff9b)
+        // Unique local
+        || (first & 0xfe00) == 0xfc00
+        // Link local
+        || (first & 0xffc0) == 0xfe80
+        // Documentation
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// Value of an attribute in the text of one tag, e.g. `property="og:title" content="..."`
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search_from = 0;
+
+    while let Some(found) = lower[search_from..].find(name) {
+        let at = search_from + found;
+        search_from = at + name.len();
+
+        // Must be a whole attribute name followed by `=`
+        let preceded_by_space = lower[..at].ends_with(|c: char| c.is_ascii_whitespace());
+        let after = lower[search_from..].trim_start();
+        if !preceded_by_space || !after.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - after.len() + 1;
+        let value = tag[value_start..].trim_start();
+        return match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next(),
+            Some(_) => value.split(|c: char| c.is_whitespace() || c == '>').next(),
+            None => None,
+        };
+    }
+
+    None
+}
+
+fn clean_text(text: &str, max_chars: usize) -> Option<String> {
+    let text = html_escape::decode_html_entities(text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.chars().take(max_chars).collect())
+    }
+}
+
+/// Read the preview metadata from a page's HTML
+pub fn parse_metadata(page_url: &Url, html: &str) -> LinkPreview {
+    let lower = html.to_ascii_lowercase();
+    let mut preview = LinkPreview {
+        url: page_url.to_string(),
+        ..LinkPreview::default()
+    };
+    let mut meta_description = None;
+
+    let mut search_from = 0;
+    while let Some(found) = lower[search_from..].find("<meta") {
+        let start = search_from + found;
+        let end = lower[start..]
+            .find('>')
+            .map_or(lower.len(), |end| start + end);
+        let tag = &html[start..end];
+        search_from = end;
+
+        let key = attribute(tag, "property")
+            .or_else(|| attribute(tag, "name"))
+            .map(str::to_ascii_lowercase);
+        let content = match attribute(tag, "content") {
+            Some(content) => content,
+            None => continue,
+        };
+
+        match key.as_deref() {
+            Some("og:title") => preview.title = clean_text(content, MAX_TITLE_CHARS),
+            Some("og:description") => {
+                preview.description = clean_text(content, MAX_DESCRIPTION_CHARS)
+            }
+            Some("description") => meta_description = clean_text(content, MAX_DESCRIPTION_CHARS),
+            Some("og:site_name") => preview.site_name = clean_text(content, MAX_TITLE_CHARS),
+            Some("og:image") => {
+                preview.image = page_url
+                    .join(content.trim())
+                    .ok()
+                    .filter(|image| matches!(image.scheme(), "http" | "https"))
+                    .map(|image| image.to_string())
+            }
+            _ => {}
+        }
+    }
+
+    if preview.description.is_none() {
+        preview.description = meta_description;
+    }
+    if preview.title.is_none() {
+        if let Some(start) = lower.find("<title") {
+            let title = lower[start..]
+                .find('>')
+                .map(|open| start + open + 1)
+                .and_then(|text_start| {
+                    lower[text_start..]
+                        .find("</title")
+                        .map(|len| &html[text_start..text_start + len])
+                });
+            preview.title = title.and_then(|title| clean_text(title, MAX_TITLE_CHARS));
+        }
+    }
+
+    preview
+}
+
+/// Resolve a URL's host and check every address it resolves to
+async fn resolve_public(url: &Url) -> Result<(String, SocketAddr), LinkPreviewError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(LinkPreviewError::InvalidUrl(url.to_string()));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| LinkPreviewError::InvalidUrl(url.to_string()))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| LinkPreviewError::InvalidUrl(url.to_string()))?;
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| LinkPreviewError::InvalidUrl(url.to_string()))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(LinkPreviewError::InvalidUrl(url.to_string()));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(LinkPreviewError::ForbiddenAddress(addr.ip().to_string()));
+    }
+
+    Ok((host.to_string(), addrs[0]))
+}
+
+/// Fetch a page and read its preview metadata.
+///
+/// The host is resolved and checked before connecting, and the connection is pinned to the
+/// checked address so a second DNS answer cannot point it elsewhere. Redirects are followed by
+/// hand so every hop goes through the same checks.
+pub async fn fetch_preview(url: &str) -> Result<LinkPreview, LinkPreviewError> {
+    let mut url = Url::parse(url).map_err(|_| LinkPreviewError::InvalidUrl(url.to_string()))?;
+
+    tokio::time::timeout(FETCH_TIMEOUT, async {
+        for _ in 0..=MAX_REDIRECTS {
+            let (host, addr) = resolve_public(&url).await?;
+            let client = reqwest::Client::builder()
+                .redirect(Policy::none())
+                // A proxy would connect on our behalf and bypass the address checks
+                .no_proxy()
+                .timeout(FETCH_TIMEOUT)
+                .resolve(&host, addr)
+                .user_agent("RealtimeBlogLinkPreview/1.0")
+                .build()?;
+
+            let mut response = client
+                .get(url.clone())
+                .header(header::ACCEPT, "text/html")
+                .send()
+                .await?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .ok_or_else(|| {
+                        LinkPreviewError::UnexpectedResponse("redirect without location".into())
+                    })?;
+                url = url
+                    .join(location)
+                    .map_err(|_| LinkPreviewError::InvalidUrl(location.to_string()))?;
+                continue;
+            }
+            if !response.status().is_success() {
+                return Err(LinkPreviewError::UnexpectedResponse(
+                    response.status().to_string(),
+                ));
+            }
+
+            let is_html = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .is_some_and(|content_type| content_type.contains("text/html"));
+            if !is_html {
+                return Err(LinkPreviewError::UnexpectedResponse(
+                    "not an HTML page".to_string(),
+                ));
+            }
+
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                body.extend_from_slice(&chunk);
+                if body.len() >= MAX_BODY_BYTES {
+                    body.truncate(MAX_BODY_BYTES);
+                    break;
+                }
+            }
+
+            return Ok(parse_metadata(&url, &String::from_utf8_lossy(&body)));
+        }
+
+        Err(LinkPreviewError::TooManyRedirects)
+    })
+    .await
+    .map_err(|_| LinkPreviewError::UnexpectedResponse("timed out".to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_urls() {
+        let content = "See https://example.com/a, and [docs](https://docs.rs/tokio). \
+            Again https://example.com/a and http://localhost:8080/x!";
+        assert_eq!(
+            extract_urls(content),
+            vec![
+                "https://example.com/a",
+                "https://docs.rs/tokio",
+                "http://localhost:8080/x"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::a00:1",
+            "64:ff9b:1::a00:1",
+        ] {
+            assert!(
+                !is_public_ip(ip.parse().unwrap()),
+                "{} should be blocked",
+                ip
+            );
+        }
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:4700::1111".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let url = Url::parse("https://example.com/posts/1").unwrap();
+        let html = r#"<html><head>
+            <title>Fallback</title>
+            <meta property="og:title" content="Tom &amp; Jerry">
+            <META NAME="description" CONTENT='A classic'>
+            <meta property="og:image" content="/cover.png" />
+            </head></html>"#;
+
+        let preview = parse_metadata(&url, html);
+        assert_eq!(preview.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(preview.description.as_deref(), Some("A classic"));
+        assert_eq!(
+            preview.image.as_deref(),
+            Some("https://example.com/cover.png")
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Metadata of a linked page, for rendering it as a rich link card
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LinkPreview {
+    /// The linked URL
+    #[schema(example = "https://www.rust-lang.org/")]
+    pub url: String,
+
+    /// Page title, from `og:title` or `<title>`
+    #[schema(example = "Rust Programming Language")]
+    pub title: Option<String>,
+
+    /// Page description, from `og:description` or `description`
+    pub description: Option<String>,
+
+    /// Absolute URL of the preview image, from `og:image`
+    pub image: Option<String>,
+
+    /// Name of the site, from `og:site_name`
+    pub site_name: Option<String>,
+}
+
+impl LinkPreview {
+    /// Whether the page had nothing worth showing, e.g. because it could not be fetched
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none() && self.image.is_none()
+    }
+}
+
+/// Query parameters of a link preview lookup
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LinkPreviewParams {
+    /// URL found in a post or comment
+    pub url: String,
+}
+
+/// Why a page could not be previewed
+#[derive(Debug, thiserror::Error)]
+pub enum LinkPreviewError {
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Address not allowed: {0}")]
+    ForbiddenAddress(String),
+
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+
+    #[error("Too many redirects")]
+    TooManyRedirects,
+}
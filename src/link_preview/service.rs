@@ -0,0 +1,159 @@
+use crate::cache::redis::RedisCache;
+use crate::link_preview::fetcher::{extract_urls, fetch_preview};
+use crate::link_preview::model::LinkPreview;
+use redis::AsyncCommands;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{debug, error, warn};
+
+// Pages change rarely, and a stale card is harmless
+const LINK_PREVIEW_CACHE_TTL: u64 = 86400;
+// Pages that could not be previewed are retried sooner, in case the failure was transient
+const EMPTY_PREVIEW_CACHE_TTL: u64 = 3600;
+const LINK_PREVIEW_QUEUE_CAPACITY: usize = 1000;
+const MAX_CONCURRENT_FETCHES: usize = 4;
+
+/// Previews are keyed by URL alone; the same page looks the same on every blog
+fn preview_cache_key(url: &str) -> String {
+    format!("link_preview:{}", url)
+}
+
+/// Resolves link previews in the background.
+///
+/// Requests never wait on a remote page: a preview is served from Redis when it is there, and
+/// otherwise its URL is queued for the fetcher and the caller asks again later. Without Redis
+/// there is nowhere to keep results, so previews are disabled.
+#[derive(Clone)]
+pub struct LinkPreviewService {
+    redis_cache: Option<RedisCache>,
+    sender: mpsc::Sender<String>,
+    /// URLs queued or being fetched, so a popular link is only fetched once
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl LinkPreviewService {
+    /// Spawn the fetcher task and return a handle to it
+    pub fn start(redis_cache: Option<RedisCache>) -> Self {
+        let (sender, receiver) = mpsc::channel(LINK_PREVIEW_QUEUE_CAPACITY);
+        let service = Self {
+            redis_cache,
+            sender,
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+        };
+
+        if service.is_enabled() {
+            tokio::spawn(service.clone().run(receiver));
+        } else {
+            warn!("Redis is not configured, link previews are disabled");
+        }
+        service
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.redis_cache.is_some()
+    }
+
+    /// Cached preview of a URL. On a miss the URL is queued and `None` returned.
+    ///
+    /// A page that could not be previewed is cached as an empty preview, which is returned
+    /// as is so callers can fall back to a plain link.
+    pub async fn get_preview(&self, url: &str) -> Option<LinkPreview> {
+        let cache = self.redis_cache.as_ref()?;
+
+        if let Ok(mut conn) = cache.connection().await {
+            if let Ok(Some(cached_data)) =
+                conn.get::<_, Option<String>>(preview_cache_key(url)).await
+            {
+                match serde_json::from_str::<LinkPreview>(&cached_data) {
+                    Ok(preview) => return Some(preview),
+                    Err(e) => error!("Failed to deserialize cached link preview: {}", e),
+                }
+            }
+        }
+
+        self.enqueue(url);
+        None
+    }
+
+    /// Queue the URLs in a post or comment, so their previews are ready by the time it is read
+    pub fn enqueue_from_content(&self, content: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        for url in extract_urls(content) {
+            self.enqueue(&url);
+        }
+    }
+
+    fn enqueue(&self, url: &str) {
+        if !self.is_enabled() || !self.in_flight.lock().unwrap().insert(url.to_string()) {
+            return;
+        }
+        if let Err(e) = self.sender.try_send(url.to_string()) {
+            warn!("Dropping link preview fetch for {}: {}", url, e);
+            self.in_flight.lock().unwrap().remove(url);
+        }
+    }
+
+    async fn run(self, mut receiver: mpsc::Receiver<String>) {
+        let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES));
+
+        while let Some(url) = receiver.recv().await {
+            let permit = match permits.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return,
+            };
+            let service = self.clone();
+            tokio::spawn(async move {
+                service.resolve(&url).await;
+                service.in_flight.lock().unwrap().remove(&url);
+                drop(permit);
+            });
+        }
+    }
+
+    /// Fetch one page and cache its preview, or an empty one if it could not be fetched
+    async fn resolve(&self, url: &str) {
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            None => return,
+        };
+
+        // Queued URLs may already have been resolved through another post
+        let mut conn = match cache.connection().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to connect to Redis for link preview: {}", e);
+                return;
+            }
+        };
+        if let Ok(true) = conn.exists::<_, bool>(preview_cache_key(url)).await {
+            return;
+        }
+
+        let preview = match fetch_preview(url).await {
+            Ok(preview) => preview,
+            Err(e) => {
+                debug!("Could not preview {}: {}", url, e);
+                LinkPreview {
+                    url: url.to_string(),
+                    ..LinkPreview::default()
+                }
+            }
+        };
+        let ttl = if preview.is_empty() {
+            EMPTY_PREVIEW_CACHE_TTL
+        } else {
+            LINK_PREVIEW_CACHE_TTL
+        };
+
+        let json_data = serde_json::to_string(&preview).unwrap_or_default();
+        if let Err(e) = conn
+            .set_ex::<_, _, ()>(preview_cache_key(url), json_data, ttl)
+            .await
+        {
+            error!("Failed to cache link preview for {}: {}", url, e);
+        }
+    }
+}
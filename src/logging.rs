@@ -0,0 +1,250 @@
+use axum::{
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use std::sync::OnceLock;
+use std::time::Instant;
+use tracing::{field, info, info_span, Instrument};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+use uuid::Uuid;
+
+/// Header carrying the id of a request, taken from the client or proxy when valid and
+/// returned on every response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Longest request id accepted from a client; longer ids are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// Identifies the request being handled, for reports made outside of the logger
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+    pub method: String,
+    pub path: String,
+    /// Set once the request is authenticated
+    pub user_id: OnceLock<Uuid>,
+}
+
+/// How log lines are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Logging configuration, read from the environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// Filter directives, such as `info,sqlx=warn`
+    pub directives: String,
+    /// OTLP gRPC endpoint spans are exported to, if any
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+}
+
+/// Errors raised while setting up logging
+#[derive(Debug, thiserror::Error)]
+pub enum LoggingError {
+    #[error("Invalid log filter: {0}")]
+    InvalidFilter(#[from] tracing_subscriber::filter::ParseError),
+
+    #[error("Failed to set up the OTLP exporter: {0}")]
+    Exporter(#[from] opentelemetry::trace::TraceError),
+
+    #[error("Failed to install the logger: {0}")]
+    Init(#[from] tracing_subscriber::util::TryInitError),
+}
+
+impl LogConfig {
+    /// Read the configuration:
+    ///
+    /// - `LOG_FORMAT`: `text` (default) or `json`
+    /// - `LOG_LEVEL`: default level, `info` unless set
+    /// - `LOG_MODULE_LEVELS`: comma-separated per-module overrides, e.g. `sqlx=warn`
+    /// - `RUST_LOG`: full filter directives, replacing the two above when set
+    /// - `OTEL_EXPORTER_OTLP_ENDPOINT`: export spans over OTLP, e.g. `http://localhost:4317`
+    /// - `OTEL_SERVICE_NAME`: service name spans are exported under
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        let format = match var("LOG_FORMAT").as_deref() {
+            Some("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        };
+        let directives = var("RUST_LOG").unwrap_or_else(|| {
+            filter_directives(
+                var("LOG_LEVEL").as_deref().unwrap_or("info"),
+                var("LOG_MODULE_LEVELS").as_deref(),
+            )
+        });
+
+        Self {
+            format,
+            directives,
+            otlp_endpoint: var("OTEL_EXPORTER_OTLP_ENDPOINT"),
+            service_name: var("OTEL_SERVICE_NAME")
+                .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string()),
+        }
+    }
+}
+
+/// Filter directives of a default level followed by per-module overrides
+pub fn filter_directives(level: &str, module_levels: Option<&str>) -> String {
+    std::iter::once(level.trim())
+        .chain(module_levels.unwrap_or_default().split(',').map(str::trim))
+        .filter(|directive| !directive.is_empty())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Install the global logger as configured in the environment
+pub fn init() -> Result<(), LoggingError> {
+    let config = LogConfig::from_env();
+    let filter = EnvFilter::try_new(&config.directives)?;
+
+    // JSON lines carry the fields of the request span, such as the request and user id
+    let fmt_layer = match config.format {
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+    };
+
+    let otlp_layer = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(trace::config().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", config.service_name.clone()),
+                ])))
+                .install_batch(runtime::Tokio)?;
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otlp_layer)
+        .try_init()?;
+
+    if let Some(endpoint) = &config.otlp_endpoint {
+        info!("Exporting traces to {}", endpoint);
+    }
+    Ok(())
+}
+
+/// Flush spans not yet exported before the process exits
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+// A request id sent by a client, if it is safe to log and echo back
+fn accepted_request_id(value: &str) -> Option<&str> {
+    let valid = !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    valid.then_some(value)
+}
+
+/// Middleware that runs each request in a `request` span, so every log line written while
+/// handling it carries the request id and, once authenticated, the user id.
+///
+/// The id is taken from the `X-Request-Id` header when a client or proxy sent a valid one and
+/// generated otherwise, and is returned in the same header.
+pub async fn request_span<B>(req: Request<B>, next: Next<B>) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(accepted_request_id)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+        user_id = field::Empty,
+    );
+
+    let context = RequestContext {
+        request_id: request_id.clone(),
+        method: req.method().to_string(),
+        path: req.uri().path().to_string(),
+        user_id: OnceLock::new(),
+    };
+
+    let started = Instant::now();
+    let work = async move {
+        let response = next.run(req).await;
+        info!(
+            status = response.status().as_u16(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "Request finished"
+        );
+        response
+    };
+    let mut response = REQUEST_CONTEXT.scope(context, work.instrument(span)).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Record the authenticated user on the span and context of the current request
+pub fn record_user_id(user_id: Uuid) {
+    tracing::Span::current().record("user_id", field::display(user_id));
+    let _ = REQUEST_CONTEXT.try_with(|context| context.user_id.set(user_id));
+}
+
+/// Context of the request being handled, if any
+pub fn current_request() -> Option<RequestContext> {
+    REQUEST_CONTEXT.try_with(|context| context.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_directives() {
+        assert_eq!(filter_directives("info", None), "info");
+        assert_eq!(
+            filter_directives(
+                "warn",
+                Some(" sqlx=error, realtime_blog_backend::jobs=debug,")
+            ),
+            "warn,sqlx=error,realtime_blog_backend::jobs=debug"
+        );
+    }
+
+    #[test]
+    fn test_accepted_request_id() {
+        assert_eq!(accepted_request_id("abc-123_x.y"), Some("abc-123_x.y"));
+        assert_eq!(accepted_request_id(""), None);
+        assert_eq!(accepted_request_id("id\nforged log line"), None);
+        assert_eq!(accepted_request_id(&"a".repeat(129)), None);
+    }
+}
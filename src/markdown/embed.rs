@@ -0,0 +1,480 @@
+use super::{render_code_block, HighlightConfig};
+use futures::future::join_all;
+use reqwest::{header, Url};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+const DEFAULT_PROVIDERS: &str = "youtube,twitter,gist";
+/// Most gists fetched for one post or comment
+const MAX_GISTS_PER_CONTENT: usize = 5;
+/// Files of a gist shown at most
+const MAX_GIST_FILES: usize = 10;
+/// Bytes of gist code shown at most; files past it are left out
+const MAX_GIST_BYTES: usize = 100 * 1024;
+/// Bytes of a gist API response read at most
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sites whose URLs may become embeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedProvider {
+    YouTube,
+    Twitter,
+    Gist,
+}
+
+impl EmbedProvider {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "youtube" => Some(EmbedProvider::YouTube),
+            "twitter" | "x" => Some(EmbedProvider::Twitter),
+            "gist" => Some(EmbedProvider::Gist),
+            _ => None,
+        }
+    }
+}
+
+/// Providers embedded in this deployment, read once from `EMBED_PROVIDERS` (comma-separated
+/// `youtube`, `twitter` and `gist`, default all three; empty for none)
+#[derive(Debug, Clone)]
+pub struct EmbedConfig {
+    pub providers: Vec<EmbedProvider>,
+}
+
+impl EmbedConfig {
+    pub fn from_env() -> Self {
+        let providers =
+            std::env::var("EMBED_PROVIDERS").unwrap_or_else(|_| DEFAULT_PROVIDERS.to_string());
+
+        Self {
+            providers: providers
+                .split(',')
+                .filter_map(EmbedProvider::parse)
+                .collect(),
+        }
+    }
+
+    /// Shared config, read on first use
+    pub fn global() -> &'static EmbedConfig {
+        static CONFIG: OnceLock<EmbedConfig> = OnceLock::new();
+        CONFIG.get_or_init(EmbedConfig::from_env)
+    }
+
+    pub fn allows(&self, provider: EmbedProvider) -> bool {
+        self.providers.contains(&provider)
+    }
+}
+
+/// Content of a recognized URL, rendered in place of the link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Embed {
+    /// A video, played from YouTube's privacy-enhanced domain
+    YouTube { id: String, start: Option<u32> },
+    /// A post on X, shown as a quote linking to it; no third-party script is loaded
+    Tweet { user: String, id: String },
+    /// A gist, shown as its code fetched by the server when the content was saved
+    Gist { user: Option<String>, id: String },
+}
+
+impl Embed {
+    /// The embed of a URL from an allowed provider, if it is one
+    pub fn recognize(url: &str, config: &EmbedConfig) -> Option<Embed> {
+        let url = Url::parse(url).ok()?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        let host = url.host_str()?.to_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host);
+        let segments: Vec<&str> = url
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        let embed = match (host, segments.as_slice()) {
+            ("youtube.com" | "m.youtube.com", ["watch"]) => Embed::YouTube {
+                id: query(&url, "v")?,
+                start: start_time(&url),
+            },
+            ("youtube.com" | "m.youtube.com", ["shorts" | "embed" | "live", id])
+            | ("youtu.be", [id]) => Embed::YouTube {
+                id: id.to_string(),
+                start: start_time(&url),
+            },
+            ("twitter.com" | "mobile.twitter.com" | "x.com", [user, "status", id, ..]) => {
+                Embed::Tweet {
+                    user: user.to_string(),
+                    id: id.to_string(),
+                }
+            }
+            ("gist.github.com", [id]) => Embed::Gist {
+                user: None,
+                id: id.trim_end_matches(".js").to_string(),
+            },
+            ("gist.github.com", [user, id]) => Embed::Gist {
+                user: Some(user.to_string()),
+                id: id.trim_end_matches(".js").to_string(),
+            },
+            _ => return None,
+        };
+
+        (embed.is_valid() && config.allows(embed.provider())).then_some(embed)
+    }
+
+    pub fn provider(&self) -> EmbedProvider {
+        match self {
+            Embed::YouTube { .. } => EmbedProvider::YouTube,
+            Embed::Tweet { .. } => EmbedProvider::Twitter,
+            Embed::Gist { .. } => EmbedProvider::Gist,
+        }
+    }
+
+    // Identifiers are checked against what the sites use, so they are safe to put in HTML
+    fn is_valid(&self) -> bool {
+        let charset = |value: &str, max: usize, allowed: fn(char) -> bool| {
+            !value.is_empty() && value.len() <= max && value.chars().all(allowed)
+        };
+
+        match self {
+            Embed::YouTube { id, .. } => {
+                id.len() == 11 && charset(id, 11, |c| c.is_ascii_alphanumeric() || "-_".contains(c))
+            }
+            Embed::Tweet { user, id } => {
+                charset(user, 15, |c| c.is_ascii_alphanumeric() || c == '_')
+                    && charset(id, 20, |c| c.is_ascii_digit())
+            }
+            Embed::Gist { user, id } => {
+                let valid_user = match user {
+                    Some(user) => charset(user, 39, |c| c.is_ascii_alphanumeric() || c == '-'),
+                    None => true,
+                };
+                valid_user && charset(id, 40, |c| c.is_ascii_hexdigit())
+            }
+        }
+    }
+
+    /// HTML of the embed; gists missing from `gists` are shown as a link
+    pub(super) fn to_html(
+        &self,
+        gists: &Gists,
+        config: &HighlightConfig,
+        budget: &mut usize,
+    ) -> String {
+        match self {
+            Embed::YouTube { id, start } => youtube_html(id, *start),
+            Embed::Tweet { user, id } => tweet_html(user, id),
+            Embed::Gist { user, id } => {
+                gist_html(user.as_deref(), id, gists.get(id), config, budget)
+            }
+        }
+    }
+}
+
+fn youtube_html(id: &str, start: Option<u32>) -> String {
+    let start = start
+        .map(|start| format!("?start={}", start))
+        .unwrap_or_default();
+    format!(
+        "<div class=\"embed embed-youtube\">\
+         <iframe src=\"https://www.youtube-nocookie.com/embed/{id}{start}\" \
+         title=\"YouTube video\" loading=\"lazy\" \
+         sandbox=\"allow-scripts allow-same-origin allow-presentation allow-popups\" \
+         referrerpolicy=\"strict-origin-when-cross-origin\" \
+         allow=\"encrypted-media; picture-in-picture; fullscreen\" allowfullscreen>\
+         </iframe></div>\n"
+    )
+}
+
+fn tweet_html(user: &str, id: &str) -> String {
+    format!(
+        "<blockquote class=\"embed embed-tweet\" data-tweet-id=\"{id}\"><p>\
+         <a href=\"https://x.com/{user}/status/{id}\">Post by @{user} on X</a></p></blockquote>\n"
+    )
+}
+
+fn gist_html(
+    user: Option<&str>,
+    id: &str,
+    gist: Option<&Gist>,
+    config: &HighlightConfig,
+    budget: &mut usize,
+) -> String {
+    let url = match user {
+        Some(user) => format!("https://gist.github.com/{}/{}", user, id),
+        None => format!("https://gist.github.com/{}", id),
+    };
+    let mut html = format!("<div class=\"embed embed-gist\" data-gist-id=\"{}\">\n", id);
+    for file in gist.map(|gist| gist.files.as_slice()).unwrap_or_default() {
+        html.push_str(&format!(
+            "<p class=\"embed-gist-file\">{}</p>\n",
+            html_escape::encode_text(&file.filename)
+        ));
+        html.push_str(&render_code_block(
+            config,
+            &file.language(),
+            &file.content,
+            budget,
+        ));
+    }
+    html.push_str(&format!(
+        "<p class=\"embed-source\"><a href=\"{}\">View gist on GitHub</a></p></div>\n",
+        url
+    ));
+    html
+}
+
+fn query(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+// Where a video starts, from `t` or `start` as seconds or e.g. `1m30s`
+fn start_time(url: &Url) -> Option<u32> {
+    let value = query(url, "t").or_else(|| query(url, "start"))?;
+    parse_duration(&value).filter(|seconds| *seconds > 0)
+}
+
+fn parse_duration(value: &str) -> Option<u32> {
+    if let Ok(seconds) = value.parse() {
+        return Some(seconds);
+    }
+
+    let mut total: u32 = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        total = total.checked_add(number.parse::<u32>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    number.is_empty().then_some(total)
+}
+
+/// A file of a fetched gist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GistFile {
+    pub filename: String,
+    pub language: Option<String>,
+    pub content: String,
+}
+
+impl GistFile {
+    // Language for highlighting: the file extension, which matches fence languages better
+    // than the names GitHub reports
+    fn language(&self) -> String {
+        match self.filename.rsplit_once('.') {
+            Some((_, extension)) if !extension.is_empty() => extension.to_lowercase(),
+            _ => self.language.as_deref().unwrap_or("").to_lowercase(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Gist {
+    pub files: Vec<GistFile>,
+}
+
+/// Fetched gists by id
+pub type Gists = HashMap<String, Gist>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmbedError {
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+}
+
+#[derive(Deserialize)]
+struct GistResponse {
+    files: BTreeMap<String, GistFileResponse>,
+}
+
+#[derive(Deserialize)]
+struct GistFileResponse {
+    filename: String,
+    language: Option<String>,
+    #[serde(default)]
+    content: String,
+}
+
+/// Fetch the gists with the given ids from GitHub's API. Gists that cannot be fetched are left
+/// out, so they are shown as links.
+pub async fn fetch_gists(ids: Vec<String>) -> Gists {
+    let mut unique: Vec<String> = Vec::new();
+    for id in ids {
+        if !unique.contains(&id) && unique.len() < MAX_GISTS_PER_CONTENT {
+            unique.push(id);
+        }
+    }
+
+    let fetched = join_all(unique.iter().map(|id| fetch_gist(id))).await;
+    unique
+        .into_iter()
+        .zip(fetched)
+        .filter_map(|(id, gist)| match gist {
+            Ok(gist) => Some((id, gist)),
+            Err(e) => {
+                warn!("Failed to fetch gist {}: {}", id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+async fn fetch_gist(id: &str) -> Result<Gist, EmbedError> {
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .user_agent("RealtimeBlogEmbeds/1.0")
+        .build()?;
+
+    let mut response = client
+        .get(format!("https://api.github.com/gists/{}", id))
+        .header(header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(EmbedError::UnexpectedResponse(
+            response.status().to_string(),
+        ));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_RESPONSE_BYTES {
+            return Err(EmbedError::UnexpectedResponse("gist too large".to_string()));
+        }
+    }
+    let gist: GistResponse =
+        serde_json::from_slice(&body).map_err(|e| EmbedError::UnexpectedResponse(e.to_string()))?;
+
+    let mut files = Vec::new();
+    let mut bytes = 0;
+    for file in gist.files.into_values().take(MAX_GIST_FILES) {
+        bytes += file.content.len();
+        if bytes > MAX_GIST_BYTES {
+            break;
+        }
+        files.push(GistFile {
+            filename: file.filename,
+            language: file.language,
+            content: file.content,
+        });
+    }
+    Ok(Gist { files })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> EmbedConfig {
+        EmbedConfig {
+            providers: vec![
+                EmbedProvider::YouTube,
+                EmbedProvider::Twitter,
+                EmbedProvider::Gist,
+            ],
+        }
+    }
+
+    fn youtube(id: &str, start: Option<u32>) -> Option<Embed> {
+        Some(Embed::YouTube {
+            id: id.to_string(),
+            start,
+        })
+    }
+
+    #[test]
+    fn test_recognize_youtube() {
+        let config = all();
+        let recognize = |url| Embed::recognize(url, &config);
+
+        assert_eq!(
+            recognize("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+            youtube("dQw4w9WgXcQ", None)
+        );
+        assert_eq!(
+            recognize("https://youtu.be/dQw4w9WgXcQ?t=1m30s"),
+            youtube("dQw4w9WgXcQ", Some(90))
+        );
+        assert_eq!(
+            recognize("https://m.youtube.com/shorts/dQw4w9WgXcQ"),
+            youtube("dQw4w9WgXcQ", None)
+        );
+        assert_eq!(recognize("https://www.youtube.com/watch?v=short"), None);
+        assert_eq!(
+            recognize("https://www.youtube.com/watch?v=dQw4w9WgXc\"><script>"),
+            None
+        );
+        assert_eq!(
+            recognize("https://youtube.example.com/watch?v=dQw4w9WgXcQ"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_recognize_tweets_and_gists() {
+        let config = all();
+        let recognize = |url| Embed::recognize(url, &config);
+
+        assert_eq!(
+            recognize("https://x.com/rustlang/status/1234567890?s=20"),
+            Some(Embed::Tweet {
+                user: "rustlang".to_string(),
+                id: "1234567890".to_string(),
+            })
+        );
+        assert_eq!(
+            recognize("https://twitter.com/rustlang/status/latest"),
+            None
+        );
+        assert_eq!(
+            recognize("https://gist.github.com/octocat/6cad326836d38bd3a7ae.js"),
+            Some(Embed::Gist {
+                user: Some("octocat".to_string()),
+                id: "6cad326836d38bd3a7ae".to_string(),
+            })
+        );
+        assert_eq!(
+            recognize("https://gist.github.com/octocat/not-a-gist"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_recognize_only_allowed_providers() {
+        let config = EmbedConfig {
+            providers: vec![EmbedProvider::Gist],
+        };
+        assert_eq!(
+            Embed::recognize("https://youtu.be/dQw4w9WgXcQ", &config),
+            None
+        );
+        assert!(
+            Embed::recognize("https://gist.github.com/6cad326836d38bd3a7ae", &config).is_some()
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("1h2m3s"), Some(3723));
+        assert_eq!(parse_duration("2m"), Some(120));
+        assert_eq!(parse_duration("1m30"), None);
+        assert_eq!(parse_duration("soon"), None);
+    }
+}
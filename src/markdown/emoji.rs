@@ -0,0 +1,116 @@
+use super::emoji_table::SHORTCODES;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+// GitHub's reactions
+const DEFAULT_REACTIONS: &str = "+1,-1,laughing,tada,confused,heart,rocket,eyes";
+
+/// Emoji of a shortcode, e.g. `🚀` for `rocket`
+pub fn emoji(shortcode: &str) -> Option<&'static str> {
+    SHORTCODES
+        .binary_search_by(|(name, _)| (*name).cmp(shortcode))
+        .ok()
+        .map(|i| SHORTCODES[i].1)
+}
+
+/// Text with its `:shortcode:`s replaced by their emoji; unknown shortcodes are left as they are
+pub fn replace_shortcodes(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let found = after
+            .find(':')
+            .map(|end| &after[..end])
+            .and_then(|name| emoji(name).map(|emoji| (name, emoji)));
+
+        match found {
+            Some((name, emoji)) => {
+                output.push_str(emoji);
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                output.push(':');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    Cow::Owned(output)
+}
+
+/// Shortcodes of the emoji users may react with, read once from `REACTION_EMOJI`
+/// (comma-separated shortcodes, default GitHub's eight reactions). Unknown shortcodes are
+/// ignored.
+pub fn allowed_reactions() -> &'static [&'static str] {
+    static REACTIONS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    REACTIONS.get_or_init(|| {
+        let reactions =
+            std::env::var("REACTION_EMOJI").unwrap_or_else(|_| DEFAULT_REACTIONS.to_string());
+        parse_reactions(&reactions)
+    })
+}
+
+/// Shortcode of an allowed reaction, given as a shortcode with or without colons, or as the
+/// emoji itself
+pub fn reaction_shortcode(reaction: &str) -> Option<&'static str> {
+    find_reaction(allowed_reactions(), reaction)
+}
+
+fn find_reaction(allowed: &[&'static str], reaction: &str) -> Option<&'static str> {
+    let reaction = reaction.trim();
+    let name = reaction.trim_matches(':');
+    allowed
+        .iter()
+        .copied()
+        .find(|shortcode| *shortcode == name || emoji(shortcode) == Some(reaction))
+}
+
+fn parse_reactions(reactions: &str) -> Vec<&'static str> {
+    reactions
+        .split(',')
+        .map(|name| name.trim().trim_matches(':'))
+        .filter_map(|name| {
+            SHORTCODES
+                .binary_search_by(|(shortcode, _)| (*shortcode).cmp(name))
+                .ok()
+                .map(|i| SHORTCODES[i].0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_sorted() {
+        assert!(SHORTCODES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_replace_shortcodes() {
+        assert_eq!(replace_shortcodes("Ship it :rocket:!"), "Ship it 🚀!");
+        assert_eq!(replace_shortcodes(":+1::tada:"), "👍🎉");
+        assert_eq!(
+            replace_shortcodes("At 10:30: :not_an_emoji: stays"),
+            "At 10:30: :not_an_emoji: stays"
+        );
+        assert!(matches!(replace_shortcodes("No colons"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_reactions() {
+        let allowed = parse_reactions("+1, :heart:, not_an_emoji");
+        assert_eq!(allowed, vec!["+1", "heart"]);
+
+        assert_eq!(find_reaction(&allowed, ":+1:"), Some("+1"));
+        assert_eq!(find_reaction(&allowed, "❤️"), Some("heart"));
+        assert_eq!(find_reaction(&allowed, "rocket"), None);
+    }
+}
@@ -0,0 +1,526 @@
+//! Markdown of posts and comments rendered to the HTML stored alongside it.
+//!
+//! CommonMark with the GitHub extensions authors expect: tables, strikethrough, task lists,
+//! footnotes and autolinked URLs and emails. The output is safe to serve as is: raw HTML in
+//! the markdown is escaped, and links and images may only point to relative, `http`, `https`
+//! or `mailto` URLs.
+//!
+//! `:shortcode:`s outside code are replaced by their emoji, from a table generated from
+//! GitHub's gemoji by `scripts/generate_emoji_table.py`.
+//!
+//! Fenced code blocks are highlighted on the server with syntect, as `<pre>` elements styled
+//! inline so clients need no stylesheet. Only allowlisted languages are highlighted, and within
+//! size caps; other code blocks are escaped as plain code.
+//!
+//! A YouTube, X or gist URL alone in a paragraph becomes an embed, for the providers enabled
+//! in `EMBED_PROVIDERS`. Embeds are built from validated identifiers, never from markup in the
+//! content: videos play in a sandboxed iframe from YouTube's privacy-enhanced domain, posts on
+//! X are shown as a linked quote without Twitter's script, and gists are fetched by the server
+//! and shown as highlighted code.
+
+pub mod embed;
+pub mod emoji;
+mod emoji_table;
+
+use embed::{Embed, EmbedConfig, Gists};
+use linkify::{LinkFinder, LinkKind};
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Color, Theme, ThemeSet};
+use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+const DEFAULT_LANGUAGES: &str =
+    "rust,rs,python,py,javascript,js,go,java,c,cpp,cs,ruby,rb,php,bash,sh,sql,json,yaml,yml,html,css,xml,diff,markdown,md";
+const DEFAULT_MAX_BLOCK_BYTES: usize = 20_000;
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 200_000;
+const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// Limits on code highlighting, read once from the environment.
+///
+/// - `CODE_HIGHLIGHT_LANGUAGES`: comma-separated languages to highlight, by name or file
+///   extension as written after the opening fence
+/// - `CODE_HIGHLIGHT_MAX_BLOCK_BYTES`: largest code block that is highlighted, default 20 KB
+/// - `CODE_HIGHLIGHT_MAX_OUTPUT_BYTES`: most highlighted HTML per post or comment, default
+///   200 KB; code blocks past it are left plain
+/// - `CODE_HIGHLIGHT_THEME`: one of syntect's default themes, default `InspiredGitHub`
+#[derive(Debug, Clone)]
+pub struct HighlightConfig {
+    pub languages: Vec<String>,
+    pub max_block_bytes: usize,
+    pub max_output_bytes: usize,
+    pub theme: String,
+}
+
+impl HighlightConfig {
+    pub fn from_env() -> Self {
+        let languages = std::env::var("CODE_HIGHLIGHT_LANGUAGES")
+            .unwrap_or_else(|_| DEFAULT_LANGUAGES.to_string());
+
+        Self {
+            languages: languages
+                .split(',')
+                .map(|language| language.trim().to_lowercase())
+                .filter(|language| !language.is_empty())
+                .collect(),
+            max_block_bytes: std::env::var("CODE_HIGHLIGHT_MAX_BLOCK_BYTES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_MAX_BLOCK_BYTES),
+            max_output_bytes: std::env::var("CODE_HIGHLIGHT_MAX_OUTPUT_BYTES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_MAX_OUTPUT_BYTES),
+            theme: std::env::var("CODE_HIGHLIGHT_THEME")
+                .unwrap_or_else(|_| DEFAULT_THEME.to_string()),
+        }
+    }
+
+    /// Shared config, read on first use
+    pub fn global() -> &'static HighlightConfig {
+        static CONFIG: OnceLock<HighlightConfig> = OnceLock::new();
+        CONFIG.get_or_init(HighlightConfig::from_env)
+    }
+
+    pub fn is_allowed_language(&self, language: &str) -> bool {
+        self.languages.iter().any(|allowed| allowed == language)
+    }
+}
+
+// Syntax definitions and the theme, loaded on first use
+struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    fn global() -> &'static Highlighter {
+        static HIGHLIGHTER: OnceLock<Highlighter> = OnceLock::new();
+        HIGHLIGHTER.get_or_init(|| {
+            let mut themes = ThemeSet::load_defaults().themes;
+            let theme = themes
+                .remove(&HighlightConfig::global().theme)
+                .or_else(|| themes.remove(DEFAULT_THEME))
+                .unwrap_or_default();
+
+            Highlighter {
+                syntaxes: SyntaxSet::load_defaults_newlines(),
+                theme,
+            }
+        })
+    }
+
+    fn syntax(&self, language: &str) -> Option<&SyntaxReference> {
+        self.syntaxes.find_syntax_by_token(language)
+    }
+
+    // Highlighted lines of `code`, or `None` when highlighting fails or the HTML would be
+    // longer than `max_bytes`
+    fn highlight(&self, code: &str, syntax: &SyntaxReference, max_bytes: usize) -> Option<String> {
+        let background = self.background();
+        let mut lines = HighlightLines::new(syntax, &self.theme);
+        let mut html = String::new();
+
+        for line in LinesWithEndings::from(code) {
+            let regions = lines.highlight_line(line, &self.syntaxes).ok()?;
+            html.push_str(
+                &styled_line_to_highlighted_html(
+                    &regions,
+                    IncludeBackground::IfDifferent(background),
+                )
+                .ok()?,
+            );
+            if html.len() > max_bytes {
+                return None;
+            }
+        }
+        Some(html)
+    }
+
+    fn background(&self) -> Color {
+        self.theme.settings.background.unwrap_or(Color::WHITE)
+    }
+}
+
+// URL schemes links and images may use; URLs without a scheme are relative
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// HTML stored alongside markdown content, with its fenced code blocks highlighted. Gists
+/// are not fetched and are shown as links.
+pub fn render(content: &str) -> String {
+    render_with(
+        content,
+        HighlightConfig::global(),
+        EmbedConfig::global(),
+        &Gists::new(),
+    )
+}
+
+/// Like `render`, with the gists embedded in the content fetched first
+pub async fn render_with_embeds(content: &str) -> String {
+    let config = EmbedConfig::global();
+    let gist_ids = standalone_embeds(content, config)
+        .into_iter()
+        .filter_map(|embed| match embed {
+            Embed::Gist { id, .. } => Some(id),
+            _ => None,
+        })
+        .collect();
+    let gists = embed::fetch_gists(gist_ids).await;
+
+    render_with(content, HighlightConfig::global(), config, &gists)
+}
+
+fn parse(content: &str) -> Vec<Event<'_>> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    Parser::new_ext(content, options).collect()
+}
+
+// The URL of a paragraph that holds nothing else, as text or an autolink, and the index of
+// the paragraph's end. `events` start at the paragraph.
+fn standalone_url(events: &[Event<'_>]) -> Option<(String, usize)> {
+    let mut url = String::new();
+    let mut autolinked = false;
+
+    for (i, event) in events.iter().enumerate().skip(1) {
+        match event {
+            Event::End(TagEnd::Paragraph) => {
+                let url = url.trim();
+                if url.is_empty() || url.contains(char::is_whitespace) {
+                    return None;
+                }
+                return Some((url.to_string(), i));
+            }
+            Event::Text(text) => url.push_str(text),
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink,
+                ..
+            }) if url.is_empty() && !autolinked => autolinked = true,
+            Event::End(TagEnd::Link) if autolinked => {}
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn standalone_embeds(content: &str, config: &EmbedConfig) -> Vec<Embed> {
+    let events = parse(content);
+    events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| matches!(event, Event::Start(Tag::Paragraph)))
+        .filter_map(|(i, _)| standalone_url(&events[i..]))
+        .filter_map(|(url, _)| Embed::recognize(&url, config))
+        .collect()
+}
+
+fn render_with(
+    content: &str,
+    config: &HighlightConfig,
+    embed_config: &EmbedConfig,
+    gists: &Gists,
+) -> String {
+    let parsed = parse(content);
+    let mut budget = config.max_output_bytes;
+    let mut events = Vec::new();
+    // Language and code of the code block being read
+    let mut code_block: Option<(String, String)> = None;
+    // Depth of links and images, whose text is not autolinked
+    let mut link_depth = 0;
+    let mut index = 0;
+
+    while index < parsed.len() {
+        if let Event::Start(Tag::Paragraph) = parsed[index] {
+            let embed = standalone_url(&parsed[index..]).and_then(|(url, end)| {
+                Embed::recognize(&url, embed_config).map(|embed| (embed, end))
+            });
+            if let Some((embed, end)) = embed {
+                events.push(Event::Html(
+                    embed.to_html(gists, config, &mut budget).into(),
+                ));
+                index += end + 1;
+                continue;
+            }
+        }
+
+        let event = parsed[index].clone();
+        index += 1;
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((language, String::new()));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((language, code)) = code_block.take() {
+                    events.push(Event::Html(
+                        render_code_block(config, &language, &code, &mut budget).into(),
+                    ));
+                }
+            }
+            Event::Text(text) if code_block.is_some() => {
+                if let Some((_, code)) = &mut code_block {
+                    code.push_str(&text);
+                }
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                link_depth += 1;
+                events.push(Event::Start(Tag::Link {
+                    link_type,
+                    dest_url: safe_url(dest_url),
+                    title,
+                    id,
+                }));
+            }
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                link_depth += 1;
+                events.push(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url: safe_url(dest_url),
+                    title,
+                    id,
+                }));
+            }
+            Event::End(end @ (TagEnd::Link | TagEnd::Image)) => {
+                link_depth -= 1;
+                events.push(Event::End(end));
+            }
+            Event::Text(text) => {
+                let text = match emoji::replace_shortcodes(&text) {
+                    Cow::Borrowed(_) => text,
+                    Cow::Owned(replaced) => CowStr::from(replaced),
+                };
+                if link_depth == 0 {
+                    autolink(text, &mut events);
+                } else {
+                    events.push(Event::Text(text));
+                }
+            }
+            // Raw HTML is shown as text rather than passed through
+            Event::Html(raw) | Event::InlineHtml(raw) => events.push(Event::Text(raw)),
+            event => events.push(event),
+        }
+    }
+
+    let mut output = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut output, events.into_iter());
+    format!("<div class=\"markdown\">{}</div>", output)
+}
+
+// Text with the URLs and emails in it turned into links, as GitHub does
+fn autolink<'a>(text: CowStr<'a>, events: &mut Vec<Event<'a>>) {
+    let finder = LinkFinder::new();
+    if finder.links(&text).next().is_none() {
+        events.push(Event::Text(text));
+        return;
+    }
+
+    for span in finder.spans(&text) {
+        let part = CowStr::from(span.as_str().to_string());
+        let dest_url = match span.kind() {
+            Some(LinkKind::Url) => part.clone(),
+            Some(LinkKind::Email) => CowStr::from(format!("mailto:{}", span.as_str())),
+            _ => {
+                events.push(Event::Text(part));
+                continue;
+            }
+        };
+        let link = Tag::Link {
+            link_type: pulldown_cmark::LinkType::Autolink,
+            dest_url: safe_url(dest_url),
+            title: CowStr::from(""),
+            id: CowStr::from(""),
+        };
+        events.push(Event::Start(link));
+        events.push(Event::Text(part));
+        events.push(Event::End(TagEnd::Link));
+    }
+}
+
+// The URL if it is relative or has an allowed scheme, and `#` otherwise
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    if is_safe_url(&url) {
+        url
+    } else {
+        CowStr::from("#")
+    }
+}
+
+fn is_safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters in schemes, e.g. `java\tscript:`
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let before_path = url.split(['/', '?', '#']).next().unwrap_or("");
+
+    match before_path.split_once(':') {
+        Some((scheme, _)) => ALLOWED_SCHEMES.contains(&scheme.to_lowercase().as_str()),
+        None => true,
+    }
+}
+
+// A code block highlighted when its language is allowed and it fits the caps, and escaped
+// as plain code otherwise. Highlighted HTML is taken from `budget`.
+fn render_code_block(
+    config: &HighlightConfig,
+    language: &str,
+    code: &str,
+    budget: &mut usize,
+) -> String {
+    let language = language.to_lowercase();
+    let language_attribute = if language.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " data-language=\"{}\"",
+            html_escape::encode_double_quoted_attribute(&language)
+        )
+    };
+
+    if config.is_allowed_language(&language) && code.len() <= config.max_block_bytes {
+        let highlighter = Highlighter::global();
+        if let Some(syntax) = highlighter.syntax(&language) {
+            if let Some(highlighted) = highlighter.highlight(code, syntax, *budget) {
+                *budget -= highlighted.len();
+                let Color { r, g, b, .. } = highlighter.background();
+                return format!(
+                    "<pre class=\"code\"{} style=\"background-color:#{:02x}{:02x}{:02x};\"><code>{}</code></pre>\n",
+                    language_attribute, r, g, b, highlighted
+                );
+            }
+        }
+    }
+
+    format!(
+        "<pre class=\"code\"{}><code>{}</code></pre>\n",
+        language_attribute,
+        html_escape::encode_text(code)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn config() -> HighlightConfig {
+        HighlightConfig {
+            languages: vec!["rust".to_string()],
+            max_block_bytes: 1_000,
+            max_output_bytes: 10_000,
+            theme: DEFAULT_THEME.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_code_block_highlights_allowed_languages() {
+        let config = config();
+        let mut budget = config.max_output_bytes;
+
+        let html = render_code_block(&config, "Rust", "let x = \"<b>\";\n", &mut budget);
+        assert!(html.starts_with("<pre class=\"code\" data-language=\"rust\" style="));
+        assert!(html.contains("<span"));
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(budget < config.max_output_bytes);
+
+        let html = render_code_block(&config, "python", "print('<b>')\n", &mut budget);
+        assert_eq!(
+            html,
+            "<pre class=\"code\" data-language=\"python\"><code>print('&lt;b&gt;')\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_code_block_caps() {
+        let config = config();
+        let long = "let x = 1;\n".repeat(200);
+
+        let mut budget = config.max_output_bytes;
+        assert!(!render_code_block(&config, "rust", &long, &mut budget).contains("<span"));
+
+        let mut budget = 10;
+        assert!(!render_code_block(&config, "rust", "let x = 1;\n", &mut budget).contains("<span"));
+        assert_eq!(budget, 10);
+    }
+
+    #[test]
+    fn test_is_safe_url() {
+        assert!(is_safe_url("https://example.com/a:b"));
+        assert!(is_safe_url("/posts/hello"));
+        assert!(is_safe_url("#fn-1"));
+        assert!(is_safe_url("mailto:someone@example.com"));
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("JaVa\tScRiPt:alert(1)"));
+        assert!(!is_safe_url("data:text/html;base64,PHNjcmlwdD4="));
+    }
+
+    // Each `<name>.md` in `tests/golden/markdown` renders to `<name>.html`. Run with
+    // `UPDATE_GOLDEN=1` to write the current output after an intended change.
+    #[test]
+    fn test_golden_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/markdown");
+        let config = config();
+        let embed_config = EmbedConfig {
+            providers: vec![
+                embed::EmbedProvider::YouTube,
+                embed::EmbedProvider::Twitter,
+                embed::EmbedProvider::Gist,
+            ],
+        };
+        // Gists are never fetched here; this one stands in for a fetched gist
+        let gists = Gists::from([(
+            "aa5a315d61ae9438b18d".to_string(),
+            embed::Gist {
+                files: vec![embed::GistFile {
+                    filename: "hello.rs".to_string(),
+                    language: Some("Rust".to_string()),
+                    content: "fn main() {\n    println!(\"<hello>\");\n}\n".to_string(),
+                }],
+            },
+        )]);
+        let update = std::env::var("UPDATE_GOLDEN").is_ok();
+        let mut checked = 0;
+
+        for entry in fs::read_dir(&dir).expect("golden file directory") {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("md") {
+                continue;
+            }
+            let markdown = fs::read_to_string(&path).unwrap();
+            let rendered = render_with(&markdown, &config, &embed_config, &gists);
+            let golden = path.with_extension("html");
+
+            if update {
+                fs::write(&golden, &rendered).unwrap();
+            } else {
+                let expected = fs::read_to_string(&golden)
+                    .unwrap_or_else(|_| panic!("missing {}", golden.display()));
+                assert_eq!(rendered, expected, "{} renders differently", path.display());
+            }
+            checked += 1;
+        }
+        assert!(checked > 0, "no golden files in {}", dir.display());
+    }
+}
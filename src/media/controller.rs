@@ -0,0 +1,411 @@
+use crate::auth::middleware::AuthUser;
+use crate::cache::redis::RedisCache;
+use crate::db::retry::unavailable_response;
+use crate::db::router::DbRouter;
+use crate::media::model::{MediaError, UploadAttachmentParams};
+use crate::media::service::MediaService;
+use crate::sitemap::encode_path_segment;
+use crate::storage::controller::storage_error_response;
+use crate::storage::model::StorageError;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use tracing::error;
+use uuid::Uuid;
+
+fn media_error_response(e: MediaError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        MediaError::QuotaExceeded(quota) => {
+            return storage_error_response(StorageError::QuotaExceeded(quota))
+        }
+        MediaError::PostNotFound | MediaError::AttachmentNotFound | MediaError::ImageNotFound => {
+            StatusCode::NOT_FOUND
+        }
+        MediaError::Forbidden | MediaError::RequiresTier(_) => StatusCode::FORBIDDEN,
+        MediaError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        MediaError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        MediaError::UnsupportedType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        MediaError::DatabaseError(_)
+        | MediaError::StorageError(_)
+        | MediaError::InternalError(_) => {
+            error!("Media error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+// Browsers must save the file rather than render it, whatever its type, so an uploaded page
+// can never run on our origin
+pub fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() && c != '\\' { c } else { '_' })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        encode_path_segment(filename)
+    )
+}
+
+/// List the attachments of a post
+///
+/// Attachments are visible to whoever can read the post in full: drafts only to their author
+/// and admins, premium posts only to members of the required tier.
+#[utoipa::path(
+    get,
+    path = "/api/posts/{id}/attachments",
+    params(
+        ("id" = i64, Path, description = "Post ID")
+    ),
+    responses(
+        (status = 200, description = "Attachments of the post", body = [Attachment]),
+        (status = 403, description = "The post requires a higher membership tier"),
+        (status = 404, description = "Post not found")
+    ),
+    tag = "posts"
+)]
+pub async fn list_attachments(
+    Path(post_id): Path<i64>,
+    Extension(user): Extension<Option<AuthUser>>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = MediaService::new(db, redis_cache);
+    match service.list_attachments(post_id, user.as_ref()).await {
+        Ok(attachments) => (StatusCode::OK, Json(attachments)).into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+/// Attach a file to a post
+///
+/// The file is sent as the raw request body with its `Content-Type`; its name goes in the
+/// `filename` query parameter. Size and accepted types are configured with
+/// `ATTACHMENT_MAX_BYTES` and `ATTACHMENT_ALLOWED_TYPES`. Only the author and admins may upload.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/attachments",
+    params(
+        ("id" = i64, Path, description = "Post ID"),
+        UploadAttachmentParams
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 201, description = "File attached", body = Attachment),
+        (status = 400, description = "Missing file name or invalid file"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - user is not the post owner or admin"),
+        (status = 404, description = "Post not found"),
+        (status = 413, description = "File is too large or exceeds a storage quota", body = StorageQuotaExceededResponse),
+        (status = 415, description = "File type is not allowed")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn upload_attachment(
+    user: AuthUser,
+    Path(post_id): Path<i64>,
+    Query(params): Query<UploadAttachmentParams>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let service = MediaService::new(db, redis_cache);
+    match service
+        .upload_attachment(post_id, &user, &params.filename, content_type, &body)
+        .await
+    {
+        Ok(attachment) => (StatusCode::CREATED, Json(attachment)).into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+/// Download an attachment
+///
+/// Subject to the same visibility rules as listing. Always served as a download.
+#[utoipa::path(
+    get,
+    path = "/api/posts/{id}/attachments/{attachment_id}",
+    params(
+        ("id" = i64, Path, description = "Post ID"),
+        ("attachment_id" = i64, Path, description = "Attachment ID")
+    ),
+    responses(
+        (status = 200, description = "The file", content_type = "application/octet-stream"),
+        (status = 403, description = "The post requires a higher membership tier"),
+        (status = 404, description = "Post or attachment not found")
+    ),
+    tag = "posts"
+)]
+pub async fn download_attachment(
+    Path((post_id, attachment_id)): Path<(i64, i64)>,
+    Extension(user): Extension<Option<AuthUser>>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = MediaService::new(db, redis_cache);
+    match service
+        .download_attachment(post_id, attachment_id, user.as_ref())
+        .await
+    {
+        Ok((attachment, data)) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, attachment.content_type.clone()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    content_disposition(&attachment.filename),
+                ),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+                (header::CACHE_CONTROL, "private, no-cache".to_string()),
+            ],
+            data,
+        )
+            .into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+/// Delete an attachment (author or admin only)
+#[utoipa::path(
+    delete,
+    path = "/api/posts/{id}/attachments/{attachment_id}",
+    params(
+        ("id" = i64, Path, description = "Post ID"),
+        ("attachment_id" = i64, Path, description = "Attachment ID")
+    ),
+    responses(
+        (status = 204, description = "Attachment deleted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - user is not the post owner or admin"),
+        (status = 404, description = "Post or attachment not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn delete_attachment(
+    user: AuthUser,
+    Path((post_id, attachment_id)): Path<(i64, i64)>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = MediaService::new(db, redis_cache);
+    match service
+        .delete_attachment(post_id, attachment_id, &user)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+/// Upload a post's cover image
+///
+/// The image (JPEG, PNG, GIF or WebP) is sent as the raw request body. It is resized to the
+/// widths in `IMAGE_VARIANT_WIDTHS` as WebP and given a blurhash placeholder, and becomes the
+/// post's `cover_image_url`. The variants are returned in the post's `cover_image`.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/cover-image",
+    params(
+        ("id" = i64, Path, description = "Post ID")
+    ),
+    request_body(content = Vec<u8>, content_type = "image/*"),
+    responses(
+        (status = 201, description = "Cover image processed", body = CoverImage),
+        (status = 400, description = "Unreadable image"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - user is not the post owner or admin"),
+        (status = 404, description = "Post not found"),
+        (status = 413, description = "Image is too large or exceeds a storage quota", body = StorageQuotaExceededResponse),
+        (status = 415, description = "Not a supported image format")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn upload_cover_image(
+    user: AuthUser,
+    Path(post_id): Path<i64>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+    body: Bytes,
+) -> Response {
+    let service = MediaService::new(db, redis_cache);
+    match service
+        .upload_cover_image(post_id, &user, body.to_vec())
+        .await
+    {
+        Ok(cover_image) => (StatusCode::CREATED, Json(cover_image)).into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+/// Get a file of a post's cover image
+///
+/// `file` is `original` or one of the variant file names listed in the post's `cover_image`.
+/// Files never change once uploaded, so they are cacheable for a long time.
+#[utoipa::path(
+    get,
+    path = "/api/posts/{id}/cover-image/{image_id}/{file}",
+    params(
+        ("id" = i64, Path, description = "Post ID"),
+        ("image_id" = i64, Path, description = "Cover image ID"),
+        ("file" = String, Path, description = "`original` or a variant such as `640.webp`")
+    ),
+    responses(
+        (status = 200, description = "The image", content_type = "image/*"),
+        (status = 404, description = "Post or image not found")
+    ),
+    tag = "posts"
+)]
+pub async fn get_cover_image_file(
+    Path((post_id, image_id, file)): Path<(i64, i64, String)>,
+    Extension(user): Extension<Option<AuthUser>>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = MediaService::new(db, redis_cache);
+    match service
+        .cover_image_file(post_id, image_id, &file, user.as_ref())
+        .await
+    {
+        Ok((content_type, data)) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+                (
+                    header::CACHE_CONTROL,
+                    "public, max-age=31536000, immutable".to_string(),
+                ),
+            ],
+            data,
+        )
+            .into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+/// Upload the authenticated user's avatar
+///
+/// The image (JPEG, PNG, GIF or WebP) is sent as the raw request body. It is cropped to a
+/// centred square and stored as WebP copies of up to 256 pixels, and becomes the
+/// `avatar_url` shown with the user's posts and comments.
+#[utoipa::path(
+    put,
+    path = "/api/users/me/avatar",
+    request_body(content = Vec<u8>, content_type = "image/*"),
+    responses(
+        (status = 200, description = "Avatar updated", body = AvatarResponse),
+        (status = 400, description = "Unreadable image"),
+        (status = 401, description = "Unauthorized"),
+        (status = 413, description = "Image is too large"),
+        (status = 415, description = "Not a supported image format")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "users"
+)]
+pub async fn upload_avatar(
+    user: AuthUser,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+    body: Bytes,
+) -> Response {
+    let service = MediaService::new(db, redis_cache);
+    match service.upload_avatar(&user, body.to_vec()).await {
+        Ok(avatar) => (StatusCode::OK, Json(avatar)).into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+/// Remove the authenticated user's avatar
+///
+/// The user's Gravatar is shown instead.
+#[utoipa::path(
+    delete,
+    path = "/api/users/me/avatar",
+    responses(
+        (status = 200, description = "Avatar removed", body = AvatarResponse),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "users"
+)]
+pub async fn delete_avatar(
+    user: AuthUser,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = MediaService::new(db, redis_cache);
+    match service.delete_avatar(&user).await {
+        Ok(avatar) => (StatusCode::OK, Json(avatar)).into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+/// Get a file of an uploaded avatar
+#[utoipa::path(
+    get,
+    path = "/api/users/{user_id}/avatar/{upload_id}/{file}",
+    params(
+        ("user_id" = String, Path, description = "User ID (UUID)"),
+        ("upload_id" = String, Path, description = "Avatar upload ID (UUID)"),
+        ("file" = String, Path, description = "Variant file name, such as `128.webp`")
+    ),
+    responses(
+        (status = 200, description = "The image", content_type = "image/webp"),
+        (status = 404, description = "Avatar not found")
+    ),
+    tag = "users"
+)]
+pub async fn get_avatar_file(
+    Path((user_id, upload_id, file)): Path<(Uuid, Uuid, String)>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = MediaService::new(db, redis_cache);
+    match service.avatar_file(user_id, upload_id, &file).await {
+        Ok(data) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "image/webp"),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            data,
+        )
+            .into_response(),
+        Err(e) => media_error_response(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("résumé.pdf"),
+            "attachment; filename=\"r_sum_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use crate::media::processing::scaled_height;
+use crate::routes::versioning::API_V1_PREFIX;
+use crate::storage::model::{StorageError, StorageQuotaExceeded};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const DEFAULT_ATTACHMENTS_DIR: &str = "data/attachments";
+const DEFAULT_ATTACHMENT_MAX_BYTES: usize = 25 * 1024 * 1024;
+const DEFAULT_ATTACHMENT_TYPES: &str = "application/pdf,application/zip,application/gzip,\
+    application/x-tar,application/x-7z-compressed,text/plain,text/markdown,text/csv,\
+    application/json";
+const MAX_FILENAME_CHARS: usize = 255;
+const DEFAULT_IMAGE_VARIANT_WIDTHS: &str = "320,640,1280";
+const DEFAULT_COVER_IMAGE_MAX_BYTES: usize = 10 * 1024 * 1024;
+
+/// A file attached to a post
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Attachment {
+    pub id: i64,
+    pub post_id: i64,
+
+    /// Name the file is downloaded as
+    #[schema(example = "slides.pdf")]
+    pub filename: String,
+
+    #[schema(example = "application/pdf")]
+    pub content_type: String,
+
+    #[schema(example = "482133")]
+    pub size_bytes: i64,
+
+    pub created_at: DateTime<Utc>,
+}
+
+/// A resized WebP copy of a cover image
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImageVariant {
+    #[schema(example = "640")]
+    pub width: i32,
+    #[schema(example = "360")]
+    pub height: i32,
+    #[schema(example = "/api/v1/posts/42/cover-image/7/640.webp")]
+    pub url: String,
+    #[schema(example = "image/webp")]
+    pub content_type: String,
+}
+
+/// An uploaded cover image with its variants and placeholder
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CoverImage {
+    /// The image as uploaded
+    #[schema(example = "/api/v1/posts/42/cover-image/7/original")]
+    pub url: String,
+    pub width: i32,
+    pub height: i32,
+    /// Blurhash placeholder to show while the image loads
+    #[schema(example = "LEHV6nWB2yk8pyo0adR*.7kCMdnj")]
+    pub blurhash: String,
+    /// WebP copies, narrowest first; the last one is full size
+    pub variants: Vec<ImageVariant>,
+}
+
+/// A user's uploaded avatar
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AvatarResponse {
+    /// URL shown next to the user's posts and comments; their Gravatar when nothing is uploaded
+    #[schema(
+        example = "/api/v1/users/a1b2c3d4-e5f6-7890-abcd-1234567890ab/avatar/0f9c1e8e-5b7a-4d0e-9d57-0c6d1d5e2f10/256.webp"
+    )]
+    pub avatar_url: String,
+    /// Square WebP copies, smallest first; empty for a Gravatar
+    pub variants: Vec<ImageVariant>,
+}
+
+/// Path, relative to the API host, a file of an uploaded avatar is served from
+pub fn avatar_file_url(user_id: Uuid, upload_id: Uuid, file_name: &str) -> String {
+    format!(
+        "{}/users/{}/avatar/{}/{}",
+        API_V1_PREFIX, user_id, upload_id, file_name
+    )
+}
+
+/// A stored cover image
+#[derive(Debug, Clone, FromRow)]
+pub struct CoverImageRecord {
+    pub id: i64,
+    pub post_id: i64,
+    /// Type of the original upload
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub blurhash: String,
+    pub variant_widths: Vec<i32>,
+    /// Bytes of the original and its variants together
+    pub size_bytes: i64,
+}
+
+/// File name of the variant of a cover image with the given width
+pub fn variant_file_name(width: i32) -> String {
+    format!("{}.webp", width)
+}
+
+/// File name of a cover image as uploaded
+pub const ORIGINAL_FILE_NAME: &str = "original";
+
+impl CoverImageRecord {
+    /// Path, relative to the API host, a file of this image is served from
+    pub fn file_url(&self, file_name: &str) -> String {
+        format!(
+            "{}/posts/{}/cover-image/{}/{}",
+            API_V1_PREFIX, self.post_id, self.id, file_name
+        )
+    }
+
+    pub fn into_cover_image(self) -> CoverImage {
+        let variants = self
+            .variant_widths
+            .iter()
+            .map(|&width| ImageVariant {
+                width,
+                height: scaled_height(width as u32, self.width as u32, self.height as u32) as i32,
+                url: self.file_url(&variant_file_name(width)),
+                content_type: "image/webp".to_string(),
+            })
+            .collect();
+
+        CoverImage {
+            url: self.file_url(ORIGINAL_FILE_NAME),
+            width: self.width,
+            height: self.height,
+            blurhash: self.blurhash,
+            variants,
+        }
+    }
+}
+
+/// Query parameters of an attachment upload; the file itself is the raw request body
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UploadAttachmentParams {
+    /// Name the file is downloaded as
+    pub filename: String,
+}
+
+/// Limits on attachments, read once from the environment.
+///
+/// - `ATTACHMENTS_DIR`: where files are stored, default `data/attachments`
+/// - `ATTACHMENT_MAX_BYTES`: largest accepted file, default 25 MB
+/// - `ATTACHMENT_ALLOWED_TYPES`: comma-separated MIME types, default documents and archives
+#[derive(Debug, Clone)]
+pub struct AttachmentConfig {
+    pub storage_dir: PathBuf,
+    pub max_bytes: usize,
+    pub allowed_types: Vec<String>,
+}
+
+impl AttachmentConfig {
+    pub fn from_env() -> Self {
+        let allowed_types = std::env::var("ATTACHMENT_ALLOWED_TYPES")
+            .unwrap_or_else(|_| DEFAULT_ATTACHMENT_TYPES.to_string());
+
+        Self {
+            storage_dir: std::env::var("ATTACHMENTS_DIR")
+                .unwrap_or_else(|_| DEFAULT_ATTACHMENTS_DIR.to_string())
+                .into(),
+            max_bytes: std::env::var("ATTACHMENT_MAX_BYTES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_ATTACHMENT_MAX_BYTES),
+            allowed_types: allowed_types
+                .split(',')
+                .map(|content_type| content_type.trim().to_ascii_lowercase())
+                .filter(|content_type| !content_type.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Shared config, read on first use
+    pub fn global() -> &'static AttachmentConfig {
+        static CONFIG: OnceLock<AttachmentConfig> = OnceLock::new();
+        CONFIG.get_or_init(AttachmentConfig::from_env)
+    }
+
+    pub fn is_allowed_type(&self, content_type: &str) -> bool {
+        self.allowed_types
+            .iter()
+            .any(|allowed| allowed == content_type)
+    }
+}
+
+/// Limits on cover images, read once from the environment.
+///
+/// - `IMAGE_VARIANT_WIDTHS`: comma-separated widths of the WebP variants, default `320,640,1280`
+/// - `COVER_IMAGE_MAX_BYTES`: largest accepted upload, default 10 MB
+#[derive(Debug, Clone)]
+pub struct ImageConfig {
+    pub variant_widths: Vec<u32>,
+    pub max_bytes: usize,
+}
+
+impl ImageConfig {
+    pub fn from_env() -> Self {
+        let variant_widths = std::env::var("IMAGE_VARIANT_WIDTHS")
+            .unwrap_or_else(|_| DEFAULT_IMAGE_VARIANT_WIDTHS.to_string());
+
+        Self {
+            variant_widths: variant_widths
+                .split(',')
+                .filter_map(|width| width.trim().parse().ok())
+                .collect(),
+            max_bytes: std::env::var("COVER_IMAGE_MAX_BYTES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_COVER_IMAGE_MAX_BYTES),
+        }
+    }
+
+    /// Shared config, read on first use
+    pub fn global() -> &'static ImageConfig {
+        static CONFIG: OnceLock<ImageConfig> = OnceLock::new();
+        CONFIG.get_or_init(ImageConfig::from_env)
+    }
+}
+
+/// MIME type without parameters, e.g. `text/plain` for `text/plain; charset=utf-8`
+pub fn normalize_content_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Whether the start of a file fits its declared type, for the binary formats that have a
+/// signature. Stops a script or page being uploaded as a "PDF".
+pub fn content_matches_type(content_type: &str, data: &[u8]) -> bool {
+    match content_type {
+        "application/pdf" => data.starts_with(b"%PDF-"),
+        "application/zip" => data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06"),
+        "application/gzip" => data.starts_with(&[0x1f, 0x8b]),
+        "application/x-7z-compressed" => data.starts_with(b"7z\xbc\xaf\x27\x1c"),
+        _ => true,
+    }
+}
+
+/// Display name of an uploaded file: its last path component, without control characters or
+/// quotes, which would break the `Content-Disposition` header
+pub fn sanitize_filename(filename: &str) -> Option<String> {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(MAX_FILENAME_CHARS)
+        .collect();
+    let name = name.trim();
+
+    if name.is_empty() || name == "." || name == ".." {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Possible attachment errors
+#[derive(Debug, thiserror::Error)]
+pub enum MediaError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Storage error: {0}")]
+    StorageError(#[from] std::io::Error),
+
+    #[error("Post not found")]
+    PostNotFound,
+
+    #[error("Attachment not found")]
+    AttachmentNotFound,
+
+    #[error("Image not found")]
+    ImageNotFound,
+
+    #[error("Only the author can manage files of this post")]
+    Forbidden,
+
+    #[error("Attachments of this post require the {0} membership tier")]
+    RequiresTier(String),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Attachments may not be larger than {0} bytes")]
+    TooLarge(usize),
+
+    #[error("Unsupported attachment type: {0}")]
+    UnsupportedType(String),
+
+    #[error("Storage quota of the {} exceeded", .0.scope.as_str())]
+    QuotaExceeded(StorageQuotaExceeded),
+
+    #[error("Internal error: {0}")]
+    InternalError(String),
+}
+
+impl From<StorageError> for MediaError {
+    fn from(e: StorageError) -> Self {
+        match e {
+            StorageError::DatabaseError(e) => MediaError::DatabaseError(e),
+            StorageError::QuotaExceeded(quota) => MediaError::QuotaExceeded(quota),
+            StorageError::ValidationError(message) => MediaError::InvalidInput(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(
+            sanitize_filename("../../etc/passwd"),
+            Some("passwd".to_string())
+        );
+        assert_eq!(
+            sanitize_filename("C:\\talks\\slides \"v2\".pdf"),
+            Some("slides v2.pdf".to_string())
+        );
+        assert_eq!(sanitize_filename("dir/"), None);
+        assert_eq!(sanitize_filename(".."), None);
+    }
+
+    #[test]
+    fn test_content_matches_type() {
+        assert!(content_matches_type("application/pdf", b"%PDF-1.7\n"));
+        assert!(!content_matches_type("application/pdf", b"<html>"));
+        assert!(content_matches_type("text/plain", b"anything"));
+        assert_eq!(
+            normalize_content_type("Text/Plain; charset=utf-8"),
+            "text/plain"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use std::io::Cursor;
+
+/// Largest width or height of an image accepted for processing, against decompression bombs
+const MAX_IMAGE_DIMENSION: u32 = 12_000;
+/// Components of the blurhash placeholder; 4x3 suits the usual landscape cover
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+/// Blurhash is computed from a tiny thumbnail, which gives the same hash much faster
+const BLURHASH_SOURCE_SIZE: u32 = 32;
+
+/// A resized WebP copy of an image
+#[derive(Debug)]
+pub struct ProcessedVariant {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// An uploaded image with its variants
+#[derive(Debug)]
+pub struct ProcessedImage {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub blurhash: String,
+    pub variants: Vec<ProcessedVariant>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessingError {
+    #[error("Not a recognised image format")]
+    UnknownFormat,
+
+    #[error("Unreadable image: {0}")]
+    Decode(#[from] image::ImageError),
+
+    #[error("Could not compute placeholder: {0}")]
+    Blurhash(String),
+}
+
+/// Widths to produce for an image: the configured ones it is larger than, plus its own width,
+/// so even a small image gets a WebP copy. Images are never scaled up.
+pub fn variant_widths(original_width: u32, configured: &[u32]) -> Vec<u32> {
+    let mut widths: Vec<u32> = configured
+        .iter()
+        .copied()
+        .filter(|width| *width > 0 && *width < original_width)
+        .collect();
+    widths.push(original_width);
+    widths.sort_unstable();
+    widths.dedup();
+    widths
+}
+
+/// Height of a variant of the given width, keeping the aspect ratio
+pub fn scaled_height(width: u32, original_width: u32, original_height: u32) -> u32 {
+    ((original_height as u64 * width as u64 + original_width as u64 / 2) / original_width as u64)
+        .max(1) as u32
+}
+
+fn encode_webp(image: &DynamicImage) -> Result<Vec<u8>, image::ImageError> {
+    let mut data = Vec::new();
+    image
+        .to_rgba8()
+        .write_with_encoder(WebPEncoder::new_lossless(&mut data))?;
+    Ok(data)
+}
+
+fn decode_image(data: &[u8]) -> Result<(ImageFormat, DynamicImage), ProcessingError> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(image::ImageError::IoError)?;
+    let format = reader.format().ok_or(ProcessingError::UnknownFormat)?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    reader.limits(limits);
+    Ok((format, reader.decode()?))
+}
+
+/// Decode an uploaded image and produce its WebP variants and blurhash placeholder.
+///
+/// CPU bound; run it with `spawn_blocking`.
+pub fn process_image(data: &[u8], widths: &[u32]) -> Result<ProcessedImage, ProcessingError> {
+    let (format, image) = decode_image(data)?;
+    process_decoded(format, image, widths)
+}
+
+/// Like [`process_image`], but crops the image to a centred square first, as avatars are
+/// always shown square, and keeps no copy larger than the largest width
+pub fn process_avatar(data: &[u8], widths: &[u32]) -> Result<ProcessedImage, ProcessingError> {
+    let (format, image) = decode_image(data)?;
+    let side = image.width().min(image.height());
+    let square = image.crop_imm(
+        (image.width() - side) / 2,
+        (image.height() - side) / 2,
+        side,
+        side,
+    );
+    let largest = widths.iter().copied().max().unwrap_or(side);
+    let square = if side > largest {
+        square.resize_exact(largest, largest, FilterType::Lanczos3)
+    } else {
+        square
+    };
+    process_decoded(format, square, widths)
+}
+
+fn process_decoded(
+    format: ImageFormat,
+    image: DynamicImage,
+    widths: &[u32],
+) -> Result<ProcessedImage, ProcessingError> {
+    let (width, height) = (image.width(), image.height());
+    let mut variants = Vec::new();
+    for variant_width in variant_widths(width, widths) {
+        let variant_height = scaled_height(variant_width, width, height);
+        let resized = if variant_width == width {
+            image.clone()
+        } else {
+            image.resize_exact(variant_width, variant_height, FilterType::Lanczos3)
+        };
+        variants.push(ProcessedVariant {
+            width: variant_width,
+            height: variant_height,
+            data: encode_webp(&resized)?,
+        });
+    }
+
+    let thumbnail = image
+        .thumbnail(BLURHASH_SOURCE_SIZE, BLURHASH_SOURCE_SIZE)
+        .to_rgba8();
+    let blurhash = blurhash::encode(
+        BLURHASH_COMPONENTS.0,
+        BLURHASH_COMPONENTS.1,
+        thumbnail.width(),
+        thumbnail.height(),
+        thumbnail.as_raw(),
+    )
+    .map_err(|e| ProcessingError::Blurhash(e.to_string()))?;
+
+    Ok(ProcessedImage {
+        format,
+        width,
+        height,
+        blurhash,
+        variants,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn test_variant_widths() {
+        assert_eq!(
+            variant_widths(1000, &[320, 640, 1280]),
+            vec![320, 640, 1000]
+        );
+        assert_eq!(variant_widths(200, &[320, 640]), vec![200]);
+        assert_eq!(scaled_height(320, 1280, 720), 180);
+    }
+
+    #[test]
+    fn test_process_image() {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(800, 400, image::Rgb([200, 40, 40])))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        let processed = process_image(&png, &[320, 640, 1280]).unwrap();
+        assert_eq!(processed.format, ImageFormat::Png);
+        assert_eq!((processed.width, processed.height), (800, 400));
+        let sizes: Vec<(u32, u32)> = processed
+            .variants
+            .iter()
+            .map(|variant| (variant.width, variant.height))
+            .collect();
+        assert_eq!(sizes, vec![(320, 160), (640, 320), (800, 400)]);
+        assert!(processed.variants[0].data.starts_with(b"RIFF"));
+        assert!(!processed.blurhash.is_empty());
+
+        let avatar = process_avatar(&png, &[64, 128]).unwrap();
+        assert_eq!((avatar.width, avatar.height), (128, 128));
+        assert_eq!(avatar.variants.len(), 2);
+    }
+}
@@ -0,0 +1,761 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::cache::redis::RedisCache;
+use crate::cache::surrogate::{purge, SurrogateKey};
+use crate::db::router::DbRouter;
+use crate::media::model::{
+    avatar_file_url, content_matches_type, normalize_content_type, sanitize_filename,
+    variant_file_name, Attachment, AttachmentConfig, AvatarResponse, CoverImage, CoverImageRecord,
+    ImageConfig, ImageVariant, MediaError, ORIGINAL_FILE_NAME,
+};
+use crate::media::processing::{process_avatar, process_image, ProcessedImage, ProcessingError};
+use crate::post::service::PostService;
+use crate::storage::service::{charge_storage, release_storage};
+use crate::tenant::middleware::current_blog_id;
+use sqlx::{FromRow, PgPool};
+use std::path::PathBuf;
+use tracing::{error, info};
+use uuid::Uuid;
+
+const ATTACHMENT_COLUMNS: &str = "id, post_id, filename, content_type, size_bytes, created_at";
+/// Widths of the square WebP copies of an avatar; the largest is linked as the `avatar_url`
+const AVATAR_WIDTHS: [u32; 3] = [64, 128, 256];
+const COVER_IMAGE_COLUMNS: &str =
+    "id, post_id, content_type, width, height, blurhash, variant_widths, size_bytes";
+
+/// Attachments of a post, oldest first
+pub async fn post_attachments(pool: &PgPool, post_id: i64) -> Result<Vec<Attachment>, sqlx::Error> {
+    sqlx::query_as::<_, Attachment>(&format!(
+        "SELECT {} FROM global.post_attachments WHERE post_id = $1 ORDER BY id",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(post_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Processed cover image of a post, if the post still uses it. A cover set by URL replaces an
+/// uploaded one without removing it, so the record alone is not enough.
+pub async fn post_cover_image(
+    pool: &PgPool,
+    post_id: i64,
+    cover_image_url: Option<&str>,
+) -> Result<Option<CoverImage>, sqlx::Error> {
+    let cover_image_url = match cover_image_url {
+        Some(url) => url,
+        None => return Ok(None),
+    };
+
+    let record = sqlx::query_as::<_, CoverImageRecord>(&format!(
+        "SELECT {} FROM global.cover_images WHERE post_id = $1 ORDER BY id DESC LIMIT 1",
+        COVER_IMAGE_COLUMNS
+    ))
+    .bind(post_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record
+        .filter(|record| record.file_url(ORIGINAL_FILE_NAME) == cover_image_url)
+        .map(CoverImageRecord::into_cover_image))
+}
+
+fn processing_error(e: ProcessingError) -> MediaError {
+    match e {
+        ProcessingError::UnknownFormat => MediaError::UnsupportedType(e.to_string()),
+        e => MediaError::InvalidInput(e.to_string()),
+    }
+}
+
+// Image processing is CPU bound, so it runs off the async workers
+async fn process_blocking(
+    data: Vec<u8>,
+    process: impl FnOnce(&[u8]) -> Result<ProcessedImage, ProcessingError> + Send + 'static,
+) -> Result<(Vec<u8>, ProcessedImage), MediaError> {
+    tokio::task::spawn_blocking(move || process(&data).map(|processed| (data, processed)))
+        .await
+        .map_err(|e| MediaError::InternalError(e.to_string()))?
+        .map_err(processing_error)
+}
+
+/// What decides who may see a post's attachments
+#[derive(Debug, FromRow)]
+struct PostAccess {
+    user_id: Uuid,
+    slug: String,
+    is_draft: bool,
+    tier_name: Option<String>,
+    tier_level: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MediaService {
+    db: DbRouter,
+    redis_cache: Option<RedisCache>,
+    config: &'static AttachmentConfig,
+}
+
+impl MediaService {
+    pub fn new(db: DbRouter, redis_cache: Option<RedisCache>) -> Self {
+        Self {
+            db,
+            redis_cache,
+            config: AttachmentConfig::global(),
+        }
+    }
+
+    // Files live under the blog and post they belong to, named by attachment ID, so user input
+    // never ends up in a path
+    fn storage_path(&self, post_id: i64, attachment_id: i64) -> PathBuf {
+        self.config
+            .storage_dir
+            .join(current_blog_id().to_string())
+            .join(post_id.to_string())
+            .join(attachment_id.to_string())
+    }
+
+    fn cover_image_dir(&self, post_id: i64, image_id: i64) -> PathBuf {
+        self.config
+            .storage_dir
+            .join(current_blog_id().to_string())
+            .join(post_id.to_string())
+            .join("cover")
+            .join(image_id.to_string())
+    }
+
+    // Only logs a failure, as the files are no longer reachable once their row is gone
+    async fn remove_cover_image_files(&self, post_id: i64, image_id: i64) {
+        if let Err(e) = tokio::fs::remove_dir_all(self.cover_image_dir(post_id, image_id)).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!("Failed to remove files of cover image {}: {}", image_id, e);
+            }
+        }
+    }
+
+    // Avatars belong to users, who are shared by all blogs
+    fn avatar_dir(&self, user_id: Uuid) -> PathBuf {
+        self.config
+            .storage_dir
+            .join("avatars")
+            .join(user_id.to_string())
+    }
+
+    async fn post_access(&self, post_id: i64) -> Result<PostAccess, MediaError> {
+        sqlx::query_as::<_, PostAccess>(
+            r#"
+            SELECT p.user_id, p.slug, p.is_draft, t.name AS tier_name, t.level AS tier_level
+            FROM global.posts p
+            LEFT JOIN global.membership_tiers t ON t.id = p.required_tier_id
+            WHERE p.id = $1 AND p.blog_id = $2 AND p.is_deleted = false
+            "#,
+        )
+        .bind(post_id)
+        .bind(current_blog_id())
+        .fetch_optional(self.db.primary())
+        .await?
+        .ok_or(MediaError::PostNotFound)
+    }
+
+    // Attachments are as visible as the post itself: drafts only to their author and admins,
+    // and premium posts only to members of the required tier
+    async fn check_read_access(
+        &self,
+        post_id: i64,
+        user: Option<&AuthUser>,
+    ) -> Result<(), MediaError> {
+        let post = self.post_access(post_id).await?;
+        let viewer = PostService::new(self.db.clone(), self.redis_cache.clone())
+            .viewer(user)
+            .await
+            .map_err(|e| MediaError::InternalError(e.to_string()))?;
+
+        if viewer.is_admin || viewer.user_id == Some(post.user_id) {
+            return Ok(());
+        }
+        if post.is_draft {
+            return Err(MediaError::PostNotFound);
+        }
+        match (post.tier_name, post.tier_level) {
+            (Some(name), Some(level)) if viewer.tier_level < level => {
+                Err(MediaError::RequiresTier(name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn check_write_access(
+        &self,
+        post_id: i64,
+        user: &AuthUser,
+    ) -> Result<PostAccess, MediaError> {
+        let post = self.post_access(post_id).await?;
+        if post.user_id != user.user_id && user.role != Role::Admin {
+            return Err(MediaError::Forbidden);
+        }
+        Ok(post)
+    }
+
+    // Cached post renderings list the attachments
+    async fn invalidate_post(&self, post_id: i64, slug: &str) {
+        if let Some(cache) = &self.redis_cache {
+            if let Err(e) = cache.invalidate_post(post_id, slug).await {
+                error!("Failed to invalidate post {} cache: {}", post_id, e);
+            }
+        }
+    }
+
+    /// List the attachments of a post the user may read
+    pub async fn list_attachments(
+        &self,
+        post_id: i64,
+        user: Option<&AuthUser>,
+    ) -> Result<Vec<Attachment>, MediaError> {
+        self.check_read_access(post_id, user).await?;
+        Ok(post_attachments(self.db.read(), post_id).await?)
+    }
+
+    /// Attach a file to a post. Only the author and admins may do so.
+    pub async fn upload_attachment(
+        &self,
+        post_id: i64,
+        user: &AuthUser,
+        filename: &str,
+        content_type: &str,
+        data: &[u8],
+    ) -> Result<Attachment, MediaError> {
+        let filename = sanitize_filename(filename)
+            .ok_or_else(|| MediaError::InvalidInput("A file name is required".to_string()))?;
+        let content_type = normalize_content_type(content_type);
+        if data.is_empty() {
+            return Err(MediaError::InvalidInput("The file is empty".to_string()));
+        }
+        if data.len() > self.config.max_bytes {
+            return Err(MediaError::TooLarge(self.config.max_bytes));
+        }
+        if !self.config.is_allowed_type(&content_type) {
+            return Err(MediaError::UnsupportedType(content_type));
+        }
+        if !content_matches_type(&content_type, data) {
+            return Err(MediaError::InvalidInput(format!(
+                "The file is not a valid {}",
+                content_type
+            )));
+        }
+
+        let post = self.check_write_access(post_id, user).await?;
+
+        // The row is only committed once the file is stored, and the file is removed again when
+        // the commit fails, so a failed upload leaves nothing
+        let mut tx = self.db.primary().begin().await?;
+        let attachment = sqlx::query_as::<_, Attachment>(&format!(
+            r#"
+            INSERT INTO global.post_attachments
+                (post_id, blog_id, uploader_id, filename, content_type, size_bytes)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING {}
+            "#,
+            ATTACHMENT_COLUMNS
+        ))
+        .bind(post_id)
+        .bind(current_blog_id())
+        .bind(user.user_id)
+        .bind(&filename)
+        .bind(&content_type)
+        .bind(data.len() as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // The quota is charged last, so uploads to the blog only wait for each other's commits
+        // and never for a file write
+        let path = self.storage_path(post_id, attachment.id);
+        let stored = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&path, data).await?;
+            charge_storage(&mut tx, user.user_id, data.len() as i64).await?;
+            tx.commit().await?;
+            Ok::<_, MediaError>(())
+        }
+        .await;
+        if let Err(e) = stored {
+            if let Err(remove_error) = tokio::fs::remove_file(&path).await {
+                if remove_error.kind() != std::io::ErrorKind::NotFound {
+                    error!(
+                        "Failed to remove file of failed attachment {}: {}",
+                        attachment.id, remove_error
+                    );
+                }
+            }
+            return Err(e);
+        }
+
+        self.invalidate_post(post_id, &post.slug).await;
+        info!(
+            "Attached {} ({} bytes) to post {}",
+            filename,
+            data.len(),
+            post_id
+        );
+        Ok(attachment)
+    }
+
+    /// Read an attachment the user may download, with its contents
+    pub async fn download_attachment(
+        &self,
+        post_id: i64,
+        attachment_id: i64,
+        user: Option<&AuthUser>,
+    ) -> Result<(Attachment, Vec<u8>), MediaError> {
+        self.check_read_access(post_id, user).await?;
+
+        let attachment = sqlx::query_as::<_, Attachment>(&format!(
+            "SELECT {} FROM global.post_attachments WHERE id = $1 AND post_id = $2",
+            ATTACHMENT_COLUMNS
+        ))
+        .bind(attachment_id)
+        .bind(post_id)
+        .fetch_optional(self.db.read())
+        .await?
+        .ok_or(MediaError::AttachmentNotFound)?;
+
+        let data = match tokio::fs::read(self.storage_path(post_id, attachment_id)).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                error!("File of attachment {} is missing", attachment_id);
+                return Err(MediaError::AttachmentNotFound);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok((attachment, data))
+    }
+
+    /// Remove an attachment and its file. Only the author and admins may do so.
+    pub async fn delete_attachment(
+        &self,
+        post_id: i64,
+        attachment_id: i64,
+        user: &AuthUser,
+    ) -> Result<(), MediaError> {
+        let post = self.check_write_access(post_id, user).await?;
+
+        let mut tx = self.db.primary().begin().await?;
+        let (uploader_id, size_bytes) = sqlx::query_as::<_, (Uuid, i64)>(
+            r#"
+            DELETE FROM global.post_attachments WHERE id = $1 AND post_id = $2
+            RETURNING uploader_id, size_bytes
+            "#,
+        )
+        .bind(attachment_id)
+        .bind(post_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(MediaError::AttachmentNotFound)?;
+        release_storage(&mut tx, uploader_id, size_bytes).await?;
+        tx.commit().await?;
+
+        // The row is gone, so a file left behind is unreachable; only log it
+        if let Err(e) = tokio::fs::remove_file(self.storage_path(post_id, attachment_id)).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!(
+                    "Failed to remove file of attachment {}: {}",
+                    attachment_id, e
+                );
+            }
+        }
+
+        self.invalidate_post(post_id, &post.slug).await;
+        info!("Deleted attachment {} of post {}", attachment_id, post_id);
+        Ok(())
+    }
+
+    /// Upload a cover image for a post. It is resized to the configured widths as WebP, given
+    /// a blurhash placeholder and made the post's cover. Only the author and admins may do so.
+    pub async fn upload_cover_image(
+        &self,
+        post_id: i64,
+        user: &AuthUser,
+        data: Vec<u8>,
+    ) -> Result<CoverImage, MediaError> {
+        let image_config = ImageConfig::global();
+        if data.is_empty() {
+            return Err(MediaError::InvalidInput("The image is empty".to_string()));
+        }
+        if data.len() > image_config.max_bytes {
+            return Err(MediaError::TooLarge(image_config.max_bytes));
+        }
+        let post = self.check_write_access(post_id, user).await?;
+
+        let (original, processed) = process_blocking(data, |data| {
+            process_image(data, &image_config.variant_widths)
+        })
+        .await?;
+        let size_bytes = original.len()
+            + processed
+                .variants
+                .iter()
+                .map(|variant| variant.data.len())
+                .sum::<usize>();
+
+        // The row is only committed once the files are stored, and the files are removed again
+        // when the commit fails, so a failed upload leaves nothing
+        let mut tx = self.db.primary().begin().await?;
+        let record = sqlx::query_as::<_, CoverImageRecord>(&format!(
+            r#"
+            INSERT INTO global.cover_images
+                (post_id, blog_id, uploader_id, content_type, width, height, blurhash,
+                 variant_widths, size_bytes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING {}
+            "#,
+            COVER_IMAGE_COLUMNS
+        ))
+        .bind(post_id)
+        .bind(current_blog_id())
+        .bind(user.user_id)
+        .bind(processed.format.to_mime_type())
+        .bind(processed.width as i32)
+        .bind(processed.height as i32)
+        .bind(&processed.blurhash)
+        .bind(
+            processed
+                .variants
+                .iter()
+                .map(|variant| variant.width as i32)
+                .collect::<Vec<_>>(),
+        )
+        .bind(size_bytes as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // The quota is charged once the files are stored, so uploads to the blog never wait for
+        // a file write
+        let stored = async {
+            let dir = self.cover_image_dir(post_id, record.id);
+            tokio::fs::create_dir_all(&dir).await?;
+            tokio::fs::write(dir.join(ORIGINAL_FILE_NAME), &original).await?;
+            for variant in &processed.variants {
+                tokio::fs::write(
+                    dir.join(variant_file_name(variant.width as i32)),
+                    &variant.data,
+                )
+                .await?;
+            }
+            charge_storage(&mut tx, user.user_id, record.size_bytes).await?;
+
+            sqlx::query(
+                "UPDATE global.posts SET cover_image_url = $1, updated_at = NOW() WHERE id = $2",
+            )
+            .bind(record.file_url(ORIGINAL_FILE_NAME))
+            .bind(post_id)
+            .execute(&mut *tx)
+            .await?;
+
+            // Earlier uploads are no longer reachable from the post
+            let replaced = sqlx::query_as::<_, (i64, Uuid, i64)>(
+                r#"
+                DELETE FROM global.cover_images WHERE post_id = $1 AND id <> $2
+                RETURNING id, uploader_id, size_bytes
+                "#,
+            )
+            .bind(post_id)
+            .bind(record.id)
+            .fetch_all(&mut *tx)
+            .await?;
+            for (_, uploader_id, size_bytes) in &replaced {
+                release_storage(&mut tx, *uploader_id, *size_bytes).await?;
+            }
+            tx.commit().await?;
+            Ok::<_, MediaError>(replaced)
+        }
+        .await;
+        let replaced = match stored {
+            Ok(replaced) => replaced,
+            Err(e) => {
+                self.remove_cover_image_files(post_id, record.id).await;
+                return Err(e);
+            }
+        };
+
+        for (image_id, _, _) in replaced {
+            self.remove_cover_image_files(post_id, image_id).await;
+        }
+
+        self.invalidate_post(post_id, &post.slug).await;
+        info!(
+            "Processed cover image of post {} into {} variants",
+            post_id,
+            processed.variants.len()
+        );
+        Ok(record.into_cover_image())
+    }
+
+    /// Copy the uploaded cover image of a post to another post of the same blog and make it
+    /// that post's cover, as when duplicating a post. Does nothing when the source post has no
+    /// uploaded cover in use.
+    pub async fn copy_cover_image(
+        &self,
+        source_post_id: i64,
+        source_cover_url: Option<&str>,
+        target_post_id: i64,
+        uploader_id: Uuid,
+    ) -> Result<Option<CoverImage>, MediaError> {
+        let source_cover_url = match source_cover_url {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let source = sqlx::query_as::<_, CoverImageRecord>(&format!(
+            "SELECT {} FROM global.cover_images WHERE post_id = $1 ORDER BY id DESC LIMIT 1",
+            COVER_IMAGE_COLUMNS
+        ))
+        .bind(source_post_id)
+        .fetch_optional(self.db.primary())
+        .await?
+        .filter(|record| record.file_url(ORIGINAL_FILE_NAME) == source_cover_url);
+        let source = match source {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+
+        // As with uploads, the row is only committed once the files are stored, and the copy
+        // counts against the quotas of whoever made it
+        let mut tx = self.db.primary().begin().await?;
+        let record = sqlx::query_as::<_, CoverImageRecord>(&format!(
+            r#"
+            INSERT INTO global.cover_images
+                (post_id, blog_id, uploader_id, content_type, width, height, blurhash,
+                 variant_widths, size_bytes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING {}
+            "#,
+            COVER_IMAGE_COLUMNS
+        ))
+        .bind(target_post_id)
+        .bind(current_blog_id())
+        .bind(uploader_id)
+        .bind(&source.content_type)
+        .bind(source.width)
+        .bind(source.height)
+        .bind(&source.blurhash)
+        .bind(&source.variant_widths)
+        .bind(source.size_bytes)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let stored = async {
+            let source_dir = self.cover_image_dir(source_post_id, source.id);
+            let dir = self.cover_image_dir(target_post_id, record.id);
+            tokio::fs::create_dir_all(&dir).await?;
+            let file_names = std::iter::once(ORIGINAL_FILE_NAME.to_string()).chain(
+                source
+                    .variant_widths
+                    .iter()
+                    .map(|&width| variant_file_name(width)),
+            );
+            for file_name in file_names {
+                tokio::fs::copy(source_dir.join(&file_name), dir.join(&file_name)).await?;
+            }
+            charge_storage(&mut tx, uploader_id, record.size_bytes).await?;
+
+            sqlx::query(
+                "UPDATE global.posts SET cover_image_url = $1, updated_at = NOW() WHERE id = $2",
+            )
+            .bind(record.file_url(ORIGINAL_FILE_NAME))
+            .bind(target_post_id)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            Ok::<_, MediaError>(())
+        }
+        .await;
+        if let Err(e) = stored {
+            self.remove_cover_image_files(target_post_id, record.id)
+                .await;
+            return Err(e);
+        }
+
+        info!(
+            "Copied cover image {} of post {} to post {}",
+            source.id, source_post_id, target_post_id
+        );
+        Ok(Some(record.into_cover_image()))
+    }
+
+    /// Read a file of a cover image, with its content type. Covers are shown with paywalled
+    /// previews too, so only drafts are restricted, to their author and admins.
+    pub async fn cover_image_file(
+        &self,
+        post_id: i64,
+        image_id: i64,
+        file_name: &str,
+        user: Option<&AuthUser>,
+    ) -> Result<(String, Vec<u8>), MediaError> {
+        let post = self.post_access(post_id).await?;
+        let privileged = user.is_some_and(|user| {
+            user.role == Role::Admin || user.user_id == post.user_id
+        });
+        if post.is_draft && !privileged {
+            return Err(MediaError::PostNotFound);
+        }
+
+        let record = sqlx::query_as::<_, CoverImageRecord>(&format!(
+            "SELECT {} FROM global.cover_images WHERE id = $1 AND post_id = $2",
+            COVER_IMAGE_COLUMNS
+        ))
+        .bind(image_id)
+        .bind(post_id)
+        .fetch_optional(self.db.read())
+        .await?
+        .ok_or(MediaError::ImageNotFound)?;
+
+        // Only names of files that were written are accepted, so the name is safe in a path
+        let content_type = if file_name == ORIGINAL_FILE_NAME {
+            record.content_type
+        } else if record
+            .variant_widths
+            .iter()
+            .any(|&width| variant_file_name(width) == file_name)
+        {
+            "image/webp".to_string()
+        } else {
+            return Err(MediaError::ImageNotFound);
+        };
+
+        match tokio::fs::read(self.cover_image_dir(post_id, image_id).join(file_name)).await {
+            Ok(data) => Ok((content_type, data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                error!("File {} of cover image {} is missing", file_name, image_id);
+                Err(MediaError::ImageNotFound)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Upload the user's avatar, cropped square and resized as WebP. Each upload gets new URLs,
+    /// so cached copies of the previous avatar are never shown in its place.
+    pub async fn upload_avatar(
+        &self,
+        user: &AuthUser,
+        data: Vec<u8>,
+    ) -> Result<AvatarResponse, MediaError> {
+        let max_bytes = ImageConfig::global().max_bytes;
+        if data.is_empty() {
+            return Err(MediaError::InvalidInput("The image is empty".to_string()));
+        }
+        if data.len() > max_bytes {
+            return Err(MediaError::TooLarge(max_bytes));
+        }
+
+        let (_, processed) =
+            process_blocking(data, |data| process_avatar(data, &AVATAR_WIDTHS)).await?;
+
+        let upload_id = Uuid::new_v4();
+        let dir = self.avatar_dir(user.user_id).join(upload_id.to_string());
+        tokio::fs::create_dir_all(&dir).await?;
+        let mut variants = Vec::new();
+        for variant in &processed.variants {
+            let file_name = variant_file_name(variant.width as i32);
+            tokio::fs::write(dir.join(&file_name), &variant.data).await?;
+            variants.push(ImageVariant {
+                width: variant.width as i32,
+                height: variant.height as i32,
+                url: avatar_file_url(user.user_id, upload_id, &file_name),
+                content_type: "image/webp".to_string(),
+            });
+        }
+        let avatar_url = variants
+            .last()
+            .map(|variant| variant.url.clone())
+            .ok_or_else(|| MediaError::InternalError("No avatar variants".to_string()))?;
+
+        sqlx::query("UPDATE global.users SET avatar_url = $1, updated_at = NOW() WHERE id = $2")
+            .bind(&avatar_url)
+            .bind(user.user_id)
+            .execute(self.db.primary())
+            .await?;
+
+        self.remove_avatars(user.user_id, Some(upload_id)).await;
+        purge(
+            self.redis_cache.as_ref(),
+            &[SurrogateKey::User(user.user_id)],
+        )
+        .await;
+        info!("Updated avatar of user {}", user.user_id);
+        Ok(AvatarResponse {
+            avatar_url,
+            variants,
+        })
+    }
+
+    /// Remove the user's uploaded avatar, falling back to their Gravatar
+    pub async fn delete_avatar(&self, user: &AuthUser) -> Result<AvatarResponse, MediaError> {
+        let avatar_url: String = sqlx::query_scalar(
+            r#"
+            UPDATE global.users SET avatar_url = NULL, updated_at = NOW()
+            WHERE id = $1
+            RETURNING global.user_avatar_url(avatar_url, email)
+            "#,
+        )
+        .bind(user.user_id)
+        .fetch_one(self.db.primary())
+        .await?;
+
+        self.remove_avatars(user.user_id, None).await;
+        purge(
+            self.redis_cache.as_ref(),
+            &[SurrogateKey::User(user.user_id)],
+        )
+        .await;
+        Ok(AvatarResponse {
+            avatar_url,
+            variants: Vec::new(),
+        })
+    }
+
+    // Delete stored avatar uploads of a user, except the one being kept
+    async fn remove_avatars(&self, user_id: Uuid, keep: Option<Uuid>) {
+        let mut entries = match tokio::fs::read_dir(self.avatar_dir(user_id)).await {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if keep.is_some_and(|keep| entry.file_name() == keep.to_string().as_str()) {
+                continue;
+            }
+            if let Err(e) = tokio::fs::remove_dir_all(entry.path()).await {
+                error!("Failed to remove old avatar of user {}: {}", user_id, e);
+            }
+        }
+    }
+
+    /// Read a file of an uploaded avatar. Avatars are public.
+    pub async fn avatar_file(
+        &self,
+        user_id: Uuid,
+        upload_id: Uuid,
+        file_name: &str,
+    ) -> Result<Vec<u8>, MediaError> {
+        // Only names in the form variants are written with are accepted, so the name is safe in
+        // a path. Avatars smaller than the smallest width keep their own size.
+        let largest = AVATAR_WIDTHS[AVATAR_WIDTHS.len() - 1] as i32;
+        let is_variant_name = file_name
+            .strip_suffix(".webp")
+            .and_then(|width| width.parse::<i32>().ok())
+            .is_some_and(|width| {
+                (1..=largest).contains(&width) && variant_file_name(width) == file_name
+            });
+        if !is_variant_name {
+            return Err(MediaError::ImageNotFound);
+        }
+
+        let path = self
+            .avatar_dir(user_id)
+            .join(upload_id.to_string())
+            .join(file_name);
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(MediaError::ImageNotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::membership::model::{CreateTierRequest, MembershipError, SetUserTierRequest};
+use crate::membership::service::MembershipService;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+fn membership_error_response(e: MembershipError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Membership error: {:?}", e);
+    let status = match e {
+        MembershipError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+        MembershipError::TierNotFound(_) | MembershipError::UserNotFound => StatusCode::NOT_FOUND,
+        MembershipError::TierExists => StatusCode::CONFLICT,
+        MembershipError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can manage memberships" })),
+    )
+        .into_response()
+}
+
+/// List membership tiers
+#[utoipa::path(
+    get,
+    path = "/api/membership/tiers",
+    tag = "membership",
+    responses(
+        (status = 200, description = "Membership tiers retrieved successfully", body = Vec<MembershipTier>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_tiers(State(service): State<Arc<MembershipService>>) -> Response {
+    match service.list_tiers().await {
+        Ok(tiers) => (StatusCode::OK, Json(json!(tiers))).into_response(),
+        Err(e) => membership_error_response(e),
+    }
+}
+
+/// Create a membership tier (admin only)
+#[utoipa::path(
+    post,
+    path = "/api/admin/membership/tiers",
+    tag = "membership",
+    request_body = CreateTierRequest,
+    responses(
+        (status = 201, description = "Tier created", body = MembershipTier),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "Tier already exists"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_tier(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<MembershipService>>,
+    Json(request): Json<CreateTierRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.create_tier(request).await {
+        Ok(tier) => (StatusCode::CREATED, Json(json!(tier))).into_response(),
+        Err(e) => membership_error_response(e),
+    }
+}
+
+/// Get a user's membership (admins, or the user themselves)
+#[utoipa::path(
+    get,
+    path = "/api/admin/users/{user_id}/membership",
+    tag = "membership",
+    params(
+        ("user_id" = Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Membership retrieved successfully", body = UserMembership),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_user_membership(
+    Extension(user): Extension<AuthUser>,
+    Path(user_id): Path<Uuid>,
+    State(service): State<Arc<MembershipService>>,
+) -> Response {
+    if user.role != Role::Admin && user.user_id != user_id {
+        return forbidden();
+    }
+
+    match service.get_user_membership(user_id).await {
+        Ok(membership) => (StatusCode::OK, Json(json!(membership))).into_response(),
+        Err(e) => membership_error_response(e),
+    }
+}
+
+/// Set or remove a user's membership tier (admin only)
+#[utoipa::path(
+    put,
+    path = "/api/admin/users/{user_id}/membership",
+    tag = "membership",
+    params(
+        ("user_id" = Uuid, Path, description = "User ID")
+    ),
+    request_body = SetUserTierRequest,
+    responses(
+        (status = 200, description = "Membership updated", body = UserMembership),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "User or tier not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_user_membership(
+    Extension(user): Extension<AuthUser>,
+    Path(user_id): Path<Uuid>,
+    State(service): State<Arc<MembershipService>>,
+    Json(request): Json<SetUserTierRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service
+        .set_user_tier(user_id, request.tier.as_deref(), request.expires_at)
+        .await
+    {
+        Ok(membership) => {
+            info!("Admin {} updated membership of {}", user.user_id, user_id);
+            (StatusCode::OK, Json(json!(membership))).into_response()
+        }
+        Err(e) => membership_error_response(e),
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Level of readers without a membership; posts at this level are free to read
+pub const FREE_TIER_LEVEL: i32 = 0;
+
+/// A membership tier. Higher levels unlock everything lower levels do.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct MembershipTier {
+    /// Tier ID
+    #[schema(example = "2")]
+    pub id: i64,
+
+    /// Unique tier name
+    #[schema(example = "premium")]
+    pub name: String,
+
+    /// Access level; a reader can open posts whose tier level is at most their own
+    #[schema(example = "2")]
+    pub level: i32,
+
+    /// Human readable description
+    #[schema(example = "Full access to all premium posts")]
+    pub description: Option<String>,
+}
+
+/// Request to create a membership tier
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateTierRequest {
+    /// Unique tier name
+    #[schema(example = "premium")]
+    pub name: String,
+
+    /// Access level, must be greater than zero
+    #[schema(example = "2")]
+    pub level: i32,
+
+    /// Human readable description
+    #[schema(example = "Full access to all premium posts")]
+    pub description: Option<String>,
+}
+
+/// Request to set a user's membership tier
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetUserTierRequest {
+    /// Tier name, or null to remove the membership
+    #[schema(example = "premium")]
+    pub tier: Option<String>,
+
+    /// When the membership lapses; never if omitted
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A user's current membership
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserMembership {
+    /// User ID
+    pub user_id: Uuid,
+
+    /// Active tier, absent for free readers
+    pub tier: Option<MembershipTier>,
+
+    /// When the membership lapses
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Error types for membership operations
+#[derive(Debug, thiserror::Error)]
+pub enum MembershipError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Tier not found: {0}")]
+    TierNotFound(String),
+
+    #[error("User not found")]
+    UserNotFound,
+
+    #[error("Tier already exists")]
+    TierExists,
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+}
@@ -0,0 +1,192 @@
+use crate::membership::model::{
+    CreateTierRequest, MembershipError, MembershipTier, UserMembership, FREE_TIER_LEVEL,
+};
+use crate::tenant::middleware::current_blog_id;
+use chrono::Utc;
+use sqlx::{PgPool, Row};
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct MembershipService {
+    pool: PgPool,
+}
+
+impl MembershipService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // List all tiers, lowest level first
+    pub async fn list_tiers(&self) -> Result<Vec<MembershipTier>, MembershipError> {
+        let tiers = sqlx::query_as::<_, MembershipTier>(
+            "SELECT id, name, level, description FROM global.membership_tiers ORDER BY level",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tiers)
+    }
+
+    // Create a new tier
+    pub async fn create_tier(
+        &self,
+        request: CreateTierRequest,
+    ) -> Result<MembershipTier, MembershipError> {
+        let name = request.name.trim();
+        if name.is_empty() {
+            return Err(MembershipError::InvalidParameter(
+                "Tier name cannot be empty".to_string(),
+            ));
+        }
+        if request.level <= FREE_TIER_LEVEL {
+            return Err(MembershipError::InvalidParameter(
+                "Tier level must be greater than zero".to_string(),
+            ));
+        }
+
+        let tier = sqlx::query_as::<_, MembershipTier>(
+            r#"
+            INSERT INTO global.membership_tiers (name, level, description)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            RETURNING id, name, level, description
+            "#,
+        )
+        .bind(name)
+        .bind(request.level)
+        .bind(&request.description)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(MembershipError::TierExists)?;
+
+        info!(
+            "Created membership tier {} (level {})",
+            tier.name, tier.level
+        );
+        Ok(tier)
+    }
+
+    // Look up a tier by name
+    pub async fn get_tier_by_name(&self, name: &str) -> Result<MembershipTier, MembershipError> {
+        sqlx::query_as::<_, MembershipTier>(
+            "SELECT id, name, level, description FROM global.membership_tiers WHERE name = $1",
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| MembershipError::TierNotFound(name.to_string()))
+    }
+
+    // Look up a tier by ID
+    pub async fn get_tier(&self, id: i64) -> Result<Option<MembershipTier>, MembershipError> {
+        let tier = sqlx::query_as::<_, MembershipTier>(
+            "SELECT id, name, level, description FROM global.membership_tiers WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(tier)
+    }
+
+    // Get a user's membership; lapsed memberships are reported as free
+    pub async fn get_user_membership(
+        &self,
+        user_id: Uuid,
+    ) -> Result<UserMembership, MembershipError> {
+        let row = sqlx::query(
+            r#"
+            SELECT t.id, t.name, t.level, t.description, m.expires_at
+            FROM global.user_memberships m
+            JOIN global.membership_tiers t ON t.id = m.tier_id
+            WHERE m.user_id = $1 AND m.blog_id = $2
+                AND (m.expires_at IS NULL OR m.expires_at > NOW())
+            "#,
+        )
+        .bind(user_id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match row {
+            Some(row) => UserMembership {
+                user_id,
+                tier: Some(MembershipTier {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    level: row.get("level"),
+                    description: row.get("description"),
+                }),
+                expires_at: row.get("expires_at"),
+            },
+            None => UserMembership {
+                user_id,
+                tier: None,
+                expires_at: None,
+            },
+        })
+    }
+
+    // Effective access level of a user
+    pub async fn get_user_tier_level(&self, user_id: Uuid) -> Result<i32, MembershipError> {
+        let membership = self.get_user_membership(user_id).await?;
+        Ok(membership
+            .tier
+            .map(|tier| tier.level)
+            .unwrap_or(FREE_TIER_LEVEL))
+    }
+
+    // Grant, change or remove a user's membership
+    pub async fn set_user_tier(
+        &self,
+        user_id: Uuid,
+        tier_name: Option<&str>,
+        expires_at: Option<chrono::DateTime<Utc>>,
+    ) -> Result<UserMembership, MembershipError> {
+        let user_exists = sqlx::query("SELECT EXISTS(SELECT 1 FROM global.users WHERE id = $1)")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await?
+            .get::<bool, _>(0);
+
+        if !user_exists {
+            return Err(MembershipError::UserNotFound);
+        }
+
+        match tier_name {
+            Some(name) => {
+                let tier = self.get_tier_by_name(name).await?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO global.user_memberships (user_id, blog_id, tier_id, expires_at, updated_at)
+                    VALUES ($1, $4, $2, $3, NOW())
+                    ON CONFLICT (user_id, blog_id) DO UPDATE
+                    SET tier_id = EXCLUDED.tier_id, expires_at = EXCLUDED.expires_at, updated_at = NOW()
+                    "#,
+                )
+                .bind(user_id)
+                .bind(tier.id)
+                .bind(expires_at)
+                .bind(current_blog_id())
+                .execute(&self.pool)
+                .await?;
+
+                info!("Set membership tier of user {} to {}", user_id, tier.name);
+            }
+            None => {
+                sqlx::query(
+                    "DELETE FROM global.user_memberships WHERE user_id = $1 AND blog_id = $2",
+                )
+                .bind(user_id)
+                .bind(current_blog_id())
+                .execute(&self.pool)
+                .await?;
+
+                info!("Removed membership of user {}", user_id);
+            }
+        }
+
+        self.get_user_membership(user_id).await
+    }
+}
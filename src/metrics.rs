@@ -0,0 +1,397 @@
+use crate::cache::breaker::{self, BreakerState, CircuitBreaker};
+use axum::{
+    extract::MatchedPath,
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the query latency histogram buckets
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Route reported for queries that run outside of a request, e.g. in background tasks
+pub const NO_ROUTE: &str = "none";
+
+tokio::task_local! {
+    static CURRENT_ROUTE: String;
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Histogram {
+    // Count per bucket, non-cumulative; the extra last slot is the +Inf bucket
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+// Histograms keyed by (query name, route)
+type QueryHistograms = BTreeMap<(&'static str, String), Histogram>;
+
+fn query_histograms() -> &'static Mutex<QueryHistograms> {
+    static HISTOGRAMS: OnceLock<Mutex<QueryHistograms>> = OnceLock::new();
+    HISTOGRAMS.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+// Rows purged by retention cleanups, keyed by data class
+type PurgedRows = BTreeMap<&'static str, u64>;
+
+fn purged_rows() -> &'static Mutex<PurgedRows> {
+    static PURGED: OnceLock<Mutex<PurgedRows>> = OnceLock::new();
+    PURGED.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+// Database pools reported, by name
+fn pools() -> &'static Mutex<BTreeMap<&'static str, PgPool>> {
+    static POOLS: OnceLock<Mutex<BTreeMap<&'static str, PgPool>>> = OnceLock::new();
+    POOLS.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+// Sampled waits for a connection and how many of them were slow, keyed by pool
+type PoolAcquires = BTreeMap<&'static str, (Histogram, u64)>;
+
+fn pool_acquires() -> &'static Mutex<PoolAcquires> {
+    static ACQUIRES: OnceLock<Mutex<PoolAcquires>> = OnceLock::new();
+    ACQUIRES.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+// Utilization of a database pool when the metrics are read, with its sampled waits
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PoolUsage {
+    name: &'static str,
+    size: u32,
+    idle: u32,
+    max: u32,
+    acquire: Histogram,
+    slow_acquires: u64,
+}
+
+/// Report the utilization of a database pool under `name`, replacing any pool of that name
+pub fn register_pool(name: &'static str, pool: PgPool) {
+    pools()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(name, pool);
+}
+
+/// Record one sampled wait for a connection of the named pool
+pub fn record_pool_acquire(name: &'static str, waited: Duration, slow: bool) {
+    let mut acquires = pool_acquires()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (histogram, slow_acquires) = acquires.entry(name).or_default();
+    histogram.observe(waited.as_secs_f64());
+    if slow {
+        *slow_acquires += 1;
+    }
+}
+
+/// Route template of the request being handled, such as `/api/v1/posts/:id`
+pub fn current_route() -> String {
+    CURRENT_ROUTE
+        .try_with(|route| route.clone())
+        .unwrap_or_else(|_| NO_ROUTE.to_string())
+}
+
+/// Record the latency of one execution of the named query
+pub fn record_query(name: &'static str, route: String, elapsed: Duration) {
+    let mut histograms = query_histograms()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    histograms
+        .entry((name, route))
+        .or_default()
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Record rows of a data class purged by a retention cleanup
+pub fn record_purged_rows(data_class: &'static str, rows: u64) {
+    let mut purged = purged_rows()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *purged.entry(data_class).or_default() += rows;
+}
+
+/// Middleware that makes the matched route available to the queries run while handling the
+/// request, so metrics and slow query logs can say where a query came from
+pub async fn route_context<B>(req: Request<B>, next: Next<B>) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| NO_ROUTE.to_string());
+
+    CURRENT_ROUTE.scope(route, next.run(req)).await
+}
+
+fn render_histogram(out: &mut String, metric: &str, labels: &str, histogram: &Histogram) {
+    let mut cumulative = 0;
+    for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets.iter()) {
+        cumulative += count;
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"{}\"}} {}",
+            metric, labels, bound, cumulative
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_bucket{{{},le=\"+Inf\"}} {}",
+        metric, labels, histogram.count
+    );
+    let _ = writeln!(out, "{}_sum{{{}}} {}", metric, labels, histogram.sum);
+    let _ = writeln!(out, "{}_count{{{}}} {}", metric, labels, histogram.count);
+}
+
+fn render(histograms: &QueryHistograms, purged: &PurgedRows, pools: &[PoolUsage]) -> String {
+    let mut out = String::new();
+    out.push_str("# HELP db_query_duration_seconds Latency of database queries\n");
+    out.push_str("# TYPE db_query_duration_seconds histogram\n");
+
+    for ((name, route), histogram) in histograms {
+        let labels = format!(
+            "query=\"{}\",route=\"{}\"",
+            name,
+            route.replace('"', "\\\"")
+        );
+
+        render_histogram(&mut out, "db_query_duration_seconds", &labels, histogram);
+    }
+
+    out.push_str("# HELP retention_purged_rows_total Rows purged by retention cleanups\n");
+    out.push_str("# TYPE retention_purged_rows_total counter\n");
+    for (data_class, rows) in purged {
+        let _ = writeln!(
+            out,
+            "retention_purged_rows_total{{data_class=\"{}\"}} {}",
+            data_class, rows
+        );
+    }
+
+    out.push_str("# HELP db_pool_connections Open connections of database pools\n");
+    out.push_str("# TYPE db_pool_connections gauge\n");
+    for pool in pools {
+        let _ = writeln!(
+            out,
+            "db_pool_connections{{pool=\"{}\",state=\"idle\"}} {}",
+            pool.name, pool.idle
+        );
+        let _ = writeln!(
+            out,
+            "db_pool_connections{{pool=\"{}\",state=\"in_use\"}} {}",
+            pool.name,
+            pool.size.saturating_sub(pool.idle)
+        );
+    }
+    out.push_str("# HELP db_pool_max_connections Connections database pools may open\n");
+    out.push_str("# TYPE db_pool_max_connections gauge\n");
+    for pool in pools {
+        let _ = writeln!(
+            out,
+            "db_pool_max_connections{{pool=\"{}\"}} {}",
+            pool.name, pool.max
+        );
+    }
+    out.push_str("# HELP db_pool_acquire_duration_seconds Sampled waits for a pool connection\n");
+    out.push_str("# TYPE db_pool_acquire_duration_seconds histogram\n");
+    for pool in pools {
+        let labels = format!("pool=\"{}\"", pool.name);
+        render_histogram(
+            &mut out,
+            "db_pool_acquire_duration_seconds",
+            &labels,
+            &pool.acquire,
+        );
+    }
+    out.push_str(
+        "# HELP db_pool_slow_acquires_total Sampled waits for a connection over the threshold\n",
+    );
+    out.push_str("# TYPE db_pool_slow_acquires_total counter\n");
+    for pool in pools {
+        let _ = writeln!(
+            out,
+            "db_pool_slow_acquires_total{{pool=\"{}\"}} {}",
+            pool.name, pool.slow_acquires
+        );
+    }
+
+    out
+}
+
+// State of circuit breakers, one gauge per state set to 1 for the current one, and how
+// often each opened
+fn render_breakers(breakers: &[Arc<CircuitBreaker>]) -> String {
+    let mut out = String::new();
+    out.push_str("# HELP circuit_breaker_state Current state of circuit breakers\n");
+    out.push_str("# TYPE circuit_breaker_state gauge\n");
+    for breaker in breakers {
+        let current = breaker.state();
+        for state in BreakerState::ALL {
+            let _ = writeln!(
+                out,
+                "circuit_breaker_state{{breaker=\"{}\",state=\"{}\"}} {}",
+                breaker.name(),
+                state.as_str(),
+                u8::from(state == current)
+            );
+        }
+    }
+    out.push_str("# HELP circuit_breaker_trips_total Times circuit breakers opened\n");
+    out.push_str("# TYPE circuit_breaker_trips_total counter\n");
+    for breaker in breakers {
+        let _ = writeln!(
+            out,
+            "circuit_breaker_trips_total{{breaker=\"{}\"}} {}",
+            breaker.name(),
+            breaker.trips()
+        );
+    }
+
+    out
+}
+
+fn pool_usage() -> Vec<PoolUsage> {
+    let pools = pools()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let acquires = pool_acquires()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    pools
+        .iter()
+        .map(|(name, pool)| {
+            let (acquire, slow_acquires) = acquires.get(name).cloned().unwrap_or_default();
+            PoolUsage {
+                name,
+                size: pool.size(),
+                idle: pool.num_idle() as u32,
+                max: pool.options().get_max_connections(),
+                acquire,
+                slow_acquires,
+            }
+        })
+        .collect()
+}
+
+/// Expose the collected metrics in the Prometheus text format
+pub async fn metrics_handler() -> impl IntoResponse {
+    let mut body = {
+        let histograms = query_histograms()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let purged = purged_rows()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        render(&histograms, &purged, &pool_usage())
+    };
+    body.push_str(&render_breakers(&breaker::breakers()));
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        body,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_by_upper_bound() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.001);
+        histogram.observe(0.3);
+        histogram.observe(60.0);
+
+        assert_eq!(histogram.buckets[0], 1);
+        assert_eq!(histogram.buckets[7], 1);
+        assert_eq!(histogram.buckets[LATENCY_BUCKETS.len()], 1);
+        assert_eq!(histogram.count, 3);
+    }
+
+    #[test]
+    fn test_render_uses_cumulative_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.002);
+        histogram.observe(0.02);
+
+        let mut histograms = QueryHistograms::new();
+        histograms.insert(
+            ("posts.popular", "/api/v1/posts/popular".to_string()),
+            histogram,
+        );
+        let text = render(&histograms, &PurgedRows::new(), &[]);
+
+        assert!(text.contains(
+            "db_query_duration_seconds_bucket{query=\"posts.popular\",route=\"/api/v1/posts/popular\",le=\"0.005\"} 1"
+        ));
+        assert!(text.contains(
+            "db_query_duration_seconds_bucket{query=\"posts.popular\",route=\"/api/v1/posts/popular\",le=\"0.025\"} 2"
+        ));
+        assert!(text.contains(
+            "db_query_duration_seconds_count{query=\"posts.popular\",route=\"/api/v1/posts/popular\"} 2"
+        ));
+    }
+
+    #[test]
+    fn test_render_purged_rows() {
+        let mut purged = PurgedRows::new();
+        purged.insert("interactions", 12);
+        let text = render(&QueryHistograms::new(), &purged, &[]);
+
+        assert!(text.contains("retention_purged_rows_total{data_class=\"interactions\"} 12"));
+    }
+
+    #[test]
+    fn test_render_pool_usage() {
+        let mut acquire = Histogram::default();
+        acquire.observe(0.2);
+        let pool = PoolUsage {
+            name: "primary",
+            size: 4,
+            idle: 1,
+            max: 10,
+            acquire,
+            slow_acquires: 1,
+        };
+        let text = render(&QueryHistograms::new(), &PurgedRows::new(), &[pool]);
+
+        assert!(text.contains("db_pool_connections{pool=\"primary\",state=\"idle\"} 1"));
+        assert!(text.contains("db_pool_connections{pool=\"primary\",state=\"in_use\"} 3"));
+        assert!(text.contains("db_pool_max_connections{pool=\"primary\"} 10"));
+        assert!(text
+            .contains("db_pool_acquire_duration_seconds_bucket{pool=\"primary\",le=\"0.25\"} 1"));
+        assert!(text.contains("db_pool_slow_acquires_total{pool=\"primary\"} 1"));
+    }
+
+    #[test]
+    fn test_render_breakers() {
+        let breaker = Arc::new(CircuitBreaker::new("redis", 1, Duration::from_secs(30)));
+        breaker.record_failure();
+        let text = render_breakers(&[breaker]);
+
+        assert!(text.contains("circuit_breaker_state{breaker=\"redis\",state=\"open\"} 1"));
+        assert!(text.contains("circuit_breaker_state{breaker=\"redis\",state=\"closed\"} 0"));
+        assert!(text.contains("circuit_breaker_trips_total{breaker=\"redis\"} 1"));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::jobs::controller::job_error_response;
+use crate::jobs::service::JobService;
+use crate::newsletter::model::{
+    CreateCampaignRequest, NewsletterError, SubscribeRequest, TokenParams, NEWSLETTER_CAMPAIGN_JOB,
+};
+use crate::newsletter::service::NewsletterService;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use crate::tenant::model::Blog;
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn newsletter_error_response(e: NewsletterError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        NewsletterError::InvalidEmail | NewsletterError::ValidationError(_) => {
+            StatusCode::BAD_REQUEST
+        }
+        NewsletterError::InvalidToken
+        | NewsletterError::PostNotFound
+        | NewsletterError::CampaignNotFound => StatusCode::NOT_FOUND,
+        NewsletterError::MailerNotConfigured => StatusCode::SERVICE_UNAVAILABLE,
+        NewsletterError::MailerError(_) | NewsletterError::DatabaseError(_) => {
+            error!("Newsletter error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn admin_required() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Admin access required" })),
+    )
+        .into_response()
+}
+
+/// Subscribe to the newsletter
+///
+/// Emails a confirmation link to the address; it only receives the newsletter once the link
+/// is followed. The response is the same whether or not the address was already subscribed.
+#[utoipa::path(
+    post,
+    path = "/api/newsletter/subscribe",
+    tag = "newsletter",
+    request_body = SubscribeRequest,
+    responses(
+        (status = 202, description = "Confirmation email sent"),
+        (status = 400, description = "Invalid email address"),
+        (status = 503, description = "Newsletter email is not configured"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn subscribe(
+    Extension(blog): Extension<Blog>,
+    State(service): State<Arc<NewsletterService>>,
+    Json(request): Json<SubscribeRequest>,
+) -> Response {
+    match service.subscribe(&blog, &request.email).await {
+        Ok(()) => (
+            StatusCode::ACCEPTED,
+            Json(json!({ "message": "Check your inbox to confirm your subscription" })),
+        )
+            .into_response(),
+        Err(e) => newsletter_error_response(e),
+    }
+}
+
+/// Confirm a newsletter subscription
+///
+/// Target of the link in the confirmation email. Links expire after seven days.
+#[utoipa::path(
+    get,
+    path = "/api/newsletter/confirm",
+    tag = "newsletter",
+    params(TokenParams),
+    responses(
+        (status = 200, description = "Subscription confirmed", body = SubscriptionResponse),
+        (status = 404, description = "Invalid or expired link"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn confirm(
+    State(service): State<Arc<NewsletterService>>,
+    Query(params): Query<TokenParams>,
+) -> Response {
+    match service.confirm(&params.token).await {
+        Ok(subscription) => (StatusCode::OK, Json(subscription)).into_response(),
+        Err(e) => newsletter_error_response(e),
+    }
+}
+
+/// Unsubscribe from the newsletter
+///
+/// Target of the link in every newsletter email. Following it again has no effect.
+#[utoipa::path(
+    get,
+    path = "/api/newsletter/unsubscribe",
+    tag = "newsletter",
+    params(TokenParams),
+    responses(
+        (status = 200, description = "Unsubscribed", body = SubscriptionResponse),
+        (status = 404, description = "Invalid link"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn unsubscribe(
+    State(service): State<Arc<NewsletterService>>,
+    Query(params): Query<TokenParams>,
+) -> Response {
+    match service.unsubscribe(&params.token).await {
+        Ok(subscription) => (StatusCode::OK, Json(subscription)).into_response(),
+        Err(e) => newsletter_error_response(e),
+    }
+}
+
+/// Send a post to the newsletter subscribers (admin only)
+///
+/// Creates a campaign for the subscribers confirmed now and queues the job that emails them
+/// in batches. Posts gated behind a membership tier are sent as an excerpt with a link.
+/// Progress is on the campaign.
+#[utoipa::path(
+    post,
+    path = "/api/admin/newsletter/campaigns",
+    tag = "newsletter",
+    request_body = CreateCampaignRequest,
+    responses(
+        (status = 202, description = "Campaign created and queued", body = Campaign),
+        (status = 400, description = "Invalid subject"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Post not found or not published"),
+        (status = 503, description = "Newsletter email is not configured"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_campaign(
+    Extension(user): Extension<AuthUser>,
+    Extension(jobs): Extension<Arc<JobService>>,
+    State(service): State<Arc<NewsletterService>>,
+    Json(request): Json<CreateCampaignRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    let campaign = match service
+        .create_campaign(request.post_id, request.subject.as_deref(), user.user_id)
+        .await
+    {
+        Ok(campaign) => campaign,
+        Err(e) => return newsletter_error_response(e),
+    };
+
+    let payload = json!({ "campaign_id": campaign.id });
+    match jobs
+        .enqueue(NEWSLETTER_CAMPAIGN_JOB, payload, Some(user.user_id))
+        .await
+    {
+        Ok(_) => (StatusCode::ACCEPTED, Json(campaign)).into_response(),
+        Err(e) => job_error_response(e),
+    }
+}
+
+/// Get a newsletter campaign (admin only)
+#[utoipa::path(
+    get,
+    path = "/api/admin/newsletter/campaigns/{id}",
+    tag = "newsletter",
+    params(
+        ("id" = i64, Path, description = "Campaign ID")
+    ),
+    responses(
+        (status = 200, description = "Campaign retrieved successfully", body = Campaign),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Campaign not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_campaign(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<NewsletterService>>,
+    Path(id): Path<i64>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    match service.get_campaign(id).await {
+        Ok(campaign) => (StatusCode::OK, Json(campaign)).into_response(),
+        Err(e) => newsletter_error_response(e),
+    }
+}
+
+/// List the deliveries of a newsletter campaign (admin only)
+///
+/// One delivery per recipient, with the error of failed sends.
+#[utoipa::path(
+    get,
+    path = "/api/admin/newsletter/campaigns/{id}/deliveries",
+    tag = "newsletter",
+    params(
+        ("id" = i64, Path, description = "Campaign ID"),
+        PageParams
+    ),
+    responses(
+        (status = 200, description = "Deliveries retrieved successfully", body = [Delivery]),
+        (status = 400, description = "Invalid cursor"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Campaign not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_deliveries(
+    Extension(user): Extension<AuthUser>,
+    OriginalUri(uri): OriginalUri,
+    State(service): State<Arc<NewsletterService>>,
+    Path(id): Path<i64>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_required();
+    }
+
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return newsletter_error_response(NewsletterError::ValidationError(e.to_string()))
+        }
+    };
+
+    match service.get_deliveries(id, &pagination).await {
+        Ok(deliveries) => {
+            let headers = pagination.headers(&uri, deliveries.len());
+            (StatusCode::OK, headers, Json(deliveries)).into_response()
+        }
+        Err(e) => newsletter_error_response(e),
+    }
+}
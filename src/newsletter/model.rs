@@ -0,0 +1,175 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Kind of the job that sends a campaign to its recipients
+pub const NEWSLETTER_CAMPAIGN_JOB: &str = "newsletter_campaign";
+
+/// Where a subscriber is in the double opt-in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberStatus {
+    /// Waiting for the address to be confirmed
+    Pending,
+    Confirmed,
+    Unsubscribed,
+}
+
+impl SubscriberStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SubscriberStatus::Pending => "pending",
+            SubscriberStatus::Confirmed => "confirmed",
+            SubscriberStatus::Unsubscribed => "unsubscribed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(SubscriberStatus::Pending),
+            "confirmed" => Some(SubscriberStatus::Confirmed),
+            "unsubscribed" => Some(SubscriberStatus::Unsubscribed),
+            _ => None,
+        }
+    }
+}
+
+/// Request to subscribe to the newsletter
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SubscribeRequest {
+    #[schema(example = "reader@example.com")]
+    pub email: String,
+}
+
+/// Token from a confirmation or unsubscribe link
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TokenParams {
+    pub token: String,
+}
+
+/// A subscription after it was confirmed or cancelled
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SubscriptionResponse {
+    #[schema(example = "reader@example.com")]
+    pub email: String,
+    pub status: SubscriberStatus,
+}
+
+/// Request to send a post to the subscribers
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateCampaignRequest {
+    #[schema(example = 42)]
+    pub post_id: i64,
+    /// Subject of the email; the post's title by default
+    #[schema(example = "New on the blog: Getting started with Rust")]
+    pub subject: Option<String>,
+}
+
+/// Progress of a campaign
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignStatus {
+    Sending,
+    /// Every recipient was attempted
+    Sent,
+}
+
+impl CampaignStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sending" => Some(CampaignStatus::Sending),
+            "sent" => Some(CampaignStatus::Sent),
+            _ => None,
+        }
+    }
+}
+
+/// A post sent to the subscribers, with its delivery counts
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Campaign {
+    #[schema(example = 7)]
+    pub id: i64,
+    #[schema(example = 42)]
+    pub post_id: i64,
+    pub subject: String,
+    pub status: CampaignStatus,
+    /// Subscribers confirmed when the campaign was created
+    #[schema(example = 120)]
+    pub recipients: i64,
+    #[schema(example = 118)]
+    pub sent: i64,
+    #[schema(example = 1)]
+    pub failed: i64,
+    /// Recipients who unsubscribed before their email went out
+    #[schema(example = 1)]
+    pub skipped: i64,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Whether a campaign's email reached a recipient
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not attempted yet
+    Pending,
+    /// Accepted by the mail server
+    Sent,
+    Failed,
+    /// Not sent as the recipient unsubscribed
+    Skipped,
+}
+
+impl DeliveryStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(DeliveryStatus::Pending),
+            "sent" => Some(DeliveryStatus::Sent),
+            "failed" => Some(DeliveryStatus::Failed),
+            "skipped" => Some(DeliveryStatus::Skipped),
+            _ => None,
+        }
+    }
+}
+
+/// Delivery of a campaign to one recipient
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Delivery {
+    #[schema(example = 3)]
+    pub subscriber_id: i64,
+    #[schema(example = "reader@example.com")]
+    pub email: String,
+    pub status: DeliveryStatus,
+    /// Why sending failed
+    pub error: Option<String>,
+    pub attempted_at: Option<DateTime<Utc>>,
+}
+
+/// Possible newsletter errors
+#[derive(Debug, thiserror::Error)]
+pub enum NewsletterError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Failed to send email: {0}")]
+    MailerError(#[from] crate::mailer::MailerError),
+
+    #[error("Newsletter email is not configured")]
+    MailerNotConfigured,
+
+    #[error("Invalid email address")]
+    InvalidEmail,
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Invalid or expired link")]
+    InvalidToken,
+
+    #[error("Post not found")]
+    PostNotFound,
+
+    #[error("Campaign not found")]
+    CampaignNotFound,
+}
@@ -0,0 +1,625 @@
+use crate::db::instrument::timed;
+use crate::hex;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::JobHandler;
+use crate::mailer::{Email, Mailer};
+use crate::newsletter::model::{
+    Campaign, CampaignStatus, Delivery, DeliveryStatus, NewsletterError, SubscriberStatus,
+    SubscriptionResponse, NEWSLETTER_CAMPAIGN_JOB,
+};
+use crate::pagination::Pagination;
+use crate::post::service::meta_description;
+use crate::sitemap::site_base_url;
+use crate::tenant::middleware::current_blog_id;
+use crate::tenant::model::Blog;
+use crate::tenant::service::TenantService;
+use futures::future::{join_all, BoxFuture};
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// Random bytes of confirmation and unsubscribe tokens
+const TOKEN_BYTES: usize = 32;
+// A pending subscriber is sent the confirmation again at most this often, so that the
+// public endpoint cannot be used to flood an inbox
+const CONFIRMATION_RESEND_SECONDS: f64 = 10.0 * 60.0;
+// Confirmation links stop working after this long
+const CONFIRMATION_TTL_SECONDS: f64 = 7.0 * 24.0 * 3600.0;
+// Emails sent at once, and the pause between batches that keeps within mail server limits
+const CAMPAIGN_BATCH_SIZE: i64 = 50;
+const CAMPAIGN_BATCH_PAUSE: Duration = Duration::from_secs(1);
+const MAX_EMAIL_LENGTH: usize = 255;
+const MAX_SUBJECT_LENGTH: usize = 200;
+
+fn generate_token() -> String {
+    hex::encode(rand::random::<[u8; TOKEN_BYTES]>())
+}
+
+// Subscriber addresses are compared case-insensitively
+fn normalize_email(email: &str) -> Result<String, NewsletterError> {
+    let email = email.trim().to_lowercase();
+    if email.len() > MAX_EMAIL_LENGTH || email.parse::<lettre::Address>().is_err() {
+        return Err(NewsletterError::InvalidEmail);
+    }
+    Ok(email)
+}
+
+fn render_confirmation(blog_name: &str, to: &str, confirm_url: &str) -> Email {
+    Email {
+        to: to.to_string(),
+        subject: format!("Confirm your subscription to {}", blog_name),
+        text: format!(
+            "Please confirm that you want to receive the {} newsletter:\n\n{}\n\nIf you did not \
+             subscribe, ignore this email and you will not hear from us again.\n",
+            blog_name, confirm_url
+        ),
+        html: format!(
+            "<p>Please confirm that you want to receive the {} newsletter:</p>\n\
+             <p><a href=\"{}\">Confirm my subscription</a></p>\n\
+             <p><small>If you did not subscribe, ignore this email and you will not hear from \
+             us again.</small></p>\n",
+            html_escape::encode_text(blog_name),
+            html_escape::encode_double_quoted_attribute(confirm_url)
+        ),
+    }
+}
+
+/// A post as sent in a campaign
+#[derive(Debug)]
+struct CampaignPost {
+    subject: String,
+    title: String,
+    url: String,
+    /// Markdown of the post, or an excerpt of gated posts
+    text: String,
+    /// Rendered post, or an escaped excerpt of gated posts
+    html: String,
+}
+
+fn render_campaign(blog_name: &str, post: &CampaignPost, to: &str, unsubscribe_url: &str) -> Email {
+    Email {
+        to: to.to_string(),
+        subject: post.subject.clone(),
+        text: format!(
+            "{}\n\n{}\n\nRead it on {}: {}\n\nTo stop receiving the newsletter, unsubscribe: {}\n",
+            post.title, post.text, blog_name, post.url, unsubscribe_url
+        ),
+        html: format!(
+            "<h1><a href=\"{}\">{}</a></h1>\n{}\n\
+             <p><small>You get this email as a subscriber of {}. \
+             <a href=\"{}\">Unsubscribe</a></small></p>\n",
+            html_escape::encode_double_quoted_attribute(&post.url),
+            html_escape::encode_text(&post.title),
+            post.html,
+            html_escape::encode_text(blog_name),
+            html_escape::encode_double_quoted_attribute(unsubscribe_url)
+        ),
+    }
+}
+
+/// Newsletter subscriptions of each blog, and campaigns that email a post to its subscribers.
+///
+/// Subscribing is double opt-in: the address gets a confirmation link and only receives
+/// campaigns once it is followed. Every email carries the subscriber's unsubscribe link.
+/// Campaigns are sent by a background job in batches, recording the delivery to every
+/// recipient; a job picked up again after its instance stopped carries on with the recipients
+/// not yet attempted. Needs a mailer, see `MAILER`.
+pub struct NewsletterService {
+    pool: PgPool,
+    mailer: Option<Arc<dyn Mailer>>,
+    tenants: TenantService,
+}
+
+impl NewsletterService {
+    pub fn new(pool: PgPool, mailer: Option<Arc<dyn Mailer>>) -> Self {
+        Self {
+            tenants: TenantService::new(pool.clone()),
+            pool,
+            mailer,
+        }
+    }
+
+    fn mailer(&self) -> Result<&Arc<dyn Mailer>, NewsletterError> {
+        self.mailer
+            .as_ref()
+            .ok_or(NewsletterError::MailerNotConfigured)
+    }
+
+    /// Subscribe an address to the blog's newsletter and email it the confirmation link.
+    /// Addresses already confirmed, or sent a confirmation within the last ten minutes, are
+    /// not emailed again; callers can't tell, so subscribing reveals nothing about who is
+    /// subscribed.
+    pub async fn subscribe(&self, blog: &Blog, email: &str) -> Result<(), NewsletterError> {
+        let mailer = self.mailer()?;
+        let email = normalize_email(email)?;
+
+        let token = sqlx::query_scalar::<_, String>(
+            r#"
+            INSERT INTO global.newsletter_subscribers (
+                blog_id, email, status, confirm_token, unsubscribe_token, confirmation_sent_at
+            )
+            VALUES ($1, $2, 'pending', $3, $4, NOW())
+            ON CONFLICT (blog_id, email) DO UPDATE SET
+                status = 'pending',
+                confirm_token = EXCLUDED.confirm_token,
+                confirmation_sent_at = NOW()
+            WHERE newsletter_subscribers.status <> 'confirmed'
+                AND (newsletter_subscribers.confirmation_sent_at IS NULL
+                    OR newsletter_subscribers.confirmation_sent_at
+                        < NOW() - make_interval(secs => $5))
+            RETURNING confirm_token
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(&email)
+        .bind(generate_token())
+        .bind(generate_token())
+        .bind(CONFIRMATION_RESEND_SECONDS)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(token) = token else {
+            return Ok(());
+        };
+
+        let confirm_url = format!(
+            "{}/api/v1/newsletter/confirm?token={}",
+            site_base_url(blog),
+            token
+        );
+        if let Err(e) = mailer
+            .send(&render_confirmation(&blog.name, &email, &confirm_url))
+            .await
+        {
+            // Let the subscriber try again right away
+            sqlx::query(
+                r#"
+                UPDATE global.newsletter_subscribers SET confirmation_sent_at = NULL
+                WHERE blog_id = $1 AND email = $2
+                "#,
+            )
+            .bind(current_blog_id())
+            .bind(&email)
+            .execute(&self.pool)
+            .await?;
+            return Err(e.into());
+        }
+
+        info!(
+            "Sent newsletter confirmation to a new subscriber of blog {}",
+            blog.id
+        );
+        Ok(())
+    }
+
+    /// Confirm a subscription with the token of its confirmation link
+    pub async fn confirm(&self, token: &str) -> Result<SubscriptionResponse, NewsletterError> {
+        let email = sqlx::query_scalar::<_, String>(
+            r#"
+            UPDATE global.newsletter_subscribers
+            SET status = 'confirmed', confirm_token = NULL, confirmed_at = NOW()
+            WHERE blog_id = $1 AND confirm_token = $2 AND status = 'pending'
+                AND confirmation_sent_at > NOW() - make_interval(secs => $3)
+            RETURNING email
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(token)
+        .bind(CONFIRMATION_TTL_SECONDS)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(NewsletterError::InvalidToken)?;
+
+        Ok(SubscriptionResponse {
+            email,
+            status: SubscriberStatus::Confirmed,
+        })
+    }
+
+    /// Unsubscribe with the token of an unsubscribe link. Unsubscribing again is a no-op.
+    pub async fn unsubscribe(&self, token: &str) -> Result<SubscriptionResponse, NewsletterError> {
+        let email = sqlx::query_scalar::<_, String>(
+            r#"
+            UPDATE global.newsletter_subscribers
+            SET status = 'unsubscribed', confirm_token = NULL,
+                unsubscribed_at = CASE
+                    WHEN status = 'unsubscribed' THEN unsubscribed_at ELSE NOW()
+                END
+            WHERE blog_id = $1 AND unsubscribe_token = $2
+            RETURNING email
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(NewsletterError::InvalidToken)?;
+
+        Ok(SubscriptionResponse {
+            email,
+            status: SubscriberStatus::Unsubscribed,
+        })
+    }
+
+    /// Create a campaign sending a published post to every confirmed subscriber. The emails
+    /// go out from the job queued for it.
+    pub async fn create_campaign(
+        &self,
+        post_id: i64,
+        subject: Option<&str>,
+        created_by: Uuid,
+    ) -> Result<Campaign, NewsletterError> {
+        self.mailer()?;
+
+        let title = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT title FROM global.posts
+            WHERE id = $1 AND blog_id = $2 AND is_draft = false AND is_deleted = false
+            "#,
+        )
+        .bind(post_id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(NewsletterError::PostNotFound)?;
+
+        let subject = subject.map(str::trim).unwrap_or(&title).to_string();
+        if subject.is_empty() || subject.chars().count() > MAX_SUBJECT_LENGTH {
+            return Err(NewsletterError::ValidationError(format!(
+                "Subject must be between 1 and {} characters",
+                MAX_SUBJECT_LENGTH
+            )));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let campaign_id = sqlx::query_scalar::<_, i64>(
+            r#"
+            INSERT INTO global.newsletter_campaigns (blog_id, post_id, subject, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(post_id)
+        .bind(&subject)
+        .bind(created_by)
+        .fetch_one(&mut *tx)
+        .await?;
+        let recipients = sqlx::query(
+            r#"
+            INSERT INTO global.newsletter_deliveries (campaign_id, subscriber_id)
+            SELECT $1, id FROM global.newsletter_subscribers
+            WHERE blog_id = $2 AND status = 'confirmed'
+            "#,
+        )
+        .bind(campaign_id)
+        .bind(current_blog_id())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+
+        info!(
+            "Created newsletter campaign {} of post {} for {} recipients",
+            campaign_id, post_id, recipients
+        );
+        self.get_campaign(campaign_id).await
+    }
+
+    /// A campaign of the blog with its delivery counts
+    pub async fn get_campaign(&self, campaign_id: i64) -> Result<Campaign, NewsletterError> {
+        let row = sqlx::query(
+            r#"
+            SELECT c.id, c.post_id, c.subject, c.status, c.created_at, c.finished_at,
+                COUNT(d.subscriber_id) AS recipients,
+                COUNT(*) FILTER (WHERE d.status = 'sent') AS sent,
+                COUNT(*) FILTER (WHERE d.status = 'failed') AS failed,
+                COUNT(*) FILTER (WHERE d.status = 'skipped') AS skipped
+            FROM global.newsletter_campaigns c
+            LEFT JOIN global.newsletter_deliveries d ON d.campaign_id = c.id
+            WHERE c.id = $1 AND c.blog_id = $2
+            GROUP BY c.id
+            "#,
+        )
+        .bind(campaign_id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(NewsletterError::CampaignNotFound)?;
+
+        Ok(Campaign {
+            id: row.get("id"),
+            post_id: row.get("post_id"),
+            subject: row.get("subject"),
+            status: CampaignStatus::parse(row.get("status")).unwrap_or(CampaignStatus::Sending),
+            recipients: row.get("recipients"),
+            sent: row.get("sent"),
+            failed: row.get("failed"),
+            skipped: row.get("skipped"),
+            created_at: row.get("created_at"),
+            finished_at: row.get("finished_at"),
+        })
+    }
+
+    /// Deliveries of a campaign of the blog, in the order they are sent
+    pub async fn get_deliveries(
+        &self,
+        campaign_id: i64,
+        pagination: &Pagination,
+    ) -> Result<Vec<Delivery>, NewsletterError> {
+        let exists = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM global.newsletter_campaigns WHERE id = $1 AND blog_id = $2
+            )
+            "#,
+        )
+        .bind(campaign_id)
+        .bind(current_blog_id())
+        .fetch_one(&self.pool)
+        .await?;
+        if !exists {
+            return Err(NewsletterError::CampaignNotFound);
+        }
+
+        let rows = sqlx::query(
+            r#"
+            SELECT d.subscriber_id, s.email, d.status, d.error, d.attempted_at
+            FROM global.newsletter_deliveries d
+            JOIN global.newsletter_subscribers s ON s.id = d.subscriber_id
+            WHERE d.campaign_id = $1
+            ORDER BY d.subscriber_id
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(campaign_id)
+        .bind(pagination.limit)
+        .bind(pagination.offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(Delivery {
+                    subscriber_id: row.get("subscriber_id"),
+                    email: row.get("email"),
+                    status: DeliveryStatus::parse(row.get("status"))?,
+                    error: row.get("error"),
+                    attempted_at: row.get("attempted_at"),
+                })
+            })
+            .collect())
+    }
+
+    /// Send a campaign of the current blog to the recipients not attempted yet. Returns how
+    /// many emails were sent and how many failed.
+    pub async fn send_campaign(&self, campaign_id: i64) -> Result<(u64, u64), JobError> {
+        let mailer = self.mailer().map_err(|e| JobError::Failed(e.to_string()))?;
+        let blog = self
+            .tenants
+            .list_blogs()
+            .await
+            .map_err(|e| JobError::InternalError(e.to_string()))?
+            .into_iter()
+            .find(|blog| blog.id == current_blog_id())
+            .ok_or_else(|| JobError::Failed("Blog not found".to_string()))?;
+        let base_url = site_base_url(&blog);
+        let post = self
+            .campaign_post(campaign_id, &base_url)
+            .await?
+            .ok_or_else(|| JobError::Failed("Campaign not found".to_string()))?;
+
+        let (mut sent, mut failed) = (0, 0);
+        loop {
+            // Recipients who unsubscribed since the campaign was created are left out
+            sqlx::query(
+                r#"
+                UPDATE global.newsletter_deliveries d
+                SET status = 'skipped', attempted_at = NOW()
+                FROM global.newsletter_subscribers s
+                WHERE d.campaign_id = $1 AND d.status = 'pending'
+                    AND s.id = d.subscriber_id AND s.status <> 'confirmed'
+                "#,
+            )
+            .bind(campaign_id)
+            .execute(&self.pool)
+            .await?;
+
+            let query = sqlx::query(
+                r#"
+                SELECT d.subscriber_id, s.email, s.unsubscribe_token
+                FROM global.newsletter_deliveries d
+                JOIN global.newsletter_subscribers s ON s.id = d.subscriber_id
+                WHERE d.campaign_id = $1 AND d.status = 'pending'
+                ORDER BY d.subscriber_id
+                LIMIT $2
+                "#,
+            )
+            .bind(campaign_id)
+            .bind(CAMPAIGN_BATCH_SIZE)
+            .fetch_all(&self.pool);
+            let batch = timed("newsletter.batch", query).await?;
+            if batch.is_empty() {
+                break;
+            }
+
+            let mut subscriber_ids = Vec::with_capacity(batch.len());
+            let mut emails = Vec::with_capacity(batch.len());
+            for row in &batch {
+                let unsubscribe_url = format!(
+                    "{}/api/v1/newsletter/unsubscribe?token={}",
+                    base_url,
+                    row.get::<String, _>("unsubscribe_token")
+                );
+                subscriber_ids.push(row.get::<i64, _>("subscriber_id"));
+                emails.push(render_campaign(
+                    &blog.name,
+                    &post,
+                    row.get("email"),
+                    &unsubscribe_url,
+                ));
+            }
+
+            let results = join_all(emails.iter().map(|email| mailer.send(email))).await;
+            let mut statuses = Vec::with_capacity(results.len());
+            let mut errors = Vec::with_capacity(results.len());
+            for (subscriber_id, result) in subscriber_ids.iter().zip(results) {
+                match result {
+                    Ok(()) => {
+                        statuses.push("sent");
+                        errors.push(None);
+                        sent += 1;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to send campaign {} to subscriber {}: {}",
+                            campaign_id, subscriber_id, e
+                        );
+                        statuses.push("failed");
+                        errors.push(Some(e.to_string()));
+                        failed += 1;
+                    }
+                }
+            }
+
+            sqlx::query(
+                r#"
+                UPDATE global.newsletter_deliveries d
+                SET status = r.status, error = r.error, attempted_at = NOW()
+                FROM UNNEST($2::BIGINT[], $3::TEXT[], $4::TEXT[])
+                    AS r(subscriber_id, status, error)
+                WHERE d.campaign_id = $1 AND d.subscriber_id = r.subscriber_id
+                "#,
+            )
+            .bind(campaign_id)
+            .bind(&subscriber_ids)
+            .bind(&statuses)
+            .bind(&errors)
+            .execute(&self.pool)
+            .await?;
+
+            if (batch.len() as i64) < CAMPAIGN_BATCH_SIZE {
+                break;
+            }
+            tokio::time::sleep(CAMPAIGN_BATCH_PAUSE).await;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE global.newsletter_campaigns SET status = 'sent', finished_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(campaign_id)
+        .execute(&self.pool)
+        .await?;
+
+        info!(
+            "Sent newsletter campaign {}: {} sent, {} failed",
+            campaign_id, sent, failed
+        );
+        Ok((sent, failed))
+    }
+
+    // The post of a campaign of the current blog as it is emailed. Posts gated behind a
+    // membership tier only go out as an excerpt.
+    async fn campaign_post(
+        &self,
+        campaign_id: i64,
+        base_url: &str,
+    ) -> Result<Option<CampaignPost>, sqlx::Error> {
+        let row = sqlx::query(
+            r#"
+            SELECT c.subject, p.title, p.slug, p.content, p.content_html, p.required_tier_id
+            FROM global.newsletter_campaigns c
+            JOIN global.posts p ON p.id = c.post_id
+            WHERE c.id = $1 AND c.blog_id = $2
+            "#,
+        )
+        .bind(campaign_id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| {
+            let content: String = row.get("content");
+            let (text, html) = match row.get::<Option<i64>, _>("required_tier_id") {
+                Some(_) => {
+                    let excerpt = meta_description(&content);
+                    let html = format!("<p>{}</p>", html_escape::encode_text(&excerpt));
+                    (excerpt, html)
+                }
+                None => (content, row.get("content_html")),
+            };
+            CampaignPost {
+                subject: row.get("subject"),
+                title: row.get("title"),
+                url: format!("{}/posts/{}", base_url, row.get::<String, _>("slug")),
+                text,
+                html,
+            }
+        }))
+    }
+
+    async fn run_job(&self, job: &Job) -> Result<Value, JobError> {
+        let campaign_id = job
+            .payload
+            .get("campaign_id")
+            .and_then(Value::as_i64)
+            .ok_or_else(|| JobError::Failed("Missing campaign_id".to_string()))?;
+        let (sent, failed) = self.send_campaign(campaign_id).await?;
+        Ok(json!({ "campaign_id": campaign_id, "sent": sent, "failed": failed }))
+    }
+}
+
+impl JobHandler for NewsletterService {
+    fn kind(&self) -> &'static str {
+        NEWSLETTER_CAMPAIGN_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_email() {
+        assert_eq!(
+            normalize_email(" Reader@Example.com ").unwrap(),
+            "reader@example.com"
+        );
+        assert!(normalize_email("not an address").is_err());
+        assert!(normalize_email("").is_err());
+    }
+
+    #[test]
+    fn test_render_campaign_escapes_html() {
+        let post = CampaignPost {
+            subject: "New: Generics <T>".to_string(),
+            title: "Generics <T> in Rust".to_string(),
+            url: "https://example.com/posts/generics".to_string(),
+            text: "Type parameters".to_string(),
+            html: "<p>Type parameters</p>".to_string(),
+        };
+
+        let email = render_campaign(
+            "Rust & Co",
+            &post,
+            "ada@example.com",
+            "https://example.com/api/v1/newsletter/unsubscribe?token=abc&x=1",
+        );
+        assert_eq!(email.to, "ada@example.com");
+        assert_eq!(email.subject, "New: Generics <T>");
+        assert!(email.html.contains("Generics &lt;T&gt; in Rust"));
+        assert!(email.html.contains("<p>Type parameters</p>"));
+        assert!(email.html.contains("token=abc&amp;x=1"));
+        assert!(email.text.contains("unsubscribe: https://example.com/"));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::notification::model::{NotificationError, QuietHoursSettings, UnreadCountResponse};
+use crate::notification::service::NotificationService;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn notification_error_response(e: NotificationError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Notification error: {:?}", e);
+    let status = match e {
+        NotificationError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+        NotificationError::NotFound => StatusCode::NOT_FOUND,
+        NotificationError::DatabaseError(_)
+        | NotificationError::CacheError(_)
+        | NotificationError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+async fn unread_count_response(service: &NotificationService, user: &AuthUser) -> Response {
+    match service.unread_count(&user.user_id).await {
+        Ok(unread_count) => {
+            (StatusCode::OK, Json(UnreadCountResponse { unread_count })).into_response()
+        }
+        Err(e) => notification_error_response(e),
+    }
+}
+
+/// List your notifications
+///
+/// Returns a page of your notifications, newest first. Further pages are linked from the
+/// `Link` response header.
+#[utoipa::path(
+    get,
+    path = "/api/notifications",
+    tag = "notifications",
+    params(PageParams),
+    responses(
+        (status = 200, description = "Notifications retrieved", body = Vec<Notification>),
+        (status = 400, description = "Invalid pagination cursor"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_notifications(
+    OriginalUri(uri): OriginalUri,
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<NotificationService>>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return notification_error_response(NotificationError::InvalidParameter(e.to_string()))
+        }
+    };
+
+    match service
+        .get_user_notifications(&user.user_id, &pagination)
+        .await
+    {
+        Ok(notifications) => {
+            let headers = pagination.headers(&uri, notifications.len());
+            (StatusCode::OK, headers, Json(notifications)).into_response()
+        }
+        Err(e) => notification_error_response(e),
+    }
+}
+
+/// Get your unread notification count
+///
+/// Cheap enough to poll for badges. Connections to the notification WebSocket are also sent
+/// `{"type": "unread_count", "unread_count": n}` whenever the count changes.
+#[utoipa::path(
+    get,
+    path = "/api/notifications/unread-count",
+    tag = "notifications",
+    responses(
+        (status = 200, description = "Unread count retrieved", body = UnreadCountResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_unread_count(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<NotificationService>>,
+) -> Response {
+    unread_count_response(&service, &user).await
+}
+
+/// Mark a notification as read
+#[utoipa::path(
+    post,
+    path = "/api/notifications/{id}/read",
+    tag = "notifications",
+    params(
+        ("id" = i64, Path, description = "Notification ID")
+    ),
+    responses(
+        (status = 200, description = "Notification marked as read", body = UnreadCountResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Notification not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn mark_as_read(
+    Path(id): Path<i64>,
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<NotificationService>>,
+) -> Response {
+    match service.mark_as_read(&user.user_id, id).await {
+        Ok(()) => unread_count_response(&service, &user).await,
+        Err(e) => notification_error_response(e),
+    }
+}
+
+/// Mark all of your notifications as read
+#[utoipa::path(
+    post,
+    path = "/api/notifications/read-all",
+    tag = "notifications",
+    responses(
+        (status = 200, description = "Notifications marked as read", body = UnreadCountResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn mark_all_read(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<NotificationService>>,
+) -> Response {
+    match service.mark_all_read(&user.user_id).await {
+        Ok(()) => unread_count_response(&service, &user).await,
+        Err(e) => notification_error_response(e),
+    }
+}
+
+/// Get your notification quiet hours
+#[utoipa::path(
+    get,
+    path = "/api/notifications/quiet-hours",
+    tag = "notifications",
+    responses(
+        (status = 200, description = "Quiet hours retrieved", body = QuietHoursSettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_quiet_hours(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<NotificationService>>,
+) -> Response {
+    match service.get_quiet_hours(&user.user_id).await {
+        Ok(settings) => (StatusCode::OK, Json(settings)).into_response(),
+        Err(e) => notification_error_response(e),
+    }
+}
+
+/// Set your notification quiet hours
+///
+/// During quiet hours, in your own time zone, notifications are stored but not pushed over
+/// the notification WebSocket. When they end, connections get
+/// `{"type": "quiet_hours_summary", "held_count": n, "unread_count": m}` instead.
+#[utoipa::path(
+    put,
+    path = "/api/notifications/quiet-hours",
+    tag = "notifications",
+    request_body = QuietHoursSettings,
+    responses(
+        (status = 200, description = "Quiet hours updated", body = QuietHoursSettings),
+        (status = 400, description = "Invalid time or time zone"),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_quiet_hours(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<NotificationService>>,
+    Json(settings): Json<QuietHoursSettings>,
+) -> Response {
+    match service.set_quiet_hours(&user.user_id, &settings).await {
+        Ok(settings) => (StatusCode::OK, Json(settings)).into_response(),
+        Err(e) => notification_error_response(e),
+    }
+}
@@ -1,13 +1,62 @@
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum NotificationType {
     CommentReply,
     NewComment,
+    ThreadReply,
     PostLike,
     FollowerUpdate,
     SystemMessage,
+    /// Sent to all of a user's sessions when their password changes
+    SecurityAlert,
+}
+
+impl NotificationType {
+    /// Name stored in the `notification_type` column, the same as in JSON
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationType::CommentReply => "CommentReply",
+            NotificationType::NewComment => "NewComment",
+            NotificationType::ThreadReply => "ThreadReply",
+            NotificationType::PostLike => "PostLike",
+            NotificationType::FollowerUpdate => "FollowerUpdate",
+            NotificationType::SystemMessage => "SystemMessage",
+            NotificationType::SecurityAlert => "SecurityAlert",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "CommentReply" => Some(NotificationType::CommentReply),
+            "NewComment" => Some(NotificationType::NewComment),
+            "ThreadReply" => Some(NotificationType::ThreadReply),
+            "PostLike" => Some(NotificationType::PostLike),
+            "FollowerUpdate" => Some(NotificationType::FollowerUpdate),
+            "SystemMessage" => Some(NotificationType::SystemMessage),
+            "SecurityAlert" => Some(NotificationType::SecurityAlert),
+            _ => None,
+        }
+    }
+}
+
+// Characters of a comment quoted in a notification
+const EXCERPT_CHARS: usize = 140;
+
+/// Human-readable details of what a notification is about, resolved when it is created so
+/// clients can show it without looking up each ID
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NotificationContext {
+    pub actor_username: Option<String>,
+    pub post_title: Option<String>,
+    pub post_slug: Option<String>,
+    pub comment_excerpt: Option<String>,
+    /// Front-end path to open, e.g. `/posts/my-post#comment-123`
+    pub target_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,9 +67,85 @@ pub struct NotificationPayload {
     pub related_object_id: Option<i64>,
     pub actor_id: Uuid,
     pub content: String,
+    // Empty for notifications queued before it was added
+    #[serde(default)]
+    pub context: NotificationContext,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl NotificationPayload {
+    /// The post and comment this notification is about, as `(post_id, comment_id)`
+    pub fn subject(&self) -> (Option<i64>, Option<i64>) {
+        match self.notification_type {
+            NotificationType::CommentReply
+            | NotificationType::NewComment
+            | NotificationType::ThreadReply => (self.related_object_id, Some(self.object_id)),
+            NotificationType::PostLike => (Some(self.object_id), None),
+            NotificationType::FollowerUpdate
+            | NotificationType::SystemMessage
+            | NotificationType::SecurityAlert => (None, None),
+        }
+    }
+
+    /// What notifications of this type are batched by: the post for comments, replies and
+    /// likes. Thread replies are already folded per thread when they are queued, and the
+    /// other types are never batched.
+    pub fn batch_target(&self) -> Option<i64> {
+        match self.notification_type {
+            NotificationType::CommentReply | NotificationType::NewComment => self.related_object_id,
+            NotificationType::PostLike => Some(self.object_id),
+            NotificationType::ThreadReply
+            | NotificationType::FollowerUpdate
+            | NotificationType::SystemMessage
+            | NotificationType::SecurityAlert => None,
+        }
+    }
+
+    /// Text of a notification standing for `count` notifications like this one
+    pub fn batch_content(&self, count: i32) -> String {
+        if count <= 1 {
+            return self.content.clone();
+        }
+        match self.notification_type {
+            NotificationType::NewComment => format!("{} new comments on your post", count),
+            NotificationType::CommentReply => {
+                format!("You have {} new replies to your comments.", count)
+            }
+            NotificationType::PostLike => format!("{} people liked your post.", count),
+            _ => self.content.clone(),
+        }
+    }
+}
+
+/// Front-end path of a post, or of a comment on it
+pub fn target_path(post_slug: &str, comment_id: Option<i64>) -> String {
+    match comment_id {
+        Some(comment_id) => format!("/posts/{}#comment-{}", post_slug, comment_id),
+        None => format!("/posts/{}", post_slug),
+    }
+}
+
+/// Start of a comment as plain text on one line, cut at a word boundary
+pub fn comment_excerpt(content: &str) -> String {
+    let text = content
+        .chars()
+        .filter(|c| !matches!(c, '#' | '*' | '_' | '`' | '>'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if text.chars().count() <= EXCERPT_CHARS {
+        return text;
+    }
+    let mut excerpt: String = text.chars().take(EXCERPT_CHARS).collect();
+    if let Some(end) = excerpt.rfind(' ') {
+        excerpt.truncate(end);
+    }
+    excerpt.push('…');
+    excerpt
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Notification {
     pub id: i64,
     pub recipient_id: Uuid,
@@ -29,8 +154,132 @@ pub struct Notification {
     pub related_object_id: Option<i64>,
     pub actor_id: Uuid,
     pub content: String,
+    #[serde(default)]
+    pub context: NotificationContext,
+    /// How many notifications this one stands for; similar notifications arriving within the
+    /// batch window are folded into one, which keeps its ID
+    #[schema(example = "5")]
+    pub count: i32,
     pub is_read: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// When the last notification was folded into this one
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Number of unread notifications, for badges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UnreadCountResponse {
+    #[schema(example = "3")]
+    pub unread_count: i64,
+}
+
+/// A user's do-not-disturb schedule. During quiet hours notifications are stored but not
+/// pushed in realtime; a summary is pushed when they end.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct QuietHoursSettings {
+    /// Whether quiet hours are on
+    #[schema(example = "true")]
+    pub enabled: bool,
+
+    /// Local time quiet hours start at, as `HH:MM`
+    #[schema(example = "22:00")]
+    pub start: String,
+
+    /// Local time quiet hours end at, as `HH:MM`; before `start` for overnight quiet hours
+    #[schema(example = "07:00")]
+    pub end: String,
+
+    /// IANA time zone of `start` and `end`
+    #[schema(example = "Europe/Berlin")]
+    pub timezone: String,
+}
+
+impl Default for QuietHoursSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+            timezone: "UTC".to_string(),
+        }
+    }
+}
+
+/// Parsed quiet hours
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub timezone: Tz,
+}
+
+impl QuietHours {
+    pub fn parse(settings: &QuietHoursSettings) -> Result<Self, NotificationError> {
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M").map_err(|_| {
+                NotificationError::InvalidParameter(format!(
+                    "Invalid time {}, expected HH:MM",
+                    value
+                ))
+            })
+        };
+        let start = time(&settings.start)?;
+        let end = time(&settings.end)?;
+        if start == end {
+            return Err(NotificationError::InvalidParameter(
+                "Quiet hours must start and end at different times".to_string(),
+            ));
+        }
+        let timezone = settings.timezone.trim().parse::<Tz>().map_err(|_| {
+            NotificationError::InvalidParameter(format!("Unknown time zone {}", settings.timezone))
+        })?;
+
+        Ok(Self {
+            start,
+            end,
+            timezone,
+        })
+    }
+
+    pub fn settings(&self, enabled: bool) -> QuietHoursSettings {
+        QuietHoursSettings {
+            enabled,
+            start: self.start.format("%H:%M").to_string(),
+            end: self.end.format("%H:%M").to_string(),
+            timezone: self.timezone.name().to_string(),
+        }
+    }
+
+    /// End of the quiet hours `now` falls in, or `None` if it is outside of them
+    pub fn window_end(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = now.with_timezone(&self.timezone);
+        let (date, time) = (local.date_naive(), local.time());
+
+        let end_date = if self.start < self.end {
+            (self.start <= time && time < self.end).then_some(date)
+        } else if time >= self.start {
+            // Overnight quiet hours that started this evening end tomorrow
+            date.checked_add_days(Days::new(1))
+        } else {
+            (time < self.end).then_some(date)
+        }?;
+
+        Some(self.local_to_utc(end_date, self.end))
+    }
+
+    fn local_to_utc(&self, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        let local = NaiveDateTime::new(date, time);
+        match self.timezone.from_local_datetime(&local).earliest() {
+            Some(datetime) => datetime.with_timezone(&Utc),
+            // The time was skipped by a DST change; the clocks moved forward at most an hour
+            None => self
+                .timezone
+                .from_local_datetime(&(local + chrono::Duration::hours(1)))
+                .earliest()
+                .map(|datetime| datetime.with_timezone(&Utc))
+                .unwrap_or_else(|| Utc.from_utc_datetime(&local)),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -44,6 +293,114 @@ pub enum NotificationError {
     #[error("Notification not found")]
     NotFound,
 
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_excerpt() {
+        assert_eq!(
+            comment_excerpt("**Great**  post,\n\n> agreed"),
+            "Great post, agreed"
+        );
+
+        let long = "word ".repeat(50);
+        let excerpt = comment_excerpt(&long);
+        assert!(excerpt.ends_with("word…"));
+        assert!(excerpt.chars().count() <= EXCERPT_CHARS + 1);
+    }
+
+    #[test]
+    fn test_notification_type_names() {
+        for notification_type in [
+            NotificationType::CommentReply,
+            NotificationType::ThreadReply,
+            NotificationType::SystemMessage,
+        ] {
+            let json = serde_json::to_value(notification_type).unwrap();
+            assert_eq!(json, notification_type.as_str());
+            assert_eq!(
+                NotificationType::parse(notification_type.as_str()),
+                Some(notification_type)
+            );
+        }
+        assert_eq!(NotificationType::parse("Unknown"), None);
+    }
+
+    #[test]
+    fn test_batching() {
+        let payload = NotificationPayload {
+            recipient_id: Uuid::nil(),
+            notification_type: NotificationType::NewComment,
+            object_id: 12,
+            related_object_id: Some(3),
+            actor_id: Uuid::nil(),
+            content: "New comment on your post".to_string(),
+            context: NotificationContext::default(),
+        };
+        assert_eq!(payload.batch_target(), Some(3));
+        assert_eq!(payload.batch_content(1), "New comment on your post");
+        assert_eq!(payload.batch_content(5), "5 new comments on your post");
+
+        let thread_reply = NotificationPayload {
+            notification_type: NotificationType::ThreadReply,
+            ..payload
+        };
+        assert_eq!(thread_reply.batch_target(), None);
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let quiet_hours = QuietHours::parse(&QuietHoursSettings {
+            enabled: true,
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+            timezone: "Europe/Berlin".to_string(),
+        })
+        .unwrap();
+        let at = |value: &str| value.parse::<DateTime<Utc>>().unwrap();
+
+        // 23:30 in Berlin (CEST) ends at 07:00 the next morning
+        assert_eq!(
+            quiet_hours.window_end(at("2024-06-01T21:30:00Z")),
+            Some(at("2024-06-02T05:00:00Z"))
+        );
+        // 06:00 in Berlin ends the same morning
+        assert_eq!(
+            quiet_hours.window_end(at("2024-06-02T04:00:00Z")),
+            Some(at("2024-06-02T05:00:00Z"))
+        );
+        // Noon is outside of quiet hours
+        assert_eq!(quiet_hours.window_end(at("2024-06-02T10:00:00Z")), None);
+
+        assert_eq!(
+            quiet_hours.settings(true).timezone,
+            "Europe/Berlin".to_string()
+        );
+        let invalid = |start: &str, end: &str, timezone: &str| {
+            QuietHours::parse(&QuietHoursSettings {
+                enabled: true,
+                start: start.to_string(),
+                end: end.to_string(),
+                timezone: timezone.to_string(),
+            })
+            .is_err()
+        };
+        assert!(invalid("22:00", "22:00", "UTC"));
+        assert!(invalid("25:00", "07:00", "UTC"));
+        assert!(invalid("22:00", "07:00", "Mars/Olympus"));
+    }
+
+    #[test]
+    fn test_target_path() {
+        assert_eq!(target_path("hello", Some(7)), "/posts/hello#comment-7");
+        assert_eq!(target_path("hello", None), "/posts/hello");
+    }
+}
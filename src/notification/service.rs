@@ -1,35 +1,239 @@
+use crate::block::service::blocked_user_ids;
 use crate::cache::redis::RedisCache;
-use crate::notification::model::{NotificationError, NotificationPayload, NotificationType};
-use chrono::Utc;
-use sqlx::PgPool;
+use crate::notification::model::{
+    comment_excerpt, target_path, Notification, NotificationContext, NotificationError,
+    NotificationPayload, NotificationType, QuietHours, QuietHoursSettings,
+};
+use crate::pagination::Pagination;
+use crate::websocket::notifications::{
+    publish_notification, publish_quiet_hours_summary, publish_unread_count,
+};
+use chrono::{DateTime, NaiveTime, Utc};
+use redis::AsyncCommands;
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Row};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::{error, info};
+use std::time::Duration;
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+// Outbox rows delivered per relay pass
+const OUTBOX_BATCH_SIZE: i64 = 100;
+/// Outbox rows that keep failing are given up on after this many attempts
+pub const OUTBOX_MAX_ATTEMPTS: i32 = 5;
+// How often the relay looks for rows that were not delivered right after their commit
+const OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(10);
+// Similar notifications within this window are folded into one unless
+// NOTIFICATION_BATCH_WINDOW_SECONDS says otherwise; 0 turns batching off
+const DEFAULT_BATCH_WINDOW_SECONDS: u64 = 300;
+// Columns of a stored notification
+const NOTIFICATION_COLUMNS: &str = "id, recipient_id, notification_type, object_id, \
+    related_object_id, actor_id, content, context, aggregate_count, is_read, created_at, \
+    updated_at";
+// Quiet hours summaries pushed per relay pass
+const SUMMARY_BATCH_SIZE: i64 = 100;
+// How long a cached unread count lives before it is recounted from Postgres
+const UNREAD_COUNT_TTL_SECONDS: u64 = 86_400;
+// INCRBY that leaves missing keys alone, so a counter that expired is recounted instead
+const ADJUST_UNREAD_COUNT_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return redis.call('INCRBY', KEYS[1], ARGV[1])
+end
+return false
+"#;
+
+pub(crate) fn unread_count_key(user_id: &Uuid) -> String {
+    format!("notifications:unread:{}", user_id)
+}
+
+fn notification_from_row(row: &PgRow) -> Result<Notification, NotificationError> {
+    let notification_type: String = row.get("notification_type");
+    let context: serde_json::Value = row.get("context");
+
+    Ok(Notification {
+        id: row.get("id"),
+        recipient_id: row.get("recipient_id"),
+        notification_type: NotificationType::parse(&notification_type).ok_or_else(|| {
+            NotificationError::InternalError(format!(
+                "Unknown notification type: {}",
+                notification_type
+            ))
+        })?,
+        object_id: row.get("object_id"),
+        related_object_id: row.get("related_object_id"),
+        actor_id: row.get("actor_id"),
+        content: row.get("content"),
+        context: serde_json::from_value(context).unwrap_or_default(),
+        count: row.get("aggregate_count"),
+        is_read: row.get("is_read"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
+/// Look up the names and titles behind a notification's IDs. Takes the connection of the
+/// transaction creating the notification, which can see the comment it announces.
+pub async fn notification_context(
+    conn: &mut PgConnection,
+    payload: &NotificationPayload,
+) -> Result<NotificationContext, sqlx::Error> {
+    let (post_id, comment_id) = payload.subject();
+
+    let row = sqlx::query(
+        r#"
+        SELECT u.username, p.title, p.slug, c.content
+        FROM (SELECT 1) AS subject
+        LEFT JOIN global.users u ON u.id = $1
+        LEFT JOIN global.posts p ON p.id = $2
+        LEFT JOIN global.comments c ON c.id = $3
+        "#,
+    )
+    .bind(payload.actor_id)
+    .bind(post_id)
+    .bind(comment_id)
+    .fetch_one(conn)
+    .await?;
+
+    let post_slug: Option<String> = row.get("slug");
+    Ok(NotificationContext {
+        actor_username: row.get("username"),
+        post_title: row.get("title"),
+        target_path: post_slug
+            .as_deref()
+            .map(|slug| target_path(slug, comment_id)),
+        post_slug,
+        comment_excerpt: row
+            .get::<Option<String>, _>("content")
+            .map(|content| comment_excerpt(&content)),
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct NotificationService {
     pool: PgPool,
     redis_cache: Option<RedisCache>,
+    batch_window: Duration,
 }
 
 impl NotificationService {
     pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
-        Self { pool, redis_cache }
+        let batch_window_seconds = std::env::var("NOTIFICATION_BATCH_WINDOW_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_BATCH_WINDOW_SECONDS);
+
+        Self {
+            pool,
+            redis_cache,
+            batch_window: Duration::from_secs(batch_window_seconds),
+        }
     }
 
+    // Store a notification and count it as unread for its recipient. Within the batch window
+    // it is folded into the recipient's unread notification of the same type about the same
+    // target instead, e.g. "5 new comments on your post". Returns the stored notification.
     pub async fn create_notification(
         &self,
         payload: NotificationPayload,
-    ) -> Result<i64, NotificationError> {
-        // This would normally insert into a database
+    ) -> Result<Notification, NotificationError> {
         info!(
             "Creating notification for recipient {} of type {:?}",
             payload.recipient_id, payload.notification_type
         );
 
-        // In a real implementation, we'd save to the database
-        // For now, just simulate success and return a dummy ID
-        Ok(1)
+        let context = serde_json::to_value(&payload.context)
+            .map_err(|e| NotificationError::InternalError(e.to_string()))?;
+        let target_id = payload.batch_target();
+
+        if let Some(target_id) = target_id.filter(|_| !self.batch_window.is_zero()) {
+            if let Some(notification) = self.fold_into_batch(&payload, target_id, &context).await? {
+                return Ok(notification);
+            }
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO global.notifications (
+                recipient_id, notification_type, object_id, related_object_id, actor_id, content,
+                context, target_id
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING {}
+            "#,
+            NOTIFICATION_COLUMNS
+        ))
+        .bind(payload.recipient_id)
+        .bind(payload.notification_type.as_str())
+        .bind(payload.object_id)
+        .bind(payload.related_object_id)
+        .bind(payload.actor_id)
+        .bind(&payload.content)
+        .bind(context)
+        .bind(target_id)
+        .fetch_one(&self.pool)
+        .await?;
+        let notification = notification_from_row(&row)?;
+
+        self.adjust_unread_count(&payload.recipient_id, 1).await;
+        Ok(notification)
+    }
+
+    // Fold a notification into the latest unread one of its batch, if that one started within
+    // the batch window. The batch points at the newest object and actor. It was already
+    // unread, so the unread count stays the same.
+    async fn fold_into_batch(
+        &self,
+        payload: &NotificationPayload,
+        target_id: i64,
+        context: &serde_json::Value,
+    ) -> Result<Option<Notification>, NotificationError> {
+        let mut tx = self.pool.begin().await?;
+
+        let batch = sqlx::query(
+            r#"
+            SELECT id, aggregate_count FROM global.notifications
+            WHERE recipient_id = $1 AND notification_type = $2 AND target_id = $3
+                AND is_read = false AND created_at > NOW() - make_interval(secs => $4)
+            ORDER BY created_at DESC
+            LIMIT 1
+            FOR UPDATE
+            "#,
+        )
+        .bind(payload.recipient_id)
+        .bind(payload.notification_type.as_str())
+        .bind(target_id)
+        .bind(self.batch_window.as_secs_f64())
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(batch) = batch else {
+            return Ok(None);
+        };
+        let id: i64 = batch.get("id");
+        let count = batch.get::<i32, _>("aggregate_count") + 1;
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE global.notifications
+            SET aggregate_count = $2, object_id = $3, actor_id = $4, content = $5, context = $6,
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            NOTIFICATION_COLUMNS
+        ))
+        .bind(id)
+        .bind(count)
+        .bind(payload.object_id)
+        .bind(payload.actor_id)
+        .bind(payload.batch_content(count))
+        .bind(context)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(notification_from_row(&row)?))
     }
 
     // Publish a notification via WebSockets
@@ -54,25 +258,566 @@ impl NotificationService {
         }
     }
 
-    // Mark notification as read
-    pub async fn mark_as_read(&self, notification_id: i64) -> Result<(), NotificationError> {
-        // In a real implementation, update the database
-        info!("Marking notification {} as read", notification_id);
+    // Mark one of the user's notifications as read; marking it again changes nothing
+    pub async fn mark_as_read(
+        &self,
+        user_id: &Uuid,
+        notification_id: i64,
+    ) -> Result<(), NotificationError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE global.notifications SET is_read = true
+            WHERE id = $1 AND recipient_id = $2 AND is_read = false
+            "#,
+        )
+        .bind(notification_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            self.unread_count_changed(user_id, -1).await;
+            return Ok(());
+        }
+
+        let exists: bool = sqlx::query(
+            "SELECT EXISTS(SELECT 1 FROM global.notifications WHERE id = $1 AND recipient_id = $2)",
+        )
+        .bind(notification_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?
+        .get(0);
+        if !exists {
+            return Err(NotificationError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    // Mark all of the user's notifications as read
+    pub async fn mark_all_read(&self, user_id: &Uuid) -> Result<(), NotificationError> {
+        let result = sqlx::query(
+            "UPDATE global.notifications SET is_read = true WHERE recipient_id = $1 AND is_read = false",
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        if let Some(cache) = &self.redis_cache {
+            let reset: Result<(), NotificationError> = async {
+                cache
+                    .connection()
+                    .await?
+                    .set_ex::<_, _, ()>(unread_count_key(user_id), 0, UNREAD_COUNT_TTL_SECONDS)
+                    .await?;
+                Ok(())
+            }
+            .await;
+            if let Err(e) = reset {
+                warn!("Failed to reset unread count of {}: {}", user_id, e);
+            }
+        }
+        if result.rows_affected() > 0 {
+            self.push_unread_count(user_id).await;
+        }
+
         Ok(())
     }
 
-    // Get notifications for a user
+    // Get notifications for a user, most recently updated first
     pub async fn get_user_notifications(
         &self,
         user_id: &Uuid,
-        limit: Option<i64>,
-    ) -> Result<Vec<NotificationPayload>, NotificationError> {
-        let _limit = limit.unwrap_or(10);
+        pagination: &Pagination,
+    ) -> Result<Vec<Notification>, NotificationError> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {} FROM global.notifications
+            WHERE recipient_id = $1
+            ORDER BY updated_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            NOTIFICATION_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(pagination.limit)
+        .bind(pagination.offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(notification_from_row).collect()
+    }
+
+    /// Number of unread notifications of a user. Served from the counter cached in Redis; a
+    /// missing counter is recounted from Postgres and cached again.
+    pub async fn unread_count(&self, user_id: &Uuid) -> Result<i64, NotificationError> {
+        if let Some(cache) = &self.redis_cache {
+            let cached: Option<i64> = cache
+                .connection()
+                .await?
+                .get(unread_count_key(user_id))
+                .await?;
+            if let Some(count) = cached {
+                return Ok(count.max(0));
+            }
+        }
+
+        let count: i64 = sqlx::query(
+            "SELECT COUNT(*) FROM global.notifications WHERE recipient_id = $1 AND is_read = false",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?
+        .get(0);
+
+        if let Some(cache) = &self.redis_cache {
+            cache
+                .connection()
+                .await?
+                .set_ex::<_, _, ()>(unread_count_key(user_id), count, UNREAD_COUNT_TTL_SECONDS)
+                .await?;
+        }
+
+        Ok(count)
+    }
 
-        // In a real implementation, fetch from database
-        info!("Getting notifications for user {}", user_id);
+    // Adjust the cached unread count after a change and push the new count to the user's
+    // WebSocket connections
+    async fn unread_count_changed(&self, user_id: &Uuid, delta: i64) {
+        self.adjust_unread_count(user_id, delta).await;
+        self.push_unread_count(user_id).await;
+    }
+
+    // The change is already stored, so failures here are only logged
+    async fn adjust_unread_count(&self, user_id: &Uuid, delta: i64) {
+        let Some(cache) = &self.redis_cache else {
+            return;
+        };
+
+        let adjusted: Result<(), NotificationError> = async {
+            let mut conn = cache.connection().await?;
+            let _: Option<i64> = redis::Script::new(ADJUST_UNREAD_COUNT_SCRIPT)
+                .key(unread_count_key(user_id))
+                .arg(delta)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = adjusted {
+            warn!("Failed to adjust unread count of {}: {}", user_id, e);
+        }
+    }
+
+    async fn push_unread_count(&self, user_id: &Uuid) {
+        let Some(cache) = &self.redis_cache else {
+            return;
+        };
+
+        let pushed = match self.unread_count(user_id).await {
+            Ok(count) => publish_unread_count(cache, user_id, count).await,
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = pushed {
+            warn!("Failed to push unread count to {}: {}", user_id, e);
+        }
+    }
+
+    // Queue a notification in the caller's transaction; it is delivered by the outbox relay
+    // after the transaction commits, so it is sent if and only if the change it announces sticks.
+    // Notifications caused by a user the recipient blocked are dropped; the rest get their
+    // context resolved here, while the announced change is visible.
+    pub async fn enqueue_notification(
+        &self,
+        conn: &mut PgConnection,
+        payload: &NotificationPayload,
+    ) -> Result<(), NotificationError> {
+        let blocked =
+            blocked_user_ids(&self.pool, self.redis_cache.as_ref(), payload.recipient_id).await?;
+        if blocked.contains(&payload.actor_id) {
+            info!(
+                "Dropping notification for {} caused by blocked user {}",
+                payload.recipient_id, payload.actor_id
+            );
+            return Ok(());
+        }
+
+        let mut payload = payload.clone();
+        payload.context = notification_context(&mut *conn, &payload).await?;
+        let payload = serde_json::to_value(payload)
+            .map_err(|e| NotificationError::InternalError(e.to_string()))?;
+
+        sqlx::query("INSERT INTO global.notification_outbox (payload) VALUES ($1)")
+            .bind(payload)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    // Deliver pending outbox notifications. Rows are locked while they are delivered, so
+    // several relays (or instances) can run at once without sending anything twice.
+    pub async fn deliver_outbox(&self) -> Result<usize, NotificationError> {
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, payload FROM global.notification_outbox
+            WHERE delivered_at IS NULL AND attempts < $1
+            ORDER BY id
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+            "#,
+        )
+        .bind(OUTBOX_MAX_ATTEMPTS)
+        .bind(OUTBOX_BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut delivered = 0;
+        for row in rows {
+            let id: i64 = row.get("id");
+            let result = match serde_json::from_value::<NotificationPayload>(row.get("payload")) {
+                Ok(payload) => self.deliver(payload).await,
+                Err(e) => Err(NotificationError::InternalError(e.to_string())),
+            };
+
+            match result {
+                Ok(()) => {
+                    sqlx::query(
+                        "UPDATE global.notification_outbox SET delivered_at = NOW() WHERE id = $1",
+                    )
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                    delivered += 1;
+                }
+                Err(e) => {
+                    warn!("Failed to deliver outbox notification {}: {}", id, e);
+                    sqlx::query(
+                        "UPDATE global.notification_outbox SET attempts = attempts + 1 WHERE id = $1",
+                    )
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(delivered)
+    }
+
+    // Store a notification and push it to the recipient's open WebSocket connections. A
+    // notification folded into a batch is pushed as the updated batch, under the batch's ID,
+    // so clients replace it rather than show another one. During the recipient's quiet hours
+    // it is only stored and counted towards the summary pushed when they end, unless it is a
+    // security alert. Once stored, a failed push is only logged, so the outbox doesn't store
+    // it again.
+    pub async fn deliver(&self, payload: NotificationPayload) -> Result<(), NotificationError> {
+        let notification = self.create_notification(payload).await?;
+        let recipient_id = notification.recipient_id;
+
+        if notification.notification_type != NotificationType::SecurityAlert
+            && self
+                .hold_during_quiet_hours(&recipient_id, Utc::now())
+                .await?
+        {
+            info!(
+                "Holding notification {} for {} until their quiet hours end",
+                notification.id, recipient_id
+            );
+            return Ok(());
+        }
+
+        if let Some(redis_cache) = &self.redis_cache {
+            if let Err(e) = publish_notification(redis_cache, &recipient_id, &notification).await {
+                warn!("Failed to publish notification {}: {}", notification.id, e);
+            }
+        }
+        self.push_unread_count(&recipient_id).await;
+
+        Ok(())
+    }
+
+    /// Store `payload` once for each recipient, in place of its own, in the caller's
+    /// transaction, as announcements sent to many users do. They are not batched with earlier
+    /// notifications. Once the
+    /// transaction commits, pass them to `push_stored`, which counts them as unread.
+    pub async fn store_for_recipients(
+        &self,
+        conn: &mut PgConnection,
+        recipient_ids: &[Uuid],
+        payload: &NotificationPayload,
+    ) -> Result<Vec<Notification>, NotificationError> {
+        let context = serde_json::to_value(&payload.context)
+            .map_err(|e| NotificationError::InternalError(e.to_string()))?;
+
+        let rows = sqlx::query(&format!(
+            r#"
+            INSERT INTO global.notifications (
+                recipient_id, notification_type, object_id, related_object_id, actor_id, content,
+                context
+            )
+            SELECT recipient_id, $2, $3, $4, $5, $6, $7
+            FROM UNNEST($1::UUID[]) AS recipients(recipient_id)
+            RETURNING {}
+            "#,
+            NOTIFICATION_COLUMNS
+        ))
+        .bind(recipient_ids)
+        .bind(payload.notification_type.as_str())
+        .bind(payload.object_id)
+        .bind(payload.related_object_id)
+        .bind(payload.actor_id)
+        .bind(&payload.content)
+        .bind(context)
+        .fetch_all(conn)
+        .await?;
+
+        rows.iter().map(notification_from_row).collect()
+    }
+
+    /// Count notifications stored by `store_for_recipients` as unread and push them as
+    /// `deliver` does, holding back those of recipients in their quiet hours. Quiet hours are
+    /// looked up for all recipients at once.
+    pub async fn push_stored(
+        &self,
+        notifications: &[Notification],
+    ) -> Result<(), NotificationError> {
+        let now = Utc::now();
+        let recipient_ids: Vec<Uuid> = notifications.iter().map(|n| n.recipient_id).collect();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT user_id, start_time, end_time, timezone
+            FROM global.notification_quiet_hours
+            WHERE user_id = ANY($1) AND enabled = true
+            "#,
+        )
+        .bind(&recipient_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let held: HashMap<Uuid, DateTime<Utc>> = rows
+            .iter()
+            .filter_map(|row| {
+                let timezone: String = row.get("timezone");
+                let quiet_hours = QuietHours {
+                    start: row.get::<NaiveTime, _>("start_time"),
+                    end: row.get::<NaiveTime, _>("end_time"),
+                    timezone: timezone.parse().ok()?,
+                };
+                Some((row.get("user_id"), quiet_hours.window_end(now)?))
+            })
+            .collect();
+
+        if !held.is_empty() {
+            let (user_ids, window_ends): (Vec<Uuid>, Vec<DateTime<Utc>>) = held.iter().unzip();
+            sqlx::query(
+                r#"
+                UPDATE global.notification_quiet_hours q
+                SET held_count = q.held_count + 1, summary_due_at = h.window_end
+                FROM UNNEST($1::UUID[], $2::TIMESTAMPTZ[]) AS h(user_id, window_end)
+                WHERE q.user_id = h.user_id
+                "#,
+            )
+            .bind(&user_ids)
+            .bind(&window_ends)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        for notification in notifications {
+            let recipient_id = &notification.recipient_id;
+            self.adjust_unread_count(recipient_id, 1).await;
+            if held.contains_key(recipient_id) {
+                continue;
+            }
+            if let Some(redis_cache) = &self.redis_cache {
+                if let Err(e) = publish_notification(redis_cache, recipient_id, notification).await
+                {
+                    warn!("Failed to publish notification {}: {}", notification.id, e);
+                }
+            }
+            self.push_unread_count(recipient_id).await;
+        }
+
+        Ok(())
+    }
+
+    // Get a user's quiet hours; users who never set them get notifications at any time
+    pub async fn get_quiet_hours(
+        &self,
+        user_id: &Uuid,
+    ) -> Result<QuietHoursSettings, NotificationError> {
+        let row = sqlx::query(
+            r#"
+            SELECT enabled, start_time, end_time, timezone
+            FROM global.notification_quiet_hours
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(QuietHoursSettings::default()),
+        };
+        let timezone: String = row.get("timezone");
+        let quiet_hours = QuietHours {
+            start: row.get("start_time"),
+            end: row.get("end_time"),
+            timezone: timezone.parse().map_err(|_| {
+                NotificationError::InternalError(format!("Unknown time zone {}", timezone))
+            })?,
+        };
+
+        Ok(quiet_hours.settings(row.get("enabled")))
+    }
+
+    // Replace a user's quiet hours. Notifications already held back are still summed up when
+    // the quiet hours they arrived in end.
+    pub async fn set_quiet_hours(
+        &self,
+        user_id: &Uuid,
+        settings: &QuietHoursSettings,
+    ) -> Result<QuietHoursSettings, NotificationError> {
+        let quiet_hours = QuietHours::parse(settings)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO global.notification_quiet_hours (
+                user_id, enabled, start_time, end_time, timezone, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT (user_id) DO UPDATE
+            SET enabled = EXCLUDED.enabled,
+                start_time = EXCLUDED.start_time,
+                end_time = EXCLUDED.end_time,
+                timezone = EXCLUDED.timezone,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(user_id)
+        .bind(settings.enabled)
+        .bind(quiet_hours.start)
+        .bind(quiet_hours.end)
+        .bind(quiet_hours.timezone.name())
+        .execute(&self.pool)
+        .await?;
+
+        info!("Updated notification quiet hours of user {}", user_id);
+        Ok(quiet_hours.settings(settings.enabled))
+    }
+
+    // Whether the user is in their quiet hours at `now`. If so, the notification being
+    // delivered is counted towards the summary due when the quiet hours end.
+    async fn hold_during_quiet_hours(
+        &self,
+        user_id: &Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<bool, NotificationError> {
+        let row = sqlx::query(
+            r#"
+            SELECT start_time, end_time, timezone
+            FROM global.notification_quiet_hours
+            WHERE user_id = $1 AND enabled = true
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let window_end = row.and_then(|row| {
+            let timezone: String = row.get("timezone");
+            let quiet_hours = QuietHours {
+                start: row.get::<NaiveTime, _>("start_time"),
+                end: row.get::<NaiveTime, _>("end_time"),
+                timezone: timezone.parse().ok()?,
+            };
+            quiet_hours.window_end(now)
+        });
+        let window_end = match window_end {
+            Some(window_end) => window_end,
+            None => return Ok(false),
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE global.notification_quiet_hours
+            SET held_count = held_count + 1, summary_due_at = $2
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .bind(window_end)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(true)
+    }
+
+    // Push a summary to every user whose quiet hours ended with notifications held back, so
+    // their clients can catch up on what arrived in the meantime
+    pub async fn deliver_quiet_hours_summaries(&self) -> Result<usize, NotificationError> {
+        let rows = sqlx::query(
+            r#"
+            WITH due AS (
+                SELECT user_id, held_count FROM global.notification_quiet_hours
+                WHERE summary_due_at <= NOW()
+                ORDER BY summary_due_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE global.notification_quiet_hours q
+            SET held_count = 0, summary_due_at = NULL
+            FROM due
+            WHERE q.user_id = due.user_id
+            RETURNING due.user_id, due.held_count
+            "#,
+        )
+        .bind(SUMMARY_BATCH_SIZE)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let Some(redis_cache) = &self.redis_cache else {
+            return Ok(0);
+        };
+        for row in &rows {
+            let user_id: Uuid = row.get("user_id");
+            let held_count: i32 = row.get("held_count");
+            let pushed = match self.unread_count(&user_id).await {
+                Ok(unread_count) => {
+                    publish_quiet_hours_summary(redis_cache, &user_id, held_count, unread_count)
+                        .await
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = pushed {
+                warn!("Failed to push quiet hours summary to {}: {}", user_id, e);
+            }
+        }
+
+        Ok(rows.len())
+    }
 
-        // Return empty vector for this stub
-        Ok(Vec::new())
+    // Periodically deliver outbox rows that were not picked up right after their commit,
+    // e.g. because the process stopped in between, and summaries of quiet hours that ended
+    pub fn start_outbox_relay(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(OUTBOX_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.deliver_outbox().await {
+                    error!("Notification outbox relay failed: {}", e);
+                }
+                if let Err(e) = self.deliver_quiet_hours_summaries().await {
+                    error!("Failed to deliver quiet hours summaries: {}", e);
+                }
+            }
+        });
     }
 }
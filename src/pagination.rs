@@ -0,0 +1,244 @@
+use axum::http::{header, HeaderMap, HeaderValue, Uri};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+/// Default number of items per page when the client does not ask for a limit
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+
+/// Hard upper bound on items per page for every list endpoint
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Version tag embedded in cursors so the encoding can change later
+const CURSOR_VERSION: &str = "v1";
+
+/// Query parameters shared by all paginated list endpoints
+#[derive(Debug, Default, Clone, Deserialize, ToSchema, IntoParams)]
+#[into_params(style = Form, parameter_in = Query)]
+pub struct PageParams {
+    /// Maximum number of items to return
+    #[schema(example = "20", default = "20", minimum = 1, maximum = 100)]
+    pub limit: Option<i64>,
+
+    /// Opaque cursor taken from the `Link` header of a previous response
+    #[schema(example = "djE6MjA")]
+    pub cursor: Option<String>,
+
+    /// Page number (1-based), kept for older clients; ignored when `cursor` is set
+    #[schema(example = "1")]
+    pub page: Option<i64>,
+
+    /// Raw offset, kept for older clients; ignored when `cursor` or `page` is set
+    #[schema(example = "0", minimum = 0)]
+    pub offset: Option<i64>,
+}
+
+impl PageParams {
+    /// Resolve these parameters into a concrete limit/offset window
+    pub fn pagination(&self, default_limit: i64) -> Result<Pagination, PaginationError> {
+        Pagination::resolve(
+            self.limit,
+            self.cursor.as_deref(),
+            self.page,
+            self.offset,
+            default_limit,
+        )
+    }
+}
+
+/// Errors raised while interpreting pagination parameters
+#[derive(Debug, thiserror::Error)]
+pub enum PaginationError {
+    #[error("Invalid pagination cursor")]
+    InvalidCursor,
+}
+
+/// A resolved page window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Pagination {
+    /// Build a page window from the raw request values.
+    ///
+    /// Precedence is `cursor`, then `page`, then `offset`. The limit is clamped
+    /// to `1..=MAX_PAGE_SIZE` so no endpoint can be asked for unbounded results.
+    pub fn resolve(
+        limit: Option<i64>,
+        cursor: Option<&str>,
+        page: Option<i64>,
+        offset: Option<i64>,
+        default_limit: i64,
+    ) -> Result<Self, PaginationError> {
+        let limit = limit.unwrap_or(default_limit).clamp(1, MAX_PAGE_SIZE);
+
+        let offset = match (cursor, page) {
+            (Some(cursor), _) => decode_cursor(cursor)?,
+            (None, Some(page)) => (page.max(1) - 1) * limit,
+            (None, None) => offset.unwrap_or(0).max(0),
+        };
+
+        Ok(Self { limit, offset })
+    }
+
+    /// Key identifying this window, for use in per-page cache entries
+    pub fn cache_field(&self) -> String {
+        format!("{}:{}", self.limit, self.offset)
+    }
+
+    /// Cursor for the page after this one, if `returned` suggests there is one
+    pub fn next_cursor(&self, returned: usize) -> Option<String> {
+        if (returned as i64) < self.limit {
+            return None;
+        }
+        Some(encode_cursor(self.offset + self.limit))
+    }
+
+    /// Cursor for the page before this one, if this is not the first page
+    pub fn prev_cursor(&self) -> Option<String> {
+        if self.offset == 0 {
+            return None;
+        }
+        Some(encode_cursor((self.offset - self.limit).max(0)))
+    }
+
+    /// Build an RFC 8288 `Link` header value with `first`, `prev` and `next`
+    /// relations for the request at `uri`, preserving unrelated query params
+    pub fn link_header(&self, uri: &Uri, returned: usize) -> Option<HeaderValue> {
+        let base_query: Vec<&str> = uri
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap_or("");
+                !matches!(key, "limit" | "cursor" | "page" | "offset")
+            })
+            .collect();
+
+        let link_for = |cursor: Option<String>| {
+            let mut query = base_query.join("&");
+            if !query.is_empty() {
+                query.push('&');
+            }
+            query.push_str(&format!("limit={}", self.limit));
+            if let Some(cursor) = cursor {
+                query.push_str(&format!("&cursor={}", cursor));
+            }
+            format!("{}?{}", uri.path(), query)
+        };
+
+        let mut links = vec![format!("<{}>; rel=\"first\"", link_for(None))];
+        if let Some(prev) = self.prev_cursor() {
+            links.push(format!("<{}>; rel=\"prev\"", link_for(Some(prev))));
+        }
+        if let Some(next) = self.next_cursor(returned) {
+            links.push(format!("<{}>; rel=\"next\"", link_for(Some(next))));
+        }
+
+        HeaderValue::from_str(&links.join(", ")).ok()
+    }
+
+    /// Response headers describing this page, ready to be returned from a handler
+    pub fn headers(&self, uri: &Uri, returned: usize) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(link) = self.link_header(uri, returned) {
+            headers.insert(header::LINK, link);
+        }
+        headers
+    }
+}
+
+/// Encode an offset as an opaque, URL-safe cursor
+pub fn encode_cursor(offset: i64) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", CURSOR_VERSION, offset))
+}
+
+/// Decode a cursor produced by [`encode_cursor`]
+pub fn decode_cursor(cursor: &str) -> Result<i64, PaginationError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| PaginationError::InvalidCursor)?;
+    let raw = String::from_utf8(bytes).map_err(|_| PaginationError::InvalidCursor)?;
+
+    match raw.split_once(':') {
+        Some((CURSOR_VERSION, offset)) => offset
+            .parse::<i64>()
+            .ok()
+            .filter(|offset| *offset >= 0)
+            .ok_or(PaginationError::InvalidCursor),
+        _ => Err(PaginationError::InvalidCursor),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        for offset in [0, 20, 12345] {
+            assert_eq!(decode_cursor(&encode_cursor(offset)).unwrap(), offset);
+        }
+    }
+
+    #[test]
+    fn test_malformed_cursors_are_rejected() {
+        assert!(decode_cursor("not base64!").is_err());
+        assert!(decode_cursor(&URL_SAFE_NO_PAD.encode("v2:20")).is_err());
+        assert!(decode_cursor(&URL_SAFE_NO_PAD.encode("v1:-5")).is_err());
+    }
+
+    #[test]
+    fn test_resolve_precedence_and_clamping() {
+        let cursor = encode_cursor(40);
+        let p = Pagination::resolve(Some(500), Some(&cursor), Some(3), Some(7), 20).unwrap();
+        assert_eq!(
+            p,
+            Pagination {
+                limit: MAX_PAGE_SIZE,
+                offset: 40
+            }
+        );
+
+        let p = Pagination::resolve(Some(10), None, Some(3), Some(7), 20).unwrap();
+        assert_eq!(
+            p,
+            Pagination {
+                limit: 10,
+                offset: 20
+            }
+        );
+
+        let p = Pagination::resolve(None, None, None, Some(-3), 20).unwrap();
+        assert_eq!(
+            p,
+            Pagination {
+                limit: 20,
+                offset: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_link_header_relations() {
+        let uri: Uri = "/api/v1/posts/popular?tag=rust&limit=10&cursor=abc"
+            .parse()
+            .unwrap();
+        let p = Pagination {
+            limit: 10,
+            offset: 10,
+        };
+
+        let link = p.link_header(&uri, 10).unwrap();
+        let link = link.to_str().unwrap();
+        assert!(link.contains("</api/v1/posts/popular?tag=rust&limit=10>; rel=\"first\""));
+        assert!(link.contains(&format!("cursor={}>; rel=\"prev\"", encode_cursor(0))));
+        assert!(link.contains(&format!("cursor={}>; rel=\"next\"", encode_cursor(20))));
+
+        let last_page = p.link_header(&uri, 3).unwrap();
+        assert!(!last_page.to_str().unwrap().contains("rel=\"next\""));
+    }
+}
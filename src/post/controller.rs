@@ -1,15 +1,34 @@
+use crate::auth::jwt::Role;
 use crate::auth::middleware::AuthUser;
 use crate::cache::redis::RedisCache;
-use crate::post::model::{CreatePostRequest, UpdatePostRequest};
-use crate::post::service::{PostError as ServiceError, PostService};
+use crate::cache::surrogate::{SurrogateKey, SurrogateKeys};
+use crate::db::retry::unavailable_response;
+use crate::db::router::DbRouter;
+use crate::fields::{FieldsError, FieldsParams, Fieldset};
+use crate::link_preview::service::LinkPreviewService;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use crate::post::metadata::parse_metadata_filters;
+use crate::post::model::{
+    AuthorPostsParams, CreatePostRequest, EditLock, EditLockParams, PostResponse,
+    UpdatePostRequest, AUTHOR_POST_FIELDS, POST_LIST_FIELDS,
+};
+use crate::post::service::{
+    listing_keys, meta_description, PostError as ServiceError, PostService,
+};
+use crate::quota::controller::quota_error_response;
+use crate::quota::model::QuotaKind;
+use crate::quota::service::QuotaService;
+use crate::sitemap::site_base_url;
+use crate::tenant::model::Blog;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::{OriginalUri, Path, Query, State},
+    http::{header, StatusCode},
     response::{IntoResponse, Json, Response},
     Extension,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::{error, info};
 use utoipa::ToSchema;
 
@@ -23,13 +42,6 @@ pub struct PostIdPathParam {
     id: i64,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
-pub struct PopularPostsParams {
-    /// Maximum number of posts to retrieve
-    #[schema(example = "10", default = "10", minimum = 1, maximum = 100)]
-    limit: Option<i64>,
-}
-
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ErrorResponse {
     pub error: String,
@@ -39,6 +51,11 @@ pub struct ErrorResponse {
 /// Create a new blog post
 ///
 /// Creates a new blog post with the provided data and associates it with the authenticated user.
+/// Counts against the daily posts quota of the user's role.
+///
+/// When the content looks like that of one of the author's posts from the last 30 days, the
+/// response has a `duplicate_of` warning naming it. A post that looks like a copy of another
+/// user's recent post is created, but flagged for moderators.
 #[utoipa::path(
     post,
     path = "/api/posts",
@@ -46,8 +63,9 @@ pub struct ErrorResponse {
     responses(
         (status = 201, description = "Post created successfully", body = PostResponse),
         (status = 400, description = "Invalid request data", body = ErrorResponse),
-        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
         (status = 409, description = "Conflict - slug or title already exists", body = ErrorResponse),
+        (status = 429, description = "Daily posts quota exceeded", body = QuotaExceededResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
@@ -57,22 +75,36 @@ pub struct ErrorResponse {
 )]
 pub async fn create_post(
     user: AuthUser,
-    State((pool, redis_cache)): State<(PgPool, Option<RedisCache>)>,
+    Extension(link_previews): Extension<Arc<LinkPreviewService>>,
+    Extension(quotas): Extension<Arc<QuotaService>>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
     Json(post_data): Json<CreatePostRequest>,
 ) -> Response {
     info!("Creating post with title: {}", post_data.title);
 
-    let service = PostService::new(pool, redis_cache);
+    if let Err(e) = quotas.consume(&user, QuotaKind::Posts).await {
+        return quota_error_response(e);
+    }
+
+    let service = PostService::new(db, redis_cache);
 
     // Use the UUID directly instead of converting to i64
     let user_id = user.user_id;
 
     match service.create_post(user_id, post_data).await {
-        Ok(post) => {
+        Ok((post, duplicate_of)) => {
+            link_previews.enqueue_from_content(&post.content);
+
             // Get the complete post with author info and tags
-            match service.get_post_by_id(post.id).await {
-                Ok(post_response) => {
+            let viewer = match service.viewer(Some(&user)).await {
+                Ok(viewer) => viewer,
+                Err(e) => return viewer_error_response(e),
+            };
+            match service.get_post_by_id(post.id, &viewer).await {
+                Ok(mut post_response) => {
                     info!("Successfully created post with ID: {}", post.id);
+                    // Only for the author, so set after the response may have been cached
+                    post_response.duplicate_of = duplicate_of;
                     (StatusCode::CREATED, Json(post_response)).into_response()
                 }
                 Err(e) => {
@@ -90,6 +122,10 @@ pub async fn create_post(
         }
         Err(e) => {
             error!("Error creating post: {:?}", e);
+            quotas.refund(user_id, QuotaKind::Posts).await;
+            if let Some(response) = unavailable_response(&e) {
+                return response;
+            }
             let (status, error_response) = match e {
                 ServiceError::SlugExists => (
                     StatusCode::CONFLICT,
@@ -126,6 +162,108 @@ pub async fn create_post(
     }
 }
 
+/// Duplicate a post
+///
+/// Copies a post's content, tags and cover image into a new draft owned by the authenticated
+/// user, titled "<title> (copy)" with the slug "<slug>-copy" (numbered when taken). The user
+/// must be able to read the whole post. Counts against the daily posts quota of the user's role.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/duplicate",
+    params(
+        ("id" = i64, Path, description = "ID of the post to copy")
+    ),
+    responses(
+        (status = 201, description = "Draft created from the post", body = PostResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 403, description = "Forbidden - the post requires a higher membership tier", body = ErrorResponse),
+        (status = 404, description = "Post not found, or a draft of another user", body = ErrorResponse),
+        (status = 409, description = "Conflict - no free title and slug for the copy", body = ErrorResponse),
+        (status = 429, description = "Daily posts quota exceeded", body = QuotaExceededResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn duplicate_post(
+    user: AuthUser,
+    Path(params): Path<PostIdPathParam>,
+    Extension(quotas): Extension<Arc<QuotaService>>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    info!("Duplicating post with ID: {}", params.id);
+
+    if let Err(e) = quotas.consume(&user, QuotaKind::Posts).await {
+        return quota_error_response(e);
+    }
+
+    let service = PostService::new(db, redis_cache);
+
+    let copy = match service.duplicate_post(params.id, &user).await {
+        Ok(copy) => copy,
+        Err(e) => {
+            error!("Error duplicating post: {:?}", e);
+            quotas.refund(user.user_id, QuotaKind::Posts).await;
+            let (status, error_response) = match e {
+                ServiceError::NotFound => (
+                    StatusCode::NOT_FOUND,
+                    ErrorResponse {
+                        error: "Post not found".to_string(),
+                        code: "NOT_FOUND".to_string(),
+                    },
+                ),
+                ServiceError::Unauthorized => (
+                    StatusCode::FORBIDDEN,
+                    ErrorResponse {
+                        error: "You do not have access to the full post".to_string(),
+                        code: "FORBIDDEN".to_string(),
+                    },
+                ),
+                ServiceError::SlugExists => (
+                    StatusCode::CONFLICT,
+                    ErrorResponse {
+                        error: "Too many copies of this post already exist".to_string(),
+                        code: "SLUG_EXISTS".to_string(),
+                    },
+                ),
+                _ => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorResponse {
+                        error: "Failed to duplicate post".to_string(),
+                        code: "INTERNAL_ERROR".to_string(),
+                    },
+                ),
+            };
+
+            return (status, Json(error_response)).into_response();
+        }
+    };
+
+    let viewer = match service.viewer(Some(&user)).await {
+        Ok(viewer) => viewer,
+        Err(e) => return viewer_error_response(e),
+    };
+    match service.get_post_by_id(copy.id, &viewer).await {
+        Ok(post_response) => {
+            info!("Duplicated post {} into draft {}", params.id, copy.id);
+            (StatusCode::CREATED, Json(post_response)).into_response()
+        }
+        Err(e) => {
+            error!("Error retrieving duplicated post: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Error retrieving duplicated post".to_string(),
+                    code: "INTERNAL_ERROR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Get post by ID or slug
 ///
 /// Retrieves a post by its ID (numeric) or slug (string)
@@ -146,20 +284,26 @@ pub async fn create_post(
     tag = "posts"
 )]
 pub async fn get_post(
-    Extension(_user): Extension<Option<AuthUser>>,
+    Extension(user): Extension<Option<AuthUser>>,
     Path(params): Path<IdOrSlugPathParam>,
-    State((pool, redis_cache)): State<(PgPool, Option<RedisCache>)>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
 ) -> Response {
     let id_or_slug = params.id_or_slug;
     info!("Getting post with ID/slug: {}", id_or_slug);
 
-    let service = PostService::new(pool, redis_cache);
+    let service = PostService::new(db, redis_cache);
+
+    // Premium posts are previewed for readers below the required membership tier
+    let viewer = match service.viewer(user.as_ref()).await {
+        Ok(viewer) => viewer,
+        Err(e) => return viewer_error_response(e),
+    };
 
     // Check if the parameter is an ID (numeric) or slug (string)
     let result = if let Ok(id) = id_or_slug.parse::<i64>() {
-        service.get_post_by_id(id).await
+        service.get_post_by_id(id, &viewer).await
     } else {
-        service.get_post_by_slug(&id_or_slug).await
+        service.get_post_by_slug(&id_or_slug, &viewer).await
     };
 
     match result {
@@ -169,6 +313,9 @@ pub async fn get_post(
         }
         Err(e) => {
             error!("Error retrieving post: {:?}", e);
+            if let Some(response) = unavailable_response(&e) {
+                return response;
+            }
             let (status, error_response) = match e {
                 ServiceError::NotFound => (
                     StatusCode::NOT_FOUND,
@@ -204,10 +351,10 @@ pub async fn get_post(
     responses(
         (status = 200, description = "Post updated successfully", body = PostResponse),
         (status = 400, description = "Invalid request data", body = ErrorResponse),
-        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
         (status = 403, description = "Forbidden - user is not the post owner or admin", body = ErrorResponse),
         (status = 404, description = "Post not found", body = ErrorResponse),
-        (status = 409, description = "Conflict - slug or title already exists", body = ErrorResponse),
+        (status = 409, description = "Conflict - slug or title already exists, or another user holds the edit lock", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
@@ -218,23 +365,33 @@ pub async fn get_post(
 pub async fn update_post(
     user: AuthUser,
     Path(params): Path<PostIdPathParam>,
-    State((pool, redis_cache)): State<(PgPool, Option<RedisCache>)>,
+    Extension(link_previews): Extension<Arc<LinkPreviewService>>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
     Json(update_data): Json<UpdatePostRequest>,
 ) -> Response {
     info!("Updating post with ID: {}", params.id);
 
-    let service = PostService::new(pool, redis_cache);
+    let service = PostService::new(db, redis_cache);
 
     // Use the UUID directly instead of converting to i64
     let user_id = user.user_id;
 
-    match service.update_post(params.id, user_id, update_data).await {
+    let is_admin = user.role == Role::Admin;
+
+    match service
+        .update_post(params.id, user_id, is_admin, update_data)
+        .await
+    {
         Ok(post) => {
             info!("Successfully updated post with ID: {}", params.id);
+            link_previews.enqueue_from_content(&post.content);
             (StatusCode::OK, Json(post)).into_response()
         }
         Err(e) => {
             error!("Error updating post: {:?}", e);
+            if let Some(response) = unavailable_response(&e) {
+                return response;
+            }
             let (status, error_response) = match e {
                 ServiceError::NotFound => (
                     StatusCode::NOT_FOUND,
@@ -271,6 +428,9 @@ pub async fn update_post(
                         code: "INVALID_INPUT".to_string(),
                     },
                 ),
+                ServiceError::EditLocked(lock) => {
+                    (StatusCode::CONFLICT, edit_locked_response(&lock))
+                }
                 _ => (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     ErrorResponse {
@@ -285,6 +445,185 @@ pub async fn update_post(
     }
 }
 
+fn edit_locked_response(lock: &EditLock) -> ErrorResponse {
+    ErrorResponse {
+        error: format!(
+            "Post is being edited by user {} until {}",
+            lock.user_id, lock.expires_at
+        ),
+        code: "EDIT_LOCKED".to_string(),
+    }
+}
+
+/// Acquire post edit lock
+///
+/// Takes (or refreshes) a short-lived soft lock on a post while editing it. Co-authors are told
+/// over the notifications WebSocket, and updates from other users are refused with 409 until the
+/// lock is released or expires. Clients should call this again periodically to keep the lock.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/edit-lock",
+    params(
+        ("id" = i64, Path, description = "Post ID"),
+        EditLockParams
+    ),
+    responses(
+        (status = 200, description = "Lock acquired or refreshed", body = EditLock),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 403, description = "Forbidden - user is not the post owner or admin", body = ErrorResponse),
+        (status = 404, description = "Post not found", body = ErrorResponse),
+        (status = 409, description = "Another user holds the edit lock", body = ErrorResponse),
+        (status = 503, description = "Edit locks are unavailable", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn acquire_edit_lock(
+    user: AuthUser,
+    Path(params): Path<PostIdPathParam>,
+    Query(lock_params): Query<EditLockParams>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = PostService::new(db, redis_cache);
+    let is_admin = user.role == Role::Admin;
+    let force = lock_params.force.unwrap_or(false);
+
+    match service
+        .acquire_edit_lock(params.id, user.user_id, is_admin, force)
+        .await
+    {
+        Ok(lock) => (StatusCode::OK, Json(lock)).into_response(),
+        Err(e) => edit_lock_error_response(e),
+    }
+}
+
+/// Release post edit lock
+///
+/// Releases the soft edit lock on a post. Only the lock holder or an admin can release it.
+#[utoipa::path(
+    delete,
+    path = "/api/posts/{id}/edit-lock",
+    params(
+        ("id" = i64, Path, description = "Post ID")
+    ),
+    responses(
+        (status = 204, description = "Lock released"),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 404, description = "Post not found", body = ErrorResponse),
+        (status = 409, description = "Another user holds the edit lock", body = ErrorResponse),
+        (status = 503, description = "Edit locks are unavailable", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn release_edit_lock(
+    user: AuthUser,
+    Path(params): Path<PostIdPathParam>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = PostService::new(db, redis_cache);
+    let is_admin = user.role == Role::Admin;
+
+    match service
+        .release_edit_lock(params.id, user.user_id, is_admin)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => edit_lock_error_response(e),
+    }
+}
+
+fn edit_lock_error_response(e: ServiceError) -> Response {
+    error!("Edit lock error: {:?}", e);
+    let (status, error_response) = match e {
+        ServiceError::NotFound => (
+            StatusCode::NOT_FOUND,
+            ErrorResponse {
+                error: "Post not found".to_string(),
+                code: "NOT_FOUND".to_string(),
+            },
+        ),
+        ServiceError::Unauthorized => (
+            StatusCode::FORBIDDEN,
+            ErrorResponse {
+                error: "You do not have permission to edit this post".to_string(),
+                code: "FORBIDDEN".to_string(),
+            },
+        ),
+        ServiceError::EditLocked(lock) => (StatusCode::CONFLICT, edit_locked_response(&lock)),
+        ServiceError::EditLocksUnavailable => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorResponse {
+                error: "Edit locks are unavailable".to_string(),
+                code: "EDIT_LOCKS_UNAVAILABLE".to_string(),
+            },
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorResponse {
+                error: "Failed to manage edit lock".to_string(),
+                code: "INTERNAL_ERROR".to_string(),
+            },
+        ),
+    };
+
+    (status, Json(error_response)).into_response()
+}
+
+fn fields_error_response(e: FieldsError) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: e.to_string(),
+            code: "INVALID_FIELDS".to_string(),
+        }),
+    )
+        .into_response()
+}
+
+// Posts of a list with only the requested fields, deriving `excerpt` when it is requested
+fn post_list_fields(posts: &[PostResponse], fieldset: Option<&Fieldset>) -> Vec<serde_json::Value> {
+    posts
+        .iter()
+        .map(|post| {
+            let mut value = serde_json::to_value(post).unwrap_or_default();
+            match fieldset {
+                Some(fieldset) => {
+                    if let (true, Some(object)) =
+                        (fieldset.contains("excerpt"), value.as_object_mut())
+                    {
+                        object.insert(
+                            "excerpt".to_string(),
+                            meta_description(&post.content).into(),
+                        );
+                    }
+                    fieldset.select(value)
+                }
+                None => value,
+            }
+        })
+        .collect()
+}
+
+fn viewer_error_response(e: ServiceError) -> Response {
+    error!("Error resolving membership tier of viewer: {:?}", e);
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Failed to retrieve post".to_string(),
+            code: "INTERNAL_ERROR".to_string(),
+        }),
+    )
+        .into_response()
+}
+
 /// Delete post
 ///
 /// Deletes (soft delete) an existing post. User must be the post owner or an admin.
@@ -296,7 +635,7 @@ pub async fn update_post(
     ),
     responses(
         (status = 204, description = "Post deleted successfully"),
-        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
         (status = 403, description = "Forbidden - user is not the post owner or admin", body = ErrorResponse),
         (status = 404, description = "Post not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
@@ -309,11 +648,11 @@ pub async fn update_post(
 pub async fn delete_post(
     user: AuthUser,
     Path(params): Path<PostIdPathParam>,
-    State((pool, redis_cache)): State<(PgPool, Option<RedisCache>)>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
 ) -> Response {
     info!("Deleting post with ID: {}", params.id);
 
-    let service = PostService::new(pool, redis_cache);
+    let service = PostService::new(db, redis_cache);
 
     // Use the UUID directly instead of converting to i64
     let user_id = user.user_id;
@@ -325,6 +664,9 @@ pub async fn delete_post(
         }
         Err(e) => {
             error!("Error deleting post: {:?}", e);
+            if let Some(response) = unavailable_response(&e) {
+                return response;
+            }
             let (status, error_response) = match e {
                 ServiceError::NotFound => (
                     StatusCode::NOT_FOUND,
@@ -354,38 +696,164 @@ pub async fn delete_post(
     }
 }
 
+/// List published posts
+///
+/// Retrieves a page of the published posts, newest first. Filter by custom metadata with
+/// `meta.<key>=<value>` parameters, e.g. `?meta.podcast.episode=12`, joining the keys of nested
+/// objects with dots; values match as strings, and as numbers or booleans when they read as
+/// one. Posts must match every filter, of which there may be up to five. Further pages are
+/// linked from the `Link` response header. Pass `fields` to return only those fields of each
+/// post.
+#[utoipa::path(
+    get,
+    path = "/api/posts",
+    params(PageParams, FieldsParams),
+    responses(
+        (status = 200, description = "Posts retrieved successfully", body = Vec<PostResponse>),
+        (status = 400, description = "Invalid metadata filter, pagination cursor or fields", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "posts"
+)]
+pub async fn list_posts(
+    Extension(user): Extension<Option<AuthUser>>,
+    OriginalUri(uri): OriginalUri,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+    Query(params): Query<PageParams>,
+    Query(fields): Query<FieldsParams>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                    code: "INVALID_CURSOR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+    let fieldset = match Fieldset::parse(fields.fields.as_deref(), POST_LIST_FIELDS) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return fields_error_response(e),
+    };
+    let metadata = match parse_metadata_filters(&query) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e,
+                    code: "INVALID_METADATA_FILTER".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    let service = PostService::new(db, redis_cache);
+
+    let viewer = match service.viewer(user.as_ref()).await {
+        Ok(viewer) => viewer,
+        Err(e) => return viewer_error_response(e),
+    };
+
+    match service
+        .get_published_posts(&pagination, None, &metadata, &viewer)
+        .await
+    {
+        Ok((posts, _)) => {
+            let headers = pagination.headers(&uri, posts.len());
+            let keys = SurrogateKeys(listing_keys(&posts));
+            let posts = post_list_fields(&posts, fieldset.as_ref());
+            (StatusCode::OK, headers, keys, Json(posts)).into_response()
+        }
+        Err(e) => {
+            error!("Error listing posts: {:?}", e);
+            if let Some(response) = unavailable_response(&e) {
+                return response;
+            }
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to retrieve posts".to_string(),
+                    code: "INTERNAL_ERROR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Get popular posts
 ///
-/// Retrieves a list of the most popular posts based on views and engagement
+/// Retrieves a page of the posts with the highest popularity score: views, likes and
+/// comments, decayed by age.
+/// Further pages are linked from the `Link` response header. Pass `fields`, e.g.
+/// `id,title,excerpt,author`, to return only those fields of each post.
 #[utoipa::path(
     get,
     path = "/api/posts/popular",
-    params(
-        ("limit" = Option<i64>, Query, description = "Maximum number of posts to retrieve", example = "10")
-    ),
+    params(PageParams, FieldsParams),
     responses(
         (status = 200, description = "Popular posts retrieved successfully", body = PopularPostsResponse),
+        (status = 400, description = "Invalid pagination cursor or fields", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     tag = "posts"
 )]
 pub async fn get_popular_posts(
-    Extension(_user): Extension<Option<AuthUser>>,
-    State((pool, redis_cache)): State<(PgPool, Option<RedisCache>)>,
-    Query(params): Query<PopularPostsParams>,
+    Extension(user): Extension<Option<AuthUser>>,
+    OriginalUri(uri): OriginalUri,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+    Query(params): Query<PageParams>,
+    Query(fields): Query<FieldsParams>,
 ) -> Response {
-    let limit = params.limit.unwrap_or(10);
-    info!("Getting popular posts, limit: {}", limit);
+    let pagination = match params.pagination(10) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                    code: "INVALID_CURSOR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+    let fieldset = match Fieldset::parse(fields.fields.as_deref(), POST_LIST_FIELDS) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return fields_error_response(e),
+    };
+    info!(
+        "Getting popular posts, limit: {}, offset: {}",
+        pagination.limit, pagination.offset
+    );
 
-    let service = PostService::new(pool, redis_cache);
+    let service = PostService::new(db, redis_cache);
 
-    match service.get_popular_posts(limit).await {
+    let viewer = match service.viewer(user.as_ref()).await {
+        Ok(viewer) => viewer,
+        Err(e) => return viewer_error_response(e),
+    };
+
+    match service.get_popular_posts(&pagination, &viewer).await {
         Ok(posts) => {
             info!("Successfully retrieved {} popular posts", posts.len());
-            (StatusCode::OK, Json(posts)).into_response()
+            let headers = pagination.headers(&uri, posts.len());
+            let keys = SurrogateKeys(listing_keys(&posts));
+            let posts = post_list_fields(&posts, fieldset.as_ref());
+            (StatusCode::OK, headers, keys, Json(posts)).into_response()
         }
         Err(e) => {
             error!("Error retrieving popular posts: {:?}", e);
+            if let Some(response) = unavailable_response(&e) {
+                return response;
+            }
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ErrorResponse {
@@ -397,3 +865,342 @@ pub async fn get_popular_posts(
         }
     }
 }
+
+/// Get the post archive
+///
+/// Returns how many posts were published in each month, newest month first, for archive
+/// navigation. Months are in UTC.
+#[utoipa::path(
+    get,
+    path = "/api/posts/archive",
+    responses(
+        (status = 200, description = "Post archive retrieved successfully", body = Vec<ArchiveMonth>),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "posts"
+)]
+pub async fn get_archive(
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = PostService::new(db, redis_cache);
+
+    match service.get_archive().await {
+        Ok(months) => (
+            StatusCode::OK,
+            SurrogateKeys(vec![SurrogateKey::Posts]),
+            Json(months),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Error retrieving post archive: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to retrieve post archive".to_string(),
+                    code: "INTERNAL_ERROR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Get the posts of an archive month
+///
+/// Retrieves a page of the posts published in a month (UTC), newest first.
+/// Further pages are linked from the `Link` response header. Pass `fields` to return only
+/// those fields of each post.
+#[utoipa::path(
+    get,
+    path = "/api/posts/archive/{year}/{month}",
+    params(
+        ("year" = i32, Path, description = "Year", example = 2025),
+        ("month" = u32, Path, description = "Month, 1 to 12", example = 3),
+        PageParams,
+        FieldsParams
+    ),
+    responses(
+        (status = 200, description = "Posts retrieved successfully", body = Vec<PostResponse>),
+        (status = 400, description = "Invalid month, pagination cursor or fields", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "posts"
+)]
+pub async fn get_archive_posts(
+    Path((year, month)): Path<(i32, u32)>,
+    Extension(user): Extension<Option<AuthUser>>,
+    OriginalUri(uri): OriginalUri,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+    Query(params): Query<PageParams>,
+    Query(fields): Query<FieldsParams>,
+) -> Response {
+    let pagination = match params.pagination(10) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                    code: "INVALID_CURSOR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+    let fieldset = match Fieldset::parse(fields.fields.as_deref(), POST_LIST_FIELDS) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return fields_error_response(e),
+    };
+
+    let service = PostService::new(db, redis_cache);
+
+    let viewer = match service.viewer(user.as_ref()).await {
+        Ok(viewer) => viewer,
+        Err(e) => return viewer_error_response(e),
+    };
+
+    match service
+        .get_archive_posts(year, month, &pagination, &viewer)
+        .await
+    {
+        Ok(posts) => {
+            let headers = pagination.headers(&uri, posts.len());
+            let keys = SurrogateKeys(listing_keys(&posts));
+            let posts = post_list_fields(&posts, fieldset.as_ref());
+            (StatusCode::OK, headers, keys, Json(posts)).into_response()
+        }
+        Err(ServiceError::InvalidInput(msg)) => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: msg,
+                code: "INVALID_INPUT".to_string(),
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Error retrieving posts of {}-{:02}: {:?}", year, month, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to retrieve archive posts".to_string(),
+                    code: "INTERNAL_ERROR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Get link preview data of a post
+///
+/// Returns the title, description, cover image, author and publish time of a published post,
+/// also formatted as Open Graph and Twitter card tags. Public and cacheable.
+#[utoipa::path(
+    get,
+    path = "/api/posts/{id_or_slug}/meta",
+    params(
+        ("id_or_slug" = String, Path, description = "Post ID or slug")
+    ),
+    responses(
+        (status = 200, description = "Post meta retrieved successfully", body = PostMeta),
+        (status = 404, description = "Post not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "posts"
+)]
+pub async fn get_post_meta(
+    // Named `id` in the route to match the other `/posts/:id/...` routes
+    Path(id_or_slug): Path<String>,
+    Extension(blog): Extension<Blog>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let service = PostService::new(db, redis_cache);
+
+    match service
+        .get_post_meta(&id_or_slug, &site_base_url(&blog))
+        .await
+    {
+        Ok(meta) => (
+            StatusCode::OK,
+            [(header::CACHE_CONTROL, "public, max-age=3600")],
+            Json(meta),
+        )
+            .into_response(),
+        Err(ServiceError::NotFound) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Post not found".to_string(),
+                code: "NOT_FOUND".to_string(),
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Error retrieving meta of post {}: {:?}", id_or_slug, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to retrieve post meta".to_string(),
+                    code: "INTERNAL_ERROR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// List my posts
+///
+/// Returns a page of the authenticated author's posts, drafts included, each with its views,
+/// likes and comments from analytics. Further pages are linked from the `Link` response header.
+/// Pass `fields` to return only those fields of each post.
+#[utoipa::path(
+    get,
+    path = "/api/posts/mine",
+    params(AuthorPostsParams, PageParams, FieldsParams),
+    responses(
+        (status = 200, description = "Posts retrieved successfully", body = Vec<AuthorPostSummary>),
+        (status = 400, description = "Invalid pagination cursor or fields", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn get_my_posts(
+    user: AuthUser,
+    OriginalUri(uri): OriginalUri,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+    Query(filter): Query<AuthorPostsParams>,
+    Query(params): Query<PageParams>,
+    Query(fields): Query<FieldsParams>,
+) -> Response {
+    let pagination = match params.pagination(20) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                    code: "INVALID_CURSOR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+    let fieldset = match Fieldset::parse(fields.fields.as_deref(), AUTHOR_POST_FIELDS) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return fields_error_response(e),
+    };
+
+    let service = PostService::new(db, redis_cache);
+
+    match service
+        .get_author_posts(
+            user.user_id,
+            filter.status.unwrap_or_default(),
+            filter.sort.unwrap_or_default(),
+            &pagination,
+        )
+        .await
+    {
+        Ok(posts) => {
+            let headers = pagination.headers(&uri, posts.len());
+            (
+                StatusCode::OK,
+                headers,
+                Json(Fieldset::apply(fieldset.as_ref(), &posts)),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("Error retrieving posts of author {}: {:?}", user.user_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to retrieve posts".to_string(),
+                    code: "INTERNAL_ERROR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// List posts flagged as duplicates
+///
+/// Admin only. New posts whose content looks like a copy of another user's post from the last
+/// 30 days, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/moderation/posts",
+    params(PageParams),
+    responses(
+        (status = 200, description = "Flagged posts", body = Vec<DuplicatePostFlag>),
+        (status = 400, description = "Invalid pagination", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn get_duplicate_flags(
+    user: AuthUser,
+    OriginalUri(uri): OriginalUri,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: "Only admins can review flagged posts".to_string(),
+                code: "FORBIDDEN".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                    code: "INVALID_CURSOR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    let service = PostService::new(db, redis_cache);
+
+    match service.get_duplicate_flags(&pagination).await {
+        Ok(flags) => (
+            StatusCode::OK,
+            pagination.headers(&uri, flags.len()),
+            Json(flags),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Error retrieving flagged posts: {:?}", e);
+            if let Some(response) = unavailable_response(&e) {
+                return response;
+            }
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to retrieve flagged posts".to_string(),
+                    code: "INTERNAL_ERROR".to_string(),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Near-duplicate detection of post content.
+//!
+//! Each post stores a 64-bit SimHash of its content: every run of three words is hashed, and
+//! each bit of the fingerprint is set when most of the hashes have it set. Posts with mostly
+//! the same words in mostly the same order get fingerprints differing in few bits, however
+//! they are formatted, so near-duplicates are found by comparing fingerprints in the database.
+
+use crate::fnv;
+use crate::tenant::middleware::current_blog_id;
+use sqlx::{FromRow, PgConnection};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Words per hashed run of words
+const SHINGLE_WORDS: usize = 3;
+/// Posts with fewer words get no fingerprint: short texts say the same thing too often
+pub const MIN_FINGERPRINT_WORDS: usize = 20;
+/// Most bits two fingerprints may differ in for their posts to count as duplicates
+pub const MAX_DUPLICATE_DISTANCE: i32 = 8;
+/// How far back, in days, new posts are compared
+pub const DUPLICATE_WINDOW_DAYS: i32 = 30;
+
+/// SimHash of a post's content, ignoring case, punctuation and Markdown syntax; `None` for
+/// posts too short to compare
+pub fn content_fingerprint(content: &str) -> Option<i64> {
+    let words: Vec<String> = content
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() < MIN_FINGERPRINT_WORDS {
+        return None;
+    }
+
+    let mut weights = [0i32; 64];
+    for shingle in words.windows(SHINGLE_WORDS) {
+        let hash = fnv::hash(shingle.join(" ").into_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if hash & (1 << bit) != 0 { 1 } else { -1 };
+        }
+    }
+
+    let fingerprint = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |fingerprint, (bit, _)| fingerprint | (1 << bit));
+    Some(fingerprint as i64)
+}
+
+/// A recent post whose content looks like that of a new one
+#[derive(Debug, Clone, FromRow)]
+pub struct DuplicateCandidate {
+    pub id: i64,
+    pub user_id: Uuid,
+    pub title: String,
+    pub slug: String,
+    /// Bits the fingerprints differ in
+    pub distance: i32,
+}
+
+/// The closest recent post of the current blog with a fingerprint near `fingerprint`, the
+/// latest of equally close ones. An existing post is compared with the posts created before
+/// it (`before_post_id`), so it matches neither itself nor later copies of it.
+pub async fn find_duplicate(
+    conn: &mut PgConnection,
+    fingerprint: i64,
+    before_post_id: Option<i64>,
+) -> Result<Option<DuplicateCandidate>, sqlx::Error> {
+    sqlx::query_as::<_, DuplicateCandidate>(
+        r#"
+        SELECT id, user_id, title, slug, distance FROM (
+            SELECT id, user_id, title, slug, created_at,
+                bit_count((content_fingerprint # $2)::BIT(64))::INTEGER AS distance
+            FROM global.posts
+            WHERE blog_id = $1 AND is_deleted = false AND content_fingerprint IS NOT NULL
+                AND created_at > NOW() - make_interval(days => $3)
+                AND ($5::BIGINT IS NULL OR id < $5)
+        ) recent
+        WHERE distance <= $4
+        ORDER BY distance, created_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(current_blog_id())
+    .bind(fingerprint)
+    .bind(DUPLICATE_WINDOW_DAYS)
+    .bind(MAX_DUPLICATE_DISTANCE)
+    .bind(before_post_id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Fingerprint the content just written to a post, in the transaction writing it, and compare
+/// it with the recent posts of the blog. A near-duplicate of another user's post is flagged
+/// for moderators; one of the author's own is returned so they can be warned.
+pub async fn check_content(
+    conn: &mut PgConnection,
+    post_id: i64,
+    user_id: Uuid,
+    content: &str,
+) -> Result<Option<DuplicateCandidate>, sqlx::Error> {
+    let fingerprint = content_fingerprint(content);
+    set_fingerprint(&mut *conn, post_id, fingerprint).await?;
+    let duplicate = match fingerprint {
+        Some(fingerprint) => find_duplicate(&mut *conn, fingerprint, Some(post_id)).await?,
+        None => None,
+    };
+
+    match duplicate {
+        Some(duplicate) if duplicate.user_id != user_id => {
+            flag_duplicate(conn, post_id, user_id, &duplicate).await?;
+            warn!(
+                "Flagged post {} by {} as a duplicate of post {} ({} bits apart)",
+                post_id, user_id, duplicate.id, duplicate.distance
+            );
+            Ok(None)
+        }
+        duplicate => Ok(duplicate),
+    }
+}
+
+// Store the fingerprint of a post's content
+async fn set_fingerprint(
+    conn: &mut PgConnection,
+    post_id: i64,
+    fingerprint: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE global.posts SET content_fingerprint = $1 WHERE id = $2")
+        .bind(fingerprint)
+        .bind(post_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Put a post copying another user's post in the moderation queue, once per copied post
+pub async fn flag_duplicate(
+    conn: &mut PgConnection,
+    post_id: i64,
+    user_id: Uuid,
+    duplicate: &DuplicateCandidate,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO global.post_flags (blog_id, post_id, user_id, duplicate_of, distance)
+        SELECT $1, $2, $3, $4, $5
+        WHERE NOT EXISTS (
+            SELECT 1 FROM global.post_flags WHERE post_id = $2 AND duplicate_of = $4
+        )
+        "#,
+    )
+    .bind(current_blog_id())
+    .bind(post_id)
+    .bind(user_id)
+    .bind(duplicate.id)
+    .bind(duplicate.distance)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "Rust makes it possible to write fast and reliable software. Its \
+        ownership model guarantees memory safety without a garbage collector, and its type \
+        system catches many bugs at compile time before they ever reach production. Cargo \
+        builds the code, runs the tests and fetches dependencies from crates.io, while rustfmt \
+        and clippy keep a code base consistent. Async functions let servers handle thousands \
+        of connections on a handful of threads, and traits describe shared behaviour without \
+        inheritance. Error handling with Result makes every failure explicit.";
+
+    fn distance(a: &str, b: &str) -> i32 {
+        let a = content_fingerprint(a).unwrap();
+        let b = content_fingerprint(b).unwrap();
+        (a ^ b).count_ones() as i32
+    }
+
+    #[test]
+    fn test_reformatted_copy_is_identical() {
+        let copy = format!("# {}", ORIGINAL.to_uppercase().replace(", and", " **and**"));
+        assert_eq!(distance(ORIGINAL, &copy), 0);
+    }
+
+    #[test]
+    fn test_edited_copy_is_a_duplicate() {
+        let edited = format!("{} Buy cheap watches at example.com today!", ORIGINAL);
+        assert!(distance(ORIGINAL, &edited) > 0);
+        assert!(distance(ORIGINAL, &edited) <= MAX_DUPLICATE_DISTANCE);
+    }
+
+    #[test]
+    fn test_different_posts_are_far_apart() {
+        let other = "Sourdough bread needs only flour, water and salt, but it takes days. \
+            Feed the starter the evening before, mix the dough in the morning, fold it every \
+            half hour and let it rise slowly in the fridge overnight before baking.";
+        assert!(distance(ORIGINAL, other) > MAX_DUPLICATE_DISTANCE);
+    }
+
+    #[test]
+    fn test_short_posts_have_no_fingerprint() {
+        assert_eq!(
+            content_fingerprint("Hello world, this is my first post!"),
+            None
+        );
+    }
+}
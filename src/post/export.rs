@@ -0,0 +1,459 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::cache::redis::RedisCache;
+use crate::db::router::DbRouter;
+use crate::jobs::controller::job_error_response;
+use crate::jobs::model::{Job, JobError, JobStatus};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::media::controller::content_disposition;
+use crate::media::model::AttachmentConfig;
+use crate::post::controller::ErrorResponse;
+use crate::routes::versioning::API_V1_PREFIX;
+use crate::tenant::middleware::current_blog_id;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{FromRow, PgPool};
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{error, info};
+use utoipa::IntoParams;
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Kind of the job that exports every published post of a blog
+pub const SITE_EXPORT_JOB: &str = "site_export";
+
+// Post columns an export needs, with the post's tags in name order
+const EXPORT_SELECT: &str = r#"
+    SELECT p.user_id, p.title, p.slug, p.content, p.content_html, p.created_at,
+        COALESCE(ARRAY_AGG(t.name ORDER BY t.name) FILTER (WHERE t.name IS NOT NULL), '{}')
+            AS tags
+    FROM global.posts p
+    LEFT JOIN global.post_tags pt ON pt.post_id = p.id
+    LEFT JOIN global.tags t ON t.id = pt.tag_id
+"#;
+
+/// Query parameters of a post export
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    /// `markdown` (the default) or `html`
+    pub format: Option<String>,
+}
+
+/// A post as it is exported
+#[derive(Debug, Clone, FromRow)]
+pub struct ExportedPost {
+    pub user_id: Uuid,
+    pub title: String,
+    pub slug: String,
+    pub content: String,
+    pub content_html: String,
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+fn export_date(post: &ExportedPost) -> String {
+    post.created_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// YAML front matter of a post, as read by static site generators. Strings are written as
+/// JSON strings, which are valid double-quoted YAML scalars.
+pub fn front_matter(post: &ExportedPost) -> String {
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    format!(
+        "---\ntitle: {}\nslug: {}\ntags: [{}]\ndate: {}\n---\n",
+        quote(&post.title),
+        quote(&post.slug),
+        post.tags
+            .iter()
+            .map(|tag| quote(tag))
+            .collect::<Vec<_>>()
+            .join(", "),
+        export_date(post)
+    )
+}
+
+/// The post as a markdown file with front matter
+pub fn to_markdown(post: &ExportedPost) -> String {
+    format!("{}\n{}\n", front_matter(post), post.content.trim_end())
+}
+
+/// The post as a standalone HTML page
+pub fn to_html(post: &ExportedPost) -> String {
+    let title = html_escape::encode_text(&post.title);
+    let date = export_date(post);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <meta name=\"keywords\" content=\"{}\">\n</head>\n<body>\n<article>\n<h1>{}</h1>\n\
+         <time datetime=\"{}\">{}</time>\n{}\n</article>\n</body>\n</html>\n",
+        title,
+        html_escape::encode_double_quoted_attribute(&post.tags.join(", ")),
+        title,
+        date,
+        date,
+        post.content_html
+    )
+}
+
+/// File name of a post in exports
+fn export_file_name(post: &ExportedPost, extension: &str) -> String {
+    format!("{}.{}", post.slug.replace(['/', '\\'], "-"), extension)
+}
+
+/// Zip of the posts as markdown files under `posts/`
+pub fn site_zip(posts: &[ExportedPost]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for post in posts {
+        zip.start_file(format!("posts/{}", export_file_name(post, "md")), options)?;
+        zip.write_all(to_markdown(post).as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Where the zip of a site export job is stored
+fn site_export_path(blog_id: i64, job_id: Uuid) -> PathBuf {
+    AttachmentConfig::global()
+        .storage_dir
+        .join("exports")
+        .join(blog_id.to_string())
+        .join(format!("{}.zip", job_id))
+}
+
+/// Builds the zip of a site export
+pub struct SiteExportJob {
+    db: DbRouter,
+}
+
+impl SiteExportJob {
+    pub fn new(db: DbRouter) -> Self {
+        Self { db }
+    }
+
+    async fn export(&self, job: &Job) -> Result<Value, JobError> {
+        let posts = published_posts(self.db.read()).await?;
+        let post_count = posts.len();
+
+        let data = tokio::task::spawn_blocking(move || site_zip(&posts))
+            .await
+            .map_err(|e| JobError::InternalError(e.to_string()))?
+            .map_err(|e| JobError::Failed(format!("Could not build zip: {}", e)))?;
+
+        let path = site_export_path(job.blog_id, job.id);
+        let write = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&path, &data).await
+        };
+        write
+            .await
+            .map_err(|e| JobError::Failed(format!("Could not store export: {}", e)))?;
+
+        info!(
+            "Exported {} posts of blog {} in job {}",
+            post_count, job.blog_id, job.id
+        );
+        Ok(json!({
+            "post_count": post_count,
+            "size_bytes": data.len(),
+            "download_url": format!("{}/admin/export/site/{}", API_V1_PREFIX, job.id),
+        }))
+    }
+}
+
+impl JobHandler for SiteExportJob {
+    fn kind(&self) -> &'static str {
+        SITE_EXPORT_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.export(job))
+    }
+}
+
+/// Every published post of the current blog, oldest first
+async fn published_posts(pool: &PgPool) -> Result<Vec<ExportedPost>, sqlx::Error> {
+    sqlx::query_as::<_, ExportedPost>(&format!(
+        "{} WHERE p.blog_id = $1 AND p.is_deleted = false AND p.is_draft = false \
+         GROUP BY p.id ORDER BY p.created_at",
+        EXPORT_SELECT
+    ))
+    .bind(current_blog_id())
+    .fetch_all(pool)
+    .await
+}
+
+fn error_response(status: StatusCode, error: &str, code: &str) -> Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+fn admin_only() -> Response {
+    error_response(
+        StatusCode::FORBIDDEN,
+        "Only admins can export the site",
+        "FORBIDDEN",
+    )
+}
+
+/// Export a post
+///
+/// Downloads a post as a markdown file with front matter (title, slug, tags and date) or as a
+/// standalone HTML page. Drafts included; only the author and admins can export a post.
+#[utoipa::path(
+    get,
+    path = "/api/posts/{id}/export",
+    params(
+        ("id" = i64, Path, description = "Post ID"),
+        ExportParams
+    ),
+    responses(
+        (status = 200, description = "The exported post", content_type = "text/markdown"),
+        (status = 400, description = "Unknown format", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 403, description = "Not the author or an admin", body = ErrorResponse),
+        (status = 404, description = "Post not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn export_post(
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(params): Query<ExportParams>,
+    State((db, _)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    let html = match params.format.as_deref() {
+        None | Some("markdown") => false,
+        Some("html") => true,
+        Some(_) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "Format must be markdown or html",
+                "INVALID_FORMAT",
+            )
+        }
+    };
+
+    let post = sqlx::query_as::<_, ExportedPost>(&format!(
+        "{} WHERE p.id = $1 AND p.blog_id = $2 AND p.is_deleted = false GROUP BY p.id",
+        EXPORT_SELECT
+    ))
+    .bind(id)
+    .bind(current_blog_id())
+    .fetch_optional(db.primary())
+    .await;
+
+    let post = match post {
+        Ok(Some(post)) => post,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Post not found", "NOT_FOUND"),
+        Err(e) => {
+            error!("Error exporting post {}: {}", id, e);
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to export post",
+                "INTERNAL_ERROR",
+            );
+        }
+    };
+
+    if post.user_id != user.user_id && user.role != Role::Admin {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Only the author can export this post",
+            "FORBIDDEN",
+        );
+    }
+
+    let (body, content_type, file_name) = if html {
+        (
+            to_html(&post),
+            "text/html; charset=utf-8",
+            export_file_name(&post, "html"),
+        )
+    } else {
+        (
+            to_markdown(&post),
+            "text/markdown; charset=utf-8",
+            export_file_name(&post, "md"),
+        )
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, content_disposition(&file_name)),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Start a site export (admin only)
+///
+/// Queues a background job that zips every published post of the blog as markdown with front
+/// matter. Poll the job at `/api/admin/jobs/{id}`; once it succeeded, its result links to the zip.
+#[utoipa::path(
+    post,
+    path = "/api/admin/export/site",
+    responses(
+        (status = 202, description = "Export queued", body = Job),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn start_site_export(
+    user: AuthUser,
+    Extension(jobs): Extension<Arc<JobService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_only();
+    }
+
+    match jobs
+        .enqueue(SITE_EXPORT_JOB, json!({}), Some(user.user_id))
+        .await
+    {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(e) => job_error_response(e),
+    }
+}
+
+/// Download a site export (admin only)
+#[utoipa::path(
+    get,
+    path = "/api/admin/export/site/{job_id}",
+    params(
+        ("job_id" = String, Path, description = "ID of the export job")
+    ),
+    responses(
+        (status = 200, description = "Zip of the blog's published posts", content_type = "application/zip"),
+        (status = 401, description = "Unauthorized", body = AuthErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "Export not found", body = ErrorResponse),
+        (status = 409, description = "Export has not succeeded (yet)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "posts"
+)]
+pub async fn download_site_export(
+    user: AuthUser,
+    Path(job_id): Path<Uuid>,
+    Extension(jobs): Extension<Arc<JobService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return admin_only();
+    }
+
+    let job = match jobs.get_job(job_id).await {
+        Ok(job) if job.kind == SITE_EXPORT_JOB => job,
+        Ok(_) | Err(JobError::NotFound) => {
+            return error_response(StatusCode::NOT_FOUND, "Export not found", "NOT_FOUND")
+        }
+        Err(e) => return job_error_response(e),
+    };
+
+    if job.status != JobStatus::Succeeded {
+        return error_response(
+            StatusCode::CONFLICT,
+            &format!("Export is {}", job.status.as_str()),
+            "EXPORT_NOT_READY",
+        );
+    }
+
+    match tokio::fs::read(site_export_path(job.blog_id, job.id)).await {
+        Ok(data) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "application/zip".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    content_disposition(&format!(
+                        "site-export-{}.zip",
+                        job.created_at.format("%Y-%m-%d")
+                    )),
+                ),
+            ],
+            data,
+        )
+            .into_response(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            error_response(StatusCode::NOT_FOUND, "Export not found", "NOT_FOUND")
+        }
+        Err(e) => {
+            error!("Error reading site export {}: {}", job.id, e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read export",
+                "INTERNAL_ERROR",
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn post() -> ExportedPost {
+        ExportedPost {
+            user_id: Uuid::nil(),
+            title: "Say \"hello\": a guide".to_string(),
+            slug: "say-hello".to_string(),
+            content: "# Hello\n\nWorld\n".to_string(),
+            content_html: "<h1>Hello</h1>".to_string(),
+            tags: vec!["rust".to_string(), "web dev".to_string()],
+            created_at: Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_markdown_front_matter() {
+        assert_eq!(
+            to_markdown(&post()),
+            "---\ntitle: \"Say \\\"hello\\\": a guide\"\nslug: \"say-hello\"\n\
+             tags: [\"rust\", \"web dev\"]\ndate: 2024-05-01T12:30:00Z\n---\n\n# Hello\n\nWorld\n"
+        );
+    }
+
+    #[test]
+    fn test_site_zip_contains_posts() {
+        let data = site_zip(&[post()]).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive.by_index(0).unwrap().name(), "posts/say-hello.md");
+    }
+}
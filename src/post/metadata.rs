@@ -0,0 +1,201 @@
+use jsonschema::JSONSchema;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use tracing::error;
+
+/// Largest metadata of a post, serialized
+pub const MAX_METADATA_BYTES: usize = 16 * 1024;
+/// Most `meta.` filters a listing may have
+pub const MAX_METADATA_FILTERS: usize = 5;
+
+const METADATA_FILTER_PREFIX: &str = "meta.";
+const MAX_FILTER_KEY_CHARS: usize = 64;
+const MAX_FILTER_VALUE_CHARS: usize = 256;
+// Validation errors reported for one write
+const MAX_REPORTED_ERRORS: usize = 5;
+
+/// The JSON Schema post metadata must follow, read once from the file named by
+/// `POST_METADATA_SCHEMA`. Without it any JSON object is accepted. A schema that cannot be
+/// read or compiled is logged and all metadata is refused until it is fixed, rather than
+/// letting unchecked metadata in.
+pub struct MetadataConfig {
+    schema: Option<Result<JSONSchema, String>>,
+}
+
+impl MetadataConfig {
+    pub fn from_env() -> Self {
+        let schema = std::env::var("POST_METADATA_SCHEMA").ok().map(|path| {
+            load_schema(&path).map_err(|e| {
+                error!("Post metadata schema {} is unusable: {}", path, e);
+                e
+            })
+        });
+        Self { schema }
+    }
+
+    /// Shared config, read on first use
+    pub fn global() -> &'static MetadataConfig {
+        static CONFIG: OnceLock<MetadataConfig> = OnceLock::new();
+        CONFIG.get_or_init(MetadataConfig::from_env)
+    }
+
+    /// Check metadata before it is stored: a JSON object of at most `MAX_METADATA_BYTES`,
+    /// valid against the schema when there is one. Errors describe what is wrong.
+    pub fn validate(&self, metadata: &Value) -> Result<(), String> {
+        if !metadata.is_object() {
+            return Err("metadata must be a JSON object".to_string());
+        }
+        if metadata.to_string().len() > MAX_METADATA_BYTES {
+            return Err(format!(
+                "metadata may not be larger than {} bytes",
+                MAX_METADATA_BYTES
+            ));
+        }
+
+        match &self.schema {
+            None => Ok(()),
+            Some(Err(_)) => Err("metadata cannot be checked: its schema is unusable".to_string()),
+            Some(Ok(schema)) => schema.validate(metadata).map_err(|errors| {
+                let errors: Vec<String> = errors
+                    .take(MAX_REPORTED_ERRORS)
+                    .map(|e| format!("metadata{}: {}", e.instance_path, e))
+                    .collect();
+                errors.join("; ")
+            }),
+        }
+    }
+}
+
+fn load_schema(path: &str) -> Result<JSONSchema, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let schema: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    JSONSchema::compile(&schema).map_err(|e| e.to_string())
+}
+
+/// A `meta.<key>=<value>` filter of post listings. Keys of nested objects are joined with
+/// dots, e.g. `meta.podcast.episode=12`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataFilter {
+    path: Vec<String>,
+    value: String,
+}
+
+impl MetadataFilter {
+    /// Metadata a matching post contains one of, for `@>`: the value as a string, and as a
+    /// number or boolean when it reads as one, since query strings cannot tell them apart
+    pub fn documents(&self) -> Vec<Value> {
+        let mut values = vec![Value::String(self.value.clone())];
+        let parsed = serde_json::from_str::<Value>(&self.value).ok();
+        if let Some(value @ (Value::Number(_) | Value::Bool(_))) = parsed {
+            values.push(value);
+        }
+
+        values
+            .into_iter()
+            .map(|value| {
+                self.path.iter().rev().fold(value, |value, key| {
+                    let mut object = Map::new();
+                    object.insert(key.clone(), value);
+                    Value::Object(object)
+                })
+            })
+            .collect()
+    }
+}
+
+/// The `meta.` filters among the query parameters of a listing, in key order
+pub fn parse_metadata_filters(
+    params: &HashMap<String, String>,
+) -> Result<Vec<MetadataFilter>, String> {
+    let mut filters: Vec<MetadataFilter> = Vec::new();
+    for (name, value) in params {
+        let Some(key) = name.strip_prefix(METADATA_FILTER_PREFIX) else {
+            continue;
+        };
+        let path: Vec<String> = key.split('.').map(str::to_string).collect();
+        let valid_key = key.chars().count() <= MAX_FILTER_KEY_CHARS
+            && path.iter().all(|segment| {
+                !segment.is_empty()
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            });
+        if !valid_key {
+            return Err(format!("Invalid metadata filter: {}", name));
+        }
+        if value.chars().count() > MAX_FILTER_VALUE_CHARS {
+            return Err(format!("Metadata filter value too long: {}", name));
+        }
+        filters.push(MetadataFilter {
+            path,
+            value: value.clone(),
+        });
+    }
+
+    if filters.len() > MAX_METADATA_FILTERS {
+        return Err(format!(
+            "At most {} metadata filters are allowed",
+            MAX_METADATA_FILTERS
+        ));
+    }
+    filters.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(filters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        let filters =
+            parse_metadata_filters(&params(&[("meta.podcast.episode", "12"), ("limit", "5")]))
+                .unwrap();
+        assert_eq!(filters.len(), 1);
+        assert_eq!(
+            filters[0].documents(),
+            vec![
+                json!({ "podcast": { "episode": "12" } }),
+                json!({ "podcast": { "episode": 12 } }),
+            ]
+        );
+
+        let filters = parse_metadata_filters(&params(&[("meta.host", "Ada")])).unwrap();
+        assert_eq!(filters[0].documents(), vec![json!({ "host": "Ada" })]);
+
+        assert!(parse_metadata_filters(&params(&[("meta.", "x")])).is_err());
+        assert!(parse_metadata_filters(&params(&[("meta.a..b", "x")])).is_err());
+        assert!(parse_metadata_filters(&params(&[("meta.a b", "x")])).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = MetadataConfig {
+            schema: Some(Ok(JSONSchema::compile(&json!({
+                "type": "object",
+                "properties": { "episode": { "type": "integer" } },
+            }))
+            .unwrap())),
+        };
+        assert!(config.validate(&json!({ "episode": 3 })).is_ok());
+        assert_eq!(
+            config.validate(&json!({ "episode": "three" })).unwrap_err(),
+            r#"metadata/episode: "three" is not of type "integer""#
+        );
+        assert!(config.validate(&json!([1, 2])).is_err());
+
+        let unchecked = MetadataConfig { schema: None };
+        assert!(unchecked.validate(&json!({ "anything": [1, 2] })).is_ok());
+        let oversized = json!({ "text": "x".repeat(MAX_METADATA_BYTES) });
+        assert!(unchecked.validate(&oversized).is_err());
+    }
+}
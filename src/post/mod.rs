@@ -1,5 +1,10 @@
 pub mod controller;
+pub mod duplicates;
+pub mod export;
+pub mod metadata;
 pub mod model;
+pub mod oembed;
+pub mod popularity;
 pub mod service;
 
 // Re-export types that should be accessible from outside the module
@@ -1,7 +1,10 @@
+use crate::media::model::{Attachment, CoverImage};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use sqlx::FromRow;
-use utoipa::ToSchema;
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone, ToSchema)]
@@ -11,19 +14,66 @@ pub struct Post {
     pub slug: String,
     pub content: String,
     pub content_html: String,
-    #[schema(value_type = UuidWrapper)]
     pub user_id: Uuid,
     pub views: i64,
     pub likes: i64,
     pub is_draft: bool,
     pub is_deleted: bool,
     pub cover_image_url: Option<String>,
-    #[schema(value_type = DateTimeWrapper)]
+    pub canonical_url: Option<String>,
+    pub required_tier_id: Option<i64>,
+    pub comments_locked: bool,
+    pub metadata: serde_json::Value,
     pub created_at: DateTime<Utc>,
-    #[schema(value_type = DateTimeWrapper)]
     pub updated_at: DateTime<Utc>,
 }
 
+/// A post with its author and tag names, as selected by the post service's single-query
+/// projection
+#[derive(Debug, FromRow)]
+pub struct PostRow {
+    #[sqlx(flatten)]
+    pub post: Post,
+    pub author: Json<UserBrief>,
+    pub tags: Json<Vec<String>>,
+}
+
+impl PostRow {
+    /// The response for the post, before gating and without the viewer's own fields
+    pub fn into_response(
+        self,
+        cover_image: Option<CoverImage>,
+        attachments: Vec<Attachment>,
+    ) -> PostResponse {
+        let post = self.post;
+        PostResponse {
+            id: post.id,
+            title: post.title,
+            slug: post.slug,
+            content: post.content,
+            content_html: post.content_html,
+            author: self.author.0,
+            tags: self.tags.0,
+            views: post.views,
+            likes: post.likes,
+            comment_count: 0,
+            liked_by_me: None,
+            bookmarked_by_me: None,
+            cover_image_url: post.cover_image_url,
+            cover_image,
+            canonical_url: post.canonical_url,
+            attachments,
+            is_draft: post.is_draft,
+            comments_locked: post.comments_locked,
+            requires_tier: None,
+            metadata: post.metadata,
+            duplicate_of: None,
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CreatePostRequest {
     pub title: String,
@@ -31,7 +81,15 @@ pub struct CreatePostRequest {
     pub content: String,
     pub tags: Vec<String>,
     pub cover_image_url: Option<String>,
+    /// Original location of cross-posted content, for search engines
+    pub canonical_url: Option<String>,
     pub is_draft: bool,
+    /// Name of the membership tier needed to read the full post; free if omitted
+    pub required_tier: Option<String>,
+    /// Custom JSON object for integrations, e.g. podcast episode info; checked against the
+    /// deployment's metadata schema when it has one
+    #[schema(value_type = Option<Object>, example = json!({"podcast": {"episode": 12}}))]
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -41,7 +99,14 @@ pub struct UpdatePostRequest {
     pub content: Option<String>,
     pub tags: Option<Vec<String>>,
     pub cover_image_url: Option<String>,
+    /// Original location of cross-posted content; an empty string removes it
+    pub canonical_url: Option<String>,
     pub is_draft: Option<bool>,
+    /// Name of the membership tier needed to read the full post; an empty string makes it free
+    pub required_tier: Option<String>,
+    /// Replaces the custom metadata; `{}` removes it
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -55,19 +120,87 @@ pub struct PostResponse {
     pub tags: Vec<String>,
     pub views: i64,
     pub likes: i64,
+    /// Number of visible comments
+    #[serde(default)]
+    pub comment_count: i64,
+    /// Whether the requesting user has liked the post; only present for authenticated requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub liked_by_me: Option<bool>,
+    /// Whether the requesting user has bookmarked the post; only present for authenticated requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bookmarked_by_me: Option<bool>,
     pub cover_image_url: Option<String>,
+    /// Resized variants and placeholder of an uploaded cover image; absent for covers set by URL
+    #[serde(default)]
+    pub cover_image: Option<CoverImage>,
+    /// Original location of cross-posted content; front-ends should emit it as the canonical link
+    #[serde(default)]
+    pub canonical_url: Option<String>,
+    /// Files attached to the post; only listed on single posts, and left out of paywalled previews
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
     pub is_draft: bool,
-    #[schema(value_type = DateTimeWrapper)]
+    /// Whether new comments are refused
+    #[serde(default)]
+    pub comments_locked: bool,
+    /// Tier needed to read the full post, set when `content` and `content_html` are only a preview
+    pub requires_tier: Option<String>,
+    /// Custom JSON object set by integrations
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+    /// Earlier post of the author's that this one looks like a copy of; only set in the
+    /// response creating the post
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<DuplicateWarning>,
     pub created_at: DateTime<Utc>,
-    #[schema(value_type = DateTimeWrapper)]
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+/// Warning that a new post looks like a duplicate of an earlier one by the same author
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DuplicateWarning {
+    #[schema(example = 42)]
+    pub post_id: i64,
+    #[schema(example = "Why Rust")]
+    pub title: String,
+    #[schema(example = "why-rust")]
+    pub slug: String,
+    #[schema(example = "Looks like a duplicate of \"Why Rust\"")]
+    pub message: String,
+}
+
+/// A new post that looks like a copy of another user's recent post
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DuplicatePostFlag {
+    #[schema(example = 7)]
+    pub id: i64,
+    #[schema(example = 123)]
+    pub post_id: i64,
+    #[schema(example = "cheap-watches")]
+    pub post_slug: String,
+    /// Author of the flagged post
+    pub user_id: Uuid,
+    #[schema(example = "spammer")]
+    pub username: String,
+    /// The post it looks like a copy of
+    #[schema(example = 42)]
+    pub duplicate_of: i64,
+    #[schema(example = "why-rust")]
+    pub duplicate_of_slug: String,
+    /// Bits the content fingerprints differ in, 0 for the same text
+    #[schema(example = 2)]
+    pub distance: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct UserBrief {
-    #[schema(value_type = UuidWrapper)]
     pub id: Uuid,
     pub name: String,
+    /// Uploaded avatar, or the user's Gravatar
+    #[serde(default)]
+    pub avatar_url: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
@@ -82,7 +215,153 @@ pub struct PostError {
     pub code: String,
 }
 
+/// Fields of a post in a list that `fields` can select; `excerpt` is derived from the content,
+/// so lists can leave the content out
+pub const POST_LIST_FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "slug",
+    "excerpt",
+    "content",
+    "content_html",
+    "author",
+    "tags",
+    "views",
+    "likes",
+    "comment_count",
+    "liked_by_me",
+    "bookmarked_by_me",
+    "cover_image_url",
+    "cover_image",
+    "canonical_url",
+    "attachments",
+    "is_draft",
+    "comments_locked",
+    "requires_tier",
+    "metadata",
+    "created_at",
+    "updated_at",
+];
+
+/// Fields of the author dashboard that `fields` can select
+pub const AUTHOR_POST_FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "slug",
+    "is_draft",
+    "views",
+    "likes",
+    "comments",
+    "popularity_score",
+    "created_at",
+    "updated_at",
+];
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct PopularPostsResponse {
     pub posts: Vec<PostResponse>,
 }
+
+/// Number of posts published in one month, for archive navigation
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ArchiveMonth {
+    #[schema(example = 2025)]
+    pub year: i32,
+    /// 1 to 12
+    #[schema(example = 3)]
+    pub month: i32,
+    pub count: i64,
+}
+
+/// Soft lock held by a user while editing a post
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EditLock {
+    pub post_id: i64,
+    pub user_id: Uuid,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EditLockParams {
+    /// Take over a lock held by someone else (admins only)
+    pub force: Option<bool>,
+}
+
+/// Which of an author's posts to list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PostStatusFilter {
+    Draft,
+    Published,
+    #[default]
+    All,
+}
+
+/// Order of an author's post listing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthorPostSort {
+    /// Most recently updated first
+    #[default]
+    Recent,
+    /// Most viewed first
+    MostViewed,
+    /// Highest popularity score first
+    Popular,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorPostsParams {
+    /// `draft`, `published` or `all` (default)
+    #[param(inline)]
+    pub status: Option<PostStatusFilter>,
+    /// `recent` (default), `most_viewed` or `popular`
+    #[param(inline)]
+    pub sort: Option<AuthorPostSort>,
+}
+
+/// One of the author's own posts with its engagement, for the author dashboard
+#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AuthorPostSummary {
+    pub id: i64,
+    pub title: String,
+    pub slug: String,
+    pub is_draft: bool,
+    /// Views recorded by analytics
+    pub views: i64,
+    /// Likes recorded by analytics
+    pub likes: i64,
+    /// Comments recorded by analytics
+    pub comments: i64,
+    /// Engagement decayed by age, the order of popular posts
+    pub popularity_score: f64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Link preview data of a post, for Open Graph and Twitter cards
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PostMeta {
+    pub title: String,
+    /// Plain text excerpt of the post
+    pub description: String,
+    pub image: Option<String>,
+    /// Display name of the author
+    pub author: String,
+    /// Avatar of the author
+    #[serde(default)]
+    pub author_avatar_url: Option<String>,
+    /// Canonical URL of the post: its `canonical_url`, or its page on this blog
+    pub url: String,
+    pub published_time: DateTime<Utc>,
+    pub modified_time: DateTime<Utc>,
+    /// Ready to render `<meta property=...>` tags, e.g. `og:title`
+    #[schema(value_type = Object)]
+    pub open_graph: BTreeMap<String, String>,
+    /// Ready to render `<meta name=...>` tags, e.g. `twitter:card`
+    #[schema(value_type = Object)]
+    pub twitter: BTreeMap<String, String>,
+}
@@ -0,0 +1,249 @@
+use crate::cache::redis::RedisCache;
+use crate::db::router::DbRouter;
+use crate::post::controller::ErrorResponse;
+use crate::post::model::PostMeta;
+use crate::post::service::{PostError as ServiceError, PostService};
+use crate::sitemap::{encode_path_segment, site_base_url};
+use crate::tenant::model::Blog;
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+/// Width of the embed when the consumer sets no `maxwidth`
+const DEFAULT_EMBED_WIDTH: u32 = 600;
+/// Height of the embed when the consumer sets no `maxheight`
+const DEFAULT_EMBED_HEIGHT: u32 = 200;
+/// Seconds consumers may cache an embed for
+const EMBED_CACHE_AGE: u64 = 3600;
+/// Longest URL accepted for embedding
+const MAX_EMBED_URL_LEN: usize = 2048;
+
+/// Query parameters of an oEmbed request
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OEmbedParams {
+    /// URL of the post page to embed
+    pub url: String,
+    /// Maximum width of the embed in pixels
+    pub maxwidth: Option<u32>,
+    /// Maximum height of the embed in pixels
+    pub maxheight: Option<u32>,
+    /// Response format; only `json` is supported
+    pub format: Option<String>,
+}
+
+/// oEmbed 1.0 payload of a post, of the `rich` type
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OEmbedResponse {
+    #[serde(rename = "type")]
+    #[schema(example = "rich")]
+    pub embed_type: String,
+    #[schema(example = "1.0")]
+    pub version: String,
+    pub title: String,
+    pub author_name: String,
+    pub author_url: String,
+    pub provider_name: String,
+    pub provider_url: String,
+    pub cache_age: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    /// Self-contained HTML snippet; all post data in it is escaped
+    pub html: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Slug or ID of the post a page URL points to, if it is a post page of this blog
+fn post_id_or_slug<'a>(url: &'a str, base_url: &str) -> Option<&'a str> {
+    let path = url.strip_prefix(base_url)?.strip_prefix("/posts/")?;
+    let path = path.split(['?', '#']).next()?.trim_end_matches('/');
+
+    if path.is_empty() || path.contains('/') {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+fn embed_html(meta: &PostMeta, provider_name: &str) -> String {
+    let image = meta
+        .image
+        .as_deref()
+        .map(|image| {
+            format!(
+                "<img src=\"{}\" alt=\"\">",
+                html_escape::encode_double_quoted_attribute(image)
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        "<blockquote class=\"blog-embed\">{}<a href=\"{}\">{}</a><p>{}</p><cite>{} · {}</cite></blockquote>",
+        image,
+        html_escape::encode_double_quoted_attribute(&meta.url),
+        html_escape::encode_text(&meta.title),
+        html_escape::encode_text(&meta.description),
+        html_escape::encode_text(&meta.author),
+        html_escape::encode_text(provider_name)
+    )
+}
+
+fn oembed_response(
+    meta: PostMeta,
+    blog: &Blog,
+    base_url: &str,
+    params: &OEmbedParams,
+) -> OEmbedResponse {
+    let html = embed_html(&meta, &blog.name);
+
+    OEmbedResponse {
+        embed_type: "rich".to_string(),
+        version: "1.0".to_string(),
+        author_url: format!("{}/authors/{}", base_url, encode_path_segment(&meta.author)),
+        title: meta.title,
+        author_name: meta.author,
+        provider_name: blog.name.clone(),
+        provider_url: base_url.to_string(),
+        cache_age: EMBED_CACHE_AGE,
+        thumbnail_url: meta.image,
+        html,
+        width: params
+            .maxwidth
+            .map_or(DEFAULT_EMBED_WIDTH, |max| max.min(DEFAULT_EMBED_WIDTH)),
+        height: params
+            .maxheight
+            .map_or(DEFAULT_EMBED_HEIGHT, |max| max.min(DEFAULT_EMBED_HEIGHT)),
+    }
+}
+
+fn error_response(status: StatusCode, error: &str, code: &str) -> Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+/// oEmbed provider endpoint
+///
+/// Returns an oEmbed payload for the URL of a published post on this blog, so other platforms
+/// can embed a preview of it. Public and cacheable.
+#[utoipa::path(
+    get,
+    path = "/api/oembed",
+    params(OEmbedParams),
+    responses(
+        (status = 200, description = "Embed data of the post", body = OEmbedResponse),
+        (status = 404, description = "URL is not a published post of this blog", body = ErrorResponse),
+        (status = 501, description = "Format not supported", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    tag = "posts"
+)]
+pub async fn get_oembed(
+    Query(params): Query<OEmbedParams>,
+    Extension(blog): Extension<Blog>,
+    State((db, redis_cache)): State<(DbRouter, Option<RedisCache>)>,
+) -> Response {
+    if params
+        .format
+        .as_deref()
+        .is_some_and(|format| format != "json")
+    {
+        return error_response(
+            StatusCode::NOT_IMPLEMENTED,
+            "Only the json format is supported",
+            "FORMAT_NOT_SUPPORTED",
+        );
+    }
+
+    let base_url = site_base_url(&blog);
+    let id_or_slug = match post_id_or_slug(&params.url, &base_url) {
+        Some(id_or_slug) if params.url.len() <= MAX_EMBED_URL_LEN => id_or_slug,
+        _ => {
+            return error_response(
+                StatusCode::NOT_FOUND,
+                "URL is not a post of this blog",
+                "NOT_FOUND",
+            )
+        }
+    };
+
+    let service = PostService::new(db, redis_cache);
+    match service.get_post_meta(id_or_slug, &base_url).await {
+        Ok(meta) => (
+            StatusCode::OK,
+            [(header::CACHE_CONTROL, "public, max-age=3600")],
+            Json(oembed_response(meta, &blog, &base_url, &params)),
+        )
+            .into_response(),
+        Err(ServiceError::NotFound) => {
+            error_response(StatusCode::NOT_FOUND, "Post not found", "NOT_FOUND")
+        }
+        Err(e) => {
+            error!("Error building oEmbed for {}: {:?}", params.url, e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to build embed",
+                "INTERNAL_ERROR",
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_id_or_slug() {
+        let base = "https://blog.example.com";
+        assert_eq!(
+            post_id_or_slug("https://blog.example.com/posts/hello-world?ref=x", base),
+            Some("hello-world")
+        );
+        assert_eq!(
+            post_id_or_slug("https://blog.example.com/posts/42/", base),
+            Some("42")
+        );
+        assert_eq!(
+            post_id_or_slug("https://evil.example.com/posts/hello-world", base),
+            None
+        );
+        assert_eq!(
+            post_id_or_slug("https://blog.example.com/tags/rust", base),
+            None
+        );
+    }
+
+    #[test]
+    fn test_embed_html_escapes_post_data() {
+        let meta = PostMeta {
+            title: "<script>alert(1)</script>".to_string(),
+            description: "Tom & Jerry".to_string(),
+            image: None,
+            author: "alice".to_string(),
+            author_avatar_url: None,
+            url: "https://blog.example.com/posts/x\"onmouseover=\"y".to_string(),
+            published_time: chrono::Utc::now(),
+            modified_time: chrono::Utc::now(),
+            open_graph: Default::default(),
+            twitter: Default::default(),
+        };
+        let html = embed_html(&meta, "Blog");
+
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("Tom &amp; Jerry"));
+        assert!(!html.contains("x\"onmouseover"));
+    }
+}
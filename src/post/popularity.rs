@@ -0,0 +1,125 @@
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Kind of the job that recomputes the popularity score of every post
+pub const POPULARITY_JOB: &str = "post_popularity";
+
+// How often scores are recomputed, and how often instances check whether a run is due
+const POPULARITY_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const POPULARITY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Posts scored per statement, so a large blog never holds locks for long
+const POPULARITY_BATCH_SIZE: i64 = 5000;
+
+/// Keeps `popularity_score` of every post up to date, the order popular posts are listed in
+/// and a signal of the popular recommendations.
+///
+/// A post scores its views, three points per like, four per reader who saved it to a reading
+/// list and five per approved comment, halved for every 30 days of its age so that posts which are read now outrank posts that were read
+/// long ago. Scores decay continuously, so all posts are rescored by a background job queued
+/// every fifteen minutes; new posts score 0 until the next run.
+pub struct PopularityService {
+    pool: PgPool,
+}
+
+impl PopularityService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Queue a rescoring every fifteen minutes
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POPULARITY_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(POPULARITY_JOB, json!({}), POPULARITY_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule post popularity scoring: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Recompute the popularity score of every post. Returns how many were scored.
+    pub async fn rescore_all(&self) -> Result<i64, JobError> {
+        let mut scored = 0;
+        let mut after_id = 0_i64;
+
+        loop {
+            let query = sqlx::query(
+                r#"
+                WITH batch AS (
+                    SELECT id FROM global.posts
+                    WHERE id > $1
+                    ORDER BY id
+                    LIMIT $2
+                ),
+                scores AS (
+                    SELECT
+                        p.id,
+                        (p.views + p.likes * 3 + r.saves * 4 + c.comments * 5)
+                            * POWER(0.5, EXTRACT(EPOCH FROM NOW() - p.created_at) / (30 * 86400))
+                            AS score
+                    FROM global.posts p
+                    JOIN batch b ON b.id = p.id
+                    CROSS JOIN LATERAL (
+                        SELECT COUNT(*) AS comments FROM global.comments
+                        WHERE post_id = p.id AND is_deleted = false
+                            AND moderation_status = 'approved'
+                    ) c
+                    CROSS JOIN LATERAL (
+                        SELECT COUNT(DISTINCT l.user_id) AS saves
+                        FROM global.reading_list_posts lp
+                        JOIN global.reading_lists l ON l.id = lp.list_id
+                        WHERE lp.post_id = p.id
+                    ) r
+                ),
+                updated AS (
+                    UPDATE global.posts p
+                    SET popularity_score = s.score
+                    FROM scores s
+                    WHERE p.id = s.id
+                )
+                SELECT MAX(id) AS last_id, COUNT(*) AS posts FROM batch
+                "#,
+            )
+            .bind(after_id)
+            .bind(POPULARITY_BATCH_SIZE)
+            .fetch_one(&self.pool);
+            let row = timed("posts.popularity", query).await?;
+
+            scored += row.get::<i64, _>("posts");
+            match row.get::<Option<i64>, _>("last_id") {
+                Some(last_id) => after_id = last_id,
+                None => break,
+            }
+        }
+
+        info!("Scored the popularity of {} posts", scored);
+        Ok(scored)
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let scored = self.rescore_all().await?;
+        Ok(json!({ "scored": scored }))
+    }
+}
+
+impl JobHandler for PopularityService {
+    fn kind(&self) -> &'static str {
+        POPULARITY_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
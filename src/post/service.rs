@@ -1,15 +1,91 @@
+use crate::analytics::anonymize::Anonymizer;
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
 use crate::cache::redis::RedisCache;
+use crate::cache::surrogate::{purge, SurrogateKey};
+use crate::comment::service::comment_count;
+use crate::consent::middleware::do_not_track;
+use crate::consent::service::ConsentService;
+use crate::db::instrument::timed;
+use crate::db::retry::with_retry;
+use crate::db::router::DbRouter;
+use crate::markdown;
+use crate::media::service::{post_attachments, post_cover_image, MediaService};
+use crate::membership::model::{MembershipError, MembershipTier, FREE_TIER_LEVEL};
+use crate::membership::service::MembershipService;
+use crate::pagination::Pagination;
+use crate::post::duplicates::check_content;
+use crate::post::metadata::{MetadataConfig, MetadataFilter};
 use crate::post::model::{
-    CreatePostRequest, Post, PostResponse, Tag, UpdatePostRequest, UserBrief,
+    ArchiveMonth, AuthorPostSort, AuthorPostSummary, CreatePostRequest, DuplicatePostFlag,
+    DuplicateWarning, EditLock, Post, PostMeta, PostResponse, PostRow, PostStatusFilter,
+    UpdatePostRequest,
 };
-use chrono::Utc;
-use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row};
-use std::collections::HashMap;
+use crate::reputation::service::queue_reputation_update;
+use crate::tag::service::set_post_tags;
+use crate::tenant::middleware::{current_blog_id, with_blog_id};
+use crate::websocket::notifications::Notification;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::future::try_join_all;
+use redis::AsyncCommands;
+use sqlx::Row;
+use std::collections::{BTreeMap, HashMap};
 use thiserror::Error;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+// How long an edit lock lasts without being refreshed
+const EDIT_LOCK_TTL_SECONDS: u64 = 120;
+// Times a release rereads a lock that changed while it was being released
+const EDIT_LOCK_RELEASE_ATTEMPTS: usize = 3;
+// Characters of a gated post shown to readers below its membership tier
+const PAYWALL_PREVIEW_CHARS: usize = 280;
+// Characters of the description in link previews; well within the paywall preview
+const META_DESCRIPTION_CHARS: usize = 200;
+// Cache variant of a post's link preview data
+const META_CACHE_VARIANT: &str = "meta";
+// Numbered copies tried for the title and slug of a duplicated post
+const MAX_COPY_NUMBER: u32 = 100;
+// Posts `p` with their author and tag names, one row each (see `PostRow`). Conditions and
+// ordering on the post must be qualified with `p.`, as users have some of the same columns.
+const POST_SELECT: &str = r#"
+    SELECT p.*,
+        json_build_object(
+            'id', u.id,
+            'name', u.username,
+            'avatar_url', global.user_avatar_url(u.avatar_url, u.email)
+        ) AS author,
+        COALESCE(post_tags.names, '[]'::json) AS tags
+    FROM global.posts p
+    JOIN global.users u ON u.id = p.user_id
+    LEFT JOIN LATERAL (
+        SELECT json_agg(t.name) AS names
+        FROM global.post_tags pt
+        JOIN global.tags t ON t.id = pt.tag_id
+        WHERE pt.post_id = p.id
+    ) post_tags ON true
+"#;
+
+/// Who is reading a post, as far as membership gating is concerned
+#[derive(Debug, Clone, Copy)]
+pub struct PostViewer {
+    pub user_id: Option<Uuid>,
+    pub tier_level: i32,
+    pub is_admin: bool,
+}
+
+impl PostViewer {
+    // Cached renderings are keyed by tier so a post rendered for one tier is never
+    // served to a reader of a lower one. Admins always get the full variant.
+    fn cache_variant(&self) -> String {
+        if self.is_admin {
+            "full".to_string()
+        } else {
+            format!("tier:{}", self.tier_level)
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum PostError {
     #[error("Database error: {0}")]
@@ -33,31 +109,131 @@ pub enum PostError {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 
+    #[error("Post is being edited by another user")]
+    EditLocked(EditLock),
+
+    #[error("Edit locks are unavailable without Redis")]
+    EditLocksUnavailable,
+
     #[error("Internal server error: {0}")]
     InternalError(String),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DataGenerationRequest {
-    pub refresh_existing: Option<bool>,
-    pub batch_size: Option<i64>,
+// Start and end of a month in UTC
+fn month_range(year: i32, month: u32) -> Result<(DateTime<Utc>, DateTime<Utc>), PostError> {
+    let invalid = || PostError::InvalidInput(format!("Invalid month {}-{:02}", year, month));
+    let start = NaiveDate::from_ymd_opt(year, month, 1).ok_or_else(invalid)?;
+    let end = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    }
+    .ok_or_else(invalid)?;
+
+    Ok((
+        start.and_hms_opt(0, 0, 0).ok_or_else(invalid)?.and_utc(),
+        end.and_hms_opt(0, 0, 0).ok_or_else(invalid)?.and_utc(),
+    ))
+}
+
+// Check that a canonical URL is an absolute http(s) URL
+fn validate_canonical_url(url: &str) -> Result<(), PostError> {
+    let host = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .and_then(|rest| rest.split(['/', '?', '#']).next())
+        .unwrap_or_default();
+
+    if host.is_empty() || url.len() > 2048 || url.contains(char::is_whitespace) {
+        return Err(PostError::InvalidInput(
+            "canonical_url must be an absolute http(s) URL".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Check custom metadata against the deployment's rules
+fn validate_metadata(metadata: &serde_json::Value) -> Result<(), PostError> {
+    MetadataConfig::global()
+        .validate(metadata)
+        .map_err(PostError::InvalidInput)
+}
+
+// Plain text description of a post for link previews: markdown markup is dropped,
+// whitespace collapsed and long content cut at a word boundary
+pub fn meta_description(content: &str) -> String {
+    let text = content
+        .chars()
+        .filter(|c| !matches!(c, '#' | '*' | '_' | '`' | '>' | '[' | ']' | '!'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if text.chars().count() <= META_DESCRIPTION_CHARS {
+        return text;
+    }
+    let mut description: String = text.chars().take(META_DESCRIPTION_CHARS).collect();
+    if let Some(end) = description.rfind(' ') {
+        description.truncate(end);
+    }
+    description.push('…');
+    description
+}
+
+/// Surrogate keys of a list of posts, purged when posts are published or removed, or when
+/// one of the posts, their authors or their tags change
+pub fn listing_keys(posts: &[PostResponse]) -> Vec<SurrogateKey> {
+    let mut keys = vec![SurrogateKey::Posts];
+    for post in posts {
+        keys.push(SurrogateKey::Post(post.id));
+        keys.push(SurrogateKey::User(post.author.id));
+        keys.extend(post.tags.iter().cloned().map(SurrogateKey::Tag));
+    }
+    keys
+}
+
+// Remember that a user read a post; the time of their first read is kept
+async fn record_read(pool: &sqlx::PgPool, user_id: Uuid, post_id: i64) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO global.post_reads (user_id, post_id)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id, post_id) DO NOTHING
+        "#,
+        user_id,
+        post_id
+    )
+    .execute(pool);
+    timed("posts.record_read", query).await?;
+    Ok(())
 }
 
 pub struct PostService {
-    pool: PgPool,
+    db: DbRouter,
     redis_cache: Option<RedisCache>,
+    anonymizer: Anonymizer,
+    consent: ConsentService,
+}
+
+/// HTML stored alongside a post's markdown
+pub fn render_markdown(content: &str) -> String {
+    markdown::render(content)
 }
 
 impl PostService {
-    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
-        Self { pool, redis_cache }
+    pub fn new(db: DbRouter, redis_cache: Option<RedisCache>) -> Self {
+        Self {
+            consent: ConsentService::new(db.primary().clone(), redis_cache.clone()),
+            anonymizer: Anonymizer::new(redis_cache.clone()),
+            db,
+            redis_cache,
+        }
     }
 
-    // Helper function to sanitize and render markdown
-    fn process_markdown(&self, content: &str) -> Result<String, PostError> {
-        // In a real implementation, we would sanitize and convert markdown to HTML
-        // For this example, we're just returning the content with a simple formatting
-        Ok(format!("<div class=\"markdown\">{}</div>", content))
+    // Helper function to sanitize and render markdown, with embedded gists fetched
+    async fn process_markdown(&self, content: &str) -> Result<String, PostError> {
+        Ok(markdown::render_with_embeds(content).await)
     }
 
     // Helper to check if slug exists
@@ -66,19 +242,20 @@ impl PostService {
         slug: &str,
         exclude_id: Option<i64>,
     ) -> Result<bool, PostError> {
-        let query = match exclude_id {
-            Some(id) => {
-                sqlx::query("SELECT EXISTS(SELECT 1 FROM global.posts WHERE slug = $1 AND id != $2 AND is_deleted = false)")
-                    .bind(slug)
-                    .bind(id)
-            },
-            None => {
-                sqlx::query("SELECT EXISTS(SELECT 1 FROM global.posts WHERE slug = $1 AND is_deleted = false)")
-                    .bind(slug)
-            }
-        };
-
-        let exists: bool = query.fetch_one(&self.pool).await?.get(0);
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM global.posts
+                WHERE global.match_key(slug) = global.match_key($1)
+                    AND ($2::BIGINT IS NULL OR id != $2) AND blog_id = $3 AND is_deleted = false
+            ) AS "exists!"
+            "#,
+            slug,
+            exclude_id,
+            current_blog_id()
+        )
+        .fetch_one(self.db.primary())
+        .await?;
 
         Ok(exists)
     }
@@ -89,29 +266,32 @@ impl PostService {
         title: &str,
         exclude_id: Option<i64>,
     ) -> Result<bool, PostError> {
-        let query = match exclude_id {
-            Some(id) => {
-                sqlx::query("SELECT EXISTS(SELECT 1 FROM global.posts WHERE title = $1 AND id != $2 AND is_deleted = false)")
-                    .bind(title)
-                    .bind(id)
-            },
-            None => {
-                sqlx::query("SELECT EXISTS(SELECT 1 FROM global.posts WHERE title = $1 AND is_deleted = false)")
-                    .bind(title)
-            }
-        };
-
-        let exists: bool = query.fetch_one(&self.pool).await?.get(0);
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM global.posts
+                WHERE title = $1 AND ($2::BIGINT IS NULL OR id != $2) AND blog_id = $3
+                    AND is_deleted = false
+            ) AS "exists!"
+            "#,
+            title,
+            exclude_id,
+            current_blog_id()
+        )
+        .fetch_one(self.db.primary())
+        .await?;
 
         Ok(exists)
     }
 
-    // Create a new post
+    /// Create a post, with a warning when it looks like a duplicate of a recent post of the
+    /// same author. One that looks like a copy of another user's post is flagged for
+    /// moderators instead.
     pub async fn create_post(
         &self,
         user_id: Uuid,
         post: CreatePostRequest,
-    ) -> Result<Post, PostError> {
+    ) -> Result<(Post, Option<DuplicateWarning>), PostError> {
         // Check if slug already exists
         if self.check_slug_exists(&post.slug, None).await? {
             return Err(PostError::SlugExists);
@@ -122,262 +302,741 @@ impl PostService {
             return Err(PostError::TitleExists);
         }
 
+        let canonical_url = post
+            .canonical_url
+            .as_deref()
+            .map(str::trim)
+            .filter(|url| !url.is_empty());
+        if let Some(url) = canonical_url {
+            validate_canonical_url(url)?;
+        }
+        let metadata = post.metadata.unwrap_or_else(|| serde_json::json!({}));
+        validate_metadata(&metadata)?;
+
         // Process markdown content
-        let content_html = self.process_markdown(&post.content)?;
+        let content_html = self.process_markdown(&post.content).await?;
+
+        // Resolve the membership tier the post is gated behind, if any
+        let required_tier_id = match &post.required_tier {
+            Some(name) => self.resolve_tier_name(name).await?,
+            None => None,
+        };
 
         // Start transaction
-        let mut tx = self.pool.begin().await?;
+        let mut tx = self.db.primary().begin().await?;
 
         // Insert post
-        let post_result = sqlx::query_as::<_, Post>(
+        let post_result = sqlx::query_as!(
+            Post,
             r#"
             INSERT INTO global.posts (
-                title, slug, content, content_html, user_id, views, likes, 
-                is_draft, is_deleted, cover_image_url, created_at, updated_at
-            ) 
-            VALUES ($1, $2, $3, $4, $5, 0, 0, $6, false, $7, $8, $8)
-            RETURNING *
+                title, slug, content, content_html, user_id, views, likes,
+                is_draft, is_deleted, cover_image_url, required_tier_id, created_at, updated_at,
+                blog_id, canonical_url, metadata
+            )
+            VALUES ($1, $2, $3, $4, $5, 0, 0, $6, false, $7, $8, $9, $9, $10, $11, $12)
+            RETURNING id, title, slug, content, content_html, user_id,
+                views::BIGINT AS "views!", likes::BIGINT AS "likes!", is_draft, is_deleted,
+                cover_image_url, canonical_url, required_tier_id, comments_locked, metadata,
+                created_at, updated_at
             "#,
+            post.title,
+            post.slug,
+            post.content,
+            content_html,
+            user_id,
+            post.is_draft,
+            post.cover_image_url,
+            required_tier_id,
+            Utc::now(),
+            current_blog_id(),
+            canonical_url,
+            metadata
         )
-        .bind(&post.title)
-        .bind(&post.slug)
-        .bind(&post.content)
-        .bind(&content_html)
-        .bind(user_id)
-        .bind(post.is_draft)
-        .bind(post.cover_image_url)
-        .bind(Utc::now())
         .fetch_one(&mut *tx)
         .await?;
 
         // Insert tags
-        for tag_name in &post.tags {
-            // Upsert tag
-            let tag_id: i64 = sqlx::query(
-                r#"
-                INSERT INTO global.tags (name) 
-                VALUES ($1) 
-                ON CONFLICT (name) DO UPDATE SET name = $1
-                RETURNING id
-                "#,
-            )
-            .bind(tag_name)
-            .fetch_one(&mut *tx)
+        set_post_tags(&mut tx, post_result.id, &post.tags).await?;
+
+        let duplicate_warning = check_content(&mut tx, post_result.id, user_id, &post.content)
             .await?
-            .get(0);
-
-            // Associate tag with post
-            sqlx::query(
-                r#"
-                INSERT INTO global.post_tags (post_id, tag_id)
-                VALUES ($1, $2)
-                "#,
-            )
-            .bind(post_result.id)
-            .bind(tag_id)
-            .execute(&mut *tx)
-            .await?;
-        }
+            .map(|duplicate| DuplicateWarning {
+                message: format!("Looks like a duplicate of \"{}\"", duplicate.title),
+                post_id: duplicate.id,
+                title: duplicate.title,
+                slug: duplicate.slug,
+            });
 
         // Commit transaction
         tx.commit().await?;
 
-        // Invalidate caches
-        if let Some(cache) = &self.redis_cache {
-            // This is a new post, so we only need to invalidate popular posts cache
-            let _ = cache.invalidate_popular_posts().await;
+        // This is a new post, so only the post lists change
+        if !post_result.is_draft {
+            purge(self.redis_cache.as_ref(), &[SurrogateKey::Posts]).await;
+        }
+
+        if !post_result.is_draft {
+            queue_reputation_update(self.db.primary(), &[user_id]).await;
         }
 
         info!("Created post with ID: {}", post_result.id);
-        Ok(post_result)
+        Ok((post_result, duplicate_warning))
+    }
+
+    // Title and slug of the nth copy of a post: "Title (copy)" and "slug-copy", then
+    // "Title (copy 2)" and "slug-copy-2"
+    fn copy_names(title: &str, slug: &str, n: u32) -> (String, String) {
+        if n == 1 {
+            (format!("{} (copy)", title), format!("{}-copy", slug))
+        } else {
+            (
+                format!("{} (copy {})", title, n),
+                format!("{}-copy-{}", slug, n),
+            )
+        }
+    }
+
+    /// Copy a post into a new draft owned by `user`, with its content, tags, metadata and cover
+    /// image under a title and slug derived from the original. The user must be able to read
+    /// the whole post: drafts of others are not found, and posts above their membership tier
+    /// are refused.
+    pub async fn duplicate_post(&self, source_id: i64, user: &AuthUser) -> Result<Post, PostError> {
+        let source = sqlx::query_as!(
+            Post,
+            r#"
+            SELECT id, title, slug, content, content_html, user_id,
+                views::BIGINT AS "views!", likes::BIGINT AS "likes!", is_draft, is_deleted,
+                cover_image_url, canonical_url, required_tier_id, comments_locked, metadata,
+                created_at, updated_at
+            FROM global.posts
+            WHERE id = $1 AND blog_id = $2 AND is_deleted = false
+            "#,
+            source_id,
+            current_blog_id()
+        )
+        .fetch_optional(self.db.primary())
+        .await?
+        .ok_or(PostError::NotFound)?;
+
+        let viewer = self.viewer(Some(user)).await?;
+        let privileged = viewer.is_admin || source.user_id == user.user_id;
+        if source.is_draft && !privileged {
+            return Err(PostError::NotFound);
+        }
+        if !privileged {
+            let required_tier = self.get_required_tier(source.required_tier_id).await?;
+            if let Some(tier) = required_tier.filter(|tier| viewer.tier_level < tier.level) {
+                info!(
+                    "User {} cannot duplicate post {} below the {} tier",
+                    user.user_id, source_id, tier.name
+                );
+                return Err(PostError::Unauthorized);
+            }
+        }
+
+        let mut names = None;
+        for n in 1..=MAX_COPY_NUMBER {
+            let (title, slug) = Self::copy_names(&source.title, &source.slug, n);
+            if !self.check_slug_exists(&slug, None).await?
+                && !self.check_title_exists(&title, None).await?
+            {
+                names = Some((title, slug));
+                break;
+            }
+        }
+        let (title, slug) = names.ok_or(PostError::SlugExists)?;
+
+        // An uploaded cover belongs to its post, so it is copied below; a cover set by URL is
+        // simply shared
+        let uploaded_cover = post_cover_image(
+            self.db.primary(),
+            source.id,
+            source.cover_image_url.as_deref(),
+        )
+        .await?
+        .is_some();
+        let cover_image_url = if uploaded_cover {
+            None
+        } else {
+            source.cover_image_url.clone()
+        };
+
+        let mut tx = self.db.primary().begin().await?;
+
+        // The rendered HTML is copied too, so embeds are not fetched again
+        let mut copy = sqlx::query_as!(
+            Post,
+            r#"
+            INSERT INTO global.posts (
+                title, slug, content, content_html, user_id, views, likes,
+                is_draft, is_deleted, cover_image_url, created_at, updated_at, blog_id, metadata
+            )
+            VALUES ($1, $2, $3, $4, $5, 0, 0, true, false, $6, $7, $7, $8, $9)
+            RETURNING id, title, slug, content, content_html, user_id,
+                views::BIGINT AS "views!", likes::BIGINT AS "likes!", is_draft, is_deleted,
+                cover_image_url, canonical_url, required_tier_id, comments_locked, metadata,
+                created_at, updated_at
+            "#,
+            title,
+            slug,
+            source.content,
+            source.content_html,
+            user.user_id,
+            cover_image_url,
+            Utc::now(),
+            current_blog_id(),
+            source.metadata
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO global.post_tags (post_id, tag_id)
+            SELECT $1, tag_id FROM global.post_tags WHERE post_id = $2
+            "#,
+            copy.id,
+            source.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // A copy of another user's post is flagged like any other
+        check_content(&mut tx, copy.id, user.user_id, &copy.content).await?;
+
+        tx.commit().await?;
+
+        // The draft is still useful without its cover, so a failed copy is only logged
+        if uploaded_cover {
+            match MediaService::new(self.db.clone(), self.redis_cache.clone())
+                .copy_cover_image(
+                    source.id,
+                    source.cover_image_url.as_deref(),
+                    copy.id,
+                    user.user_id,
+                )
+                .await
+            {
+                Ok(Some(cover)) => copy.cover_image_url = Some(cover.url),
+                Ok(None) => {}
+                Err(e) => error!("Failed to copy cover image of post {}: {}", source.id, e),
+            }
+        }
+
+        info!(
+            "Duplicated post {} into draft {} for user {}",
+            source.id, copy.id, user.user_id
+        );
+        Ok(copy)
+    }
+
+    // Resolve the membership level of the requesting user
+    pub async fn viewer(&self, user: Option<&AuthUser>) -> Result<PostViewer, PostError> {
+        let user = match user {
+            Some(user) => user,
+            None => {
+                return Ok(PostViewer {
+                    user_id: None,
+                    tier_level: FREE_TIER_LEVEL,
+                    is_admin: false,
+                })
+            }
+        };
+
+        let tier_level = MembershipService::new(self.db.primary().clone())
+            .get_user_tier_level(user.user_id)
+            .await
+            .map_err(|e| PostError::InternalError(e.to_string()))?;
+
+        Ok(PostViewer {
+            user_id: Some(user.user_id),
+            tier_level,
+            is_admin: user.role == Role::Admin,
+        })
+    }
+
+    // Use a cached rendering unless it is a preview of the viewer's own post
+    fn usable_cached_post(&self, cached_post: &str, viewer: &PostViewer) -> Option<PostResponse> {
+        match serde_json::from_str::<PostResponse>(cached_post) {
+            Ok(post) if post.requires_tier.is_some() && viewer.user_id == Some(post.author.id) => {
+                None
+            }
+            Ok(post) => Some(post),
+            Err(e) => {
+                error!("Error deserializing cached post: {}", e);
+                // Continue to DB retrieval if cache deserialization fails
+                None
+            }
+        }
+    }
+
+    // Log a read of a post to the post views stream, which the live analytics dashboard
+    // follows, and to the reader's read posts. Reads served from cache count too. Readers are
+    // anonymized like recorded interactions, so read posts are only kept while analytics keep
+    // user IDs, and reads by readers who opted out of tracking are not logged.
+    fn log_view(&self, post_id: i64, viewer: &PostViewer) {
+        if do_not_track() {
+            return;
+        }
+
+        let pool = self.db.primary().clone();
+        let cache = self.redis_cache.clone();
+        let anonymizer = self.anonymizer.clone();
+        let consent = self.consent.clone();
+        let user_id = viewer.user_id;
+
+        tokio::spawn(with_blog_id(current_blog_id(), async move {
+            if !consent.allows_tracking(user_id).await {
+                return;
+            }
+
+            if let (Some(reader_id), true) = (user_id, anonymizer.keeps_user_ids()) {
+                if let Err(e) = record_read(&pool, reader_id, post_id).await {
+                    error!("Failed to record read of post {}: {}", post_id, e);
+                }
+            }
+
+            if let Some(cache) = cache {
+                let user_id = anonymizer.user_id(user_id).await;
+                // Convert timestamp to a hash of the IP address
+                let ip_hash = Some(format!("timestamp-{}", chrono::Utc::now().timestamp()));
+
+                if let Err(e) = cache.log_post_view(post_id, user_id, ip_hash).await {
+                    error!("Failed to log post view: {}", e);
+                }
+            }
+        }));
     }
 
     // Get post by ID
-    pub async fn get_post_by_id(&self, id: i64) -> Result<PostResponse, PostError> {
+    pub async fn get_post_by_id(
+        &self,
+        id: i64,
+        viewer: &PostViewer,
+    ) -> Result<PostResponse, PostError> {
         // Try to get from cache first
         if let Some(cache) = &self.redis_cache {
-            if let Ok(Some(cached_post)) = cache.get_post_by_id(id).await {
-                info!("Retrieved post with ID: {} from cache", id);
-                // Deserialize and return
-                return match serde_json::from_str(&cached_post) {
-                    Ok(post) => Ok(post),
-                    Err(e) => {
-                        error!("Error deserializing cached post: {}", e);
-                        // Continue to DB retrieval if cache deserialization fails
-                        self.get_post_from_db(id).await
-                    }
-                };
+            if let Ok(Some(cached_post)) = cache.get_post_by_id(id, &viewer.cache_variant()).await {
+                if let Some(mut post) = self.usable_cached_post(&cached_post, viewer) {
+                    info!("Retrieved post with ID: {} from cache", id);
+                    self.fill_live_fields(std::slice::from_mut(&mut post), viewer)
+                        .await?;
+                    self.log_view(post.id, viewer);
+                    return Ok(post);
+                }
             }
         }
 
         // Not in cache or cache error, get from DB
-        self.get_post_from_db(id).await
+        let mut post = with_retry("posts.by_id", || self.get_post_from_db(id, viewer)).await?;
+        self.fill_live_fields(std::slice::from_mut(&mut post), viewer)
+            .await?;
+        self.log_view(post.id, viewer);
+        Ok(post)
     }
 
     // Get post by slug
-    pub async fn get_post_by_slug(&self, slug: &str) -> Result<PostResponse, PostError> {
+    pub async fn get_post_by_slug(
+        &self,
+        slug: &str,
+        viewer: &PostViewer,
+    ) -> Result<PostResponse, PostError> {
         // Try to get from cache first
         if let Some(cache) = &self.redis_cache {
-            if let Ok(Some(cached_post)) = cache.get_post_by_slug(slug).await {
-                info!("Retrieved post with slug: {} from cache", slug);
-                // Deserialize and return
-                return match serde_json::from_str(&cached_post) {
-                    Ok(post) => Ok(post),
-                    Err(e) => {
-                        error!("Error deserializing cached post: {}", e);
-                        // Continue to DB retrieval if cache deserialization fails
-                        self.get_post_from_db_by_slug(slug).await
-                    }
-                };
+            if let Ok(Some(cached_post)) =
+                cache.get_post_by_slug(slug, &viewer.cache_variant()).await
+            {
+                if let Some(mut post) = self.usable_cached_post(&cached_post, viewer) {
+                    info!("Retrieved post with slug: {} from cache", slug);
+                    self.fill_live_fields(std::slice::from_mut(&mut post), viewer)
+                        .await?;
+                    self.log_view(post.id, viewer);
+                    return Ok(post);
+                }
             }
         }
 
         // Not in cache or cache error, get from DB
-        self.get_post_from_db_by_slug(slug).await
+        let mut post = with_retry("posts.by_slug", || {
+            self.get_post_from_db_by_slug(slug, viewer)
+        })
+        .await?;
+        self.fill_live_fields(std::slice::from_mut(&mut post), viewer)
+            .await?;
+        self.log_view(post.id, viewer);
+        Ok(post)
     }
 
-    // Helper to get post from DB by ID
-    async fn get_post_from_db(&self, id: i64) -> Result<PostResponse, PostError> {
-        // Get post
-        let post = sqlx::query_as::<_, Post>(
+    // Get the link preview data of a published post. Nothing here depends on the reader, so
+    // it is cached next to the post's renderings and invalidated with them.
+    pub async fn get_post_meta(
+        &self,
+        id_or_slug: &str,
+        base_url: &str,
+    ) -> Result<PostMeta, PostError> {
+        let id = id_or_slug.parse::<i64>().ok();
+
+        if let Some(cache) = &self.redis_cache {
+            let cached = match id {
+                Some(id) => cache.get_post_by_id(id, META_CACHE_VARIANT).await,
+                None => cache.get_post_by_slug(id_or_slug, META_CACHE_VARIANT).await,
+            };
+            if let Ok(Some(cached)) = cached {
+                match serde_json::from_str::<PostMeta>(&cached) {
+                    Ok(meta) => return Ok(meta),
+                    Err(e) => error!("Error deserializing cached post meta: {}", e),
+                }
+            }
+        }
+
+        // Unlike reading the post, this does not count as a view
+        let row = sqlx::query!(
             r#"
-            SELECT * FROM global.posts
-            WHERE id = $1 AND is_deleted = false
+            SELECT p.id, p.title, p.slug, p.content, p.cover_image_url, p.canonical_url,
+                p.created_at, p.updated_at, u.username,
+                global.user_avatar_url(u.avatar_url, u.email) AS author_avatar_url
+            FROM global.posts p
+            JOIN global.users u ON u.id = p.user_id
+            WHERE (p.id = $1 OR ($1 IS NULL AND global.match_key(p.slug) = global.match_key($2)))
+                AND p.blog_id = $3 AND p.is_draft = false AND p.is_deleted = false
             "#,
+            id,
+            id_or_slug,
+            current_blog_id()
         )
-        .bind(id)
-        .fetch_optional(&self.pool)
+        .fetch_optional(self.db.read())
         .await?
         .ok_or(PostError::NotFound)?;
 
-        // Get author info
-        let author = sqlx::query_as::<_, UserBrief>(
+        let title = row.title;
+        let slug = row.slug;
+        let description = meta_description(&row.content);
+        let image = row.cover_image_url;
+        let author = row.username;
+        let url = row
+            .canonical_url
+            .unwrap_or_else(|| format!("{}/posts/{}", base_url, slug));
+        let published_time = row.created_at;
+        let modified_time = row.updated_at;
+
+        let mut open_graph = BTreeMap::new();
+        open_graph.insert("og:type".to_string(), "article".to_string());
+        open_graph.insert("og:title".to_string(), title.clone());
+        open_graph.insert("og:description".to_string(), description.clone());
+        open_graph.insert("og:url".to_string(), url.clone());
+        open_graph.insert("article:author".to_string(), author.clone());
+        open_graph.insert(
+            "article:published_time".to_string(),
+            published_time.to_rfc3339(),
+        );
+        open_graph.insert(
+            "article:modified_time".to_string(),
+            modified_time.to_rfc3339(),
+        );
+
+        let mut twitter = BTreeMap::new();
+        twitter.insert("twitter:title".to_string(), title.clone());
+        twitter.insert("twitter:description".to_string(), description.clone());
+        if let Some(image) = &image {
+            open_graph.insert("og:image".to_string(), image.clone());
+            twitter.insert("twitter:image".to_string(), image.clone());
+            twitter.insert(
+                "twitter:card".to_string(),
+                "summary_large_image".to_string(),
+            );
+        } else {
+            twitter.insert("twitter:card".to_string(), "summary".to_string());
+        }
+
+        let meta = PostMeta {
+            title,
+            description,
+            image,
+            author,
+            author_avatar_url: row.author_avatar_url,
+            url,
+            published_time,
+            modified_time,
+            open_graph,
+            twitter,
+        };
+
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(json_data) = serde_json::to_string(&meta) {
+                let post_id = row.id;
+                let _ = cache
+                    .cache_post_by_id(post_id, META_CACHE_VARIANT, &json_data)
+                    .await;
+                let _ = cache
+                    .cache_post_by_slug(post_id, &slug, META_CACHE_VARIANT, &json_data)
+                    .await;
+            }
+        }
+
+        Ok(meta)
+    }
+
+    // Set the fields that are never part of a cached post: comment counts, which change far
+    // more often than the posts themselves, and the viewer's own likes and bookmarks
+    async fn fill_live_fields(
+        &self,
+        posts: &mut [PostResponse],
+        viewer: &PostViewer,
+    ) -> Result<(), PostError> {
+        self.fill_viewer_interactions(posts, viewer).await?;
+
+        for post in posts {
+            post.comment_count = comment_count(self.db.read(), self.redis_cache.as_ref(), post.id)
+                .await
+                .map_err(|e| PostError::InternalError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    // Mark which posts an authenticated viewer has liked or bookmarked, with one query for all
+    // of them. Read from the primary so users see their own interactions straight away.
+    async fn fill_viewer_interactions(
+        &self,
+        posts: &mut [PostResponse],
+        viewer: &PostViewer,
+    ) -> Result<(), PostError> {
+        let user_id = match viewer.user_id {
+            Some(user_id) if !posts.is_empty() => user_id,
+            _ => return Ok(()),
+        };
+
+        let post_ids: Vec<i64> = posts.iter().map(|post| post.id).collect();
+        let rows = sqlx::query!(
             r#"
-            SELECT id, username as name FROM global.users
-            WHERE id = $1
+            SELECT p.id AS "post_id!",
+                EXISTS (
+                    SELECT 1 FROM global.post_likes l
+                    WHERE l.post_id = p.id AND l.user_id = $1
+                ) AS "liked!",
+                EXISTS (
+                    SELECT 1 FROM global.user_interactions i
+                    WHERE i.post_id = p.id AND i.user_id = $1 AND i.interaction_type = 'bookmark'
+                ) AS "bookmarked!"
+            FROM UNNEST($2::BIGINT[]) AS p(id)
             "#,
+            user_id,
+            &post_ids
         )
-        .bind(post.user_id)
-        .fetch_one(&self.pool)
+        .fetch_all(self.db.primary())
         .await?;
 
-        // Get tags
-        let tags = sqlx::query_as::<_, Tag>(
+        let interactions: HashMap<i64, (bool, bool)> = rows
+            .into_iter()
+            .map(|row| (row.post_id, (row.liked, row.bookmarked)))
+            .collect();
+
+        for post in posts {
+            let (liked, bookmarked) = interactions.get(&post.id).copied().unwrap_or_default();
+            post.liked_by_me = Some(liked);
+            post.bookmarked_by_me = Some(bookmarked);
+        }
+        Ok(())
+    }
+
+    // Helper to get the tier a post requires, if any
+    async fn get_required_tier(
+        &self,
+        tier_id: Option<i64>,
+    ) -> Result<Option<MembershipTier>, PostError> {
+        match tier_id {
+            Some(tier_id) => MembershipService::new(self.db.primary().clone())
+                .get_tier(tier_id)
+                .await
+                .map_err(|e| PostError::InternalError(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    // Helper to turn a tier name from a request into a tier ID; an empty name means free
+    async fn resolve_tier_name(&self, name: &str) -> Result<Option<i64>, PostError> {
+        if name.trim().is_empty() {
+            return Ok(None);
+        }
+
+        MembershipService::new(self.db.primary().clone())
+            .get_tier_by_name(name.trim())
+            .await
+            .map(|tier| Some(tier.id))
+            .map_err(|e| match e {
+                MembershipError::TierNotFound(name) => {
+                    PostError::InvalidInput(format!("Unknown membership tier: {}", name))
+                }
+                e => PostError::InternalError(e.to_string()),
+            })
+    }
+
+    // Replace the body of a post with a short preview for readers below its tier
+    fn apply_paywall(
+        &self,
+        post: &mut PostResponse,
+        tier: &MembershipTier,
+    ) -> Result<(), PostError> {
+        let mut preview: String = post.content.chars().take(PAYWALL_PREVIEW_CHARS).collect();
+        if preview.len() < post.content.len() {
+            preview.push('…');
+        }
+
+        // The preview is rendered on every read, so gists in it are not fetched
+        post.content_html = render_markdown(&preview);
+        post.content = preview;
+        post.attachments.clear();
+        post.requires_tier = Some(tier.name.clone());
+        Ok(())
+    }
+
+    // Helper to get post from DB by ID
+    async fn get_post_from_db(
+        &self,
+        id: i64,
+        viewer: &PostViewer,
+    ) -> Result<PostResponse, PostError> {
+        let row = sqlx::query_as::<_, PostRow>(&format!(
+            "{} WHERE p.id = $1 AND p.blog_id = $2 AND p.is_deleted = false",
+            POST_SELECT
+        ))
+        .bind(id)
+        .bind(current_blog_id())
+        .fetch_optional(self.db.primary())
+        .await?
+        .ok_or(PostError::NotFound)?;
+
+        self.post_response(row, viewer).await
+    }
+
+    // Helper to get post from DB by slug
+    async fn get_post_from_db_by_slug(
+        &self,
+        slug: &str,
+        viewer: &PostViewer,
+    ) -> Result<PostResponse, PostError> {
+        let row = sqlx::query_as::<_, PostRow>(&format!(
             r#"
-            SELECT t.id, t.name FROM global.tags t
-            JOIN global.post_tags pt ON pt.tag_id = t.id
-            WHERE pt.post_id = $1
+            {}
+            WHERE global.match_key(p.slug) = global.match_key($1) AND p.blog_id = $2
+                AND p.is_deleted = false
             "#,
-        )
-        .bind(post.id)
-        .fetch_all(&self.pool)
-        .await?;
+            POST_SELECT
+        ))
+        .bind(slug)
+        .bind(current_blog_id())
+        .fetch_optional(self.db.primary())
+        .await?
+        .ok_or(PostError::NotFound)?;
 
-        // Construct response
-        let post_response = PostResponse {
-            id: post.id,
-            title: post.title,
-            slug: post.slug,
-            content: post.content,
-            content_html: post.content_html,
-            author,
-            tags: tags.into_iter().map(|t| t.name).collect(),
-            views: post.views,
-            likes: post.likes,
-            cover_image_url: post.cover_image_url,
-            is_draft: post.is_draft,
-            created_at: post.created_at,
-            updated_at: post.updated_at,
+        self.post_response(row, viewer).await
+    }
+
+    // Build the response for a single post with its attachments, gate it for the viewer,
+    // cache it and count the view
+    async fn post_response(
+        &self,
+        row: PostRow,
+        viewer: &PostViewer,
+    ) -> Result<PostResponse, PostError> {
+        let id = row.post.id;
+        let author_id = row.post.user_id;
+        let required_tier_id = row.post.required_tier_id;
+
+        let attachments = post_attachments(self.db.primary(), id).await?;
+        let cover_image =
+            post_cover_image(self.db.primary(), id, row.post.cover_image_url.as_deref()).await?;
+        let mut post_response = row.into_response(cover_image, attachments);
+
+        // Gate premium content. The author and admins always read the full post, but that
+        // rendering is cached as the "full" variant so it never leaks to a tier's cache entry.
+        let required_tier = self.get_required_tier(required_tier_id).await?;
+        let entitled = required_tier
+            .as_ref()
+            .is_none_or(|tier| viewer.tier_level >= tier.level);
+        let privileged = viewer.is_admin || viewer.user_id == Some(author_id);
+        let variant = if !entitled && privileged {
+            "full".to_string()
+        } else {
+            viewer.cache_variant()
         };
+        if !entitled && !privileged {
+            if let Some(tier) = &required_tier {
+                self.apply_paywall(&mut post_response, tier)?;
+            }
+        }
 
         // Cache the result
         if let Some(cache) = &self.redis_cache {
             // Serialize and cache
             if let Ok(json_data) = serde_json::to_string(&post_response) {
-                let _ = cache.cache_post_by_id(id, &json_data).await;
+                let _ = cache.cache_post_by_id(id, &variant, &json_data).await;
                 let _ = cache
-                    .cache_post_by_slug(&post_response.slug, &json_data)
+                    .cache_post_by_slug(id, &post_response.slug, &variant, &json_data)
                     .await;
 
                 // Increment views asynchronously
                 let _ = cache.increment_post_views(id).await;
-
-                // Log the view in Redis
-                if let Some(ref cache) = self.redis_cache {
-                    // Log view asynchronously
-                    let cache_clone = cache.clone();
-                    let post_id = id.clone();
-
-                    tokio::spawn(async move {
-                        // Convert timestamp to a hash of the IP address
-                        let ip_hash = Some(format!("timestamp-{}", chrono::Utc::now().timestamp()));
-
-                        if let Err(e) = cache_clone.log_post_view(post_id, None, ip_hash).await {
-                            error!("Failed to log post view: {}", e);
-                        }
-                    });
-                }
             }
         }
 
         // Update view count in database asynchronously
-        let pool = self.pool.clone();
-        let post_id = post.id;
+        let pool = self.db.primary().clone();
         tokio::spawn(async move {
-            let _ = sqlx::query("UPDATE global.posts SET views = views + 1 WHERE id = $1")
-                .bind(post_id)
-                .execute(&pool)
-                .await;
+            let _ = sqlx::query!(
+                "UPDATE global.posts SET views = views + 1 WHERE id = $1",
+                id
+            )
+            .execute(&pool)
+            .await;
         });
 
         info!("Retrieved post with ID: {}", id);
         Ok(post_response)
     }
 
-    // Helper to get post from DB by slug
-    async fn get_post_from_db_by_slug(&self, slug: &str) -> Result<PostResponse, PostError> {
-        // Get post
-        let post = sqlx::query_as::<_, Post>(
-            r#"
-            SELECT * FROM global.posts
-            WHERE slug = $1 AND is_deleted = false
-            "#,
-        )
-        .bind(slug)
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or(PostError::NotFound)?;
-
-        // Use the existing method to get the full post with author and tags
-        self.get_post_from_db(post.id).await
-    }
-
     // Update post
     pub async fn update_post(
         &self,
         post_id: i64,
         user_id: Uuid,
+        is_admin: bool,
         update: UpdatePostRequest,
     ) -> Result<PostResponse, PostError> {
+        // Editors always see the full post, whatever tier it is gated behind
+        let viewer = PostViewer {
+            user_id: Some(user_id),
+            tier_level: FREE_TIER_LEVEL,
+            is_admin,
+        };
+
         // Check if post exists and user is authorized
-        let post = self.get_post_from_db(post_id).await?;
+        let post = self.get_post_from_db(post_id, &viewer).await?;
 
         // Get the post's user_id from the database directly
-        let post_user_id = sqlx::query("SELECT user_id FROM global.posts WHERE id = $1")
-            .bind(post_id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| {
-                error!("Error fetching post owner: {:?}", e);
-                PostError::DatabaseError(e)
-            })?
-            .get::<Uuid, _>("user_id");
+        let post_user_id =
+            sqlx::query_scalar!("SELECT user_id FROM global.posts WHERE id = $1", post_id)
+                .fetch_one(self.db.primary())
+                .await
+                .map_err(|e| {
+                    error!("Error fetching post owner: {:?}", e);
+                    PostError::DatabaseError(e)
+                })?;
 
         // Check if the user is the author
-        if post_user_id != user_id {
+        if post_user_id != user_id && !is_admin {
             return Err(PostError::Unauthorized);
         }
 
+        // Refuse to clobber someone else's in-progress edit; admins may override
+        if !is_admin {
+            if let Some(lock) = self.get_edit_lock(post_id).await? {
+                if lock.user_id != user_id {
+                    return Err(PostError::EditLocked(lock));
+                }
+            }
+        }
+
         // Clone slug and title for existence checks if provided
         let slug_check = update
             .slug
@@ -401,140 +1060,168 @@ impl PostService {
             }
         }
 
+        // An empty canonical URL removes it
+        let canonical_url = update.canonical_url.as_deref().map(str::trim);
+        if let Some(url) = canonical_url.filter(|url| !url.is_empty()) {
+            validate_canonical_url(url)?;
+        }
+        if let Some(metadata) = &update.metadata {
+            validate_metadata(metadata)?;
+        }
+
         // Prepare content_html if content is updated
         let content_html = if let Some(ref content) = update.content {
-            Some(self.process_markdown(content)?)
+            Some(self.process_markdown(content).await?)
         } else {
             None
         };
 
+        // Resolve the new membership tier if it is being changed
+        let required_tier_id = match &update.required_tier {
+            Some(name) => Some(self.resolve_tier_name(name).await?),
+            None => None,
+        };
+
         // Create a transaction
-        let mut tx = self.pool.begin().await.map_err(|e| {
+        let mut tx = self.db.primary().begin().await.map_err(|e| {
             error!("Error starting transaction: {:?}", e);
             PostError::DatabaseError(e)
         })?;
 
         // Update post attributes
         if let Some(title) = &update.title {
-            sqlx::query("UPDATE global.posts SET title = $1 WHERE id = $2")
-                .bind(title)
-                .bind(post_id)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| {
-                    error!("Error updating post title: {:?}", e);
-                    PostError::DatabaseError(e)
-                })?;
+            sqlx::query!(
+                "UPDATE global.posts SET title = $1 WHERE id = $2",
+                title,
+                post_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Error updating post title: {:?}", e);
+                PostError::DatabaseError(e)
+            })?;
         }
 
         if let Some(slug) = &update.slug {
-            sqlx::query("UPDATE global.posts SET slug = $1 WHERE id = $2")
-                .bind(slug)
-                .bind(post_id)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| {
-                    error!("Error updating post slug: {:?}", e);
-                    PostError::DatabaseError(e)
-                })?;
+            sqlx::query!(
+                "UPDATE global.posts SET slug = $1 WHERE id = $2",
+                slug,
+                post_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Error updating post slug: {:?}", e);
+                PostError::DatabaseError(e)
+            })?;
         }
 
         if let Some(content) = &update.content {
-            sqlx::query("UPDATE global.posts SET content = $1, content_html = $2 WHERE id = $3")
-                .bind(content)
-                .bind(content_html.unwrap_or_default())
-                .bind(post_id)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| {
-                    error!("Error updating post content: {:?}", e);
-                    PostError::DatabaseError(e)
-                })?;
+            sqlx::query!(
+                "UPDATE global.posts SET content = $1, content_html = $2 WHERE id = $3",
+                content,
+                content_html.unwrap_or_default(),
+                post_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Error updating post content: {:?}", e);
+                PostError::DatabaseError(e)
+            })?;
+            check_content(&mut tx, post_id, post_user_id, content).await?;
         }
 
         if let Some(cover_image_url) = &update.cover_image_url {
-            sqlx::query("UPDATE global.posts SET cover_image_url = $1 WHERE id = $2")
-                .bind(cover_image_url)
-                .bind(post_id)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| {
-                    error!("Error updating post cover image: {:?}", e);
-                    PostError::DatabaseError(e)
-                })?;
+            sqlx::query!(
+                "UPDATE global.posts SET cover_image_url = $1 WHERE id = $2",
+                cover_image_url,
+                post_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Error updating post cover image: {:?}", e);
+                PostError::DatabaseError(e)
+            })?;
+        }
+
+        if let Some(canonical_url) = canonical_url {
+            sqlx::query!(
+                "UPDATE global.posts SET canonical_url = NULLIF($1, '') WHERE id = $2",
+                canonical_url,
+                post_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Error updating post canonical URL: {:?}", e);
+                PostError::DatabaseError(e)
+            })?;
         }
 
         if let Some(is_draft) = update.is_draft {
-            sqlx::query("UPDATE global.posts SET is_draft = $1 WHERE id = $2")
-                .bind(is_draft) // Directly binding the boolean value
-                .bind(post_id)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| {
-                    error!("Error updating post draft status: {:?}", e);
-                    PostError::DatabaseError(e)
-                })?;
+            sqlx::query!(
+                "UPDATE global.posts SET is_draft = $1 WHERE id = $2",
+                is_draft,
+                post_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Error updating post draft status: {:?}", e);
+                PostError::DatabaseError(e)
+            })?;
         }
 
-        // Always update the updated_at timestamp
-        sqlx::query("UPDATE global.posts SET updated_at = $1 WHERE id = $2")
-            .bind(Utc::now())
-            .bind(post_id)
+        if let Some(metadata) = &update.metadata {
+            sqlx::query!(
+                "UPDATE global.posts SET metadata = $1 WHERE id = $2",
+                metadata,
+                post_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Error updating post metadata: {:?}", e);
+                PostError::DatabaseError(e)
+            })?;
+        }
+
+        if let Some(required_tier_id) = required_tier_id {
+            sqlx::query!(
+                "UPDATE global.posts SET required_tier_id = $1 WHERE id = $2",
+                required_tier_id,
+                post_id
+            )
             .execute(&mut *tx)
             .await
             .map_err(|e| {
-                error!("Error updating post timestamp: {:?}", e);
+                error!("Error updating post membership tier: {:?}", e);
                 PostError::DatabaseError(e)
             })?;
+        }
+
+        // Always update the updated_at timestamp
+        sqlx::query!(
+            "UPDATE global.posts SET updated_at = $1 WHERE id = $2",
+            Utc::now(),
+            post_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Error updating post timestamp: {:?}", e);
+            PostError::DatabaseError(e)
+        })?;
 
         // Update tags if provided
         if let Some(tags) = &update.tags {
-            // Remove existing tags
-            sqlx::query("DELETE FROM global.post_tags WHERE post_id = $1")
-                .bind(post_id)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| {
-                    error!("Error removing existing tags: {:?}", e);
-                    PostError::DatabaseError(e)
-                })?;
-
-            // Add new tags
-            for tag_name in tags {
-                // Upsert tag
-                let tag_id: i64 = sqlx::query(
-                    r#"
-                    INSERT INTO global.tags (name) 
-                    VALUES ($1) 
-                    ON CONFLICT (name) DO UPDATE SET name = $1
-                    RETURNING id
-                    "#,
-                )
-                .bind(tag_name)
-                .fetch_one(&mut *tx)
-                .await
-                .map_err(|e| {
-                    error!("Error upserting tag: {:?}", e);
-                    PostError::DatabaseError(e)
-                })?
-                .get(0);
-
-                // Associate tag with post
-                sqlx::query(
-                    r#"
-                    INSERT INTO global.post_tags (post_id, tag_id)
-                    VALUES ($1, $2)
-                    "#,
-                )
-                .bind(post_id)
-                .bind(tag_id)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| {
-                    error!("Error associating tag with post: {:?}", e);
-                    PostError::DatabaseError(e)
-                })?;
-            }
+            set_post_tags(&mut tx, post_id, tags).await.map_err(|e| {
+                error!("Error updating post tags: {:?}", e);
+                PostError::DatabaseError(e)
+            })?;
         }
 
         // Commit the transaction
@@ -543,47 +1230,290 @@ impl PostService {
             PostError::DatabaseError(e)
         })?;
 
-        // Clear cache if using Redis
-        if let Some(ref cache) = self.redis_cache {
-            // Use methods from RedisCache instead of directly calling del
-            if let Err(e) = cache.invalidate_post(post_id, &post.slug).await {
-                error!("Failed to clear Redis cache for post: {:?}", e);
+        // Publishing or unpublishing moves the post in or out of the post lists and archive
+        let published_changed = update
+            .is_draft
+            .is_some_and(|is_draft| is_draft != post.is_draft);
+        let mut keys = vec![SurrogateKey::Post(post_id)];
+        if published_changed {
+            keys.push(SurrogateKey::Posts);
+        }
+        purge(self.redis_cache.as_ref(), &keys).await;
+
+        // Publishing or unpublishing changes what the post earns its author
+        if published_changed {
+            queue_reputation_update(self.db.primary(), &[post_user_id]).await;
+        }
+
+        // Return the updated post with author info
+        self.get_post_by_id(post_id, &viewer).await
+    }
+
+    // Get the owner of a post that has not been deleted
+    async fn get_post_owner(&self, post_id: i64) -> Result<Uuid, PostError> {
+        sqlx::query_scalar!(
+            "SELECT user_id FROM global.posts WHERE id = $1 AND blog_id = $2 AND is_deleted = false",
+            post_id,
+            current_blog_id()
+        )
+        .fetch_optional(self.db.primary())
+        .await?
+        .ok_or(PostError::NotFound)
+    }
+
+    // Get the active edit lock of a post, if any
+    async fn get_edit_lock(&self, post_id: i64) -> Result<Option<EditLock>, PostError> {
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            None => return Ok(None),
+        };
+
+        match cache.get_edit_lock(post_id).await? {
+            Some(json) => Ok(serde_json::from_str(&json).ok()),
+            None => Ok(None),
+        }
+    }
+
+    // Acquire (or refresh) the edit lock of a post. Admins may take over someone else's lock with `force`.
+    pub async fn acquire_edit_lock(
+        &self,
+        post_id: i64,
+        user_id: Uuid,
+        is_admin: bool,
+        force: bool,
+    ) -> Result<EditLock, PostError> {
+        let cache = self
+            .redis_cache
+            .as_ref()
+            .ok_or(PostError::EditLocksUnavailable)?;
+
+        let owner_id = self.get_post_owner(post_id).await?;
+        if owner_id != user_id && !is_admin {
+            return Err(PostError::Unauthorized);
+        }
+
+        let now = Utc::now();
+        let lock = EditLock {
+            post_id,
+            user_id,
+            acquired_at: now,
+            expires_at: now + Duration::seconds(EDIT_LOCK_TTL_SECONDS as i64),
+        };
+        let json =
+            serde_json::to_string(&lock).map_err(|e| PostError::InternalError(e.to_string()))?;
+
+        if cache
+            .set_edit_lock(post_id, &json, EDIT_LOCK_TTL_SECONDS, true)
+            .await?
+        {
+            info!("User {} started editing post {}", user_id, post_id);
+            self.broadcast_editing_event(post_id, &[owner_id], user_id, "editing_started")
+                .await;
+            return Ok(lock);
+        }
+
+        // Someone holds the lock already: refresh it if it is ours, take it over if forced
+        let current = self.get_edit_lock(post_id).await?;
+        match current {
+            Some(current) if current.user_id == user_id => {
+                let refreshed = EditLock {
+                    acquired_at: current.acquired_at,
+                    ..lock
+                };
+                let json = serde_json::to_string(&refreshed)
+                    .map_err(|e| PostError::InternalError(e.to_string()))?;
+                cache
+                    .set_edit_lock(post_id, &json, EDIT_LOCK_TTL_SECONDS, false)
+                    .await?;
+                Ok(refreshed)
+            }
+            Some(current) if !(is_admin && force) => Err(PostError::EditLocked(current)),
+            previous => {
+                cache
+                    .set_edit_lock(post_id, &json, EDIT_LOCK_TTL_SECONDS, false)
+                    .await?;
+                let mut recipients = vec![owner_id];
+                if let Some(previous) = previous {
+                    warn!(
+                        "User {} took over the edit lock of post {} from {}",
+                        user_id, post_id, previous.user_id
+                    );
+                    recipients.push(previous.user_id);
+                }
+                self.broadcast_editing_event(post_id, &recipients, user_id, "editing_started")
+                    .await;
+                Ok(lock)
+            }
+        }
+    }
+
+    // Release the edit lock of a post. Only the holder or an admin can release it.
+    pub async fn release_edit_lock(
+        &self,
+        post_id: i64,
+        user_id: Uuid,
+        is_admin: bool,
+    ) -> Result<(), PostError> {
+        let cache = self
+            .redis_cache
+            .as_ref()
+            .ok_or(PostError::EditLocksUnavailable)?;
+
+        let owner_id = self.get_post_owner(post_id).await?;
+
+        // The lock is only deleted if it is still the one checked here; if it was refreshed
+        // or taken over in between, it is checked again
+        for _ in 0..EDIT_LOCK_RELEASE_ATTEMPTS {
+            let json = match cache.get_edit_lock(post_id).await? {
+                Some(json) => json,
+                None => return Ok(()),
+            };
+            let lock: EditLock = match serde_json::from_str(&json) {
+                Ok(lock) => lock,
+                Err(_) => return Ok(()),
+            };
+
+            if lock.user_id != user_id && !is_admin {
+                return Err(PostError::EditLocked(lock));
             }
 
-            // Clear popular posts cache
-            if let Err(e) = cache.invalidate_popular_posts().await {
-                error!("Failed to clear Redis cache for popular posts: {:?}", e);
+            if cache.release_edit_lock(post_id, &json).await? {
+                info!("User {} stopped editing post {}", lock.user_id, post_id);
+                self.broadcast_editing_event(
+                    post_id,
+                    &[owner_id, lock.user_id],
+                    user_id,
+                    "editing_ended",
+                )
+                .await;
+                return Ok(());
             }
         }
 
-        // Return the updated post with author info
-        self.get_post_by_id(post_id).await
+        Err(PostError::InternalError(
+            "Edit lock kept changing while releasing it".to_string(),
+        ))
+    }
+
+    // Tell co-authors over their notification WebSocket that someone started or stopped editing
+    async fn broadcast_editing_event(
+        &self,
+        post_id: i64,
+        recipients: &[Uuid],
+        actor_id: Uuid,
+        event: &str,
+    ) {
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            None => return,
+        };
+
+        let action = if event == "editing_started" {
+            "started"
+        } else {
+            "stopped"
+        };
+        let notification = Notification {
+            notification_type: event.to_string(),
+            message: format!("User {} {} editing post {}", actor_id, action, post_id),
+            post_id: Some(post_id),
+            comment_id: None,
+            timestamp: Utc::now(),
+        };
+        let json = match serde_json::to_string(&notification) {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Failed to serialize editing event: {}", e);
+                return;
+            }
+        };
+
+        let mut connection = match cache.connection().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Failed to publish editing event: {}", e);
+                return;
+            }
+        };
+
+        let mut notified = Vec::with_capacity(recipients.len());
+        for recipient in recipients {
+            if *recipient == actor_id || notified.contains(recipient) {
+                continue;
+            }
+            notified.push(*recipient);
+
+            let channel = format!("notifications:user:{}", recipient);
+            if let Err(e) = connection.publish::<_, _, ()>(&channel, &json).await {
+                error!("Failed to publish editing event to {}: {}", recipient, e);
+            }
+        }
+    }
+
+    /// New posts of the blog flagged as copies of other users' posts, newest first
+    pub async fn get_duplicate_flags(
+        &self,
+        pagination: &Pagination,
+    ) -> Result<Vec<DuplicatePostFlag>, PostError> {
+        let query = sqlx::query(
+            r#"
+            SELECT f.id, f.post_id, p.slug AS post_slug, f.user_id, u.username,
+                f.duplicate_of, d.slug AS duplicate_of_slug, f.distance, f.created_at
+            FROM global.post_flags f
+            JOIN global.posts p ON p.id = f.post_id
+            JOIN global.posts d ON d.id = f.duplicate_of
+            JOIN global.users u ON u.id = f.user_id
+            WHERE f.blog_id = $1
+            ORDER BY f.created_at DESC, f.id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(pagination.limit)
+        .bind(pagination.offset)
+        .fetch_all(self.db.read());
+        let rows = timed("posts.duplicate_flags", query).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| DuplicatePostFlag {
+                id: row.get("id"),
+                post_id: row.get("post_id"),
+                post_slug: row.get("post_slug"),
+                user_id: row.get("user_id"),
+                username: row.get("username"),
+                duplicate_of: row.get("duplicate_of"),
+                duplicate_of_slug: row.get("duplicate_of_slug"),
+                distance: row.get("distance"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
     }
 
     // Delete post (soft delete)
     pub async fn delete_post(&self, id: i64, user_id: Uuid) -> Result<(), PostError> {
         // Check if post exists and belongs to user (or user is admin)
-        let post = sqlx::query_as::<_, Post>(
+        sqlx::query_scalar!(
             r#"
-            SELECT * FROM global.posts
-            WHERE id = $1 AND is_deleted = false
+            SELECT id FROM global.posts
+            WHERE id = $1 AND blog_id = $2 AND is_deleted = false
             "#,
+            id,
+            current_blog_id()
         )
-        .bind(id)
-        .fetch_optional(&self.pool)
+        .fetch_optional(self.db.primary())
         .await?
         .ok_or(PostError::NotFound)?;
 
         // Get the post's user_id from the database directly
-        let post_user_id = sqlx::query("SELECT user_id FROM global.posts WHERE id = $1")
-            .bind(id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| {
-                error!("Error fetching post owner: {:?}", e);
-                PostError::DatabaseError(e)
-            })?
-            .get::<Uuid, _>("user_id");
+        let post_user_id =
+            sqlx::query_scalar!("SELECT user_id FROM global.posts WHERE id = $1", id)
+                .fetch_one(self.db.primary())
+                .await
+                .map_err(|e| {
+                    error!("Error fetching post owner: {:?}", e);
+                    PostError::DatabaseError(e)
+                })?;
 
         // Check ownership
         if post_user_id != user_id {
@@ -592,36 +1522,104 @@ impl PostService {
         }
 
         // Soft delete the post
-        sqlx::query(
+        sqlx::query!(
             r#"
             UPDATE global.posts
             SET is_deleted = true, updated_at = $1
             WHERE id = $2
             "#,
+            Utc::now(),
+            id
         )
-        .bind(Utc::now())
-        .bind(id)
-        .execute(&self.pool)
+        .execute(self.db.primary())
         .await?;
 
-        // Invalidate caches
-        if let Some(cache) = &self.redis_cache {
-            let _ = cache.invalidate_post(id, &post.slug).await;
-            let _ = cache.invalidate_popular_posts().await;
-        }
+        purge(
+            self.redis_cache.as_ref(),
+            &[SurrogateKey::Post(id), SurrogateKey::Posts],
+        )
+        .await;
+
+        queue_reputation_update(self.db.primary(), &[post_user_id]).await;
 
         Ok(())
     }
 
+    // Build the responses for the posts of a listing: authors, tags and covers, without
+    // attachments, and gated unless the viewer is an admin. Authors and tags come with the
+    // rows; covers and tiers are loaded concurrently.
+    async fn list_item_responses(
+        &self,
+        posts: Vec<PostRow>,
+        viewer: &PostViewer,
+    ) -> Result<Vec<PostResponse>, PostError> {
+        // Listings show the cover too, so they get its variants
+        let covers = try_join_all(posts.iter().map(|row| {
+            post_cover_image(
+                self.db.read(),
+                row.post.id,
+                row.post.cover_image_url.as_deref(),
+            )
+        }))
+        .await?;
+
+        // Gate premium content, looking up each tier once
+        let mut tiers: HashMap<i64, MembershipTier> = HashMap::new();
+        if !viewer.is_admin {
+            let mut tier_ids: Vec<i64> = posts
+                .iter()
+                .filter_map(|row| row.post.required_tier_id)
+                .collect();
+            tier_ids.sort();
+            tier_ids.dedup();
+            let found = try_join_all(
+                tier_ids
+                    .iter()
+                    .map(|tier_id| self.get_required_tier(Some(*tier_id))),
+            )
+            .await?;
+            tiers = found
+                .into_iter()
+                .flatten()
+                .map(|tier| (tier.id, tier))
+                .collect();
+        }
+
+        let mut responses = Vec::with_capacity(posts.len());
+        for (row, cover_image) in posts.into_iter().zip(covers) {
+            let required_tier_id = row.post.required_tier_id;
+            let mut post_response = row.into_response(cover_image, Vec::new());
+
+            if let Some(tier) = required_tier_id.and_then(|id| tiers.get(&id)) {
+                if viewer.tier_level < tier.level {
+                    self.apply_paywall(&mut post_response, tier)?;
+                }
+            }
+            responses.push(post_response);
+        }
+
+        Ok(responses)
+    }
+
     // Get popular posts
-    pub async fn get_popular_posts(&self, limit: i64) -> Result<Vec<PostResponse>, PostError> {
+    pub async fn get_popular_posts(
+        &self,
+        pagination: &Pagination,
+        viewer: &PostViewer,
+    ) -> Result<Vec<PostResponse>, PostError> {
+        // Lists are shared between readers, so they are only ungated for admins
+        let page_key = format!("{}:{}", viewer.cache_variant(), pagination.cache_field());
+
         // Try to get from cache first
         if let Some(cache) = &self.redis_cache {
-            if let Ok(Some(cached_posts)) = cache.get_popular_posts().await {
+            if let Ok(Some(cached_posts)) = cache.get_popular_posts(&page_key).await {
                 info!("Retrieved popular posts from cache");
                 // Deserialize and return
                 match serde_json::from_str::<Vec<PostResponse>>(&cached_posts) {
-                    Ok(posts) => return Ok(posts),
+                    Ok(mut posts) => {
+                        self.fill_live_fields(&mut posts, viewer).await?;
+                        return Ok(posts);
+                    }
                     Err(e) => {
                         error!("Error deserializing cached popular posts: {}", e);
                         // Continue to DB retrieval if cache deserialization fails
@@ -630,82 +1628,328 @@ impl PostService {
             }
         }
 
-        // Calculate popular posts using weightings for various factors
-        let posts = sqlx::query_as::<_, Post>(
+        // Scores are kept up to date by the post_popularity job
+        let sql = format!(
+            r#"
+            {}
+            WHERE p.blog_id = $3 AND p.is_draft = false AND p.is_deleted = false
+            ORDER BY p.popularity_score DESC, p.id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+            POST_SELECT
+        );
+        let query = sqlx::query_as::<_, PostRow>(&sql)
+            .bind(pagination.limit)
+            .bind(pagination.offset)
+            .bind(current_blog_id())
+            .fetch_all(self.db.read());
+        let posts = timed("posts.popular", query).await?;
+
+        let mut post_responses = self.list_item_responses(posts, viewer).await?;
+
+        // Cache the result
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(json_data) = serde_json::to_string(&post_responses) {
+                let _ = cache
+                    .cache_popular_posts(&page_key, &json_data, &listing_keys(&post_responses))
+                    .await;
+            }
+        }
+
+        self.fill_live_fields(&mut post_responses, viewer).await?;
+
+        info!("Retrieved {} popular posts", post_responses.len());
+        Ok(post_responses)
+    }
+
+    // Count published posts per month, newest month first
+    pub async fn get_archive(&self) -> Result<Vec<ArchiveMonth>, PostError> {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(Some(cached)) = cache.get_post_archive("months").await {
+                match serde_json::from_str::<Vec<ArchiveMonth>>(&cached) {
+                    Ok(months) => return Ok(months),
+                    Err(e) => error!("Error deserializing cached post archive: {}", e),
+                }
+            }
+        }
+
+        let query = sqlx::query_as!(
+            ArchiveMonth,
             r#"
-            SELECT * FROM global.posts
-            WHERE is_draft = false AND is_deleted = false
-            ORDER BY (views * 0.6 + likes * 0.3) DESC
-            LIMIT $1
+            SELECT
+                EXTRACT(YEAR FROM created_at AT TIME ZONE 'UTC')::INT AS "year!",
+                EXTRACT(MONTH FROM created_at AT TIME ZONE 'UTC')::INT AS "month!",
+                COUNT(*) AS "count!"
+            FROM global.posts
+            WHERE blog_id = $1 AND is_draft = false AND is_deleted = false
+            GROUP BY 1, 2
+            ORDER BY 1 DESC, 2 DESC
             "#,
+            current_blog_id()
         )
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+        .fetch_all(self.db.read());
+        let months = timed("posts.archive", query).await?;
 
-        // Get additional data for each post
-        let mut post_responses = Vec::new();
-        for post in posts {
-            // Get author info
-            let author = sqlx::query_as::<_, UserBrief>(
-                r#"
-                SELECT id, username as name FROM global.users
-                WHERE id = $1
-                "#,
-            )
-            .bind(post.user_id)
-            .fetch_one(&self.pool)
-            .await?;
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(json_data) = serde_json::to_string(&months) {
+                let _ = cache
+                    .cache_post_archive("months", &json_data, &[SurrogateKey::Posts])
+                    .await;
+            }
+        }
 
-            // Get tags
-            let tags = sqlx::query_as::<_, Tag>(
-                r#"
-                SELECT t.id, t.name FROM global.tags t
-                JOIN global.post_tags pt ON pt.tag_id = t.id
-                WHERE pt.post_id = $1
-                "#,
-            )
-            .bind(post.id)
-            .fetch_all(&self.pool)
-            .await?;
+        Ok(months)
+    }
 
-            // Construct response
-            let post_response = PostResponse {
-                id: post.id,
-                title: post.title,
-                slug: post.slug,
-                content: post.content,
-                content_html: post.content_html,
-                author,
-                tags: tags.into_iter().map(|t| t.name).collect(),
-                views: post.views,
-                likes: post.likes,
-                cover_image_url: post.cover_image_url,
-                is_draft: post.is_draft,
-                created_at: post.created_at,
-                updated_at: post.updated_at,
-            };
+    // List the posts published in one month (UTC), newest first
+    pub async fn get_archive_posts(
+        &self,
+        year: i32,
+        month: u32,
+        pagination: &Pagination,
+        viewer: &PostViewer,
+    ) -> Result<Vec<PostResponse>, PostError> {
+        let (start, end) = month_range(year, month)?;
+        // Lists are shared between readers, so they are only ungated for admins
+        let field = format!(
+            "{}-{:02}:{}:{}",
+            year,
+            month,
+            viewer.cache_variant(),
+            pagination.cache_field()
+        );
 
-            post_responses.push(post_response);
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(Some(cached_posts)) = cache.get_post_archive(&field).await {
+                match serde_json::from_str::<Vec<PostResponse>>(&cached_posts) {
+                    Ok(mut posts) => {
+                        self.fill_live_fields(&mut posts, viewer).await?;
+                        return Ok(posts);
+                    }
+                    Err(e) => error!("Error deserializing cached archive posts: {}", e),
+                }
+            }
         }
 
-        // Cache the result
+        let sql = format!(
+            r#"
+            {}
+            WHERE p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false
+                AND p.created_at >= $2 AND p.created_at < $3
+            ORDER BY p.created_at DESC, p.id DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            POST_SELECT
+        );
+        let query = sqlx::query_as::<_, PostRow>(&sql)
+            .bind(current_blog_id())
+            .bind(start)
+            .bind(end)
+            .bind(pagination.limit)
+            .bind(pagination.offset)
+            .fetch_all(self.db.read());
+        let posts = timed("posts.archive_month", query).await?;
+
+        let mut post_responses = self.list_item_responses(posts, viewer).await?;
+
         if let Some(cache) = &self.redis_cache {
             if let Ok(json_data) = serde_json::to_string(&post_responses) {
-                let _ = cache.cache_popular_posts(&json_data).await;
+                let _ = cache
+                    .cache_post_archive(&field, &json_data, &listing_keys(&post_responses))
+                    .await;
             }
         }
 
-        info!("Retrieved {} popular posts", post_responses.len());
+        self.fill_live_fields(&mut post_responses, viewer).await?;
+
+        info!(
+            "Retrieved {} posts of {}-{:02}",
+            post_responses.len(),
+            year,
+            month
+        );
         Ok(post_responses)
     }
 
-    /// Trigger an asynchronous data generation process
-    pub async fn trigger_data_generation(
+    // List published posts, newest first, optionally only those with a tag of the given slug
+    // and matching metadata filters. Returns the page and the total number of matching posts.
+    pub async fn get_published_posts(
+        &self,
+        pagination: &Pagination,
+        tag_slug: Option<&str>,
+        metadata: &[MetadataFilter],
+        viewer: &PostViewer,
+    ) -> Result<(Vec<PostResponse>, i64), PostError> {
+        // Tag slugs are not stored, so they are derived from names as `slugify` does
+        let tag_filter = r#"
+            ($2::TEXT IS NULL OR EXISTS (
+                SELECT 1 FROM global.post_tags pt
+                JOIN global.tags t ON t.id = pt.tag_id
+                WHERE pt.post_id = p.id
+                    AND trim(BOTH '-' FROM regexp_replace(global.match_key(t.name), '[^a-z0-9]+', '-', 'g'))
+                        = global.match_key($2)
+            ))
+        "#;
+
+        // Each filter matches when the metadata contains one of its documents, which
+        // `@>` finds with the GIN index; the documents are bound from $3 on
+        let mut documents = Vec::new();
+        let mut metadata_filter = String::new();
+        for filter in metadata {
+            let alternatives: Vec<String> = filter
+                .documents()
+                .into_iter()
+                .map(|document| {
+                    documents.push(document);
+                    format!("p.metadata @> ${}", documents.len() + 2)
+                })
+                .collect();
+            metadata_filter.push_str(&format!(" AND ({})", alternatives.join(" OR ")));
+        }
+
+        let count_sql = format!(
+            r#"
+            SELECT COUNT(*) FROM global.posts p
+            WHERE p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false AND {}{}
+            "#,
+            tag_filter, metadata_filter
+        );
+        let mut count_query = sqlx::query(&count_sql)
+            .bind(current_blog_id())
+            .bind(tag_slug);
+        for document in &documents {
+            count_query = count_query.bind(document);
+        }
+        let total: i64 = count_query.fetch_one(self.db.read()).await?.get(0);
+
+        let sql = format!(
+            r#"
+            {}
+            WHERE p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false AND {}{}
+            ORDER BY p.created_at DESC, p.id DESC
+            LIMIT ${} OFFSET ${}
+            "#,
+            POST_SELECT,
+            tag_filter,
+            metadata_filter,
+            documents.len() + 3,
+            documents.len() + 4
+        );
+        let mut query = sqlx::query_as::<_, PostRow>(&sql)
+            .bind(current_blog_id())
+            .bind(tag_slug);
+        for document in &documents {
+            query = query.bind(document);
+        }
+        let query = query
+            .bind(pagination.limit)
+            .bind(pagination.offset)
+            .fetch_all(self.db.read());
+        let posts = timed("posts.published", query).await?;
+
+        let post_responses = self.list_item_responses(posts, viewer).await?;
+
+        info!(
+            "Retrieved {} of {} published posts",
+            post_responses.len(),
+            total
+        );
+        Ok((post_responses, total))
+    }
+
+    // List an author's own posts, drafts included, with their engagement from analytics
+    pub async fn get_author_posts(
         &self,
-        _request: &DataGenerationRequest,
-    ) -> Result<String, PostError> {
-        info!("Data generation skipped due to database schema issues");
-        Ok("Data generation skipped due to database schema issues".to_string())
+        user_id: Uuid,
+        status: PostStatusFilter,
+        sort: AuthorPostSort,
+        pagination: &Pagination,
+    ) -> Result<Vec<AuthorPostSummary>, PostError> {
+        let is_draft = match status {
+            PostStatusFilter::Draft => Some(true),
+            PostStatusFilter::Published => Some(false),
+            PostStatusFilter::All => None,
+        };
+        let order_by = match sort {
+            AuthorPostSort::Recent => "p.updated_at DESC, p.id DESC",
+            AuthorPostSort::MostViewed => "views DESC, p.updated_at DESC, p.id DESC",
+            AuthorPostSort::Popular => "p.popularity_score DESC, p.updated_at DESC, p.id DESC",
+        };
+
+        let sql = format!(
+            r#"
+            SELECT p.id, p.title, p.slug, p.is_draft, p.popularity_score, p.created_at,
+                p.updated_at,
+                COALESCE(s.views, 0) AS views,
+                COALESCE(s.likes, 0) AS likes,
+                COALESCE(s.comments, 0) AS comments
+            FROM global.posts p
+            LEFT JOIN LATERAL (
+                SELECT
+                    ROUND(COALESCE(SUM(1.0 / COALESCE((ui.metadata->>'sample_rate')::FLOAT8, 1.0)) FILTER (WHERE ui.interaction_type = 'view'), 0))::BIGINT AS views,
+                    COUNT(*) FILTER (WHERE ui.interaction_type = 'like') AS likes,
+                    COUNT(*) FILTER (WHERE ui.interaction_type = 'comment') AS comments
+                FROM global.user_interactions ui
+                WHERE ui.post_id = p.id
+            ) s ON true
+            WHERE p.user_id = $1 AND p.blog_id = $5 AND p.is_deleted = false
+                AND ($2::BOOLEAN IS NULL OR p.is_draft = $2)
+            ORDER BY {}
+            LIMIT $3 OFFSET $4
+            "#,
+            order_by
+        );
+
+        let query = sqlx::query_as::<_, AuthorPostSummary>(&sql)
+            .bind(user_id)
+            .bind(is_draft)
+            .bind(pagination.limit)
+            .bind(pagination.offset)
+            .bind(current_blog_id())
+            .fetch_all(self.db.read());
+        let posts = timed("posts.author_dashboard", query).await?;
+
+        info!("Retrieved {} posts of author {}", posts.len(), user_id);
+        Ok(posts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_month_range() {
+        let (start, end) = month_range(2024, 12).unwrap();
+        assert_eq!(start.to_rfc3339(), "2024-12-01T00:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2025-01-01T00:00:00+00:00");
+
+        let (start, end) = month_range(2024, 2).unwrap();
+        assert_eq!(start.to_rfc3339(), "2024-02-01T00:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2024-03-01T00:00:00+00:00");
+
+        assert!(matches!(
+            month_range(2024, 13),
+            Err(PostError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            month_range(2024, 0),
+            Err(PostError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_meta_description_strips_markdown() {
+        assert_eq!(
+            meta_description("# Hello\n\nSome **bold** and `code`."),
+            "Hello Some bold and code."
+        );
+    }
+
+    #[test]
+    fn test_meta_description_cuts_at_word_boundary() {
+        let description = meta_description(&"word ".repeat(100));
+        assert!(description.ends_with("word…"));
+        assert!(description.chars().count() <= META_DESCRIPTION_CHARS + 1);
     }
 }
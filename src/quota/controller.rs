@@ -0,0 +1,63 @@
+use crate::auth::middleware::AuthUser;
+use crate::quota::model::{QuotaError, QuotaExceededResponse};
+use crate::quota::service::QuotaService;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+/// Response of a request refused or failed because of a quota, shared with the handlers that
+/// create posts and comments
+pub fn quota_error_response(e: QuotaError) -> Response {
+    match e {
+        QuotaError::Exceeded(quota) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(QuotaExceededResponse {
+                error: format!("Daily {} quota exceeded", quota.kind.as_str()),
+                code: "QUOTA_EXCEEDED".to_string(),
+                quota,
+            }),
+        )
+            .into_response(),
+        QuotaError::CacheError(e) => {
+            error!("Quota error: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to look up quotas" })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Get your daily quotas
+///
+/// How many posts and comments your role may create per day, and how many you created today.
+/// Quotas reset at midnight UTC.
+#[utoipa::path(
+    get,
+    path = "/api/users/me/quota",
+    tag = "users",
+    responses(
+        (status = 200, description = "Quotas retrieved", body = QuotaResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_quota(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<QuotaService>>,
+) -> Response {
+    match service.usage(&user).await {
+        Ok(quota) => (StatusCode::OK, Json(quota)).into_response(),
+        Err(e) => quota_error_response(e),
+    }
+}
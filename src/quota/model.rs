@@ -0,0 +1,222 @@
+use crate::auth::jwt::Role;
+use chrono::{DateTime, Days, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Kinds of content counted against a daily quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaKind {
+    Posts,
+    Comments,
+}
+
+impl QuotaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaKind::Posts => "posts",
+            QuotaKind::Comments => "comments",
+        }
+    }
+}
+
+/// Daily limits of one role; `None` is unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaLimits {
+    pub posts: Option<u32>,
+    pub comments: Option<u32>,
+}
+
+impl QuotaLimits {
+    /// Limits of a role before any configuration: readers may post a little, authors write
+    /// for a living, and admins are not limited
+    pub fn default_for(role: &Role) -> Self {
+        match role {
+            Role::User | Role::Analyst => Self {
+                posts: Some(3),
+                comments: Some(50),
+            },
+            Role::Author => Self {
+                posts: Some(20),
+                comments: Some(200),
+            },
+            Role::Admin => Self {
+                posts: None,
+                comments: None,
+            },
+        }
+    }
+
+    pub fn limit(&self, kind: QuotaKind) -> Option<u32> {
+        match kind {
+            QuotaKind::Posts => self.posts,
+            QuotaKind::Comments => self.comments,
+        }
+    }
+}
+
+/// Daily limits of every role, read from the environment.
+///
+/// `QUOTA_<ROLE>_POSTS_PER_DAY` and `QUOTA_<ROLE>_COMMENTS_PER_DAY` (e.g.
+/// `QUOTA_USER_POSTS_PER_DAY=5`) override the defaults of a role with a number, or with
+/// `unlimited`.
+#[derive(Debug, Clone)]
+pub struct QuotaPolicy {
+    user: QuotaLimits,
+    author: QuotaLimits,
+    admin: QuotaLimits,
+    analyst: QuotaLimits,
+}
+
+impl QuotaPolicy {
+    pub fn from_env() -> Self {
+        let limits = |role: Role| {
+            let defaults = QuotaLimits::default_for(&role);
+            let limit = |kind: QuotaKind, default: Option<u32>| {
+                let name = format!(
+                    "QUOTA_{}_{}_PER_DAY",
+                    role.as_str().to_uppercase(),
+                    kind.as_str().to_uppercase()
+                );
+                std::env::var(name)
+                    .ok()
+                    .and_then(|value| parse_limit(&value))
+                    .unwrap_or(default)
+            };
+
+            QuotaLimits {
+                posts: limit(QuotaKind::Posts, defaults.posts),
+                comments: limit(QuotaKind::Comments, defaults.comments),
+            }
+        };
+
+        Self {
+            user: limits(Role::User),
+            author: limits(Role::Author),
+            admin: limits(Role::Admin),
+            analyst: limits(Role::Analyst),
+        }
+    }
+
+    pub fn limits(&self, role: &Role) -> QuotaLimits {
+        match role {
+            Role::User => self.user,
+            Role::Author => self.author,
+            Role::Admin => self.admin,
+            Role::Analyst => self.analyst,
+        }
+    }
+}
+
+// A limit is a whole number or `unlimited`; anything else keeps the default
+fn parse_limit(value: &str) -> Option<Option<u32>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("unlimited") {
+        return Some(None);
+    }
+    value.parse().ok().map(Some)
+}
+
+/// Quotas start over at midnight UTC
+pub fn next_reset(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.date_naive()
+        .checked_add_days(Days::new(1))
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc())
+        .unwrap_or(now)
+}
+
+/// Use of one quota today
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QuotaUsage {
+    pub kind: QuotaKind,
+    /// Most allowed per day; absent when unlimited
+    #[schema(example = 3)]
+    pub limit: Option<u32>,
+    #[schema(example = 1)]
+    pub used: u32,
+    /// Left for today; absent when unlimited
+    #[schema(example = 2)]
+    pub remaining: Option<u32>,
+    pub resets_at: DateTime<Utc>,
+}
+
+impl QuotaUsage {
+    pub fn new(kind: QuotaKind, limit: Option<u32>, used: u32, now: DateTime<Utc>) -> Self {
+        Self {
+            kind,
+            limit,
+            used,
+            remaining: limit.map(|limit| limit.saturating_sub(used)),
+            resets_at: next_reset(now),
+        }
+    }
+}
+
+/// Your daily quotas and how much of them you used
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QuotaResponse {
+    #[schema(example = "user")]
+    pub role: String,
+    pub posts: QuotaUsage,
+    pub comments: QuotaUsage,
+}
+
+/// Error body when a daily quota is used up
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QuotaExceededResponse {
+    #[schema(example = "Daily posts quota exceeded")]
+    pub error: String,
+    #[schema(example = "QUOTA_EXCEEDED")]
+    pub code: String,
+    pub quota: QuotaUsage,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum QuotaError {
+    #[error("Daily {} quota exceeded", .0.kind.as_str())]
+    Exceeded(QuotaUsage),
+
+    #[error("Cache error: {0}")]
+    CacheError(#[from] redis::RedisError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit("5"), Some(Some(5)));
+        assert_eq!(parse_limit(" Unlimited "), Some(None));
+        assert_eq!(parse_limit("lots"), None);
+        assert_eq!(parse_limit("-1"), None);
+    }
+
+    #[test]
+    fn test_next_reset_is_next_midnight() {
+        let now = Utc.with_ymd_and_hms(2024, 12, 31, 23, 59, 0).unwrap();
+        assert_eq!(
+            next_reset(now),
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_usage_remaining() {
+        let now = Utc::now();
+        assert_eq!(
+            QuotaUsage::new(QuotaKind::Posts, Some(3), 1, now).remaining,
+            Some(2)
+        );
+        assert_eq!(
+            QuotaUsage::new(QuotaKind::Posts, Some(3), 5, now).remaining,
+            Some(0)
+        );
+        assert_eq!(
+            QuotaUsage::new(QuotaKind::Comments, None, 5, now).remaining,
+            None
+        );
+    }
+}
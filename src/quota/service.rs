@@ -0,0 +1,134 @@
+use crate::auth::middleware::AuthUser;
+use crate::cache::redis::RedisCache;
+use crate::quota::model::{
+    next_reset, QuotaError, QuotaKind, QuotaPolicy, QuotaResponse, QuotaUsage,
+};
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use tracing::{error, info};
+use uuid::Uuid;
+
+fn quota_key(kind: QuotaKind, user_id: Uuid, now: DateTime<Utc>) -> String {
+    format!(
+        "quota:{}:{}:{}",
+        kind.as_str(),
+        user_id,
+        now.format("%Y-%m-%d")
+    )
+}
+
+/// Daily quotas of posts and comments per user, on top of the burst rate limits.
+///
+/// Counters live in Redis, one per user, kind and day, and expire at the midnight that resets
+/// them. Without Redis, or when it fails, nothing is limited.
+#[derive(Debug, Clone)]
+pub struct QuotaService {
+    redis_cache: Option<RedisCache>,
+    policy: QuotaPolicy,
+}
+
+impl QuotaService {
+    pub fn new(redis_cache: Option<RedisCache>) -> Self {
+        Self {
+            redis_cache,
+            policy: QuotaPolicy::from_env(),
+        }
+    }
+
+    /// Count one post or comment against the user's quota, or fail when it is used up. Call
+    /// `refund` when creating it fails afterwards.
+    pub async fn consume(&self, user: &AuthUser, kind: QuotaKind) -> Result<(), QuotaError> {
+        let limit = match self.policy.limits(&user.role).limit(kind) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+
+        match self.increment(user.user_id, kind).await {
+            Ok(Some(used)) if used > limit => {
+                info!(
+                    "User {} exceeded their daily {} quota of {}",
+                    user.user_id,
+                    kind.as_str(),
+                    limit
+                );
+                self.refund(user.user_id, kind).await;
+                Err(QuotaError::Exceeded(QuotaUsage::new(
+                    kind,
+                    Some(limit),
+                    limit,
+                    Utc::now(),
+                )))
+            }
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed to count {} quota: {}", kind.as_str(), e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Give back a unit of quota counted for something that was not created
+    pub async fn refund(&self, user_id: Uuid, kind: QuotaKind) {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Err(e) = conn
+                    .decr::<_, _, ()>(quota_key(kind, user_id, Utc::now()), 1)
+                    .await
+                {
+                    error!("Failed to refund {} quota: {}", kind.as_str(), e);
+                }
+            }
+        }
+    }
+
+    /// The user's quotas and how much of them they used today
+    pub async fn usage(&self, user: &AuthUser) -> Result<QuotaResponse, QuotaError> {
+        let now = Utc::now();
+        let limits = self.policy.limits(&user.role);
+        let posts = self.used(user.user_id, QuotaKind::Posts, now).await?;
+        let comments = self.used(user.user_id, QuotaKind::Comments, now).await?;
+
+        Ok(QuotaResponse {
+            role: user.role.as_str().to_string(),
+            posts: QuotaUsage::new(QuotaKind::Posts, limits.posts, posts, now),
+            comments: QuotaUsage::new(QuotaKind::Comments, limits.comments, comments, now),
+        })
+    }
+
+    // Count one more for today, returning the new count; `None` without Redis
+    async fn increment(&self, user_id: Uuid, kind: QuotaKind) -> Result<Option<u32>, QuotaError> {
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            None => return Ok(None),
+        };
+
+        let now = Utc::now();
+        let key = quota_key(kind, user_id, now);
+        let mut conn = cache.connection().await?;
+        let (used,): (i64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire_at(&key, next_reset(now).timestamp())
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(Some(used.max(0) as u32))
+    }
+
+    async fn used(
+        &self,
+        user_id: Uuid,
+        kind: QuotaKind,
+        now: DateTime<Utc>,
+    ) -> Result<u32, QuotaError> {
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            None => return Ok(0),
+        };
+
+        let mut conn = cache.connection().await?;
+        let used: Option<i64> = conn.get(quota_key(kind, user_id, now)).await?;
+        Ok(used.unwrap_or(0).max(0) as u32)
+    }
+}
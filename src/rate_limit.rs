@@ -0,0 +1,463 @@
+//! Burst rate limits of individual routes, counted per user in Redis.
+//!
+//! Limits are sliding windows: a user may make `limit` requests within any `window`. Routes
+//! open to anonymous clients count those per address, taken from the `X-Forwarded-For` header
+//! set by the reverse proxy in front of the server, or else the address of the connection.
+//! Limited routes tell clients where they stand with `X-RateLimit-Limit` (requests allowed per
+//! window), `X-RateLimit-Remaining` and `X-RateLimit-Reset` (Unix time a request next frees up)
+//! on every response. Refused requests also get `Retry-After` and a `rate_limit` object in the
+//! body. Only requests that succeed use up the window. Without Redis, or when it fails, nothing is
+//! limited.
+
+use crate::auth::middleware::AuthUser;
+use crate::cache::redis::RedisCache;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use redis::AsyncCommands;
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+pub const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+pub const X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+
+/// At most `limit` requests within any `window` for each user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Names the counters of the limit in Redis and its environment variables
+    pub name: &'static str,
+    pub limit: u32,
+    pub window: Duration,
+}
+
+/// Creating comments: one every 100 seconds
+pub const COMMENT_RATE_LIMIT: RateLimit = RateLimit {
+    name: "comment",
+    limit: 1,
+    window: Duration::from_secs(100),
+};
+
+/// Liking and unliking posts: 30 a minute
+pub const LIKE_RATE_LIMIT: RateLimit = RateLimit {
+    name: "like",
+    limit: 30,
+    window: Duration::from_secs(60),
+};
+
+/// Sending feedback to the site admins: 3 an hour
+pub const FEEDBACK_RATE_LIMIT: RateLimit = RateLimit {
+    name: "feedback",
+    limit: 3,
+    window: Duration::from_secs(3600),
+};
+
+/// Starting tip checkouts: 10 an hour
+pub const TIP_RATE_LIMIT: RateLimit = RateLimit {
+    name: "tip",
+    limit: 10,
+    window: Duration::from_secs(3600),
+};
+
+/// Looking up link previews: 60 a minute
+pub const LINK_PREVIEW_RATE_LIMIT: RateLimit = RateLimit {
+    name: "link_preview",
+    limit: 60,
+    window: Duration::from_secs(60),
+};
+
+impl RateLimit {
+    /// The limit with `<NAME>_RATE_LIMIT_ATTEMPTS` and `<NAME>_RATE_LIMIT_WINDOW_SECONDS`
+    /// (e.g. `COMMENT_RATE_LIMIT_ATTEMPTS=3`) in place of its defaults; anything but a
+    /// positive number keeps the default
+    pub fn configured(self) -> Self {
+        self.configured_with(|name| std::env::var(name).ok())
+    }
+
+    /// The limit with the settings `lookup` returns by variable name, as for `configured`
+    pub fn configured_with(self, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let setting = |suffix: &str| {
+            let name = format!("{}_RATE_LIMIT_{}", self.name.to_uppercase(), suffix);
+            lookup(&name)
+                .and_then(|value| value.trim().parse::<u32>().ok())
+                .filter(|value| *value > 0)
+        };
+
+        Self {
+            limit: setting("ATTEMPTS").unwrap_or(self.limit),
+            window: setting("WINDOW_SECONDS")
+                .map(|seconds| Duration::from_secs(seconds.into()))
+                .unwrap_or(self.window),
+            ..self
+        }
+    }
+}
+
+/// Where a user stands in the current window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RateLimitStatus {
+    /// Requests allowed per window
+    #[schema(example = 1)]
+    pub limit: u32,
+    /// Requests counted in the current window
+    #[schema(example = 1)]
+    pub used: u32,
+    #[schema(example = 0)]
+    pub remaining: u32,
+    /// When the oldest counted request leaves the window
+    pub resets_at: DateTime<Utc>,
+}
+
+impl RateLimitStatus {
+    pub fn new(limit: u32, used: u32, resets_at: DateTime<Utc>) -> Self {
+        Self {
+            limit,
+            used,
+            remaining: limit.saturating_sub(used),
+            resets_at,
+        }
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.used > self.limit
+    }
+
+    /// Whole seconds from `now` until `resets_at`, rounded up
+    pub fn retry_after(&self, now: DateTime<Utc>) -> u64 {
+        let millis = (self.resets_at - now).num_milliseconds().max(0) as u64;
+        millis.div_ceil(1000)
+    }
+
+    /// Add the `X-RateLimit-*` headers
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        headers.insert(X_RATELIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(X_RATELIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(
+            X_RATELIMIT_RESET,
+            HeaderValue::from(self.resets_at.timestamp()),
+        );
+    }
+}
+
+/// Error body when a rate limit is exceeded
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RateLimitExceededResponse {
+    #[schema(example = "Rate limit exceeded, please try again later")]
+    pub error: String,
+    #[schema(example = "RATE_LIMITED")]
+    pub code: String,
+    pub rate_limit: RateLimitStatus,
+}
+
+/// A request counted by `RateLimiter::acquire`
+#[derive(Debug, Clone)]
+pub struct Reservation {
+    pub status: RateLimitStatus,
+    // Member of the request in the user's sorted set
+    member: String,
+}
+
+/// Counts the requests of each client against one `RateLimit`, in a sorted set per client of
+/// the times of their requests within the window. Clients are users by ID, or anonymous clients
+/// by `ip:<address>`.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    redis_cache: Option<RedisCache>,
+    limit: RateLimit,
+}
+
+impl RateLimiter {
+    pub fn new(redis_cache: Option<RedisCache>, limit: RateLimit) -> Self {
+        Self { redis_cache, limit }
+    }
+
+    fn key(&self, client: &str) -> String {
+        format!("rate_limit:{}:{}", self.limit.name, client)
+    }
+
+    fn window(&self) -> ChronoDuration {
+        ChronoDuration::from_std(self.limit.window).unwrap_or(ChronoDuration::zero())
+    }
+
+    /// Count a request of the client; `None` without Redis. Requests over the limit are counted
+    /// as well, until they are given back with `release`.
+    pub async fn acquire(&self, client: &str) -> Result<Option<Reservation>, redis::RedisError> {
+        let cache = match &self.redis_cache {
+            Some(cache) => cache,
+            None => return Ok(None),
+        };
+
+        let now = Utc::now();
+        let window = self.window();
+        let key = self.key(client);
+        let member = Uuid::new_v4().to_string();
+        let mut conn = cache.connection().await?;
+        // Requests that left the window are dropped before this one is added
+        let (used, oldest): (u32, Vec<(String, f64)>) = redis::pipe()
+            .atomic()
+            .zrembyscore(&key, "-inf", (now - window).timestamp_millis())
+            .ignore()
+            .zadd(&key, &member, now.timestamp_millis())
+            .ignore()
+            .zcard(&key)
+            .zrange_withscores(&key, 0, 0)
+            .pexpire(&key, window.num_milliseconds())
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        let oldest = oldest
+            .first()
+            .and_then(|(_, score)| DateTime::from_timestamp_millis(*score as i64))
+            .unwrap_or(now);
+
+        Ok(Some(Reservation {
+            status: RateLimitStatus::new(self.limit.limit, used, oldest + window),
+            member,
+        }))
+    }
+
+    /// Give back a request counted by `acquire` that was refused or failed, and return the
+    /// status without it
+    pub async fn release(&self, client: &str, reservation: &Reservation) -> RateLimitStatus {
+        if let Some(cache) = &self.redis_cache {
+            let result = match cache.connection().await {
+                Ok(mut conn) => {
+                    conn.zrem::<_, _, ()>(self.key(client), &reservation.member)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error!("Failed to release {} rate limit: {}", self.limit.name, e);
+            }
+        }
+
+        // The request is the newest of the window, so the window only changes when it was
+        // the only one
+        let status = reservation.status;
+        let used = status.used.saturating_sub(1);
+        let resets_at = if used == 0 {
+            Utc::now()
+        } else {
+            status.resets_at
+        };
+        RateLimitStatus::new(status.limit, used, resets_at)
+    }
+}
+
+/// Address of the client that sent a request: the last `X-Forwarded-For` entry, which is the
+/// one added by the reverse proxy, or `X-Real-IP`
+pub fn client_address(headers: &HeaderMap) -> Option<IpAddr> {
+    let forwarded_for = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last();
+    forwarded_for
+        .or_else(|| headers.get(X_REAL_IP).and_then(|value| value.to_str().ok()))
+        .and_then(|address| address.trim().parse().ok())
+}
+
+/// Address of the client that sent a request: `client_address`, or else the peer address of
+/// the connection when the server records it
+pub fn request_address<B>(req: &Request<B>) -> Option<IpAddr> {
+    client_address(req.headers()).or_else(|| {
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(peer)| peer.ip())
+    })
+}
+
+/// Middleware enforcing a `RateLimiter` on the routes it wraps, which must be authenticated.
+/// The request is counted up front, so concurrent requests cannot overrun the limit, and given
+/// back when it is refused or fails, so only successful requests use up the window.
+pub async fn rate_limit<B>(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    match req.extensions().get::<AuthUser>() {
+        Some(user) => {
+            let client = user.user_id.to_string();
+            enforce(&limiter, &client, req, next).await
+        }
+        None => next.run(req).await,
+    }
+}
+
+/// Middleware enforcing a `RateLimiter` on routes open to anonymous clients, like `rate_limit`.
+/// Signed-in users are counted by ID when the route also has `optional_auth_middleware`, and
+/// other clients by address. Requests without a client address, which only happens when the
+/// server does not record peer addresses, are not limited.
+pub async fn rate_limit_clients<B>(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let user = req
+        .extensions()
+        .get::<Option<AuthUser>>()
+        .cloned()
+        .flatten()
+        .or_else(|| req.extensions().get::<AuthUser>().cloned());
+    let client = match user {
+        Some(user) => user.user_id.to_string(),
+        None => match request_address(&req) {
+            Some(address) => format!("ip:{}", address),
+            None => return next.run(req).await,
+        },
+    };
+    enforce(&limiter, &client, req, next).await
+}
+
+async fn enforce<B>(
+    limiter: &RateLimiter,
+    client: &str,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let reservation = match limiter.acquire(client).await {
+        Ok(Some(reservation)) => reservation,
+        Ok(None) => return next.run(req).await,
+        Err(e) => {
+            error!("Failed to count {} rate limit: {}", limiter.limit.name, e);
+            return next.run(req).await;
+        }
+    };
+
+    if reservation.status.exceeded() {
+        info!(
+            "Client {} exceeded the {} rate limit",
+            client, limiter.limit.name
+        );
+        let status = limiter.release(client, &reservation).await;
+        let mut headers = HeaderMap::new();
+        status.apply(&mut headers);
+        headers.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(status.retry_after(Utc::now())),
+        );
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            headers,
+            Json(RateLimitExceededResponse {
+                error: "Rate limit exceeded, please try again later".to_string(),
+                code: "RATE_LIMITED".to_string(),
+                rate_limit: status,
+            }),
+        )
+            .into_response();
+    }
+
+    let mut response = next.run(req).await;
+    let status = if response.status().is_success() {
+        reservation.status
+    } else {
+        limiter.release(client, &reservation).await
+    };
+    status.apply(response.headers_mut());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_status_headers() {
+        let now = Utc::now();
+        let status = RateLimitStatus::new(5, 2, now + ChronoDuration::milliseconds(29_500));
+        assert_eq!(status.remaining, 3);
+        assert!(!status.exceeded());
+        assert_eq!(status.retry_after(now), 30);
+
+        let mut headers = HeaderMap::new();
+        status.apply(&mut headers);
+        assert_eq!(headers[X_RATELIMIT_LIMIT], "5");
+        assert_eq!(headers[X_RATELIMIT_REMAINING], "3");
+        assert_eq!(
+            headers[X_RATELIMIT_RESET],
+            status.resets_at.timestamp().to_string().as_str()
+        );
+
+        let over = RateLimitStatus::new(5, 6, now);
+        assert!(over.exceeded());
+        assert_eq!(over.remaining, 0);
+        assert_eq!(over.retry_after(now + ChronoDuration::seconds(1)), 0);
+    }
+
+    #[test]
+    fn test_configured_limit() {
+        const LIMIT: RateLimit = RateLimit {
+            name: "rate_limit_test",
+            limit: 1,
+            window: Duration::from_secs(100),
+        };
+        let settings = HashMap::from([
+            ("RATE_LIMIT_TEST_RATE_LIMIT_ATTEMPTS", " 3 "),
+            ("RATE_LIMIT_TEST_RATE_LIMIT_WINDOW_SECONDS", "0"),
+        ]);
+
+        let configured =
+            LIMIT.configured_with(|name| settings.get(name).map(|value| value.to_string()));
+        assert_eq!(configured.limit, 3);
+        assert_eq!(configured.window, Duration::from_secs(100));
+
+        let configured = LIMIT.configured_with(|_| None);
+        assert_eq!(configured.limit, 1);
+    }
+
+    #[test]
+    fn test_client_address() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_address(&headers), None);
+
+        headers.insert(X_REAL_IP, HeaderValue::from_static("198.51.100.7"));
+        assert_eq!(
+            client_address(&headers),
+            Some("198.51.100.7".parse().unwrap())
+        );
+
+        // The proxy appends the address it saw; earlier entries come from the client
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("10.0.0.1, 203.0.113.9"),
+        );
+        assert_eq!(
+            client_address(&headers),
+            Some("203.0.113.9".parse().unwrap())
+        );
+
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("not an address"));
+        assert_eq!(client_address(&headers), None);
+    }
+
+    #[test]
+    fn test_request_address() {
+        let mut req = Request::new(());
+        assert_eq!(request_address(&req), None);
+
+        // Without proxy headers the peer of the connection is the client
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 4], 51000))));
+        assert_eq!(request_address(&req), Some("192.0.2.4".parse().unwrap()));
+
+        req.headers_mut()
+            .insert(X_REAL_IP, HeaderValue::from_static("198.51.100.7"));
+        assert_eq!(request_address(&req), Some("198.51.100.7".parse().unwrap()));
+    }
+}
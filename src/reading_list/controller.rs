@@ -0,0 +1,301 @@
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::reading_list::model::{
+    AddListPostRequest, CreateReadingListRequest, ReadingListError, ReorderListRequest,
+    UpdateReadingListRequest,
+};
+use crate::reading_list::service::ReadingListService;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn reading_list_error_response(e: ReadingListError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        ReadingListError::ValidationError(_) => StatusCode::BAD_REQUEST,
+        ReadingListError::NotFound | ReadingListError::PostNotFound => StatusCode::NOT_FOUND,
+        ReadingListError::LimitReached(_) => StatusCode::CONFLICT,
+        ReadingListError::DatabaseError(_) => {
+            error!("Reading list error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// List the current user's reading lists, newest first
+#[utoipa::path(
+    get,
+    path = "/api/users/me/lists",
+    tag = "reading-lists",
+    responses(
+        (status = 200, description = "The user's reading lists", body = [ReadingList]),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_reading_lists(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ReadingListService>>,
+) -> Response {
+    match service.list_lists(user.user_id).await {
+        Ok(lists) => (StatusCode::OK, Json(lists)).into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
+
+/// Create a reading list, private unless `is_public` is set
+#[utoipa::path(
+    post,
+    path = "/api/users/me/lists",
+    tag = "reading-lists",
+    request_body = CreateReadingListRequest,
+    responses(
+        (status = 201, description = "Reading list created", body = ReadingListDetail),
+        (status = 400, description = "Invalid name or description"),
+        (status = 401, description = "Unauthorized"),
+        (status = 409, description = "Too many reading lists"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_reading_list(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ReadingListService>>,
+    Json(request): Json<CreateReadingListRequest>,
+) -> Response {
+    match service.create_list(user.user_id, &request).await {
+        Ok(list) => (StatusCode::CREATED, Json(list)).into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
+
+/// Get one of the current user's reading lists with its posts
+#[utoipa::path(
+    get,
+    path = "/api/users/me/lists/{id}",
+    tag = "reading-lists",
+    params(
+        ("id" = i64, Path, description = "Reading list ID")
+    ),
+    responses(
+        (status = 200, description = "The reading list", body = ReadingListDetail),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Reading list not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_my_reading_list(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ReadingListService>>,
+    Path(id): Path<i64>,
+) -> Response {
+    match service.get_own_list(user.user_id, id).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
+
+/// Rename a reading list, change its description or make it public or private
+#[utoipa::path(
+    put,
+    path = "/api/users/me/lists/{id}",
+    tag = "reading-lists",
+    params(
+        ("id" = i64, Path, description = "Reading list ID")
+    ),
+    request_body = UpdateReadingListRequest,
+    responses(
+        (status = 200, description = "Reading list updated", body = ReadingListDetail),
+        (status = 400, description = "Invalid name or description"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Reading list not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_reading_list(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ReadingListService>>,
+    Path(id): Path<i64>,
+    Json(request): Json<UpdateReadingListRequest>,
+) -> Response {
+    match service.update_list(user.user_id, id, &request).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
+
+/// Delete a reading list
+#[utoipa::path(
+    delete,
+    path = "/api/users/me/lists/{id}",
+    tag = "reading-lists",
+    params(
+        ("id" = i64, Path, description = "Reading list ID")
+    ),
+    responses(
+        (status = 204, description = "Reading list deleted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Reading list not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_reading_list(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ReadingListService>>,
+    Path(id): Path<i64>,
+) -> Response {
+    match service.delete_list(user.user_id, id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
+
+/// Add a published post to a reading list
+///
+/// The post goes at `position`, or at the end without one. Adding a post already in the list
+/// moves it to `position`.
+#[utoipa::path(
+    post,
+    path = "/api/users/me/lists/{id}/posts",
+    tag = "reading-lists",
+    params(
+        ("id" = i64, Path, description = "Reading list ID")
+    ),
+    request_body = AddListPostRequest,
+    responses(
+        (status = 200, description = "Post added", body = ReadingListDetail),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Reading list or post not found"),
+        (status = 409, description = "The list is full"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn add_reading_list_post(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ReadingListService>>,
+    Path(id): Path<i64>,
+    Json(request): Json<AddListPostRequest>,
+) -> Response {
+    match service.add_post(user.user_id, id, &request).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
+
+/// Put the posts of a reading list in a new order
+///
+/// Posts left out of `post_ids` follow the others in their current order.
+#[utoipa::path(
+    put,
+    path = "/api/users/me/lists/{id}/posts",
+    tag = "reading-lists",
+    params(
+        ("id" = i64, Path, description = "Reading list ID")
+    ),
+    request_body = ReorderListRequest,
+    responses(
+        (status = 200, description = "Posts reordered", body = ReadingListDetail),
+        (status = 400, description = "A post is not in the list or is listed twice"),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Reading list not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn reorder_reading_list(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ReadingListService>>,
+    Path(id): Path<i64>,
+    Json(request): Json<ReorderListRequest>,
+) -> Response {
+    match service.reorder(user.user_id, id, &request.post_ids).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
+
+/// Remove a post from a reading list
+#[utoipa::path(
+    delete,
+    path = "/api/users/me/lists/{id}/posts/{post_id}",
+    tag = "reading-lists",
+    params(
+        ("id" = i64, Path, description = "Reading list ID"),
+        ("post_id" = i64, Path, description = "Post ID")
+    ),
+    responses(
+        (status = 200, description = "Post removed", body = ReadingListDetail),
+        (status = 401, description = "Unauthorized"),
+        (status = 404, description = "Reading list not found or post not in it"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn remove_reading_list_post(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<ReadingListService>>,
+    Path((id, post_id)): Path<(i64, i64)>,
+) -> Response {
+    match service.remove_post(user.user_id, id, post_id).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
+
+/// Get a public reading list with its posts
+///
+/// Private lists are only shown to their owner.
+#[utoipa::path(
+    get,
+    path = "/api/lists/{id}",
+    tag = "reading-lists",
+    params(
+        ("id" = i64, Path, description = "Reading list ID")
+    ),
+    responses(
+        (status = 200, description = "The reading list", body = ReadingListDetail),
+        (status = 404, description = "Reading list not found or private"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_reading_list(
+    Extension(user): Extension<Option<AuthUser>>,
+    State(service): State<Arc<ReadingListService>>,
+    Path(id): Path<i64>,
+) -> Response {
+    match service.get_list(id, user.map(|user| user.user_id)).await {
+        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Err(e) => reading_list_error_response(e),
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Longest name of a list, in characters
+pub const MAX_LIST_NAME_LENGTH: usize = 100;
+/// Longest description of a list, in characters
+pub const MAX_LIST_DESCRIPTION_LENGTH: usize = 1000;
+/// Lists a user may have on each blog
+pub const MAX_LISTS_PER_USER: i64 = 100;
+/// Posts a list may hold
+pub const MAX_POSTS_PER_LIST: i64 = 500;
+
+/// Request to create a reading list
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateReadingListRequest {
+    #[schema(example = "Rust deep dives")]
+    pub name: String,
+    pub description: Option<String>,
+    /// Whether anyone with the link can see the list; private by default
+    #[serde(default)]
+    pub is_public: bool,
+}
+
+/// Changes to a reading list; omitted fields are kept
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateReadingListRequest {
+    pub name: Option<String>,
+    /// An empty string removes the description
+    pub description: Option<String>,
+    pub is_public: Option<bool>,
+}
+
+/// Request to add a post to a list
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddListPostRequest {
+    #[schema(example = 123)]
+    pub post_id: i64,
+    /// Zero-based place in the list; the end by default
+    pub position: Option<i32>,
+}
+
+/// New order of the posts of a list
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReorderListRequest {
+    /// Every post of the list, in the new order
+    #[schema(example = json!([124, 123]))]
+    pub post_ids: Vec<i64>,
+}
+
+/// A named collection of posts
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReadingList {
+    #[schema(example = 12)]
+    pub id: i64,
+    pub user_id: Uuid,
+    /// Username of the owner
+    pub owner: String,
+    #[schema(example = "Rust deep dives")]
+    pub name: String,
+    pub description: Option<String>,
+    pub is_public: bool,
+    /// Published posts in the list
+    #[schema(example = 8)]
+    pub post_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A post in a reading list
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReadingListPost {
+    #[schema(example = 123)]
+    pub post_id: i64,
+    pub title: String,
+    pub slug: String,
+    /// Username of the post's author
+    pub author: String,
+    /// Zero-based place in the list
+    pub position: i32,
+    pub added_at: DateTime<Utc>,
+}
+
+/// A reading list with its posts in order
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReadingListDetail {
+    #[serde(flatten)]
+    pub list: ReadingList,
+    /// Published posts of the list; posts unpublished or deleted since are left out
+    pub posts: Vec<ReadingListPost>,
+}
+
+/// Possible reading list errors
+#[derive(Debug, thiserror::Error)]
+pub enum ReadingListError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Reading list not found")]
+    NotFound,
+
+    #[error("Post not found")]
+    PostNotFound,
+
+    #[error("{0}")]
+    LimitReached(String),
+}
@@ -0,0 +1,529 @@
+use crate::db::instrument::timed;
+use crate::db::retry::with_retry;
+use crate::reading_list::model::{
+    AddListPostRequest, CreateReadingListRequest, ReadingList, ReadingListDetail, ReadingListError,
+    ReadingListPost, UpdateReadingListRequest, MAX_LISTS_PER_USER, MAX_LIST_DESCRIPTION_LENGTH,
+    MAX_LIST_NAME_LENGTH, MAX_POSTS_PER_LIST,
+};
+use crate::tenant::middleware::current_blog_id;
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Row};
+use std::collections::HashSet;
+use tracing::info;
+use uuid::Uuid;
+
+// Columns of a list, from the lists table aliased as `l` joined with its owner as `u`
+const LIST_COLUMNS: &str = r#"
+    l.id, l.user_id, u.username AS owner, l.name, l.description, l.is_public,
+    l.created_at, l.updated_at,
+    (
+        SELECT COUNT(*) FROM global.reading_list_posts lp
+        JOIN global.posts p ON p.id = lp.post_id
+        WHERE lp.list_id = l.id AND p.is_draft = false AND p.is_deleted = false
+    ) AS post_count
+"#;
+
+fn reading_list_from_row(row: &PgRow) -> ReadingList {
+    ReadingList {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        owner: row.get("owner"),
+        name: row.get("name"),
+        description: row.get("description"),
+        is_public: row.get("is_public"),
+        post_count: row.get("post_count"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+// Trimmed name of a list
+fn validate_name(name: &str) -> Result<String, ReadingListError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_LIST_NAME_LENGTH {
+        return Err(ReadingListError::ValidationError(format!(
+            "The name must be 1 to {} characters",
+            MAX_LIST_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_string())
+}
+
+// Trimmed description of a list; blank descriptions are dropped
+fn validate_description(description: &str) -> Result<Option<String>, ReadingListError> {
+    let description = description.trim();
+    if description.chars().count() > MAX_LIST_DESCRIPTION_LENGTH {
+        return Err(ReadingListError::ValidationError(format!(
+            "The description must be at most {} characters",
+            MAX_LIST_DESCRIPTION_LENGTH
+        )));
+    }
+    Ok(Some(description.to_string()).filter(|description| !description.is_empty()))
+}
+
+// Order of a list with a post put at a position, or kept where it is without one. New posts
+// go at the end by default.
+fn place(order: &[i64], post_id: i64, position: Option<i32>) -> Vec<i64> {
+    let present = order.contains(&post_id);
+    if present && position.is_none() {
+        return order.to_vec();
+    }
+
+    let mut placed: Vec<i64> = order.iter().copied().filter(|id| *id != post_id).collect();
+    let index = position.map_or(placed.len(), |position| {
+        (position.max(0) as usize).min(placed.len())
+    });
+    placed.insert(index, post_id);
+    placed
+}
+
+// Order of a list with the requested posts first; posts left out follow in their current order
+fn reordered(order: &[i64], requested: &[i64]) -> Result<Vec<i64>, ReadingListError> {
+    let mut seen = HashSet::new();
+    for post_id in requested {
+        if !order.contains(post_id) {
+            return Err(ReadingListError::ValidationError(format!(
+                "Post {} is not in the list",
+                post_id
+            )));
+        }
+        if !seen.insert(*post_id) {
+            return Err(ReadingListError::ValidationError(format!(
+                "Post {} is listed more than once",
+                post_id
+            )));
+        }
+    }
+
+    let mut reordered = requested.to_vec();
+    reordered.extend(order.iter().copied().filter(|id| !seen.contains(id)));
+    Ok(reordered)
+}
+
+/// Named reading lists of posts.
+///
+/// Lists belong to a user on a blog and hold published posts of that blog in the order the
+/// user gives them. Private lists are seen only by their owner; public ones by anyone with
+/// their ID. Posts unpublished or deleted after being added stay in the list but are not
+/// shown until they are published again. Every reader with a post in a list counts as a save
+/// of the post toward its popularity.
+pub struct ReadingListService {
+    pool: PgPool,
+}
+
+impl ReadingListService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// The user's lists on the blog, newest first
+    pub async fn list_lists(&self, user_id: Uuid) -> Result<Vec<ReadingList>, ReadingListError> {
+        let sql = format!(
+            "SELECT {} FROM global.reading_lists l JOIN global.users u ON u.id = l.user_id \
+             WHERE l.blog_id = $1 AND l.user_id = $2 \
+             ORDER BY l.created_at DESC, l.id DESC",
+            LIST_COLUMNS
+        );
+        let query = sqlx::query(&sql)
+            .bind(current_blog_id())
+            .bind(user_id)
+            .fetch_all(&self.pool);
+        let rows = timed("reading_lists.list", query).await?;
+
+        Ok(rows.iter().map(reading_list_from_row).collect())
+    }
+
+    /// Create an empty list
+    pub async fn create_list(
+        &self,
+        user_id: Uuid,
+        request: &CreateReadingListRequest,
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        let name = validate_name(&request.name)?;
+        let description = match request.description.as_deref() {
+            Some(description) => validate_description(description)?,
+            None => None,
+        };
+        let blog_id = current_blog_id();
+
+        let mut tx = self.pool.begin().await?;
+
+        // Serialize list creation per user so concurrent requests cannot pass the limit
+        sqlx::query("SELECT id FROM global.users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        let lists: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM global.reading_lists WHERE blog_id = $1 AND user_id = $2",
+        )
+        .bind(blog_id)
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if lists >= MAX_LISTS_PER_USER {
+            return Err(ReadingListError::LimitReached(format!(
+                "A user can have at most {} reading lists",
+                MAX_LISTS_PER_USER
+            )));
+        }
+
+        let list_id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO global.reading_lists (blog_id, user_id, name, description, is_public)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#,
+        )
+        .bind(blog_id)
+        .bind(user_id)
+        .bind(&name)
+        .bind(&description)
+        .bind(request.is_public)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        info!("User {} created reading list {}", user_id, list_id);
+        self.detail(list_id).await
+    }
+
+    /// A list of the blog as seen by a viewer: public lists by anyone, private ones only by
+    /// their owner. Private lists of others are reported as not found.
+    pub async fn get_list(
+        &self,
+        list_id: i64,
+        viewer_id: Option<Uuid>,
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        let detail = self.detail(list_id).await?;
+        if !detail.list.is_public && Some(detail.list.user_id) != viewer_id {
+            return Err(ReadingListError::NotFound);
+        }
+        Ok(detail)
+    }
+
+    /// One of the user's lists
+    pub async fn get_own_list(
+        &self,
+        user_id: Uuid,
+        list_id: i64,
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        let detail = self.detail(list_id).await?;
+        if detail.list.user_id != user_id {
+            return Err(ReadingListError::NotFound);
+        }
+        Ok(detail)
+    }
+
+    /// Rename a list, change its description or who can see it
+    pub async fn update_list(
+        &self,
+        user_id: Uuid,
+        list_id: i64,
+        request: &UpdateReadingListRequest,
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        let name = request.name.as_deref().map(validate_name).transpose()?;
+        let description = request
+            .description
+            .as_deref()
+            .map(validate_description)
+            .transpose()?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE global.reading_lists
+            SET name = COALESCE($4, name),
+                description = CASE WHEN $5 THEN $6 ELSE description END,
+                is_public = COALESCE($7, is_public),
+                updated_at = NOW()
+            WHERE id = $1 AND blog_id = $2 AND user_id = $3
+            "#,
+        )
+        .bind(list_id)
+        .bind(current_blog_id())
+        .bind(user_id)
+        .bind(&name)
+        .bind(description.is_some())
+        .bind(description.flatten())
+        .bind(request.is_public)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(ReadingListError::NotFound);
+        }
+
+        self.detail(list_id).await
+    }
+
+    /// Delete a list. Its posts are not affected.
+    pub async fn delete_list(&self, user_id: Uuid, list_id: i64) -> Result<(), ReadingListError> {
+        let result = sqlx::query(
+            "DELETE FROM global.reading_lists WHERE id = $1 AND blog_id = $2 AND user_id = $3",
+        )
+        .bind(list_id)
+        .bind(current_blog_id())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(ReadingListError::NotFound);
+        }
+
+        info!("User {} deleted reading list {}", user_id, list_id);
+        Ok(())
+    }
+
+    /// Add a published post of the blog to a list, or move a post already in it
+    pub async fn add_post(
+        &self,
+        user_id: Uuid,
+        list_id: i64,
+        request: &AddListPostRequest,
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        with_retry("reading_lists.add_post", || {
+            self.try_add_post(user_id, list_id, request)
+        })
+        .await
+    }
+
+    async fn try_add_post(
+        &self,
+        user_id: Uuid,
+        list_id: i64,
+        request: &AddListPostRequest,
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        let mut tx = self.pool.begin().await?;
+        let order = self.lock_order(&mut tx, user_id, list_id).await?;
+
+        if !order.contains(&request.post_id) {
+            let published = sqlx::query_scalar::<_, bool>(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM global.posts
+                    WHERE id = $1 AND blog_id = $2 AND is_draft = false AND is_deleted = false
+                )
+                "#,
+            )
+            .bind(request.post_id)
+            .bind(current_blog_id())
+            .fetch_one(&mut *tx)
+            .await?;
+            if !published {
+                return Err(ReadingListError::PostNotFound);
+            }
+            if order.len() as i64 >= MAX_POSTS_PER_LIST {
+                return Err(ReadingListError::LimitReached(format!(
+                    "A reading list can hold at most {} posts",
+                    MAX_POSTS_PER_LIST
+                )));
+            }
+
+            sqlx::query(
+                "INSERT INTO global.reading_list_posts (list_id, post_id, position) \
+                 VALUES ($1, $2, $3)",
+            )
+            .bind(list_id)
+            .bind(request.post_id)
+            .bind(order.len() as i32)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let placed = place(&order, request.post_id, request.position);
+        self.write_order(&mut tx, list_id, &placed).await?;
+        tx.commit().await?;
+
+        self.detail(list_id).await
+    }
+
+    /// Take a post out of a list
+    pub async fn remove_post(
+        &self,
+        user_id: Uuid,
+        list_id: i64,
+        post_id: i64,
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        let mut tx = self.pool.begin().await?;
+        let order = self.lock_order(&mut tx, user_id, list_id).await?;
+        if !order.contains(&post_id) {
+            return Err(ReadingListError::PostNotFound);
+        }
+
+        sqlx::query("DELETE FROM global.reading_list_posts WHERE list_id = $1 AND post_id = $2")
+            .bind(list_id)
+            .bind(post_id)
+            .execute(&mut *tx)
+            .await?;
+        let remaining: Vec<i64> = order.into_iter().filter(|id| *id != post_id).collect();
+        self.write_order(&mut tx, list_id, &remaining).await?;
+        tx.commit().await?;
+
+        self.detail(list_id).await
+    }
+
+    /// Put the posts of a list in a new order
+    pub async fn reorder(
+        &self,
+        user_id: Uuid,
+        list_id: i64,
+        post_ids: &[i64],
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        with_retry("reading_lists.reorder", || {
+            self.try_reorder(user_id, list_id, post_ids)
+        })
+        .await
+    }
+
+    async fn try_reorder(
+        &self,
+        user_id: Uuid,
+        list_id: i64,
+        post_ids: &[i64],
+    ) -> Result<ReadingListDetail, ReadingListError> {
+        let mut tx = self.pool.begin().await?;
+        let order = self.lock_order(&mut tx, user_id, list_id).await?;
+        let reordered = reordered(&order, post_ids)?;
+        self.write_order(&mut tx, list_id, &reordered).await?;
+        tx.commit().await?;
+
+        self.detail(list_id).await
+    }
+
+    // Lock one of the user's lists for changes to its posts, and get their order
+    async fn lock_order(
+        &self,
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        list_id: i64,
+    ) -> Result<Vec<i64>, ReadingListError> {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT id FROM global.reading_lists \
+             WHERE id = $1 AND blog_id = $2 AND user_id = $3 FOR UPDATE",
+        )
+        .bind(list_id)
+        .bind(current_blog_id())
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(ReadingListError::NotFound)?;
+
+        let order = sqlx::query_scalar(
+            "SELECT post_id FROM global.reading_list_posts WHERE list_id = $1 \
+             ORDER BY position, added_at",
+        )
+        .bind(list_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(order)
+    }
+
+    // Number the posts of a list in the given order
+    async fn write_order(
+        &self,
+        conn: &mut PgConnection,
+        list_id: i64,
+        order: &[i64],
+    ) -> Result<(), ReadingListError> {
+        sqlx::query(
+            r#"
+            UPDATE global.reading_list_posts lp
+            SET position = o.position - 1
+            FROM UNNEST($2::BIGINT[]) WITH ORDINALITY AS o(post_id, position)
+            WHERE lp.list_id = $1 AND lp.post_id = o.post_id
+            "#,
+        )
+        .bind(list_id)
+        .bind(order)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query("UPDATE global.reading_lists SET updated_at = NOW() WHERE id = $1")
+            .bind(list_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    // A list of the blog with its published posts
+    async fn detail(&self, list_id: i64) -> Result<ReadingListDetail, ReadingListError> {
+        let sql = format!(
+            "SELECT {} FROM global.reading_lists l JOIN global.users u ON u.id = l.user_id \
+             WHERE l.id = $1 AND l.blog_id = $2",
+            LIST_COLUMNS
+        );
+        let query = sqlx::query(&sql)
+            .bind(list_id)
+            .bind(current_blog_id())
+            .fetch_optional(&self.pool);
+        let list = timed("reading_lists.get", query)
+            .await?
+            .map(|row| reading_list_from_row(&row))
+            .ok_or(ReadingListError::NotFound)?;
+
+        let query = sqlx::query(
+            r#"
+            SELECT lp.post_id, p.title, p.slug, u.username AS author, lp.position, lp.added_at
+            FROM global.reading_list_posts lp
+            JOIN global.posts p ON p.id = lp.post_id
+            JOIN global.users u ON u.id = p.user_id
+            WHERE lp.list_id = $1 AND p.is_draft = false AND p.is_deleted = false
+            ORDER BY lp.position
+            "#,
+        )
+        .bind(list_id)
+        .fetch_all(&self.pool);
+        let posts = timed("reading_lists.posts", query)
+            .await?
+            .iter()
+            .map(|row| ReadingListPost {
+                post_id: row.get("post_id"),
+                title: row.get("title"),
+                slug: row.get("slug"),
+                author: row.get("author"),
+                position: row.get("position"),
+                added_at: row.get("added_at"),
+            })
+            .collect();
+
+        Ok(ReadingListDetail { list, posts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_list_fields() {
+        assert_eq!(validate_name("  Rust  ").unwrap(), "Rust");
+        assert!(validate_name("   ").is_err());
+        assert!(validate_name(&"a".repeat(MAX_LIST_NAME_LENGTH + 1)).is_err());
+
+        assert_eq!(validate_description(" ").unwrap(), None);
+        assert_eq!(
+            validate_description(" Long reads ").unwrap().as_deref(),
+            Some("Long reads")
+        );
+        assert!(validate_description(&"a".repeat(MAX_LIST_DESCRIPTION_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_place() {
+        assert_eq!(place(&[1, 2, 3], 4, None), vec![1, 2, 3, 4]);
+        assert_eq!(place(&[1, 2, 3], 4, Some(0)), vec![4, 1, 2, 3]);
+        assert_eq!(place(&[1, 2, 3], 4, Some(99)), vec![1, 2, 3, 4]);
+        // Posts already in the list are moved, or left alone without a position
+        assert_eq!(place(&[1, 2, 3], 3, Some(0)), vec![3, 1, 2]);
+        assert_eq!(place(&[1, 2, 3], 1, Some(-5)), vec![1, 2, 3]);
+        assert_eq!(place(&[1, 2, 3], 2, None), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_reordered() {
+        assert_eq!(reordered(&[1, 2, 3], &[3, 2, 1]).unwrap(), vec![3, 2, 1]);
+        // Posts left out keep their order after the others
+        assert_eq!(reordered(&[1, 2, 3, 4], &[4]).unwrap(), vec![4, 1, 2, 3]);
+        assert!(reordered(&[1, 2], &[3]).is_err());
+        assert!(reordered(&[1, 2], &[2, 2]).is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use crate::auth::jwt::Role;
 use crate::auth::middleware::AuthUser;
 use crate::recommendations::model::{
-    PostRecommendation, RecommendationError, RecommendationParams,
+    DigestSettings, PostRecommendation, PreviewParams, RecommendationError, RecommendationParams,
 };
 use crate::recommendations::service::RecommendationService;
 use axum::{
@@ -23,10 +23,15 @@ use utoipa::{IntoParams, ToSchema};
     params(
         ("limit" = Option<i64>, Query, description = "Maximum number of recommendations", example = "10"),
         ("offset" = Option<i64>, Query, description = "Offset for pagination", example = "0"),
-        ("algorithm" = Option<String>, Query, description = "Algorithm to use: collaborative, content_based, hybrid, popular", example = "hybrid"),
+        ("algorithm" = Option<String>, Query, description = "Algorithm to use: collaborative, content_based, hybrid, popular, embedding", example = "hybrid"),
         ("include_tags" = Option<Vec<String>>, Query, description = "Tags to include in recommendations (comma-separated)", example = "rust,programming,webdev"),
         ("exclude_tags" = Option<Vec<String>>, Query, description = "Tags to exclude from recommendations (comma-separated)", example = "deprecated,outdated"),
-        ("min_score" = Option<f64>, Query, description = "Minimum score threshold", example = "0.5")
+        ("min_score" = Option<f64>, Query, description = "Minimum score threshold", example = "0.5"),
+        ("max_per_author" = Option<i64>, Query, description = "Posts by one author before the rest of theirs are moved after other authors' posts", example = "2"),
+        ("max_per_tag" = Option<i64>, Query, description = "Posts with one tag before the rest with it are moved after other posts", example = "3"),
+        ("recency_boost" = Option<f64>, Query, description = "How much the scores of new posts are raised, between 0 and 1", example = "0.2"),
+        ("interleave" = Option<bool>, Query, description = "Whether posts recommended by different algorithms take turns", example = "true"),
+        ("include_read" = Option<bool>, Query, description = "Whether posts you have already read are included", example = "false")
     ),
     responses(
         (status = 200, description = "Recommendations retrieved successfully", body = Vec<PostRecommendation>),
@@ -81,7 +86,7 @@ pub async fn get_recommended_posts(
         ("post_id" = i64, Path, description = "Post ID to find similar posts for"),
         ("limit" = Option<i64>, Query, description = "Maximum number of recommendations", example = "10"),
         ("offset" = Option<i64>, Query, description = "Offset for pagination", example = "0"),
-        ("algorithm" = Option<String>, Query, description = "Algorithm to use: collaborative, content_based, hybrid, popular", example = "hybrid"),
+        ("algorithm" = Option<String>, Query, description = "Algorithm to use: collaborative, content_based, hybrid, popular, embedding", example = "hybrid"),
         ("include_tags" = Option<Vec<String>>, Query, description = "Tags to include in recommendations (comma-separated)", example = "rust,programming,webdev"),
         ("exclude_tags" = Option<Vec<String>>, Query, description = "Tags to exclude from recommendations (comma-separated)", example = "deprecated,outdated"),
         ("min_score" = Option<f64>, Query, description = "Minimum score threshold", example = "0.5")
@@ -132,7 +137,8 @@ pub async fn get_similar_posts(
     path = "/api/recommendations/refresh",
     tag = "recommendations",
     responses(
-        (status = 200, description = "Recommendation model refreshed successfully"),
+        (status = 200, description = "Recommendation model refreshed successfully", body = Object,
+            example = json!({"message": "Recommendation model refreshed successfully"})),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden - admin access required"),
         (status = 500, description = "Internal server error")
@@ -173,6 +179,146 @@ pub async fn refresh_recommendation_model(
     }
 }
 
+/// Preview a user's recommendations with score breakdowns (admin only)
+///
+/// Runs the same pipeline as `GET /api/recommendations` for the given user and lists every
+/// ranked post with the signals it was scored on, its recency boost and whether a diversity
+/// cap moved it down. Nothing is stored: recommendations generated for the preview are
+/// discarded.
+#[utoipa::path(
+    get,
+    path = "/api/admin/recommendations/preview",
+    tag = "recommendations",
+    params(
+        ("user_id" = Uuid, Query, description = "User to preview recommendations for"),
+        ("regenerate" = Option<bool>, Query, description = "Whether to generate the user's recommendations afresh instead of ranking the stored ones", example = "false"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of recommendations", example = "10"),
+        ("offset" = Option<i64>, Query, description = "Offset for pagination", example = "0"),
+        ("algorithm" = Option<String>, Query, description = "Algorithm to use: collaborative, content_based, hybrid, popular, embedding", example = "hybrid"),
+        ("include_tags" = Option<Vec<String>>, Query, description = "Tags to include in recommendations (comma-separated)", example = "rust,programming,webdev"),
+        ("exclude_tags" = Option<Vec<String>>, Query, description = "Tags to exclude from recommendations (comma-separated)", example = "deprecated,outdated"),
+        ("min_score" = Option<f64>, Query, description = "Minimum score threshold", example = "0.5"),
+        ("max_per_author" = Option<i64>, Query, description = "Posts by one author before the rest of theirs are moved after other authors' posts", example = "2"),
+        ("max_per_tag" = Option<i64>, Query, description = "Posts with one tag before the rest with it are moved after other posts", example = "3"),
+        ("recency_boost" = Option<f64>, Query, description = "How much the scores of new posts are raised, between 0 and 1", example = "0.2"),
+        ("interleave" = Option<bool>, Query, description = "Whether posts recommended by different algorithms take turns", example = "true"),
+        ("include_read" = Option<bool>, Query, description = "Whether posts the user has already read are included", example = "false")
+    ),
+    responses(
+        (status = 200, description = "Recommendations previewed", body = RecommendationPreview),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - admin access required"),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn preview_recommendations(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<RecommendationService>>,
+    Query(preview): Query<PreviewParams>,
+    Query(params): Query<RecommendationParams>,
+) -> impl IntoResponse {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Admin access required",
+            })),
+        );
+    }
+
+    match service
+        .preview_recommendations(
+            preview.user_id,
+            preview.regenerate.unwrap_or(false),
+            &params,
+        )
+        .await
+    {
+        Ok(preview) => (StatusCode::OK, Json(json!(preview))),
+        Err(err) => {
+            let status = match err {
+                RecommendationError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+                RecommendationError::NotFound => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            error!("Failed to preview recommendations: {}", err);
+            (
+                status,
+                Json(json!({
+                    "error": format!("Failed to preview recommendations: {}", err),
+                })),
+            )
+        }
+    }
+}
+
+fn digest_error_response(err: RecommendationError) -> (StatusCode, Json<serde_json::Value>) {
+    error!("Failed to update recommendation digest: {}", err);
+    let status = match err {
+        RecommendationError::NotFound => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(json!({ "error": err.to_string() })))
+}
+
+/// Get your recommendation digest setting
+#[utoipa::path(
+    get,
+    path = "/api/recommendations/digest",
+    tag = "recommendations",
+    responses(
+        (status = 200, description = "Digest setting retrieved", body = DigestSettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_digest_settings(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<RecommendationService>>,
+) -> impl IntoResponse {
+    match service.get_digest_settings(user.user_id).await {
+        Ok(settings) => (StatusCode::OK, Json(json!(settings))),
+        Err(err) => digest_error_response(err),
+    }
+}
+
+/// Turn the recommendation digest on or off
+///
+/// The digest is a weekly email of up to five recommended posts you have not read yet, sent
+/// when the instance has email set up. It is on by default.
+#[utoipa::path(
+    put,
+    path = "/api/recommendations/digest",
+    tag = "recommendations",
+    request_body = DigestSettings,
+    responses(
+        (status = 200, description = "Digest setting updated", body = DigestSettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_digest_settings(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<RecommendationService>>,
+    Json(settings): Json<DigestSettings>,
+) -> impl IntoResponse {
+    match service.set_digest_settings(user.user_id, &settings).await {
+        Ok(settings) => (StatusCode::OK, Json(json!(settings))),
+        Err(err) => digest_error_response(err),
+    }
+}
+
 /// Get personalized post recommendations for the current user - boxed version
 pub async fn get_recommended_posts_boxed(
     Extension(user): Extension<AuthUser>,
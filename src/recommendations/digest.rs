@@ -0,0 +1,306 @@
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::mailer::{mailer_from_env, Email, Mailer};
+use crate::post::service::meta_description;
+use crate::sitemap::site_base_url;
+use crate::tenant::middleware::with_blog_id;
+use crate::tenant::model::Blog;
+use crate::tenant::service::TenantService;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+pub const DIGEST_JOB: &str = "recommendation_digest";
+
+// How often digests go out, and how often instances check whether they are due
+const DIGEST_INTERVAL: Duration = Duration::from_secs(7 * 24 * 3600);
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+// Posts per digest
+const DIGEST_POSTS: i64 = 5;
+
+/// A recommended post as listed in a digest
+#[derive(Debug, Clone)]
+struct DigestPost {
+    post_id: i64,
+    title: String,
+    url: String,
+    excerpt: String,
+    reason: String,
+}
+
+/// A digest to send to one user
+#[derive(Debug)]
+struct Digest {
+    user_id: Uuid,
+    username: String,
+    email: String,
+    posts: Vec<DigestPost>,
+}
+
+fn render_digest(blog_name: &str, digest: &Digest) -> Email {
+    let mut text = format!(
+        "Hi {},\n\nHere are posts on {} you haven't read yet:\n",
+        digest.username, blog_name
+    );
+    for post in &digest.posts {
+        let _ = write!(
+            text,
+            "\n{}\n{}\n{}\n{}\n",
+            post.title, post.reason, post.excerpt, post.url
+        );
+    }
+    text.push_str(
+        "\nYou get this email every week. To stop it, turn off the recommendation digest in \
+         your account settings.\n",
+    );
+
+    let mut html = format!(
+        "<p>Hi {},</p>\n<p>Here are posts on {} you haven't read yet:</p>\n",
+        html_escape::encode_text(&digest.username),
+        html_escape::encode_text(blog_name)
+    );
+    for post in &digest.posts {
+        let _ = write!(
+            html,
+            "<h3><a href=\"{}\">{}</a></h3>\n<p><em>{}</em></p>\n<p>{}</p>\n",
+            html_escape::encode_double_quoted_attribute(&post.url),
+            html_escape::encode_text(&post.title),
+            html_escape::encode_text(&post.reason),
+            html_escape::encode_text(&post.excerpt)
+        );
+    }
+    html.push_str(
+        "<p><small>You get this email every week. To stop it, turn off the recommendation \
+         digest in your account settings.</small></p>\n",
+    );
+
+    Email {
+        to: digest.email.clone(),
+        subject: format!("Posts you missed on {}", blog_name),
+        text,
+        html,
+    }
+}
+
+/// Emails every user a weekly digest of their top recommended posts they have not seen.
+///
+/// Runs as a background job queued once a week by whichever instance notices first, and only
+/// when a mailer is configured. Each blog sends its own digest. Posts in a digest are recorded
+/// as `digest` interactions, so they are neither sent again nor recommended again later.
+pub struct DigestService {
+    pool: PgPool,
+    mailer: Arc<dyn Mailer>,
+    tenants: TenantService,
+}
+
+impl DigestService {
+    /// Digests sent with the configured mailer, or `None` when email is not set up
+    pub fn from_env(pool: PgPool) -> Option<Arc<Self>> {
+        let mailer = mailer_from_env()?;
+        info!("Sending recommendation digests by {}", mailer.name());
+        Some(Arc::new(Self {
+            tenants: TenantService::new(pool.clone()),
+            pool,
+            mailer,
+        }))
+    }
+
+    /// Queue the digests once a week
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DIGEST_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(DIGEST_JOB, json!({}), DIGEST_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule recommendation digests: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Send the digests of every blog. Returns how many were sent and how many failed.
+    pub async fn send_digests(&self) -> Result<(u64, u64), JobError> {
+        let blogs = self
+            .tenants
+            .list_blogs()
+            .await
+            .map_err(|e| JobError::InternalError(e.to_string()))?;
+
+        let (mut sent, mut failed) = (0, 0);
+        for blog in blogs {
+            let (blog_sent, blog_failed) = with_blog_id(blog.id, self.send_for_blog(&blog)).await?;
+            sent += blog_sent;
+            failed += blog_failed;
+        }
+
+        info!("Sent {} recommendation digests, {} failed", sent, failed);
+        Ok((sent, failed))
+    }
+
+    async fn send_for_blog(&self, blog: &Blog) -> Result<(u64, u64), JobError> {
+        let base_url = site_base_url(blog);
+        let (mut sent, mut failed) = (0, 0);
+
+        for digest in self.pending_digests(blog.id, &base_url).await? {
+            match self.mailer.send(&render_digest(&blog.name, &digest)).await {
+                Ok(()) => {
+                    self.record_sent(&digest).await?;
+                    sent += 1;
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to send recommendation digest to user {}: {}",
+                        digest.user_id, e
+                    );
+                    failed += 1;
+                }
+            }
+        }
+        Ok((sent, failed))
+    }
+
+    // Top recommendations of each user with the digest on that they have not read, or
+    // interacted with in any way, including through an earlier digest
+    async fn pending_digests(&self, blog_id: i64, base_url: &str) -> Result<Vec<Digest>, JobError> {
+        let query = sqlx::query(
+            r#"
+            WITH unseen AS (
+                SELECT
+                    r.user_id,
+                    r.post_id,
+                    r.reason,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY r.user_id ORDER BY r.score DESC, r.post_id DESC
+                    ) AS rank
+                FROM global.recommendations r
+                JOIN global.posts p ON p.id = r.post_id
+                JOIN global.users u ON u.id = r.user_id
+                WHERE r.expires_at > NOW()
+                    AND p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false
+                    AND u.recommendation_digest
+                    AND NOT EXISTS (
+                        SELECT 1 FROM global.post_reads pr
+                        WHERE pr.user_id = r.user_id AND pr.post_id = r.post_id
+                    )
+                    AND NOT EXISTS (
+                        SELECT 1 FROM global.user_interactions ui
+                        WHERE ui.user_id = r.user_id AND ui.post_id = r.post_id
+                    )
+            )
+            SELECT
+                un.user_id,
+                u.username,
+                u.email,
+                un.post_id,
+                un.reason,
+                p.title,
+                p.slug,
+                p.content
+            FROM unseen un
+            JOIN global.posts p ON p.id = un.post_id
+            JOIN global.users u ON u.id = un.user_id
+            WHERE un.rank <= $2
+            ORDER BY un.user_id, un.rank
+            "#,
+        )
+        .bind(blog_id)
+        .bind(DIGEST_POSTS)
+        .fetch_all(&self.pool);
+        let rows = timed("recommendations.digest_pending", query).await?;
+
+        let mut digests: Vec<Digest> = Vec::new();
+        for row in rows {
+            let user_id: Uuid = row.get("user_id");
+            let post = DigestPost {
+                post_id: row.get("post_id"),
+                title: row.get("title"),
+                url: format!("{}/posts/{}", base_url, row.get::<String, _>("slug")),
+                excerpt: meta_description(row.get("content")),
+                reason: row.get("reason"),
+            };
+
+            match digests.last_mut() {
+                Some(digest) if digest.user_id == user_id => digest.posts.push(post),
+                _ => digests.push(Digest {
+                    user_id,
+                    username: row.get("username"),
+                    email: row.get("email"),
+                    posts: vec![post],
+                }),
+            }
+        }
+        Ok(digests)
+    }
+
+    async fn record_sent(&self, digest: &Digest) -> Result<(), JobError> {
+        let post_ids: Vec<i64> = digest.posts.iter().map(|post| post.post_id).collect();
+        let query = sqlx::query(
+            r#"
+            INSERT INTO global.user_interactions (user_id, interaction_type, post_id)
+            SELECT $1, 'digest', post_id FROM UNNEST($2::BIGINT[]) AS post_id
+            "#,
+        )
+        .bind(digest.user_id)
+        .bind(&post_ids)
+        .execute(&self.pool);
+        timed("recommendations.digest_record", query).await?;
+        Ok(())
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let (sent, failed) = self.send_digests().await?;
+        Ok(json!({ "sent": sent, "failed": failed }))
+    }
+}
+
+impl JobHandler for DigestService {
+    fn kind(&self) -> &'static str {
+        DIGEST_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_digest_escapes_html() {
+        let digest = Digest {
+            user_id: Uuid::new_v4(),
+            username: "ada".to_string(),
+            email: "ada@example.com".to_string(),
+            posts: vec![DigestPost {
+                post_id: 1,
+                title: "Generics <T> in Rust".to_string(),
+                url: "https://example.com/posts/generics?a=1&b=2".to_string(),
+                excerpt: "Type parameters explained".to_string(),
+                reason: "Popular in #rust".to_string(),
+            }],
+        };
+
+        let email = render_digest("Rust & Co", &digest);
+        assert_eq!(email.to, "ada@example.com");
+        assert_eq!(email.subject, "Posts you missed on Rust & Co");
+        assert!(email
+            .text
+            .contains("Generics <T> in Rust\nPopular in #rust"));
+        assert!(email.html.contains("Generics &lt;T&gt; in Rust"));
+        assert!(email.html.contains("Rust &amp; Co"));
+        assert!(email
+            .html
+            .contains("href=\"https://example.com/posts/generics?a=1&amp;b=2\""));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::fnv;
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::error;
+
+const DEFAULT_HASHING_DIMENSIONS: usize = 512;
+const DEFAULT_API_DIMENSIONS: usize = 1536;
+const DEFAULT_API_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_API_MODEL: &str = "text-embedding-3-small";
+const API_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmbeddingError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Request error: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Embedding API rejected request with status {0}")]
+    Rejected(reqwest::StatusCode),
+
+    #[error("Invalid embedding response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Nothing to embed")]
+    EmptyInput,
+
+    #[error("Stored embeddings have {stored} dimensions but the embedder produces {configured}")]
+    DimensionMismatch { stored: usize, configured: usize },
+}
+
+/// Turns text into a vector, so posts can be compared by meaning
+pub trait Embedder: Send + Sync {
+    /// Identifies the model; posts embedded with another model are embedded again
+    fn model(&self) -> String;
+
+    fn dimensions(&self) -> usize;
+
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>, EmbeddingError>>;
+}
+
+/// Local embedder hashing words into a fixed number of buckets. Needs no model or network
+/// access and catches shared vocabulary, though not synonyms.
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self { dimensions }
+    }
+
+    fn vector(&self, text: &str) -> Result<Vec<f32>, EmbeddingError> {
+        let mut vector = vec![0.0_f32; self.dimensions];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() > 1)
+        {
+            let hash = fnv::hash(word.to_lowercase().into_bytes());
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % self.dimensions as u64) as usize] += sign;
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            return Err(EmbeddingError::EmptyInput);
+        }
+        vector.iter_mut().for_each(|x| *x /= norm);
+        Ok(vector)
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn model(&self) -> String {
+        format!("hashing-{}", self.dimensions)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>, EmbeddingError>> {
+        Box::pin(async move { self.vector(text) })
+    }
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+/// Embedder calling an OpenAI-compatible `/embeddings` endpoint, which hosted providers and
+/// local model servers alike offer
+pub struct ApiEmbedder {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    model: String,
+    dimensions: usize,
+}
+
+impl Embedder for ApiEmbedder {
+    fn model(&self) -> String {
+        format!("api:{}-{}", self.model, self.dimensions)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>, EmbeddingError>> {
+        Box::pin(async move {
+            if text.trim().is_empty() {
+                return Err(EmbeddingError::EmptyInput);
+            }
+
+            let mut request = self
+                .client
+                .post(format!("{}/embeddings", self.url.trim_end_matches('/')))
+                .json(&serde_json::json!({
+                    "model": self.model,
+                    "input": text,
+                    "dimensions": self.dimensions,
+                }));
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(EmbeddingError::Rejected(response.status()));
+            }
+
+            let embedding = response
+                .json::<EmbeddingResponse>()
+                .await?
+                .data
+                .into_iter()
+                .next()
+                .ok_or_else(|| EmbeddingError::InvalidResponse("no embedding".to_string()))?
+                .embedding;
+            if embedding.len() != self.dimensions {
+                return Err(EmbeddingError::InvalidResponse(format!(
+                    "expected {} dimensions, got {}",
+                    self.dimensions,
+                    embedding.len()
+                )));
+            }
+            Ok(embedding)
+        })
+    }
+}
+
+/// Which embedder to use, read from the environment:
+///
+/// - `EMBEDDINGS_PROVIDER`: `hashing` or `api`; unset disables embeddings
+/// - `EMBEDDINGS_DIMENSIONS` (default 512 for `hashing`, 1536 for `api`)
+/// - `EMBEDDINGS_API_URL` (default `https://api.openai.com/v1`), `EMBEDDINGS_API_KEY`,
+///   `EMBEDDINGS_MODEL` (default `text-embedding-3-small`)
+pub fn embedder_from_env() -> Option<Arc<dyn Embedder>> {
+    let kind = std::env::var("EMBEDDINGS_PROVIDER").ok()?;
+    let dimensions = std::env::var("EMBEDDINGS_DIMENSIONS")
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|dimensions| *dimensions > 0);
+
+    match kind.to_lowercase().as_str() {
+        "hashing" => Some(Arc::new(HashingEmbedder::new(
+            dimensions.unwrap_or(DEFAULT_HASHING_DIMENSIONS),
+        ))),
+        "api" => match reqwest::Client::builder().timeout(API_TIMEOUT).build() {
+            Ok(client) => Some(Arc::new(ApiEmbedder {
+                client,
+                url: std::env::var("EMBEDDINGS_API_URL")
+                    .unwrap_or_else(|_| DEFAULT_API_URL.to_string()),
+                api_key: std::env::var("EMBEDDINGS_API_KEY").ok(),
+                model: std::env::var("EMBEDDINGS_MODEL")
+                    .unwrap_or_else(|_| DEFAULT_API_MODEL.to_string()),
+                dimensions: dimensions.unwrap_or(DEFAULT_API_DIMENSIONS),
+            })),
+            Err(e) => {
+                error!("Failed to create embedding API client: {}", e);
+                None
+            }
+        },
+        other => {
+            error!("Unknown EMBEDDINGS_PROVIDER: {}", other);
+            None
+        }
+    }
+}
+
+/// Text form of a vector, as pgvector parses it
+pub fn vector_literal(vector: &[f32]) -> String {
+    let values: Vec<String> = vector.iter().map(|x| x.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_hashing_embedder_compares_vocabulary() {
+        let embedder = HashingEmbedder::new(256);
+        let rust = embedder.vector("Async Rust with Tokio and axum").unwrap();
+        let rust_again = embedder.vector("Writing async services in Rust").unwrap();
+        let baking = embedder.vector("Baking sourdough bread at home").unwrap();
+
+        assert_eq!(rust.len(), 256);
+        assert!((cosine(&rust, &rust) - 1.0).abs() < 1e-5);
+        assert!(cosine(&rust, &rust_again) > cosine(&rust, &baking));
+        assert!(matches!(
+            embedder.vector("- a !"),
+            Err(EmbeddingError::EmptyInput)
+        ));
+    }
+
+    #[test]
+    fn test_vector_literal() {
+        assert_eq!(vector_literal(&[0.5, -1.0, 0.0]), "[0.5,-1,0]");
+    }
+}
@@ -0,0 +1,200 @@
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::recommendations::embedder::{
+    embedder_from_env, vector_literal, Embedder, EmbeddingError,
+};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+pub const EMBEDDING_JOB: &str = "embed_posts";
+
+// How often new and edited posts are embedded, and how often instances check whether a run
+// is due
+const EMBEDDING_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const EMBEDDING_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Posts loaded per query, and embedded per run at most so a large backlog is spread out
+const EMBEDDING_BATCH_SIZE: i64 = 50;
+const MAX_EMBEDDED_PER_RUN: u64 = 1000;
+// Start of a post that is embedded; more rarely changes what a post is about
+const MAX_EMBEDDING_INPUT_CHARS: usize = 8000;
+
+/// Keeps an embedding of every published post in `global.post_embeddings`, a pgvector
+/// table, for the `embedding` recommendation algorithm.
+///
+/// Embedding is optional: it is enabled by configuring an embedder and needs the `vector`
+/// extension in Postgres. Posts are embedded by a background job queued every ten minutes,
+/// which picks up new posts, edited posts and posts embedded with another model.
+pub struct EmbeddingService {
+    pool: PgPool,
+    embedder: Arc<dyn Embedder>,
+}
+
+impl EmbeddingService {
+    /// Embeddings with the configured embedder, or `None` when none is configured or the
+    /// embeddings table cannot be set up
+    pub async fn from_env(pool: PgPool) -> Option<Arc<Self>> {
+        let embedder = embedder_from_env()?;
+        let service = Self { pool, embedder };
+
+        match service.init_schema().await {
+            Ok(()) => {
+                info!(
+                    "Embedding posts with {} for recommendations",
+                    service.embedder.model()
+                );
+                Some(Arc::new(service))
+            }
+            Err(e) => {
+                error!("Failed to set up post embeddings, disabling them: {}", e);
+                None
+            }
+        }
+    }
+
+    // The vector column is sized for the embedder, so the table is created here rather than
+    // in schema.sql, which also has to work without pgvector
+    async fn init_schema(&self) -> Result<(), EmbeddingError> {
+        let dimensions = self.embedder.dimensions();
+        sqlx::query(&format!(
+            r#"
+            CREATE EXTENSION IF NOT EXISTS vector;
+            CREATE TABLE IF NOT EXISTS global.post_embeddings (
+                post_id BIGINT PRIMARY KEY REFERENCES global.posts(id) ON DELETE CASCADE,
+                embedding vector({}) NOT NULL,
+                model TEXT NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            );
+            CREATE INDEX IF NOT EXISTS idx_post_embeddings_embedding
+                ON global.post_embeddings USING hnsw (embedding vector_cosine_ops);
+            "#,
+            dimensions
+        ))
+        .execute(&self.pool)
+        .await?;
+
+        // A table left by an embedder of another size has to be dropped to switch
+        let stored = sqlx::query_scalar::<_, i32>(
+            r#"
+            SELECT atttypmod FROM pg_attribute
+            WHERE attrelid = 'global.post_embeddings'::regclass AND attname = 'embedding'
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await? as usize;
+        if stored != dimensions {
+            return Err(EmbeddingError::DimensionMismatch {
+                stored,
+                configured: dimensions,
+            });
+        }
+        Ok(())
+    }
+
+    /// Queue an embedding run every ten minutes
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EMBEDDING_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(EMBEDDING_JOB, json!({}), EMBEDDING_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule post embedding: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Embed published posts without an up to date embedding. Returns how many were
+    /// embedded and how many failed; failed posts are tried again on the next run.
+    pub async fn embed_pending(&self) -> Result<(u64, u64), JobError> {
+        let model = self.embedder.model();
+        let (mut embedded, mut failed) = (0, 0);
+        let mut after_id = 0_i64;
+
+        while embedded < MAX_EMBEDDED_PER_RUN {
+            let query = sqlx::query(
+                r#"
+                SELECT p.id, p.title, p.content
+                FROM global.posts p
+                LEFT JOIN global.post_embeddings e ON e.post_id = p.id
+                WHERE p.is_draft = false AND p.is_deleted = false AND p.id > $2
+                    AND (e.post_id IS NULL OR e.model != $1 OR e.updated_at < p.updated_at)
+                ORDER BY p.id
+                LIMIT $3
+                "#,
+            )
+            .bind(&model)
+            .bind(after_id)
+            .bind(EMBEDDING_BATCH_SIZE)
+            .fetch_all(&self.pool);
+            let rows = timed("embeddings.pending", query).await?;
+            if rows.is_empty() {
+                break;
+            }
+
+            for row in rows {
+                let post_id: i64 = row.get("id");
+                after_id = post_id;
+                let title: String = row.get("title");
+                let content: String = row.get("content");
+                let text: String = format!("{}\n\n{}", title, content)
+                    .chars()
+                    .take(MAX_EMBEDDING_INPUT_CHARS)
+                    .collect();
+
+                match self.embedder.embed(&text).await {
+                    Ok(embedding) => {
+                        self.store(post_id, &embedding, &model).await?;
+                        embedded += 1;
+                    }
+                    Err(e) => {
+                        warn!("Failed to embed post {}: {}", post_id, e);
+                        failed += 1;
+                    }
+                }
+            }
+        }
+
+        info!("Embedded {} posts, {} failed", embedded, failed);
+        Ok((embedded, failed))
+    }
+
+    async fn store(&self, post_id: i64, embedding: &[f32], model: &str) -> Result<(), JobError> {
+        let query = sqlx::query(
+            r#"
+            INSERT INTO global.post_embeddings (post_id, embedding, model, updated_at)
+            VALUES ($1, $2::vector, $3, NOW())
+            ON CONFLICT (post_id) DO UPDATE
+            SET embedding = EXCLUDED.embedding, model = EXCLUDED.model, updated_at = NOW()
+            "#,
+        )
+        .bind(post_id)
+        .bind(vector_literal(embedding))
+        .bind(model)
+        .execute(&self.pool);
+        timed("embeddings.store", query).await?;
+        Ok(())
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let (embedded, failed) = self.embed_pending().await?;
+        Ok(json!({ "embedded": embedded, "failed": failed }))
+    }
+}
+
+impl JobHandler for EmbeddingService {
+    fn kind(&self) -> &'static str {
+        EMBEDDING_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
@@ -0,0 +1,320 @@
+use crate::recommendations::model::{
+    PostRecommendation, RecommendationError, RecommendationParams,
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+const DEFAULT_MAX_PER_AUTHOR: usize = 2;
+const DEFAULT_MAX_PER_TAG: usize = 3;
+const DEFAULT_RECENCY_BOOST: f64 = 0.2;
+// The recency boost of a post halves every this many days
+const RECENCY_HALF_LIFE_DAYS: f64 = 14.0;
+
+/// How a list of recommendations is reordered before it is served
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankingConstraints {
+    /// Posts by one author before the rest of theirs go to the back of the list
+    pub max_per_author: usize,
+    /// Posts with one tag before the rest with it go to the back of the list
+    pub max_per_tag: usize,
+    /// Share by which a brand new post's score is raised, fading with its age
+    pub recency_boost: f64,
+    /// Whether posts from different algorithms take turns
+    pub interleave: bool,
+}
+
+impl Default for RankingConstraints {
+    fn default() -> Self {
+        Self {
+            max_per_author: DEFAULT_MAX_PER_AUTHOR,
+            max_per_tag: DEFAULT_MAX_PER_TAG,
+            recency_boost: DEFAULT_RECENCY_BOOST,
+            interleave: true,
+        }
+    }
+}
+
+impl RankingConstraints {
+    pub fn from_params(params: &RecommendationParams) -> Result<Self, RecommendationError> {
+        let defaults = Self::default();
+        let cap = |value: Option<i64>, default: usize, name: &str| match value {
+            None => Ok(default),
+            Some(value) if value >= 1 => Ok(value as usize),
+            Some(_) => Err(RecommendationError::InvalidParameter(format!(
+                "{} must be at least 1",
+                name
+            ))),
+        };
+
+        let recency_boost = params.recency_boost.unwrap_or(defaults.recency_boost);
+        if !(0.0..=1.0).contains(&recency_boost) {
+            return Err(RecommendationError::InvalidParameter(
+                "recency_boost must be between 0 and 1".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            max_per_author: cap(
+                params.max_per_author,
+                defaults.max_per_author,
+                "max_per_author",
+            )?,
+            max_per_tag: cap(params.max_per_tag, defaults.max_per_tag, "max_per_tag")?,
+            recency_boost,
+            interleave: params.interleave.unwrap_or(defaults.interleave),
+        })
+    }
+}
+
+/// A stored recommendation along with what ranking needs to know about it
+#[derive(Debug)]
+pub struct Candidate {
+    pub recommendation: PostRecommendation,
+    pub author_id: Uuid,
+    /// Algorithm that recommended the post
+    pub source: String,
+    /// Signals the algorithm scored the post on
+    pub features: Value,
+}
+
+/// A candidate after ranking, with how its score came about
+#[derive(Debug)]
+pub struct RankedCandidate {
+    /// The candidate, its score now including the recency boost
+    pub candidate: Candidate,
+    /// Score the algorithm gave the post
+    pub base_score: f64,
+    /// Factor the recency boost multiplied the score by
+    pub recency_multiplier: f64,
+    /// Whether the post was moved back for going over the per-author or per-tag cap
+    pub capped: bool,
+}
+
+fn recency_multiplier(created_at: DateTime<Utc>, boost: f64, now: DateTime<Utc>) -> f64 {
+    let age_days = (now - created_at).num_seconds().max(0) as f64 / 86_400.0;
+    1.0 + boost * 0.5_f64.powf(age_days / RECENCY_HALF_LIFE_DAYS)
+}
+
+// Take turns between sources, starting with the one whose best post scores highest
+fn interleave(candidates: Vec<RankedCandidate>) -> Vec<RankedCandidate> {
+    let mut sources: Vec<(String, VecDeque<RankedCandidate>)> = Vec::new();
+    for ranked in candidates {
+        match sources
+            .iter_mut()
+            .find(|(source, _)| *source == ranked.candidate.source)
+        {
+            Some((_, queue)) => queue.push_back(ranked),
+            None => sources.push((ranked.candidate.source.clone(), VecDeque::from([ranked]))),
+        }
+    }
+
+    let mut interleaved = Vec::new();
+    while !sources.is_empty() {
+        for (_, queue) in sources.iter_mut() {
+            interleaved.extend(queue.pop_front());
+        }
+        sources.retain(|(_, queue)| !queue.is_empty());
+    }
+    interleaved
+}
+
+/// Reorder recommendations: raise the scores of recent posts, let the algorithms that
+/// recommended them take turns, and move posts past the per-author and per-tag caps after
+/// the rest. Capped posts are kept so blogs with few authors or tags still fill a page.
+pub fn rank(
+    candidates: Vec<Candidate>,
+    constraints: &RankingConstraints,
+    now: DateTime<Utc>,
+) -> Vec<PostRecommendation> {
+    rank_explained(candidates, constraints, now)
+        .into_iter()
+        .map(|ranked| ranked.candidate.recommendation)
+        .collect()
+}
+
+/// [`rank`], keeping what each step did to each candidate
+pub fn rank_explained(
+    candidates: Vec<Candidate>,
+    constraints: &RankingConstraints,
+    now: DateTime<Utc>,
+) -> Vec<RankedCandidate> {
+    let mut candidates: Vec<RankedCandidate> = candidates
+        .into_iter()
+        .map(|mut candidate| {
+            let recommendation = &mut candidate.recommendation;
+            let base_score = recommendation.score;
+            let recency_multiplier =
+                recency_multiplier(recommendation.created_at, constraints.recency_boost, now);
+            recommendation.score = base_score * recency_multiplier;
+            RankedCandidate {
+                candidate,
+                base_score,
+                recency_multiplier,
+                capped: false,
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        let (a, b) = (&a.candidate.recommendation, &b.candidate.recommendation);
+        b.score.total_cmp(&a.score).then(b.post_id.cmp(&a.post_id))
+    });
+
+    if constraints.interleave {
+        candidates = interleave(candidates);
+    }
+
+    let mut per_author: HashMap<Uuid, usize> = HashMap::new();
+    let mut per_tag: HashMap<String, usize> = HashMap::new();
+    let mut ranked = Vec::with_capacity(candidates.len());
+    let mut capped = Vec::new();
+
+    for mut candidate in candidates {
+        let author_id = candidate.candidate.author_id;
+        let tags = &candidate.candidate.recommendation.tags;
+        let author_full =
+            per_author.get(&author_id).copied().unwrap_or(0) >= constraints.max_per_author;
+        let tag_full = tags
+            .iter()
+            .any(|tag| per_tag.get(tag).copied().unwrap_or(0) >= constraints.max_per_tag);
+
+        if author_full || tag_full {
+            candidate.capped = true;
+            capped.push(candidate);
+            continue;
+        }
+
+        *per_author.entry(author_id).or_default() += 1;
+        for tag in tags {
+            *per_tag.entry(tag.clone()).or_default() += 1;
+        }
+        ranked.push(candidate);
+    }
+
+    ranked.extend(capped);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn candidate(
+        post_id: i64,
+        score: f64,
+        author_id: Uuid,
+        source: &str,
+        tags: &[&str],
+        age_days: i64,
+        now: DateTime<Utc>,
+    ) -> Candidate {
+        Candidate {
+            recommendation: PostRecommendation {
+                post_id,
+                title: format!("Post {}", post_id),
+                score,
+                similarity: None,
+                reason: None,
+                author: "author".to_string(),
+                author_avatar_url: None,
+                created_at: now - Duration::days(age_days),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                excerpt: None,
+            },
+            author_id,
+            source: source.to_string(),
+            features: Value::Null,
+        }
+    }
+
+    fn ids(ranked: &[PostRecommendation]) -> Vec<i64> {
+        ranked.iter().map(|r| r.post_id).collect()
+    }
+
+    #[test]
+    fn test_caps_move_posts_to_the_back() {
+        let now = Utc::now();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let candidates = vec![
+            candidate(1, 0.9, a, "popular", &[], 100, now),
+            candidate(2, 0.8, a, "popular", &[], 100, now),
+            candidate(3, 0.7, a, "popular", &[], 100, now),
+            candidate(4, 0.6, b, "popular", &["rust"], 100, now),
+            candidate(5, 0.5, b, "popular", &["rust"], 100, now),
+        ];
+        let constraints = RankingConstraints {
+            max_per_author: 2,
+            max_per_tag: 1,
+            recency_boost: 0.0,
+            interleave: false,
+        };
+
+        assert_eq!(
+            ids(&rank(candidates, &constraints, now)),
+            vec![1, 2, 4, 3, 5]
+        );
+    }
+
+    #[test]
+    fn test_ranking_is_explained() {
+        let now = Utc::now();
+        let author = Uuid::new_v4();
+        let candidates = vec![
+            candidate(1, 0.5, author, "popular", &[], 0, now),
+            candidate(2, 0.4, author, "popular", &[], 14, now),
+        ];
+        let constraints = RankingConstraints {
+            max_per_author: 1,
+            interleave: false,
+            ..RankingConstraints::default()
+        };
+
+        let ranked = rank_explained(candidates, &constraints, now);
+        assert_eq!(ranked[0].base_score, 0.5);
+        assert!((ranked[0].recency_multiplier - 1.2).abs() < 1e-9);
+        assert!(!ranked[0].capped);
+        assert!((ranked[1].recency_multiplier - 1.1).abs() < 1e-9);
+        assert!((ranked[1].candidate.recommendation.score - 0.44).abs() < 1e-9);
+        assert!(ranked[1].capped);
+    }
+
+    #[test]
+    fn test_recent_posts_are_boosted() {
+        let now = Utc::now();
+        let candidates = vec![
+            candidate(1, 0.80, Uuid::new_v4(), "popular", &[], 365, now),
+            candidate(2, 0.75, Uuid::new_v4(), "popular", &[], 0, now),
+        ];
+        let constraints = RankingConstraints {
+            interleave: false,
+            ..RankingConstraints::default()
+        };
+
+        let ranked = rank(candidates, &constraints, now);
+        assert_eq!(ids(&ranked), vec![2, 1]);
+        assert!((ranked[0].score - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sources_take_turns() {
+        let now = Utc::now();
+        let candidates = vec![
+            candidate(1, 0.9, Uuid::new_v4(), "collaborative", &[], 100, now),
+            candidate(2, 0.8, Uuid::new_v4(), "collaborative", &[], 100, now),
+            candidate(3, 0.7, Uuid::new_v4(), "collaborative", &[], 100, now),
+            candidate(4, 0.5, Uuid::new_v4(), "popular", &[], 100, now),
+            candidate(5, 0.4, Uuid::new_v4(), "popular", &[], 100, now),
+        ];
+        let constraints = RankingConstraints {
+            recency_boost: 0.0,
+            ..RankingConstraints::default()
+        };
+
+        assert_eq!(
+            ids(&rank(candidates, &constraints, now)),
+            vec![1, 4, 2, 5, 3]
+        );
+    }
+}
@@ -1,4 +1,7 @@
 pub mod controller;
+pub mod digest;
+pub mod embedder;
+pub mod embedding;
 pub mod engine;
 pub mod model;
 pub mod scheduler;
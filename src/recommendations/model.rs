@@ -12,6 +12,7 @@ pub struct Recommendation {
     pub post_id: i64,
     pub score: f64,
     pub recommendation_type: String,
+    pub reason: String,
     pub created_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
 }
@@ -32,8 +33,13 @@ pub struct PostRecommendation {
     pub title: String,
     pub score: f64,
     pub similarity: Option<f64>,
+    /// Why the post is recommended to you
+    #[schema(example = "Because you read Getting started with Rust")]
+    pub reason: Option<String>,
     pub author: String,
-    #[schema(value_type = String, format = "date-time", example = "2025-03-26T12:00:00Z")]
+    /// Uploaded avatar of the author, or their Gravatar
+    pub author_avatar_url: Option<String>,
+    #[schema(example = "2025-03-26T12:00:00Z")]
     pub created_at: DateTime<Utc>,
     pub tags: Vec<String>,
     pub excerpt: Option<String>,
@@ -51,7 +57,8 @@ pub struct RecommendationParams {
     #[schema(example = "0", default = "0", minimum = 0)]
     pub offset: Option<i64>,
 
-    /// Algorithm to use: "collaborative", "content_based", "hybrid", "popular"
+    /// Algorithm to use: "collaborative", "content_based", "hybrid", "popular", or
+    /// "embedding" when post embeddings are enabled
     #[schema(example = "hybrid")]
     pub algorithm: Option<String>,
 
@@ -66,6 +73,80 @@ pub struct RecommendationParams {
     /// Minimum score threshold
     #[schema(example = "0.5", minimum = 0.0, maximum = 1.0)]
     pub min_score: Option<f64>,
+
+    /// Posts by one author before the rest of theirs are moved after other authors' posts
+    #[schema(example = "2", default = "2", minimum = 1)]
+    pub max_per_author: Option<i64>,
+
+    /// Posts with one tag before the rest with it are moved after other posts
+    #[schema(example = "3", default = "3", minimum = 1)]
+    pub max_per_tag: Option<i64>,
+
+    /// How much the scores of new posts are raised, halving every 14 days of a post's age
+    #[schema(example = "0.2", default = "0.2", minimum = 0.0, maximum = 1.0)]
+    pub recency_boost: Option<f64>,
+
+    /// Whether posts recommended by different algorithms take turns
+    #[schema(example = "true", default = "true")]
+    pub interleave: Option<bool>,
+
+    /// Whether posts the user has already read are included
+    #[schema(example = "false", default = "false")]
+    pub include_read: Option<bool>,
+}
+
+/// User whose recommendations an admin previews
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(style = Form)]
+pub struct PreviewParams {
+    /// User to preview recommendations for
+    pub user_id: Uuid,
+
+    /// Whether to generate the user's recommendations afresh instead of ranking the stored ones
+    pub regenerate: Option<bool>,
+}
+
+/// A recommendation as ranked for a user, with how its score came about
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScoredCandidate {
+    /// Position in the user's recommendations, from 1
+    pub rank: i64,
+    pub post_id: i64,
+    pub title: String,
+    pub author: String,
+    /// Algorithm that recommended the post
+    #[schema(example = "collaborative")]
+    pub source: String,
+    pub reason: Option<String>,
+    /// Score given by the algorithm
+    pub base_score: f64,
+    /// Signals the algorithm scored the post on
+    #[schema(value_type = Object, example = json!({"co_reader_interactions": 12, "via_post_id": 42}))]
+    pub features: serde_json::Value,
+    /// Factor new posts are boosted by
+    pub recency_multiplier: f64,
+    /// Score the post is ranked by
+    pub final_score: f64,
+    /// Whether the post was moved down for going over the per author or per tag cap
+    pub capped: bool,
+}
+
+/// What a user would be recommended, as previewed by an admin
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecommendationPreview {
+    pub user_id: Uuid,
+    #[schema(example = "hybrid")]
+    pub algorithm: String,
+    /// Whether the recommendations were generated afresh for the preview
+    pub regenerated: bool,
+    pub candidates: Vec<ScoredCandidate>,
+}
+
+/// Whether a user gets the weekly "posts you missed" email
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DigestSettings {
+    #[schema(example = "true")]
+    pub enabled: bool,
 }
 
 /// Request to generate recommendations
@@ -79,7 +160,7 @@ pub struct GenerateRecommendationsRequest {
     #[schema(example = "20", default = "10", minimum = 1, maximum = 100)]
     pub limit_per_user: Option<i64>,
 
-    /// Algorithm to use: "collaborative", "content_based", "popular"
+    /// Algorithm to use: "collaborative", "content_based", "popular", "embedding"
     #[schema(example = "hybrid")]
     pub algorithm: Option<String>,
 
@@ -1,14 +1,36 @@
 use crate::cache::redis::RedisCache;
+use crate::db::instrument::timed;
+use crate::post::service::meta_description;
+use crate::recommendations::engine::{self, Candidate, RankingConstraints};
 use crate::recommendations::model::{
-    GenerateRecommendationsRequest, PostRecommendation, RecommendationError, RecommendationParams,
+    DigestSettings, GenerateRecommendationsRequest, PostRecommendation, RecommendationError,
+    RecommendationParams, RecommendationPreview, ScoredCandidate,
 };
-use sqlx::PgPool;
+use crate::tenant::middleware::{current_blog_id, with_blog_id};
+use chrono::{Duration, Utc};
+use sqlx::{PgConnection, PgPool, Row};
 use std::sync::{Arc, Mutex};
-use tracing::info;
+use tracing::{error, info};
 use uuid::Uuid;
 
-const RECOMMENDATION_CACHE_TTL: u64 = 3600; // 1 hour
 const DEFAULT_RECOMMENDATION_LIMIT: i64 = 20;
+const MAX_RECOMMENDATION_LIMIT: i64 = 100;
+// Personalized recommendations are kept longer than popular ones, which go stale sooner
+const PERSONALIZED_EXPIRY_DAYS: i64 = 7;
+const POPULAR_EXPIRY_DAYS: i64 = 5;
+// Stored recommendations ranked per request; pages are cut from the ranked list so they
+// stay consistent while ranking moves posts around
+const MAX_RANKED_CANDIDATES: i64 = 500;
+
+// Number and offset of the recommendations on the requested page
+fn page(params: &RecommendationParams) -> (usize, usize) {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_RECOMMENDATION_LIMIT)
+        .clamp(1, MAX_RECOMMENDATION_LIMIT);
+    let offset = params.offset.unwrap_or(0).max(0);
+    (limit as usize, offset as usize)
+}
 
 /// Status of recommendation generation
 #[derive(Debug, Clone)]
@@ -19,11 +41,61 @@ pub enum GenerationStatus {
     Completed(String),
 }
 
+/// Algorithms recommendations are generated with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Posts engaged with by readers of the same posts; "Because you read X"
+    Collaborative,
+    /// Unread posts sharing tags with the posts a user engaged with; "Popular in #tag"
+    ContentBased,
+    /// The posts of the blog with the highest popularity score
+    Popular,
+    /// A mix of the three
+    Hybrid,
+    /// Posts nearest to the average embedding of the posts a user read or liked; "Similar to
+    /// X". Only available when post embeddings are enabled.
+    Embedding,
+}
+
+impl Algorithm {
+    pub fn parse(value: Option<&str>) -> Result<Self, RecommendationError> {
+        match value.unwrap_or("hybrid") {
+            "collaborative" => Ok(Algorithm::Collaborative),
+            "content_based" => Ok(Algorithm::ContentBased),
+            "popular" => Ok(Algorithm::Popular),
+            "hybrid" => Ok(Algorithm::Hybrid),
+            "embedding" => Ok(Algorithm::Embedding),
+            other => Err(RecommendationError::InvalidParameter(format!(
+                "Unknown algorithm {}",
+                other
+            ))),
+        }
+    }
+
+    /// Name the algorithm is requested by
+    pub fn name(&self) -> &'static str {
+        self.recommendation_type().unwrap_or("hybrid")
+    }
+
+    /// Stored `recommendation_type` of the recommendations it generates; `None` for hybrid,
+    /// which stores the type of each part
+    fn recommendation_type(&self) -> Option<&'static str> {
+        match self {
+            Algorithm::Collaborative => Some("collaborative"),
+            Algorithm::ContentBased => Some("content_based"),
+            Algorithm::Popular => Some("popular"),
+            Algorithm::Hybrid => None,
+            Algorithm::Embedding => Some("embedding"),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct RecommendationService {
     pool: PgPool,
     redis_cache: Option<RedisCache>,
     generation_status: Arc<Mutex<GenerationStatus>>,
+    embeddings_enabled: bool,
 }
 
 impl RecommendationService {
@@ -32,163 +104,316 @@ impl RecommendationService {
             pool,
             redis_cache,
             generation_status: Arc::new(Mutex::new(GenerationStatus::Idle)),
+            embeddings_enabled: false,
+        }
+    }
+
+    /// Offer the `embedding` algorithm, once post embeddings are set up
+    pub fn with_embeddings(mut self, enabled: bool) -> Self {
+        self.embeddings_enabled = enabled;
+        self
+    }
+
+    fn parse_algorithm(&self, value: Option<&str>) -> Result<Algorithm, RecommendationError> {
+        let algorithm = Algorithm::parse(value)?;
+        if algorithm == Algorithm::Embedding && !self.embeddings_enabled {
+            return Err(RecommendationError::InvalidParameter(
+                "Embedding recommendations are not enabled".to_string(),
+            ));
         }
+        Ok(algorithm)
     }
 
-    /// Get recommendations for a user
+    /// Get recommendations for a user in the current blog, best first, each with the reason
+    /// it was recommended. Users without any are given a fresh set first. Posts the user has
+    /// read since are left out unless `include_read` is set.
     pub async fn get_recommendations_for_user(
         &self,
-        _user_id: Uuid,
+        user_id: Uuid,
         params: &RecommendationParams,
     ) -> Result<Vec<PostRecommendation>, RecommendationError> {
-        let _limit = params.limit.unwrap_or(DEFAULT_RECOMMENDATION_LIMIT);
+        let constraints = RankingConstraints::from_params(params)?;
+        let (limit, offset) = page(params);
 
-        // TODO: Fix the SQL queries below once the database schema includes the required tables and columns.
-        // The following queries reference tables and columns that don't exist in the current database schema:
-        // - global.recommendations
-        // - global.user_interactions
-        // - p.author_id
+        let mut conn = self.pool.acquire().await?;
+        let candidates = self
+            .candidates_for_user(&mut conn, user_id, params, false)
+            .await?;
 
-        // Return an empty vector for now
-        info!("Returning empty recommendations list due to database schema issues");
-        return Ok(Vec::new());
+        Ok(engine::rank(candidates, &constraints, Utc::now())
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect())
+    }
 
-        /* Commented out due to database schema issues
-        // Query database for recommendations
-        let rows = sqlx::query!(
+    /// Run the recommendation pipeline for any user as they would see it, with how each
+    /// score came about. Nothing is kept: recommendations generated on the way are rolled
+    /// back. With `regenerate` the user's recommendations are first generated afresh, as
+    /// the next refresh would.
+    pub async fn preview_recommendations(
+        &self,
+        user_id: Uuid,
+        regenerate: bool,
+        params: &RecommendationParams,
+    ) -> Result<RecommendationPreview, RecommendationError> {
+        let algorithm = self.parse_algorithm(params.algorithm.as_deref())?;
+        let constraints = RankingConstraints::from_params(params)?;
+        let (limit, offset) = page(params);
+
+        let user_exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM global.users WHERE id = $1)",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+        if !user_exists {
+            return Err(RecommendationError::NotFound);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let candidates = self
+            .candidates_for_user(&mut tx, user_id, params, regenerate)
+            .await?;
+        tx.rollback().await?;
+
+        let candidates = engine::rank_explained(candidates, &constraints, Utc::now())
+            .into_iter()
+            .enumerate()
+            .skip(offset)
+            .take(limit)
+            .map(|(position, ranked)| {
+                let recommendation = ranked.candidate.recommendation;
+                ScoredCandidate {
+                    rank: position as i64 + 1,
+                    post_id: recommendation.post_id,
+                    title: recommendation.title,
+                    author: recommendation.author,
+                    source: ranked.candidate.source,
+                    reason: recommendation.reason,
+                    base_score: ranked.base_score,
+                    features: ranked.candidate.features,
+                    recency_multiplier: ranked.recency_multiplier,
+                    final_score: recommendation.score,
+                    capped: ranked.capped,
+                }
+            })
+            .collect();
+
+        Ok(RecommendationPreview {
+            user_id,
+            algorithm: algorithm.name().to_string(),
+            regenerated: regenerate,
+            candidates,
+        })
+    }
+
+    // Stored recommendations of a user that ranking starts from, generating a set first when
+    // the user has none, or afresh with `regenerate`
+    async fn candidates_for_user(
+        &self,
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        params: &RecommendationParams,
+        regenerate: bool,
+    ) -> Result<Vec<Candidate>, RecommendationError> {
+        let algorithm = self.parse_algorithm(params.algorithm.as_deref())?;
+
+        if regenerate {
+            Self::clear_for_user(&mut *conn, user_id).await?;
+        }
+        if regenerate
+            || (params.offset.unwrap_or(0) <= 0
+                && !Self::has_recommendations(&mut *conn, user_id, algorithm).await?)
+        {
+            Self::generate_for_user(&mut *conn, user_id, algorithm, DEFAULT_RECOMMENDATION_LIMIT)
+                .await?;
+        }
+
+        let query = sqlx::query(
             r#"
-            WITH recs AS (
-                SELECT r.post_id, r.score, r.recommendation_type
-                FROM global.recommendations r
-                WHERE r.user_id = $1
-                  AND r.expires_at > NOW()
-                ORDER BY r.score DESC
-                LIMIT $2
-            )
             SELECT
                 r.post_id,
                 r.score,
+                r.reason,
+                r.recommendation_type,
+                r.features,
+                p.user_id AS author_id,
                 p.title,
+                p.content,
                 p.created_at,
-                u.username as author,
-                p.excerpt,
-                ARRAY_AGG(t.name) as tags
-            FROM recs r
-            JOIN global.posts p ON r.post_id = p.id
-            JOIN global.users u ON p.author_id = u.id
-            LEFT JOIN global.post_tags pt ON p.id = pt.post_id
-            LEFT JOIN global.tags t ON pt.tag_id = t.id
-            WHERE p.is_deleted = false
-              AND p.is_draft = false
-            GROUP BY r.post_id, r.score, p.title, p.created_at, u.username, p.excerpt
-            ORDER BY r.score DESC
+                u.username AS author,
+                global.user_avatar_url(u.avatar_url, u.email) AS author_avatar_url,
+                COALESCE(ARRAY_AGG(t.name ORDER BY t.name) FILTER (WHERE t.name IS NOT NULL), '{}') AS tags
+            FROM global.recommendations r
+            JOIN global.posts p ON p.id = r.post_id
+            JOIN global.users u ON u.id = p.user_id
+            LEFT JOIN global.post_tags pt ON pt.post_id = p.id
+            LEFT JOIN global.tags t ON t.id = pt.tag_id
+            WHERE
+                r.user_id = $1 AND
+                r.expires_at > NOW() AND
+                p.blog_id = $2 AND
+                p.is_draft = false AND
+                p.is_deleted = false AND
+                ($3::TEXT IS NULL OR r.recommendation_type = $3) AND
+                r.score >= $4 AND
+                ($8 OR NOT EXISTS (
+                    SELECT 1 FROM global.post_reads pr
+                    WHERE pr.user_id = $1 AND pr.post_id = r.post_id
+                ))
+            GROUP BY r.id, p.id, u.id
+            HAVING
+                ($5::TEXT[] IS NULL OR ARRAY_AGG(global.match_key(t.name)) && (
+                    SELECT ARRAY_AGG(global.match_key(tag)) FROM UNNEST($5::TEXT[]) AS tag
+                )) AND
+                ($6::TEXT[] IS NULL OR NOT (ARRAY_AGG(global.match_key(t.name)) && (
+                    SELECT ARRAY_AGG(global.match_key(tag)) FROM UNNEST($6::TEXT[]) AS tag
+                )))
+            ORDER BY r.score DESC, r.post_id DESC
+            LIMIT $7
             "#,
-            user_id,
-            limit
         )
-        .fetch_all(&self.pool)
-        .await?;
-
-        let recommendations: Vec<PostRecommendation> = rows
+        .bind(user_id)
+        .bind(current_blog_id())
+        .bind(algorithm.recommendation_type())
+        .bind(params.min_score.unwrap_or(0.0))
+        .bind(params.include_tags.as_ref())
+        .bind(params.exclude_tags.as_ref())
+        .bind(MAX_RANKED_CANDIDATES)
+        .bind(params.include_read.unwrap_or(false))
+        .fetch_all(&mut *conn);
+        let rows = timed("recommendations.for_user", query).await?;
+
+        Ok(rows
             .into_iter()
-            .map(|row| PostRecommendation {
-                post_id: row.post_id,
-                score: row.score,
-                title: row.title,
-                author: row.author,
-                created_at: row.created_at,
-                tags: row.tags.unwrap_or_default(),
-                similarity: None,
-                excerpt: row.excerpt,
+            .map(|row| Candidate {
+                author_id: row.get("author_id"),
+                source: row.get("recommendation_type"),
+                features: row.get("features"),
+                recommendation: PostRecommendation {
+                    post_id: row.get("post_id"),
+                    title: row.get("title"),
+                    score: row.get("score"),
+                    similarity: None,
+                    reason: row.get("reason"),
+                    author: row.get("author"),
+                    author_avatar_url: row.get("author_avatar_url"),
+                    created_at: row.get("created_at"),
+                    tags: row.get("tags"),
+                    excerpt: Some(meta_description(row.get("content"))),
+                },
             })
-            .collect();
+            .collect())
+    }
 
-        // If we have no recommendations, generate fallback popular posts
-        if recommendations.is_empty() {
-            let fallback_rows = sqlx::query!(
-                r#"
-                SELECT
-                    p.id as post_id,
-                    0.5 as score,
-                    p.title,
-                    p.created_at,
-                    u.username as author,
-                    p.excerpt,
-                    ARRAY_AGG(t.name) as tags
-                FROM global.posts p
-                JOIN global.users u ON p.author_id = u.id
-                LEFT JOIN global.post_tags pt ON p.id = pt.post_id
-                LEFT JOIN global.tags t ON pt.tag_id = t.id
-                WHERE p.is_deleted = false
-                  AND p.is_draft = false
-                GROUP BY p.id, p.title, p.views, p.likes, p.created_at, u.username, p.excerpt
-                ORDER BY (p.views + p.likes * 2) DESC
-                LIMIT $1
-                "#,
-                limit
+    // Whether the user has unexpired recommendations from the algorithm in the current blog
+    async fn has_recommendations(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        algorithm: Algorithm,
+    ) -> Result<bool, RecommendationError> {
+        let query = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM global.recommendations r
+                JOIN global.posts p ON p.id = r.post_id
+                WHERE r.user_id = $1 AND r.expires_at > NOW() AND p.blog_id = $2
+                    AND ($3::TEXT IS NULL OR r.recommendation_type = $3)
             )
-            .fetch_all(&self.pool)
-            .await?;
+            "#,
+        )
+        .bind(user_id)
+        .bind(current_blog_id())
+        .bind(algorithm.recommendation_type())
+        .fetch_one(conn);
+        Ok(timed("recommendations.exists", query).await?)
+    }
 
-            let fallbacks: Vec<PostRecommendation> = fallback_rows
-                .into_iter()
-                .map(|row| PostRecommendation {
-                    post_id: row.post_id,
-                    score: row.score,
-                    title: row.title,
-                    author: row.author,
-                    created_at: row.created_at,
-                    tags: row.tags.unwrap_or_default(),
-                    similarity: None,
-                    excerpt: row.excerpt,
-                })
-                .collect();
-
-            // Cache the fallback recommendations
-            if let Some(cache) = &self.redis_cache {
-                let cache_key = format!("recommendations:{}", user_id);
-                let json_data = serde_json::to_string(&fallbacks).unwrap_or_default();
-
-                let _ = cache
-                    .get_client()
-                    .get_multiplexed_async_connection()
-                    .await
-                    .map_err(RecommendationError::CacheError)?
-                    .set_ex(&cache_key, &json_data, RECOMMENDATION_CACHE_TTL / 2) // Half TTL for fallbacks
-                    .await
-                    .map_err(RecommendationError::CacheError)?;
+    /// Generate recommendations for the given users, or for every user with interactions,
+    /// from the posts of the current blog. Returns a summary of what was generated.
+    pub async fn generate_recommendations(
+        &self,
+        request: GenerateRecommendationsRequest,
+    ) -> Result<String, RecommendationError> {
+        let algorithm = self.parse_algorithm(request.algorithm.as_deref())?;
+        let limit = request
+            .limit_per_user
+            .unwrap_or(DEFAULT_RECOMMENDATION_LIMIT)
+            .clamp(1, MAX_RECOMMENDATION_LIMIT);
+
+        let user_ids = match request.user_ids {
+            Some(user_ids) => user_ids,
+            None => {
+                sqlx::query_scalar::<_, Uuid>(
+                    r#"
+                    SELECT DISTINCT ui.user_id
+                    FROM global.user_interactions ui
+                    JOIN global.users u ON u.id = ui.user_id
+                    "#,
+                )
+                .fetch_all(&self.pool)
+                .await?
             }
+        };
 
-            return Ok(fallbacks);
+        let mut conn = self.pool.acquire().await?;
+        let mut generated = 0;
+        for user_id in &user_ids {
+            if request.refresh_existing.unwrap_or(false) {
+                Self::clear_for_user(&mut conn, *user_id).await?;
+            }
+            generated += Self::generate_for_user(&mut conn, *user_id, algorithm, limit).await?;
         }
 
-        // Cache the recommendations
-        if let Some(cache) = &self.redis_cache {
-            let cache_key = format!("recommendations:{}", user_id);
-            let json_data = serde_json::to_string(&recommendations).unwrap_or_default();
+        info!(
+            "Generated {} recommendations for {} users",
+            generated,
+            user_ids.len()
+        );
+        Ok(format!(
+            "Generated {} recommendations for {} users",
+            generated,
+            user_ids.len()
+        ))
+    }
 
-            let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
-                .await
-                .map_err(RecommendationError::CacheError)?
-                .set_ex(&cache_key, &json_data, RECOMMENDATION_CACHE_TTL)
-                .await
-                .map_err(RecommendationError::CacheError)?;
-        }
+    /// Whether the user gets the weekly digest of recommended posts
+    pub async fn get_digest_settings(
+        &self,
+        user_id: Uuid,
+    ) -> Result<DigestSettings, RecommendationError> {
+        let enabled = sqlx::query_scalar::<_, bool>(
+            "SELECT recommendation_digest FROM global.users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RecommendationError::NotFound)?;
 
-        Ok(recommendations)
-        */
+        Ok(DigestSettings { enabled })
     }
 
-    /// Generate recommendations for users
-    pub async fn generate_recommendations(
+    pub async fn set_digest_settings(
         &self,
-        _request: GenerateRecommendationsRequest,
-    ) -> Result<String, RecommendationError> {
-        // Skip all database operations and just return a placeholder response
-        info!("Skipping recommendation generation due to database schema issues");
-        Ok("Recommendations generation skipped due to database schema issues".to_string())
+        user_id: Uuid,
+        settings: &DigestSettings,
+    ) -> Result<DigestSettings, RecommendationError> {
+        let enabled = sqlx::query_scalar::<_, bool>(
+            r#"
+            UPDATE global.users SET recommendation_digest = $2, updated_at = NOW()
+            WHERE id = $1
+            RETURNING recommendation_digest
+            "#,
+        )
+        .bind(user_id)
+        .bind(settings.enabled)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RecommendationError::NotFound)?;
+
+        info!("User {} set recommendation digest to {}", user_id, enabled);
+        Ok(DigestSettings { enabled })
     }
 
     /// Get current generation status
@@ -196,289 +421,446 @@ impl RecommendationService {
         self.generation_status.lock().unwrap().clone()
     }
 
-    /// Generate collaborative filtering recommendations
-    async fn generate_collaborative_filtering(
-        _pool: &PgPool,
-        _user_id: Uuid,
-        _limit: i64,
+    // Drop a user's recommendations in the current blog, and expired ones anywhere
+    async fn clear_for_user(
+        conn: &mut PgConnection,
+        user_id: Uuid,
     ) -> Result<(), RecommendationError> {
-        // TODO: Fix the SQL queries below once the database schema includes the required tables and columns.
-        // The following queries reference tables and columns that don't exist in the current database schema:
-        // - global.user_interactions
-        // - global.recommendations
+        sqlx::query(
+            r#"
+            DELETE FROM global.recommendations r
+            USING global.posts p
+            WHERE r.user_id = $1 AND p.id = r.post_id
+                AND (p.blog_id = $2 OR r.expires_at <= NOW())
+            "#,
+        )
+        .bind(user_id)
+        .bind(current_blog_id())
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
 
-        // Just return success without doing anything for now
-        info!("Skipping generate_collaborative_filtering due to database schema issues");
-        return Ok(());
+    // Generate and store up to `limit` recommendations, returning how many were stored.
+    // Posts already recommended are left as they are.
+    async fn generate_for_user(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        algorithm: Algorithm,
+        limit: i64,
+    ) -> Result<u64, RecommendationError> {
+        match algorithm {
+            Algorithm::Collaborative => {
+                Self::generate_collaborative_filtering(&mut *conn, user_id, limit).await
+            }
+            Algorithm::ContentBased => {
+                Self::generate_content_based_recommendations(&mut *conn, user_id, limit).await
+            }
+            Algorithm::Popular => {
+                Self::generate_popular_recommendations(&mut *conn, user_id, limit).await
+            }
+            Algorithm::Hybrid => {
+                Self::generate_hybrid_recommendations(&mut *conn, user_id, limit).await
+            }
+            Algorithm::Embedding => {
+                Self::generate_embedding_recommendations(&mut *conn, user_id, limit).await
+            }
+        }
+    }
 
-        /* Commented out due to database schema issues
-        // Find posts liked by similar users
-        // This is a simplified approach; in production you would use more advanced algorithms
-        let now = Utc::now();
-        let expires_at = now + Duration::days(7);
+    /// Generate collaborative filtering recommendations: posts engaged with by readers who
+    /// liked or commented on the same posts as the user. The reason names the user's post
+    /// that links them most strongly.
+    async fn generate_collaborative_filtering(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<u64, RecommendationError> {
+        let expires_at = Utc::now() + Duration::days(PERSONALIZED_EXPIRY_DAYS);
 
-        // Get posts liked or commented on by users who liked similar posts
-        sqlx::query!(
+        let query = sqlx::query(
             r#"
-            WITH user_interactions AS (
-                -- Get all interactions by this user
-                SELECT post_id, interaction_type
+            WITH seen AS (
+                SELECT DISTINCT post_id
                 FROM global.user_interactions
-                WHERE user_id = $1
-                AND interaction_type IN ('like', 'comment', 'view')
+                WHERE user_id = $1 AND post_id IS NOT NULL
             ),
             similar_users AS (
-                -- Find users who interacted with the same posts
-                SELECT DISTINCT ui2.user_id
-                FROM user_interactions ui1
-                JOIN global.user_interactions ui2
-                  ON ui1.post_id = ui2.post_id
-                  AND ui2.user_id != $1
-                  AND ui2.interaction_type IN ('like', 'comment')
+                -- Readers who engaged with the user's posts, and through which post
+                SELECT ui.user_id, ui.post_id AS via_post_id
+                FROM global.user_interactions ui
+                JOIN seen s ON s.post_id = ui.post_id
+                WHERE ui.user_id != $1 AND ui.interaction_type IN ('like', 'comment')
+                GROUP BY ui.user_id, ui.post_id
             ),
-            candidate_posts AS (
-                -- Get posts that similar users like but this user hasn't seen
-                SELECT
-                    ui.post_id,
-                    COUNT(*) AS interaction_count,
-                    0.7 + (COUNT(*) * 0.01) AS base_score
+            candidates AS (
+                SELECT ui.post_id, su.via_post_id, COUNT(*) AS interaction_count
                 FROM global.user_interactions ui
-                JOIN similar_users su ON ui.user_id = su.user_id
+                JOIN similar_users su ON su.user_id = ui.user_id
+                JOIN global.posts p ON p.id = ui.post_id
                 WHERE ui.interaction_type IN ('like', 'comment', 'view')
-                  AND NOT EXISTS (
-                    SELECT 1 FROM global.user_interactions
-                    WHERE user_id = $1 AND post_id = ui.post_id
-                  )
-                  AND NOT EXISTS (
-                    SELECT 1 FROM global.recommendations
-                    WHERE user_id = $1 AND post_id = ui.post_id
-                  )
-                GROUP BY ui.post_id
-                ORDER BY interaction_count DESC
-                LIMIT $2
+                    AND p.blog_id = $2 AND p.is_draft = false AND p.is_deleted = false
+                    AND p.user_id != $1
+                    AND ui.post_id NOT IN (SELECT post_id FROM seen)
+                GROUP BY ui.post_id, su.via_post_id
+            ),
+            ranked AS (
+                SELECT
+                    post_id,
+                    SUM(interaction_count) AS interaction_count,
+                    (ARRAY_AGG(via_post_id ORDER BY interaction_count DESC, via_post_id))[1] AS via_post_id
+                FROM candidates
+                GROUP BY post_id
+                ORDER BY interaction_count DESC, post_id DESC
+                LIMIT $3
             )
             INSERT INTO global.recommendations (
-                user_id, post_id, score, recommendation_type, created_at, expires_at
+                user_id, post_id, score, recommendation_type, reason, features, expires_at
             )
             SELECT
                 $1,
-                post_id,
-                LEAST(base_score, 1.0),
+                r.post_id,
+                LEAST(0.7 + r.interaction_count * 0.01, 1.0),
                 'collaborative',
-                $3,
+                'Because you read ' || via.title,
+                jsonb_build_object(
+                    'co_reader_interactions', r.interaction_count,
+                    'via_post_id', r.via_post_id
+                ),
                 $4
-            FROM candidate_posts
-            RETURNING id
+            FROM ranked r
+            JOIN global.posts via ON via.id = r.via_post_id
+            ON CONFLICT (user_id, post_id) DO NOTHING
             "#,
-            user_id,
-            limit,
-            now,
-            expires_at
         )
-        .fetch_all(pool)
-        .await?;
-
-        info!(
-            "Generated collaborative filtering recommendations for user {}",
-            user_id
-        );
-        Ok(())
-        */
+        .bind(user_id)
+        .bind(current_blog_id())
+        .bind(limit)
+        .bind(expires_at)
+        .execute(&mut *conn);
+        let result = timed("recommendations.generate_collaborative", query).await?;
+
+        Ok(result.rows_affected())
     }
 
-    /// Generate content-based recommendations
+    /// Generate content-based recommendations: unread posts sharing tags with the posts the
+    /// user engaged with. The reason names the shared tag the user engages with most.
     async fn generate_content_based_recommendations(
-        _pool: &PgPool,
-        _user_id: Uuid,
-        _limit: i64,
-    ) -> Result<(), RecommendationError> {
-        // TODO: Fix the SQL queries below once the database schema includes the required tables and columns.
-        // The following queries reference tables and columns that don't exist in the current database schema:
-        // - global.user_interactions
-        // - global.recommendations
-
-        // Just return success without doing anything for now
-        info!("Skipping generate_content_based_recommendations due to database schema issues");
-        return Ok(());
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<u64, RecommendationError> {
+        let expires_at = Utc::now() + Duration::days(PERSONALIZED_EXPIRY_DAYS);
 
-        /* Commented out due to database schema issues
-        // Recommend posts with similar tags to what the user has engaged with
-        let now = Utc::now();
-        let expires_at = now + Duration::days(7);
-
-        sqlx::query!(
+        let query = sqlx::query(
             r#"
             WITH user_tags AS (
-                -- Get tags from posts the user has interacted with
-                SELECT DISTINCT t.id, t.name
+                SELECT pt.tag_id, COUNT(*) AS engagement
                 FROM global.user_interactions ui
-                JOIN global.posts p ON ui.post_id = p.id
-                JOIN global.post_tags pt ON p.id = pt.post_id
-                JOIN global.tags t ON pt.tag_id = t.id
-                WHERE ui.user_id = $1
-                AND ui.interaction_type IN ('like', 'comment', 'view')
+                JOIN global.post_tags pt ON pt.post_id = ui.post_id
+                WHERE ui.user_id = $1 AND ui.interaction_type IN ('like', 'comment', 'view')
+                GROUP BY pt.tag_id
             ),
             tag_matches AS (
-                -- Find posts that have similar tags
                 SELECT
                     p.id AS post_id,
-                    COUNT(DISTINCT pt.tag_id) AS matching_tags,
-                    0.6 + (COUNT(DISTINCT pt.tag_id) * 0.05) AS base_score
+                    COUNT(*) AS matching_tags,
+                    (ARRAY_AGG(pt.tag_id ORDER BY ut.engagement DESC, pt.tag_id))[1] AS top_tag_id
                 FROM global.posts p
-                JOIN global.post_tags pt ON p.id = pt.post_id
-                JOIN user_tags ut ON pt.tag_id = ut.id
-                WHERE NOT EXISTS (
-                    SELECT 1 FROM global.user_interactions
-                    WHERE user_id = $1 AND post_id = p.id
-                )
-                AND NOT EXISTS (
-                    SELECT 1 FROM global.recommendations
-                    WHERE user_id = $1 AND post_id = p.id
-                )
-                AND p.is_deleted = false
-                AND p.is_draft = false
+                JOIN global.post_tags pt ON pt.post_id = p.id
+                JOIN user_tags ut ON ut.tag_id = pt.tag_id
+                WHERE p.blog_id = $2 AND p.is_draft = false AND p.is_deleted = false
+                    AND p.user_id != $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM global.user_interactions
+                        WHERE user_id = $1 AND post_id = p.id
+                    )
                 GROUP BY p.id
-                ORDER BY matching_tags DESC
-                LIMIT $2
+                ORDER BY matching_tags DESC, MAX(p.popularity_score) DESC, p.id DESC
+                LIMIT $3
             )
             INSERT INTO global.recommendations (
-                user_id, post_id, score, recommendation_type, created_at, expires_at
+                user_id, post_id, score, recommendation_type, reason, features, expires_at
             )
             SELECT
                 $1,
-                post_id,
-                LEAST(base_score, 1.0),
+                m.post_id,
+                LEAST(0.6 + m.matching_tags * 0.05, 1.0),
                 'content_based',
-                $3,
+                'Popular in #' || t.name,
+                jsonb_build_object('matching_tags', m.matching_tags, 'top_tag', t.name),
                 $4
-            FROM tag_matches
-            RETURNING id
+            FROM tag_matches m
+            JOIN global.tags t ON t.id = m.top_tag_id
+            ON CONFLICT (user_id, post_id) DO NOTHING
             "#,
-            user_id,
-            limit,
-            now,
-            expires_at
         )
-        .fetch_all(pool)
-        .await?;
-
-        info!(
-            "Generated content-based recommendations for user {}",
-            user_id
-        );
-        Ok(())
-        */
+        .bind(user_id)
+        .bind(current_blog_id())
+        .bind(limit)
+        .bind(expires_at)
+        .execute(&mut *conn);
+        let result = timed("recommendations.generate_content_based", query).await?;
+
+        Ok(result.rows_affected())
     }
 
-    /// Generate popular post recommendations
+    /// Generate popular post recommendations: the posts of the blog with the highest
+    /// popularity score the user has not seen yet
     async fn generate_popular_recommendations(
-        _pool: &PgPool,
-        _user_id: Uuid,
-        _limit: i64,
-    ) -> Result<(), RecommendationError> {
-        // TODO: Fix the SQL queries below once the database schema includes the required tables and columns.
-        // The following queries reference tables and columns that don't exist in the current database schema:
-        // - global.user_interactions
-        // - global.recommendations
-
-        // Just return success without doing anything for now
-        info!("Skipping generate_popular_recommendations due to database schema issues");
-        return Ok(());
-
-        /* Commented out due to database schema issues
-        // Recommend generally popular posts the user hasn't seen
-        let now = Utc::now();
-        let expires_at = now + Duration::days(5); // Shorter expiry for popular posts
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<u64, RecommendationError> {
+        let expires_at = Utc::now() + Duration::days(POPULAR_EXPIRY_DAYS);
 
-        sqlx::query!(
+        let query = sqlx::query(
             r#"
             WITH popular_posts AS (
-                -- Get popular posts not seen by this user
                 SELECT
                     p.id AS post_id,
-                    (p.views + p.likes * 2) AS popularity,
-                    0.5 + (LEAST(p.views, 1000) / 2000.0) AS base_score
+                    p.views,
+                    p.likes,
+                    p.popularity_score,
+                    MAX(p.popularity_score) OVER () AS top_score
                 FROM global.posts p
-                WHERE NOT EXISTS (
-                    SELECT 1 FROM global.user_interactions
-                    WHERE user_id = $1 AND post_id = p.id
-                )
-                AND NOT EXISTS (
-                    SELECT 1 FROM global.recommendations
-                    WHERE user_id = $1 AND post_id = p.id
-                )
-                AND p.is_deleted = false
-                AND p.is_draft = false
-                ORDER BY popularity DESC
-                LIMIT $2
+                WHERE p.blog_id = $2 AND p.is_draft = false AND p.is_deleted = false
+                    AND p.user_id != $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM global.user_interactions
+                        WHERE user_id = $1 AND post_id = p.id
+                    )
+                ORDER BY p.popularity_score DESC, p.id DESC
+                LIMIT $3
             )
             INSERT INTO global.recommendations (
-                user_id, post_id, score, recommendation_type, created_at, expires_at
+                user_id, post_id, score, recommendation_type, reason, features, expires_at
             )
             SELECT
                 $1,
                 post_id,
-                LEAST(base_score, 0.9), -- Cap at 0.9 to prioritize personalized recs
+                -- Relative to the most popular candidate, capped below personalized
+                -- recommendations
+                0.5 + 0.4 * COALESCE(popularity_score / NULLIF(top_score, 0), 0),
                 'popular',
-                $3,
+                'Popular on this blog',
+                jsonb_build_object(
+                    'views', views,
+                    'likes', likes,
+                    'popularity_score', popularity_score
+                ),
                 $4
             FROM popular_posts
-            RETURNING id
+            ON CONFLICT (user_id, post_id) DO NOTHING
             "#,
-            user_id,
-            limit,
-            now,
-            expires_at
         )
-        .fetch_all(pool)
-        .await?;
-
-        info!(
-            "Generated popular post recommendations for user {}",
-            user_id
-        );
-        Ok(())
-        */
+        .bind(user_id)
+        .bind(current_blog_id())
+        .bind(limit)
+        .bind(expires_at)
+        .execute(&mut *conn);
+        let result = timed("recommendations.generate_popular", query).await?;
+
+        Ok(result.rows_affected())
     }
 
-    /// Generate hybrid recommendations combining multiple approaches
+    /// Generate hybrid recommendations, a third from each algorithm
     async fn generate_hybrid_recommendations(
-        _pool: &PgPool,
-        _user_id: Uuid,
-        _limit: i64,
-    ) -> Result<(), RecommendationError> {
-        // TODO: Fix the SQL queries below once the database schema includes the required tables and columns.
-        // The following queries reference tables and columns that don't exist in the current database schema:
-        // - global.user_interactions
-        // - global.recommendations
-
-        // Just return success without doing anything for now
-        info!("Skipping generate_hybrid_recommendations due to database schema issues");
-        return Ok(());
-
-        /* Commented out due to database schema issues
-        // Split the limit between different algorithms
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<u64, RecommendationError> {
         let collab_limit = limit / 3;
         let content_limit = limit / 3;
         let popular_limit = limit - collab_limit - content_limit;
 
-        // Generate recommendations using each approach
-        Self::generate_collaborative_filtering(pool, user_id, collab_limit).await?;
-        Self::generate_content_based_recommendations(pool, user_id, content_limit).await?;
-        Self::generate_popular_recommendations(pool, user_id, popular_limit).await?;
+        let generated = Self::generate_collaborative_filtering(&mut *conn, user_id, collab_limit)
+            .await?
+            + Self::generate_content_based_recommendations(&mut *conn, user_id, content_limit)
+                .await?
+            + Self::generate_popular_recommendations(&mut *conn, user_id, popular_limit).await?;
 
         info!("Generated hybrid recommendations for user {}", user_id);
-        Ok(())
-        */
+        Ok(generated)
+    }
+
+    /// Generate embedding recommendations: unread posts nearest to the average embedding of
+    /// the posts the user recently read or liked. The reason names the closest of those.
+    async fn generate_embedding_recommendations(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<u64, RecommendationError> {
+        let expires_at = Utc::now() + Duration::days(PERSONALIZED_EXPIRY_DAYS);
+
+        let query = sqlx::query(
+            r#"
+            WITH profile_posts AS (
+                (
+                    SELECT post_id FROM global.post_reads
+                    WHERE user_id = $1
+                    ORDER BY read_at DESC
+                    LIMIT 50
+                )
+                UNION
+                SELECT post_id FROM global.user_interactions
+                WHERE user_id = $1 AND interaction_type = 'like' AND post_id IS NOT NULL
+            ),
+            profile AS (
+                SELECT AVG(e.embedding) AS embedding
+                FROM global.post_embeddings e
+                JOIN profile_posts pp ON pp.post_id = e.post_id
+            ),
+            nearest AS (
+                SELECT e.post_id, e.embedding, 1 - (e.embedding <=> profile.embedding) AS similarity
+                FROM global.post_embeddings e
+                JOIN global.posts p ON p.id = e.post_id
+                CROSS JOIN profile
+                WHERE profile.embedding IS NOT NULL
+                    AND p.blog_id = $2 AND p.is_draft = false AND p.is_deleted = false
+                    AND p.user_id != $1
+                    AND e.post_id NOT IN (SELECT post_id FROM profile_posts)
+                    AND NOT EXISTS (
+                        SELECT 1 FROM global.user_interactions
+                        WHERE user_id = $1 AND post_id = p.id
+                    )
+                ORDER BY e.embedding <=> profile.embedding
+                LIMIT $3
+            )
+            INSERT INTO global.recommendations (
+                user_id, post_id, score, recommendation_type, reason, features, expires_at
+            )
+            SELECT
+                $1,
+                n.post_id,
+                GREATEST(n.similarity, 0.0),
+                'embedding',
+                'Similar to ' || closest.title,
+                jsonb_build_object(
+                    'profile_similarity', n.similarity,
+                    'closest_read_post_id', closest.id
+                ),
+                $4
+            FROM nearest n
+            CROSS JOIN LATERAL (
+                SELECT p.id, p.title
+                FROM profile_posts pp
+                JOIN global.post_embeddings e ON e.post_id = pp.post_id
+                JOIN global.posts p ON p.id = pp.post_id
+                ORDER BY e.embedding <=> n.embedding
+                LIMIT 1
+            ) closest
+            ON CONFLICT (user_id, post_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(current_blog_id())
+        .bind(limit)
+        .bind(expires_at)
+        .execute(&mut *conn);
+        let result = timed("recommendations.generate_embedding", query).await?;
+
+        Ok(result.rows_affected())
     }
 
-    /// Get similar posts to a specific post
+    // Published posts of the current blog nearest to a post by embedding
+    async fn get_similar_posts_by_embedding(
+        &self,
+        post_id: i64,
+        params: &RecommendationParams,
+    ) -> Result<Vec<PostRecommendation>, RecommendationError> {
+        let limit = params
+            .limit
+            .unwrap_or(DEFAULT_RECOMMENDATION_LIMIT)
+            .clamp(1, MAX_RECOMMENDATION_LIMIT);
+        let offset = params.offset.unwrap_or(0).max(0);
+
+        let query = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM global.posts
+                WHERE id = $1 AND blog_id = $2 AND is_draft = false AND is_deleted = false
+            )
+            "#,
+        )
+        .bind(post_id)
+        .bind(current_blog_id())
+        .fetch_one(&self.pool);
+        if !timed("recommendations.similar_post_exists", query).await? {
+            return Err(RecommendationError::NotFound);
+        }
+
+        // Posts not embedded yet have no neighbours until the next embedding run
+        let query = sqlx::query(
+            r#"
+            WITH target AS (
+                SELECT embedding FROM global.post_embeddings WHERE post_id = $1
+            ),
+            nearest AS (
+                SELECT e.post_id, 1 - (e.embedding <=> target.embedding) AS similarity
+                FROM global.post_embeddings e
+                JOIN global.posts p ON p.id = e.post_id
+                CROSS JOIN target
+                WHERE e.post_id != $1
+                    AND p.blog_id = $2 AND p.is_draft = false AND p.is_deleted = false
+                ORDER BY e.embedding <=> target.embedding
+                LIMIT $3
+                OFFSET $4
+            )
+            SELECT
+                p.id AS post_id,
+                p.title,
+                p.content,
+                p.created_at,
+                n.similarity,
+                u.username AS author,
+                global.user_avatar_url(u.avatar_url, u.email) AS author_avatar_url,
+                COALESCE(ARRAY_AGG(t.name ORDER BY t.name) FILTER (WHERE t.name IS NOT NULL), '{}') AS tags
+            FROM nearest n
+            JOIN global.posts p ON p.id = n.post_id
+            JOIN global.users u ON u.id = p.user_id
+            LEFT JOIN global.post_tags pt ON pt.post_id = p.id
+            LEFT JOIN global.tags t ON t.id = pt.tag_id
+            GROUP BY n.post_id, n.similarity, p.id, u.id
+            ORDER BY n.similarity DESC, p.id DESC
+            "#,
+        )
+        .bind(post_id)
+        .bind(current_blog_id())
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool);
+        let rows = timed("recommendations.similar_by_embedding", query).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PostRecommendation {
+                post_id: row.get("post_id"),
+                title: row.get("title"),
+                score: row.get("similarity"),
+                similarity: Some(row.get("similarity")),
+                reason: None,
+                author: row.get("author"),
+                author_avatar_url: row.get("author_avatar_url"),
+                created_at: row.get("created_at"),
+                tags: row.get("tags"),
+                excerpt: Some(meta_description(row.get("content"))),
+            })
+            .collect())
+    }
+
+    /// Get similar posts to a specific post. With the `embedding` algorithm these are its
+    /// nearest neighbours by embedding.
     pub async fn get_similar_posts(
         &self,
-        _post_id: i64,
+        post_id: i64,
         _user_id: Option<Uuid>,
         params: &RecommendationParams,
     ) -> Result<Vec<PostRecommendation>, RecommendationError> {
+        if self.parse_algorithm(params.algorithm.as_deref())? == Algorithm::Embedding {
+            return self.get_similar_posts_by_embedding(post_id, params).await;
+        }
+
         let _limit = params.limit.unwrap_or(DEFAULT_RECOMMENDATION_LIMIT);
 
         // TODO: Fix the SQL queries below once the database schema includes the required tables and columns.
@@ -547,6 +929,7 @@ impl RecommendationService {
                 JOIN global.post_tags pt2 ON p.id = pt2.post_id
                 LEFT JOIN global.post_tags pt ON pt2.tag_id = pt.tag_id AND pt.tag_id IN (SELECT tag_id FROM post_tags)
                 WHERE p.id != $1
+                  AND p.blog_id = $3
                   AND p.is_deleted = false
                   AND p.is_draft = false
                 GROUP BY p.id
@@ -559,6 +942,7 @@ impl RecommendationService {
                 p.title,
                 p.created_at,
                 u.username as author,
+                global.user_avatar_url(u.avatar_url, u.email) as author_avatar_url,
                 p.excerpt,
                 sp.similarity_score,
                 ARRAY_AGG(t.name) as tags
@@ -567,11 +951,12 @@ impl RecommendationService {
             JOIN global.users u ON p.author_id = u.id
             LEFT JOIN global.post_tags pt ON p.id = pt.post_id
             LEFT JOIN global.tags t ON pt.tag_id = t.id
-            GROUP BY p.id, p.title, p.created_at, u.username, p.excerpt, sp.similarity_score
+            GROUP BY p.id, p.title, p.created_at, u.username, u.avatar_url, u.email, p.excerpt, sp.similarity_score
             ORDER BY sp.similarity_score DESC, p.views DESC
             "#,
             post_id,
-            limit
+            limit,
+            current_blog_id()
         )
         .fetch_all(&self.pool)
         .await?;
@@ -583,6 +968,7 @@ impl RecommendationService {
                 title: row.title,
                 score: 0.0, // Not using recommendation score for similar posts
                 author: row.author,
+                author_avatar_url: row.author_avatar_url,
                 created_at: row.created_at,
                 tags: row.tags.unwrap_or_default(),
                 similarity: Some(row.similarity_score),
@@ -602,6 +988,7 @@ impl RecommendationService {
                         p.title,
                         p.created_at,
                         u.username as author,
+                        global.user_avatar_url(u.avatar_url, u.email) as author_avatar_url,
                         p.excerpt,
                         ARRAY_AGG(t.name) as tags
                     FROM global.posts p
@@ -609,16 +996,18 @@ impl RecommendationService {
                     JOIN global.post_tags pt ON p.id = pt.post_id
                     JOIN global.tags t ON pt.tag_id = t.id
                     WHERE p.id != $1
+                      AND p.blog_id = $4
                       AND p.is_deleted = false
                       AND p.is_draft = false
                       AND t.name = $3
-                    GROUP BY p.id, p.title, p.created_at, u.username, p.excerpt
+                    GROUP BY p.id, p.title, p.created_at, u.username, u.avatar_url, u.email, p.excerpt
                     ORDER BY p.views DESC
                     LIMIT $2
                     "#,
                     post_id,
                     limit,
-                    category
+                    category,
+                    current_blog_id()
                 )
                 .fetch_all(&self.pool)
                 .await?;
@@ -630,6 +1019,7 @@ impl RecommendationService {
                         title: row.title,
                         score: 0.0,
                         author: row.author,
+                        author_avatar_url: row.author_avatar_url,
                         created_at: row.created_at,
                         tags: row.tags.unwrap_or_default(),
                         similarity: Some(0.5), // Medium similarity based on category
@@ -646,8 +1036,7 @@ impl RecommendationService {
                     let json_data = serde_json::to_string(&fallbacks).unwrap_or_default();
 
                     let _ = cache
-                        .get_client()
-                        .get_multiplexed_async_connection()
+                        .connection()
                         .await
                         .map_err(RecommendationError::CacheError)?
                         .set_ex(&cache_key, &json_data, RECOMMENDATION_CACHE_TTL / 2) // Half TTL for fallbacks
@@ -669,8 +1058,7 @@ impl RecommendationService {
             let json_data = serde_json::to_string(&similar_posts).unwrap_or_default();
 
             let _ = cache
-                .get_client()
-                .get_multiplexed_async_connection()
+                .connection()
                 .await
                 .map_err(RecommendationError::CacheError)?
                 .set_ex(&cache_key, &json_data, RECOMMENDATION_CACHE_TTL)
@@ -683,77 +1071,57 @@ impl RecommendationService {
         */
     }
 
-    /// Refresh the recommendation model
+    /// Refresh the recommendation model: regenerate the recommendations of every user in
+    /// the background
     pub async fn refresh_recommendation_model(&self) -> Result<(), RecommendationError> {
-        // TODO: Fix the SQL queries below once the database schema includes the required tables and columns.
-        // The following queries reference tables and columns that don't exist in the current database schema:
-        // - global.recommendations
-        // - global.user_interactions
+        self.trigger_recommendation_generation(&GenerateRecommendationsRequest {
+            user_ids: None,
+            limit_per_user: Some(50),
+            algorithm: Some("hybrid".to_string()),
+            refresh_existing: Some(true),
+        })
+        .await
+        .map(|_| ())
+    }
 
-        // Just return success without doing anything for now
-        info!("Skipping refresh_recommendation_model due to database schema issues");
-        return Ok(());
+    /// Trigger an asynchronous recommendation generation process
+    pub async fn trigger_recommendation_generation(
+        &self,
+        request: &GenerateRecommendationsRequest,
+    ) -> Result<String, RecommendationError> {
+        self.parse_algorithm(request.algorithm.as_deref())?;
 
-        /* Commented out due to database schema issues
-        // Lock status to indicate we're refreshing
         {
             let mut status = self.generation_status.lock().unwrap();
-            *status = GenerationStatus::Running("Refreshing recommendation model".to_string());
+            if matches!(*status, GenerationStatus::Running(_)) {
+                return Err(RecommendationError::GenerationInProgress);
+            }
+            *status = GenerationStatus::Running("Generating recommendations".to_string());
         }
 
-        // Create a clone of self to move into the task
-        let service_clone = self.clone();
-
-        // Spawn a background task to handle the refresh
-        tokio::spawn(async move {
-            match service_clone
-                .generate_recommendations(GenerateRecommendationsRequest {
-                    user_ids: None, // All users
-                    limit_per_user: Some(50),
-                    algorithm: Some("hybrid".to_string()),
-                    refresh_existing: Some(true),
-                })
-                .await
-            {
-                Ok(_) => {
-                    let mut status = service_clone.generation_status.lock().unwrap();
-                    *status = GenerationStatus::Completed(format!(
-                        "Recommendation model refreshed successfully at {}",
-                        Utc::now().to_rfc3339()
-                    ));
-                    info!("Recommendation model refreshed successfully");
-                }
+        let service = self.clone();
+        let request = GenerateRecommendationsRequest {
+            user_ids: request.user_ids.clone(),
+            limit_per_user: request.limit_per_user,
+            algorithm: request.algorithm.clone(),
+            refresh_existing: request.refresh_existing,
+        };
+        tokio::spawn(with_blog_id(current_blog_id(), async move {
+            let outcome = service.generate_recommendations(request).await;
+            let mut status = service.generation_status.lock().unwrap();
+            *status = match outcome {
+                Ok(summary) => GenerationStatus::Completed(format!(
+                    "{} at {}",
+                    summary,
+                    Utc::now().to_rfc3339()
+                )),
                 Err(e) => {
-                    let mut status = service_clone.generation_status.lock().unwrap();
-                    *status = GenerationStatus::Failed(format!(
-                        "Failed to refresh recommendation model: {}",
-                        e
-                    ));
-                    error!("Failed to refresh recommendation model: {}", e);
+                    error!("Failed to generate recommendations: {}", e);
+                    GenerationStatus::Failed(format!("Failed to generate recommendations: {}", e))
                 }
-            }
-        });
-
-        Ok(())
-        */
-    }
+            };
+        }));
 
-    /// Trigger an asynchronous recommendation generation process
-    pub async fn trigger_recommendation_generation(
-        &self,
-        _request: &GenerateRecommendationsRequest,
-    ) -> Result<String, RecommendationError> {
-        // TODO: Fix the SQL queries below once the database schema includes the required tables and columns.
-        // The following code references tables that don't exist in the current database schema:
-        // - global.recommendations
-        // - global.user_interactions
-
-        // Just return success without doing anything for now
-        info!("Skipping generate_recommendations due to database schema issues");
-        return Ok("Recommendation generation skipped due to database schema issues".to_string());
-
-        /* Commented out due to database schema issues
-        // Original implementation...
-         */
+        Ok("Recommendation generation started".to_string())
     }
 }
@@ -0,0 +1,53 @@
+use crate::db::retry::unavailable_response;
+use crate::reputation::model::ReputationError;
+use crate::reputation::service::ReputationService;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn reputation_error_response(e: ReputationError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        ReputationError::UserNotFound => StatusCode::NOT_FOUND,
+        ReputationError::DatabaseError(_) => {
+            error!("Reputation error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Get a user's public profile
+///
+/// Includes the user's reputation: points for published posts and for the likes and comments
+/// they receive, minus penalties for removed comments.
+#[utoipa::path(
+    get,
+    path = "/api/users/{username}",
+    tag = "users",
+    params(
+        ("username" = String, Path, description = "Username of the user")
+    ),
+    responses(
+        (status = 200, description = "Profile retrieved successfully", body = UserProfile),
+        (status = 404, description = "User not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_user_profile(
+    Path(username): Path<String>,
+    State(service): State<Arc<ReputationService>>,
+) -> Response {
+    match service.get_profile(&username).await {
+        Ok(profile) => (StatusCode::OK, Json(profile)).into_response(),
+        Err(e) => reputation_error_response(e),
+    }
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::sync::OnceLock;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Points per published post
+pub const POST_POINTS: i64 = 10;
+/// Points per counted like of the user's posts
+pub const LIKE_POINTS: i64 = 2;
+/// Points per approved comment others left on the user's posts
+pub const COMMENT_POINTS: i64 = 1;
+/// Points lost per comment of the user removed by a moderator
+pub const REMOVAL_PENALTY: i64 = 15;
+
+/// Reputation points of a user's standing
+pub fn reputation_points(
+    posts_published: i64,
+    likes_received: i64,
+    comments_received: i64,
+    content_removed: i64,
+) -> i32 {
+    let points = posts_published * POST_POINTS
+        + likes_received * LIKE_POINTS
+        + comments_received * COMMENT_POINTS
+        - content_removed * REMOVAL_PENALTY;
+    points.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// How reputation feeds the spam heuristics.
+///
+/// Likes by users whose reputation is at or below `LOW_REPUTATION_THRESHOLD` (default -30,
+/// two removed comments more than the user has earned) do not count toward the popularity
+/// of posts.
+#[derive(Debug, Clone)]
+pub struct ReputationConfig {
+    pub low_reputation: i32,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self {
+            low_reputation: -30,
+        }
+    }
+}
+
+impl ReputationConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let low_reputation = std::env::var("LOW_REPUTATION_THRESHOLD")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(defaults.low_reputation);
+        Self { low_reputation }
+    }
+
+    /// Shared config, read on first use
+    pub fn global() -> &'static ReputationConfig {
+        static CONFIG: OnceLock<ReputationConfig> = OnceLock::new();
+        CONFIG.get_or_init(ReputationConfig::from_env)
+    }
+}
+
+/// A user's reputation and what it is made of
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Reputation {
+    /// Ten points per published post, two per like and one per comment received, minus
+    /// fifteen per removed comment
+    #[schema(example = "142")]
+    pub points: i32,
+
+    /// Published posts
+    #[schema(example = "8")]
+    pub posts_published: i32,
+
+    /// Counted likes of the user's posts
+    #[schema(example = "25")]
+    pub likes_received: i32,
+
+    /// Approved comments others left on the user's posts
+    #[schema(example = "12")]
+    pub comments_received: i32,
+
+    /// Comments of the user deleted by a moderator or rejected in moderation
+    #[schema(example = "0")]
+    pub content_removed: i32,
+}
+
+/// Public profile of a user
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserProfile {
+    #[schema(example = "a1b2c3d4-e5f6-7890-abcd-1234567890ab")]
+    pub id: Uuid,
+
+    #[schema(example = "jane")]
+    pub username: String,
+
+    /// Uploaded avatar, or the user's Gravatar
+    pub avatar_url: Option<String>,
+
+    /// When the user signed up
+    #[schema(example = "2023-01-01T12:00:00Z")]
+    pub joined_at: DateTime<Utc>,
+
+    /// Reputation as of the last update, which lags activity by up to five minutes
+    pub reputation: Reputation,
+}
+
+/// Possible reputation errors
+#[derive(Debug, thiserror::Error)]
+pub enum ReputationError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("User not found")]
+    UserNotFound,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reputation_points() {
+        assert_eq!(reputation_points(0, 0, 0, 0), 0);
+        assert_eq!(reputation_points(2, 5, 3, 0), 33);
+        assert_eq!(reputation_points(0, 0, 4, 2), -26);
+    }
+}
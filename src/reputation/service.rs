@@ -0,0 +1,251 @@
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::reputation::model::{reputation_points, Reputation, ReputationError, UserProfile};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Kind of the job that updates the reputation of users with new activity
+pub const REPUTATION_JOB: &str = "user_reputation";
+
+// How often reputations are updated, and how often instances check whether a run is due
+const REPUTATION_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const REPUTATION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Users updated per statement
+const REPUTATION_BATCH_SIZE: i64 = 500;
+
+/// Queue the reputation of users for an update, after activity that changes it: their posts
+/// being published, liked or commented on, or their comments being removed. Failures are
+/// logged rather than failing the activity; the user is picked up on the next change.
+pub async fn queue_reputation_update(pool: &PgPool, user_ids: &[Uuid]) {
+    if user_ids.is_empty() {
+        return;
+    }
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO global.reputation_updates (user_id)
+        SELECT UNNEST($1::UUID[])
+        ON CONFLICT (user_id) DO NOTHING
+        "#,
+    )
+    .bind(user_ids)
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        error!("Failed to queue reputation update: {}", e);
+    }
+}
+
+/// Reputation points of a user, 0 before their first update
+pub async fn user_reputation(pool: &PgPool, user_id: Uuid) -> Result<i32, sqlx::Error> {
+    Ok(
+        sqlx::query_scalar::<_, i32>(
+            "SELECT points FROM global.user_reputation WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .unwrap_or(0),
+    )
+}
+
+/// Reputation of users, shown on their public profiles and weighed by the spam heuristics.
+///
+/// Reputation is recounted from a user's posts and comments, so it is always what their
+/// content earns now. Activity queues the users it affects with [`queue_reputation_update`],
+/// and a background job queued every five minutes recounts just those users. Users without a
+/// reputation yet are queued when the schema is applied, which scores existing users on the
+/// first deploy.
+pub struct ReputationService {
+    pool: PgPool,
+}
+
+impl ReputationService {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Queue an update every five minutes
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REPUTATION_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(REPUTATION_JOB, json!({}), REPUTATION_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule reputation updates: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Public profile of a user
+    pub async fn get_profile(&self, username: &str) -> Result<UserProfile, ReputationError> {
+        let row = sqlx::query(
+            r#"
+            SELECT u.id, u.username, u.created_at,
+                global.user_avatar_url(u.avatar_url, u.email) AS avatar_url,
+                COALESCE(r.points, 0) AS points,
+                COALESCE(r.posts_published, 0) AS posts_published,
+                COALESCE(r.likes_received, 0) AS likes_received,
+                COALESCE(r.comments_received, 0) AS comments_received,
+                COALESCE(r.content_removed, 0) AS content_removed
+            FROM global.users u
+            LEFT JOIN global.user_reputation r ON r.user_id = u.id
+            WHERE u.username = $1
+            "#,
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(ReputationError::UserNotFound)?;
+
+        Ok(UserProfile {
+            id: row.get("id"),
+            username: row.get("username"),
+            avatar_url: row.get("avatar_url"),
+            joined_at: row.get("created_at"),
+            reputation: Reputation {
+                points: row.get("points"),
+                posts_published: row.get("posts_published"),
+                likes_received: row.get("likes_received"),
+                comments_received: row.get("comments_received"),
+                content_removed: row.get("content_removed"),
+            },
+        })
+    }
+
+    /// Recount the reputation of every queued user. Returns how many were updated.
+    pub async fn update_queued(&self) -> Result<i64, sqlx::Error> {
+        let mut updated = 0;
+
+        loop {
+            // Users queued again while they are recounted stay queued for the next run
+            let mut tx = self.pool.begin().await?;
+            let user_ids = sqlx::query_scalar::<_, Uuid>(
+                r#"
+                DELETE FROM global.reputation_updates
+                WHERE user_id IN (
+                    SELECT user_id FROM global.reputation_updates
+                    ORDER BY queued_at
+                    LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING user_id
+                "#,
+            )
+            .bind(REPUTATION_BATCH_SIZE)
+            .fetch_all(&mut *tx)
+            .await?;
+            if user_ids.is_empty() {
+                tx.commit().await?;
+                break;
+            }
+
+            let query = sqlx::query(
+                r#"
+                SELECT u.user_id,
+                    (SELECT COUNT(*) FROM global.posts p
+                        WHERE p.user_id = u.user_id AND p.is_draft = false
+                            AND p.is_deleted = false) AS posts_published,
+                    (SELECT COALESCE(SUM(p.likes), 0)::BIGINT FROM global.posts p
+                        WHERE p.user_id = u.user_id AND p.is_draft = false
+                            AND p.is_deleted = false) AS likes_received,
+                    (SELECT COUNT(*) FROM global.comments c
+                        JOIN global.posts p ON p.id = c.post_id
+                        LEFT JOIN global.users cu ON cu.id = c.user_id
+                        WHERE p.user_id = u.user_id AND p.is_draft = false
+                            AND p.is_deleted = false AND c.is_deleted = false
+                            AND c.moderation_status = 'approved'
+                            AND c.user_id IS DISTINCT FROM u.user_id
+                            AND NOT COALESCE(cu.is_shadow_banned, false)) AS comments_received,
+                    (SELECT COUNT(*) FROM global.comments c
+                        WHERE c.user_id = u.user_id
+                            AND ((c.is_deleted AND c.deleted_by IS DISTINCT FROM u.user_id)
+                                OR c.moderation_status = 'rejected')) AS content_removed
+                FROM UNNEST($1::UUID[]) AS u(user_id)
+                "#,
+            )
+            .bind(&user_ids)
+            .fetch_all(&mut *tx);
+            let rows = timed("reputation.counts", query).await?;
+
+            let mut counted_ids = Vec::with_capacity(rows.len());
+            let mut points = Vec::with_capacity(rows.len());
+            let mut posts_published = Vec::with_capacity(rows.len());
+            let mut likes_received = Vec::with_capacity(rows.len());
+            let mut comments_received = Vec::with_capacity(rows.len());
+            let mut content_removed = Vec::with_capacity(rows.len());
+            for row in rows {
+                let posts: i64 = row.get("posts_published");
+                let likes: i64 = row.get("likes_received");
+                let comments: i64 = row.get("comments_received");
+                let removed: i64 = row.get("content_removed");
+                counted_ids.push(row.get::<Uuid, _>("user_id"));
+                points.push(reputation_points(posts, likes, comments, removed));
+                posts_published.push(posts as i32);
+                likes_received.push(likes as i32);
+                comments_received.push(comments as i32);
+                content_removed.push(removed as i32);
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO global.user_reputation (
+                    user_id, points, posts_published, likes_received, comments_received,
+                    content_removed, updated_at
+                )
+                SELECT *, NOW()
+                FROM UNNEST($1::UUID[], $2::INTEGER[], $3::INTEGER[], $4::INTEGER[],
+                    $5::INTEGER[], $6::INTEGER[])
+                ON CONFLICT (user_id) DO UPDATE SET
+                    points = EXCLUDED.points,
+                    posts_published = EXCLUDED.posts_published,
+                    likes_received = EXCLUDED.likes_received,
+                    comments_received = EXCLUDED.comments_received,
+                    content_removed = EXCLUDED.content_removed,
+                    updated_at = EXCLUDED.updated_at
+                "#,
+            )
+            .bind(&counted_ids)
+            .bind(&points)
+            .bind(&posts_published)
+            .bind(&likes_received)
+            .bind(&comments_received)
+            .bind(&content_removed)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            updated += counted_ids.len() as i64;
+        }
+
+        if updated > 0 {
+            info!("Updated the reputation of {} users", updated);
+        }
+        Ok(updated)
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let updated = self.update_queued().await?;
+        Ok(json!({ "updated": updated }))
+    }
+}
+
+impl JobHandler for ReputationService {
+    fn kind(&self) -> &'static str {
+        REPUTATION_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
@@ -0,0 +1,90 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::jobs::controller::job_error_response;
+use crate::jobs::service::JobService;
+use crate::retention::model::{RunRetentionRequest, RETENTION_JOB};
+use crate::retention::service::RetentionService;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can manage data retention" })),
+    )
+        .into_response()
+}
+
+/// Get the data retention policy (admin only)
+///
+/// Returns how long each class of data is kept. Periods are configured per instance with
+/// `RETENTION_INTERACTIONS_DAYS`, `RETENTION_ROLLUPS_MONTHS` and `RETENTION_NOTIFICATIONS_DAYS`;
+/// a missing period keeps that data forever.
+#[utoipa::path(
+    get,
+    path = "/api/admin/retention",
+    tag = "retention",
+    responses(
+        (status = 200, description = "Retention policy retrieved", body = RetentionPolicy),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_policy(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<RetentionService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    (StatusCode::OK, Json(service.policy().clone())).into_response()
+}
+
+/// Run a retention cleanup now (admin only)
+///
+/// Queues a cleanup as a background job and returns it. Dry runs, the default, only count
+/// the rows past their retention period. The `RetentionReport` is the result of the job at
+/// `/api/admin/jobs/{id}`.
+#[utoipa::path(
+    post,
+    path = "/api/admin/retention/run",
+    tag = "retention",
+    request_body = RunRetentionRequest,
+    responses(
+        (status = 202, description = "Cleanup queued", body = Job),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn run_cleanup(
+    Extension(user): Extension<AuthUser>,
+    Extension(jobs): Extension<Arc<JobService>>,
+    Json(request): Json<RunRetentionRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    let payload = json!({ "dry_run": request.dry_run.unwrap_or(true) });
+    match jobs
+        .enqueue(RETENTION_JOB, payload, Some(user.user_id))
+        .await
+    {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(e) => job_error_response(e),
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Duration, Months, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Kind of the background job that enforces the retention policy
+pub const RETENTION_JOB: &str = "retention_cleanup";
+
+/// Kinds of data that are purged once they are old enough
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DataClass {
+    /// Raw rows of `user_interactions`, rolled up per day and post before they are purged
+    Interactions,
+    /// Daily rollups of interactions
+    InteractionRollups,
+    /// Notifications, by when they were last updated
+    Notifications,
+}
+
+impl DataClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataClass::Interactions => "interactions",
+            DataClass::InteractionRollups => "interaction_rollups",
+            DataClass::Notifications => "notifications",
+        }
+    }
+}
+
+/// How long each class of data is kept; `None` keeps it forever
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct RetentionPolicy {
+    #[schema(example = "90")]
+    pub interactions_days: Option<u32>,
+    #[schema(example = "24")]
+    pub rollups_months: Option<u32>,
+    #[schema(example = "180")]
+    pub notifications_days: Option<u32>,
+    /// Whether scheduled cleanups only report what they would purge
+    pub dry_run: bool,
+}
+
+impl RetentionPolicy {
+    /// Read `RETENTION_INTERACTIONS_DAYS`, `RETENTION_ROLLUPS_MONTHS`,
+    /// `RETENTION_NOTIFICATIONS_DAYS` and `RETENTION_DRY_RUN`
+    pub fn from_env() -> Self {
+        let period = |name: &str| parse_period(std::env::var(name).ok().as_deref());
+
+        Self {
+            interactions_days: period("RETENTION_INTERACTIONS_DAYS"),
+            rollups_months: period("RETENTION_ROLLUPS_MONTHS"),
+            notifications_days: period("RETENTION_NOTIFICATIONS_DAYS"),
+            dry_run: std::env::var("RETENTION_DRY_RUN")
+                .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Whether any class of data is purged at all
+    pub fn is_enabled(&self) -> bool {
+        !self.cutoffs(Utc::now()).is_empty()
+    }
+
+    /// The classes with a retention period, each with the time before which its rows go
+    pub fn cutoffs(&self, now: DateTime<Utc>) -> Vec<(DataClass, DateTime<Utc>)> {
+        let days = |days: Option<u32>| days.map(|days| now - Duration::days(days.into()));
+
+        [
+            (DataClass::Interactions, days(self.interactions_days)),
+            (
+                DataClass::InteractionRollups,
+                self.rollups_months
+                    .and_then(|months| now.checked_sub_months(Months::new(months))),
+            ),
+            (DataClass::Notifications, days(self.notifications_days)),
+        ]
+        .into_iter()
+        .filter_map(|(class, cutoff)| cutoff.map(|cutoff| (class, cutoff)))
+        .collect()
+    }
+}
+
+// A retention period is a positive whole number; anything else keeps data forever
+fn parse_period(value: Option<&str>) -> Option<u32> {
+    value
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .filter(|period| *period > 0)
+}
+
+/// Rows of one class purged by a cleanup, or that would be in a dry run
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PurgeResult {
+    pub data_class: DataClass,
+    /// Rows older than this were purged
+    pub cutoff: DateTime<Utc>,
+    pub rows: i64,
+}
+
+/// Outcome of a cleanup, stored as the result of its job
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub purged: Vec<PurgeResult>,
+}
+
+/// Request to run a cleanup now
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RunRetentionRequest {
+    /// Only report what would be purged; defaults to true
+    #[schema(example = true)]
+    pub dry_run: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period(Some("90")), Some(90));
+        assert_eq!(parse_period(Some(" 7 ")), Some(7));
+        assert_eq!(parse_period(Some("0")), None);
+        assert_eq!(parse_period(Some("-5")), None);
+        assert_eq!(parse_period(None), None);
+    }
+
+    #[test]
+    fn test_cutoffs() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 31, 12, 0, 0).unwrap();
+        let policy = RetentionPolicy {
+            interactions_days: Some(30),
+            rollups_months: Some(1),
+            notifications_days: None,
+            dry_run: false,
+        };
+
+        assert_eq!(
+            policy.cutoffs(now),
+            vec![
+                (
+                    DataClass::Interactions,
+                    Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap()
+                ),
+                // Clamped to the end of the shorter month
+                (
+                    DataClass::InteractionRollups,
+                    Utc.with_ymd_and_hms(2025, 2, 28, 12, 0, 0).unwrap()
+                ),
+            ]
+        );
+        assert!(!RetentionPolicy::default().is_enabled());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::cache::redis::RedisCache;
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::metrics::record_purged_rows;
+use crate::notification::service::unread_count_key;
+use crate::retention::model::{
+    DataClass, PurgeResult, RetentionPolicy, RetentionReport, RETENTION_JOB,
+};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use redis::AsyncCommands;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+// How often a cleanup runs, and how often instances check whether one is due
+const RETENTION_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+const RETENTION_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+// Rows deleted per statement, so a large backlog never holds locks for long
+const PURGE_BATCH_SIZE: i64 = 5000;
+
+/// Enforces the data retention policy.
+///
+/// Cleanups run as background jobs: one is queued every day by whichever instance notices
+/// first, and admins can queue one at any time, e.g. as a dry run to see what a policy would
+/// purge. The report of each run is the result of its job.
+pub struct RetentionService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+    policy: RetentionPolicy,
+}
+
+impl RetentionService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self {
+            pool,
+            redis_cache,
+            policy: RetentionPolicy::from_env(),
+        }
+    }
+
+    pub fn policy(&self) -> &RetentionPolicy {
+        &self.policy
+    }
+
+    /// Queue a daily cleanup while any class of data has a retention period
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        if !self.policy.is_enabled() {
+            info!("No retention periods configured, keeping all data");
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RETENTION_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                let payload = json!({ "dry_run": self.policy.dry_run });
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(RETENTION_JOB, payload, RETENTION_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule retention cleanup: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Purge every class of data past its retention period, or only count the rows that
+    /// would be purged
+    pub async fn run(&self, dry_run: bool) -> Result<RetentionReport, JobError> {
+        let mut purged = Vec::new();
+
+        for (data_class, cutoff) in self.policy.cutoffs(Utc::now()) {
+            let rows = if dry_run {
+                self.count_expired(data_class, cutoff).await?
+            } else {
+                let rows = self.purge(data_class, cutoff).await?;
+                record_purged_rows(data_class.as_str(), rows as u64);
+                rows
+            };
+
+            info!(
+                "Retention {}: {} {} rows older than {}",
+                if dry_run { "dry run" } else { "cleanup" },
+                rows,
+                data_class.as_str(),
+                cutoff
+            );
+            purged.push(PurgeResult {
+                data_class,
+                cutoff,
+                rows,
+            });
+        }
+
+        Ok(RetentionReport { dry_run, purged })
+    }
+
+    async fn count_expired(
+        &self,
+        data_class: DataClass,
+        cutoff: DateTime<Utc>,
+    ) -> Result<i64, JobError> {
+        let sql = match data_class {
+            DataClass::Interactions => {
+                "SELECT COUNT(*) FROM global.user_interactions WHERE created_at < $1"
+            }
+            DataClass::InteractionRollups => {
+                "SELECT COUNT(*) FROM global.interaction_rollups WHERE day < $1::DATE"
+            }
+            DataClass::Notifications => {
+                "SELECT COUNT(*) FROM global.notifications WHERE updated_at < $1"
+            }
+        };
+
+        let query = sqlx::query_scalar::<_, i64>(sql)
+            .bind(cutoff)
+            .fetch_one(&self.pool);
+        Ok(timed("retention.count", query).await?)
+    }
+
+    // Delete in batches until nothing older than the cutoff is left
+    async fn purge(&self, data_class: DataClass, cutoff: DateTime<Utc>) -> Result<i64, JobError> {
+        let mut total = 0;
+        loop {
+            let rows = match data_class {
+                DataClass::Interactions => self.purge_interactions(cutoff).await?,
+                DataClass::InteractionRollups => self.purge_rollups(cutoff).await?,
+                DataClass::Notifications => self.purge_notifications(cutoff).await?,
+            };
+            total += rows;
+            if rows < PURGE_BATCH_SIZE {
+                return Ok(total);
+            }
+        }
+    }
+
+    // Raw interactions are added to the daily rollups in the statement deleting them, so
+    // counts are never lost or added twice. Rollups are scaled up for sampled interactions.
+    async fn purge_interactions(&self, cutoff: DateTime<Utc>) -> Result<i64, JobError> {
+        let query = sqlx::query_scalar::<_, i64>(
+            r#"
+            WITH expired AS (
+                SELECT id FROM global.user_interactions
+                WHERE created_at < $1
+                ORDER BY id
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            ),
+            deleted AS (
+                DELETE FROM global.user_interactions ui
+                USING expired
+                WHERE ui.id = expired.id
+                RETURNING ui.post_id, ui.interaction_type, ui.created_at, ui.metadata
+            ),
+            rolled_up AS (
+                INSERT INTO global.interaction_rollups AS r (day, post_id, interaction_type, count)
+                SELECT
+                    (created_at AT TIME ZONE 'UTC')::DATE,
+                    post_id,
+                    interaction_type,
+                    ROUND(SUM(1.0 / COALESCE((metadata->>'sample_rate')::FLOAT8, 1.0)))::BIGINT
+                FROM deleted
+                WHERE post_id IS NOT NULL
+                GROUP BY 1, 2, 3
+                ON CONFLICT (day, post_id, interaction_type)
+                DO UPDATE SET count = r.count + EXCLUDED.count
+            )
+            SELECT COUNT(*) FROM deleted
+            "#,
+        )
+        .bind(cutoff)
+        .bind(PURGE_BATCH_SIZE)
+        .fetch_one(&self.pool);
+        Ok(timed("retention.purge_interactions", query).await?)
+    }
+
+    async fn purge_rollups(&self, cutoff: DateTime<Utc>) -> Result<i64, JobError> {
+        let query = sqlx::query(
+            r#"
+            DELETE FROM global.interaction_rollups
+            WHERE (day, post_id, interaction_type) IN (
+                SELECT day, post_id, interaction_type FROM global.interaction_rollups
+                WHERE day < $1::DATE
+                LIMIT $2
+            )
+            "#,
+        )
+        .bind(cutoff)
+        .bind(PURGE_BATCH_SIZE)
+        .execute(&self.pool);
+        let result = timed("retention.purge_rollups", query).await?;
+        Ok(result.rows_affected() as i64)
+    }
+
+    async fn purge_notifications(&self, cutoff: DateTime<Utc>) -> Result<i64, JobError> {
+        let query = sqlx::query(
+            r#"
+            WITH expired AS (
+                SELECT id FROM global.notifications
+                WHERE updated_at < $1
+                ORDER BY id
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            DELETE FROM global.notifications n
+            USING expired
+            WHERE n.id = expired.id
+            RETURNING n.recipient_id, n.is_read
+            "#,
+        )
+        .bind(cutoff)
+        .bind(PURGE_BATCH_SIZE)
+        .fetch_all(&self.pool);
+        let rows = timed("retention.purge_notifications", query).await?;
+
+        // Cached unread counts that included purged notifications are recounted on next read
+        let recipients: HashSet<Uuid> = rows
+            .iter()
+            .filter(|row| !row.get::<bool, _>("is_read"))
+            .map(|row| row.get("recipient_id"))
+            .collect();
+        if let (Some(cache), false) = (&self.redis_cache, recipients.is_empty()) {
+            let keys: Vec<String> = recipients.iter().map(unread_count_key).collect();
+            match cache.connection().await {
+                Ok(mut conn) => {
+                    if let Err(e) = conn.del::<_, ()>(keys).await {
+                        error!(
+                            "Failed to clear unread counts of purged notifications: {}",
+                            e
+                        );
+                    }
+                }
+                Err(e) => error!(
+                    "Failed to clear unread counts of purged notifications: {}",
+                    e
+                ),
+            }
+        }
+
+        Ok(rows.len() as i64)
+    }
+
+    async fn run_job(&self, job: &Job) -> Result<Value, JobError> {
+        let dry_run = job
+            .payload
+            .get("dry_run")
+            .and_then(Value::as_bool)
+            .unwrap_or(true);
+        let report = self.run(dry_run).await?;
+        serde_json::to_value(report).map_err(|e| JobError::InternalError(e.to_string()))
+    }
+}
+
+impl JobHandler for RetentionService {
+    fn kind(&self) -> &'static str {
+        RETENTION_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
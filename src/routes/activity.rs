@@ -0,0 +1,25 @@
+use crate::activity::{controller, service::ActivityService};
+use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
+use crate::cache::redis::RedisCache;
+use crate::db::router::DbRouter;
+use axum::{middleware, routing::get, Router};
+use std::sync::Arc;
+
+/// Set up activity feed routes
+pub fn routes(db: DbRouter, redis_cache: Option<RedisCache>) -> Router {
+    let activity_service = Arc::new(ActivityService::new(db, redis_cache));
+
+    Router::new()
+        .route(
+            "/users/:username/activity",
+            get(controller::get_user_activity)
+                .route_layer(middleware::from_fn(optional_auth_middleware)),
+        )
+        .route(
+            "/activity/privacy",
+            get(controller::get_privacy_settings)
+                .put(controller::set_privacy_settings)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(activity_service)
+}
@@ -1,41 +1,79 @@
-use crate::analytics::{controller, service::AnalyticsService};
-use crate::auth::middleware::auth_middleware;
+use crate::analytics::{controller, live::LiveDashboard, service::AnalyticsService};
+use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
 use crate::cache::redis::RedisCache;
+use crate::cache::response::{cache_response, ResponseCache, PUBLIC_STATS_CACHE};
+use crate::db::router::DbRouter;
+use crate::websocket::live_dashboard::live_dashboard_ws;
 use axum::{
     middleware,
     routing::{get, post},
     Router,
 };
-use sqlx::PgPool;
 use std::sync::Arc;
 
 /// Set up analytics routes
-pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
-    let analytics_service = Arc::new(AnalyticsService::new(pool.clone(), redis_cache));
+pub fn routes(
+    db: DbRouter,
+    redis_cache: Option<RedisCache>,
+    live_dashboard: Arc<LiveDashboard>,
+) -> Router {
+    let stats_cache = Arc::new(ResponseCache::new(
+        redis_cache.clone(),
+        PUBLIC_STATS_CACHE.configured(),
+    ));
+    let analytics_service = Arc::new(AnalyticsService::new(db, redis_cache));
+
+    // Authenticated with a `token` query parameter, as browsers can't set WebSocket headers
+    let live_routes = Router::new()
+        .route("/analytics/live/ws", get(live_dashboard_ws))
+        .with_state(live_dashboard);
 
     Router::new()
         .route(
-            "/api/analytics/engagement",
+            "/analytics/engagement",
             get(controller::get_user_engagement).route_layer(middleware::from_fn(auth_middleware)),
         )
         .route(
-            "/api/analytics/engagement/user/:target_user_id",
+            "/analytics/engagement/user/:target_user_id",
             get(controller::get_user_engagement_by_id)
                 .route_layer(middleware::from_fn(auth_middleware)),
         )
-        .route("/api/analytics/posts", get(controller::get_post_stats))
+        .route("/analytics/posts", get(controller::get_post_stats))
         .route(
-            "/api/analytics/posts/:post_id",
+            "/analytics/posts/:post_id",
             get(controller::get_post_stats_by_id),
         )
         .route(
-            "/api/analytics/posts/:post_id/time/:time_range",
+            "/analytics/posts/:post_id/time/:time_range",
             get(controller::get_post_stats_by_time),
         )
         .route(
-            "/api/analytics/refresh",
+            "/analytics/posts/:post_id/audience",
+            get(controller::get_post_audience).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/analytics/posts/:post_id/campaigns",
+            get(controller::get_post_campaigns).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/analytics/views",
+            post(controller::record_view)
+                .route_layer(middleware::from_fn(optional_auth_middleware)),
+        )
+        .route(
+            "/analytics/activity-heatmap",
+            get(controller::get_activity_heatmap).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/stats/public",
+            get(controller::get_public_stats)
+                .route_layer(middleware::from_fn_with_state(stats_cache, cache_response)),
+        )
+        .route(
+            "/analytics/refresh",
             post(controller::refresh_analytics_views)
                 .route_layer(middleware::from_fn(auth_middleware)),
         )
         .with_state(analytics_service)
+        .merge(live_routes)
 }
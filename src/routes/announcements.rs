@@ -0,0 +1,27 @@
+use crate::announcement::{controller, service::AnnouncementService};
+use crate::auth::middleware::auth_middleware;
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use std::sync::Arc;
+
+/// Set up announcement routes; their jobs are scheduled on the job service from the
+/// `Extension` layer
+pub fn routes(announcement_service: Arc<AnnouncementService>) -> Router {
+    Router::new()
+        .route(
+            "/admin/announcements",
+            post(controller::create_announcement)
+                .get(controller::list_announcements)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/admin/announcements/:id",
+            get(controller::get_announcement)
+                .delete(controller::cancel_announcement)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(announcement_service)
+}
@@ -1,11 +1,24 @@
 use crate::auth::controller;
-use axum::{routing::post, Router};
+use crate::auth::middleware::auth_middleware;
+use crate::notification::service::NotificationService;
+use axum::{
+    middleware,
+    routing::{post, put},
+    Extension, Router,
+};
 use sqlx::PgPool;
+use std::sync::Arc;
 
-/// Authentication routes for login and registration
-pub fn routes(pool: PgPool) -> Router {
+/// Authentication routes for login, registration and password changes
+pub fn routes(pool: PgPool, notification_service: Arc<NotificationService>) -> Router {
     Router::new()
-        .route("/api/auth/login", post(controller::login))
-        .route("/api/auth/register", post(controller::register))
+        .route("/auth/login", post(controller::login))
+        .route("/auth/register", post(controller::register))
+        .route(
+            "/users/me/password",
+            put(controller::change_password)
+                .layer(Extension(notification_service))
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
         .with_state(pool)
 }
@@ -0,0 +1,20 @@
+use crate::auth::middleware::auth_middleware;
+use crate::block::{controller, service::BlockService};
+use crate::cache::redis::RedisCache;
+use axum::{middleware, routing::post, Router};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up user blocking routes
+pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
+    let block_service = Arc::new(BlockService::new(pool, redis_cache));
+
+    Router::new()
+        .route(
+            "/users/:id/block",
+            post(controller::block_user)
+                .delete(controller::unblock_user)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(block_service)
+}
@@ -0,0 +1,12 @@
+use crate::auth::middleware::auth_middleware;
+use crate::cache::{controller, warmer::CacheWarmer};
+use axum::{middleware, routing::post, Router};
+use std::sync::Arc;
+
+/// Set up cache routes; warmings are queued on the job service from the `Extension` layer
+pub fn routes(cache_warmer: Option<Arc<CacheWarmer>>) -> Router {
+    Router::new()
+        .route("/admin/cache/warm", post(controller::warm_cache))
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(cache_warmer)
+}
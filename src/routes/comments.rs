@@ -1,30 +1,107 @@
 use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
-use crate::comment::controller::{create_comment, delete_comment, get_post_comments};
+use crate::cache::redis::RedisCache;
+use crate::comment::controller::{
+    approve_comment, create_comment, create_guest_comment, delete_comment, get_comment,
+    get_pending_comments, get_post_comments, lock_comments, pin_comment, react_to_comment,
+    reject_comment, remove_comment_reaction, report_comment, set_shadow_ban, subscribe_to_thread,
+    unlock_comments, unpin_comment, unsubscribe_from_thread,
+};
 use crate::comment::service::CommentService;
+use crate::rate_limit::{rate_limit, RateLimiter, COMMENT_RATE_LIMIT};
 use axum::{
     middleware,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use std::sync::Arc;
 
 /// Create a router for comment routes
-pub fn routes(comment_service: Arc<CommentService>) -> Router {
+pub fn routes(comment_service: Arc<CommentService>, redis_cache: Option<RedisCache>) -> Router {
+    let comment_limiter = Arc::new(RateLimiter::new(
+        redis_cache,
+        COMMENT_RATE_LIMIT.configured(),
+    ));
+
     Router::new()
         // Route for getting post comments (public, but with optional auth)
         .route(
-            "/api/posts/:id/comments",
+            "/posts/:id/comments",
             get(get_post_comments).route_layer(middleware::from_fn(optional_auth_middleware)),
         )
-        // Route for creating comments (requires authentication)
+        // Route for creating comments (requires authentication, rate limited)
+        .route(
+            "/posts/:id/comments",
+            post(create_comment)
+                .route_layer(middleware::from_fn_with_state(comment_limiter, rate_limit))
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        // Route for guest comments (public, captcha-verified and moderated)
+        .route("/posts/:id/comments/guest", post(create_guest_comment))
+        // Routes for locking a post's comments (post author or admin)
+        .route(
+            "/posts/:id/comments/lock",
+            post(lock_comments).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/posts/:id/comments/unlock",
+            post(unlock_comments).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        // Routes for following a comment thread (requires authentication)
+        .route(
+            "/posts/:id/comments/:comment_id/subscribe",
+            post(subscribe_to_thread).route_layer(middleware::from_fn(auth_middleware)),
+        )
         .route(
-            "/api/posts/:id/comments",
-            post(create_comment).route_layer(middleware::from_fn(auth_middleware)),
+            "/posts/:id/comments/:comment_id/unsubscribe",
+            post(unsubscribe_from_thread).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        // Route for finding a comment's place in its post, e.g. from a notification
+        .route(
+            "/comments/:id",
+            get(get_comment).route_layer(middleware::from_fn(optional_auth_middleware)),
         )
         // Route for deleting comments (requires authentication)
         .route(
-            "/api/comments/:id",
+            "/comments/:id",
             delete(delete_comment).route_layer(middleware::from_fn(auth_middleware)),
         )
+        // Route for pinning comments (post author or admin)
+        .route(
+            "/comments/:id/pin",
+            post(pin_comment)
+                .delete(unpin_comment)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        // Routes for reacting to and reporting comments, which score them (requires authentication)
+        .route(
+            "/comments/:id/reactions",
+            post(react_to_comment).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/comments/:id/reactions/:reaction",
+            delete(remove_comment_reaction).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/comments/:id/report",
+            post(report_comment).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        // Moderation queue routes (admin only)
+        .route(
+            "/moderation/comments",
+            get(get_pending_comments).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/moderation/comments/:id/approve",
+            post(approve_comment).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/moderation/comments/:id/reject",
+            post(reject_comment).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        // Route for shadow-banning abusive users (admin only)
+        .route(
+            "/admin/users/:user_id/shadow-ban",
+            put(set_shadow_ban).route_layer(middleware::from_fn(auth_middleware)),
+        )
         .layer(axum::extract::Extension(comment_service))
 }
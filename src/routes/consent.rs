@@ -0,0 +1,19 @@
+use crate::auth::middleware::auth_middleware;
+use crate::cache::redis::RedisCache;
+use crate::consent::{controller, service::ConsentService};
+use axum::{middleware, routing::get, Router};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up tracking consent routes
+pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
+    let consent_service = Arc::new(ConsentService::new(pool, redis_cache));
+
+    Router::new()
+        .route(
+            "/users/me/consent",
+            get(controller::get_consent).put(controller::set_consent),
+        )
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(consent_service)
+}
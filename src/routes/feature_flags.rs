@@ -0,0 +1,29 @@
+use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
+use crate::feature_flags::{controller, service::FeatureFlagService};
+use axum::{
+    middleware,
+    routing::{get, put},
+    Router,
+};
+use std::sync::Arc;
+
+/// Set up feature flag routes
+pub fn routes(feature_flag_service: Arc<FeatureFlagService>) -> Router {
+    Router::new()
+        .route(
+            "/feature-flags",
+            get(controller::get_evaluated_flags)
+                .route_layer(middleware::from_fn(optional_auth_middleware)),
+        )
+        .route(
+            "/admin/feature-flags",
+            get(controller::list_flags).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/admin/feature-flags/:key",
+            put(controller::upsert_flag)
+                .delete(controller::delete_flag)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(feature_flag_service)
+}
@@ -0,0 +1,38 @@
+use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
+use crate::cache::redis::RedisCache;
+use crate::feedback::{controller, service::FeedbackService};
+use crate::rate_limit::{rate_limit_clients, RateLimiter, FEEDBACK_RATE_LIMIT};
+use axum::{
+    middleware,
+    routing::{get, post, put},
+    Router,
+};
+use std::sync::Arc;
+
+/// Set up the feedback form, rate limited per user or client address, and its admin inbox
+pub fn routes(feedback_service: Arc<FeedbackService>, redis_cache: Option<RedisCache>) -> Router {
+    let feedback_limiter = Arc::new(RateLimiter::new(
+        redis_cache,
+        FEEDBACK_RATE_LIMIT.configured(),
+    ));
+
+    Router::new()
+        .route(
+            "/feedback",
+            post(controller::create_feedback)
+                .route_layer(middleware::from_fn_with_state(
+                    feedback_limiter,
+                    rate_limit_clients,
+                ))
+                .route_layer(middleware::from_fn(optional_auth_middleware)),
+        )
+        .route(
+            "/admin/feedback",
+            get(controller::list_feedback).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/admin/feedback/:id",
+            put(controller::update_feedback).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(feedback_service)
+}
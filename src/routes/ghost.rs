@@ -0,0 +1,53 @@
+use crate::auth::middleware::auth_middleware;
+use crate::cache::redis::RedisCache;
+use crate::db::router::DbRouter;
+use crate::ghost::{controller, service::ContentApiKeyService};
+use axum::{
+    middleware,
+    routing::{delete, get},
+    Router,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up the read-only Ghost-compatible content API. It lives outside of the versioned API at
+/// the paths Ghost uses, and Ghost clients add a trailing slash, so both forms are routed.
+pub fn content_api_routes(db: DbRouter, redis_cache: Option<RedisCache>) -> Router {
+    let app_state = (db, redis_cache);
+
+    Router::new()
+        .route("/ghost/api/content/posts", get(controller::browse_posts))
+        .route("/ghost/api/content/posts/", get(controller::browse_posts))
+        .route("/ghost/api/content/posts/:id", get(controller::read_post))
+        .route("/ghost/api/content/posts/:id/", get(controller::read_post))
+        .route(
+            "/ghost/api/content/posts/slug/:slug",
+            get(controller::read_post_by_slug),
+        )
+        .route(
+            "/ghost/api/content/posts/slug/:slug/",
+            get(controller::read_post_by_slug),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            controller::require_content_api_key,
+        ))
+        .with_state(app_state)
+}
+
+/// Set up admin management of Content API keys
+pub fn key_routes(pool: PgPool) -> Router {
+    let key_service = Arc::new(ContentApiKeyService::new(pool));
+
+    Router::new()
+        .route(
+            "/admin/content-api-keys",
+            get(controller::list_keys).post(controller::create_key),
+        )
+        .route(
+            "/admin/content-api-keys/:id",
+            delete(controller::delete_key),
+        )
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(key_service)
+}
@@ -4,10 +4,14 @@ use axum::{
 };
 use serde::Serialize;
 use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
 #[allow(unused_imports)]
 use utoipa::{OpenApi, ToSchema};
 
 use crate::auth::middleware::{auth_middleware, AuthUser};
+use crate::cache::breaker;
+use crate::cache::redis::RedisCache;
 
 #[derive(Serialize, ToSchema)]
 pub struct HealthResponse {
@@ -22,7 +26,7 @@ pub struct HealthResponse {
     get,
     path = "/api/health",
     responses(
-        (status = 200, description = "Server is healthy"),
+        (status = 200, description = "Server is healthy", body = HealthResponse),
     ),
     tag = "health"
 )]
@@ -43,7 +47,7 @@ pub async fn health_check() -> impl IntoResponse {
     get,
     path = "/api/health/protected",
     responses(
-        (status = 200, description = "Server is healthy and user is authenticated"),
+        (status = 200, description = "Server is healthy and user is authenticated", body = HealthResponse),
         (status = 401, description = "Unauthorized - Invalid or missing token")
     ),
     security(
@@ -73,11 +77,93 @@ pub async fn protected_health_check(
     )
 }
 
-pub fn routes(pool: PgPool) -> Router {
-    Router::new().route("/api/health", get(health_check)).route(
-        "/api/health/protected",
-        get(protected_health_check)
-            .route_layer(from_fn(auth_middleware))
-            .with_state(pool),
-    )
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// `ready`, or `unavailable` when the database cannot be reached
+    pub status: String,
+    /// `ok` or `error`
+    pub database: String,
+    /// `ok`, `error`, or `disabled` when no Redis is configured. Redis is optional, so an
+    /// unreachable Redis leaves the server ready.
+    pub redis: String,
+    /// State of each circuit breaker: `closed`, `open` or `half_open`
+    pub breakers: HashMap<String, String>,
+}
+
+#[derive(Clone)]
+pub struct ReadinessState {
+    pool: PgPool,
+    redis_cache: Option<Arc<RedisCache>>,
+}
+
+/// Check the database and Redis, and read the state of the circuit breakers, as the
+/// readiness probe and the health monitor do
+pub async fn check_readiness(pool: &PgPool, redis_cache: Option<&RedisCache>) -> ReadinessResponse {
+    let database_ok = sqlx::query("SELECT 1").fetch_one(pool).await.is_ok();
+    let redis = match redis_cache {
+        Some(cache) => match cache.ping().await {
+            Ok(()) => "ok",
+            Err(_) => "error",
+        },
+        None => "disabled",
+    };
+    let breakers = breaker::breakers()
+        .iter()
+        .map(|breaker| {
+            (
+                breaker.name().to_string(),
+                breaker.state().as_str().to_string(),
+            )
+        })
+        .collect();
+
+    ReadinessResponse {
+        status: if database_ok { "ready" } else { "unavailable" }.to_string(),
+        database: if database_ok { "ok" } else { "error" }.to_string(),
+        redis: redis.to_string(),
+        breakers,
+    }
+}
+
+/// Readiness probe
+///
+/// Checks the database and Redis, and reports the state of the circuit breakers. Answers
+/// 503 when the database cannot be reached. While the Redis circuit breaker is open, Redis
+/// is reported as failing without being contacted, except once its cooldown is over, when
+/// the check probes it.
+#[utoipa::path(
+    get,
+    path = "/api/health/ready",
+    responses(
+        (status = 200, description = "Ready to serve requests", body = ReadinessResponse),
+        (status = 503, description = "The database cannot be reached", body = ReadinessResponse)
+    ),
+    tag = "health"
+)]
+pub async fn readiness_check(State(state): State<ReadinessState>) -> impl IntoResponse {
+    let readiness = check_readiness(&state.pool, state.redis_cache.as_deref()).await;
+    let status = if readiness.database == "ok" {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+pub fn routes(pool: PgPool, redis_cache: Option<Arc<RedisCache>>) -> Router {
+    Router::new()
+        .route("/health", get(health_check))
+        .route(
+            "/health/ready",
+            get(readiness_check).with_state(ReadinessState {
+                pool: pool.clone(),
+                redis_cache,
+            }),
+        )
+        .route(
+            "/health/protected",
+            get(protected_health_check)
+                .route_layer(from_fn(auth_middleware))
+                .with_state(pool),
+        )
 }
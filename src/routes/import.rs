@@ -0,0 +1,20 @@
+use crate::auth::middleware::auth_middleware;
+use crate::cache::redis::RedisCache;
+use crate::import::{controller, model::MAX_IMPORT_BYTES, service::ImportService};
+use axum::{extract::DefaultBodyLimit, middleware, routing::post, Router};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up post import routes
+pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
+    let import_service = Arc::new(ImportService::new(pool, redis_cache));
+
+    Router::new()
+        .route(
+            "/admin/import",
+            post(controller::import_posts)
+                .layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES))
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(import_service)
+}
@@ -0,0 +1,31 @@
+use crate::auth::middleware::auth_middleware;
+use crate::invite::{controller, service::InviteService};
+use axum::{
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up the admins' invite code routes
+pub fn routes(pool: PgPool) -> Router {
+    let invite_service = Arc::new(InviteService::new(pool));
+
+    Router::new()
+        .route(
+            "/admin/invites",
+            post(controller::create_invites)
+                .get(controller::list_invites)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/admin/invites/usage",
+            get(controller::get_invite_usage).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/admin/invites/:id",
+            delete(controller::delete_invite).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(invite_service)
+}
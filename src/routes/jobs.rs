@@ -0,0 +1,11 @@
+use crate::auth::middleware::auth_middleware;
+use crate::jobs::controller;
+use axum::{middleware, routing::get, Router};
+
+/// Set up background job routes; the job service comes from the `Extension` layer
+pub fn routes() -> Router {
+    Router::new().route(
+        "/admin/jobs/:id",
+        get(controller::get_job).route_layer(middleware::from_fn(auth_middleware)),
+    )
+}
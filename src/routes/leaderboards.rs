@@ -0,0 +1,17 @@
+use crate::leaderboard::{controller, service::LeaderboardService};
+use axum::{routing::get, Router};
+use std::sync::Arc;
+
+/// Set up leaderboard routes
+pub fn routes(leaderboard_service: Arc<LeaderboardService>) -> Router {
+    Router::new()
+        .route(
+            "/leaderboards/authors",
+            get(controller::get_author_leaderboard),
+        )
+        .route(
+            "/leaderboards/commenters",
+            get(controller::get_commenter_leaderboard),
+        )
+        .with_state(leaderboard_service)
+}
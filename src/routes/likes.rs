@@ -0,0 +1,39 @@
+use crate::analytics::service::AnalyticsService;
+use crate::auth::middleware::auth_middleware;
+use crate::cache::redis::RedisCache;
+use crate::like::{controller, service::LikeService};
+use crate::rate_limit::{rate_limit, RateLimiter, LIKE_RATE_LIMIT};
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up post like routes and the moderation view of flagged likes
+pub fn routes(
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+    analytics_service: Arc<AnalyticsService>,
+) -> Router {
+    let like_limiter = Arc::new(RateLimiter::new(
+        redis_cache.clone(),
+        LIKE_RATE_LIMIT.configured(),
+    ));
+    let like_service = Arc::new(LikeService::new(pool, redis_cache, analytics_service));
+
+    Router::new()
+        .route(
+            "/posts/:id/like",
+            post(controller::like_post)
+                .delete(controller::unlike_post)
+                .route_layer(middleware::from_fn_with_state(like_limiter, rate_limit))
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/moderation/likes",
+            get(controller::get_like_flags).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(like_service)
+}
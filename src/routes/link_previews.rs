@@ -0,0 +1,29 @@
+use crate::auth::middleware::auth_middleware;
+use crate::cache::redis::RedisCache;
+use crate::link_preview::{controller, service::LinkPreviewService};
+use crate::rate_limit::{rate_limit, RateLimiter, LINK_PREVIEW_RATE_LIMIT};
+use axum::{middleware, routing::get, Router};
+use std::sync::Arc;
+
+/// Set up link preview routes, rate limited per user since a miss makes the server fetch a page
+pub fn routes(
+    link_preview_service: Arc<LinkPreviewService>,
+    redis_cache: Option<RedisCache>,
+) -> Router {
+    let link_preview_limiter = Arc::new(RateLimiter::new(
+        redis_cache,
+        LINK_PREVIEW_RATE_LIMIT.configured(),
+    ));
+
+    Router::new()
+        .route(
+            "/link-previews",
+            get(controller::get_link_preview)
+                .route_layer(middleware::from_fn_with_state(
+                    link_preview_limiter,
+                    rate_limit,
+                ))
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(link_preview_service)
+}
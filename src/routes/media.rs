@@ -0,0 +1,35 @@
+use crate::auth::middleware::auth_middleware;
+use crate::cache::redis::RedisCache;
+use crate::db::router::DbRouter;
+use crate::media::controller;
+use crate::media::model::ImageConfig;
+use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{get, put},
+    Router,
+};
+
+/// Set up user avatar routes; post attachments and cover images live with the post routes
+pub fn routes(db: DbRouter, redis_cache: Option<RedisCache>) -> Router {
+    let app_state = (db, redis_cache);
+
+    let public_routes = Router::new()
+        .route(
+            "/users/:user_id/avatar/:upload_id/:file",
+            get(controller::get_avatar_file),
+        )
+        .with_state(app_state.clone());
+
+    let private_routes = Router::new()
+        .route(
+            "/users/me/avatar",
+            put(controller::upload_avatar)
+                .delete(controller::delete_avatar)
+                .layer(DefaultBodyLimit::max(ImageConfig::global().max_bytes)),
+        )
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(app_state);
+
+    public_routes.merge(private_routes)
+}
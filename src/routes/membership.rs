@@ -0,0 +1,28 @@
+use crate::auth::middleware::auth_middleware;
+use crate::membership::{controller, service::MembershipService};
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up membership routes
+pub fn routes(pool: PgPool) -> Router {
+    let membership_service = Arc::new(MembershipService::new(pool));
+
+    Router::new()
+        .route("/membership/tiers", get(controller::list_tiers))
+        .route(
+            "/admin/membership/tiers",
+            post(controller::create_tier).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/admin/users/:user_id/membership",
+            get(controller::get_user_membership)
+                .put(controller::set_user_membership)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(membership_service)
+}
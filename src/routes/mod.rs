@@ -1,8 +1,37 @@
+pub mod activity;
 pub mod analytics;
+pub mod announcements;
 pub mod auth;
+pub mod blocks;
+pub mod cache;
 pub mod comments;
+pub mod consent;
+pub mod feature_flags;
+pub mod feedback;
+pub mod ghost;
 pub mod health;
+pub mod import;
+pub mod invites;
+pub mod jobs;
+pub mod leaderboards;
+pub mod likes;
+pub mod link_previews;
+pub mod media;
+pub mod membership;
+pub mod newsletter;
 pub mod notifications;
 pub mod posts;
+pub mod quota;
+pub mod reading_lists;
 pub mod recommendations;
+pub mod reputation;
+pub mod retention;
+pub mod seed;
+pub mod settings;
+pub mod storage;
+pub mod tags;
+pub mod tenants;
+pub mod tips;
+pub mod title_tests;
 pub mod users;
+pub mod versioning;
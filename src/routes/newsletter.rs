@@ -0,0 +1,29 @@
+use crate::auth::middleware::auth_middleware;
+use crate::newsletter::{controller, service::NewsletterService};
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use std::sync::Arc;
+
+/// Set up newsletter routes; campaigns are queued on the job service from the `Extension` layer
+pub fn routes(newsletter_service: Arc<NewsletterService>) -> Router {
+    Router::new()
+        .route("/newsletter/subscribe", post(controller::subscribe))
+        .route("/newsletter/confirm", get(controller::confirm))
+        .route("/newsletter/unsubscribe", get(controller::unsubscribe))
+        .route(
+            "/admin/newsletter/campaigns",
+            post(controller::create_campaign).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/admin/newsletter/campaigns/:id",
+            get(controller::get_campaign).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/admin/newsletter/campaigns/:id/deliveries",
+            get(controller::get_deliveries).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(newsletter_service)
+}
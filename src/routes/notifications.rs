@@ -1,13 +1,38 @@
-use axum::{routing::get, Router};
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
 use std::sync::Arc;
 
+use crate::auth::middleware::auth_middleware;
+use crate::notification::{controller, service::NotificationService};
 use crate::websocket::notifications::{ws_handler, NotificationState};
 
 /// Create a router for notifications
-pub fn routes(notification_state: Arc<NotificationState>) -> Router {
+pub fn routes(
+    notification_state: Arc<NotificationState>,
+    notification_service: Arc<NotificationService>,
+) -> Router {
+    let rest_routes = Router::new()
+        .route("/notifications", get(controller::list_notifications))
+        .route(
+            "/notifications/unread-count",
+            get(controller::get_unread_count),
+        )
+        .route("/notifications/:id/read", post(controller::mark_as_read))
+        .route("/notifications/read-all", post(controller::mark_all_read))
+        .route(
+            "/notifications/quiet-hours",
+            get(controller::get_quiet_hours).put(controller::set_quiet_hours),
+        )
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(notification_service);
+
     Router::new()
-        .route("/api/notifications/ws", get(ws_handler))
+        .route("/notifications/ws", get(ws_handler))
         .with_state(notification_state)
+        .merge(rest_routes)
 }
 
 /// Configure notification routes
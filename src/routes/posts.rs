@@ -1,28 +1,98 @@
 use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
 use crate::cache::redis::RedisCache;
-use crate::post::controller;
+use crate::cache::response::{
+    cache_response, CachePolicy, ResponseCache, POPULAR_POSTS_CACHE, POST_ARCHIVE_CACHE,
+    POST_LIST_CACHE,
+};
+use crate::db::router::DbRouter;
+use crate::media::controller as media;
+use crate::media::model::{AttachmentConfig, ImageConfig};
+use crate::post::{controller, export, oembed};
 use axum::{
+    extract::DefaultBodyLimit,
     middleware,
     routing::{delete, get, post, put},
     Router,
 };
-use sqlx::PgPool;
+use std::sync::Arc;
 
-pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
+pub fn routes(db: DbRouter, redis_cache: Option<RedisCache>) -> Router {
     // Create routers with their state once
-    let app_state = (pool, redis_cache);
+    let app_state = (db, redis_cache.clone());
+
+    // Public listings can be cached by browsers, CDNs and the server
+    let cached = |policy: CachePolicy| {
+        middleware::from_fn_with_state(
+            Arc::new(ResponseCache::new(redis_cache.clone(), policy.configured())),
+            cache_response,
+        )
+    };
 
     let public_routes = Router::new()
         // Order matters here - more specific routes first
-        .route("/api/posts/popular", get(controller::get_popular_posts))
-        .route("/api/posts/view/:id_or_slug", get(controller::get_post))
+        .route(
+            "/posts",
+            get(controller::list_posts).route_layer(cached(POST_LIST_CACHE)),
+        )
+        .route(
+            "/posts/popular",
+            get(controller::get_popular_posts).route_layer(cached(POPULAR_POSTS_CACHE)),
+        )
+        .route(
+            "/posts/archive",
+            get(controller::get_archive).route_layer(cached(POST_ARCHIVE_CACHE)),
+        )
+        .route(
+            "/posts/archive/:year/:month",
+            get(controller::get_archive_posts).route_layer(cached(POST_ARCHIVE_CACHE)),
+        )
+        .route("/posts/view/:id_or_slug", get(controller::get_post))
+        .route("/posts/:id/meta", get(controller::get_post_meta))
+        .route("/posts/:id/attachments", get(media::list_attachments))
+        .route(
+            "/posts/:id/attachments/:attachment_id",
+            get(media::download_attachment),
+        )
+        .route(
+            "/posts/:id/cover-image/:image_id/:file",
+            get(media::get_cover_image_file),
+        )
+        .route("/oembed", get(oembed::get_oembed))
         .route_layer(middleware::from_fn(optional_auth_middleware))
         .with_state(app_state.clone());
 
     let private_routes = Router::new()
-        .route("/api/posts", post(controller::create_post))
-        .route("/api/posts/edit/:id", put(controller::update_post))
-        .route("/api/posts/delete/:id", delete(controller::delete_post))
+        .route("/posts", post(controller::create_post))
+        .route("/posts/mine", get(controller::get_my_posts))
+        .route("/posts/edit/:id", put(controller::update_post))
+        .route("/posts/delete/:id", delete(controller::delete_post))
+        .route("/posts/:id/duplicate", post(controller::duplicate_post))
+        .route(
+            "/posts/:id/edit-lock",
+            post(controller::acquire_edit_lock).delete(controller::release_edit_lock),
+        )
+        // Uploads are the raw file, so they get the attachment size limit instead of the default
+        .route(
+            "/posts/:id/attachments",
+            post(media::upload_attachment)
+                .layer(DefaultBodyLimit::max(AttachmentConfig::global().max_bytes)),
+        )
+        .route(
+            "/posts/:id/attachments/:attachment_id",
+            delete(media::delete_attachment),
+        )
+        .route(
+            "/posts/:id/cover-image",
+            post(media::upload_cover_image)
+                .layer(DefaultBodyLimit::max(ImageConfig::global().max_bytes)),
+        )
+        .route("/posts/:id/export", get(export::export_post))
+        .route("/moderation/posts", get(controller::get_duplicate_flags))
+        .route("/admin/export/site", post(export::start_site_export))
+        .route(
+            "/admin/export/site/:job_id",
+            get(export::download_site_export),
+        )
         .route_layer(middleware::from_fn(auth_middleware))
         .with_state(app_state);
 
@@ -0,0 +1,12 @@
+use crate::auth::middleware::auth_middleware;
+use crate::quota::{controller, service::QuotaService};
+use axum::{middleware, routing::get, Router};
+use std::sync::Arc;
+
+/// Set up daily quota routes
+pub fn routes(quota_service: Arc<QuotaService>) -> Router {
+    Router::new()
+        .route("/users/me/quota", get(controller::get_quota))
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(quota_service)
+}
@@ -0,0 +1,46 @@
+use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
+use crate::reading_list::{controller, service::ReadingListService};
+use axum::{
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up the current user's reading lists and the public view of a list
+pub fn routes(pool: PgPool) -> Router {
+    let reading_list_service = Arc::new(ReadingListService::new(pool));
+
+    Router::new()
+        .route(
+            "/users/me/lists",
+            get(controller::list_reading_lists)
+                .post(controller::create_reading_list)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/users/me/lists/:id",
+            get(controller::get_my_reading_list)
+                .put(controller::update_reading_list)
+                .delete(controller::delete_reading_list)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/users/me/lists/:id/posts",
+            post(controller::add_reading_list_post)
+                .put(controller::reorder_reading_list)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/users/me/lists/:id/posts/:post_id",
+            delete(controller::remove_reading_list_post)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/lists/:id",
+            get(controller::get_reading_list)
+                .route_layer(middleware::from_fn(optional_auth_middleware)),
+        )
+        .with_state(reading_list_service)
+}
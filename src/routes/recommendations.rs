@@ -11,8 +11,10 @@ use sqlx::PgPool;
 use std::sync::Arc;
 
 /// Set up recommendations routes
-pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
-    let recommendation_service = Arc::new(RecommendationService::new(pool.clone(), redis_cache));
+pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>, embeddings_enabled: bool) -> Router {
+    let recommendation_service = Arc::new(
+        RecommendationService::new(pool.clone(), redis_cache).with_embeddings(embeddings_enabled),
+    );
 
     Router::new()
         .route(
@@ -21,13 +23,24 @@ pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
                 .route_layer(middleware::from_fn(auth_middleware)),
         )
         .route(
-            "/similar/:post_id",
+            "/recommendations/similar/:post_id",
             get(controller::get_similar_posts).route_layer(middleware::from_fn(auth_middleware)),
         )
         .route(
-            "/model/refresh",
+            "/recommendations/digest",
+            get(controller::get_digest_settings)
+                .put(controller::set_digest_settings)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/recommendations/refresh",
             post(controller::refresh_recommendation_model)
                 .route_layer(middleware::from_fn(auth_middleware)),
         )
+        .route(
+            "/admin/recommendations/preview",
+            get(controller::preview_recommendations)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
         .with_state(recommendation_service)
 }
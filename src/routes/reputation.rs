@@ -0,0 +1,10 @@
+use crate::reputation::{controller, service::ReputationService};
+use axum::{routing::get, Router};
+use std::sync::Arc;
+
+/// Set up public profile routes
+pub fn routes(reputation_service: Arc<ReputationService>) -> Router {
+    Router::new()
+        .route("/users/:username", get(controller::get_user_profile))
+        .with_state(reputation_service)
+}
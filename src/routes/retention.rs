@@ -0,0 +1,18 @@
+use crate::auth::middleware::auth_middleware;
+use crate::retention::{controller, service::RetentionService};
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use std::sync::Arc;
+
+/// Set up data retention routes; cleanups are queued on the job service from the
+/// `Extension` layer
+pub fn routes(retention_service: Arc<RetentionService>) -> Router {
+    Router::new()
+        .route("/admin/retention", get(controller::get_policy))
+        .route("/admin/retention/run", post(controller::run_cleanup))
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(retention_service)
+}
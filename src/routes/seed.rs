@@ -0,0 +1,12 @@
+use crate::auth::middleware::auth_middleware;
+use crate::seed::{controller, service::SeedService};
+use axum::{middleware, routing::post, Router};
+use std::sync::Arc;
+
+/// Set up seed data routes; generation is queued on the job service from the `Extension` layer
+pub fn routes(seed_service: Option<Arc<SeedService>>) -> Router {
+    Router::new()
+        .route("/admin/seed", post(controller::generate_seed_data))
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(seed_service)
+}
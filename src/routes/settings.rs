@@ -0,0 +1,21 @@
+use crate::auth::middleware::auth_middleware;
+use crate::cache::redis::RedisCache;
+use crate::settings::{controller, service::SettingsService};
+use axum::{middleware, routing::get, Router};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up blog settings routes
+pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
+    let settings_service = Arc::new(SettingsService::new(pool, redis_cache));
+
+    Router::new()
+        .route("/settings", get(controller::get_public_settings))
+        .route(
+            "/admin/settings",
+            get(controller::get_settings)
+                .put(controller::update_settings)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(settings_service)
+}
@@ -0,0 +1,16 @@
+use crate::auth::middleware::auth_middleware;
+use crate::storage::{controller, service::StorageService};
+use axum::{middleware, routing::get, Router};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up storage use routes; quotas are enforced by the upload routes
+pub fn routes(pool: PgPool) -> Router {
+    let storage_service = Arc::new(StorageService::new(pool));
+
+    Router::new()
+        .route("/users/me/storage", get(controller::get_my_storage))
+        .route("/admin/storage", get(controller::get_storage_overview))
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(storage_service)
+}
@@ -0,0 +1,14 @@
+use crate::auth::middleware::auth_middleware;
+use crate::tag::{controller, service::TagService};
+use axum::{middleware, routing::post, Router};
+use std::sync::Arc;
+
+/// Set up tag maintenance routes
+pub fn routes(tag_service: Arc<TagService>) -> Router {
+    Router::new()
+        .route(
+            "/admin/tags/retag",
+            post(controller::retag).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(tag_service)
+}
@@ -0,0 +1,20 @@
+use crate::auth::middleware::auth_middleware;
+use crate::tenant::{controller, service::TenantService};
+use axum::{
+    middleware,
+    routing::{get, put},
+    Router,
+};
+use std::sync::Arc;
+
+/// Set up blog provisioning routes
+pub fn routes(tenant_service: Arc<TenantService>) -> Router {
+    Router::new()
+        .route(
+            "/admin/blogs",
+            get(controller::list_blogs).post(controller::create_blog),
+        )
+        .route("/admin/blogs/:blog_id", put(controller::update_blog))
+        .route_layer(middleware::from_fn(auth_middleware))
+        .with_state(tenant_service)
+}
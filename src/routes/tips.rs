@@ -0,0 +1,36 @@
+use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
+use crate::cache::redis::RedisCache;
+use crate::rate_limit::{rate_limit_clients, RateLimiter, TIP_RATE_LIMIT};
+use crate::tip::provider::tip_provider_from_env;
+use crate::tip::{controller, service::TipService};
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Set up tipping authors, rate limited per user or client address, the payment provider's
+/// webhook and authors' earnings
+pub fn routes(pool: PgPool, redis_cache: Option<RedisCache>) -> Router {
+    let tip_service = Arc::new(TipService::new(pool, tip_provider_from_env()));
+    let tip_limiter = Arc::new(RateLimiter::new(redis_cache, TIP_RATE_LIMIT.configured()));
+
+    Router::new()
+        .route(
+            "/posts/:id/tips",
+            post(controller::create_tip)
+                .route_layer(middleware::from_fn_with_state(
+                    tip_limiter,
+                    rate_limit_clients,
+                ))
+                .route_layer(middleware::from_fn(optional_auth_middleware)),
+        )
+        .route("/tips/webhook", post(controller::tip_webhook))
+        .route(
+            "/analytics/authors/me/earnings",
+            get(controller::get_earnings).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(tip_service)
+}
@@ -0,0 +1,34 @@
+use crate::auth::middleware::{auth_middleware, optional_auth_middleware};
+use crate::title_test::{controller, service::TitleTestService};
+use axum::{
+    middleware,
+    routing::{get, post, put},
+    Router,
+};
+use std::sync::Arc;
+
+/// Set up post title tests and the analytics routes that assign, count and compare titles
+pub fn routes(title_test_service: Arc<TitleTestService>) -> Router {
+    Router::new()
+        .route(
+            "/posts/:id/title-test",
+            put(controller::start_title_test)
+                .delete(controller::stop_title_test)
+                .route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .route(
+            "/analytics/title-impressions",
+            post(controller::record_title_impressions)
+                .route_layer(middleware::from_fn(optional_auth_middleware)),
+        )
+        .route(
+            "/analytics/title-clicks",
+            post(controller::record_title_click)
+                .route_layer(middleware::from_fn(optional_auth_middleware)),
+        )
+        .route(
+            "/analytics/posts/:post_id/title-test",
+            get(controller::get_title_test).route_layer(middleware::from_fn(auth_middleware)),
+        )
+        .with_state(title_test_service)
+}
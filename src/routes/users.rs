@@ -10,9 +10,9 @@ use crate::auth::middleware::auth_middleware;
 pub fn routes(pool: PgPool) -> Router {
     Router::new()
         // This route will appear in Swagger because register() has #[utoipa::path] attribute
-        .route("/api/auth/register", post(register))
+        .route("/auth/register", post(register))
         // This route will appear in Swagger because login() has #[utoipa::path] attribute
-        .route("/api/auth/login", post(login))
+        .route("/auth/login", post(login))
         .with_state(pool)
 }
 
@@ -20,9 +20,9 @@ pub fn routes(pool: PgPool) -> Router {
 pub fn protected_routes(pool: PgPool) -> Router {
     Router::new()
         // Admin routes
-        .route("/api/admin/example", post(|| async { "Admin only" }))
+        .route("/admin/example", post(|| async { "Admin only" }))
         // Author routes
-        .route("/api/author/example", post(|| async { "Author only" }))
+        .route("/author/example", post(|| async { "Author only" }))
         // Add basic auth middleware
         .layer(from_fn(auth_middleware))
         .with_state(pool)
@@ -0,0 +1,85 @@
+use axum::{
+    http::{header, HeaderValue, Request},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+
+/// Prefix for the current, versioned API surface
+pub const API_V1_PREFIX: &str = "/api/v1";
+
+/// Prefix for the original unversioned API, kept as a deprecated alias of v1
+pub const LEGACY_API_PREFIX: &str = "/api";
+
+/// HTTP-date after which the unversioned `/api` paths may be removed
+pub const LEGACY_API_SUNSET: &str = "Thu, 01 Jul 2027 00:00:00 GMT";
+
+/// Mount the API routes under `/api/v1` and keep the unversioned `/api` paths
+/// working as a compatibility layer that advertises its deprecation.
+///
+/// `api` is called once per prefix so each mount gets its own router instance.
+pub fn versioned_routes<F>(api: F) -> Router
+where
+    F: Fn() -> Router,
+{
+    let v1 = Router::new().nest(API_V1_PREFIX, api());
+
+    // The layer is applied after nesting so it sees the full request path
+    let legacy = Router::new()
+        .nest(LEGACY_API_PREFIX, api())
+        .layer(middleware::from_fn(legacy_deprecation_headers));
+
+    v1.merge(legacy)
+}
+
+/// Map an unversioned `/api/...` path to its `/api/v1/...` successor
+pub fn successor_path(path: &str) -> String {
+    match path.strip_prefix(LEGACY_API_PREFIX) {
+        Some(rest) if !rest.starts_with("/v1/") && rest != "/v1" => {
+            format!("{}{}", API_V1_PREFIX, rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Middleware that flags responses from the legacy API with `Deprecation`,
+/// `Sunset` and a `Link` to the successor version (RFC 8594)
+pub async fn legacy_deprecation_headers<B>(req: Request<B>, next: Next<B>) -> Response {
+    let successor = successor_path(req.uri().path());
+
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.insert("sunset", HeaderValue::from_static(LEGACY_API_SUNSET));
+
+    if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor))
+    {
+        headers.append(header::LINK, link);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_successor_path_for_legacy_routes() {
+        assert_eq!(
+            successor_path("/api/posts/popular"),
+            "/api/v1/posts/popular"
+        );
+        assert_eq!(successor_path("/api/health"), "/api/v1/health");
+    }
+
+    #[test]
+    fn test_successor_path_leaves_versioned_routes_alone() {
+        assert_eq!(
+            successor_path("/api/v1/posts/popular"),
+            "/api/v1/posts/popular"
+        );
+        assert_eq!(successor_path("/docs"), "/docs");
+    }
+}
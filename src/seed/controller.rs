@@ -0,0 +1,75 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::jobs::controller::job_error_response;
+use crate::jobs::service::JobService;
+use crate::seed::model::{SeedRequest, SEED_JOB};
+use crate::seed::service::SeedService;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Generate seed data (admin only)
+///
+/// Queues a background job that fills the blog with users, posts, tags, comments and
+/// interactions for staging and benchmarks. Only available when `SEED_DATA_ENABLED` is set.
+/// The `SeedReport` is the result of the job at `/api/admin/jobs/{id}`.
+#[utoipa::path(
+    post,
+    path = "/api/admin/seed",
+    tag = "seed",
+    request_body = SeedRequest,
+    responses(
+        (status = 202, description = "Generation queued", body = Job),
+        (status = 400, description = "Invalid counts"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin, or seed data generation is disabled"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn generate_seed_data(
+    Extension(user): Extension<AuthUser>,
+    Extension(jobs): Extension<Arc<JobService>>,
+    State(seed_service): State<Option<Arc<SeedService>>>,
+    Json(request): Json<SeedRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Admin access required" })),
+        )
+            .into_response();
+    }
+
+    if seed_service.is_none() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Seed data generation is disabled" })),
+        )
+            .into_response();
+    }
+
+    let plan = match request.plan() {
+        Ok(plan) => plan,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    };
+
+    let payload = serde_json::to_value(plan).unwrap_or_default();
+    match jobs.enqueue(SEED_JOB, payload, Some(user.user_id)).await {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(e) => job_error_response(e),
+    }
+}
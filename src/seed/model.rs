@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Kind of the background job that generates seed data
+pub const SEED_JOB: &str = "seed_data";
+
+// Upper bounds of one run, so a stray zero cannot fill the database
+const MAX_USERS: u32 = 100_000;
+const MAX_POSTS: u32 = 200_000;
+const MAX_TAGS: u32 = 1_000;
+const MAX_COMMENTS: u32 = 1_000_000;
+const MAX_INTERACTIONS: u32 = 5_000_000;
+const MAX_DAYS: u32 = 3_650;
+
+/// How much data to generate; every count has a default suited to a small staging blog
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct SeedRequest {
+    #[schema(example = 100)]
+    pub users: Option<u32>,
+    #[schema(example = 500)]
+    pub posts: Option<u32>,
+    #[schema(example = 30)]
+    pub tags: Option<u32>,
+    #[schema(example = 2000)]
+    pub comments: Option<u32>,
+    /// Views, likes and bookmarks
+    #[schema(example = 20000)]
+    pub interactions: Option<u32>,
+    /// Posts are spread over this many days before now, more of them recent
+    #[schema(example = 365)]
+    pub days: Option<u32>,
+    /// Seed of the random generator, to generate the same data again; random when absent
+    pub seed: Option<u64>,
+}
+
+/// A validated seed request with every count filled in, the payload of a seed job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeedPlan {
+    pub users: u32,
+    pub posts: u32,
+    pub tags: u32,
+    pub comments: u32,
+    pub interactions: u32,
+    pub days: u32,
+    pub seed: u64,
+}
+
+/// Errors raised while validating a seed request
+#[derive(Debug, thiserror::Error)]
+pub enum SeedError {
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+}
+
+impl SeedRequest {
+    /// Fill in the defaults and check the counts are within bounds and consistent
+    pub fn plan(&self) -> Result<SeedPlan, SeedError> {
+        let plan = SeedPlan {
+            users: self.users.unwrap_or(100),
+            posts: self.posts.unwrap_or(500),
+            tags: self.tags.unwrap_or(30),
+            comments: self.comments.unwrap_or(2000),
+            interactions: self.interactions.unwrap_or(20_000),
+            days: self.days.unwrap_or(365),
+            seed: self.seed.unwrap_or_else(rand::random),
+        };
+
+        for (name, count, max) in [
+            ("users", plan.users, MAX_USERS),
+            ("posts", plan.posts, MAX_POSTS),
+            ("tags", plan.tags, MAX_TAGS),
+            ("comments", plan.comments, MAX_COMMENTS),
+            ("interactions", plan.interactions, MAX_INTERACTIONS),
+            ("days", plan.days, MAX_DAYS),
+        ] {
+            if count > max {
+                return Err(SeedError::InvalidInput(format!(
+                    "At most {} {} can be generated at once",
+                    max, name
+                )));
+            }
+        }
+        if plan.days == 0 {
+            return Err(SeedError::InvalidInput(
+                "Posts must be spread over at least one day".to_string(),
+            ));
+        }
+        if plan.users == 0 && plan.posts + plan.comments + plan.interactions > 0 {
+            return Err(SeedError::InvalidInput(
+                "Posts, comments and interactions need users".to_string(),
+            ));
+        }
+        if plan.posts == 0 && plan.comments + plan.interactions > 0 {
+            return Err(SeedError::InvalidInput(
+                "Comments and interactions need posts".to_string(),
+            ));
+        }
+
+        Ok(plan)
+    }
+}
+
+/// Rows generated by a seed run, stored as the result of its job
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct SeedReport {
+    /// Marks the usernames, emails and slugs of this run, e.g. `seed_3fa2c1_17`
+    #[schema(example = "3fa2c1")]
+    pub run: String,
+    pub seed: u64,
+    pub users: u64,
+    pub posts: u64,
+    pub tags: u64,
+    pub comments: u64,
+    pub interactions: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_defaults() {
+        let plan = SeedRequest {
+            seed: Some(7),
+            ..Default::default()
+        }
+        .plan()
+        .unwrap();
+
+        assert_eq!(plan.users, 100);
+        assert_eq!(plan.posts, 500);
+        assert_eq!(plan.seed, 7);
+    }
+
+    #[test]
+    fn test_plan_rejects_inconsistent_counts() {
+        let request = |users, posts, comments| SeedRequest {
+            users: Some(users),
+            posts: Some(posts),
+            comments: Some(comments),
+            interactions: Some(0),
+            ..Default::default()
+        };
+
+        assert!(request(0, 10, 0).plan().is_err());
+        assert!(request(10, 0, 5).plan().is_err());
+        assert!(request(MAX_USERS + 1, 10, 0).plan().is_err());
+        assert!(request(10, 0, 0).plan().is_ok());
+    }
+}
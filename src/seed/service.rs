@@ -0,0 +1,567 @@
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::JobHandler;
+use crate::post::service::render_markdown;
+use crate::seed::model::{SeedPlan, SeedReport, SEED_JOB};
+use crate::tag::service::resolve_tags;
+use crate::tenant::middleware::current_blog_id;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2,
+};
+use chrono::{DateTime, Duration, Utc};
+use futures::future::BoxFuture;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::Value;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// Rows inserted per statement
+const BATCH_SIZE: usize = 1000;
+// Skews of the power laws rows are picked by; the higher, the more rows go to the first few.
+// A few prolific authors write most posts and a few hits get most comments and views.
+const AUTHOR_SKEW: f64 = 2.0;
+const POST_SKEW: f64 = 3.0;
+const TAG_SKEW: f64 = 2.0;
+// Share of posts left as drafts, and of comments that reply to another comment
+const DRAFT_SHARE: f64 = 0.05;
+const REPLY_SHARE: f64 = 0.3;
+// Share of interactions that are views and likes; the rest are bookmarks
+const VIEW_SHARE: f64 = 0.85;
+const LIKE_SHARE: f64 = 0.12;
+
+const TOPICS: [&str; 20] = [
+    "rust",
+    "databases",
+    "web",
+    "devops",
+    "security",
+    "design",
+    "testing",
+    "performance",
+    "career",
+    "open-source",
+    "frontend",
+    "backend",
+    "cloud",
+    "machine-learning",
+    "mobile",
+    "networking",
+    "linux",
+    "productivity",
+    "tutorial",
+    "news",
+];
+
+const WORDS: [&str; 48] = [
+    "the", "a", "system", "query", "cache", "server", "request", "latency", "data", "index",
+    "deploy", "build", "test", "release", "user", "feature", "design", "change", "review",
+    "service", "error", "stream", "model", "schema", "table", "network", "thread", "memory",
+    "fast", "simple", "reliable", "quietly", "often", "never", "always", "makes", "breaks",
+    "scales", "reads", "writes", "improves", "with", "without", "for", "into", "under", "over",
+    "again",
+];
+
+// A post as inserted, for picking the posts comments and interactions go to
+struct SeedPost {
+    id: i64,
+    created_at: DateTime<Utc>,
+}
+
+/// Generates realistic-looking users, posts, tags, comments and interactions for staging
+/// environments and benchmarks.
+///
+/// Only set up when `SEED_DATA_ENABLED` is `true`, and runs as a background job queued by
+/// admins. Rows of a run are marked by a random run id in usernames, emails and slugs, so
+/// runs never collide. Seeded users can log in with `SEED_USER_PASSWORD` when it is set.
+pub struct SeedService {
+    pool: PgPool,
+}
+
+impl SeedService {
+    pub fn from_env(pool: PgPool) -> Option<Arc<Self>> {
+        let enabled = std::env::var("SEED_DATA_ENABLED")
+            .map(|value| value == "true")
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+
+        warn!("Seed data generation is enabled, do not enable it in production");
+        Some(Arc::new(Self { pool }))
+    }
+
+    /// Generate the data of a plan into the current blog
+    pub async fn run(&self, plan: &SeedPlan) -> Result<SeedReport, JobError> {
+        let mut rng = StdRng::seed_from_u64(plan.seed);
+        // Not drawn from the seeded generator, so running a seed again does not collide
+        let run = format!("{:06x}", rand::random::<u32>() & 0xff_ffff);
+        let now = Utc::now();
+
+        let users = self.insert_users(&run, plan, now).await?;
+        let tags = self.upsert_tags(plan.tags).await?;
+        let posts = self
+            .insert_posts(&mut rng, &run, plan, &users, &tags, now)
+            .await?;
+
+        let (mut comments, mut interactions) = (0, 0);
+        if !posts.is_empty() {
+            comments = self
+                .insert_comments(&mut rng, plan.comments, &users, &posts, now)
+                .await?;
+            interactions = self
+                .insert_interactions(&mut rng, plan.interactions, &users, &posts, now)
+                .await?;
+        }
+
+        let report = SeedReport {
+            run,
+            seed: plan.seed,
+            users: users.len() as u64,
+            posts: plan.posts as u64,
+            tags: tags.len() as u64,
+            comments,
+            interactions,
+        };
+        info!("Generated seed data: {:?}", report);
+        Ok(report)
+    }
+
+    async fn insert_users(
+        &self,
+        run: &str,
+        plan: &SeedPlan,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Uuid>, JobError> {
+        let password_hash = seed_password_hash()?;
+        // Everyone joined before the oldest post
+        let joined_at = now - Duration::days(plan.days.into());
+        let count = plan.users as usize;
+        let mut users = Vec::with_capacity(count);
+
+        for start in (0..count).step_by(BATCH_SIZE) {
+            let end = (start + BATCH_SIZE).min(count);
+            let ids: Vec<Uuid> = (start..end).map(|_| Uuid::new_v4()).collect();
+            let usernames: Vec<String> = (start..end)
+                .map(|i| format!("seed_{}_{}", run, i + 1))
+                .collect();
+            let emails: Vec<String> = usernames
+                .iter()
+                .map(|username| format!("{}@example.com", username))
+                .collect();
+
+            let query = sqlx::query(
+                r#"
+                INSERT INTO global.users (id, username, email, password_hash, created_at, updated_at)
+                SELECT id, username, email, $4, $5, $5
+                FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS u(id, username, email)
+                "#,
+            )
+            .bind(&ids)
+            .bind(&usernames)
+            .bind(&emails)
+            .bind(&password_hash)
+            .bind(joined_at)
+            .execute(&self.pool);
+            timed("seed.users", query).await?;
+
+            users.extend(ids);
+        }
+
+        Ok(users)
+    }
+
+    // Tags are shared between blogs and runs, so existing ones are reused
+    async fn upsert_tags(&self, count: u32) -> Result<Vec<i64>, JobError> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let names: Vec<String> = (0..count as usize).map(tag_name).collect();
+        let mut conn = self.pool.acquire().await?;
+        Ok(timed("seed.tags", resolve_tags(&mut conn, &names)).await?)
+    }
+
+    // Returns the published posts
+    async fn insert_posts(
+        &self,
+        rng: &mut StdRng,
+        run: &str,
+        plan: &SeedPlan,
+        users: &[Uuid],
+        tags: &[i64],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<SeedPost>, JobError> {
+        let count = plan.posts as usize;
+        let mut published = Vec::with_capacity(count);
+
+        for start in (0..count).step_by(BATCH_SIZE) {
+            let end = (start + BATCH_SIZE).min(count);
+            let (mut titles, mut slugs, mut contents, mut contents_html) =
+                (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+            let (mut authors, mut drafts, mut created_at) = (Vec::new(), Vec::new(), Vec::new());
+
+            for i in start..end {
+                let content = post_content(rng);
+                titles.push(title(rng));
+                slugs.push(format!("seed-{}-{}", run, i + 1));
+                contents_html.push(render_markdown(&content));
+                contents.push(content);
+                authors.push(users[skewed_index(rng, users.len(), AUTHOR_SKEW)]);
+                drafts.push(rng.random_bool(DRAFT_SHARE));
+                created_at.push(now - recent_age(rng, plan.days));
+            }
+
+            let query = sqlx::query(
+                r#"
+                INSERT INTO global.posts (
+                    blog_id, title, slug, content, content_html, user_id, is_draft,
+                    created_at, updated_at
+                )
+                SELECT $8, title, slug, content, content_html, user_id, is_draft,
+                    created_at, created_at
+                FROM UNNEST(
+                    $1::text[], $2::text[], $3::text[], $4::text[], $5::uuid[], $6::bool[],
+                    $7::timestamptz[]
+                ) AS p(title, slug, content, content_html, user_id, is_draft, created_at)
+                RETURNING id, created_at, is_draft
+                "#,
+            )
+            .bind(&titles)
+            .bind(&slugs)
+            .bind(&contents)
+            .bind(&contents_html)
+            .bind(&authors)
+            .bind(&drafts)
+            .bind(&created_at)
+            .bind(current_blog_id())
+            .fetch_all(&self.pool);
+            let rows = timed("seed.posts", query).await?;
+
+            let (mut tagged_posts, mut post_tags) = (Vec::new(), Vec::new());
+            for row in &rows {
+                let id: i64 = row.get("id");
+                if !tags.is_empty() {
+                    for _ in 0..rng.random_range(1..=3) {
+                        tagged_posts.push(id);
+                        post_tags.push(tags[skewed_index(rng, tags.len(), TAG_SKEW)]);
+                    }
+                }
+                if !row.get::<bool, _>("is_draft") {
+                    published.push(SeedPost {
+                        id,
+                        created_at: row.get("created_at"),
+                    });
+                }
+            }
+
+            if !post_tags.is_empty() {
+                let query = sqlx::query(
+                    r#"
+                    INSERT INTO global.post_tags (post_id, tag_id)
+                    SELECT * FROM UNNEST($1::bigint[], $2::bigint[])
+                    ON CONFLICT DO NOTHING
+                    "#,
+                )
+                .bind(&tagged_posts)
+                .bind(&post_tags)
+                .execute(&self.pool);
+                timed("seed.post_tags", query).await?;
+            }
+        }
+
+        Ok(published)
+    }
+
+    async fn insert_comments(
+        &self,
+        rng: &mut StdRng,
+        count: u32,
+        users: &[Uuid],
+        posts: &[SeedPost],
+        now: DateTime<Utc>,
+    ) -> Result<u64, JobError> {
+        let count = count as usize;
+        let post_index: HashMap<i64, usize> = posts
+            .iter()
+            .enumerate()
+            .map(|(index, post)| (post.id, index))
+            .collect();
+        // Top-level comments of each post inserted so far, which later comments can reply to
+        let mut threads: Vec<Vec<i64>> = vec![Vec::new(); posts.len()];
+
+        for start in (0..count).step_by(BATCH_SIZE) {
+            let end = (start + BATCH_SIZE).min(count);
+            let (mut post_ids, mut authors, mut parents) = (Vec::new(), Vec::new(), Vec::new());
+            let (mut contents, mut contents_html, mut created_at) =
+                (Vec::new(), Vec::new(), Vec::new());
+
+            for _ in start..end {
+                let index = skewed_index(rng, posts.len(), POST_SKEW);
+                let post = &posts[index];
+                let thread = &threads[index];
+                let parent = if !thread.is_empty() && rng.random_bool(REPLY_SHARE) {
+                    Some(thread[rng.random_range(0..thread.len())])
+                } else {
+                    None
+                };
+                let content = sentences(rng, 1, 3);
+
+                post_ids.push(post.id);
+                authors.push(users[rng.random_range(0..users.len())]);
+                parents.push(parent);
+                contents_html.push(render_markdown(&content));
+                contents.push(content);
+                created_at.push(between(rng, post.created_at, now));
+            }
+
+            let query = sqlx::query(
+                r#"
+                INSERT INTO global.comments (
+                    post_id, blog_id, user_id, parent_comment_id, content, content_html,
+                    nesting_level, created_at, updated_at
+                )
+                SELECT post_id, $7, user_id, parent_id, content, content_html,
+                    CASE WHEN parent_id IS NULL THEN 0 ELSE 1 END, created_at, created_at
+                FROM UNNEST(
+                    $1::bigint[], $2::uuid[], $3::bigint[], $4::text[], $5::text[],
+                    $6::timestamptz[]
+                ) AS c(post_id, user_id, parent_id, content, content_html, created_at)
+                RETURNING id, post_id, parent_comment_id
+                "#,
+            )
+            .bind(&post_ids)
+            .bind(&authors)
+            .bind(&parents)
+            .bind(&contents)
+            .bind(&contents_html)
+            .bind(&created_at)
+            .bind(current_blog_id())
+            .fetch_all(&self.pool);
+            let rows = timed("seed.comments", query).await?;
+
+            for row in &rows {
+                if row.get::<Option<i64>, _>("parent_comment_id").is_none() {
+                    if let Some(index) = post_index.get(&row.get::<i64, _>("post_id")) {
+                        threads[*index].push(row.get("id"));
+                    }
+                }
+            }
+        }
+
+        Ok(count as u64)
+    }
+
+    // Also sets the view and like counters of the posts to match their interactions
+    async fn insert_interactions(
+        &self,
+        rng: &mut StdRng,
+        count: u32,
+        users: &[Uuid],
+        posts: &[SeedPost],
+        now: DateTime<Utc>,
+    ) -> Result<u64, JobError> {
+        let count = count as usize;
+
+        for start in (0..count).step_by(BATCH_SIZE) {
+            let end = (start + BATCH_SIZE).min(count);
+            let (mut user_ids, mut kinds, mut post_ids, mut created_at) =
+                (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+
+            for _ in start..end {
+                let post = &posts[skewed_index(rng, posts.len(), POST_SKEW)];
+                let roll = rng.random::<f64>();
+                let kind = if roll < VIEW_SHARE {
+                    "view"
+                } else if roll < VIEW_SHARE + LIKE_SHARE {
+                    "like"
+                } else {
+                    "bookmark"
+                };
+
+                user_ids.push(users[rng.random_range(0..users.len())]);
+                kinds.push(kind);
+                post_ids.push(post.id);
+                created_at.push(between(rng, post.created_at, now));
+            }
+
+            let query = sqlx::query(
+                r#"
+                INSERT INTO global.user_interactions (user_id, interaction_type, post_id, created_at)
+                SELECT * FROM UNNEST($1::uuid[], $2::text[], $3::bigint[], $4::timestamptz[])
+                "#,
+            )
+            .bind(&user_ids)
+            .bind(&kinds)
+            .bind(&post_ids)
+            .bind(&created_at)
+            .execute(&self.pool);
+            timed("seed.interactions", query).await?;
+        }
+
+        let post_ids: Vec<i64> = posts.iter().map(|post| post.id).collect();
+        // A user likes a post once, however often they were picked to like it
+        let query = sqlx::query(
+            r#"
+            INSERT INTO global.post_likes (post_id, user_id, created_at)
+            SELECT post_id, user_id, MIN(created_at)
+            FROM global.user_interactions
+            WHERE post_id = ANY($1) AND interaction_type = 'like'
+            GROUP BY post_id, user_id
+            ON CONFLICT (post_id, user_id) DO NOTHING
+            "#,
+        )
+        .bind(&post_ids)
+        .execute(&self.pool);
+        timed("seed.likes", query).await?;
+
+        let query = sqlx::query(
+            r#"
+            UPDATE global.posts p
+            SET views = s.views, likes = s.likes
+            FROM (
+                SELECT
+                    post_id,
+                    COUNT(*) FILTER (WHERE interaction_type = 'view')::INTEGER AS views,
+                    (
+                        SELECT COUNT(*)::INTEGER FROM global.post_likes l
+                        WHERE l.post_id = i.post_id AND l.counted
+                    ) AS likes
+                FROM global.user_interactions i
+                WHERE post_id = ANY($1)
+                GROUP BY post_id
+            ) s
+            WHERE p.id = s.post_id
+            "#,
+        )
+        .bind(&post_ids)
+        .execute(&self.pool);
+        timed("seed.post_counters", query).await?;
+
+        Ok(count as u64)
+    }
+
+    async fn run_job(&self, job: &Job) -> Result<Value, JobError> {
+        let plan: SeedPlan = serde_json::from_value(job.payload.clone())
+            .map_err(|e| JobError::Failed(format!("Invalid seed plan: {}", e)))?;
+        let report = self.run(&plan).await?;
+        serde_json::to_value(report).map_err(|e| JobError::InternalError(e.to_string()))
+    }
+}
+
+impl JobHandler for SeedService {
+    fn kind(&self) -> &'static str {
+        SEED_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
+
+// Seeded users log in with SEED_USER_PASSWORD; without it they cannot log in at all
+fn seed_password_hash() -> Result<String, JobError> {
+    match std::env::var("SEED_USER_PASSWORD") {
+        Ok(password) if !password.is_empty() => Argon2::default()
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
+            .map(|hash| hash.to_string())
+            .map_err(|e| JobError::InternalError(format!("Password hashing failed: {}", e))),
+        _ => Ok("!".to_string()),
+    }
+}
+
+fn tag_name(index: usize) -> String {
+    match TOPICS.get(index) {
+        Some(topic) => topic.to_string(),
+        None => format!("topic-{}", index + 1),
+    }
+}
+
+// Index into `len` items following a power law: with a skew above 1 the first items are
+// picked far more often than the last
+fn skewed_index(rng: &mut impl Rng, len: usize, skew: f64) -> usize {
+    let index = (rng.random::<f64>().powf(skew) * len as f64) as usize;
+    index.min(len - 1)
+}
+
+// Age of a post up to `days` old, with more recent posts than old ones
+fn recent_age(rng: &mut impl Rng, days: u32) -> Duration {
+    let seconds = rng.random::<f64>().powi(2) * f64::from(days) * 86_400.0;
+    Duration::seconds(seconds as i64)
+}
+
+fn between(rng: &mut impl Rng, start: DateTime<Utc>, end: DateTime<Utc>) -> DateTime<Utc> {
+    let span = (end - start).num_seconds().max(0);
+    start + Duration::seconds(rng.random_range(0..=span))
+}
+
+fn sentences(rng: &mut impl Rng, min: usize, max: usize) -> String {
+    (0..rng.random_range(min..=max))
+        .map(|_| {
+            let words: Vec<&str> = (0..rng.random_range(6..=16))
+                .map(|_| WORDS[rng.random_range(0..WORDS.len())])
+                .collect();
+            let sentence = words.join(" ");
+            let mut chars = sentence.chars();
+            match chars.next() {
+                Some(first) => format!("{}{}.", first.to_uppercase(), chars.as_str()),
+                None => sentence,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn title(rng: &mut impl Rng) -> String {
+    let title = sentences(rng, 1, 1);
+    let words: Vec<&str> = title.trim_end_matches('.').split(' ').take(8).collect();
+    words.join(" ")
+}
+
+// Markdown of a few paragraphs, sometimes under a heading
+fn post_content(rng: &mut impl Rng) -> String {
+    (0..rng.random_range(2..=8))
+        .map(|_| {
+            let paragraph = sentences(rng, 2, 6);
+            if rng.random_bool(0.2) {
+                format!("## {}\n\n{}", title(rng), paragraph)
+            } else {
+                paragraph
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skewed_index_favours_first_items() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut counts = [0; 10];
+        for _ in 0..10_000 {
+            counts[skewed_index(&mut rng, 10, POST_SKEW)] += 1;
+        }
+
+        assert!(counts[0] > counts[9] * 5);
+        assert!(counts.iter().all(|count| *count > 0));
+    }
+
+    #[test]
+    fn test_generated_data_is_reproducible() {
+        let generate = || {
+            let mut rng = StdRng::seed_from_u64(42);
+            (post_content(&mut rng), recent_age(&mut rng, 30))
+        };
+
+        let (content, age) = generate();
+        assert_eq!((content.clone(), age), generate());
+        assert!(age <= Duration::days(30));
+        assert!(!content.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::settings::model::{SettingsError, UpdateSettingsRequest};
+use crate::settings::service::SettingsService;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+
+fn settings_error_response(e: SettingsError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Settings error: {:?}", e);
+    let status = match e {
+        SettingsError::UnknownSetting(_) | SettingsError::InvalidSetting(_) => {
+            StatusCode::BAD_REQUEST
+        }
+        SettingsError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can manage settings" })),
+    )
+        .into_response()
+}
+
+/// Get the public settings of the blog
+///
+/// Returns the settings front-ends need to render the site, such as its title, theme colors,
+/// social links and feature flags.
+#[utoipa::path(
+    get,
+    path = "/api/settings",
+    tag = "settings",
+    responses(
+        (status = 200, description = "Settings retrieved successfully", body = SettingsResponse),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_public_settings(State(service): State<Arc<SettingsService>>) -> Response {
+    match service.get_settings().await {
+        Ok(settings) => (StatusCode::OK, Json(settings.public_subset())).into_response(),
+        Err(e) => settings_error_response(e),
+    }
+}
+
+/// Get all settings of the blog, including private ones (admin only)
+#[utoipa::path(
+    get,
+    path = "/api/admin/settings",
+    tag = "settings",
+    responses(
+        (status = 200, description = "Settings retrieved successfully", body = SettingsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_settings(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<SettingsService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.get_settings().await {
+        Ok(settings) => (StatusCode::OK, Json(settings)).into_response(),
+        Err(e) => settings_error_response(e),
+    }
+}
+
+/// Change settings of the blog (admin only)
+///
+/// Settings not included are left unchanged and a null value unsets a setting. Returns all
+/// settings after the change.
+#[utoipa::path(
+    put,
+    path = "/api/admin/settings",
+    tag = "settings",
+    request_body = UpdateSettingsRequest,
+    responses(
+        (status = 200, description = "Settings updated", body = SettingsResponse),
+        (status = 400, description = "Unknown setting or invalid value"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_settings(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<SettingsService>>,
+    Json(request): Json<UpdateSettingsRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.update_settings(request).await {
+        Ok(settings) => {
+            info!("Admin {} updated blog settings", user.user_id);
+            (StatusCode::OK, Json(settings)).into_response()
+        }
+        Err(e) => settings_error_response(e),
+    }
+}
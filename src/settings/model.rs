@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use utoipa::ToSchema;
+
+/// Longest value accepted for text settings
+pub const MAX_TEXT_SETTING_LEN: usize = 1000;
+
+/// Type of a setting's value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingKind {
+    /// Free text up to `MAX_TEXT_SETTING_LEN` characters
+    Text,
+    /// An absolute http(s) URL
+    Url,
+    /// A `#rrggbb` color
+    Color,
+    /// A feature flag
+    Bool,
+    /// One of a fixed set of strings
+    Choice(&'static [&'static str]),
+}
+
+/// A setting the front-ends understand
+#[derive(Debug, Clone, Copy)]
+pub struct SettingDefinition {
+    pub key: &'static str,
+    pub kind: SettingKind,
+    /// Whether the setting is returned to anonymous readers by `GET /api/settings`
+    pub public: bool,
+}
+
+const fn setting(key: &'static str, kind: SettingKind, public: bool) -> SettingDefinition {
+    SettingDefinition { key, kind, public }
+}
+
+/// Every setting a blog can store. Keys outside this list are rejected.
+pub const SETTING_DEFINITIONS: &[SettingDefinition] = &[
+    setting("site_title", SettingKind::Text, true),
+    setting("site_description", SettingKind::Text, true),
+    setting("theme.primary_color", SettingKind::Color, true),
+    setting("theme.accent_color", SettingKind::Color, true),
+    setting("theme.logo_url", SettingKind::Url, true),
+    setting("social.twitter", SettingKind::Url, true),
+    setting("social.github", SettingKind::Url, true),
+    setting("social.mastodon", SettingKind::Url, true),
+    setting("social.linkedin", SettingKind::Url, true),
+    setting("features.newsletter", SettingKind::Bool, true),
+    setting("features.dark_mode", SettingKind::Bool, true),
+    setting("features.comments", SettingKind::Bool, true),
+    // Who may sign up through the blog, see `crate::invite`
+    setting(
+        "registration.mode",
+        SettingKind::Choice(&["open", "invite-only", "closed"]),
+        true,
+    ),
+    setting("contact_email", SettingKind::Text, false),
+    setting("analytics.tracking_id", SettingKind::Text, false),
+];
+
+/// Look up the definition of a setting key
+pub fn setting_definition(key: &str) -> Option<&'static SettingDefinition> {
+    SETTING_DEFINITIONS.iter().find(|def| def.key == key)
+}
+
+/// Check that `value` has the type the setting requires
+pub fn validate_setting(def: &SettingDefinition, value: &Value) -> Result<(), SettingsError> {
+    let invalid = |expected: &str| {
+        Err(SettingsError::InvalidSetting(format!(
+            "{} must be {}",
+            def.key, expected
+        )))
+    };
+
+    match (def.kind, value) {
+        (SettingKind::Bool, Value::Bool(_)) => Ok(()),
+        (SettingKind::Bool, _) => invalid("a boolean"),
+        (SettingKind::Text, Value::String(text)) => {
+            if text.chars().count() > MAX_TEXT_SETTING_LEN {
+                invalid(&format!("at most {} characters", MAX_TEXT_SETTING_LEN))
+            } else {
+                Ok(())
+            }
+        }
+        (SettingKind::Url, Value::String(url))
+            if (url.starts_with("https://") || url.starts_with("http://"))
+                && !url.contains(char::is_whitespace)
+                && url.len() <= MAX_TEXT_SETTING_LEN =>
+        {
+            Ok(())
+        }
+        (SettingKind::Color, Value::String(color))
+            if color.len() == 7
+                && color.starts_with('#')
+                && color[1..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            Ok(())
+        }
+        (SettingKind::Choice(choices), Value::String(choice))
+            if choices.contains(&choice.as_str()) =>
+        {
+            Ok(())
+        }
+        (SettingKind::Text, _) => invalid("a string"),
+        (SettingKind::Url, _) => invalid("an http(s) URL"),
+        (SettingKind::Color, _) => invalid("a #rrggbb color"),
+        (SettingKind::Choice(choices), _) => invalid(&format!("one of {}", choices.join(", "))),
+    }
+}
+
+/// Settings of a blog, keyed by setting key. Unset settings are omitted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SettingsResponse {
+    #[schema(value_type = Object, example = json!({"site_title": "My Blog", "features.newsletter": true}))]
+    pub settings: BTreeMap<String, Value>,
+}
+
+impl SettingsResponse {
+    /// Only the settings anonymous readers may see
+    pub fn public_subset(&self) -> SettingsResponse {
+        let settings = self
+            .settings
+            .iter()
+            .filter(|(key, _)| setting_definition(key).is_some_and(|def| def.public))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        SettingsResponse { settings }
+    }
+}
+
+/// Request to change settings. Settings not mentioned are kept; a null value unsets one.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateSettingsRequest {
+    #[schema(value_type = Object, example = json!({"site_title": "My Blog", "social.mastodon": null}))]
+    pub settings: HashMap<String, Value>,
+}
+
+/// Possible settings errors
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Unknown setting: {0}")]
+    UnknownSetting(String),
+
+    #[error("Invalid setting: {0}")]
+    InvalidSetting(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_setting_checks_kind() {
+        let color = setting_definition("theme.primary_color").unwrap();
+        assert!(validate_setting(color, &json!("#1a2B3c")).is_ok());
+        assert!(validate_setting(color, &json!("red")).is_err());
+
+        let url = setting_definition("social.github").unwrap();
+        assert!(validate_setting(url, &json!("https://github.com/example")).is_ok());
+        assert!(validate_setting(url, &json!("javascript:alert(1)")).is_err());
+
+        let flag = setting_definition("features.newsletter").unwrap();
+        assert!(validate_setting(flag, &json!(true)).is_ok());
+        assert!(validate_setting(flag, &json!("true")).is_err());
+
+        let mode = setting_definition("registration.mode").unwrap();
+        assert!(validate_setting(mode, &json!("invite-only")).is_ok());
+        assert!(validate_setting(mode, &json!("invite_only")).is_err());
+    }
+
+    #[test]
+    fn test_public_subset_hides_private_settings() {
+        let mut settings = BTreeMap::new();
+        settings.insert("site_title".to_string(), json!("My Blog"));
+        settings.insert("contact_email".to_string(), json!("owner@example.com"));
+
+        let public = SettingsResponse { settings }.public_subset();
+        assert!(public.settings.contains_key("site_title"));
+        assert!(!public.settings.contains_key("contact_email"));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::cache::redis::RedisCache;
+use crate::settings::model::{
+    setting_definition, validate_setting, SettingsError, SettingsResponse, UpdateSettingsRequest,
+};
+use crate::tenant::middleware::{blog_key, current_blog_id};
+use redis::AsyncCommands;
+use serde_json::Value;
+use sqlx::{PgPool, Row};
+use tracing::{error, info};
+
+const SETTINGS_CACHE_KEY: &str = "settings";
+const SETTINGS_CACHE_TTL: u64 = 3600; // 1 hour
+
+#[derive(Debug, Clone)]
+pub struct SettingsService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+}
+
+impl SettingsService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self { pool, redis_cache }
+    }
+
+    /// Get all settings of the current blog.
+    ///
+    /// Every page load of a front-end asks for these, so they are served from cache when
+    /// possible and a cache outage only costs a fresh query.
+    pub async fn get_settings(&self) -> Result<SettingsResponse, SettingsError> {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Ok(Some(cached_data)) = conn
+                    .get::<_, Option<String>>(blog_key(SETTINGS_CACHE_KEY))
+                    .await
+                {
+                    match serde_json::from_str::<SettingsResponse>(&cached_data) {
+                        Ok(settings) => return Ok(settings),
+                        Err(e) => error!("Failed to deserialize cached settings: {}", e),
+                    }
+                }
+            }
+        }
+
+        let rows = sqlx::query("SELECT key, value FROM global.blog_settings WHERE blog_id = $1")
+            .bind(current_blog_id())
+            .fetch_all(&self.pool)
+            .await?;
+
+        let settings = SettingsResponse {
+            settings: rows
+                .into_iter()
+                .map(|row| (row.get::<String, _>("key"), row.get::<Value, _>("value")))
+                .collect(),
+        };
+
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                let json_data = serde_json::to_string(&settings).unwrap_or_default();
+                if let Err(e) = conn
+                    .set_ex::<_, _, ()>(blog_key(SETTINGS_CACHE_KEY), json_data, SETTINGS_CACHE_TTL)
+                    .await
+                {
+                    error!("Failed to cache settings: {}", e);
+                }
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Change settings of the current blog. All changes are validated before any is applied.
+    pub async fn update_settings(
+        &self,
+        request: UpdateSettingsRequest,
+    ) -> Result<SettingsResponse, SettingsError> {
+        for (key, value) in &request.settings {
+            let def = setting_definition(key)
+                .ok_or_else(|| SettingsError::UnknownSetting(key.clone()))?;
+            if !value.is_null() {
+                validate_setting(def, value)?;
+            }
+        }
+
+        let blog_id = current_blog_id();
+        let mut tx = self.pool.begin().await?;
+        for (key, value) in &request.settings {
+            if value.is_null() {
+                sqlx::query("DELETE FROM global.blog_settings WHERE blog_id = $1 AND key = $2")
+                    .bind(blog_id)
+                    .bind(key)
+                    .execute(&mut *tx)
+                    .await?;
+            } else {
+                sqlx::query(
+                    r#"
+                    INSERT INTO global.blog_settings (blog_id, key, value, updated_at)
+                    VALUES ($1, $2, $3, NOW())
+                    ON CONFLICT (blog_id, key) DO UPDATE
+                    SET value = EXCLUDED.value, updated_at = NOW()
+                    "#,
+                )
+                .bind(blog_id)
+                .bind(key)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+
+        self.invalidate_cache().await;
+        info!(
+            "Updated {} settings of blog {}",
+            request.settings.len(),
+            blog_id
+        );
+
+        self.get_settings().await
+    }
+
+    async fn invalidate_cache(&self) {
+        if let Some(cache) = &self.redis_cache {
+            if let Ok(mut conn) = cache.connection().await {
+                if let Err(e) = conn.del::<_, ()>(blog_key(SETTINGS_CACHE_KEY)).await {
+                    error!("Failed to invalidate cached settings: {}", e);
+                }
+            }
+        }
+    }
+}
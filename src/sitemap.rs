@@ -0,0 +1,205 @@
+use crate::db::instrument::timed;
+use crate::db::router::DbRouter;
+use crate::tenant::model::{Blog, DEFAULT_BLOG_ID};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::Row;
+use std::fmt::Write;
+use tracing::error;
+
+/// Most URLs a single sitemap file may list
+const SITEMAP_MAX_URLS: i64 = 50_000;
+
+/// Front-end base URL of the default blog when `SITE_URL` is not set
+const DEFAULT_SITE_URL: &str = "http://localhost:3000";
+
+/// One `<url>` of the sitemap
+#[derive(Debug, Clone, PartialEq)]
+struct SitemapEntry {
+    loc: String,
+    lastmod: DateTime<Utc>,
+}
+
+/// Public front-end URL of a blog: its custom domain, or `SITE_URL` with the blog's path prefix
+pub fn site_base_url(blog: &Blog) -> String {
+    if let Some(domain) = &blog.domain {
+        return format!("https://{}", domain);
+    }
+
+    let site_url = std::env::var("SITE_URL").unwrap_or_else(|_| DEFAULT_SITE_URL.to_string());
+    let site_url = site_url.trim_end_matches('/');
+    if blog.id == DEFAULT_BLOG_ID {
+        site_url.to_string()
+    } else {
+        format!("{}/blogs/{}", site_url, blog.slug)
+    }
+}
+
+/// Percent-encode a tag name or username for use as one path segment
+pub fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+fn render(entries: &[SitemapEntry]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for entry in entries {
+        let _ = writeln!(
+            out,
+            "  <url><loc>{}</loc><lastmod>{}</lastmod></url>",
+            html_escape::encode_text(&entry.loc),
+            entry.lastmod.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+    }
+    out.push_str("</urlset>\n");
+    out
+}
+
+/// Collect the published posts, tag archives and author archives of a blog
+async fn sitemap_entries(db: &DbRouter, blog: &Blog) -> Result<Vec<SitemapEntry>, sqlx::Error> {
+    let base_url = site_base_url(blog);
+    let mut entries = Vec::new();
+
+    let query = sqlx::query(
+        r#"
+        SELECT slug, canonical_url, updated_at FROM global.posts
+        WHERE blog_id = $1 AND is_draft = false AND is_deleted = false
+        ORDER BY updated_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(blog.id)
+    .bind(SITEMAP_MAX_URLS)
+    .fetch_all(db.read());
+    let posts = timed("sitemap.posts", query).await?;
+    for row in posts {
+        let loc = format!("{}/posts/{}", base_url, row.get::<String, _>("slug"));
+        // Cross-posted content is indexed at its canonical URL, not here
+        let canonical_url: Option<String> = row.get("canonical_url");
+        if canonical_url.is_none_or(|canonical| canonical == loc) {
+            entries.push(SitemapEntry {
+                loc,
+                lastmod: row.get("updated_at"),
+            });
+        }
+    }
+
+    let query = sqlx::query(
+        r#"
+        SELECT t.name, MAX(p.updated_at) AS updated_at
+        FROM global.tags t
+        JOIN global.post_tags pt ON pt.tag_id = t.id
+        JOIN global.posts p ON p.id = pt.post_id
+        WHERE p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false
+        GROUP BY t.name
+        ORDER BY t.name
+        LIMIT $2
+        "#,
+    )
+    .bind(blog.id)
+    .bind(SITEMAP_MAX_URLS - entries.len() as i64)
+    .fetch_all(db.read());
+    let tags = timed("sitemap.tags", query).await?;
+    for row in tags {
+        entries.push(SitemapEntry {
+            loc: format!(
+                "{}/tags/{}",
+                base_url,
+                encode_path_segment(row.get::<&str, _>("name"))
+            ),
+            lastmod: row.get("updated_at"),
+        });
+    }
+
+    let query = sqlx::query(
+        r#"
+        SELECT u.username, MAX(p.updated_at) AS updated_at
+        FROM global.users u
+        JOIN global.posts p ON p.user_id = u.id
+        WHERE p.blog_id = $1 AND p.is_draft = false AND p.is_deleted = false
+        GROUP BY u.username
+        ORDER BY u.username
+        LIMIT $2
+        "#,
+    )
+    .bind(blog.id)
+    .bind(SITEMAP_MAX_URLS - entries.len() as i64)
+    .fetch_all(db.read());
+    let authors = timed("sitemap.authors", query).await?;
+    for row in authors {
+        entries.push(SitemapEntry {
+            loc: format!(
+                "{}/authors/{}",
+                base_url,
+                encode_path_segment(row.get::<&str, _>("username"))
+            ),
+            lastmod: row.get("updated_at"),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Serve the sitemap of the requested blog: its published posts plus the tag and author
+/// archive pages that list them
+pub async fn sitemap_handler(
+    State(db): State<DbRouter>,
+    Extension(blog): Extension<Blog>,
+) -> Response {
+    match sitemap_entries(&db, &blog).await {
+        Ok(entries) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "application/xml"),
+                (header::CACHE_CONTROL, "public, max-age=3600"),
+            ],
+            render(&entries),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to build sitemap for blog {}: {}", blog.slug, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_path_segment() {
+        assert_eq!(encode_path_segment("rust"), "rust");
+        assert_eq!(encode_path_segment("web dev"), "web%20dev");
+        assert_eq!(encode_path_segment("c++/c#"), "c%2B%2B%2Fc%23");
+    }
+
+    #[test]
+    fn test_render_escapes_locations() {
+        let entries = vec![SitemapEntry {
+            loc: "https://example.com/tags/a&b".to_string(),
+            lastmod: DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        }];
+        let xml = render(&entries);
+
+        assert!(xml.contains(
+            "<url><loc>https://example.com/tags/a&amp;b</loc><lastmod>2024-05-01T10:00:00Z</lastmod></url>"
+        ));
+    }
+}
@@ -0,0 +1,30 @@
+//! Matching of post slugs and tag names, which ignores case and diacritics so that
+//! `/posts/view/My-Post` finds `my-post` and the tag `Café` is found as `cafe`. Names are
+//! stored as written and shown that way; only lookups use the match key.
+//!
+//! The database matches on `global.match_key`, which folds with Postgres' `unaccent`; this is
+//! the same key for Rust, used where lookups do not reach the database, such as cache keys.
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Text lowercased and without diacritics, e.g. `creme brulee` for `Crème Brûlée`
+pub fn match_key(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_key() {
+        assert_eq!(match_key("My-Post"), "my-post");
+        assert_eq!(match_key("Crème Brûlée"), "creme brulee");
+        assert_eq!(match_key("ÅNGSTRÖM"), "angstrom");
+        assert_eq!(match_key("rust-2024"), "rust-2024");
+    }
+}
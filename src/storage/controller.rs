@@ -0,0 +1,121 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::pagination::{PageParams, DEFAULT_PAGE_SIZE};
+use crate::storage::model::{StorageError, StorageQuotaExceededResponse};
+use crate::storage::service::StorageService;
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+/// Response of a request refused or failed because of storage quotas, shared with the upload
+/// handlers
+pub fn storage_error_response(e: StorageError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    match e {
+        StorageError::QuotaExceeded(quota) => (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(StorageQuotaExceededResponse {
+                error: format!("Storage quota of the {} exceeded", quota.scope.as_str()),
+                code: "STORAGE_QUOTA_EXCEEDED".to_string(),
+                quota,
+            }),
+        )
+            .into_response(),
+        StorageError::ValidationError(message) => {
+            (StatusCode::BAD_REQUEST, Json(json!({ "error": message }))).into_response()
+        }
+        StorageError::DatabaseError(_) => {
+            error!("Storage error: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Get your storage use
+///
+/// Bytes of post attachments and cover images you uploaded to this blog, against your quota.
+#[utoipa::path(
+    get,
+    path = "/api/users/me/storage",
+    tag = "storage",
+    responses(
+        (status = 200, description = "Storage use retrieved", body = StorageUsage),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_my_storage(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<StorageService>>,
+) -> Response {
+    match service.user_usage(user.user_id).await {
+        Ok(usage) => (StatusCode::OK, Json(usage)).into_response(),
+        Err(e) => storage_error_response(e),
+    }
+}
+
+/// Get the blog's storage use (admin only)
+///
+/// Bytes stored by the blog against its quota, and by each user who stores anything, largest
+/// first.
+#[utoipa::path(
+    get,
+    path = "/api/admin/storage",
+    tag = "storage",
+    params(PageParams),
+    responses(
+        (status = 200, description = "Storage use retrieved", body = StorageOverview),
+        (status = 400, description = "Invalid page"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not an admin"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_storage_overview(
+    Extension(user): Extension<AuthUser>,
+    OriginalUri(uri): OriginalUri,
+    State(service): State<Arc<StorageService>>,
+    Query(params): Query<PageParams>,
+) -> Response {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Only admins can see the blog's storage use" })),
+        )
+            .into_response();
+    }
+
+    let pagination = match params.pagination(DEFAULT_PAGE_SIZE) {
+        Ok(pagination) => pagination,
+        Err(e) => return storage_error_response(StorageError::ValidationError(e.to_string())),
+    };
+
+    match service.overview(&pagination).await {
+        Ok(overview) => (
+            StatusCode::OK,
+            pagination.headers(&uri, overview.users.len()),
+            Json(overview),
+        )
+            .into_response(),
+        Err(e) => storage_error_response(e),
+    }
+}
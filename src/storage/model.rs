@@ -0,0 +1,229 @@
+use serde::Serialize;
+use std::sync::OnceLock;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const DEFAULT_USER_QUOTA_BYTES: i64 = 1024 * 1024 * 1024;
+const DEFAULT_BLOG_QUOTA_BYTES: i64 = 20 * 1024 * 1024 * 1024;
+
+/// Storage quotas, read once from the environment.
+///
+/// - `STORAGE_QUOTA_USER_BYTES`: most bytes each user may store in a blog, default 1 GB
+/// - `STORAGE_QUOTA_BLOG_BYTES`: most bytes all users of a blog may store, default 20 GB
+///
+/// Either may be `unlimited`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageConfig {
+    pub user_quota_bytes: Option<i64>,
+    pub blog_quota_bytes: Option<i64>,
+}
+
+impl StorageConfig {
+    pub fn from_env() -> Self {
+        let quota = |name: &str, default: i64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| parse_quota(&value))
+                .unwrap_or(Some(default))
+        };
+
+        Self {
+            user_quota_bytes: quota("STORAGE_QUOTA_USER_BYTES", DEFAULT_USER_QUOTA_BYTES),
+            blog_quota_bytes: quota("STORAGE_QUOTA_BLOG_BYTES", DEFAULT_BLOG_QUOTA_BYTES),
+        }
+    }
+
+    /// Shared config, read on first use
+    pub fn global() -> &'static StorageConfig {
+        static CONFIG: OnceLock<StorageConfig> = OnceLock::new();
+        CONFIG.get_or_init(StorageConfig::from_env)
+    }
+
+    /// Check that storing `bytes` more keeps the user and the blog within their quotas
+    pub fn check(
+        &self,
+        user_bytes: i64,
+        blog_bytes: i64,
+        bytes: i64,
+    ) -> Result<(), StorageQuotaExceeded> {
+        let quotas = [
+            (StorageScope::User, self.user_quota_bytes, user_bytes),
+            (StorageScope::Blog, self.blog_quota_bytes, blog_bytes),
+        ];
+        for (scope, quota, used) in quotas {
+            if let Some(quota) = quota {
+                if used + bytes > quota {
+                    return Err(StorageQuotaExceeded {
+                        scope,
+                        quota_bytes: quota,
+                        used_bytes: used,
+                        requested_bytes: bytes,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// A quota is a whole number of bytes or `unlimited`; anything else keeps the default
+fn parse_quota(value: &str) -> Option<Option<i64>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("unlimited") {
+        return Some(None);
+    }
+    value.parse().ok().filter(|bytes| *bytes >= 0).map(Some)
+}
+
+/// Whose quota an upload would exceed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageScope {
+    /// The uploader's own files in the blog
+    User,
+    /// Files of all users of the blog
+    Blog,
+}
+
+impl StorageScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageScope::User => "user",
+            StorageScope::Blog => "blog",
+        }
+    }
+}
+
+/// Storage used against one quota. Attachments and cover images count, with all the resized
+/// copies of an image.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StorageUsage {
+    #[schema(example = 52428800)]
+    pub used_bytes: i64,
+    /// Attachments and cover images stored
+    #[schema(example = 12)]
+    pub uploads: i64,
+    /// Most bytes allowed; absent when unlimited
+    #[schema(example = 1073741824)]
+    pub quota_bytes: Option<i64>,
+    /// Bytes left; absent when unlimited
+    #[schema(example = 1021313024)]
+    pub remaining_bytes: Option<i64>,
+}
+
+impl StorageUsage {
+    pub fn new(used_bytes: i64, uploads: i64, quota_bytes: Option<i64>) -> Self {
+        Self {
+            used_bytes,
+            uploads,
+            quota_bytes,
+            remaining_bytes: quota_bytes.map(|quota| (quota - used_bytes).max(0)),
+        }
+    }
+}
+
+/// Storage used by one user of the blog
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UserStorage {
+    pub user_id: Uuid,
+    #[schema(example = "ada")]
+    pub username: String,
+    #[schema(example = 52428800)]
+    pub used_bytes: i64,
+    #[schema(example = 12)]
+    pub uploads: i64,
+}
+
+/// Storage used by the blog and its users, largest users first
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StorageOverview {
+    pub blog: StorageUsage,
+    /// Quota of each user; absent when unlimited
+    #[schema(example = 1073741824)]
+    pub user_quota_bytes: Option<i64>,
+    pub users: Vec<UserStorage>,
+}
+
+/// An upload refused for lack of quota
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StorageQuotaExceeded {
+    pub scope: StorageScope,
+    #[schema(example = 1073741824)]
+    pub quota_bytes: i64,
+    #[schema(example = 1070000000)]
+    pub used_bytes: i64,
+    /// Size of the refused upload
+    #[schema(example = 5242880)]
+    pub requested_bytes: i64,
+}
+
+/// Error body when an upload would exceed a storage quota
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StorageQuotaExceededResponse {
+    #[schema(example = "Storage quota of the user exceeded")]
+    pub error: String,
+    #[schema(example = "STORAGE_QUOTA_EXCEEDED")]
+    pub code: String,
+    pub quota: StorageQuotaExceeded,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Storage quota of the {} exceeded", .0.scope.as_str())]
+    QuotaExceeded(StorageQuotaExceeded),
+
+    #[error("{0}")]
+    ValidationError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quota() {
+        assert_eq!(parse_quota("1048576"), Some(Some(1048576)));
+        assert_eq!(parse_quota(" Unlimited "), Some(None));
+        assert_eq!(parse_quota("1 GB"), None);
+        assert_eq!(parse_quota("-1"), None);
+    }
+
+    #[test]
+    fn test_check_quotas() {
+        let config = StorageConfig {
+            user_quota_bytes: Some(100),
+            blog_quota_bytes: Some(1000),
+        };
+        assert!(config.check(60, 500, 40).is_ok());
+
+        let exceeded = config.check(60, 500, 41).unwrap_err();
+        assert_eq!(exceeded.scope, StorageScope::User);
+        assert_eq!(exceeded.used_bytes, 60);
+        assert_eq!(
+            config.check(0, 990, 20).unwrap_err().scope,
+            StorageScope::Blog
+        );
+
+        let unlimited = StorageConfig {
+            user_quota_bytes: None,
+            blog_quota_bytes: None,
+        };
+        assert!(unlimited.check(i64::MAX / 2, 0, 1).is_ok());
+    }
+
+    #[test]
+    fn test_usage_remaining() {
+        assert_eq!(
+            StorageUsage::new(30, 1, Some(100)).remaining_bytes,
+            Some(70)
+        );
+        assert_eq!(
+            StorageUsage::new(130, 2, Some(100)).remaining_bytes,
+            Some(0)
+        );
+        assert_eq!(StorageUsage::new(30, 1, None).remaining_bytes, None);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::pagination::Pagination;
+use crate::storage::model::{
+    StorageConfig, StorageError, StorageOverview, StorageUsage, UserStorage,
+};
+use crate::tenant::middleware::current_blog_id;
+use sqlx::{PgConnection, PgPool, Row};
+use tracing::info;
+use uuid::Uuid;
+
+/// Count an upload of `bytes` against the user's and the current blog's quotas, in the
+/// transaction storing it, or fail when it would exceed either.
+///
+/// Uploads to the same blog wait for each other here until their transaction ends, so two of
+/// them can never both take the last of a quota.
+pub async fn charge_storage(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    bytes: i64,
+) -> Result<(), StorageError> {
+    // Does not conflict with the key share locks of rows referencing the blog, so only
+    // uploads wait
+    sqlx::query("SELECT id FROM global.blogs WHERE id = $1 FOR NO KEY UPDATE")
+        .bind(current_blog_id())
+        .execute(&mut *conn)
+        .await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT COALESCE(SUM(bytes), 0)::BIGINT AS blog_bytes,
+            COALESCE(SUM(bytes) FILTER (WHERE user_id = $2), 0)::BIGINT AS user_bytes
+        FROM global.storage_usage
+        WHERE blog_id = $1
+        "#,
+    )
+    .bind(current_blog_id())
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    if let Err(exceeded) =
+        StorageConfig::global().check(row.get("user_bytes"), row.get("blog_bytes"), bytes)
+    {
+        info!(
+            "Refused upload of {} bytes by user {}: {} storage quota of {} bytes exceeded",
+            bytes,
+            user_id,
+            exceeded.scope.as_str(),
+            exceeded.quota_bytes
+        );
+        return Err(StorageError::QuotaExceeded(exceeded));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO global.storage_usage (blog_id, user_id, bytes, uploads)
+        VALUES ($1, $2, $3, 1)
+        ON CONFLICT (blog_id, user_id) DO UPDATE
+        SET bytes = storage_usage.bytes + EXCLUDED.bytes,
+            uploads = storage_usage.uploads + 1,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(current_blog_id())
+    .bind(user_id)
+    .bind(bytes)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Give back the storage of a deleted upload to the user who uploaded it, in the transaction
+/// deleting it
+pub async fn release_storage(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    bytes: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE global.storage_usage
+        SET bytes = GREATEST(bytes - $3, 0), uploads = GREATEST(uploads - 1, 0),
+            updated_at = NOW()
+        WHERE blog_id = $1 AND user_id = $2
+        "#,
+    )
+    .bind(current_blog_id())
+    .bind(user_id)
+    .bind(bytes)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Storage used by each user of each blog for post attachments and cover images.
+///
+/// Usage is counted as files are uploaded and deleted, in the same transactions, and checked
+/// against the quotas of [`StorageConfig`] before an upload is stored. Avatars do not count:
+/// they belong to no blog, and each user keeps only one.
+pub struct StorageService {
+    pool: PgPool,
+    config: &'static StorageConfig,
+}
+
+impl StorageService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            config: StorageConfig::global(),
+        }
+    }
+
+    /// Storage used by the user in the current blog
+    pub async fn user_usage(&self, user_id: Uuid) -> Result<StorageUsage, StorageError> {
+        let row = sqlx::query(
+            r#"
+            SELECT COALESCE(SUM(bytes), 0)::BIGINT AS bytes,
+                COALESCE(SUM(uploads), 0)::BIGINT AS uploads
+            FROM global.storage_usage
+            WHERE blog_id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(StorageUsage::new(
+            row.get("bytes"),
+            row.get("uploads"),
+            self.config.user_quota_bytes,
+        ))
+    }
+
+    /// Storage used by the current blog, with its users who store anything, largest first
+    pub async fn overview(&self, pagination: &Pagination) -> Result<StorageOverview, StorageError> {
+        let totals = sqlx::query(
+            r#"
+            SELECT COALESCE(SUM(bytes), 0)::BIGINT AS bytes,
+                COALESCE(SUM(uploads), 0)::BIGINT AS uploads
+            FROM global.storage_usage
+            WHERE blog_id = $1
+            "#,
+        )
+        .bind(current_blog_id())
+        .fetch_one(&self.pool)
+        .await?;
+
+        let users = sqlx::query(
+            r#"
+            SELECT s.user_id, u.username, s.bytes, s.uploads::BIGINT AS uploads
+            FROM global.storage_usage s
+            JOIN global.users u ON u.id = s.user_id
+            WHERE s.blog_id = $1 AND s.bytes > 0
+            ORDER BY s.bytes DESC, s.user_id
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(pagination.limit)
+        .bind(pagination.offset)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| UserStorage {
+            user_id: row.get("user_id"),
+            username: row.get("username"),
+            used_bytes: row.get("bytes"),
+            uploads: row.get("uploads"),
+        })
+        .collect();
+
+        Ok(StorageOverview {
+            blog: StorageUsage::new(
+                totals.get("bytes"),
+                totals.get("uploads"),
+                self.config.blog_quota_bytes,
+            ),
+            user_quota_bytes: self.config.user_quota_bytes,
+            users,
+        })
+    }
+}
@@ -0,0 +1,69 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::tag::model::{RetagRequest, TagError};
+use crate::tag::service::TagService;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+fn tag_error_response(e: TagError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Tag error: {:?}", e);
+    let status = match e {
+        TagError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        TagError::TagNotFound(_) => StatusCode::NOT_FOUND,
+        TagError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Move posts from one tag to another (admin only)
+///
+/// Moves the posts of the blog tagged `from_tag`, or only those in `post_ids`, to `to_tag`,
+/// creating it if needed. Everything is moved in one transaction and recorded in the audit
+/// log, and the cached posts and post lists are cleared.
+#[utoipa::path(
+    post,
+    path = "/api/admin/tags/retag",
+    tag = "posts",
+    request_body = RetagRequest,
+    responses(
+        (status = 200, description = "Posts retagged", body = RetagResponse),
+        (status = 400, description = "Missing, equal or too long tags"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "from_tag does not exist"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn retag(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<TagService>>,
+    Json(request): Json<RetagRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "Only admins can retag posts" })),
+        )
+            .into_response();
+    }
+
+    match service.retag(user.user_id, request).await {
+        Ok(result) => (StatusCode::OK, Json(result)).into_response(),
+        Err(e) => tag_error_response(e),
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Longest tag name, as stored
+pub const MAX_TAG_CHARS: usize = 100;
+
+/// A tag name as stored: trimmed, with runs of whitespace collapsed to one space, and cut to
+/// `MAX_TAG_CHARS`. Casing is kept; tags are matched ignoring case and accents, see
+/// `crate::slug`.
+pub fn normalize_tag_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_TAG_CHARS)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// Posts to move from one tag to another, e.g. after a taxonomy change
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RetagRequest {
+    /// Tag the posts are moved off
+    #[schema(example = "rustlang")]
+    pub from_tag: String,
+    /// Tag the posts are moved to; created when it does not exist yet
+    #[schema(example = "rust")]
+    pub to_tag: String,
+    /// Only move these posts; all posts of the blog with `from_tag` when omitted
+    pub post_ids: Option<Vec<i64>>,
+}
+
+/// Outcome of a retagging
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RetagResponse {
+    pub from_tag: String,
+    pub to_tag: String,
+    /// Posts moved from `from_tag` to `to_tag`
+    pub post_ids: Vec<i64>,
+    /// How many of them had `to_tag` already, and only lost `from_tag`
+    #[schema(example = 0)]
+    pub already_tagged: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TagError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Tag not found: {0}")]
+    TagNotFound(String),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tag_name() {
+        assert_eq!(normalize_tag_name("  Rust \t Lang "), "Rust Lang");
+        assert_eq!(normalize_tag_name(" \n "), "");
+        assert_eq!(normalize_tag_name(&"a".repeat(150)).len(), MAX_TAG_CHARS);
+        assert_eq!(
+            normalize_tag_name(&format!("{} b", "a".repeat(MAX_TAG_CHARS - 1))),
+            "a".repeat(MAX_TAG_CHARS - 1)
+        );
+    }
+}
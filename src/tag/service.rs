@@ -0,0 +1,337 @@
+use crate::audit;
+use crate::cache::redis::RedisCache;
+use crate::cache::surrogate::{purge, SurrogateKey};
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::slug::match_key;
+use crate::tag::model::{normalize_tag_name, RetagRequest, RetagResponse, TagError};
+use crate::tenant::middleware::current_blog_id;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Kind of the job that merges tags differing only in case or accents and deletes unused tags
+pub const TAG_CLEANUP_JOB: &str = "tag_cleanup";
+
+// How often tags are cleaned up, and how often instances check whether a run is due
+const TAG_CLEANUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const TAG_CLEANUP_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Ids of the tags with the given names, in order and without duplicates, creating the
+/// missing ones. Names are normalized and matched on their `match_key`, so `Café`, ` cafe`
+/// and `CAFE` are one tag, named as it was first written.
+///
+/// The tags stay locked until the transaction on `conn` ends, so a cleanup cannot delete
+/// them before they are given to a post.
+pub async fn resolve_tags(
+    conn: &mut PgConnection,
+    names: &[String],
+) -> Result<Vec<i64>, sqlx::Error> {
+    let mut unique: Vec<String> = Vec::new();
+    for name in names.iter().map(|name| normalize_tag_name(name)) {
+        let key = match_key(&name);
+        if !name.is_empty() && !unique.iter().any(|seen| match_key(seen) == key) {
+            unique.push(name);
+        }
+    }
+    if unique.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // A tag deleted by a cleanup between the two statements is created again on the retry
+    for _ in 0..2 {
+        sqlx::query(
+            r#"
+            INSERT INTO global.tags (name)
+            SELECT n.name FROM UNNEST($1::TEXT[]) AS n(name)
+            WHERE NOT EXISTS (
+                SELECT 1 FROM global.tags t
+                WHERE global.match_key(t.name) = global.match_key(n.name)
+            )
+            ON CONFLICT (name) DO NOTHING
+            "#,
+        )
+        .bind(&unique)
+        .execute(&mut *conn)
+        .await?;
+
+        // Of tags with the same match key, which cleanups merge, the oldest is used
+        let ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT t.id
+            FROM UNNEST($1::TEXT[]) WITH ORDINALITY AS n(name, position)
+            CROSS JOIN LATERAL (
+                SELECT id FROM global.tags
+                WHERE global.match_key(name) = global.match_key(n.name)
+                ORDER BY id
+                LIMIT 1
+                FOR KEY SHARE
+            ) t
+            ORDER BY n.position
+            "#,
+        )
+        .bind(&unique)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        if ids.len() == unique.len() {
+            let mut resolved: Vec<i64> = Vec::with_capacity(ids.len());
+            for id in ids {
+                if !resolved.contains(&id) {
+                    resolved.push(id);
+                }
+            }
+            return Ok(resolved);
+        }
+    }
+
+    Err(sqlx::Error::RowNotFound)
+}
+
+/// Replace the tags of a post with the given names, creating missing tags
+pub async fn set_post_tags(
+    conn: &mut PgConnection,
+    post_id: i64,
+    names: &[String],
+) -> Result<(), sqlx::Error> {
+    let tag_ids = resolve_tags(conn, names).await?;
+
+    sqlx::query("DELETE FROM global.post_tags WHERE post_id = $1")
+        .bind(post_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO global.post_tags (post_id, tag_id)
+        SELECT $1, tag_id FROM UNNEST($2::BIGINT[]) AS tag_id
+        "#,
+    )
+    .bind(post_id)
+    .bind(&tag_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Tag maintenance: bulk retagging by admins, and a daily background job that merges tags
+/// differing only in case or accents and deletes tags no post has.
+///
+/// Tags are shared by all blogs, so retagging only ever touches the posts of the current
+/// blog, and a tag is only unused once no post of any blog has it.
+#[derive(Debug, Clone)]
+pub struct TagService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+}
+
+impl TagService {
+    pub fn new(pool: PgPool, redis_cache: Option<RedisCache>) -> Self {
+        Self { pool, redis_cache }
+    }
+
+    /// Move posts from one tag to another in one transaction, recorded in the audit log as
+    /// done by `actor_id`. Posts that already have the new tag just lose the old one.
+    pub async fn retag(
+        &self,
+        actor_id: Uuid,
+        request: RetagRequest,
+    ) -> Result<RetagResponse, TagError> {
+        let from_tag = normalize_tag_name(&request.from_tag);
+        let to_tag = normalize_tag_name(&request.to_tag);
+        if from_tag.is_empty() || to_tag.is_empty() {
+            return Err(TagError::InvalidInput(
+                "Both from_tag and to_tag are required".to_string(),
+            ));
+        }
+        if match_key(&from_tag) == match_key(&to_tag) {
+            return Err(TagError::InvalidInput(
+                "from_tag and to_tag must differ".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let from_id: i64 = sqlx::query_scalar(
+            r#"
+            SELECT id FROM global.tags
+            WHERE global.match_key(name) = global.match_key($1)
+            ORDER BY id
+            LIMIT 1
+            "#,
+        )
+        .bind(&from_tag)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| TagError::TagNotFound(from_tag.clone()))?;
+        let to_id = resolve_tags(&mut tx, std::slice::from_ref(&to_tag)).await?[0];
+
+        // The associations are locked so concurrent post edits wait for the move
+        let post_ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            SELECT p.id
+            FROM global.posts p
+            JOIN global.post_tags pt ON pt.post_id = p.id
+            WHERE pt.tag_id = $1 AND p.blog_id = $2
+                AND ($3::BIGINT[] IS NULL OR p.id = ANY($3))
+            ORDER BY p.id
+            FOR UPDATE OF pt
+            "#,
+        )
+        .bind(from_id)
+        .bind(current_blog_id())
+        .bind(&request.post_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let added = sqlx::query(
+            r#"
+            INSERT INTO global.post_tags (post_id, tag_id)
+            SELECT post_id, $2 FROM UNNEST($1::BIGINT[]) AS post_id
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(&post_ids)
+        .bind(to_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query("DELETE FROM global.post_tags WHERE tag_id = $1 AND post_id = ANY($2)")
+            .bind(from_id)
+            .bind(&post_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        audit::record(
+            &mut tx,
+            actor_id,
+            "tags.retag",
+            json!({
+                "from_tag": from_tag,
+                "to_tag": to_tag,
+                "post_ids": post_ids,
+            }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        // Cached posts and the lists showing them carry their tags
+        if !post_ids.is_empty() {
+            let mut keys = vec![SurrogateKey::Tag(from_tag.clone())];
+            keys.extend(post_ids.iter().copied().map(SurrogateKey::Post));
+            purge(self.redis_cache.as_ref(), &keys).await;
+        }
+
+        info!(
+            "Retagged {} posts from {} to {}",
+            post_ids.len(),
+            from_tag,
+            to_tag
+        );
+        Ok(RetagResponse {
+            from_tag,
+            to_tag,
+            already_tagged: post_ids.len() as u64 - added,
+            post_ids,
+        })
+    }
+
+    /// Queue a cleanup once a day
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TAG_CLEANUP_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(TAG_CLEANUP_JOB, json!({}), TAG_CLEANUP_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule tag cleanup: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Merge tags with the same match key into the oldest of them, then delete the tags no
+    /// post has. Returns how many tags were merged and how many deleted.
+    pub async fn cleanup(&self) -> Result<(u64, u64), JobError> {
+        let mut tx = self.pool.begin().await?;
+
+        let duplicates: Vec<(i64, i64)> = sqlx::query_as(
+            r#"
+            SELECT id, keeper_id FROM (
+                SELECT id, MIN(id) OVER (PARTITION BY global.match_key(name)) AS keeper_id
+                FROM global.tags
+            ) tags
+            WHERE id <> keeper_id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        if !duplicates.is_empty() {
+            let (ids, keeper_ids): (Vec<i64>, Vec<i64>) = duplicates.iter().copied().unzip();
+            sqlx::query(
+                r#"
+                INSERT INTO global.post_tags (post_id, tag_id)
+                SELECT pt.post_id, d.keeper_id
+                FROM global.post_tags pt
+                JOIN UNNEST($1::BIGINT[], $2::BIGINT[]) AS d(id, keeper_id) ON d.id = pt.tag_id
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(&ids)
+            .bind(&keeper_ids)
+            .execute(&mut *tx)
+            .await?;
+            // Their remaining associations go with them
+            sqlx::query("DELETE FROM global.tags WHERE id = ANY($1)")
+                .bind(&ids)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // Tags locked by a post being saved are about to be used, so they are skipped
+        let deleted = sqlx::query(
+            r#"
+            DELETE FROM global.tags WHERE id IN (
+                SELECT t.id FROM global.tags t
+                WHERE NOT EXISTS (SELECT 1 FROM global.post_tags pt WHERE pt.tag_id = t.id)
+                FOR UPDATE SKIP LOCKED
+            )
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        info!(
+            "Merged {} duplicate tags and deleted {} unused tags",
+            duplicates.len(),
+            deleted
+        );
+        Ok((duplicates.len() as u64, deleted))
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let (merged, deleted) = self.cleanup().await?;
+        Ok(json!({ "merged": merged, "deleted": deleted }))
+    }
+}
+
+impl JobHandler for TagService {
+    fn kind(&self) -> &'static str {
+        TAG_CLEANUP_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
@@ -0,0 +1,142 @@
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::tenant::model::{CreateBlogRequest, TenantError, UpdateBlogRequest};
+use crate::tenant::service::TenantService;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+
+fn tenant_error_response(e: TenantError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    error!("Tenant error: {:?}", e);
+    let status = match e {
+        TenantError::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+        TenantError::BlogNotFound => StatusCode::NOT_FOUND,
+        TenantError::BlogExists => StatusCode::CONFLICT,
+        TenantError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({ "error": "Only admins can manage blogs" })),
+    )
+        .into_response()
+}
+
+/// List blogs (admin only)
+#[utoipa::path(
+    get,
+    path = "/api/admin/blogs",
+    tag = "blogs",
+    responses(
+        (status = 200, description = "Blogs retrieved successfully", body = Vec<Blog>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_blogs(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<TenantService>>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.list_blogs().await {
+        Ok(blogs) => (StatusCode::OK, Json(json!(blogs))).into_response(),
+        Err(e) => tenant_error_response(e),
+    }
+}
+
+/// Provision a blog (admin only)
+///
+/// The new blog is served under `/blogs/{slug}/api/...` and, if a domain is given, on that domain.
+#[utoipa::path(
+    post,
+    path = "/api/admin/blogs",
+    tag = "blogs",
+    request_body = CreateBlogRequest,
+    responses(
+        (status = 201, description = "Blog created", body = Blog),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "Slug or domain already in use"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_blog(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<TenantService>>,
+    Json(request): Json<CreateBlogRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.create_blog(request).await {
+        Ok(blog) => {
+            info!("Admin {} provisioned blog {}", user.user_id, blog.slug);
+            (StatusCode::CREATED, Json(json!(blog))).into_response()
+        }
+        Err(e) => tenant_error_response(e),
+    }
+}
+
+/// Update a blog (admin only)
+#[utoipa::path(
+    put,
+    path = "/api/admin/blogs/{blog_id}",
+    tag = "blogs",
+    params(
+        ("blog_id" = i64, Path, description = "Blog ID")
+    ),
+    request_body = UpdateBlogRequest,
+    responses(
+        (status = 200, description = "Blog updated", body = Blog),
+        (status = 400, description = "Invalid parameters"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Blog not found"),
+        (status = 409, description = "Domain already in use"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_blog(
+    Extension(user): Extension<AuthUser>,
+    Path(blog_id): Path<i64>,
+    State(service): State<Arc<TenantService>>,
+    Json(request): Json<UpdateBlogRequest>,
+) -> Response {
+    if user.role != Role::Admin {
+        return forbidden();
+    }
+
+    match service.update_blog(blog_id, request).await {
+        Ok(blog) => (StatusCode::OK, Json(json!(blog))).into_response(),
+        Err(e) => tenant_error_response(e),
+    }
+}
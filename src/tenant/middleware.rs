@@ -0,0 +1,122 @@
+use crate::tenant::model::{TenantError, DEFAULT_BLOG_ID};
+use crate::tenant::service::TenantService;
+use axum::{
+    extract::{OriginalUri, State},
+    http::{header, uri::PathAndQuery, Request, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+use std::future::Future;
+use std::sync::Arc;
+use tracing::error;
+
+/// Path prefix that selects a blog by slug, e.g. `/blogs/engineering/api/v1/posts/popular`
+pub const BLOG_PATH_PREFIX: &str = "/blogs/";
+
+tokio::task_local! {
+    static CURRENT_BLOG_ID: i64;
+}
+
+/// Blog of the request being handled; the default blog outside of requests
+pub fn current_blog_id() -> i64 {
+    CURRENT_BLOG_ID
+        .try_with(|id| *id)
+        .unwrap_or(DEFAULT_BLOG_ID)
+}
+
+/// Run work outside of a request, such as a background job, scoped to a blog
+pub async fn with_blog_id<F: Future>(blog_id: i64, work: F) -> F::Output {
+    CURRENT_BLOG_ID.scope(blog_id, work).await
+}
+
+/// Prefix a cache key with the current blog so blogs never see each other's cached data
+pub fn blog_key(key: &str) -> String {
+    format!("blog:{}:{}", current_blog_id(), key)
+}
+
+/// Split `/blogs/{slug}/rest` into the slug and the remaining path
+fn split_blog_path(path: &str) -> Option<(&str, &str)> {
+    let rest = path.strip_prefix(BLOG_PATH_PREFIX)?;
+    match rest.find('/') {
+        Some(index) => Some((&rest[..index], &rest[index..])),
+        None => Some((rest, "/")),
+    }
+}
+
+/// Middleware that resolves the blog a request is for and scopes the rest of the request to it.
+///
+/// It runs before routing: a `/blogs/{slug}` prefix is stripped from the path so the normal
+/// routes match, and otherwise the `Host` header is matched against custom domains.
+pub async fn resolve_tenant<B>(
+    State(service): State<Arc<TenantService>>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let original_uri = req.uri().clone();
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let (path_slug, stripped) = match split_blog_path(original_uri.path()) {
+        Some((slug, rest)) => (Some(slug.to_string()), Some(rest.to_string())),
+        None => (None, None),
+    };
+
+    let blog = match service.resolve(path_slug.as_deref(), host.as_deref()).await {
+        Ok(blog) => blog,
+        Err(TenantError::BlogNotFound) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": "Blog not found" })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("Failed to resolve blog: {:?}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to resolve blog" })),
+            )
+                .into_response();
+        }
+    };
+
+    if let Some(path) = stripped {
+        let path_and_query = match original_uri.query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        };
+        let mut parts = original_uri.clone().into_parts();
+        parts.path_and_query = path_and_query.parse::<PathAndQuery>().ok();
+        if let Ok(uri) = Uri::from_parts(parts) {
+            // Keep the prefixed URI for handlers that build links back to this request
+            req.extensions_mut().insert(OriginalUri(original_uri));
+            *req.uri_mut() = uri;
+        }
+    }
+
+    let blog_id = blog.id;
+    req.extensions_mut().insert(blog);
+    CURRENT_BLOG_ID.scope(blog_id, next.run(req)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_blog_path() {
+        assert_eq!(
+            split_blog_path("/blogs/engineering/api/v1/posts/popular"),
+            Some(("engineering", "/api/v1/posts/popular"))
+        );
+        assert_eq!(
+            split_blog_path("/blogs/engineering"),
+            Some(("engineering", "/"))
+        );
+        assert_eq!(split_blog_path("/api/v1/posts/popular"), None);
+    }
+}
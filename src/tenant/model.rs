@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+/// Blog that requests without a blog prefix or custom domain belong to. Created with the
+/// schema so single-blog deployments keep working unchanged.
+pub const DEFAULT_BLOG_ID: i64 = 1;
+
+/// A blog hosted on the platform. Posts, comments and memberships belong to exactly one blog.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Blog {
+    /// Blog ID
+    #[schema(example = "2")]
+    pub id: i64,
+
+    /// Unique slug, used in `/blogs/{slug}/api/...` paths
+    #[schema(example = "engineering")]
+    pub slug: String,
+
+    /// Display name
+    #[schema(example = "Engineering Blog")]
+    pub name: String,
+
+    /// Custom domain serving this blog
+    #[schema(example = "engineering.example.com")]
+    pub domain: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request to provision a blog
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateBlogRequest {
+    /// Unique slug: lowercase letters, digits and hyphens
+    #[schema(example = "engineering")]
+    pub slug: String,
+
+    /// Display name
+    #[schema(example = "Engineering Blog")]
+    pub name: String,
+
+    /// Custom domain serving this blog
+    #[schema(example = "engineering.example.com")]
+    pub domain: Option<String>,
+}
+
+/// Request to update a blog
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateBlogRequest {
+    /// New display name
+    #[schema(example = "Engineering Blog")]
+    pub name: Option<String>,
+
+    /// New custom domain; an empty string removes it
+    #[schema(example = "engineering.example.com")]
+    pub domain: Option<String>,
+}
+
+/// Possible tenant errors
+#[derive(Debug, thiserror::Error)]
+pub enum TenantError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Blog not found")]
+    BlogNotFound,
+
+    #[error("A blog with this slug or domain already exists")]
+    BlogExists,
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+}
+
+/// Whether `slug` can be used as a blog slug
+pub fn is_valid_blog_slug(slug: &str) -> bool {
+    (1..=63).contains(&slug.len())
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+}
+
+/// Normalize a domain or `Host` header value for lookups: lowercase, without port
+pub fn normalize_domain(host: &str) -> String {
+    let host = host.trim().trim_end_matches('.');
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    host.to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blog_slug_validation() {
+        assert!(is_valid_blog_slug("engineering"));
+        assert!(is_valid_blog_slug("team-2"));
+        assert!(!is_valid_blog_slug(""));
+        assert!(!is_valid_blog_slug("Engineering"));
+        assert!(!is_valid_blog_slug("-team"));
+        assert!(!is_valid_blog_slug("team/blog"));
+    }
+
+    #[test]
+    fn test_normalize_domain_strips_port_and_case() {
+        assert_eq!(
+            normalize_domain("Blog.Example.com:8080"),
+            "blog.example.com"
+        );
+        assert_eq!(normalize_domain("blog.example.com."), "blog.example.com");
+        assert_eq!(normalize_domain("localhost"), "localhost");
+    }
+}
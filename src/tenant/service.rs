@@ -0,0 +1,185 @@
+use crate::tenant::model::{
+    is_valid_blog_slug, normalize_domain, Blog, CreateBlogRequest, TenantError, UpdateBlogRequest,
+    DEFAULT_BLOG_ID,
+};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// Blog lookups are resolved on every request, so they are kept in memory for a short while.
+// Changes made through another instance show up once the entry expires.
+const BLOG_LOOKUP_TTL: Duration = Duration::from_secs(60);
+
+const BLOG_COLUMNS: &str = "id, slug, name, domain, created_at";
+
+// Resolved blog (or a remembered miss) with the time it was looked up
+type BlogLookups = HashMap<String, (Option<Blog>, Instant)>;
+
+#[derive(Debug, Clone)]
+pub struct TenantService {
+    pool: PgPool,
+    // Keyed by `slug:<slug>` or `domain:<domain>`; misses are remembered too
+    lookups: Arc<Mutex<BlogLookups>>,
+}
+
+impl TenantService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            lookups: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // List all blogs
+    pub async fn list_blogs(&self) -> Result<Vec<Blog>, TenantError> {
+        let blogs = sqlx::query_as::<_, Blog>(&format!(
+            "SELECT {} FROM global.blogs ORDER BY id",
+            BLOG_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(blogs)
+    }
+
+    // Provision a new blog
+    pub async fn create_blog(&self, request: CreateBlogRequest) -> Result<Blog, TenantError> {
+        let slug = request.slug.trim();
+        if !is_valid_blog_slug(slug) {
+            return Err(TenantError::InvalidParameter(
+                "Slug must be 1-63 lowercase letters, digits or hyphens".to_string(),
+            ));
+        }
+        let name = request.name.trim();
+        if name.is_empty() {
+            return Err(TenantError::InvalidParameter(
+                "Blog name cannot be empty".to_string(),
+            ));
+        }
+        let domain = request
+            .domain
+            .as_deref()
+            .map(normalize_domain)
+            .filter(|domain| !domain.is_empty());
+
+        let blog = sqlx::query_as::<_, Blog>(&format!(
+            r#"
+            INSERT INTO global.blogs (slug, name, domain)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            RETURNING {}
+            "#,
+            BLOG_COLUMNS
+        ))
+        .bind(slug)
+        .bind(name)
+        .bind(&domain)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(TenantError::BlogExists)?;
+
+        self.clear_lookups();
+        info!("Provisioned blog {} ({})", blog.slug, blog.id);
+        Ok(blog)
+    }
+
+    // Rename a blog or change its custom domain
+    pub async fn update_blog(
+        &self,
+        blog_id: i64,
+        request: UpdateBlogRequest,
+    ) -> Result<Blog, TenantError> {
+        let name = request.name.as_deref().map(str::trim);
+        if name == Some("") {
+            return Err(TenantError::InvalidParameter(
+                "Blog name cannot be empty".to_string(),
+            ));
+        }
+        // None keeps the domain, Some("") removes it
+        let domain = request.domain.as_deref().map(normalize_domain);
+
+        let blog = sqlx::query_as::<_, Blog>(&format!(
+            r#"
+            UPDATE global.blogs
+            SET name = COALESCE($2, name),
+                domain = CASE WHEN $3::TEXT IS NULL THEN domain ELSE NULLIF($3, '') END
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            BLOG_COLUMNS
+        ))
+        .bind(blog_id)
+        .bind(name)
+        .bind(&domain)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => TenantError::BlogExists,
+            e => TenantError::DatabaseError(e),
+        })?
+        .ok_or(TenantError::BlogNotFound)?;
+
+        self.clear_lookups();
+        info!("Updated blog {} ({})", blog.slug, blog.id);
+        Ok(blog)
+    }
+
+    // Find the blog a request belongs to: the slug from a `/blogs/{slug}` path prefix wins,
+    // then a custom domain matching the `Host` header, then the default blog
+    pub async fn resolve(
+        &self,
+        path_slug: Option<&str>,
+        host: Option<&str>,
+    ) -> Result<Blog, TenantError> {
+        if let Some(slug) = path_slug {
+            return self
+                .lookup("slug", slug, "slug = $1")
+                .await?
+                .ok_or(TenantError::BlogNotFound);
+        }
+
+        if let Some(host) = host.map(normalize_domain) {
+            if let Some(blog) = self.lookup("domain", &host, "domain = $1").await? {
+                return Ok(blog);
+            }
+        }
+
+        self.lookup("id", &DEFAULT_BLOG_ID.to_string(), "id = $1::BIGINT")
+            .await?
+            .ok_or(TenantError::BlogNotFound)
+    }
+
+    async fn lookup(
+        &self,
+        kind: &str,
+        value: &str,
+        condition: &str,
+    ) -> Result<Option<Blog>, TenantError> {
+        let key = format!("{}:{}", kind, value);
+        if let Some((blog, fetched_at)) = self.lookups.lock().unwrap().get(&key) {
+            if fetched_at.elapsed() < BLOG_LOOKUP_TTL {
+                return Ok(blog.clone());
+            }
+        }
+
+        let blog = sqlx::query_as::<_, Blog>(&format!(
+            "SELECT {} FROM global.blogs WHERE {}",
+            BLOG_COLUMNS, condition
+        ))
+        .bind(value)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        self.lookups
+            .lock()
+            .unwrap()
+            .insert(key, (blog.clone(), Instant::now()));
+        Ok(blog)
+    }
+
+    fn clear_lookups(&self) {
+        self.lookups.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,137 @@
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::sitemap::site_base_url;
+use crate::tenant::model::Blog;
+use crate::tip::model::{CreateTipRequest, EarningsParams, TipError};
+use crate::tip::service::TipService;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, warn};
+
+fn tip_error_response(e: TipError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        TipError::ValidationError(_) | TipError::InvalidWebhook(_) => StatusCode::BAD_REQUEST,
+        TipError::PostNotFound => StatusCode::NOT_FOUND,
+        TipError::ProviderNotConfigured => StatusCode::SERVICE_UNAVAILABLE,
+        TipError::ProviderError(_) => {
+            error!("Tip error: {:?}", e);
+            StatusCode::BAD_GATEWAY
+        }
+        TipError::DatabaseError(_) => {
+            error!("Tip error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Tip the author of a post
+///
+/// Opens a checkout with the payment provider and returns its URL, where the reader pays.
+/// The tip counts toward the author's earnings once the provider reports it paid. Anyone may
+/// tip; signed-in readers are recorded as the tipper.
+#[utoipa::path(
+    post,
+    path = "/api/posts/{id}/tips",
+    tag = "tips",
+    params(
+        ("id" = i64, Path, description = "Post ID")
+    ),
+    request_body = CreateTipRequest,
+    responses(
+        (status = 201, description = "Checkout opened", body = TipCheckout),
+        (status = 400, description = "Invalid amount or message, or tipping one's own post"),
+        (status = 404, description = "Post not found"),
+        (status = 429, description = "Too many tips started"),
+        (status = 500, description = "Internal server error"),
+        (status = 502, description = "The payment provider failed"),
+        (status = 503, description = "Tipping is not configured")
+    )
+)]
+pub async fn create_tip(
+    Extension(user): Extension<Option<AuthUser>>,
+    Extension(blog): Extension<Blog>,
+    State(service): State<Arc<TipService>>,
+    Path(id): Path<i64>,
+    Json(request): Json<CreateTipRequest>,
+) -> Response {
+    let tipper_id = user.map(|user| user.user_id);
+    match service
+        .create_tip(id, tipper_id, &request, &site_base_url(&blog))
+        .await
+    {
+        Ok(checkout) => (StatusCode::CREATED, Json(checkout)).into_response(),
+        Err(e) => tip_error_response(e),
+    }
+}
+
+/// Receive payment outcomes from the payment provider
+///
+/// Called by the provider, not by front-ends; calls are verified by the provider's
+/// signature.
+#[utoipa::path(
+    post,
+    path = "/api/tips/webhook",
+    tag = "tips",
+    responses(
+        (status = 204, description = "Event processed"),
+        (status = 400, description = "Invalid signature or event"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Tipping is not configured")
+    )
+)]
+pub async fn tip_webhook(
+    State(service): State<Arc<TipService>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    match service.handle_webhook(&headers, &body).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            if let TipError::InvalidWebhook(reason) = &e {
+                warn!("Refused tip webhook: {}", reason);
+            }
+            tip_error_response(e)
+        }
+    }
+}
+
+/// Summarize the tips paid to the current user
+///
+/// Totals by currency and by post, and the latest tips, optionally within a period of
+/// payment.
+#[utoipa::path(
+    get,
+    path = "/api/analytics/authors/me/earnings",
+    tag = "tips",
+    params(EarningsParams),
+    responses(
+        (status = 200, description = "Earnings from tips", body = EarningsSummary),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_earnings(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<TipService>>,
+    Query(params): Query<EarningsParams>,
+) -> Response {
+    match service.earnings(user.user_id, &params).await {
+        Ok(summary) => (StatusCode::OK, Json(summary)).into_response(),
+        Err(e) => tip_error_response(e),
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// Smallest and largest tip, in the minor unit of the currency (e.g. cents)
+pub const MIN_TIP_AMOUNT: i64 = 100;
+pub const MAX_TIP_AMOUNT: i64 = 100_000;
+/// Longest message to the author, in characters
+pub const MAX_TIP_MESSAGE_LENGTH: usize = 500;
+/// Currency of tips when `TIPS_CURRENCY` is not set
+pub const DEFAULT_TIP_CURRENCY: &str = "usd";
+/// Latest tips listed in an earnings summary
+pub const RECENT_TIPS: i64 = 20;
+
+/// Where the payment of a tip stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TipStatus {
+    /// Checkout started, not paid yet
+    Pending,
+    Paid,
+    /// Checkout expired or payment failed
+    Failed,
+}
+
+impl TipStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TipStatus::Pending => "pending",
+            TipStatus::Paid => "paid",
+            TipStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(TipStatus::Pending),
+            "paid" => Some(TipStatus::Paid),
+            "failed" => Some(TipStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Request to tip the author of a post
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateTipRequest {
+    /// Amount in the minor unit of the blog's currency, e.g. 500 for $5
+    #[schema(example = 500)]
+    pub amount: i64,
+    /// Message to the author
+    #[schema(example = "Thanks, this saved my afternoon!")]
+    pub message: Option<String>,
+}
+
+/// A started checkout to pay a tip
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TipCheckout {
+    #[schema(example = 31)]
+    pub tip_id: i64,
+    /// Page of the payment provider to send the reader to
+    pub checkout_url: String,
+}
+
+/// A tip to an author
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Tip {
+    #[schema(example = 31)]
+    pub id: i64,
+    /// Post tipped; `None` once the post is deleted
+    pub post_id: Option<i64>,
+    pub author_id: Uuid,
+    /// Signed-in reader who tipped
+    pub tipper_id: Option<Uuid>,
+    #[schema(example = 500)]
+    pub amount: i64,
+    #[schema(example = "usd")]
+    pub currency: String,
+    pub message: Option<String>,
+    pub status: TipStatus,
+    pub created_at: DateTime<Utc>,
+    pub paid_at: Option<DateTime<Utc>>,
+}
+
+/// Period of an earnings summary
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EarningsParams {
+    /// Only tips paid at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only tips paid before this time
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Tips paid in one currency
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CurrencyEarnings {
+    #[schema(example = "usd")]
+    pub currency: String,
+    /// Sum of the tips, in the minor unit of the currency
+    #[schema(example = 4500)]
+    pub amount: i64,
+    #[schema(example = 7)]
+    pub tips: i64,
+}
+
+/// Tips paid for one post, in one currency
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PostEarnings {
+    #[schema(example = 123)]
+    pub post_id: i64,
+    pub title: String,
+    #[schema(example = "usd")]
+    pub currency: String,
+    #[schema(example = 2500)]
+    pub amount: i64,
+    #[schema(example = 4)]
+    pub tips: i64,
+}
+
+/// Tips an author was paid
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EarningsSummary {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Totals by currency
+    pub totals: Vec<CurrencyEarnings>,
+    /// Totals by post, highest first; tips of deleted posts count only toward the totals
+    pub posts: Vec<PostEarnings>,
+    /// Latest paid tips
+    pub recent: Vec<Tip>,
+}
+
+/// Possible tipping errors
+#[derive(Debug, thiserror::Error)]
+pub enum TipError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Post not found")]
+    PostNotFound,
+
+    #[error("Tipping is not configured")]
+    ProviderNotConfigured,
+
+    #[error("Payment provider error: {0}")]
+    ProviderError(String),
+
+    #[error("Invalid webhook: {0}")]
+    InvalidWebhook(String),
+}
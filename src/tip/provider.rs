@@ -0,0 +1,348 @@
+use crate::hex;
+use crate::tip::model::TipError;
+use axum::http::HeaderMap;
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::error;
+
+const DEFAULT_STRIPE_API_URL: &str = "https://api.stripe.com/v1";
+const STRIPE_TIMEOUT: Duration = Duration::from_secs(30);
+// Webhook calls signed longer ago than this are refused, so captured calls cannot be replayed
+const STRIPE_SIGNATURE_TOLERANCE_SECONDS: i64 = 300;
+
+/// A checkout to open with the provider
+#[derive(Debug, Clone)]
+pub struct CheckoutRequest {
+    pub tip_id: i64,
+    /// In the minor unit of the currency
+    pub amount: i64,
+    pub currency: String,
+    /// Shown to the reader on the checkout page
+    pub description: String,
+    /// Where the reader is sent after paying, or after giving up
+    pub success_url: String,
+    pub cancel_url: String,
+}
+
+/// A checkout opened with the provider
+#[derive(Debug, Clone)]
+pub struct Checkout {
+    /// The provider's ID of the checkout, which its webhook calls refer to
+    pub reference: String,
+    pub url: String,
+}
+
+/// How a checkout ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    /// Paid, with the amount and currency actually charged
+    Paid {
+        amount: i64,
+        currency: String,
+    },
+    Failed,
+}
+
+/// The end of a checkout, reported by a webhook call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentEvent {
+    pub reference: String,
+    pub outcome: PaymentOutcome,
+}
+
+/// Takes payments of tips. The rest of the tip jar knows nothing of the provider: it opens a
+/// checkout for each tip and learns the outcome from the provider's webhook calls.
+pub trait TipProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn create_checkout<'a>(
+        &'a self,
+        request: &'a CheckoutRequest,
+    ) -> BoxFuture<'a, Result<Checkout, TipError>>;
+
+    /// Verify that a webhook call comes from the provider and read the outcome it reports.
+    /// `None` for events that are not about the end of a checkout.
+    fn parse_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<PaymentEvent>, TipError>;
+}
+
+/// Check a `Stripe-Signature` header: an HMAC-SHA256 of `{timestamp}.{body}` with the
+/// endpoint's signing secret, made within the tolerance of `now` (Unix time)
+pub fn verify_stripe_signature(
+    secret: &str,
+    header: &str,
+    body: &[u8],
+    now: i64,
+) -> Result<(), TipError> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.extend(hex::decode(value)),
+            _ => {}
+        }
+    }
+
+    let timestamp = timestamp
+        .ok_or_else(|| TipError::InvalidWebhook("Missing signature timestamp".to_string()))?;
+    if (now - timestamp).abs() > STRIPE_SIGNATURE_TOLERANCE_SECONDS {
+        return Err(TipError::InvalidWebhook(
+            "Signature timestamp out of tolerance".to_string(),
+        ));
+    }
+
+    let verified = signatures.iter().any(|signature| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        mac.verify_slice(signature).is_ok()
+    });
+    if !verified {
+        return Err(TipError::InvalidWebhook(
+            "Signature does not match".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Outcome of a Stripe event, for the Checkout events that end a session
+fn stripe_payment_event(event: &Value) -> Option<PaymentEvent> {
+    let session = &event["data"]["object"];
+    let reference = session["id"].as_str()?.to_string();
+    let paid = || PaymentOutcome::Paid {
+        amount: session["amount_total"].as_i64().unwrap_or_default(),
+        currency: session["currency"].as_str().unwrap_or_default().to_string(),
+    };
+
+    let outcome = match event["type"].as_str()? {
+        // Delayed payment methods complete unpaid and report the outcome later
+        "checkout.session.completed" if session["payment_status"] == "paid" => paid(),
+        "checkout.session.async_payment_succeeded" => paid(),
+        "checkout.session.expired" | "checkout.session.async_payment_failed" => {
+            PaymentOutcome::Failed
+        }
+        _ => return None,
+    };
+    Some(PaymentEvent { reference, outcome })
+}
+
+#[derive(Deserialize)]
+struct StripeSession {
+    id: String,
+    url: String,
+}
+
+/// Takes tips with Stripe Checkout. Tips are paid to the platform's Stripe account; paying
+/// authors out is left to the operator.
+pub struct StripeProvider {
+    client: reqwest::Client,
+    api_url: String,
+    secret_key: String,
+    webhook_secret: String,
+}
+
+impl TipProvider for StripeProvider {
+    fn name(&self) -> &'static str {
+        "stripe"
+    }
+
+    fn create_checkout<'a>(
+        &'a self,
+        request: &'a CheckoutRequest,
+    ) -> BoxFuture<'a, Result<Checkout, TipError>> {
+        Box::pin(async move {
+            let tip_id = request.tip_id.to_string();
+            let amount = request.amount.to_string();
+            let form = [
+                ("mode", "payment"),
+                ("success_url", request.success_url.as_str()),
+                ("cancel_url", request.cancel_url.as_str()),
+                ("client_reference_id", tip_id.as_str()),
+                ("metadata[tip_id]", tip_id.as_str()),
+                ("line_items[0][quantity]", "1"),
+                (
+                    "line_items[0][price_data][currency]",
+                    request.currency.as_str(),
+                ),
+                ("line_items[0][price_data][unit_amount]", amount.as_str()),
+                (
+                    "line_items[0][price_data][product_data][name]",
+                    request.description.as_str(),
+                ),
+            ];
+
+            let response = self
+                .client
+                .post(format!(
+                    "{}/checkout/sessions",
+                    self.api_url.trim_end_matches('/')
+                ))
+                .basic_auth(&self.secret_key, None::<&str>)
+                .header("Idempotency-Key", format!("tip-{}", tip_id))
+                .form(&form)
+                .send()
+                .await
+                .map_err(|e| TipError::ProviderError(e.to_string()))?;
+            if !response.status().is_success() {
+                return Err(TipError::ProviderError(format!(
+                    "Stripe rejected the checkout with status {}",
+                    response.status()
+                )));
+            }
+
+            let session = response
+                .json::<StripeSession>()
+                .await
+                .map_err(|e| TipError::ProviderError(e.to_string()))?;
+            Ok(Checkout {
+                reference: session.id,
+                url: session.url,
+            })
+        })
+    }
+
+    fn parse_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Option<PaymentEvent>, TipError> {
+        let signature = headers
+            .get("stripe-signature")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| TipError::InvalidWebhook("Missing Stripe-Signature".to_string()))?;
+        verify_stripe_signature(
+            &self.webhook_secret,
+            signature,
+            body,
+            chrono::Utc::now().timestamp(),
+        )?;
+
+        let event: Value = serde_json::from_slice(body)
+            .map_err(|e| TipError::InvalidWebhook(format!("Invalid event: {}", e)))?;
+        Ok(stripe_payment_event(&event))
+    }
+}
+
+/// Which payment provider takes tips, read from the environment:
+///
+/// - `TIPS_PROVIDER`: `stripe`; unset disables tipping
+/// - `STRIPE_SECRET_KEY` and `STRIPE_WEBHOOK_SECRET` (the signing secret of the webhook
+///   endpoint), both required
+/// - `STRIPE_API_URL` (default `https://api.stripe.com/v1`)
+pub fn tip_provider_from_env() -> Option<Arc<dyn TipProvider>> {
+    let kind = std::env::var("TIPS_PROVIDER").ok()?;
+
+    match kind.to_lowercase().as_str() {
+        "stripe" => {
+            let (Ok(secret_key), Ok(webhook_secret)) = (
+                std::env::var("STRIPE_SECRET_KEY"),
+                std::env::var("STRIPE_WEBHOOK_SECRET"),
+            ) else {
+                error!(
+                    "TIPS_PROVIDER=stripe but STRIPE_SECRET_KEY or STRIPE_WEBHOOK_SECRET is not set"
+                );
+                return None;
+            };
+
+            match reqwest::Client::builder().timeout(STRIPE_TIMEOUT).build() {
+                Ok(client) => Some(Arc::new(StripeProvider {
+                    client,
+                    api_url: std::env::var("STRIPE_API_URL")
+                        .unwrap_or_else(|_| DEFAULT_STRIPE_API_URL.to_string()),
+                    secret_key,
+                    webhook_secret,
+                })),
+                Err(e) => {
+                    error!("Failed to create Stripe client: {}", e);
+                    None
+                }
+            }
+        }
+        other => {
+            error!("Unknown TIPS_PROVIDER: {}", other);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body);
+        format!(
+            "t={},v1={}",
+            timestamp,
+            hex::encode(mac.finalize().into_bytes())
+        )
+    }
+
+    #[test]
+    fn test_verify_stripe_signature() {
+        let body = br#"{"type":"checkout.session.completed"}"#;
+        let header = sign("whsec_test", 1_700_000_000, body);
+
+        assert!(verify_stripe_signature("whsec_test", &header, body, 1_700_000_060).is_ok());
+        // Wrong secret, tampered body, stale signature, no signature
+        assert!(verify_stripe_signature("whsec_other", &header, body, 1_700_000_060).is_err());
+        assert!(verify_stripe_signature("whsec_test", &header, b"{}", 1_700_000_060).is_err());
+        assert!(verify_stripe_signature("whsec_test", &header, body, 1_700_001_000).is_err());
+        assert!(
+            verify_stripe_signature("whsec_test", "t=1700000000", body, 1_700_000_000).is_err()
+        );
+    }
+
+    #[test]
+    fn test_stripe_payment_event() {
+        let event = |kind: &str, payment_status: &str| {
+            json!({
+                "type": kind,
+                "data": { "object": {
+                    "id": "cs_test_1",
+                    "payment_status": payment_status,
+                    "amount_total": 500,
+                    "currency": "usd",
+                }},
+            })
+        };
+
+        assert_eq!(
+            stripe_payment_event(&event("checkout.session.completed", "paid")),
+            Some(PaymentEvent {
+                reference: "cs_test_1".to_string(),
+                outcome: PaymentOutcome::Paid {
+                    amount: 500,
+                    currency: "usd".to_string(),
+                },
+            })
+        );
+        assert_eq!(
+            stripe_payment_event(&event("checkout.session.completed", "unpaid")),
+            None
+        );
+        assert_eq!(
+            stripe_payment_event(&event("checkout.session.expired", "unpaid"))
+                .map(|event| event.outcome),
+            Some(PaymentOutcome::Failed)
+        );
+        assert_eq!(
+            stripe_payment_event(&event("customer.created", "paid")),
+            None
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use crate::db::instrument::timed;
+use crate::tenant::middleware::current_blog_id;
+use crate::tip::model::{
+    CreateTipRequest, CurrencyEarnings, EarningsParams, EarningsSummary, PostEarnings, Tip,
+    TipCheckout, TipError, TipStatus, DEFAULT_TIP_CURRENCY, MAX_TIP_AMOUNT, MAX_TIP_MESSAGE_LENGTH,
+    MIN_TIP_AMOUNT, RECENT_TIPS,
+};
+use crate::tip::provider::{CheckoutRequest, PaymentOutcome, TipProvider};
+use axum::http::HeaderMap;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+// Columns of a tip
+const TIP_COLUMNS: &str =
+    "id, post_id, author_id, tipper_id, amount, currency, message, status, created_at, paid_at";
+
+fn tip_from_row(row: &PgRow) -> Tip {
+    let status: String = row.get("status");
+    Tip {
+        id: row.get("id"),
+        post_id: row.get("post_id"),
+        author_id: row.get("author_id"),
+        tipper_id: row.get("tipper_id"),
+        amount: row.get("amount"),
+        currency: row.get("currency"),
+        message: row.get("message"),
+        status: TipStatus::parse(&status).unwrap_or(TipStatus::Failed),
+        created_at: row.get("created_at"),
+        paid_at: row.get("paid_at"),
+    }
+}
+
+// Trimmed message of a valid tip
+fn validate_tip(request: &CreateTipRequest) -> Result<Option<String>, TipError> {
+    if !(MIN_TIP_AMOUNT..=MAX_TIP_AMOUNT).contains(&request.amount) {
+        return Err(TipError::ValidationError(format!(
+            "The amount must be {} to {}",
+            MIN_TIP_AMOUNT, MAX_TIP_AMOUNT
+        )));
+    }
+
+    let message = request
+        .message
+        .as_deref()
+        .map(str::trim)
+        .filter(|message| !message.is_empty());
+    if message.is_some_and(|message| message.chars().count() > MAX_TIP_MESSAGE_LENGTH) {
+        return Err(TipError::ValidationError(format!(
+            "The message must be at most {} characters",
+            MAX_TIP_MESSAGE_LENGTH
+        )));
+    }
+    Ok(message.map(str::to_string))
+}
+
+/// Tips from readers to the authors of posts.
+///
+/// A tip is recorded as pending and a checkout for it is opened with the payment provider,
+/// which the reader is sent to. The provider reports the outcome to the webhook endpoint;
+/// paid tips count toward the author's earnings. Without a provider (see `TIPS_PROVIDER`)
+/// tipping is disabled, but earnings from tips already paid are still reported.
+pub struct TipService {
+    pool: PgPool,
+    provider: Option<Arc<dyn TipProvider>>,
+    currency: String,
+}
+
+impl TipService {
+    /// Tips are in the currency set with `TIPS_CURRENCY`, an ISO 4217 code (default `usd`)
+    pub fn new(pool: PgPool, provider: Option<Arc<dyn TipProvider>>) -> Self {
+        let currency = std::env::var("TIPS_CURRENCY")
+            .ok()
+            .map(|currency| currency.trim().to_lowercase())
+            .filter(|currency| currency.len() == 3)
+            .unwrap_or_else(|| DEFAULT_TIP_CURRENCY.to_string());
+        Self {
+            pool,
+            provider,
+            currency,
+        }
+    }
+
+    fn provider(&self) -> Result<&Arc<dyn TipProvider>, TipError> {
+        self.provider
+            .as_ref()
+            .ok_or(TipError::ProviderNotConfigured)
+    }
+
+    /// Start tipping the author of a published post of the blog. `base_url` is the blog's
+    /// front-end URL, where the reader returns to the post after the checkout.
+    pub async fn create_tip(
+        &self,
+        post_id: i64,
+        tipper_id: Option<Uuid>,
+        request: &CreateTipRequest,
+        base_url: &str,
+    ) -> Result<TipCheckout, TipError> {
+        let provider = self.provider()?;
+        let message = validate_tip(request)?;
+
+        let post = sqlx::query(
+            r#"
+            SELECT p.title, p.slug, p.user_id, u.username
+            FROM global.posts p
+            JOIN global.users u ON u.id = p.user_id
+            WHERE p.id = $1 AND p.blog_id = $2 AND p.is_draft = false AND p.is_deleted = false
+            "#,
+        )
+        .bind(post_id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(TipError::PostNotFound)?;
+        let author_id: Uuid = post.get("user_id");
+        if tipper_id == Some(author_id) {
+            return Err(TipError::ValidationError(
+                "Authors cannot tip their own posts".to_string(),
+            ));
+        }
+
+        let tip_id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO global.tips
+                (blog_id, post_id, author_id, tipper_id, amount, currency, message, provider)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id
+            "#,
+        )
+        .bind(current_blog_id())
+        .bind(post_id)
+        .bind(author_id)
+        .bind(tipper_id)
+        .bind(request.amount)
+        .bind(&self.currency)
+        .bind(&message)
+        .bind(provider.name())
+        .fetch_one(&self.pool)
+        .await?;
+
+        let post_url = format!("{}/posts/{}", base_url, post.get::<String, _>("slug"));
+        let checkout = provider
+            .create_checkout(&CheckoutRequest {
+                tip_id,
+                amount: request.amount,
+                currency: self.currency.clone(),
+                description: format!(
+                    "Tip for {}: {}",
+                    post.get::<String, _>("username"),
+                    post.get::<String, _>("title")
+                ),
+                success_url: format!("{}?tip=paid", post_url),
+                cancel_url: format!("{}?tip=cancelled", post_url),
+            })
+            .await;
+        let checkout = match checkout {
+            Ok(checkout) => checkout,
+            Err(e) => {
+                error!("Failed to open checkout of tip {}: {}", tip_id, e);
+                sqlx::query("UPDATE global.tips SET status = 'failed' WHERE id = $1")
+                    .bind(tip_id)
+                    .execute(&self.pool)
+                    .await?;
+                return Err(e);
+            }
+        };
+
+        sqlx::query("UPDATE global.tips SET provider_ref = $2 WHERE id = $1")
+            .bind(tip_id)
+            .bind(&checkout.reference)
+            .execute(&self.pool)
+            .await?;
+
+        info!("Opened checkout of tip {} on post {}", tip_id, post_id);
+        Ok(TipCheckout {
+            tip_id,
+            checkout_url: checkout.url,
+        })
+    }
+
+    /// Record the outcome of a checkout reported by the provider. Calls about checkouts that
+    /// are unknown or already settled are accepted and ignored, as providers retry refused
+    /// calls.
+    pub async fn handle_webhook(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), TipError> {
+        let provider = self.provider()?;
+        let Some(event) = provider.parse_webhook(headers, body)? else {
+            return Ok(());
+        };
+
+        let result = match &event.outcome {
+            PaymentOutcome::Paid { amount, currency } => {
+                sqlx::query(
+                    r#"
+                    UPDATE global.tips
+                    SET status = 'paid', amount = $3, currency = $4, paid_at = NOW()
+                    WHERE provider = $1 AND provider_ref = $2 AND status <> 'paid'
+                    "#,
+                )
+                .bind(provider.name())
+                .bind(&event.reference)
+                .bind(amount)
+                .bind(currency.to_lowercase())
+                .execute(&self.pool)
+                .await?
+            }
+            PaymentOutcome::Failed => {
+                sqlx::query(
+                    "UPDATE global.tips SET status = 'failed' \
+                     WHERE provider = $1 AND provider_ref = $2 AND status = 'pending'",
+                )
+                .bind(provider.name())
+                .bind(&event.reference)
+                .execute(&self.pool)
+                .await?
+            }
+        };
+
+        if result.rows_affected() == 0 {
+            warn!(
+                "Ignored {} webhook about unknown or settled checkout {}",
+                provider.name(),
+                event.reference
+            );
+        } else {
+            info!("Checkout {} ended: {:?}", event.reference, event.outcome);
+        }
+        Ok(())
+    }
+
+    /// Tips the author was paid on the blog, optionally within a period
+    pub async fn earnings(
+        &self,
+        author_id: Uuid,
+        params: &EarningsParams,
+    ) -> Result<EarningsSummary, TipError> {
+        let blog_id = current_blog_id();
+        // Paid tips of the author in the period, from the tips table aliased as `t`
+        let paid = "t.blog_id = $1 AND t.author_id = $2 AND t.status = 'paid' \
+                    AND ($3::TIMESTAMPTZ IS NULL OR t.paid_at >= $3) \
+                    AND ($4::TIMESTAMPTZ IS NULL OR t.paid_at < $4)";
+
+        let sql = format!(
+            "SELECT t.currency, SUM(t.amount)::BIGINT AS amount, COUNT(*) AS tips \
+             FROM global.tips t WHERE {} GROUP BY t.currency ORDER BY amount DESC",
+            paid
+        );
+        let query = sqlx::query(&sql)
+            .bind(blog_id)
+            .bind(author_id)
+            .bind(params.from)
+            .bind(params.to)
+            .fetch_all(&self.pool);
+        let totals = timed("tips.earnings_totals", query)
+            .await?
+            .iter()
+            .map(|row| CurrencyEarnings {
+                currency: row.get("currency"),
+                amount: row.get("amount"),
+                tips: row.get("tips"),
+            })
+            .collect();
+
+        let sql = format!(
+            "SELECT t.post_id, p.title, t.currency, SUM(t.amount)::BIGINT AS amount, \
+             COUNT(*) AS tips \
+             FROM global.tips t JOIN global.posts p ON p.id = t.post_id \
+             WHERE {} GROUP BY t.post_id, p.title, t.currency \
+             ORDER BY amount DESC, t.post_id",
+            paid
+        );
+        let query = sqlx::query(&sql)
+            .bind(blog_id)
+            .bind(author_id)
+            .bind(params.from)
+            .bind(params.to)
+            .fetch_all(&self.pool);
+        let posts = timed("tips.earnings_posts", query)
+            .await?
+            .iter()
+            .map(|row| PostEarnings {
+                post_id: row.get("post_id"),
+                title: row.get("title"),
+                currency: row.get("currency"),
+                amount: row.get("amount"),
+                tips: row.get("tips"),
+            })
+            .collect();
+
+        let sql = format!(
+            "SELECT {} FROM global.tips t WHERE {} ORDER BY t.paid_at DESC LIMIT $5",
+            TIP_COLUMNS, paid
+        );
+        let recent = sqlx::query(&sql)
+            .bind(blog_id)
+            .bind(author_id)
+            .bind(params.from)
+            .bind(params.to)
+            .bind(RECENT_TIPS)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(tip_from_row)
+            .collect();
+
+        Ok(EarningsSummary {
+            from: params.from,
+            to: params.to,
+            totals,
+            posts,
+            recent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(amount: i64, message: Option<&str>) -> CreateTipRequest {
+        CreateTipRequest {
+            amount,
+            message: message.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_validate_tip() {
+        assert_eq!(validate_tip(&request(500, None)).unwrap(), None);
+        assert_eq!(
+            validate_tip(&request(500, Some("  Thanks! "))).unwrap(),
+            Some("Thanks!".to_string())
+        );
+        assert_eq!(validate_tip(&request(500, Some("   "))).unwrap(), None);
+        assert!(validate_tip(&request(MIN_TIP_AMOUNT - 1, None)).is_err());
+        assert!(validate_tip(&request(MAX_TIP_AMOUNT + 1, None)).is_err());
+        assert!(
+            validate_tip(&request(500, Some(&"a".repeat(MAX_TIP_MESSAGE_LENGTH + 1)))).is_err()
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::auth::middleware::AuthUser;
+use crate::db::retry::unavailable_response;
+use crate::rate_limit::client_address;
+use crate::title_test::model::{
+    StartTitleTestRequest, TitleClickRequest, TitleImpressionsRequest, TitleTestError, TitleViewer,
+};
+use crate::title_test::service::TitleTestService;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+// Longest visitor ID accepted from front-ends
+const MAX_VISITOR_ID_LENGTH: usize = 100;
+
+fn title_test_error_response(e: TitleTestError) -> Response {
+    if let Some(response) = unavailable_response(&e) {
+        return response;
+    }
+    let status = match e {
+        TitleTestError::ValidationError(_) => StatusCode::BAD_REQUEST,
+        TitleTestError::PostNotFound | TitleTestError::TestNotFound => StatusCode::NOT_FOUND,
+        TitleTestError::Forbidden => StatusCode::FORBIDDEN,
+        TitleTestError::DatabaseError(_) => {
+            error!("Title test error: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+
+    (status, Json(json!({ "error": e.to_string() }))).into_response()
+}
+
+// Who is looking: the signed-in user, else the front-end's visitor ID, else the client address
+fn viewer(
+    user: Option<&AuthUser>,
+    visitor_id: Option<&str>,
+    headers: &HeaderMap,
+) -> Option<TitleViewer> {
+    if let Some(user) = user {
+        return Some(TitleViewer::User(user.user_id));
+    }
+    let visitor_id = visitor_id
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_VISITOR_ID_LENGTH);
+    match visitor_id {
+        Some(visitor_id) => Some(TitleViewer::Visitor(visitor_id.to_string())),
+        None => client_address(headers).map(|address| TitleViewer::Address(address.to_string())),
+    }
+}
+
+/// Start an A/B test of a post's titles (author or admin)
+///
+/// Viewers are shown one of the titles each, always the same one, until a title has a
+/// significantly better click-through rate and becomes the post's title. Starting a test
+/// ends the one running on the post without a winner.
+#[utoipa::path(
+    put,
+    path = "/api/posts/{id}/title-test",
+    tag = "posts",
+    params(
+        ("id" = i64, Path, description = "Post ID")
+    ),
+    request_body = StartTitleTestRequest,
+    responses(
+        (status = 201, description = "Title test started", body = TitleTest),
+        (status = 400, description = "Invalid titles"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not the author or an admin"),
+        (status = 404, description = "Post not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn start_title_test(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<TitleTestService>>,
+    Path(id): Path<i64>,
+    Json(request): Json<StartTitleTestRequest>,
+) -> Response {
+    match service.start_test(id, &user, &request.titles).await {
+        Ok(test) => (StatusCode::CREATED, Json(test)).into_response(),
+        Err(e) => title_test_error_response(e),
+    }
+}
+
+/// Stop the title test of a post without changing its title (author or admin)
+#[utoipa::path(
+    delete,
+    path = "/api/posts/{id}/title-test",
+    tag = "posts",
+    params(
+        ("id" = i64, Path, description = "Post ID")
+    ),
+    responses(
+        (status = 200, description = "Title test stopped", body = TitleTest),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not the author or an admin"),
+        (status = 404, description = "Post not found or no test running"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn stop_title_test(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<TitleTestService>>,
+    Path(id): Path<i64>,
+) -> Response {
+    match service.stop_test(id, &user).await {
+        Ok(test) => (StatusCode::OK, Json(test)).into_response(),
+        Err(e) => title_test_error_response(e),
+    }
+}
+
+/// Get the titles a viewer is shown
+///
+/// Front-ends send the posts they are about to list; the response has the title to show for
+/// those with a title test running, and an impression of each is recorded. Posts not under
+/// test are left out. Anonymous viewers should send a stable `visitor_id`, or are told apart
+/// by address.
+#[utoipa::path(
+    post,
+    path = "/api/analytics/title-impressions",
+    tag = "analytics",
+    request_body = TitleImpressionsRequest,
+    responses(
+        (status = 200, description = "Titles to show", body = [AssignedTitle]),
+        (status = 400, description = "Too many posts"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn record_title_impressions(
+    Extension(user): Extension<Option<AuthUser>>,
+    headers: HeaderMap,
+    State(service): State<Arc<TitleTestService>>,
+    Json(request): Json<TitleImpressionsRequest>,
+) -> Response {
+    let viewer = viewer(user.as_ref(), request.visitor_id.as_deref(), &headers);
+    match service
+        .assign_titles(viewer.as_ref(), &request.post_ids)
+        .await
+    {
+        Ok(titles) => (StatusCode::OK, Json(titles)).into_response(),
+        Err(e) => title_test_error_response(e),
+    }
+}
+
+/// Record that a viewer opened a post from its title
+///
+/// Counted for the title the viewer was shown, when the post has a title test running.
+#[utoipa::path(
+    post,
+    path = "/api/analytics/title-clicks",
+    tag = "analytics",
+    request_body = TitleClickRequest,
+    responses(
+        (status = 204, description = "Click recorded"),
+        (status = 404, description = "Post not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn record_title_click(
+    Extension(user): Extension<Option<AuthUser>>,
+    headers: HeaderMap,
+    State(service): State<Arc<TitleTestService>>,
+    Json(request): Json<TitleClickRequest>,
+) -> Response {
+    let Some(viewer) = viewer(user.as_ref(), request.visitor_id.as_deref(), &headers) else {
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    match service.record_click(&viewer, request.post_id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => title_test_error_response(e),
+    }
+}
+
+/// Get the results of a post's title test (author, admins and analysts)
+///
+/// Impressions, clicks and click-through rate of every title of the post's latest test, the
+/// leading title and whether it is significantly better than the others. A significant
+/// leader is promoted to the post's title within fifteen minutes.
+#[utoipa::path(
+    get,
+    path = "/api/analytics/posts/{post_id}/title-test",
+    tag = "analytics",
+    params(
+        ("post_id" = i64, Path, description = "Post ID")
+    ),
+    responses(
+        (status = 200, description = "Title test results", body = TitleTestReport),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not the author, an admin or an analyst"),
+        (status = 404, description = "Post not found or never tested"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_title_test(
+    Extension(user): Extension<AuthUser>,
+    State(service): State<Arc<TitleTestService>>,
+    Path(post_id): Path<i64>,
+) -> Response {
+    match service.get_report(post_id, &user).await {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(e) => title_test_error_response(e),
+    }
+}
@@ -0,0 +1,274 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Titles a test may compare
+pub const MIN_TITLE_VARIANTS: usize = 2;
+pub const MAX_TITLE_VARIANTS: usize = 4;
+/// Longest title of a variant, as for posts
+pub const MAX_TITLE_LENGTH: usize = 255;
+
+/// Impressions every variant needs before a winner can be promoted
+pub const MIN_VARIANT_IMPRESSIONS: i64 = 200;
+/// z-score the leader must beat every other variant by: 95% confidence, two-sided
+pub const SIGNIFICANCE_Z: f64 = 1.96;
+
+/// Interaction types of title test events
+pub const TITLE_IMPRESSION: &str = "title_impression";
+pub const TITLE_CLICK: &str = "title_click";
+
+/// Where a title test stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleTestStatus {
+    /// Viewers are shown the variants
+    Running,
+    /// A variant won and became the post's title
+    Finished,
+    /// Ended by an editor without a winner
+    Stopped,
+}
+
+impl TitleTestStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TitleTestStatus::Running => "running",
+            TitleTestStatus::Finished => "finished",
+            TitleTestStatus::Stopped => "stopped",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "running" => Some(TitleTestStatus::Running),
+            "finished" => Some(TitleTestStatus::Finished),
+            "stopped" => Some(TitleTestStatus::Stopped),
+            _ => None,
+        }
+    }
+}
+
+/// Request to start testing titles of a post
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StartTitleTestRequest {
+    /// Two to four titles, shown to equal shares of viewers
+    #[schema(example = json!(["Why Rust?", "Rust in 10 minutes"]))]
+    pub titles: Vec<String>,
+}
+
+/// Who is looking at a title, for assigning them a variant. Signed-in viewers are assigned
+/// by account; anonymous ones by `visitor_id`, else by address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TitleViewer {
+    User(Uuid),
+    Visitor(String),
+    Address(String),
+}
+
+impl TitleViewer {
+    fn key(&self) -> String {
+        match self {
+            TitleViewer::User(user_id) => format!("user:{}", user_id),
+            TitleViewer::Visitor(visitor_id) => format!("visitor:{}", visitor_id),
+            TitleViewer::Address(address) => format!("ip:{}", address),
+        }
+    }
+
+    /// Variant of a test with `variants` titles the viewer sees. The same viewer always sees
+    /// the same variant of a test, on every instance.
+    pub fn variant(&self, test_id: i64, variants: usize) -> usize {
+        let digest = Sha256::new()
+            .chain_update(test_id.to_be_bytes())
+            .chain_update(self.key().as_bytes())
+            .finalize();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        (u64::from_be_bytes(bytes) % variants.max(1) as u64) as usize
+    }
+}
+
+/// Posts shown to a viewer, whose titles are under test
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TitleImpressionsRequest {
+    #[schema(example = json!([123, 124]))]
+    pub post_ids: Vec<i64>,
+    /// Stable random ID of an anonymous visitor, e.g. from a first-party cookie
+    pub visitor_id: Option<String>,
+}
+
+/// Title a viewer is shown for a post under test
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AssignedTitle {
+    #[schema(example = 123)]
+    pub post_id: i64,
+    #[schema(example = 1)]
+    pub variant: usize,
+    #[schema(example = "Rust in 10 minutes")]
+    pub title: String,
+}
+
+/// A viewer opened a post from a title under test
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TitleClickRequest {
+    #[schema(example = 123)]
+    pub post_id: i64,
+    /// The same ID as sent with the impressions
+    pub visitor_id: Option<String>,
+}
+
+/// A title test of a post
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TitleTest {
+    #[schema(example = 7)]
+    pub id: i64,
+    #[schema(example = 123)]
+    pub post_id: i64,
+    pub titles: Vec<String>,
+    pub status: TitleTestStatus,
+    /// Variant promoted to the post's title
+    pub winner: Option<usize>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+/// How one title performed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct VariantStats {
+    #[schema(example = 0)]
+    pub variant: usize,
+    #[schema(example = "Why Rust?")]
+    pub title: String,
+    #[schema(example = 1200)]
+    pub impressions: i64,
+    #[schema(example = 84)]
+    pub clicks: i64,
+    /// Clicks per impression
+    #[schema(example = 0.07)]
+    pub ctr: f64,
+}
+
+/// Results of a title test
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TitleTestReport {
+    #[serde(flatten)]
+    pub test: TitleTest,
+    pub variants: Vec<VariantStats>,
+    /// Variant with the best click-through rate so far
+    pub leader: Option<usize>,
+    /// Smallest z-score of the leader against another variant
+    #[schema(example = 2.4)]
+    pub z_score: Option<f64>,
+    /// Whether the leader beats every other variant with 95% confidence, after enough
+    /// impressions of each
+    pub significant: bool,
+}
+
+/// z-score of the difference of two click-through rates, from a two-proportion z-test
+pub fn z_score(a: &VariantStats, b: &VariantStats) -> Option<f64> {
+    if a.impressions == 0 || b.impressions == 0 {
+        return None;
+    }
+    let (n_a, n_b) = (a.impressions as f64, b.impressions as f64);
+    let pooled = (a.clicks + b.clicks) as f64 / (n_a + n_b);
+    let error = (pooled * (1.0 - pooled) * (1.0 / n_a + 1.0 / n_b)).sqrt();
+    if error == 0.0 {
+        return None;
+    }
+    Some((a.clicks as f64 / n_a - b.clicks as f64 / n_b) / error)
+}
+
+/// The best variant, how sure that is, and whether it is significant
+pub fn leader(variants: &[VariantStats]) -> (Option<usize>, Option<f64>, bool) {
+    let Some(best) = variants
+        .iter()
+        .filter(|stats| stats.impressions > 0)
+        .max_by(|a, b| a.ctr.total_cmp(&b.ctr))
+    else {
+        return (None, None, false);
+    };
+
+    let z = variants
+        .iter()
+        .filter(|stats| stats.variant != best.variant)
+        .map(|other| z_score(best, other))
+        .collect::<Option<Vec<_>>>()
+        .and_then(|scores| scores.into_iter().min_by(f64::total_cmp));
+    let significant = variants
+        .iter()
+        .all(|stats| stats.impressions >= MIN_VARIANT_IMPRESSIONS)
+        && z.is_some_and(|z| z >= SIGNIFICANCE_Z);
+
+    (Some(best.variant), z, significant)
+}
+
+/// Possible title test errors
+#[derive(Debug, thiserror::Error)]
+pub enum TitleTestError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Post not found")]
+    PostNotFound,
+
+    #[error("The post has no title test")]
+    TestNotFound,
+
+    #[error("Not allowed to access the title tests of this post")]
+    Forbidden,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(variant: usize, impressions: i64, clicks: i64) -> VariantStats {
+        VariantStats {
+            variant,
+            title: format!("Title {}", variant),
+            impressions,
+            clicks,
+            ctr: clicks as f64 / impressions.max(1) as f64,
+        }
+    }
+
+    #[test]
+    fn test_variant_assignment_is_deterministic() {
+        let viewer = TitleViewer::Visitor("abc".to_string());
+        let variant = viewer.variant(1, 2);
+        assert!(variant < 2);
+        assert_eq!(viewer.variant(1, 2), variant);
+
+        // Viewers are spread over the variants
+        let counts = (0..1000).fold([0; 2], |mut counts, i| {
+            counts[TitleViewer::Visitor(i.to_string()).variant(1, 2)] += 1;
+            counts
+        });
+        assert!(counts.iter().all(|count| *count > 400), "{:?}", counts);
+    }
+
+    #[test]
+    fn test_leader_needs_significance() {
+        let (leader_variant, z, significant) = leader(&[stats(0, 1000, 50), stats(1, 1000, 90)]);
+        assert_eq!(leader_variant, Some(1));
+        assert!(z.unwrap() > SIGNIFICANCE_Z);
+        assert!(significant);
+
+        // Too close to call
+        let (_, _, significant) = leader(&[stats(0, 1000, 50), stats(1, 1000, 55)]);
+        assert!(!significant);
+
+        // Too few impressions
+        let (_, _, significant) = leader(&[stats(0, 100, 5), stats(1, 100, 40)]);
+        assert!(!significant);
+
+        assert_eq!(
+            leader(&[stats(0, 0, 0), stats(1, 0, 0)]),
+            (None, None, false)
+        );
+    }
+}
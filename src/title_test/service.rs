@@ -0,0 +1,533 @@
+use crate::analytics::service::AnalyticsService;
+use crate::auth::jwt::Role;
+use crate::auth::middleware::AuthUser;
+use crate::cache::redis::RedisCache;
+use crate::db::instrument::timed;
+use crate::jobs::model::{Job, JobError};
+use crate::jobs::service::{JobHandler, JobService};
+use crate::tenant::middleware::{current_blog_id, with_blog_id};
+use crate::title_test::model::{
+    leader, AssignedTitle, TitleTest, TitleTestError, TitleTestReport, TitleTestStatus,
+    TitleViewer, VariantStats, MAX_TITLE_LENGTH, MAX_TITLE_VARIANTS, MIN_TITLE_VARIANTS,
+    TITLE_CLICK, TITLE_IMPRESSION,
+};
+use futures::future::{join_all, BoxFuture};
+use serde_json::{json, Value};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Kind of the job that promotes the winners of running title tests
+pub const TITLE_TEST_JOB: &str = "title_tests";
+
+// How often running tests are checked for a winner, and how often instances check whether a
+// run is due
+const TITLE_TEST_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const TITLE_TEST_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Posts a viewer can be assigned titles of at once, e.g. a page of a listing
+const MAX_IMPRESSION_POSTS: usize = 100;
+
+// Columns of a test, from the tests table aliased as `t`
+const TITLE_TEST_COLUMNS: &str =
+    "t.id, t.post_id, t.titles, t.status, t.winner, t.started_at, t.ended_at";
+
+fn title_test_from_row(row: &PgRow) -> TitleTest {
+    let status: String = row.get("status");
+    TitleTest {
+        id: row.get("id"),
+        post_id: row.get("post_id"),
+        titles: row.get("titles"),
+        status: TitleTestStatus::parse(&status).unwrap_or(TitleTestStatus::Stopped),
+        winner: row
+            .get::<Option<i16>, _>("winner")
+            .map(|winner| winner as usize),
+        started_at: row.get("started_at"),
+        ended_at: row.get("ended_at"),
+    }
+}
+
+// Trimmed titles of a new test
+fn validate_titles(titles: &[String]) -> Result<Vec<String>, TitleTestError> {
+    if !(MIN_TITLE_VARIANTS..=MAX_TITLE_VARIANTS).contains(&titles.len()) {
+        return Err(TitleTestError::ValidationError(format!(
+            "A title test needs {} to {} titles",
+            MIN_TITLE_VARIANTS, MAX_TITLE_VARIANTS
+        )));
+    }
+
+    let mut seen = HashSet::new();
+    let mut trimmed = Vec::with_capacity(titles.len());
+    for title in titles {
+        let title = title.trim();
+        if title.is_empty() || title.chars().count() > MAX_TITLE_LENGTH {
+            return Err(TitleTestError::ValidationError(format!(
+                "Titles must be 1 to {} characters",
+                MAX_TITLE_LENGTH
+            )));
+        }
+        if !seen.insert(title.to_lowercase()) {
+            return Err(TitleTestError::ValidationError(
+                "Titles must be different".to_string(),
+            ));
+        }
+        trimmed.push(title.to_string());
+    }
+    Ok(trimmed)
+}
+
+/// A/B tests of post titles.
+///
+/// While a test runs, front-ends ask which title each viewer sees. Viewers are assigned a
+/// variant by a hash of who they are, so they keep seeing the same title, and every title
+/// shown and every post opened from one is recorded as a `title_impression` or `title_click`
+/// interaction. A background job queued every fifteen minutes promotes the title with the
+/// best click-through rate to the post's title once it is significantly better than the
+/// others, which ends the test. Impressions and clicks are purged with other interactions by
+/// the retention policy, so tests should end well within it.
+pub struct TitleTestService {
+    pool: PgPool,
+    redis_cache: Option<RedisCache>,
+    analytics: Arc<AnalyticsService>,
+}
+
+impl TitleTestService {
+    pub fn new(
+        pool: PgPool,
+        redis_cache: Option<RedisCache>,
+        analytics: Arc<AnalyticsService>,
+    ) -> Self {
+        Self {
+            pool,
+            redis_cache,
+            analytics,
+        }
+    }
+
+    /// Queue a check for winners every fifteen minutes
+    pub fn start_scheduler(self: Arc<Self>, jobs: Arc<JobService>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TITLE_TEST_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = jobs
+                    .enqueue_unless_recent(TITLE_TEST_JOB, json!({}), TITLE_TEST_INTERVAL)
+                    .await
+                {
+                    error!("Failed to schedule title test promotion: {}", e);
+                }
+            }
+        });
+    }
+
+    // Author of a post of the blog
+    async fn post_author(&self, post_id: i64) -> Result<Uuid, TitleTestError> {
+        sqlx::query_scalar(
+            "SELECT user_id FROM global.posts WHERE id = $1 AND blog_id = $2 AND is_deleted = false",
+        )
+        .bind(post_id)
+        .bind(current_blog_id())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(TitleTestError::PostNotFound)
+    }
+
+    async fn check_editor(&self, post_id: i64, user: &AuthUser) -> Result<(), TitleTestError> {
+        let author_id = self.post_author(post_id).await?;
+        if author_id != user.user_id && user.role != Role::Admin {
+            return Err(TitleTestError::Forbidden);
+        }
+        Ok(())
+    }
+
+    /// Start testing titles of a post, ending any test running on it without a winner
+    pub async fn start_test(
+        &self,
+        post_id: i64,
+        user: &AuthUser,
+        titles: &[String],
+    ) -> Result<TitleTest, TitleTestError> {
+        let titles = validate_titles(titles)?;
+        self.check_editor(post_id, user).await?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "UPDATE global.post_title_tests SET status = 'stopped', ended_at = NOW() \
+             WHERE post_id = $1 AND status = 'running'",
+        )
+        .bind(post_id)
+        .execute(&mut *tx)
+        .await?;
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO global.post_title_tests AS t (post_id, titles, created_by)
+            VALUES ($1, $2, $3)
+            RETURNING {}
+            "#,
+            TITLE_TEST_COLUMNS
+        ))
+        .bind(post_id)
+        .bind(&titles)
+        .bind(user.user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let test = title_test_from_row(&row);
+        info!(
+            "User {} started title test {} of post {}",
+            user.user_id, test.id, post_id
+        );
+        Ok(test)
+    }
+
+    /// End the test running on a post without changing its title
+    pub async fn stop_test(
+        &self,
+        post_id: i64,
+        user: &AuthUser,
+    ) -> Result<TitleTest, TitleTestError> {
+        self.check_editor(post_id, user).await?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE global.post_title_tests t SET status = 'stopped', ended_at = NOW()
+            WHERE t.post_id = $1 AND t.status = 'running'
+            RETURNING {}
+            "#,
+            TITLE_TEST_COLUMNS
+        ))
+        .bind(post_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(TitleTestError::TestNotFound)?;
+
+        Ok(title_test_from_row(&row))
+    }
+
+    // Running tests of the given posts of the blog, by post
+    async fn running_tests(&self, post_ids: &[i64]) -> Result<Vec<TitleTest>, TitleTestError> {
+        let sql = format!(
+            r#"
+            SELECT {}
+            FROM global.post_title_tests t
+            JOIN global.posts p ON p.id = t.post_id
+            WHERE t.post_id = ANY($1) AND t.status = 'running'
+                AND p.blog_id = $2 AND p.is_deleted = false
+            "#,
+            TITLE_TEST_COLUMNS
+        );
+        let query = sqlx::query(&sql)
+            .bind(post_ids)
+            .bind(current_blog_id())
+            .fetch_all(&self.pool);
+        let rows = timed("title_tests.running", query).await?;
+
+        Ok(rows.iter().map(title_test_from_row).collect())
+    }
+
+    async fn record(
+        &self,
+        user_id: Option<Uuid>,
+        interaction_type: &str,
+        test: &TitleTest,
+        variant: usize,
+    ) {
+        let metadata = json!({ "test_id": test.id, "variant": variant });
+        if let Err(e) = self
+            .analytics
+            .record_interaction(
+                user_id,
+                interaction_type,
+                Some(test.post_id),
+                None,
+                Some(metadata),
+            )
+            .await
+        {
+            warn!(
+                "Failed to record {} of title test {}: {:?}",
+                interaction_type, test.id, e
+            );
+        }
+    }
+
+    /// Titles the viewer is shown of those posts that have a test running, recording an
+    /// impression of each. Viewers who can't be told apart see the first title and are not
+    /// recorded.
+    pub async fn assign_titles(
+        &self,
+        viewer: Option<&TitleViewer>,
+        post_ids: &[i64],
+    ) -> Result<Vec<AssignedTitle>, TitleTestError> {
+        if post_ids.len() > MAX_IMPRESSION_POSTS {
+            return Err(TitleTestError::ValidationError(format!(
+                "At most {} posts at once",
+                MAX_IMPRESSION_POSTS
+            )));
+        }
+        let tests = self.running_tests(post_ids).await?;
+
+        let user_id = match viewer {
+            Some(TitleViewer::User(user_id)) => Some(*user_id),
+            _ => None,
+        };
+        let assigned: Vec<(TitleTest, usize)> = tests
+            .into_iter()
+            .map(|test| {
+                let variant = viewer.map_or(0, |viewer| viewer.variant(test.id, test.titles.len()));
+                (test, variant)
+            })
+            .collect();
+        if viewer.is_some() {
+            join_all(
+                assigned
+                    .iter()
+                    .map(|(test, variant)| self.record(user_id, TITLE_IMPRESSION, test, *variant)),
+            )
+            .await;
+        }
+
+        Ok(assigned
+            .into_iter()
+            .map(|(test, variant)| AssignedTitle {
+                post_id: test.post_id,
+                variant,
+                title: test.titles[variant].clone(),
+            })
+            .collect())
+    }
+
+    /// Record that the viewer opened a post from its title. Posts without a running test are
+    /// ignored.
+    pub async fn record_click(
+        &self,
+        viewer: &TitleViewer,
+        post_id: i64,
+    ) -> Result<(), TitleTestError> {
+        self.post_author(post_id).await?;
+        let Some(test) = self.running_tests(&[post_id]).await?.into_iter().next() else {
+            return Ok(());
+        };
+
+        let user_id = match viewer {
+            TitleViewer::User(user_id) => Some(*user_id),
+            _ => None,
+        };
+        let variant = viewer.variant(test.id, test.titles.len());
+        self.record(user_id, TITLE_CLICK, &test, variant).await;
+        Ok(())
+    }
+
+    // Impressions and clicks of every variant of a test
+    async fn variant_stats(&self, test: &TitleTest) -> Result<Vec<VariantStats>, TitleTestError> {
+        let query = sqlx::query(
+            r#"
+            SELECT (metadata->>'variant')::INT AS variant,
+                COUNT(*) FILTER (WHERE interaction_type = $3) AS impressions,
+                COUNT(*) FILTER (WHERE interaction_type = $4) AS clicks
+            FROM global.user_interactions
+            WHERE post_id = $1 AND interaction_type IN ($3, $4) AND created_at >= $5
+                AND metadata->>'test_id' = $2
+            GROUP BY 1
+            "#,
+        )
+        .bind(test.post_id)
+        .bind(test.id.to_string())
+        .bind(TITLE_IMPRESSION)
+        .bind(TITLE_CLICK)
+        .bind(test.started_at)
+        .fetch_all(&self.pool);
+        let rows = timed("title_tests.stats", query).await?;
+
+        let counts: HashMap<i32, (i64, i64)> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get::<Option<i32>, _>("variant").unwrap_or(-1),
+                    (row.get("impressions"), row.get("clicks")),
+                )
+            })
+            .collect();
+
+        Ok(test
+            .titles
+            .iter()
+            .enumerate()
+            .map(|(variant, title)| {
+                let (impressions, clicks) =
+                    counts.get(&(variant as i32)).copied().unwrap_or((0, 0));
+                VariantStats {
+                    variant,
+                    title: title.clone(),
+                    impressions,
+                    clicks,
+                    ctr: if impressions > 0 {
+                        clicks as f64 / impressions as f64
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect())
+    }
+
+    async fn report(&self, test: TitleTest) -> Result<TitleTestReport, TitleTestError> {
+        let variants = self.variant_stats(&test).await?;
+        let (leader, z_score, significant) = leader(&variants);
+        Ok(TitleTestReport {
+            test,
+            variants,
+            leader,
+            z_score,
+            significant,
+        })
+    }
+
+    /// Results of the latest title test of a post, for its author, admins and analysts
+    pub async fn get_report(
+        &self,
+        post_id: i64,
+        user: &AuthUser,
+    ) -> Result<TitleTestReport, TitleTestError> {
+        let author_id = self.post_author(post_id).await?;
+        if author_id != user.user_id && user.role != Role::Admin && user.role != Role::Analyst {
+            return Err(TitleTestError::Forbidden);
+        }
+
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT {}
+            FROM global.post_title_tests t
+            WHERE t.post_id = $1
+            ORDER BY t.started_at DESC, t.id DESC
+            LIMIT 1
+            "#,
+            TITLE_TEST_COLUMNS
+        ))
+        .bind(post_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(TitleTestError::TestNotFound)?;
+
+        self.report(title_test_from_row(&row)).await
+    }
+
+    /// Make the winner of every running test whose leader is significant the title of its
+    /// post. Returns how many were promoted.
+    pub async fn promote_winners(&self) -> Result<usize, JobError> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT p.blog_id, p.slug, {}
+            FROM global.post_title_tests t
+            JOIN global.posts p ON p.id = t.post_id
+            WHERE t.status = 'running'
+            "#,
+            TITLE_TEST_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut promoted = 0;
+        for row in rows {
+            let blog_id: i64 = row.get("blog_id");
+            let slug: String = row.get("slug");
+            let test = title_test_from_row(&row);
+            let test_id = test.id;
+            let outcome = with_blog_id(blog_id, self.promote_if_significant(test, &slug)).await;
+            match outcome {
+                Ok(true) => promoted += 1,
+                Ok(false) => {}
+                Err(e) => error!("Failed to check title test {}: {}", test_id, e),
+            }
+        }
+
+        info!("Promoted the winners of {} title tests", promoted);
+        Ok(promoted)
+    }
+
+    async fn promote_if_significant(
+        &self,
+        test: TitleTest,
+        slug: &str,
+    ) -> Result<bool, TitleTestError> {
+        let report = self.report(test).await?;
+        let Some(winner) = report.leader.filter(|_| report.significant) else {
+            return Ok(false);
+        };
+        let title = &report.test.titles[winner];
+
+        let mut tx = self.pool.begin().await?;
+        let ended = sqlx::query(
+            "UPDATE global.post_title_tests SET status = 'finished', winner = $2, ended_at = NOW() \
+             WHERE id = $1 AND status = 'running'",
+        )
+        .bind(report.test.id)
+        .bind(winner as i16)
+        .execute(&mut *tx)
+        .await?;
+        // Stopped or restarted meanwhile
+        if ended.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query("UPDATE global.posts SET title = $2, updated_at = NOW() WHERE id = $1")
+            .bind(report.test.post_id)
+            .bind(title)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        if let Some(cache) = &self.redis_cache {
+            if let Err(e) = cache.invalidate_post(report.test.post_id, slug).await {
+                warn!(
+                    "Failed to invalidate post {} after its title test: {}",
+                    report.test.post_id, e
+                );
+            }
+            let _ = cache.invalidate_popular_posts().await;
+            let _ = cache.invalidate_post_archive().await;
+        }
+
+        info!(
+            "Title test {} of post {} promoted variant {}: {}",
+            report.test.id, report.test.post_id, winner, title
+        );
+        Ok(true)
+    }
+
+    async fn run_job(&self, _job: &Job) -> Result<Value, JobError> {
+        let promoted = self.promote_winners().await?;
+        Ok(json!({ "promoted": promoted }))
+    }
+}
+
+impl JobHandler for TitleTestService {
+    fn kind(&self) -> &'static str {
+        TITLE_TEST_JOB
+    }
+
+    fn run<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<Value, JobError>> {
+        Box::pin(self.run_job(job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_titles() {
+        let titles =
+            validate_titles(&[" Why Rust? ".to_string(), "Rust in 10 minutes".to_string()])
+                .unwrap();
+        assert_eq!(titles, vec!["Why Rust?", "Rust in 10 minutes"]);
+
+        assert!(validate_titles(&["Only one".to_string()]).is_err());
+        assert!(validate_titles(&vec!["A title".to_string(); 5]).is_err());
+        assert!(validate_titles(&["Same".to_string(), "same ".to_string()]).is_err());
+        assert!(validate_titles(&["A title".to_string(), "  ".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::IntoResponse,
+    Extension,
+};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::{debug, info};
+
+use crate::analytics::live::{LiveDashboard, LiveSnapshot};
+use crate::auth::jwt::{validate_token, Role};
+use crate::auth::token_version::TokenVersions;
+use crate::tenant::middleware::current_blog_id;
+use crate::websocket::notifications::{handle_invalid_socket, revocation_error};
+use uuid::Uuid;
+
+/// Query parameters for live dashboard connections
+#[derive(Debug, Deserialize)]
+pub struct LiveDashboardParams {
+    token: Option<String>,
+}
+
+/// The counters of a blog as sent to dashboards
+fn live_stats_message(snapshot: &LiveSnapshot, blog_id: i64) -> String {
+    let stats = snapshot.blogs.get(&blog_id).cloned().unwrap_or_default();
+    serde_json::json!({
+        "type": "live_stats",
+        "views_per_minute": stats.views_per_minute,
+        "active_readers": stats.active_readers,
+        "top_posts": stats.top_posts,
+        "timestamp": snapshot.generated_at,
+    })
+    .to_string()
+}
+
+/// Stream the live counters of a blog to an admin or analyst (`GET /api/v1/analytics/live/ws`)
+pub async fn live_dashboard_ws(
+    ws: WebSocketUpgrade,
+    Query(params): Query<LiveDashboardParams>,
+    State(dashboard): State<Arc<LiveDashboard>>,
+    token_versions: Option<Extension<Arc<TokenVersions>>>,
+) -> impl IntoResponse {
+    let token = params.token.unwrap_or_default();
+
+    let error_message = match validate_token(&token) {
+        Ok(claims) if claims.role != Role::Admin && claims.role != Role::Analyst => {
+            Some("Only admins and analysts can view the live dashboard".to_string())
+        }
+        Ok(_) if !dashboard.is_enabled() => {
+            Some("The live dashboard is not available without Redis".to_string())
+        }
+        // Tokens revoked by a password change are refused like invalid ones
+        Ok(claims) => match (Uuid::parse_str(&claims.sub), token_versions) {
+            (Err(e), _) => Some(format!("Invalid user ID in token: {}", e)),
+            (Ok(user_id), Some(Extension(token_versions))) => {
+                revocation_error(&token_versions, user_id, claims.ver).await
+            }
+            (Ok(_), None) => None,
+        },
+        Err(e) => Some(format!("Invalid token: {}", e)),
+    };
+
+    // The blog is only known while handling the request, not in the upgraded connection
+    let blog_id = current_blog_id();
+    ws.on_upgrade(move |socket| async move {
+        match error_message {
+            Some(error_message) => handle_invalid_socket(socket, error_message).await,
+            None => stream_live_stats(socket, blog_id, dashboard.subscribe()).await,
+        }
+    })
+}
+
+/// Send the counters of a blog whenever they are updated, until the dashboard disconnects
+async fn stream_live_stats(
+    socket: WebSocket,
+    blog_id: i64,
+    mut snapshots: watch::Receiver<Arc<LiveSnapshot>>,
+) {
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    info!("Live dashboard connected for blog {}", blog_id);
+
+    let send_task = tokio::spawn(async move {
+        loop {
+            let message = live_stats_message(&snapshots.borrow_and_update(), blog_id);
+            if ws_sender.send(Message::Text(message)).await.is_err() {
+                break;
+            }
+            if snapshots.changed().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(result) = ws_receiver.next().await {
+        match result {
+            Ok(Message::Close(_)) | Err(_) => break,
+            _ => debug!("Ignoring message from live dashboard"),
+        }
+    }
+
+    send_task.abort();
+    info!("Live dashboard disconnected for blog {}", blog_id);
+}
@@ -1 +1,2 @@
+pub mod live_dashboard;
 pub mod notifications;
@@ -4,6 +4,7 @@ use axum::{
         Query, State,
     },
     response::IntoResponse,
+    Extension,
 };
 use futures::{SinkExt, StreamExt};
 use redis::AsyncCommands;
@@ -17,7 +18,8 @@ use tokio::{sync::mpsc, time};
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
-use crate::notification::model::NotificationPayload;
+use crate::auth::token_version::TokenVersions;
+use crate::notification::model::Notification as StoredNotification;
 use crate::{auth::jwt::validate_token, cache::redis::RedisCache};
 
 /// Query parameters for WebSocket connections
@@ -48,7 +50,7 @@ pub struct NotificationState {
 }
 
 /// Handle an invalid socket connection (authentication failure)
-async fn handle_invalid_socket(mut socket: WebSocket, error_message: String) {
+pub(crate) async fn handle_invalid_socket(mut socket: WebSocket, error_message: String) {
     // Send error message to client
     if let Err(e) = socket
         .send(Message::Text(format!(
@@ -139,18 +141,36 @@ async fn handle_valid_connection(
     info!("WebSocket connection closed for user: {}", user_id);
 }
 
+/// Why a valid token may still not open a connection: it was revoked by a password change, or
+/// its version could not be checked
+pub(crate) async fn revocation_error(
+    token_versions: &TokenVersions,
+    user_id: Uuid,
+    version: i32,
+) -> Option<String> {
+    match token_versions.is_current(user_id, version).await {
+        Ok(true) => None,
+        Ok(false) => Some("Token has been revoked. Please login again".to_string()),
+        Err(e) => {
+            error!("Token version lookup failed: {}", e);
+            Some("Failed to verify token".to_string())
+        }
+    }
+}
+
 /// Handle incoming WebSocket connection
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     Query(params): Query<WebSocketParams>,
     State(state): State<Arc<NotificationState>>,
+    token_versions: Option<Extension<Arc<TokenVersions>>>,
 ) -> impl IntoResponse {
     let token = params.token.unwrap_or_default();
 
     // Validate token and extract the user ID
-    let user_id = match validate_token(&token) {
+    let (user_id, version) = match validate_token(&token) {
         Ok(claims) => match Uuid::parse_str(&claims.sub) {
-            Ok(uuid) => uuid,
+            Ok(uuid) => (uuid, claims.ver),
             Err(e) => {
                 let error_message = format!("Invalid user ID in token: {}", e);
                 return ws.on_upgrade(move |socket| async move {
@@ -166,6 +186,16 @@ pub async fn ws_handler(
         }
     };
 
+    // Tokens revoked by a password change are refused like invalid ones. Routers without
+    // token versions skip this.
+    if let Some(Extension(token_versions)) = token_versions {
+        if let Some(error_message) = revocation_error(&token_versions, user_id, version).await {
+            return ws.on_upgrade(move |socket| async move {
+                handle_invalid_socket(socket, error_message).await;
+            });
+        }
+    }
+
     // Valid connection, upgrade and handle
     info!("User {} connected to notifications WebSocket", user_id);
     ws.on_upgrade(move |socket| async move {
@@ -173,13 +203,18 @@ pub async fn ws_handler(
     })
 }
 
+/// Redis PubSub channel carrying a user's notifications to their WebSocket connections
+pub fn user_channel(user_id: &Uuid) -> String {
+    format!("notifications:user:{}", user_id)
+}
+
 /// Subscribe to Redis PubSub channel for user notifications
 async fn subscribe_to_user_notifications(
     user_id: Uuid,
     redis_cache: Arc<RedisCache>,
     tx: mpsc::Sender<Message>,
 ) {
-    let channel_name = format!("notifications:user:{}", user_id);
+    let channel_name = user_channel(&user_id);
     info!("Subscribing to Redis channel: {}", channel_name);
 
     // Get a Redis PubSub connection using client::get_async_pubsub
@@ -215,31 +250,63 @@ async fn subscribe_to_user_notifications(
     }
 }
 
-/// Publish a notification to a user
+/// Publish a stored notification to a user
 pub async fn publish_notification(
     redis_cache: &RedisCache,
     user_id: &Uuid,
-    notification: NotificationPayload,
+    notification: &StoredNotification,
 ) -> Result<(), String> {
-    let json = serde_json::to_string(&notification).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(notification).map_err(|e| e.to_string())?;
 
     // In a real implementation, we'd publish to a Redis channel for WebSocket distribution
     // For this stub implementation, just log it
     info!("Publishing notification to user {}: {}", user_id, json);
 
     // Try to publish to Redis stream if available
-    if let Ok(mut conn) = redis_cache
-        .get_client()
-        .get_multiplexed_async_connection()
-        .await
-    {
-        let channel_name = format!("notifications:{}", user_id);
+    if let Ok(mut conn) = redis_cache.connection().await {
+        let channel_name = user_channel(user_id);
         let _: Result<(), redis::RedisError> = conn.publish(&channel_name, &json).await;
     }
 
     Ok(())
 }
 
+/// Push a user's new unread notification count to their open connections, as
+/// `{"type": "unread_count", "unread_count": 3}`
+pub async fn publish_unread_count(
+    redis_cache: &RedisCache,
+    user_id: &Uuid,
+    unread_count: i64,
+) -> Result<(), String> {
+    let json = serde_json::json!({ "type": "unread_count", "unread_count": unread_count });
+
+    let mut conn = redis_cache.connection().await.map_err(|e| e.to_string())?;
+    conn.publish::<_, _, ()>(user_channel(user_id), json.to_string())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tell a user's open connections that their quiet hours ended, as
+/// `{"type": "quiet_hours_summary", "held_count": 4, "unread_count": 6}`, where `held_count`
+/// is the number of notifications that arrived without being pushed
+pub async fn publish_quiet_hours_summary(
+    redis_cache: &RedisCache,
+    user_id: &Uuid,
+    held_count: i32,
+    unread_count: i64,
+) -> Result<(), String> {
+    let json = serde_json::json!({
+        "type": "quiet_hours_summary",
+        "held_count": held_count,
+        "unread_count": unread_count,
+    });
+
+    let mut conn = redis_cache.connection().await.map_err(|e| e.to_string())?;
+    conn.publish::<_, _, ()>(user_channel(user_id), json.to_string())
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -294,7 +361,7 @@ mod tests {
     async fn test_notification_channel_format() {
         // Test that the notification channel format is correct
         let user_id = Uuid::parse_str("123e4567-e89b-12d3-a456-426614174000").unwrap();
-        let channel_name = format!("notifications:user:{}", user_id);
+        let channel_name = user_channel(&user_id);
         assert_eq!(
             channel_name,
             "notifications:user:123e4567-e89b-12d3-a456-426614174000"
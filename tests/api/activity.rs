@@ -0,0 +1,69 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+
+// Activity types of a feed, newest first
+fn activity_types(feed: &Value) -> Vec<&str> {
+    feed.as_array()
+        .expect("activity feed")
+        .iter()
+        .map(|item| item["activity_type"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_activity_feed_respects_privacy_settings() {
+    let app = TestApp::spawn().await;
+    let user = app.register("user").await;
+    let other = app.register("user").await;
+    let post_id = app.create_post(&other, "Someone else's post").await;
+    app.create_post(&user, "My post").await;
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/comments", post_id),
+            Some(&user),
+            json!({ "content": "Nice one", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/like", post_id),
+            Some(&user),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let feed = format!("/api/v1/users/{}/activity", user.username);
+    let response = app.get(&feed, None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(
+        activity_types(&response.body),
+        vec!["like", "comment", "post_published"]
+    );
+    assert_eq!(response.body[1]["excerpt"], "Nice one");
+    assert_eq!(response.body[1]["post_id"], post_id);
+
+    let response = app
+        .put(
+            "/api/v1/activity/privacy",
+            Some(&user),
+            json!({ "hide_posts": false, "hide_comments": true, "hide_likes": true }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // Others only see what is not hidden, the user still sees everything
+    let response = app.get(&feed, Some(&other)).await;
+    assert_eq!(activity_types(&response.body), vec!["post_published"]);
+    let response = app.get(&feed, Some(&user)).await;
+    assert_eq!(activity_types(&response.body).len(), 3);
+
+    let response = app
+        .get("/api/v1/users/nobody-by-this-name/activity", None)
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,49 @@
+use super::TestApp;
+use axum::http::{header, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_public_stats_count_published_content() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Counted post").await;
+    app.create_post(&author, "Another counted post").await;
+
+    // Drafts are not counted
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&reader),
+            json!({
+                "title": "Unfinished",
+                "slug": "unfinished-draft",
+                "content": "Not yet",
+                "tags": ["testing"],
+                "is_draft": true,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/comments", post_id),
+            Some(&reader),
+            json!({ "content": "Counted comment", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let response = app.get("/api/v1/stats/public", None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["post_count"], 2);
+    assert_eq!(response.body["author_count"], 1);
+    assert_eq!(response.body["total_comments"], 1);
+    assert!(response.body["total_views"].as_i64().unwrap() >= 0);
+
+    // The widget is embedded on other sites, so shared caches may keep it
+    let cache_control = response.headers[header::CACHE_CONTROL].to_str().unwrap();
+    assert!(cache_control.contains("public"), "{}", cache_control);
+}
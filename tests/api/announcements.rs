@@ -0,0 +1,97 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_announcement_reaches_its_role() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let reader = app.register("user").await;
+    let author = app.register("author").await;
+
+    let response = app
+        .post(
+            "/api/v1/admin/announcements",
+            Some(&reader),
+            json!({ "content": "Maintenance tonight" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = app
+        .post(
+            "/api/v1/admin/announcements",
+            Some(&admin),
+            json!({ "content": "Maintenance tonight", "role": "superuser" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = app
+        .post(
+            "/api/v1/admin/announcements",
+            Some(&admin),
+            json!({ "content": "Maintenance tonight", "role": "user" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.body);
+    let announcement = format!(
+        "/api/v1/admin/announcements/{}",
+        response.body["id"].as_i64().unwrap()
+    );
+
+    let mut status = String::new();
+    for _ in 0..50 {
+        let response = app.get(&announcement, Some(&admin)).await;
+        status = response.body["status"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if status == "sent" {
+            assert_eq!(response.body["recipients"], 1);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    assert_eq!(status, "sent");
+
+    let response = app.get("/api/v1/notifications", Some(&reader)).await;
+    assert_eq!(response.body[0]["notification_type"], "SystemMessage");
+    assert_eq!(response.body[0]["content"], "Maintenance tonight");
+    let response = app.get("/api/v1/notifications", Some(&author)).await;
+    assert!(!response.body.to_string().contains("Maintenance tonight"));
+
+    // Sent announcements cannot be cancelled
+    let response = app.delete(&announcement, Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_scheduled_announcement_can_be_cancelled() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+
+    let response = app
+        .post(
+            "/api/v1/admin/announcements",
+            Some(&admin),
+            json!({ "content": "Happy new year", "scheduled_for": "2099-01-01T00:00:00Z" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.body);
+    assert_eq!(response.body["status"], "scheduled");
+    let announcement = format!(
+        "/api/v1/admin/announcements/{}",
+        response.body["id"].as_i64().unwrap()
+    );
+
+    let response = app.get("/api/v1/admin/announcements", Some(&admin)).await;
+    assert_eq!(response.body[0]["content"], "Happy new year");
+
+    let response = app.delete(&announcement, Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = app.get(&announcement, Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
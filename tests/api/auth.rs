@@ -0,0 +1,148 @@
+use super::{TestApp, TEST_PASSWORD};
+use axum::http::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_register_and_login() {
+    let app = TestApp::spawn().await;
+    let user = app.register("user").await;
+
+    let response = app
+        .post(
+            "/api/v1/auth/login",
+            None,
+            json!({ "email": user.email, "password": TEST_PASSWORD }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["user_id"], user.id.to_string());
+    assert!(response.body["token"].is_string());
+
+    let response = app
+        .post(
+            "/api/v1/auth/login",
+            None,
+            json!({ "email": user.email, "password": "wrong password" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_register_rejects_duplicate_email() {
+    let app = TestApp::spawn().await;
+    let user = app.register("user").await;
+
+    let response = app
+        .post(
+            "/api/v1/auth/register",
+            None,
+            json!({ "username": "other", "email": user.email, "password": TEST_PASSWORD }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_protected_routes_need_a_valid_token() {
+    let app = TestApp::spawn().await;
+    let user = app.register("user").await;
+
+    let response = app.get("/api/v1/posts/mine", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let mut forged = user.clone();
+    forged.token.push('x');
+    let response = app.get("/api/v1/posts/mine", Some(&forged)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.get("/api/v1/posts/mine", Some(&user)).await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_password_change_revokes_other_tokens() {
+    let app = TestApp::spawn().await;
+    let user = app.register("user").await;
+    let post_id = app.create_post(&user, "Public post").await;
+    let response = app
+        .post(
+            "/api/v1/auth/login",
+            None,
+            json!({ "email": user.email, "password": TEST_PASSWORD }),
+        )
+        .await;
+    let mut other_session = user.clone();
+    other_session.token = response.body["token"].as_str().unwrap().to_string();
+
+    let response = app
+        .put(
+            "/api/v1/users/me/password",
+            Some(&user),
+            json!({ "current_password": "wrong password", "new_password": "new password" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app
+        .put(
+            "/api/v1/users/me/password",
+            Some(&user),
+            json!({ "current_password": TEST_PASSWORD, "new_password": "new password" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let mut current_session = user.clone();
+    current_session.token = response.body["token"].as_str().unwrap().to_string();
+
+    for revoked in [&user, &other_session] {
+        let response = app.get("/api/v1/posts/mine", Some(revoked)).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+        // Public routes treat a revoked token as no token
+        let response = app
+            .get(&format!("/api/v1/posts/view/{}", post_id), Some(revoked))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert!(response.body.get("liked_by_me").is_none());
+    }
+    let response = app.get("/api/v1/posts/mine", Some(&current_session)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = app
+        .get("/api/v1/notifications", Some(&current_session))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.to_string().contains("SecurityAlert"));
+
+    let response = app
+        .post(
+            "/api/v1/auth/login",
+            None,
+            json!({ "email": user.email, "password": "new password" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_public_profile_shows_reputation() {
+    let app = TestApp::spawn().await;
+    let user = app.register("user").await;
+
+    let response = app
+        .get(&format!("/api/v1/users/{}", user.username), None)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["id"], user.id.to_string());
+    assert_eq!(response.body["reputation"]["points"], 0);
+    assert!(response.body.get("email").is_none());
+
+    let response = app.get("/api/v1/users/nobody-by-this-name", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
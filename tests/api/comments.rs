@@ -0,0 +1,828 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_comment_and_reply() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Commented post").await;
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+
+    let response = app
+        .post(
+            &uri,
+            Some(&reader),
+            json!({ "content": "Great *post*", "markdown_enabled": true }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let comment_id = response.body["id"].as_i64().unwrap();
+
+    let response = app
+        .post(
+            &uri,
+            Some(&author),
+            json!({
+                "content": "Thanks!",
+                "parent_comment_id": comment_id,
+                "markdown_enabled": false,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["total_count"], 2);
+    assert!(response.body.to_string().contains("<em>post</em>"));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_replies_must_be_on_the_parent_post() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Commented post").await;
+    let other_post_id = app.create_post(&author, "Other post").await;
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/comments", post_id),
+            Some(&reader),
+            json!({ "content": "First!", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let comment_id = response.body["id"].as_i64().unwrap();
+
+    let uri = format!("/api/v1/posts/{}/comments", other_post_id);
+    let response = app
+        .post(
+            &uri,
+            Some(&author),
+            json!({
+                "content": "Wrong thread",
+                "parent_comment_id": comment_id,
+                "markdown_enabled": false,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND, "{}", response.body);
+
+    let response = app
+        .put(
+            "/api/v1/admin/feature-flags/anonymous_comments",
+            Some(&admin),
+            json!({ "enabled": true, "rollout_percentage": 100 }),
+        )
+        .await;
+    assert!(response.status.is_success(), "{}", response.body);
+
+    let response = app
+        .post(
+            &format!("{}/guest", uri),
+            None,
+            json!({
+                "name": "Visitor",
+                "email": "visitor@example.com",
+                "content": "Wrong thread",
+                "parent_comment_id": comment_id,
+                "captcha_token": "test-token",
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND, "{}", response.body);
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM global.comments")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_commenting_needs_authentication() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let post_id = app.create_post(&author, "Quiet post").await;
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/comments", post_id),
+            None,
+            json!({ "content": "Anonymous", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_locked_posts_reject_comments() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Locked post").await;
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/comments/lock", post_id),
+            Some(&author),
+            json!({}),
+        )
+        .await;
+    assert!(response.status.is_success(), "{}", response.body);
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/comments", post_id),
+            Some(&reader),
+            json!({ "content": "Too late", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN, "{}", response.body);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_comment_rate_limit() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Busy post").await;
+    let comment = json!({ "content": "First!", "markdown_enabled": false });
+
+    // Comments that fail do not use up the window
+    let response = app
+        .post(
+            "/api/v1/posts/999999/comments",
+            Some(&reader),
+            comment.clone(),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+    let response = app.post(&uri, Some(&reader), comment.clone()).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let response = app.post(&uri, Some(&reader), comment.clone()).await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.body["code"], "RATE_LIMITED");
+    assert_eq!(response.body["rate_limit"]["limit"], 1);
+    assert_eq!(response.body["rate_limit"]["remaining"], 0);
+
+    // The limit is per user
+    let response = app.post(&uri, Some(&author), comment).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_reported_comments_are_collapsed() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Heated post").await;
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+
+    let response = app
+        .post(
+            &uri,
+            Some(&reader),
+            json!({ "content": "Flame bait", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let comment_id = response.body["id"].as_i64().unwrap();
+
+    let response = app
+        .post(
+            &format!("/api/v1/comments/{}/reactions", comment_id),
+            Some(&author),
+            json!({ "reaction": ":rocket:" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["score"], 1);
+
+    // Each report costs three points, and the comment collapses at -5
+    let report = format!("/api/v1/comments/{}/report", comment_id);
+    let response = app.post(&report, Some(&reader), json!({})).await;
+    assert_eq!(
+        response.status,
+        StatusCode::BAD_REQUEST,
+        "{}",
+        response.body
+    );
+    for _ in 0..2 {
+        let reporter = app.register("user").await;
+        let response = app
+            .post(&report, Some(&reporter), json!({ "reason": "Trolling" }))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["comments"][0]["score"], -5);
+    assert_eq!(response.body["comments"][0]["collapsed"], true);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_guest_comment_is_held_until_approved() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let post_id = app.create_post(&author, "Guest post").await;
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+
+    let response = app
+        .put(
+            "/api/v1/admin/feature-flags/anonymous_comments",
+            Some(&admin),
+            json!({ "enabled": true, "rollout_percentage": 100 }),
+        )
+        .await;
+    assert!(response.status.is_success(), "{}", response.body);
+
+    let response = app
+        .post(
+            &format!("{}/guest", uri),
+            None,
+            json!({
+                "name": "Visitor",
+                "email": "visitor@example.com",
+                "content": "Hello from a guest",
+                "captcha_token": "test-token",
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.body);
+    let comment_id = response.body["id"].as_i64().unwrap();
+
+    // Held for moderation: not listed yet, but in the queue
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.body["total_count"], 0);
+    let response = app.get("/api/v1/moderation/comments", Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert!(response.body.to_string().contains("Hello from a guest"));
+
+    // Only admins moderate
+    let response = app
+        .post(
+            &format!("/api/v1/moderation/comments/{}/approve", comment_id),
+            Some(&author),
+            json!({}),
+        )
+        .await;
+    assert!(response.status.is_client_error());
+
+    let response = app
+        .post(
+            &format!("/api/v1/moderation/comments/{}/approve", comment_id),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT, "{}", response.body);
+
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.body["total_count"], 1);
+    assert_eq!(
+        response.body["comments"][0]["guest_author"]["name"],
+        "Visitor"
+    );
+
+    // Approving announces the comment to the post's author like any new comment
+    let mut unread = 0;
+    for _ in 0..20 {
+        let response = app
+            .get("/api/v1/notifications/unread-count", Some(&author))
+            .await;
+        unread = response.body["unread_count"].as_i64().unwrap_or(0);
+        if unread > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(unread, 1);
+
+    // An approved comment is no longer pending
+    let response = app
+        .post(
+            &format!("/api/v1/moderation/comments/{}/approve", comment_id),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_pinned_comments_are_listed_first() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Pinned post").await;
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+
+    let response = app
+        .post(
+            &uri,
+            Some(&reader),
+            json!({ "content": "First!", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let first_id = response.body["id"].as_i64().unwrap();
+    let response = app
+        .post(
+            &uri,
+            Some(&author),
+            json!({ "content": "Author here", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.body["is_post_author"], true);
+
+    // Only the post author or an admin pins
+    let pin = format!("/api/v1/comments/{}/pin", first_id);
+    let response = app.post(&pin, Some(&reader), json!({})).await;
+    assert!(response.status.is_client_error());
+
+    let response = app.post(&pin, Some(&author), json!({})).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["pinned"], true);
+
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.body["comments"][0]["id"], first_id);
+    assert_eq!(response.body["comments"][0]["pinned"], true);
+    assert_eq!(response.body["comments"][1]["is_post_author"], true);
+
+    let response = app.delete(&pin, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.body["comments"][0]["pinned"], false);
+    assert_eq!(response.body["comments"][1]["id"], first_id);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_shadow_banned_comments_are_only_seen_by_their_author() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let troll = app.register("user").await;
+    let post_id = app.create_post(&author, "Trolled post").await;
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+    let ban = format!("/api/v1/admin/users/{}/shadow-ban", troll.id);
+
+    let response = app
+        .put(&ban, Some(&author), json!({ "shadow_banned": true }))
+        .await;
+    assert!(response.status.is_client_error());
+    let response = app
+        .put(&ban, Some(&admin), json!({ "shadow_banned": true }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = app
+        .post(
+            &uri,
+            Some(&troll),
+            json!({ "content": "Nobody sees this", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    // The troll sees their comment as usual, everybody else sees nothing
+    let response = app.get(&uri, Some(&troll)).await;
+    assert_eq!(response.body["comments"].as_array().unwrap().len(), 1);
+    for viewer in [None, Some(&author)] {
+        let response = app.get(&uri, viewer).await;
+        assert_eq!(response.body["total_count"], 0);
+        assert!(response.body["comments"].as_array().unwrap().is_empty());
+    }
+
+    // Nor is the post author notified
+    let response = app
+        .get("/api/v1/notifications/unread-count", Some(&author))
+        .await;
+    assert_eq!(response.body["unread_count"], 0);
+
+    // Lifting the ban makes their comments visible again
+    let response = app
+        .put(&ban, Some(&admin), json!({ "shadow_banned": false }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.body["total_count"], 1);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_comment_notifications_go_through_the_outbox() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let replier = app.register("user").await;
+    let post_id = app.create_post(&author, "Outbox post").await;
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+
+    let response = app
+        .post(
+            &uri,
+            Some(&reader),
+            json!({ "content": "Root", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let root_id = response.body["id"].as_i64().unwrap();
+    let response = app
+        .post(
+            &uri,
+            Some(&replier),
+            json!({
+                "content": "Reply",
+                "parent_comment_id": root_id,
+                "markdown_enabled": false,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    // One outbox entry per notification: the post author for both comments, the root
+    // comment's author for the reply
+    let recipients: Vec<String> = sqlx::query_scalar(
+        "SELECT payload->>'recipient_id' FROM global.notification_outbox ORDER BY id",
+    )
+    .fetch_all(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        recipients,
+        vec![
+            author.id.to_string(),
+            reader.id.to_string(),
+            author.id.to_string(),
+        ]
+    );
+
+    // A comment that fails leaves nothing behind
+    let response = app
+        .post(
+            &uri,
+            Some(&replier),
+            json!({
+                "content": "Orphan",
+                "parent_comment_id": 999_999,
+                "markdown_enabled": false,
+            }),
+        )
+        .await;
+    assert!(response.status.is_client_error());
+    let queued: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM global.notification_outbox")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(queued, 3);
+
+    // Everything queued is delivered right after the commit
+    let mut pending = 0;
+    for _ in 0..20 {
+        pending = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM global.notification_outbox WHERE delivered_at IS NULL",
+        )
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+        if pending == 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(pending, 0);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_locked_posts_refuse_new_comments() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Discussion closed").await;
+    let post = format!("/api/v1/posts/{}", post_id);
+    let comments = format!("{}/comments", post);
+    let comment = json!({ "content": "Too late?", "markdown_enabled": false });
+
+    // Only the author or an admin may lock
+    let response = app
+        .post(&format!("{}/lock", comments), Some(&reader), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .post(&format!("{}/lock", comments), Some(&author), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["comments_locked"], true);
+
+    let response = app
+        .get(&format!("/api/v1/posts/view/{}", post_id), None)
+        .await;
+    assert_eq!(response.body["comments_locked"], true);
+
+    let response = app.post(&comments, Some(&reader), comment.clone()).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.body["code"], "COMMENTS_LOCKED");
+
+    // Unlocking lets comments in again, and the post shows it right away
+    let response = app
+        .post(&format!("{}/unlock", comments), Some(&author), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = app
+        .get(&format!("/api/v1/posts/view/{}", post_id), None)
+        .await;
+    assert_eq!(response.body["comments_locked"], false);
+
+    let response = app.post(&comments, Some(&reader), comment).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_blocked_users_are_hidden_from_the_blocker() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let troll = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Not feeding trolls").await;
+    let block = format!("/api/v1/users/{}/block", troll.id);
+
+    let response = app.post(&block, Some(&author), json!({})).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let comments = format!("/api/v1/posts/{}/comments", post_id);
+    let response = app
+        .post(
+            &comments,
+            Some(&troll),
+            json!({ "content": "Troll comment", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    // The author is not notified about the comment, and does not see it
+    let notified: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM global.notification_outbox WHERE payload->>'recipient_id' = $1",
+    )
+    .bind(author.id.to_string())
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(notified, 0);
+
+    let response = app.get(&comments, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.body.to_string().contains("Troll comment"));
+
+    // Everyone else still does
+    let response = app.get(&comments, Some(&reader)).await;
+    assert!(response.body.to_string().contains("Troll comment"));
+    let response = app.get(&comments, None).await;
+    assert!(response.body.to_string().contains("Troll comment"));
+
+    // Unblocking shows it again right away
+    let response = app.delete(&block, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = app.get(&comments, Some(&author)).await;
+    assert!(response.body.to_string().contains("Troll comment"));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_listing_nests_replies_with_their_authors() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let post_id = app.create_post(&author, "Threaded post").await;
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+
+    // One comment per user because of the rate limit: a thread down to the deepest level
+    let mut commenters = Vec::new();
+    let mut parent_id = None;
+    for depth in 0..4 {
+        let commenter = app.register("user").await;
+        let response = app
+            .post(
+                &uri,
+                Some(&commenter),
+                json!({
+                    "content": format!("Level {}", depth),
+                    "parent_comment_id": parent_id,
+                    "markdown_enabled": false,
+                }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+        parent_id = response.body["id"].as_i64();
+        commenters.push(commenter);
+    }
+    let response = app
+        .post(
+            &uri,
+            Some(&author),
+            json!({ "content": "Unanswered", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["total_count"], 5);
+
+    let mut comment = &response.body["comments"][0];
+    for commenter in &commenters {
+        assert_eq!(comment["author"]["name"], commenter.username.as_str());
+        assert_eq!(comment["is_post_author"], false);
+        comment = &comment["replies"][0];
+    }
+    let deepest = &response.body["comments"][0]["replies"][0]["replies"][0]["replies"][0];
+    assert!(deepest["replies"].is_null());
+
+    let unanswered = &response.body["comments"][1];
+    assert_eq!(unanswered["author"]["name"], author.username.as_str());
+    assert_eq!(unanswered["is_post_author"], true);
+    assert_eq!(unanswered["replies"], json!([]));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_thread_subscriptions_permalinks_and_deletion() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let replier = app.register("user").await;
+    let post_id = app.create_post(&author, "Followed post").await;
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+
+    let response = app
+        .post(
+            &uri,
+            Some(&reader),
+            json!({ "content": "Root comment", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let root_id = response.body["id"].as_i64().unwrap();
+    let response = app
+        .post(
+            &uri,
+            Some(&replier),
+            json!({
+                "content": "A reply",
+                "parent_comment_id": root_id,
+                "markdown_enabled": false,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let reply_id = response.body["id"].as_i64().unwrap();
+
+    // Following a reply follows the thread of its root comment
+    let response = app
+        .post(
+            &format!("{}/{}/subscribe", uri, reply_id),
+            Some(&author),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["root_comment_id"], root_id);
+    assert_eq!(response.body["subscribed"], true);
+    let subscriptions = || {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM global.comment_thread_subscriptions \
+             WHERE root_comment_id = $1 AND user_id = $2",
+        )
+        .bind(root_id)
+        .bind(author.id)
+        .fetch_one(&app.pool)
+    };
+    assert_eq!(subscriptions().await.unwrap(), 1);
+
+    let response = app
+        .post(
+            &format!("{}/{}/unsubscribe", uri, root_id),
+            Some(&author),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["subscribed"], false);
+    assert_eq!(subscriptions().await.unwrap(), 0);
+
+    let permalink = format!("/api/v1/comments/{}", reply_id);
+    let response = app.get(&permalink, None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["comment"]["id"], reply_id);
+    assert_eq!(response.body["post_id"], post_id);
+    assert_eq!(response.body["root_comment_id"], root_id);
+
+    // Only the commenter or an admin deletes a comment
+    let response = app.delete(&permalink, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    let response = app.delete(&permalink, Some(&replier)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.get(&permalink, None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.body["comments"][0]["id"], root_id);
+    assert_eq!(response.body["comments"][0]["replies"], json!([]));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_moderation_queue_is_kept_per_blog() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let post_id = app.create_post(&author, "Guest post").await;
+
+    let response = app
+        .put(
+            "/api/v1/admin/feature-flags/anonymous_comments",
+            Some(&admin),
+            json!({ "enabled": true, "rollout_percentage": 100 }),
+        )
+        .await;
+    assert!(response.status.is_success(), "{}", response.body);
+    let slug = format!("blog-{}", Uuid::new_v4().simple());
+    let response = app
+        .post(
+            "/api/v1/admin/blogs",
+            Some(&admin),
+            json!({ "slug": slug, "name": "Other blog" }),
+        )
+        .await;
+    assert!(response.status.is_success(), "{}", response.body);
+    let other_blog = format!("/blogs/{}/api/v1", slug);
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/comments/guest", post_id),
+            None,
+            json!({
+                "name": "Visitor",
+                "email": "visitor@example.com",
+                "content": "Hello from a guest",
+                "captcha_token": "test-token",
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.body);
+    let comment_id = response.body["id"].as_i64().unwrap();
+
+    // The other blog's queue neither shows nor moderates it
+    let response = app
+        .get(&format!("{}/moderation/comments", other_blog), Some(&admin))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert!(!response.body.to_string().contains("visitor@example.com"));
+    for action in ["approve", "reject"] {
+        let response = app
+            .post(
+                &format!(
+                    "{}/moderation/comments/{}/{}",
+                    other_blog, comment_id, action
+                ),
+                Some(&admin),
+                json!({}),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND, "{}", action);
+    }
+
+    let response = app.get("/api/v1/moderation/comments", Some(&admin)).await;
+    assert!(response.body.to_string().contains("visitor@example.com"));
+    let response = app
+        .post(
+            &format!("/api/v1/moderation/comments/{}/approve", comment_id),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT, "{}", response.body);
+}
@@ -0,0 +1,638 @@
+use super::openapi::Contract;
+use super::TestApp;
+use serde_json::json;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Documented operations the contract test does not call, with the reason. Keep it short:
+/// a new route belongs in the scenario below.
+const NOT_EXERCISED: &[(&str, &str, &str)] = &[
+    ("POST", "/api/v1/posts/{id}/attachments", "binary upload"),
+    ("POST", "/api/v1/posts/{id}/cover-image", "binary upload"),
+    ("PUT", "/api/v1/users/me/avatar", "binary upload"),
+    ("POST", "/api/v1/admin/import", "binary upload"),
+];
+
+// Call every documented operation at least once, as the right kind of user where it matters.
+// Statuses are not asserted: any documented status with a matching body honours the contract.
+async fn exercise_api(app: &TestApp) {
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let missing = 987_654_321;
+
+    // Health and public endpoints
+    app.get("/api/v1/health", None).await;
+    app.get("/api/v1/health/ready", None).await;
+    app.get("/api/v1/health/protected", Some(&reader)).await;
+    app.get("/api/v1/health/protected", None).await;
+    app.post(
+        "/api/v1/auth/login",
+        None,
+        json!({ "email": reader.email, "password": super::TEST_PASSWORD }),
+    )
+    .await;
+    app.get("/api/v1/settings", None).await;
+    app.get("/api/v1/feature-flags", Some(&reader)).await;
+    app.get("/api/v1/membership/tiers", None).await;
+    app.get("/api/v1/stats/public", None).await;
+
+    // Posts
+    let post_id = app.create_post(&author, "Contract post").await;
+    let post = format!("/api/v1/posts/{}", post_id);
+    app.get(&format!("/api/v1/posts/view/{}", post_id), None)
+        .await;
+    app.get(&format!("{}/meta", post), None).await;
+    app.put(
+        &format!("/api/v1/posts/edit/{}", post_id),
+        Some(&author),
+        json!({ "title": "Contract post, edited", "tags": ["testing", "contracts"] }),
+    )
+    .await;
+    app.post(&format!("{}/edit-lock", post), Some(&author), json!({}))
+        .await;
+    app.delete(&format!("{}/edit-lock", post), Some(&author))
+        .await;
+    app.post(&format!("{}/duplicate", post), Some(&author), json!({}))
+        .await;
+    app.get("/api/v1/posts/mine", Some(&author)).await;
+    app.post(&format!("{}/like", post), Some(&reader), json!({}))
+        .await;
+    app.delete(&format!("{}/like", post), Some(&reader)).await;
+    app.get("/api/v1/posts?meta.podcast.episode=12", None).await;
+    app.get("/api/v1/posts/popular", None).await;
+    app.get("/api/v1/posts/archive", None).await;
+    app.get("/api/v1/posts/archive/2024/1", None).await;
+    app.get(&format!("{}/export?format=markdown", post), Some(&author))
+        .await;
+    app.get(
+        "/api/v1/oembed?url=http%3A%2F%2Flocalhost%2Fposts%2Fcontract-post",
+        None,
+    )
+    .await;
+    app.get(
+        "/api/v1/link-previews?url=https%3A%2F%2Fexample.com%2F",
+        Some(&reader),
+    )
+    .await;
+
+    // Media, without uploads
+    app.get(&format!("{}/attachments", post), None).await;
+    app.get(&format!("{}/attachments/{}", post, missing), None)
+        .await;
+    app.delete(&format!("{}/attachments/{}", post, missing), Some(&author))
+        .await;
+    app.get(
+        &format!("{}/cover-image/{}/original.webp", post, missing),
+        None,
+    )
+    .await;
+    app.get(
+        &format!(
+            "/api/v1/users/{}/avatar/{}/256.webp",
+            author.id,
+            Uuid::new_v4()
+        ),
+        None,
+    )
+    .await;
+    app.delete("/api/v1/users/me/avatar", Some(&author)).await;
+
+    // Comments and moderation
+    let comments = format!("{}/comments", post);
+    let response = app
+        .post(
+            &comments,
+            Some(&reader),
+            json!({ "content": "A contract comment", "markdown_enabled": false }),
+        )
+        .await;
+    let comment_id = response.body["id"].as_i64().unwrap_or(missing);
+    app.post(
+        &format!("{}/guest", comments),
+        None,
+        json!({
+            "name": "Guest",
+            "email": "guest@example.com",
+            "content": "A guest comment",
+            "captcha_token": "token",
+        }),
+    )
+    .await;
+    app.get(&comments, None).await;
+    app.get(&format!("/api/v1/comments/{}", comment_id), None)
+        .await;
+    app.post(
+        &format!("/api/v1/comments/{}/pin", comment_id),
+        Some(&author),
+        json!({}),
+    )
+    .await;
+    app.delete(
+        &format!("/api/v1/comments/{}/pin", comment_id),
+        Some(&author),
+    )
+    .await;
+    app.post(
+        &format!("/api/v1/comments/{}/reactions", comment_id),
+        Some(&author),
+        json!({ "reaction": "heart" }),
+    )
+    .await;
+    app.delete(
+        &format!("/api/v1/comments/{}/reactions/heart", comment_id),
+        Some(&author),
+    )
+    .await;
+    app.post(
+        &format!("/api/v1/comments/{}/report", comment_id),
+        Some(&author),
+        json!({ "reason": "Off topic" }),
+    )
+    .await;
+    app.post(&format!("{}/lock", comments), Some(&author), json!({}))
+        .await;
+    app.post(&format!("{}/unlock", comments), Some(&author), json!({}))
+        .await;
+    app.post(
+        &format!("{}/{}/subscribe", comments, comment_id),
+        Some(&author),
+        json!({}),
+    )
+    .await;
+    app.post(
+        &format!("{}/{}/unsubscribe", comments, comment_id),
+        Some(&author),
+        json!({}),
+    )
+    .await;
+    app.get("/api/v1/moderation/comments", Some(&author)).await;
+    app.get("/api/v1/moderation/likes", Some(&admin)).await;
+    app.get("/api/v1/moderation/posts", Some(&admin)).await;
+    app.post(
+        &format!("/api/v1/moderation/comments/{}/approve", comment_id),
+        Some(&author),
+        json!({}),
+    )
+    .await;
+    app.post(
+        &format!("/api/v1/moderation/comments/{}/reject", missing),
+        Some(&author),
+        json!({}),
+    )
+    .await;
+    app.put(
+        &format!("/api/v1/admin/users/{}/shadow-ban", reader.id),
+        Some(&admin),
+        json!({ "shadow_banned": false }),
+    )
+    .await;
+
+    // Notifications
+    let response = app.get("/api/v1/notifications", Some(&author)).await;
+    let notification_id = response.body[0]["id"].as_i64().unwrap_or(missing);
+    app.get("/api/v1/notifications/unread-count", Some(&author))
+        .await;
+    app.post(
+        &format!("/api/v1/notifications/{}/read", notification_id),
+        Some(&author),
+        json!({}),
+    )
+    .await;
+    app.post("/api/v1/notifications/read-all", Some(&author), json!({}))
+        .await;
+    app.get("/api/v1/notifications/quiet-hours", Some(&author))
+        .await;
+    app.put(
+        "/api/v1/notifications/quiet-hours",
+        Some(&author),
+        json!({ "enabled": true, "start": "22:00", "end": "07:00", "timezone": "Europe/Paris" }),
+    )
+    .await;
+
+    // Analytics
+    app.post(
+        "/api/v1/analytics/views",
+        Some(&reader),
+        json!({ "post_id": post_id, "utm_source": "contract" }),
+    )
+    .await;
+    app.get("/api/v1/analytics/engagement", Some(&reader)).await;
+    app.get(
+        &format!("/api/v1/analytics/engagement/user/{}", reader.id),
+        Some(&admin),
+    )
+    .await;
+    app.get("/api/v1/analytics/posts", None).await;
+    app.get(&format!("/api/v1/analytics/posts/{}", post_id), None)
+        .await;
+    app.get(
+        &format!("/api/v1/analytics/posts/{}/time/week", post_id),
+        None,
+    )
+    .await;
+    app.get(
+        &format!("/api/v1/analytics/posts/{}/audience", post_id),
+        Some(&author),
+    )
+    .await;
+    app.get(
+        &format!("/api/v1/analytics/posts/{}/campaigns", post_id),
+        Some(&author),
+    )
+    .await;
+    app.get("/api/v1/analytics/activity-heatmap", Some(&admin))
+        .await;
+    app.post("/api/v1/analytics/refresh", Some(&admin), json!({}))
+        .await;
+
+    // Recommendations
+    app.get("/api/v1/recommendations", Some(&reader)).await;
+    app.get(
+        &format!("/api/v1/recommendations/similar/{}", post_id),
+        None,
+    )
+    .await;
+    app.post("/api/v1/recommendations/refresh", Some(&admin), json!({}))
+        .await;
+    app.get(
+        &format!(
+            "/api/v1/admin/recommendations/preview?user_id={}",
+            reader.id
+        ),
+        Some(&admin),
+    )
+    .await;
+    app.get("/api/v1/recommendations/digest", Some(&reader))
+        .await;
+    app.put(
+        "/api/v1/recommendations/digest",
+        Some(&reader),
+        json!({ "enabled": false }),
+    )
+    .await;
+
+    // Leaderboards
+    app.get("/api/v1/leaderboards/authors?period=month", None)
+        .await;
+    app.get("/api/v1/leaderboards/commenters", None).await;
+
+    // Newsletter
+    app.post(
+        "/api/v1/newsletter/subscribe",
+        None,
+        json!({ "email": "contract-reader@example.com" }),
+    )
+    .await;
+    app.get("/api/v1/newsletter/confirm?token=unknown", None)
+        .await;
+    app.get("/api/v1/newsletter/unsubscribe?token=unknown", None)
+        .await;
+    let campaign = app
+        .post(
+            "/api/v1/admin/newsletter/campaigns",
+            Some(&admin),
+            json!({ "post_id": post_id }),
+        )
+        .await;
+    let campaign_id = campaign.body["id"].as_i64().unwrap_or(missing);
+    app.get(
+        &format!("/api/v1/admin/newsletter/campaigns/{}", campaign_id),
+        Some(&admin),
+    )
+    .await;
+    app.get(
+        &format!(
+            "/api/v1/admin/newsletter/campaigns/{}/deliveries",
+            campaign_id
+        ),
+        Some(&admin),
+    )
+    .await;
+
+    // Title tests
+    let title_test = format!("/api/v1/posts/{}/title-test", post_id);
+    app.put(
+        &title_test,
+        Some(&author),
+        json!({ "titles": ["Contract title", "Another contract title"] }),
+    )
+    .await;
+    app.post(
+        "/api/v1/analytics/title-impressions",
+        None,
+        json!({ "post_ids": [post_id], "visitor_id": "contract-visitor" }),
+    )
+    .await;
+    app.post(
+        "/api/v1/analytics/title-clicks",
+        None,
+        json!({ "post_id": post_id, "visitor_id": "contract-visitor" }),
+    )
+    .await;
+    app.get(
+        &format!("/api/v1/analytics/posts/{}/title-test", post_id),
+        Some(&author),
+    )
+    .await;
+    app.delete(&title_test, Some(&author)).await;
+
+    // Reading lists
+    let list = app
+        .post(
+            "/api/v1/users/me/lists",
+            Some(&author),
+            json!({ "name": "Contract list", "is_public": true }),
+        )
+        .await;
+    let list_id = list.body["id"].as_i64().unwrap_or(missing);
+    let list_path = format!("/api/v1/users/me/lists/{}", list_id);
+    app.get("/api/v1/users/me/lists", Some(&author)).await;
+    app.put(
+        &list_path,
+        Some(&author),
+        json!({ "description": "Posts for the contract tests" }),
+    )
+    .await;
+    app.post(
+        &format!("{}/posts", list_path),
+        Some(&author),
+        json!({ "post_id": post_id }),
+    )
+    .await;
+    app.put(
+        &format!("{}/posts", list_path),
+        Some(&author),
+        json!({ "post_ids": [post_id] }),
+    )
+    .await;
+    app.get(&format!("/api/v1/lists/{}", list_id), None).await;
+    app.delete(&format!("{}/posts/{}", list_path, post_id), Some(&author))
+        .await;
+    app.get(&list_path, Some(&author)).await;
+    app.delete(&list_path, Some(&author)).await;
+
+    // Tips, unavailable without a payment provider
+    app.post(
+        &format!("/api/v1/posts/{}/tips", post_id),
+        Some(&admin),
+        json!({ "amount": 500, "message": "Contract tip" }),
+    )
+    .await;
+    app.get("/api/v1/analytics/authors/me/earnings", Some(&author))
+        .await;
+
+    // Feedback
+    let feedback = app
+        .post(
+            "/api/v1/feedback",
+            None,
+            json!({ "message": "Contract feedback", "email": "contract-reader@example.com" }),
+        )
+        .await;
+    let feedback_id = feedback.body["id"].as_i64().unwrap_or(missing);
+    app.get("/api/v1/admin/feedback", Some(&admin)).await;
+    app.put(
+        &format!("/api/v1/admin/feedback/{}", feedback_id),
+        Some(&admin),
+        json!({ "status": "answered" }),
+    )
+    .await;
+
+    // Announcements
+    let announcement = app
+        .post(
+            "/api/v1/admin/announcements",
+            Some(&admin),
+            json!({ "content": "Contract announcement", "scheduled_for": "2099-01-01T00:00:00Z" }),
+        )
+        .await;
+    let announcement_id = announcement.body["id"].as_i64().unwrap_or(missing);
+    let announcement = format!("/api/v1/admin/announcements/{}", announcement_id);
+    app.get("/api/v1/admin/announcements", Some(&admin)).await;
+    app.get(&announcement, Some(&admin)).await;
+    app.delete(&announcement, Some(&admin)).await;
+
+    // Invites
+    let invites = app
+        .post(
+            "/api/v1/admin/invites",
+            Some(&admin),
+            json!({ "count": 1, "note": "Contract invite" }),
+        )
+        .await;
+    let invite_id = invites.body[0]["id"].as_i64().unwrap_or(missing);
+    app.get("/api/v1/admin/invites", Some(&admin)).await;
+    app.get("/api/v1/admin/invites/usage", Some(&admin)).await;
+    app.delete(
+        &format!("/api/v1/admin/invites/{}", invite_id),
+        Some(&admin),
+    )
+    .await;
+
+    // Users: profiles, activity, privacy, consent and blocks
+    app.get(&format!("/api/v1/users/{}", author.username), None)
+        .await;
+    app.get(&format!("/api/v1/users/{}/activity", author.username), None)
+        .await;
+    app.get("/api/v1/activity/privacy", Some(&author)).await;
+    app.put(
+        "/api/v1/activity/privacy",
+        Some(&author),
+        json!({ "hide_posts": false, "hide_comments": true, "hide_likes": true }),
+    )
+    .await;
+    app.get("/api/v1/users/me/consent", Some(&reader)).await;
+    app.put(
+        "/api/v1/users/me/consent",
+        Some(&reader),
+        json!({ "analytics": false }),
+    )
+    .await;
+    app.get("/api/v1/users/me/quota", Some(&reader)).await;
+    app.get("/api/v1/users/me/storage", Some(&author)).await;
+    app.get("/api/v1/admin/storage", Some(&admin)).await;
+    // A wrong current password, so the reader's token stays valid for the calls below
+    app.put(
+        "/api/v1/users/me/password",
+        Some(&reader),
+        json!({ "current_password": "wrong password", "new_password": "new password" }),
+    )
+    .await;
+    app.post(
+        &format!("/api/v1/users/{}/block", author.id),
+        Some(&reader),
+        json!({}),
+    )
+    .await;
+    app.delete(&format!("/api/v1/users/{}/block", author.id), Some(&reader))
+        .await;
+
+    // Memberships
+    let tier = format!("tier_{}", Uuid::new_v4().simple());
+    app.post(
+        "/api/v1/admin/membership/tiers",
+        Some(&admin),
+        json!({ "name": tier, "level": 1, "description": "Contract tier" }),
+    )
+    .await;
+    app.put(
+        &format!("/api/v1/admin/users/{}/membership", reader.id),
+        Some(&admin),
+        json!({ "tier": tier }),
+    )
+    .await;
+    app.get(
+        &format!("/api/v1/admin/users/{}/membership", reader.id),
+        Some(&admin),
+    )
+    .await;
+
+    // Administration: settings, flags, blogs and content API keys
+    app.get("/api/v1/admin/settings", Some(&admin)).await;
+    app.put(
+        "/api/v1/admin/settings",
+        Some(&admin),
+        json!({ "settings": { "site_title": "Contract blog" } }),
+    )
+    .await;
+    app.post(
+        "/api/v1/admin/tags/retag",
+        Some(&admin),
+        json!({ "from_tag": "contracts", "to_tag": "contract-tests" }),
+    )
+    .await;
+    app.get("/api/v1/admin/feature-flags", Some(&admin)).await;
+    app.put(
+        "/api/v1/admin/feature-flags/contract_flag",
+        Some(&admin),
+        json!({
+            "description": "Contract flag",
+            "enabled": true,
+            "rollout_percentage": 50,
+            "target_roles": [],
+            "target_users": [],
+        }),
+    )
+    .await;
+    app.delete("/api/v1/admin/feature-flags/contract_flag", Some(&admin))
+        .await;
+    let slug = format!("blog-{}", Uuid::new_v4().simple());
+    let response = app
+        .post(
+            "/api/v1/admin/blogs",
+            Some(&admin),
+            json!({ "slug": slug, "name": "Contract blog" }),
+        )
+        .await;
+    let blog_id = response.body["id"].as_i64().unwrap_or(missing);
+    app.get("/api/v1/admin/blogs", Some(&admin)).await;
+    app.put(
+        &format!("/api/v1/admin/blogs/{}", blog_id),
+        Some(&admin),
+        json!({ "name": "Contract blog, renamed" }),
+    )
+    .await;
+    let response = app
+        .post(
+            "/api/v1/admin/content-api-keys",
+            Some(&admin),
+            json!({ "name": "Contract theme" }),
+        )
+        .await;
+    let key_id = response.body["id"].as_i64().unwrap_or(missing);
+    app.get("/api/v1/admin/content-api-keys", Some(&admin))
+        .await;
+    app.delete(
+        &format!("/api/v1/admin/content-api-keys/{}", key_id),
+        Some(&admin),
+    )
+    .await;
+
+    // Background jobs
+    let response = app
+        .post(
+            "/api/v1/admin/retention/run",
+            Some(&admin),
+            json!({ "dry_run": true }),
+        )
+        .await;
+    let job_id = response.body["id"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    app.get("/api/v1/admin/retention", Some(&admin)).await;
+    app.get(&format!("/api/v1/admin/jobs/{}", job_id), Some(&admin))
+        .await;
+    app.post("/api/v1/admin/cache/warm", Some(&admin), json!({}))
+        .await;
+    app.post("/api/v1/admin/seed", Some(&admin), json!({ "users": 1 }))
+        .await;
+    let response = app
+        .post("/api/v1/admin/export/site", Some(&admin), json!({}))
+        .await;
+    let export_id = response.body["id"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    app.get(
+        &format!("/api/v1/admin/export/site/{}", export_id),
+        Some(&admin),
+    )
+    .await;
+
+    // Clean-up last, so the routes above had something to work on
+    app.delete(&format!("/api/v1/comments/{}", comment_id), Some(&reader))
+        .await;
+    app.delete(&format!("/api/v1/posts/delete/{}", post_id), Some(&author))
+        .await;
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_responses_match_openapi_document() {
+    let app = TestApp::spawn().await;
+    let contract = Contract::v1();
+
+    exercise_api(&app).await;
+
+    let mut failures = Vec::new();
+    let mut exercised = HashSet::new();
+    for exchange in app.exchanges() {
+        if let Some((template, _)) = contract.operation(&exchange.method, &exchange.path) {
+            exercised.insert((exchange.method.to_string(), template.to_string()));
+        }
+        if let Err(e) = contract.check(&exchange) {
+            failures.push(format!("{} {}: {}", exchange.method, exchange.path, e));
+        }
+    }
+
+    let not_exercised: HashSet<_> = NOT_EXERCISED
+        .iter()
+        .map(|(method, template, _)| (method.to_string(), template.to_string()))
+        .collect();
+    let documented = contract.operations();
+    for operation in &documented {
+        if !exercised.contains(operation) && !not_exercised.contains(operation) {
+            failures.push(format!(
+                "{} {}: documented but never called by the contract test",
+                operation.0, operation.1
+            ));
+        }
+    }
+    for operation in &not_exercised {
+        if !documented.contains(operation) {
+            failures.push(format!(
+                "{} {}: listed in NOT_EXERCISED but not documented",
+                operation.0, operation.1
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} contract violations:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,70 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_feedback_reaches_admins() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let reader = app.register("user").await;
+
+    let response = app
+        .post(
+            "/api/v1/feedback",
+            None,
+            json!({ "message": "Where is the RSS feed?" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = app
+        .post(
+            "/api/v1/feedback",
+            None,
+            json!({ "message": "Where is the RSS feed?", "email": "ada@example.com" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.body["status"], "new");
+
+    let response = app
+        .post(
+            "/api/v1/feedback",
+            Some(&reader),
+            json!({ "message": "Great post on lifetimes" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.body["name"], reader.username.as_str());
+    let id = response.body["id"].as_i64().unwrap();
+
+    let response = app.get("/api/v1/admin/feedback", Some(&reader)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = app.get("/api/v1/admin/feedback", Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body.as_array().unwrap().len(), 2);
+
+    let response = app
+        .put(
+            &format!("/api/v1/admin/feedback/{}", id),
+            Some(&admin),
+            json!({ "status": "answered" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["answered_by"], admin.id.to_string());
+    let response = app
+        .get("/api/v1/admin/feedback?status=new", Some(&admin))
+        .await;
+    assert_eq!(response.body.as_array().unwrap().len(), 1);
+
+    let notifications: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM global.notification_outbox \
+         WHERE payload->>'recipient_id' = $1 AND payload->>'notification_type' = 'SystemMessage'",
+    )
+    .bind(admin.id.to_string())
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(notifications, 2);
+}
@@ -0,0 +1,111 @@
+use super::{TestApp, TEST_PASSWORD};
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+fn registration(invite_code: Option<&str>) -> Value {
+    let name = format!("user_{}", Uuid::new_v4().simple());
+    json!({
+        "username": name,
+        "email": format!("{}@example.com", name),
+        "password": TEST_PASSWORD,
+        "invite_code": invite_code,
+    })
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_invite_only_registration() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+
+    let response = app
+        .put(
+            "/api/v1/admin/settings",
+            Some(&admin),
+            json!({ "settings": { "registration.mode": "invite-only" } }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = app
+        .post("/api/v1/auth/register", None, registration(None))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = app
+        .post("/api/v1/auth/register", None, registration(Some("NOPE")))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app
+        .post(
+            "/api/v1/admin/invites",
+            Some(&admin),
+            json!({ "count": 2, "note": "Beta readers" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let code = response.body[0]["code"].as_str().unwrap().to_string();
+
+    // Codes match ignoring case
+    let response = app
+        .post(
+            "/api/v1/auth/register",
+            None,
+            registration(Some(&code.to_lowercase())),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let response = app
+        .post("/api/v1/auth/register", None, registration(Some(&code)))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app
+        .get("/api/v1/admin/invites?status=used", Some(&admin))
+        .await;
+    assert_eq!(response.body.as_array().unwrap().len(), 1);
+    assert_eq!(response.body[0]["code"], code.as_str());
+    assert!(response.body[0]["used_by_username"].is_string());
+    let used = format!(
+        "/api/v1/admin/invites/{}",
+        response.body[0]["id"].as_i64().unwrap()
+    );
+
+    let response = app.get("/api/v1/admin/invites/usage", Some(&admin)).await;
+    assert_eq!(response.body["created"], 2);
+    assert_eq!(response.body["used"], 1);
+    assert_eq!(response.body["unused"], 1);
+
+    // Used invites cannot be deleted
+    let response = app.delete(&used, Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_closed_registration() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let reader = app.register("user").await;
+
+    let response = app
+        .post("/api/v1/admin/invites", Some(&reader), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app
+        .put(
+            "/api/v1/admin/settings",
+            Some(&admin),
+            json!({ "settings": { "registration.mode": "closed" } }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let response = app
+        .post("/api/v1/auth/register", None, registration(None))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.body["error"], "Registration is closed");
+}
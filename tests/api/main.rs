@@ -0,0 +1,304 @@
+//! End-to-end tests of the HTTP API against real Postgres and Redis, started in containers
+//! with testcontainers.
+//!
+//! They use the crate only through its public API, as any program embedding it would. They
+//! need a Docker daemon, so they are ignored by default; run them with
+//! `cargo test -- --ignored`. Every test gets its own containers and a fresh schema, so tests
+//! can run in parallel and never see each other's data.
+
+mod activity;
+mod analytics;
+mod announcements;
+mod auth;
+mod comments;
+mod contract;
+mod feedback;
+mod invites;
+mod newsletter;
+mod openapi;
+mod posts;
+mod reading_lists;
+mod tips;
+mod title_tests;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    response::{Json, Response},
+    routing::post,
+    Router,
+};
+use realtime_blog_backend::{build_router, cache::redis::RedisCache, db, AppState};
+use serde_json::{json, Value};
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex, Once};
+use testcontainers_modules::{
+    postgres::Postgres,
+    redis::{Redis, REDIS_PORT},
+    testcontainers::{runners::AsyncRunner, ContainerAsync, ImageExt},
+};
+use tower::{util::BoxCloneService, ServiceExt};
+use uuid::Uuid;
+
+/// Password of every user registered by [`TestApp::register`]
+pub const TEST_PASSWORD: &str = "correct horse battery staple";
+
+/// The application with its own Postgres and Redis, stopped when dropped
+pub struct TestApp {
+    app: BoxCloneService<Request<Body>, Response, Infallible>,
+    pub pool: PgPool,
+    exchanges: Mutex<Vec<Exchange>>,
+    _postgres: ContainerAsync<Postgres>,
+    _redis: ContainerAsync<Redis>,
+}
+
+/// A registered user and the token to authenticate as them
+#[derive(Debug, Clone)]
+pub struct TestUser {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub token: String,
+}
+
+/// A request sent by [`TestApp::request`] and its response, kept for the contract tests
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub method: Method,
+    /// Path without the query string
+    pub path: String,
+    pub request_body: Option<Value>,
+    pub status: StatusCode,
+    /// `None` when the response is empty or not JSON
+    pub response_body: Option<Value>,
+}
+
+/// Status, headers and JSON body of a response; the body is `Null` when it is empty or not JSON
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+// Configuration the application reads from the environment, set once per test binary
+fn configure_env() {
+    static CONFIGURE: Once = Once::new();
+    CONFIGURE.call_once(|| {
+        if std::env::var("JWT_SECRET").is_err() {
+            std::env::set_var("JWT_SECRET", "integration-test-secret");
+        }
+        // Emails are logged rather than sent
+        if std::env::var("MAILER").is_err() {
+            std::env::set_var("MAILER", "log");
+        }
+        // Captcha tokens are checked by a stub that accepts all of them
+        if std::env::var("CAPTCHA_SECRET").is_err() {
+            std::env::set_var("CAPTCHA_SECRET", "integration-test-secret");
+            std::env::set_var("CAPTCHA_VERIFY_URL", start_captcha_stub());
+        }
+    });
+}
+
+// Stand in for the captcha provider, accepting every token. It runs on its own thread so it
+// outlives the runtime of the test that started it. Returns its verification URL.
+fn start_captcha_stub() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind captcha stub");
+    let url = format!(
+        "http://{}/siteverify",
+        listener.local_addr().expect("Captcha stub address")
+    );
+
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to start captcha stub runtime");
+        runtime.block_on(async move {
+            let app = Router::new().route(
+                "/siteverify",
+                post(|| async { Json(json!({ "success": true })) }),
+            );
+            axum::Server::from_tcp(listener)
+                .expect("Failed to start captcha stub")
+                .serve(app.into_make_service())
+                .await
+                .expect("Captcha stub failed");
+        });
+    });
+
+    url
+}
+
+impl TestApp {
+    /// Start Postgres and Redis, create the schema and build the application on them
+    pub async fn spawn() -> Self {
+        configure_env();
+
+        let postgres = Postgres::default()
+            .with_tag("16-alpine")
+            .start()
+            .await
+            .expect("Failed to start Postgres");
+        let database_url = format!(
+            "postgres://postgres:postgres@{}:{}/postgres",
+            postgres.get_host().await.expect("Postgres host"),
+            postgres
+                .get_host_port_ipv4(5432)
+                .await
+                .expect("Postgres port")
+        );
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(&database_url)
+            .await
+            .expect("Failed to connect to Postgres");
+        db::init_db(&pool).await.expect("Failed to create schema");
+
+        let redis = Redis::default()
+            .start()
+            .await
+            .expect("Failed to start Redis");
+        let redis_url = format!(
+            "redis://{}:{}",
+            redis.get_host().await.expect("Redis host"),
+            redis
+                .get_host_port_ipv4(REDIS_PORT)
+                .await
+                .expect("Redis port")
+        );
+        let client = redis::Client::open(redis_url).expect("Invalid Redis URL");
+        let redis_cache = Arc::new(RedisCache::new(client, None));
+
+        let state = AppState::new(pool.clone(), Some(redis_cache)).await;
+        state.start_background_tasks();
+        let app = build_router(state);
+
+        Self {
+            app: BoxCloneService::new(app),
+            pool,
+            exchanges: Mutex::new(Vec::new()),
+            _postgres: postgres,
+            _redis: redis,
+        }
+    }
+
+    /// Send a request, as `user` when given, with an optional JSON body
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        user: Option<&TestUser>,
+        body: Option<Value>,
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method.clone()).uri(uri);
+        if let Some(user) = user {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", user.token));
+        }
+        let request = match &body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .expect("Invalid request");
+
+        let response = self.app.clone().oneshot(request).await.expect("Infallible");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = hyper::body::to_bytes(response.into_body())
+            .await
+            .expect("Failed to read response body");
+
+        let response_body: Option<Value> = serde_json::from_slice(&bytes).ok();
+
+        self.exchanges.lock().unwrap().push(Exchange {
+            method,
+            path: uri.split('?').next().unwrap_or(uri).to_string(),
+            request_body: body,
+            status,
+            response_body: response_body.clone(),
+        });
+
+        TestResponse {
+            status,
+            headers,
+            body: response_body.unwrap_or(Value::Null),
+        }
+    }
+
+    /// Every request sent so far, with its response
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().unwrap().clone()
+    }
+
+    pub async fn get(&self, uri: &str, user: Option<&TestUser>) -> TestResponse {
+        self.request(Method::GET, uri, user, None).await
+    }
+
+    pub async fn post(&self, uri: &str, user: Option<&TestUser>, body: Value) -> TestResponse {
+        self.request(Method::POST, uri, user, Some(body)).await
+    }
+
+    pub async fn put(&self, uri: &str, user: Option<&TestUser>, body: Value) -> TestResponse {
+        self.request(Method::PUT, uri, user, Some(body)).await
+    }
+
+    pub async fn delete(&self, uri: &str, user: Option<&TestUser>) -> TestResponse {
+        self.request(Method::DELETE, uri, user, None).await
+    }
+
+    /// Register a user with a unique name and email; `role` is `user` or `admin`
+    pub async fn register(&self, role: &str) -> TestUser {
+        let name = format!("user_{}", Uuid::new_v4().simple());
+        let email = format!("{}@example.com", name);
+        let response = self
+            .post(
+                "/api/v1/auth/register",
+                None,
+                json!({
+                    "username": name,
+                    "email": email,
+                    "password": TEST_PASSWORD,
+                    "role": role,
+                }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+        TestUser {
+            id: response.body["user_id"]
+                .as_str()
+                .and_then(|id| id.parse().ok())
+                .expect("user_id in registration response"),
+            username: name,
+            email,
+            token: response.body["token"]
+                .as_str()
+                .expect("token in registration response")
+                .to_string(),
+        }
+    }
+
+    /// Create a published post as `author`, returning its id
+    pub async fn create_post(&self, author: &TestUser, title: &str) -> i64 {
+        let slug = format!("post-{}", Uuid::new_v4().simple());
+        let response = self
+            .post(
+                "/api/v1/posts",
+                Some(author),
+                json!({
+                    "title": title,
+                    "slug": slug,
+                    "content": format!("# {}\n\nSome **content**.", title),
+                    "tags": ["testing"],
+                    "is_draft": false,
+                }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+        response.body["id"].as_i64().expect("id of created post")
+    }
+}
@@ -0,0 +1,111 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_newsletter_subscription_and_campaign() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let post_id = app.create_post(&author, "Newsletter post").await;
+
+    let response = app
+        .post(
+            "/api/v1/newsletter/subscribe",
+            None,
+            json!({ "email": "Reader@Example.com" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.body);
+    let response = app
+        .post(
+            "/api/v1/newsletter/subscribe",
+            None,
+            json!({ "email": "not an address" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let (confirm_token, unsubscribe_token): (String, String) = sqlx::query_as(
+        "SELECT confirm_token, unsubscribe_token FROM global.newsletter_subscribers \
+         WHERE email = 'reader@example.com'",
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .get(
+            &format!("/api/v1/newsletter/confirm?token={}", confirm_token),
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["status"], "confirmed");
+    let response = app
+        .get(
+            &format!("/api/v1/newsletter/confirm?token={}", confirm_token),
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app
+        .post(
+            "/api/v1/admin/newsletter/campaigns",
+            Some(&author),
+            json!({ "post_id": post_id }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = app
+        .post(
+            "/api/v1/admin/newsletter/campaigns",
+            Some(&admin),
+            json!({ "post_id": post_id }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.body);
+    assert_eq!(response.body["subject"], "Newsletter post");
+    assert_eq!(response.body["recipients"], 1);
+    let campaign = format!(
+        "/api/v1/admin/newsletter/campaigns/{}",
+        response.body["id"].as_i64().unwrap()
+    );
+
+    let mut status = String::new();
+    for _ in 0..50 {
+        let response = app.get(&campaign, Some(&admin)).await;
+        status = response.body["status"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if status == "sent" {
+            assert_eq!(response.body["sent"], 1);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    assert_eq!(status, "sent");
+
+    let response = app
+        .get(&format!("{}/deliveries", campaign), Some(&admin))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body[0]["email"], "reader@example.com");
+    assert_eq!(response.body[0]["status"], "sent");
+
+    // Unsubscribing is idempotent
+    for _ in 0..2 {
+        let response = app
+            .get(
+                &format!("/api/v1/newsletter/unsubscribe?token={}", unsubscribe_token),
+                None,
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body["status"], "unsubscribed");
+    }
+}
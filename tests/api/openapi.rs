@@ -0,0 +1,273 @@
+//! Checks requests and responses against the OpenAPI document the API publishes.
+//!
+//! Only the parts of JSON Schema that utoipa generates are understood: types, required and
+//! nullable properties, arrays, enums, maps, `$ref` and `allOf`/`oneOf`/`anyOf`.
+
+use super::Exchange;
+use axum::http::Method;
+use realtime_blog_backend::api_doc;
+use serde_json::Value;
+
+/// The `/api/v1` document, as JSON
+pub struct Contract {
+    document: Value,
+}
+
+impl Contract {
+    pub fn v1() -> Self {
+        Self {
+            document: serde_json::to_value(api_doc::v1_openapi())
+                .expect("OpenAPI document is serializable"),
+        }
+    }
+
+    /// Every documented operation, as an upper-case method and a path template
+    pub fn operations(&self) -> Vec<(String, String)> {
+        let mut operations = Vec::new();
+        for (template, item) in self.paths() {
+            for method in item.as_object().into_iter().flat_map(|item| item.keys()) {
+                operations.push((method.to_uppercase(), template.clone()));
+            }
+        }
+        operations
+    }
+
+    /// The documented operation a request was routed to, with its path template. When several
+    /// templates match, the one with the most literal segments wins, as in the router.
+    pub fn operation(&self, method: &Method, path: &str) -> Option<(&str, &Value)> {
+        let method = method.as_str().to_lowercase();
+        self.paths()
+            .filter(|(template, _)| template_matches(template, path))
+            .filter_map(|(template, item)| Some((template.as_str(), item.get(&method)?)))
+            .max_by_key(|(template, _)| literal_segments(template))
+    }
+
+    /// Check an exchange: its route, status, request body and response body must be
+    /// documented, and the bodies must match their schemas
+    pub fn check(&self, exchange: &Exchange) -> Result<(), String> {
+        let (template, operation) = self
+            .operation(&exchange.method, &exchange.path)
+            .ok_or_else(|| "undocumented route".to_string())?;
+
+        if let Some(body) = &exchange.request_body {
+            if let Some(schema) = operation.pointer("/requestBody/content/application~1json/schema")
+            {
+                self.validate(schema, body, "request")
+                    .map_err(|e| format!("request body does not match {}: {}", template, e))?;
+            }
+        }
+
+        let status = exchange.status.as_str();
+        let response = operation["responses"]
+            .get(status)
+            .or_else(|| operation["responses"].get("default"))
+            .ok_or_else(|| format!("status {} is not documented for {}", status, template))?;
+
+        match response.get("content") {
+            None => {
+                // Error responses are often documented without a body; success bodies must be
+                if exchange.status.is_success() && exchange.response_body.is_some() {
+                    return Err(format!(
+                        "{} returned a JSON body its {} response does not document",
+                        template, status
+                    ));
+                }
+            }
+            Some(content) => {
+                if let Some(schema) = content.pointer("/application~1json/schema") {
+                    let body = exchange.response_body.as_ref().ok_or_else(|| {
+                        format!("{} response of {} is documented as JSON", status, template)
+                    })?;
+                    self.validate(schema, body, "response").map_err(|e| {
+                        format!("{} response does not match {}: {}", status, template, e)
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn paths(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.document["paths"]
+            .as_object()
+            .into_iter()
+            .flat_map(|paths| paths.iter())
+    }
+
+    fn resolve<'a>(&'a self, reference: &str) -> Result<&'a Value, String> {
+        reference
+            .strip_prefix('#')
+            .and_then(|pointer| self.document.pointer(pointer))
+            .ok_or_else(|| format!("unresolved reference {}", reference))
+    }
+
+    /// Validate `value` against `schema`; `at` locates the value in error messages
+    fn validate(&self, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+        if value.is_null() && schema["nullable"] == true {
+            return Ok(());
+        }
+        if let Some(reference) = schema["$ref"].as_str() {
+            return self.validate(self.resolve(reference)?, value, at);
+        }
+        if let Some(schemas) = schema["allOf"].as_array() {
+            for schema in schemas {
+                self.validate(schema, value, at)?;
+            }
+        }
+        for combinator in ["oneOf", "anyOf"] {
+            if let Some(schemas) = schema[combinator].as_array() {
+                if !schemas
+                    .iter()
+                    .any(|schema| self.validate(schema, value, at).is_ok())
+                {
+                    return Err(format!(
+                        "{}: matches none of the {} schemas",
+                        at, combinator
+                    ));
+                }
+            }
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return Err(format!("{}: {} is not one of {:?}", at, value, values));
+            }
+        }
+
+        let valid = match schema["type"].as_str() {
+            Some("object") => return self.validate_object(schema, value, at),
+            Some("array") => {
+                let items = value
+                    .as_array()
+                    .ok_or_else(|| format!("{}: expected an array, got {}", at, value))?;
+                for (i, item) in items.iter().enumerate() {
+                    self.validate(&schema["items"], item, &format!("{}[{}]", at, i))?;
+                }
+                true
+            }
+            Some("string") => value.is_string(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            Some("boolean") => value.is_boolean(),
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!(
+                "{}: expected {}, got {}",
+                at, schema["type"], value
+            ))
+        }
+    }
+
+    fn validate_object(&self, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| format!("{}: expected an object, got {}", at, value))?;
+
+        for name in schema["required"].as_array().into_iter().flatten() {
+            let name = name.as_str().unwrap_or_default();
+            if !object.contains_key(name) {
+                return Err(format!("{}: missing required property `{}`", at, name));
+            }
+        }
+        for (name, field) in object {
+            let at = format!("{}.{}", at, name);
+            match schema["properties"].get(name) {
+                Some(property) => self.validate(property, field, &at)?,
+                None => {
+                    if schema["additionalProperties"].is_object() {
+                        self.validate(&schema["additionalProperties"], field, &at)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn template_matches(template: &str, path: &str) -> bool {
+    let template: Vec<_> = template.trim_end_matches('/').split('/').collect();
+    let path: Vec<_> = path.trim_end_matches('/').split('/').collect();
+    template.len() == path.len()
+        && template
+            .iter()
+            .zip(&path)
+            .all(|(expected, actual)| expected.starts_with('{') || expected == actual)
+}
+
+fn literal_segments(template: &str) -> usize {
+    template
+        .split('/')
+        .filter(|segment| !segment.starts_with('{'))
+        .count()
+}
+
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    fn exchange(method: Method, path: &str, status: StatusCode, body: Option<Value>) -> Exchange {
+        Exchange {
+            method,
+            path: path.to_string(),
+            request_body: None,
+            status,
+            response_body: body,
+        }
+    }
+
+    #[test]
+    fn test_check_response_body() {
+        let contract = Contract::v1();
+        let healthy = json!({ "status": "ok", "message": "Server is running" });
+
+        assert!(contract
+            .check(&exchange(
+                Method::GET,
+                "/api/v1/health",
+                StatusCode::OK,
+                Some(healthy)
+            ))
+            .is_ok());
+        assert!(contract
+            .check(&exchange(
+                Method::GET,
+                "/api/v1/health",
+                StatusCode::OK,
+                Some(json!({ "status": "ok" }))
+            ))
+            .is_err());
+        assert!(contract
+            .check(&exchange(
+                Method::GET,
+                "/api/v1/health",
+                StatusCode::IM_A_TEAPOT,
+                None
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn test_check_route() {
+        let contract = Contract::v1();
+
+        assert!(contract
+            .check(&exchange(
+                Method::GET,
+                "/api/v1/no-such-route",
+                StatusCode::NOT_FOUND,
+                None
+            ))
+            .is_err());
+        let (template, _) = contract
+            .operation(&Method::GET, "/api/v1/posts/popular")
+            .unwrap();
+        assert_eq!(template, "/api/v1/posts/popular");
+        let (template, _) = contract
+            .operation(&Method::GET, "/api/v1/posts/42/meta")
+            .unwrap();
+        assert_eq!(template, "/api/v1/posts/{id_or_slug}/meta");
+    }
+}
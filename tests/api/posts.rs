@@ -0,0 +1,1000 @@
+use super::{TestApp, TestResponse, TestUser};
+use axum::http::{header, StatusCode};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_create_and_read_post() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let post_id = app.create_post(&author, "Hello integration tests").await;
+
+    let response = app
+        .get(&format!("/api/v1/posts/view/{}", post_id), None)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["title"], "Hello integration tests");
+    assert!(response.body["content_html"]
+        .as_str()
+        .unwrap()
+        .contains("<strong>content</strong>"));
+
+    let tags: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM global.post_tags WHERE post_id = $1")
+        .bind(post_id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(tags, 1);
+
+    let response = app.get("/api/v1/posts/mine", Some(&author)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response
+        .body
+        .to_string()
+        .contains("Hello integration tests"));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_post_slugs_are_unique() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let post = json!({
+        "title": "Same slug",
+        "slug": "same-slug",
+        "content": "Content",
+        "tags": [],
+        "is_draft": false,
+    });
+
+    let response = app.post("/api/v1/posts", Some(&author), post.clone()).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let response = app.post("/api/v1/posts", Some(&author), post).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_only_the_author_can_edit_a_post() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let other = app.register("user").await;
+    let post_id = app.create_post(&author, "Original title").await;
+    let uri = format!("/api/v1/posts/edit/{}", post_id);
+
+    let response = app
+        .put(&uri, Some(&other), json!({ "title": "Hijacked" }))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app
+        .put(&uri, Some(&author), json!({ "title": "Edited title" }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["title"], "Edited title");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_edit_lock_contention_and_release() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let admin = app.register("admin").await;
+    let post_id = app.create_post(&author, "Locked for editing").await;
+    let uri = format!("/api/v1/posts/{}/edit-lock", post_id);
+
+    let response = app.post(&uri, Some(&author), json!({})).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["user_id"], author.id.to_string());
+
+    // Refreshing our own lock keeps it
+    let response = app.post(&uri, Some(&author), json!({})).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // Held by the author: others are turned away until it is released or taken over
+    let response = app.post(&uri, Some(&admin), json!({})).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    let response = app
+        .post(&format!("{}?force=true", uri), Some(&admin), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["user_id"], admin.id.to_string());
+
+    // The author lost the lock, so releasing leaves the admin's lock in place
+    let response = app.delete(&uri, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    let response = app.post(&uri, Some(&author), json!({})).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    let response = app.delete(&uri, Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    // Releasing a lock nobody holds is a no-op
+    let response = app.delete(&uri, Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app.post(&uri, Some(&author), json!({})).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_missing_post_is_not_found() {
+    let app = TestApp::spawn().await;
+
+    let response = app.get("/api/v1/posts/view/999999", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_paywalled_posts_are_cached_per_tier() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let member = app.register("user").await;
+    let free_reader = app.register("user").await;
+
+    let response = app
+        .post(
+            "/api/v1/admin/membership/tiers",
+            Some(&admin),
+            json!({ "name": "premium", "level": 2 }),
+        )
+        .await;
+    assert!(response.status.is_success(), "{}", response.body);
+    let response = app
+        .put(
+            &format!("/api/v1/admin/users/{}/membership", member.id),
+            Some(&admin),
+            json!({ "tier": "premium" }),
+        )
+        .await;
+    assert!(response.status.is_success(), "{}", response.body);
+
+    let content = "Premium insight. ".repeat(100);
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            json!({
+                "title": "Members only",
+                "slug": format!("members-{}", Uuid::new_v4().simple()),
+                "content": content,
+                "tags": ["testing"],
+                "is_draft": false,
+                "required_tier": "premium",
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let uri = format!("/api/v1/posts/view/{}", response.body["id"]);
+
+    // A member reads, and caches, the full post first
+    let response = app.get(&uri, Some(&member)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["content"], content.as_str());
+    assert!(response.body["requires_tier"].is_null());
+
+    // Readers below the tier still only get the preview
+    for reader in [None, Some(&free_reader)] {
+        let response = app.get(&uri, reader).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let preview = response.body["content"].as_str().unwrap();
+        assert!(preview.chars().count() < content.chars().count());
+        assert!(preview.ends_with('…'));
+        assert_eq!(response.body["requires_tier"], "premium");
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_public_listings_are_cacheable() {
+    let app = TestApp::spawn().await;
+    let reader = app.register("user").await;
+
+    let response = app.get("/api/v1/posts/popular", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers[header::CACHE_CONTROL],
+        "public, max-age=60, s-maxage=300"
+    );
+    assert_eq!(response.headers[header::VARY], "Authorization");
+
+    let response = app.get("/api/v1/posts/archive", Some(&reader)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CACHE_CONTROL], "private, no-cache");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_listings_carry_surrogate_keys() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let post_id = app.create_post(&author, "Tagged for the CDN").await;
+
+    let response = app.get("/api/v1/posts", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let keys: Vec<&str> = response.headers["surrogate-key"]
+        .to_str()
+        .unwrap()
+        .split(' ')
+        .collect();
+    for key in [
+        "posts".to_string(),
+        format!("post:{}", post_id),
+        format!("user:{}", author.id),
+        "tag:testing".to_string(),
+    ] {
+        assert!(
+            keys.iter()
+                .any(|scoped| scoped.ends_with(&format!(":{}", key))),
+            "{} not in {:?}",
+            key,
+            keys
+        );
+    }
+    assert_eq!(
+        response.headers["cache-tag"].to_str().unwrap(),
+        keys.join(",")
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_daily_post_quota() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+
+    for i in 0..3 {
+        app.create_post(&author, &format!("Post number {}", i))
+            .await;
+    }
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            json!({
+                "title": "One post too many",
+                "slug": "one-post-too-many",
+                "content": "Over the quota.",
+                "tags": [],
+                "is_draft": false,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.body["code"], "QUOTA_EXCEEDED");
+    assert_eq!(response.body["quota"]["kind"], "posts");
+
+    let response = app.get("/api/v1/users/me/quota", Some(&author)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["posts"]["used"], 3);
+    assert_eq!(response.body["posts"]["remaining"], 0);
+    assert_eq!(response.body["comments"]["used"], 0);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_duplicate_post_into_draft() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let editor = app.register("user").await;
+    let post_id = app.create_post(&author, "Template post").await;
+    let uri = format!("/api/v1/posts/{}/duplicate", post_id);
+
+    let response = app.post(&uri, Some(&editor), json!({})).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.body["title"], "Template post (copy)");
+    assert!(response.body["slug"].as_str().unwrap().ends_with("-copy"));
+    assert_eq!(response.body["is_draft"], true);
+    assert_eq!(response.body["author"]["id"], editor.id.to_string());
+    assert_eq!(response.body["tags"], json!(["testing"]));
+    let copy_id = response.body["id"].as_i64().unwrap();
+
+    // A second copy gets a numbered title and slug
+    let response = app.post(&uri, Some(&editor), json!({})).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.body["title"], "Template post (copy 2)");
+
+    // The copy is a draft of the editor, which nobody else can duplicate
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/duplicate", copy_id),
+            Some(&author),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_admin_retags_posts() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let moved = app.create_post(&author, "Moved post").await;
+    let kept = app.create_post(&author, "Kept post").await;
+    let retag = json!({ "from_tag": "testing", "to_tag": "tested", "post_ids": [moved] });
+
+    let response = app
+        .post("/api/v1/admin/tags/retag", Some(&author), retag.clone())
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app
+        .post("/api/v1/admin/tags/retag", Some(&admin), retag)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["post_ids"], json!([moved]));
+
+    let response = app
+        .get(&format!("/api/v1/posts/view/{}", moved), None)
+        .await;
+    assert_eq!(response.body["tags"], json!(["tested"]));
+    let response = app.get(&format!("/api/v1/posts/view/{}", kept), None).await;
+    assert_eq!(response.body["tags"], json!(["testing"]));
+
+    let entries: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM global.audit_log WHERE action = 'tags.retag' AND actor_id = $1",
+    )
+    .bind(admin.id)
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(entries, 1);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_tags_are_matched_case_insensitively() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    app.create_post(&author, "Lowercase tag").await;
+
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            json!({
+                "title": "Shouted tag",
+                "slug": "shouted-tag",
+                "content": "Content",
+                "tags": [" TESTING ", "Testing"],
+                "is_draft": false,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.body["tags"], json!(["testing"]));
+
+    let tags: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM global.tags WHERE lower(name) = 'testing'")
+            .fetch_one(&app.pool)
+            .await
+            .unwrap();
+    assert_eq!(tags, 1);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_slugs_and_tags_match_ignoring_case_and_accents() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let post = |title: &str, slug: &str, tag: &str| {
+        json!({
+            "title": title,
+            "slug": slug,
+            "content": "Content",
+            "tags": [tag],
+            "is_draft": false,
+        })
+    };
+
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            post("Crème brûlée", "creme-brulee", "Café"),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED);
+
+    let response = app.get("/api/v1/posts/view/Creme-Brulee", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["slug"], "creme-brulee");
+
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            post("Another brûlée", "Crème-Brûlée", "cafe"),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            post("Café au lait", "cafe-au-lait", "CAFE"),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.body["tags"], json!(["Café"]));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_post_metadata_filtering() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            json!({
+                "title": "Episode twelve",
+                "slug": "episode-twelve",
+                "content": "Show notes",
+                "tags": [],
+                "is_draft": false,
+                "metadata": { "podcast": { "episode": 12, "host": "Ada" } },
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.body["metadata"]["podcast"]["episode"], 12);
+    let post_id = response.body["id"].as_i64().unwrap();
+    app.create_post(&author, "No metadata").await;
+
+    let response = app.get("/api/v1/posts?meta.podcast.episode=12", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let posts = response.body.as_array().unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0]["slug"], "episode-twelve");
+
+    let response = app.get("/api/v1/posts?meta.podcast.host=Grace", None).await;
+    assert_eq!(response.body, json!([]));
+
+    let response = app.get("/api/v1/posts?meta.bad!key=1", None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = app
+        .put(
+            &format!("/api/v1/posts/edit/{}", post_id),
+            Some(&author),
+            json!({ "metadata": ["not", "an", "object"] }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_like_toggling_is_flagged() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Liked post").await;
+    let uri = format!("/api/v1/posts/{}/like", post_id);
+
+    let response = app.post(&uri, Some(&reader), json!({})).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["liked"], true);
+    assert_eq!(response.body["likes"], 1);
+
+    // Liking again changes nothing
+    let response = app.post(&uri, Some(&reader), json!({})).await;
+    assert_eq!(response.body["likes"], 1);
+
+    let response = app
+        .get(&format!("/api/v1/posts/view/{}", post_id), Some(&reader))
+        .await;
+    assert_eq!(response.body["liked_by_me"], true);
+
+    let response = app.delete(&uri, Some(&reader)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["liked"], false);
+    assert_eq!(response.body["likes"], 0);
+
+    // The fifth toggle within ten minutes is flagged, and the like does not count
+    app.post(&uri, Some(&reader), json!({})).await;
+    app.delete(&uri, Some(&reader)).await;
+    let response = app.post(&uri, Some(&reader), json!({})).await;
+    assert_eq!(response.body["liked"], true);
+    assert_eq!(response.body["likes"], 0);
+
+    let response = app.get("/api/v1/moderation/likes", Some(&reader)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app.get("/api/v1/moderation/likes", Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body[0]["post_id"], post_id);
+    assert_eq!(response.body[0]["user_id"], reader.id.to_string());
+    assert_eq!(response.body[0]["reason"], "toggling");
+}
+
+// Create a published post with the given content
+async fn create_post_with_content(
+    app: &TestApp,
+    author: &TestUser,
+    title: &str,
+    content: &str,
+) -> TestResponse {
+    app.post(
+        "/api/v1/posts",
+        Some(author),
+        json!({
+            "title": title,
+            "slug": format!("post-{}", Uuid::new_v4().simple()),
+            "content": content,
+            "tags": ["testing"],
+            "is_draft": false,
+        }),
+    )
+    .await
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_duplicate_posts() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let copier = app.register("user").await;
+    let content = "Rust makes it possible to write fast and reliable software. Its ownership \
+        model guarantees memory safety without a garbage collector, and its type system catches \
+        many bugs at compile time before they ever reach production.";
+
+    let response = create_post_with_content(&app, &author, "Why Rust", content).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert!(response.body.get("duplicate_of").is_none());
+    let original_id = response.body["id"].as_i64().unwrap();
+
+    // The author is warned about reposting their own post
+    let response =
+        create_post_with_content(&app, &author, "Why Rust again", &content.to_uppercase()).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.body["duplicate_of"]["post_id"], original_id);
+    assert_eq!(
+        response.body["duplicate_of"]["message"],
+        "Looks like a duplicate of \"Why Rust\""
+    );
+
+    // A copy by someone else is flagged for moderators instead
+    let copied = format!("{} Follow me for more posts like this.", content);
+    let response = create_post_with_content(&app, &copier, "My thoughts on Rust", &copied).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert!(response.body.get("duplicate_of").is_none());
+    let copy_id = response.body["id"].as_i64().unwrap();
+
+    let response = app.get("/api/v1/moderation/posts", Some(&copier)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    let response = app.get("/api/v1/moderation/posts", Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body.as_array().unwrap().len(), 1);
+    assert_eq!(response.body[0]["post_id"], copy_id);
+    assert_eq!(response.body[0]["user_id"], copier.id.to_string());
+    assert!(response.body[0]["duplicate_of"].as_i64().is_some());
+}
+
+// List the posts flagged for moderators
+async fn flagged_posts(app: &TestApp, admin: &TestUser) -> Vec<Value> {
+    let response = app.get("/api/v1/moderation/posts", Some(admin)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    response.body.as_array().unwrap().clone()
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_edited_and_duplicated_copies_are_flagged() {
+    let app = TestApp::spawn().await;
+    let admin = app.register("admin").await;
+    let author = app.register("user").await;
+    let copier = app.register("user").await;
+    let content = "Rust makes it possible to write fast and reliable software. Its ownership \
+        model guarantees memory safety without a garbage collector, and its type system catches \
+        many bugs at compile time before they ever reach production.";
+
+    let response = create_post_with_content(&app, &author, "Why Rust", content).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let original_id = response.body["id"].as_i64().unwrap();
+
+    // Too short to compare when created, then edited into a copy
+    let response = create_post_with_content(&app, &copier, "Placeholder", "Coming soon.").await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let edited_id = response.body["id"].as_i64().unwrap();
+    assert!(flagged_posts(&app, &admin).await.is_empty());
+
+    let uri = format!("/api/v1/posts/edit/{}", edited_id);
+    for _ in 0..2 {
+        let response = app
+            .put(&uri, Some(&copier), json!({ "content": content }))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+    let flagged = flagged_posts(&app, &admin).await;
+    assert_eq!(flagged.len(), 1, "{:?}", flagged);
+    assert_eq!(flagged[0]["post_id"], edited_id);
+    assert_eq!(flagged[0]["duplicate_of"], original_id);
+
+    // Editing the original does not match itself or the later copy
+    let response = app
+        .put(
+            &format!("/api/v1/posts/edit/{}", original_id),
+            Some(&author),
+            json!({ "content": content }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(flagged_posts(&app, &admin).await.len(), 1);
+
+    // A duplicated draft of someone else's post is flagged too
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/duplicate", original_id),
+            Some(&copier),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let clone_id = response.body["id"].as_i64().unwrap();
+    let flagged = flagged_posts(&app, &admin).await;
+    assert_eq!(flagged.len(), 2, "{:?}", flagged);
+    assert!(flagged.iter().any(|flag| flag["post_id"] == clone_id));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_leaderboards() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Ranked post").await;
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/comments", post_id),
+            Some(&reader),
+            json!({ "content": "First!", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    // A week long past is neither cached nor rolled up, so it is computed from the comments
+    sqlx::query("UPDATE global.comments SET created_at = NOW() - INTERVAL '35 days'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let date = (chrono::Utc::now() - chrono::Duration::days(35)).date_naive();
+
+    let response = app
+        .get(
+            &format!("/api/v1/leaderboards/authors?period=week&date={}", date),
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.body["entries"][0]["user_id"],
+        author.id.to_string()
+    );
+    assert_eq!(response.body["entries"][0]["rank"], 1);
+    assert_eq!(response.body["entries"][0]["score"], 5);
+
+    let response = app
+        .get(
+            &format!("/api/v1/leaderboards/commenters?period=month&date={}", date),
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.body["entries"][0]["user_id"],
+        reader.id.to_string()
+    );
+    assert_eq!(response.body["entries"][0]["score"], 1);
+
+    let response = app
+        .get(
+            "/api/v1/leaderboards/authors?period=all&date=2026-01-01",
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_posts_show_the_viewers_likes_and_bookmarks() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Bookmarked post").await;
+    let uri = format!("/api/v1/posts/view/{}", post_id);
+
+    // Cached for anonymous readers first, who never get the viewer fields
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert!(response.body["liked_by_me"].is_null());
+    assert!(response.body["bookmarked_by_me"].is_null());
+
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/like", post_id),
+            Some(&reader),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    sqlx::query(
+        r#"
+        INSERT INTO global.user_interactions (user_id, interaction_type, post_id)
+        VALUES ($1, 'bookmark', $2)
+        "#,
+    )
+    .bind(reader.id)
+    .bind(post_id)
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let response = app.get(&uri, Some(&reader)).await;
+    assert_eq!(response.body["liked_by_me"], true);
+    assert_eq!(response.body["bookmarked_by_me"], true);
+
+    let response = app.get(&uri, Some(&author)).await;
+    assert_eq!(response.body["liked_by_me"], false);
+    assert_eq!(response.body["bookmarked_by_me"], false);
+
+    let response = app.get(&uri, None).await;
+    assert!(response.body["liked_by_me"].is_null());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_my_posts_lists_drafts_with_stats() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let other = app.register("user").await;
+    let published = app.create_post(&author, "Published").await;
+    app.create_post(&other, "Not mine").await;
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            json!({
+                "title": "Work in progress",
+                "slug": format!("draft-{}", Uuid::new_v4().simple()),
+                "content": "Draft content",
+                "tags": ["testing"],
+                "is_draft": true,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let draft = response.body["id"].as_i64().unwrap();
+
+    let response = app.get("/api/v1/posts/mine", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let ids = |response: &TestResponse| -> Vec<i64> {
+        response
+            .body
+            .as_array()
+            .expect("list of posts")
+            .iter()
+            .map(|post| post["id"].as_i64().unwrap())
+            .collect()
+    };
+
+    let response = app.get("/api/v1/posts/mine", Some(&author)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(ids(&response), vec![draft, published]);
+    assert_eq!(response.body[1]["views"], 0);
+    assert_eq!(response.body[1]["comments"], 0);
+
+    let response = app
+        .get("/api/v1/posts/mine?status=draft", Some(&author))
+        .await;
+    assert_eq!(ids(&response), vec![draft]);
+    assert_eq!(response.body[0]["is_draft"], true);
+
+    let response = app
+        .get("/api/v1/posts/mine?status=published", Some(&author))
+        .await;
+    assert_eq!(ids(&response), vec![published]);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_authors_show_their_avatar_or_gravatar() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let commenter = app.register("user").await;
+    let post_id = app.create_post(&author, "Faces of the authors").await;
+
+    let uploaded = format!(
+        "/api/v1/users/{}/avatar/{}/256.webp",
+        commenter.id,
+        Uuid::new_v4()
+    );
+    sqlx::query("UPDATE global.users SET avatar_url = $1 WHERE id = $2")
+        .bind(&uploaded)
+        .bind(commenter.id)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    // Without an upload the author gets the Gravatar of their email
+    let gravatar = format!(
+        "https://www.gravatar.com/avatar/{:x}?d=identicon",
+        Sha256::digest(author.email.trim().to_lowercase().as_bytes())
+    );
+    let response = app
+        .get(&format!("/api/v1/posts/view/{}", post_id), None)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["author"]["avatar_url"], gravatar);
+
+    let uri = format!("/api/v1/posts/{}/comments", post_id);
+    let response = app
+        .post(
+            &uri,
+            Some(&commenter),
+            json!({ "content": "Nice post", "markdown_enabled": false }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.body["comments"][0]["author"]["avatar_url"],
+        uploaded
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_popular_posts_show_each_authors_name_and_tags() {
+    let app = TestApp::spawn().await;
+    let first_author = app.register("user").await;
+    let second_author = app.register("user").await;
+
+    let mut expected = Vec::new();
+    for (author, tags) in [
+        (&first_author, vec!["databases", "rust"]),
+        (&second_author, vec![]),
+        (&first_author, vec!["rust"]),
+    ] {
+        let response = app
+            .post(
+                "/api/v1/posts",
+                Some(author),
+                json!({
+                    "title": "Popular post",
+                    "slug": format!("popular-{}", Uuid::new_v4().simple()),
+                    "content": "Read by many.",
+                    "tags": tags,
+                    "is_draft": false,
+                }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+        expected.push((response.body["id"].as_i64().unwrap(), author, tags));
+    }
+
+    let response = app.get("/api/v1/posts/popular", None).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let listed = response.body["posts"].as_array().unwrap();
+    assert_eq!(listed.len(), expected.len());
+    for (post_id, author, tags) in expected {
+        let post = listed
+            .iter()
+            .find(|post| post["id"] == post_id)
+            .unwrap_or_else(|| panic!("post {} not listed", post_id));
+        assert_eq!(post["author"]["id"], author.id.to_string());
+        assert_eq!(post["author"]["name"], author.username.as_str());
+        let mut listed_tags: Vec<&str> = post["tags"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tag| tag.as_str().unwrap())
+            .collect();
+        listed_tags.sort();
+        assert_eq!(listed_tags, tags);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_posts_read_the_same_alone_and_in_listings() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let tagged = app.create_post(&author, "Tagged post").await;
+    let response = app
+        .post(
+            "/api/v1/posts",
+            Some(&author),
+            json!({
+                "title": "Untagged post",
+                "slug": "untagged-post",
+                "content": "No tags here.",
+                "tags": [],
+                "is_draft": false,
+                "metadata": { "series": "misc" },
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    let untagged = response.body["id"].as_i64().unwrap();
+
+    let response = app.get("/api/v1/posts", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let listed = response.body.as_array().unwrap().clone();
+    assert_eq!(listed.len(), 2);
+
+    for post_id in [tagged, untagged] {
+        let by_id = app
+            .get(&format!("/api/v1/posts/view/{}", post_id), None)
+            .await;
+        assert_eq!(by_id.status, StatusCode::OK, "{}", by_id.body);
+        let slug = by_id.body["slug"].as_str().unwrap();
+        let by_slug = app.get(&format!("/api/v1/posts/view/{}", slug), None).await;
+        assert_eq!(by_slug.status, StatusCode::OK, "{}", by_slug.body);
+        let in_listing = listed
+            .iter()
+            .find(|post| post["id"] == post_id)
+            .unwrap_or_else(|| panic!("post {} not listed", post_id));
+
+        for field in [
+            "id", "title", "slug", "author", "tags", "metadata", "is_draft",
+        ] {
+            assert_eq!(by_id.body[field], by_slug.body[field], "{}", field);
+            assert_eq!(by_id.body[field], in_listing[field], "{}", field);
+        }
+        assert_eq!(by_id.body["author"]["name"], author.username.as_str());
+    }
+
+    let response = app
+        .get(&format!("/api/v1/posts/view/{}", tagged), None)
+        .await;
+    assert_eq!(response.body["tags"], json!(["testing"]));
+    let response = app.get("/api/v1/posts/view/untagged-post", None).await;
+    assert_eq!(response.body["tags"], json!([]));
+    assert_eq!(response.body["metadata"]["series"], "misc");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_only_the_author_can_delete_a_post() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let other = app.register("user").await;
+    let post_id = app.create_post(&author, "Short-lived post").await;
+    let uri = format!("/api/v1/posts/delete/{}", post_id);
+
+    let response = app.delete(&uri, Some(&other)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.body["code"], "FORBIDDEN");
+
+    let response = app.delete(&uri, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = app
+        .get(&format!("/api/v1/posts/view/{}", post_id), None)
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.delete(&uri, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.get("/api/v1/posts", None).await;
+    assert_eq!(response.body, json!([]));
+}
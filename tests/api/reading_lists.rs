@@ -0,0 +1,124 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_reading_list_keeps_posts_in_order() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let first = app.create_post(&author, "First").await;
+    let second = app.create_post(&author, "Second").await;
+
+    let response = app
+        .post(
+            "/api/v1/users/me/lists",
+            Some(&reader),
+            json!({ "name": "  Weekend reads  " }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.body["name"], "Weekend reads");
+    assert_eq!(response.body["is_public"], false);
+    let list_id = response.body["id"].as_i64().unwrap();
+    let posts_path = format!("/api/v1/users/me/lists/{}/posts", list_id);
+
+    app.post(&posts_path, Some(&reader), json!({ "post_id": first }))
+        .await;
+    let response = app
+        .post(
+            &posts_path,
+            Some(&reader),
+            json!({ "post_id": second, "position": 0 }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["post_count"], 2);
+    assert_eq!(response.body["posts"][0]["post_id"], second);
+    assert_eq!(response.body["posts"][1]["post_id"], first);
+
+    let response = app
+        .put(&posts_path, Some(&reader), json!({ "post_ids": [first] }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["posts"][0]["post_id"], first);
+    assert_eq!(response.body["posts"][1]["post_id"], second);
+    assert_eq!(response.body["posts"][1]["position"], 1);
+
+    let response = app
+        .post(&posts_path, Some(&reader), json!({ "post_id": 999_999 }))
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    // Others cannot change the list
+    let response = app
+        .post(&posts_path, Some(&author), json!({ "post_id": first }))
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app
+        .delete(&format!("{}/{}", posts_path, first), Some(&reader))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["posts"][0]["post_id"], second);
+    assert_eq!(response.body["posts"][0]["position"], 0);
+
+    let response = app.get("/api/v1/users/me/lists", Some(&reader)).await;
+    assert_eq!(response.body.as_array().map(Vec::len), Some(1));
+    let response = app.get("/api/v1/users/me/lists", Some(&author)).await;
+    assert_eq!(response.body.as_array().map(Vec::len), Some(0));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_private_reading_lists_are_hidden() {
+    let app = TestApp::spawn().await;
+    let owner = app.register("user").await;
+    let other = app.register("user").await;
+
+    let response = app
+        .post(
+            "/api/v1/users/me/lists",
+            Some(&owner),
+            json!({ "name": "Private" }),
+        )
+        .await;
+    let list_id = response.body["id"].as_i64().unwrap();
+    let public_path = format!("/api/v1/lists/{}", list_id);
+
+    assert_eq!(
+        app.get(&public_path, None).await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        app.get(&public_path, Some(&other)).await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        app.get(&public_path, Some(&owner)).await.status,
+        StatusCode::OK
+    );
+
+    let list_path = format!("/api/v1/users/me/lists/{}", list_id);
+    let response = app
+        .put(&list_path, Some(&owner), json!({ "is_public": true }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = app.get(&public_path, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["owner"], owner.username);
+
+    assert_eq!(
+        app.delete(&list_path, Some(&other)).await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        app.delete(&list_path, Some(&owner)).await.status,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        app.get(&public_path, None).await.status,
+        StatusCode::NOT_FOUND
+    );
+}
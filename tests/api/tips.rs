@@ -0,0 +1,65 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_tipping_needs_a_provider() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Worth a coffee").await;
+
+    // The tests configure no payment provider
+    let response = app
+        .post(
+            &format!("/api/v1/posts/{}/tips", post_id),
+            Some(&reader),
+            json!({ "amount": 500 }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let response = app.post("/api/v1/tips/webhook", None, json!({})).await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_earnings_count_paid_tips() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Worth a coffee").await;
+
+    for (amount, status) in [(500, "paid"), (300, "paid"), (1000, "pending")] {
+        sqlx::query(
+            "INSERT INTO global.tips \
+             (post_id, author_id, tipper_id, amount, currency, status, provider, paid_at) \
+             VALUES ($1, $2, $3, $4, 'usd', $5, 'stripe', \
+             CASE WHEN $5 = 'paid' THEN NOW() END)",
+        )
+        .bind(post_id)
+        .bind(author.id)
+        .bind(reader.id)
+        .bind(amount as i64)
+        .bind(status)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    }
+
+    let response = app
+        .get("/api/v1/analytics/authors/me/earnings", Some(&author))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["totals"][0]["currency"], "usd");
+    assert_eq!(response.body["totals"][0]["amount"], 800);
+    assert_eq!(response.body["totals"][0]["tips"], 2);
+    assert_eq!(response.body["posts"][0]["post_id"], post_id);
+    assert_eq!(response.body["recent"].as_array().map(Vec::len), Some(2));
+
+    let response = app
+        .get("/api/v1/analytics/authors/me/earnings", Some(&reader))
+        .await;
+    assert_eq!(response.body["totals"].as_array().map(Vec::len), Some(0));
+}
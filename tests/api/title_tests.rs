@@ -0,0 +1,71 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn test_title_test_assigns_and_counts_titles() {
+    let app = TestApp::spawn().await;
+    let author = app.register("user").await;
+    let reader = app.register("user").await;
+    let post_id = app.create_post(&author, "Why Rust?").await;
+    let test_path = format!("/api/v1/posts/{}/title-test", post_id);
+
+    let titles = json!({ "titles": ["Why Rust?", "Rust in 10 minutes"] });
+    let response = app.put(&test_path, Some(&reader), titles.clone()).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = app
+        .put(&test_path, Some(&author), json!({ "titles": ["Only one"] }))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = app.put(&test_path, Some(&author), titles).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.body["status"], "running");
+
+    // The same visitor always gets the same title
+    let impressions = json!({ "post_ids": [post_id], "visitor_id": "visitor-1" });
+    let first = app
+        .post(
+            "/api/v1/analytics/title-impressions",
+            None,
+            impressions.clone(),
+        )
+        .await;
+    assert_eq!(first.status, StatusCode::OK, "{}", first.body);
+    let second = app
+        .post("/api/v1/analytics/title-impressions", None, impressions)
+        .await;
+    assert_eq!(first.body, second.body);
+    let variant = first.body[0]["variant"].as_u64().unwrap() as usize;
+
+    let response = app
+        .post(
+            "/api/v1/analytics/title-clicks",
+            None,
+            json!({ "post_id": post_id, "visitor_id": "visitor-1" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let report_path = format!("/api/v1/analytics/posts/{}/title-test", post_id);
+    let response = app.get(&report_path, Some(&reader)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = app.get(&report_path, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["variants"][variant]["impressions"], 2);
+    assert_eq!(response.body["variants"][variant]["clicks"], 1);
+    assert_eq!(response.body["leader"], variant);
+    assert_eq!(response.body["significant"], false);
+
+    let response = app.delete(&test_path, Some(&author)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["status"], "stopped");
+    let response = app
+        .post(
+            "/api/v1/analytics/title-impressions",
+            None,
+            json!({ "post_ids": [post_id], "visitor_id": "visitor-1" }),
+        )
+        .await;
+    assert_eq!(response.body, json!([]));
+}